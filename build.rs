@@ -0,0 +1,7 @@
+// Rebuild when a migration, template or static file is added, since
+// `sqlx::migrate!` and `RustEmbed` embed them
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+    println!("cargo:rerun-if-changed=templates");
+    println!("cargo:rerun-if-changed=static");
+}
@@ -0,0 +1,20 @@
+-- History of quantity changes, used for consumption statistics
+
+CREATE TABLE item_events (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    item_id INTEGER,
+    item_name VARCHAR(255) NOT NULL,
+    kind VARCHAR(32) NOT NULL,
+    quantity_delta INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT fk_user_item_events
+        FOREIGN KEY(user_id)
+            REFERENCES users(id),
+    CONSTRAINT fk_item_item_events
+        FOREIGN KEY(item_id)
+            REFERENCES items(id)
+            ON DELETE SET NULL
+);
+
+CREATE INDEX idx_item_events_user_id_created_at ON item_events (user_id, created_at);
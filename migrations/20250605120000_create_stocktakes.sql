@@ -0,0 +1,34 @@
+-- Stocktake (inventory count) sessions
+
+CREATE TABLE stocktakes (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'open',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ,
+    CONSTRAINT fk_user_stocktakes
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+);
+
+-- Only one stocktake can be in progress per user
+CREATE UNIQUE INDEX idx_stocktakes_user_id_open ON stocktakes (user_id) WHERE status = 'open';
+
+CREATE TABLE stocktake_entries (
+    id SERIAL PRIMARY KEY,
+    stocktake_id INTEGER NOT NULL,
+    item_id INTEGER,
+    item_name VARCHAR(255) NOT NULL,
+    expected_quantity INTEGER NOT NULL,
+    counted_quantity INTEGER,
+    CONSTRAINT fk_stocktake_entries
+        FOREIGN KEY(stocktake_id)
+            REFERENCES stocktakes(id)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_item_stocktake_entries
+        FOREIGN KEY(item_id)
+            REFERENCES items(id)
+            ON DELETE SET NULL
+);
+
+CREATE UNIQUE INDEX idx_stocktake_entries_stocktake_id_item_id ON stocktake_entries (stocktake_id, item_id);
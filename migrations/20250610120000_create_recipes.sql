@@ -0,0 +1,35 @@
+-- Recipes and the inventory items they consume
+
+CREATE TABLE recipes (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    instructions TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT fk_user_recipes
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+);
+
+CREATE TABLE recipe_ingredients (
+    id SERIAL PRIMARY KEY,
+    recipe_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    CONSTRAINT fk_recipe_ingredients
+        FOREIGN KEY(recipe_id)
+            REFERENCES recipes(id)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_item_recipe_ingredients
+        FOREIGN KEY(item_id)
+            REFERENCES items(id)
+            ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_recipe_ingredients_recipe_id_item_id ON recipe_ingredients (recipe_id, item_id);
+
+CREATE TRIGGER set_timestamp
+BEFORE UPDATE ON recipes
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_set_timestamp();
@@ -0,0 +1,19 @@
+-- Weekly meal plan; uncooked future meals reserve their recipe ingredients
+
+CREATE TABLE meal_plans (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    recipe_id INTEGER NOT NULL,
+    planned_for DATE NOT NULL,
+    cooked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT fk_user_meal_plans
+        FOREIGN KEY(user_id)
+            REFERENCES users(id),
+    CONSTRAINT fk_recipe_meal_plans
+        FOREIGN KEY(recipe_id)
+            REFERENCES recipes(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX idx_meal_plans_user_id_planned_for ON meal_plans (user_id, planned_for);
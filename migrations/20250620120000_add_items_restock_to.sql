@@ -0,0 +1,3 @@
+-- Target quantity to restock up to; NULL means "up to the restock threshold"
+
+ALTER TABLE items ADD COLUMN restock_to INTEGER;
@@ -0,0 +1,20 @@
+-- Batches (lots) an item's quantity is made of, each with its own dates.
+-- The sum of batch quantities always equals items.quantity.
+CREATE TABLE item_batches (
+    id SERIAL PRIMARY KEY,
+    item_id INTEGER NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    purchased_on DATE NOT NULL DEFAULT CURRENT_DATE,
+    expires_on DATE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_item_batches_item_id ON item_batches (item_id, purchased_on);
+CREATE INDEX idx_item_batches_expires_on ON item_batches (expires_on)
+    WHERE expires_on IS NOT NULL;
+
+-- Existing stock becomes one undated batch per item
+INSERT INTO item_batches (item_id, quantity, purchased_on)
+SELECT id, quantity, created_at::DATE
+FROM items
+WHERE quantity > 0;
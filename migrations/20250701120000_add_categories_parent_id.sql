@@ -0,0 +1,6 @@
+-- Optional parent for two-level category nesting.
+-- Subcategories of a deleted category become top-level.
+ALTER TABLE categories
+    ADD COLUMN parent_id INTEGER REFERENCES categories (id) ON DELETE SET NULL;
+
+CREATE INDEX idx_categories_parent_id ON categories (parent_id);
@@ -0,0 +1,2 @@
+-- Optional emoji shown next to the category name
+ALTER TABLE categories ADD COLUMN icon VARCHAR(32);
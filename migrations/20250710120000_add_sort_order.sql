@@ -0,0 +1,20 @@
+-- Manual ordering of categories and items (drag and drop on the index page).
+-- Existing rows keep their alphabetical order.
+ALTER TABLE categories ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE items ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0;
+
+UPDATE categories c
+SET sort_order = ranked.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY name) - 1 AS position
+    FROM categories
+) ranked
+WHERE c.id = ranked.id;
+
+UPDATE items i
+SET sort_order = ranked.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY name) - 1 AS position
+    FROM items
+) ranked
+WHERE i.id = ranked.id;
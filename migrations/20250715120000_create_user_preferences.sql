@@ -0,0 +1,11 @@
+-- Per-user settings, previously kept in the unsigned `group` cookie.
+-- A missing row means the defaults below.
+CREATE TABLE user_preferences (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    group_by_category BOOLEAN NOT NULL DEFAULT TRUE,
+    sort TEXT NOT NULL DEFAULT 'manual' CHECK (sort IN ('manual')),
+    language TEXT NOT NULL DEFAULT 'pl' CHECK (language IN ('pl', 'en')),
+    theme TEXT NOT NULL DEFAULT 'auto' CHECK (theme IN ('light', 'dark', 'auto')),
+    default_location VARCHAR(255),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,4 @@
+-- More ways to sort the items list, picked on the index page.
+ALTER TABLE user_preferences DROP CONSTRAINT user_preferences_sort_check;
+ALTER TABLE user_preferences ADD CONSTRAINT user_preferences_sort_check
+    CHECK (sort IN ('manual', 'name', 'quantity', 'updated', 'stock'));
@@ -0,0 +1,3 @@
+-- Unit of the quantity ("szt.", "kg", "l") and where the item is kept.
+ALTER TABLE items ADD COLUMN unit VARCHAR(32);
+ALTER TABLE items ADD COLUMN location VARCHAR(255);
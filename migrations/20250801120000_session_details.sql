@@ -0,0 +1,6 @@
+-- Sessions get a number to list and revoke them by, so the token itself is
+-- never sent anywhere but the cookie
+ALTER TABLE sessions RENAME COLUMN id TO token;
+ALTER TABLE sessions ADD COLUMN id SERIAL UNIQUE;
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
+ALTER TABLE sessions ADD COLUMN ip_address TEXT;
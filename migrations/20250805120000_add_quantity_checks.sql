@@ -0,0 +1,10 @@
+-- Quantities can't go negative, whatever path the update takes. Rows that
+-- already did are clamped first, so the constraints can be added.
+UPDATE items SET quantity = 0 WHERE quantity < 0;
+UPDATE items SET restock_threshold = 0 WHERE restock_threshold < 0;
+UPDATE items SET restock_to = NULL WHERE restock_to < 0;
+
+ALTER TABLE items
+    ADD CONSTRAINT items_quantity_non_negative CHECK (quantity >= 0),
+    ADD CONSTRAINT items_restock_threshold_non_negative CHECK (restock_threshold >= 0),
+    ADD CONSTRAINT items_restock_to_non_negative CHECK (restock_to >= 0);
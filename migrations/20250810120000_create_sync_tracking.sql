@@ -0,0 +1,58 @@
+-- Change tracking for GET /api/sync. `changed_at` is set on every insert and
+-- update, including restores that carry their own `updated_at`, and deleted
+-- items and categories leave a tombstone behind.
+ALTER TABLE items ADD COLUMN changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE categories ADD COLUMN changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+CREATE INDEX idx_items_user_id_changed_at ON items (user_id, changed_at);
+CREATE INDEX idx_categories_user_id_changed_at ON categories (user_id, changed_at);
+
+CREATE OR REPLACE FUNCTION trigger_set_changed_at()
+RETURNS TRIGGER AS $$
+BEGIN
+  NEW.changed_at = NOW();
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER set_changed_at
+BEFORE INSERT OR UPDATE ON items
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_set_changed_at();
+
+CREATE TRIGGER set_changed_at
+BEFORE INSERT OR UPDATE ON categories
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_set_changed_at();
+
+CREATE TABLE deletions (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    record_type TEXT NOT NULL CHECK (record_type IN ('item', 'category')),
+    record_id INTEGER NOT NULL,
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_deletions_user_id_deleted_at ON deletions (user_id, deleted_at);
+
+-- The record type is passed as the trigger argument
+CREATE OR REPLACE FUNCTION trigger_record_deletion()
+RETURNS TRIGGER AS $$
+BEGIN
+  IF OLD.user_id IS NOT NULL THEN
+    INSERT INTO deletions (user_id, record_type, record_id)
+    VALUES (OLD.user_id, TG_ARGV[0], OLD.id);
+  END IF;
+  RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER record_deletion
+AFTER DELETE ON items
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_record_deletion('item');
+
+CREATE TRIGGER record_deletion
+AFTER DELETE ON categories
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_record_deletion('category');
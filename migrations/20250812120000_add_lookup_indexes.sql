@@ -0,0 +1,7 @@
+-- Indexes for the lookups every page makes, which scanned all items once
+-- an account had a few thousand: items by category (grouped list, category
+-- counts), items by quantity (restock warnings, the "out" filter) and
+-- categories by name (Grocy import, the category list).
+CREATE INDEX idx_items_user_id_category_id ON items (user_id, category_id);
+CREATE INDEX idx_items_user_id_quantity ON items (user_id, quantity);
+CREATE INDEX idx_categories_user_id_name ON categories (user_id, name);
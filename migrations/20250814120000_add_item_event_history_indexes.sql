@@ -0,0 +1,5 @@
+-- Keyset pages of the history walk (created_at, id) backwards, per user or
+-- per item. The first index supersedes the old (user_id, created_at) one.
+CREATE INDEX idx_item_events_user_id_created_at_id ON item_events (user_id, created_at, id);
+DROP INDEX idx_item_events_user_id_created_at;
+CREATE INDEX idx_item_events_item_id_created_at_id ON item_events (item_id, created_at, id);
@@ -0,0 +1,7 @@
+-- When each background job last ran, shared by all instances on this
+-- database. An instance locks a job's row while running it, so a job that
+-- changes shared data runs on one instance at a time and once per period.
+CREATE TABLE scheduled_jobs (
+    name VARCHAR(100) PRIMARY KEY,
+    last_run_at TIMESTAMPTZ
+);
@@ -0,0 +1,9 @@
+-- Maintenance mode as switched by `household-inventory maintenance on|off`.
+-- A single row, so every instance on this database sees the same switch.
+CREATE TABLE maintenance_mode (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO maintenance_mode DEFAULT VALUES;
@@ -0,0 +1,17 @@
+-- Read-only links for people without an account: `/share/{token}` shows the
+-- items of the chosen categories, or the shopping list, until revoked
+CREATE TABLE share_links (
+    id SERIAL PRIMARY KEY,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- Who it was made for, e.g. "Niania"
+    label TEXT NOT NULL,
+    -- 'categories' or 'shopping_list'
+    scope TEXT NOT NULL CHECK (scope IN ('categories', 'shopping_list')),
+    -- For 'categories'; their subcategories are shown too
+    category_ids INTEGER[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_viewed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_share_links_user_id ON share_links (user_id);
@@ -0,0 +1,25 @@
+-- Where each user's restock and expiry alerts are sent, and which alerts
+-- went out already, so each is sent once while it lasts
+CREATE TABLE notification_channels (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    label VARCHAR(255) NOT NULL,
+    -- 'apprise': an Apprise API notify URL; 'url': any URL, with {title}
+    -- and {message} filled in
+    kind TEXT NOT NULL CHECK (kind IN ('apprise', 'url')),
+    url TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_sent_at TIMESTAMPTZ,
+    -- Of the last attempt; NULL once one succeeds
+    last_error TEXT
+);
+
+CREATE INDEX idx_notification_channels_user_id ON notification_channels (user_id);
+
+CREATE TABLE sent_alerts (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- e.g. 'restock:12' or 'expiry:40'
+    alert_key TEXT NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, alert_key)
+);
@@ -0,0 +1,8 @@
+-- Matrix rooms as notification channels: `url` is then the homeserver
+ALTER TABLE notification_channels DROP CONSTRAINT notification_channels_kind_check;
+ALTER TABLE notification_channels
+    ADD CONSTRAINT notification_channels_kind_check CHECK (kind IN ('apprise', 'url', 'matrix')),
+    ADD COLUMN room_id TEXT,
+    ADD COLUMN access_token TEXT,
+    ADD CONSTRAINT notification_channels_matrix_check
+        CHECK (kind <> 'matrix' OR (room_id IS NOT NULL AND access_token IS NOT NULL));
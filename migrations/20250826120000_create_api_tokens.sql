@@ -0,0 +1,13 @@
+-- Long-lived tokens for Home Assistant and similar automations, sent as
+-- `Authorization: Bearer {token}` to the `/api/ha` endpoints until revoked
+CREATE TABLE api_tokens (
+    id SERIAL PRIMARY KEY,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- What uses it, e.g. "Home Assistant"
+    label TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_api_tokens_user_id ON api_tokens (user_id);
@@ -0,0 +1,9 @@
+-- How far the MQTT publisher has got through `item_events`. A single row, so
+-- whichever instance publishes next carries on where the last one stopped.
+-- It starts at the newest event, so earlier history isn't published.
+CREATE TABLE mqtt_cursor (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    last_event_id INTEGER NOT NULL
+);
+
+INSERT INTO mqtt_cursor (last_event_id) SELECT COALESCE(MAX(id), 0) FROM item_events;
@@ -0,0 +1,5 @@
+-- Share links can also be calendar feeds: `/calendar/{token}.ics` lists the
+-- expiry dates of the owner's items
+ALTER TABLE share_links DROP CONSTRAINT share_links_scope_check;
+ALTER TABLE share_links ADD CONSTRAINT share_links_scope_check
+    CHECK (scope IN ('categories', 'shopping_list', 'calendar'));
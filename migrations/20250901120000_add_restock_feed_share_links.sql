@@ -0,0 +1,5 @@
+-- Share links can also be Atom feeds: `/feeds/{token}/restock.atom` lists the
+-- owner's items below their restock threshold
+ALTER TABLE share_links DROP CONSTRAINT share_links_scope_check;
+ALTER TABLE share_links ADD CONSTRAINT share_links_scope_check
+    CHECK (scope IN ('categories', 'shopping_list', 'calendar', 'restock_feed'));
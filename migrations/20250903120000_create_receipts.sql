@@ -0,0 +1,12 @@
+-- Photos or PDFs of the receipt for a purchase. The file itself is in the
+-- file store under `storage_key`; one receipt per purchase event
+CREATE TABLE receipts (
+    event_id INTEGER PRIMARY KEY REFERENCES item_events (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    storage_key VARCHAR(255) NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_receipts_user_id ON receipts (user_id);
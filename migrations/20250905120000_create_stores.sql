@@ -0,0 +1,17 @@
+-- Shops the user buys in, in the order they visit them. The shopping list
+-- is grouped by each item's preferred store, in that order
+CREATE TABLE stores (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_stores_user_id_name ON stores (user_id, name);
+
+-- Items of a deleted store can be bought anywhere
+ALTER TABLE items ADD COLUMN preferred_store_id INTEGER
+    CONSTRAINT fk_store_items REFERENCES stores (id) ON DELETE SET NULL;
+
+CREATE INDEX idx_items_preferred_store_id ON items (preferred_store_id);
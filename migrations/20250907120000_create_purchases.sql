@@ -0,0 +1,24 @@
+-- What was paid for each purchase and where. A purchase shares its id with
+-- its `purchased` event, so the receipt of purchase N is that of event N
+CREATE TABLE purchases (
+    id INTEGER PRIMARY KEY REFERENCES item_events (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- Kept with the name after the item is deleted, like its events
+    item_id INTEGER REFERENCES items (id) ON DELETE SET NULL,
+    item_name VARCHAR(255) NOT NULL,
+    quantity INTEGER NOT NULL,
+    -- In hundredths of the currency (grosze); NULL when not given
+    price_cents INTEGER CONSTRAINT purchases_price_non_negative CHECK (price_cents >= 0),
+    store_id INTEGER CONSTRAINT fk_store_purchases REFERENCES stores (id) ON DELETE SET NULL,
+    purchased_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_purchases_user_id_purchased_at ON purchases (user_id, purchased_at, id);
+CREATE INDEX idx_purchases_item_id_purchased_at ON purchases (item_id, purchased_at, id);
+CREATE INDEX idx_purchases_store_id ON purchases (store_id);
+
+-- Earlier purchases, without what they cost
+INSERT INTO purchases (id, user_id, item_id, item_name, quantity, purchased_at)
+SELECT id, user_id, item_id, item_name, quantity_delta, created_at
+FROM item_events
+WHERE kind = 'purchased';
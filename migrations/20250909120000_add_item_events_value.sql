@@ -0,0 +1,8 @@
+-- What the stock thrown away by an `expired`, `spoiled` or `lost` event was
+-- worth, in hundredths, estimated from the item's latest priced purchase;
+-- NULL when it was never bought with a price
+ALTER TABLE item_events
+    ADD COLUMN value_cents INTEGER CONSTRAINT item_events_value_non_negative CHECK (value_cents >= 0);
+
+CREATE INDEX idx_item_events_user_id_waste ON item_events (user_id, created_at)
+    WHERE kind IN ('expired', 'spoiled', 'lost');
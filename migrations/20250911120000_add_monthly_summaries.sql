@@ -0,0 +1,11 @@
+-- Whether the user gets the monthly summary email; nobody does unless they
+-- ask for it
+ALTER TABLE user_preferences ADD COLUMN monthly_summary BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The months each user was sent the summary of, so none goes out twice
+CREATE TABLE monthly_summaries_sent (
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    month DATE NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, month)
+);
@@ -0,0 +1,16 @@
+-- Stock that is used up at a steady pace, such as a dishwasher tab a day:
+-- every `every_days` days `quantity` units of the item are used on their own
+CREATE TABLE consumption_rules (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- One rule per item
+    item_id INTEGER NOT NULL UNIQUE REFERENCES items (id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CONSTRAINT consumption_rules_quantity_positive CHECK (quantity > 0),
+    every_days INTEGER NOT NULL
+        CONSTRAINT consumption_rules_every_days_positive CHECK (every_days > 0),
+    next_run_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_consumption_rules_next_run_at ON consumption_rules (next_run_at);
+CREATE INDEX idx_consumption_rules_user_id ON consumption_rules (user_id);
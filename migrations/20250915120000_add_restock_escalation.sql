@@ -0,0 +1,35 @@
+-- Since when an item has been below its restock threshold, kept up to date
+-- by a trigger so that every way the quantity changes is covered
+ALTER TABLE items ADD COLUMN low_since TIMESTAMPTZ;
+
+CREATE OR REPLACE FUNCTION trigger_set_low_since()
+RETURNS TRIGGER AS $$
+BEGIN
+  IF NEW.quantity >= NEW.restock_threshold THEN
+    NEW.low_since = NULL;
+  ELSIF NEW.low_since IS NULL THEN
+    NEW.low_since = NOW();
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Items low already count from now; the other triggers would bump their
+-- version and make every client sync them again
+ALTER TABLE items DISABLE TRIGGER USER;
+UPDATE items SET low_since = NOW() WHERE quantity < restock_threshold;
+ALTER TABLE items ENABLE TRIGGER USER;
+
+CREATE TRIGGER set_low_since
+BEFORE INSERT OR UPDATE ON items
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_set_low_since();
+
+-- After how many days below the threshold an item becomes urgent and its
+-- alert is sent again; 0 turns escalation off
+ALTER TABLE user_preferences ADD COLUMN restock_escalation_days INTEGER NOT NULL DEFAULT 0
+    CONSTRAINT user_preferences_restock_escalation_days_non_negative
+    CHECK (restock_escalation_days >= 0);
+
+-- Channels that only get urgent alerts, such as another household member's
+ALTER TABLE notification_channels ADD COLUMN escalation_only BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- The pack size an item comes in, e.g. 10 eggs or a 6-pack of water;
+-- purchase suggestions are rounded up to it
+ALTER TABLE items
+    ADD COLUMN quantity_step INTEGER NOT NULL DEFAULT 1
+        CONSTRAINT items_quantity_step_positive CHECK (quantity_step > 0);
@@ -0,0 +1,43 @@
+-- A product's barcode, e.g. the EAN printed on the package
+ALTER TABLE items ADD COLUMN barcode VARCHAR(64);
+
+-- Every product the user has had, kept after its item is deleted, so it can
+-- be added again without typing its details
+CREATE TABLE catalog_products (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    barcode VARCHAR(64),
+    category_id INTEGER REFERENCES categories (id) ON DELETE SET NULL,
+    unit VARCHAR(32),
+    restock_threshold INTEGER NOT NULL,
+    last_used_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_catalog_products_user_id_name ON catalog_products (user_id, name);
+CREATE INDEX idx_catalog_products_user_id_barcode ON catalog_products (user_id, barcode);
+
+-- Items are entered into the catalog however they are created or edited
+CREATE OR REPLACE FUNCTION trigger_remember_catalog_product()
+RETURNS TRIGGER AS $$
+BEGIN
+  INSERT INTO catalog_products (user_id, name, barcode, category_id, unit, restock_threshold)
+  VALUES (NEW.user_id, NEW.name, NEW.barcode, NEW.category_id, NEW.unit, NEW.restock_threshold)
+  ON CONFLICT (user_id, name) DO UPDATE
+  SET barcode = COALESCE(EXCLUDED.barcode, catalog_products.barcode),
+      category_id = EXCLUDED.category_id,
+      unit = EXCLUDED.unit,
+      restock_threshold = EXCLUDED.restock_threshold,
+      last_used_at = NOW();
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER remember_catalog_product
+AFTER INSERT OR UPDATE OF name, barcode, category_id, unit, restock_threshold ON items
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_remember_catalog_product();
+
+INSERT INTO catalog_products (user_id, name, barcode, category_id, unit, restock_threshold,
+                              last_used_at)
+SELECT user_id, name, barcode, category_id, unit, restock_threshold, updated_at FROM items;
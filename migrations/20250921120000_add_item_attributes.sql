@@ -0,0 +1,17 @@
+-- Extra fields the user defines for their items, such as "Marka" or "Dawka".
+-- Each item keeps its values in `items.attributes`, keyed by field name
+CREATE TABLE attribute_fields (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name VARCHAR(64) NOT NULL,
+    kind VARCHAR(16) NOT NULL
+        CONSTRAINT attribute_fields_kind_check CHECK (kind IN ('text', 'number', 'boolean', 'date')),
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_attribute_fields_user_id_name ON attribute_fields (user_id, name);
+
+ALTER TABLE items ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
+
+CREATE INDEX idx_items_attributes ON items USING GIN (attributes jsonb_path_ops);
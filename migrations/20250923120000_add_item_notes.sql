@@ -0,0 +1,2 @@
+-- Free-form remarks about an item, e.g. which of two similar products to buy
+ALTER TABLE items ADD COLUMN notes TEXT;
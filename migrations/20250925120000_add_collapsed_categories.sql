@@ -0,0 +1,3 @@
+-- Category groups the user folded away on the item list, shown collapsed on
+-- every device. Ids of deleted categories are dropped when the list is saved
+ALTER TABLE user_preferences ADD COLUMN collapsed_category_ids INTEGER[] NOT NULL DEFAULT '{}';
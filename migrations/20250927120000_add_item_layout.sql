@@ -0,0 +1,3 @@
+-- How the item list is drawn: a dense table for desktops or cards for phones
+ALTER TABLE user_preferences ADD COLUMN layout TEXT NOT NULL DEFAULT 'table'
+    CONSTRAINT user_preferences_layout_check CHECK (layout IN ('table', 'cards'));
@@ -0,0 +1,2 @@
+-- Zone the pages show times in; timestamps stay in UTC in the database
+ALTER TABLE user_preferences ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
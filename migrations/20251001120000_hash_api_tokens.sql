@@ -0,0 +1,12 @@
+-- Tokens are kept only as their SHA-256, so the table doesn't hand out
+-- working credentials to whoever can read it; the start of each stays to
+-- tell them apart on the settings page
+ALTER TABLE api_tokens ADD COLUMN token_hash CHAR(64);
+ALTER TABLE api_tokens ADD COLUMN token_prefix VARCHAR(8);
+UPDATE api_tokens
+SET token_hash = encode(sha256(convert_to(token, 'UTF8')), 'hex'),
+    token_prefix = left(token, 8);
+ALTER TABLE api_tokens ALTER COLUMN token_hash SET NOT NULL;
+ALTER TABLE api_tokens ALTER COLUMN token_prefix SET NOT NULL;
+ALTER TABLE api_tokens ADD CONSTRAINT api_tokens_token_hash_key UNIQUE (token_hash);
+ALTER TABLE api_tokens DROP COLUMN token;
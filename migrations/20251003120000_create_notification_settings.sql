@@ -0,0 +1,23 @@
+-- When each user's alerts go out: not during their quiet hours, and for
+-- channels set to `digest`, once a day as a summary
+CREATE TABLE notification_settings (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- Local times; nothing is sent from quiet_start until quiet_end, which
+    -- may be on the next day
+    quiet_start TIME,
+    quiet_end TIME,
+    digest_time TIME NOT NULL DEFAULT '08:00',
+    -- IANA name of the zone of the times above; NULL for the zone the pages
+    -- show times in
+    timezone TEXT,
+    -- So each day's digest goes out once
+    last_digest_at TIMESTAMPTZ,
+    CONSTRAINT notification_settings_quiet_hours_check
+        CHECK ((quiet_start IS NULL) = (quiet_end IS NULL))
+);
+
+-- 'immediate': new alerts as they come up; 'digest': all current alerts
+-- once a day at the user's digest time
+ALTER TABLE notification_channels
+    ADD COLUMN delivery TEXT NOT NULL DEFAULT 'immediate'
+        CHECK (delivery IN ('immediate', 'digest'));
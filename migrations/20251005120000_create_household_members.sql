@@ -0,0 +1,33 @@
+-- Households: other accounts let into a user's inventory. A household is
+-- known by the id of the user who owns it, and only they manage its members
+CREATE TABLE household_members (
+    household_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- 'viewer': sees the inventory; 'editor': also changes it
+    role TEXT NOT NULL CHECK (role IN ('viewer', 'editor')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (household_id, user_id),
+    CONSTRAINT household_members_not_owner_check CHECK (household_id <> user_id)
+);
+
+CREATE INDEX idx_household_members_user_id ON household_members (user_id);
+
+-- The household a session works in; NULL for the user's own inventory.
+-- household_switched_at is when that last changed, since pages cached from
+-- one inventory are no good in another
+ALTER TABLE sessions
+    ADD COLUMN household_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN household_switched_at TIMESTAMPTZ;
+
+-- Members are invited by email and join once the account with that email
+-- accepts, so the owner isn't told which emails have an account
+CREATE TABLE household_invitations (
+    id SERIAL PRIMARY KEY,
+    household_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('viewer', 'editor')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT household_invitations_household_id_email_key UNIQUE (household_id, email)
+);
+
+CREATE INDEX idx_household_invitations_email ON household_invitations (email);
@@ -0,0 +1,8 @@
+-- Who made a change: the owner of the inventory or, in a household, the
+-- member working in it. NULL once their account is deleted
+ALTER TABLE item_events
+    ADD COLUMN actor_id INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+UPDATE item_events SET actor_id = user_id;
+
+CREATE INDEX idx_item_events_actor_id_created_at_id ON item_events (actor_id, created_at, id);
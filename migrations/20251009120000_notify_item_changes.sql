@@ -0,0 +1,21 @@
+-- Tells listeners such as the terminal dashboard that a user's items or
+-- categories changed, as a notification on `item_changes` carrying the
+-- user's id. It goes out when the transaction commits, once per user however
+-- many rows the transaction touched.
+CREATE OR REPLACE FUNCTION trigger_notify_item_change()
+RETURNS TRIGGER AS $$
+BEGIN
+  PERFORM pg_notify('item_changes', COALESCE(NEW.user_id, OLD.user_id)::TEXT);
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notify_item_change
+AFTER INSERT OR UPDATE OR DELETE ON items
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_notify_item_change();
+
+CREATE TRIGGER notify_item_change
+AFTER INSERT OR UPDATE OR DELETE ON categories
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_notify_item_change();
@@ -0,0 +1,80 @@
+//! The item history as a feed of who did what, to read through rather than
+//! to count: "Ala: zużyto Mleko ×1". Lines use impersonal verbs, as nothing
+//! tells the grammatical gender of a name.
+
+use crate::models::{ActivityEntry, ActivityQuery, ItemEvent};
+use crate::pagination::{Page, PageQuery};
+use crate::{AppState, db, errors::AppError};
+
+/// What happened, without who did it.
+pub fn describe(event: &ItemEvent) -> String {
+    let name = &event.item_name;
+    let delta = event.quantity_delta;
+    match event.kind.as_str() {
+        "used" => format!("zużyto {name} ×{}", -delta),
+        "purchased" => format!("kupiono {name} ×{delta}"),
+        "expired" => format!("wyrzucono przeterminowane {name} ×{}", -delta),
+        "spoiled" => format!("wyrzucono zepsute {name} ×{}", -delta),
+        "lost" => format!("zgubiono {name} ×{}", -delta),
+        "correction" => format!("poprawiono stan {name} o {delta:+}"),
+        "stocktake" => format!("inwentaryzacja: {name} {delta:+}"),
+        kind => format!("{kind}: {name} {delta:+}"),
+    }
+}
+
+/// Changes of accounts deleted since, whose names are gone with them.
+const DELETED_ACCOUNT: &str = "Usunięte konto";
+
+/// Lines of the feed from events with the id and name of who made each.
+pub fn entries(events: Vec<(ItemEvent, Option<i32>, Option<String>)>) -> Vec<ActivityEntry> {
+    events
+        .into_iter()
+        .map(|(event, user_id, user_name)| {
+            let user_name = user_name.unwrap_or_else(|| DELETED_ACCOUNT.to_string());
+            ActivityEntry {
+                text: format!("{user_name}: {}", describe(&event)),
+                user_id,
+                user_name,
+                event,
+                receipt: None,
+            }
+        })
+        .collect()
+}
+
+/// One page of the feed of `user_id`, or of one of their items, or of the
+/// changes of one account, the owner or a member, per `filter`.
+pub async fn page(
+    state: &AppState,
+    user_id: i32,
+    filter: &ActivityQuery,
+    query: &PageQuery,
+) -> Result<Page<ActivityEntry>, AppError> {
+    let limit = query.limit();
+    let cursor = query.cursor()?;
+    if let Some(item_id) = filter.item_id {
+        db::get_item_fingerprint(&state.db_pool, user_id, item_id)
+            .await?
+            .ok_or(AppError::ItemNotFound)?;
+    }
+    let events = db::get_activity(
+        &state.db_pool,
+        user_id,
+        filter.item_id,
+        filter.user_id,
+        cursor,
+        limit + 1,
+    )
+    .await?;
+    let event_ids: Vec<i32> = events.iter().map(|(event, ..)| event.id).collect();
+    let mut receipts = db::get_receipts(&state.db_pool, user_id, &event_ids).await?;
+    let mut entries = entries(events);
+    for entry in &mut entries {
+        if let Some(index) = receipts.iter().position(|r| r.event_id == entry.event.id) {
+            entry.receipt = Some(receipts.swap_remove(index));
+        }
+    }
+    Ok(Page::new(entries, limit, |entry: &ActivityEntry| {
+        entry.event.cursor()
+    }))
+}
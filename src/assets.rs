@@ -0,0 +1,244 @@
+use crate::branding::Branding;
+use crate::conditional;
+use axum::{
+    extract::{Path, Query},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+use std::{fs, io};
+use tera::{Context, Tera, Value};
+
+/// For static URLs carrying the file's current version: browsers may keep the
+/// file for a year without asking, as a new version gets a new URL.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// For plain static URLs, which browsers have to revalidate.
+const REVALIDATE: &str = "no-cache";
+
+// Release builds carry both folders inside the binary, so it runs from any
+// working directory. Debug builds read the files from disk instead, so edits
+// show up without recompiling.
+#[derive(RustEmbed)]
+#[folder = "templates/"]
+struct TemplateFiles;
+
+#[derive(RustEmbed)]
+#[folder = "static/"]
+struct StaticFiles;
+
+/// The page templates. Debug builds parse them again for every render, so a
+/// template edit only needs a browser refresh.
+pub struct Templates {
+    tera: Tera,
+    /// Added to the context of every render.
+    globals: Context,
+    /// Where templates replacing the built-in ones of the same name are read
+    /// from.
+    override_dir: Option<PathBuf>,
+}
+
+impl Templates {
+    pub fn load() -> tera::Result<Templates> {
+        Ok(Templates {
+            tera: load_tera(None)?,
+            globals: Context::new(),
+            override_dir: None,
+        })
+    }
+
+    /// Loads the templates of `dir` over the built-in ones. Its subfolders
+    /// mirror those of `templates/`, e.g. `partials/_items_table.html`.
+    pub fn with_overrides(mut self, dir: impl Into<PathBuf>) -> tera::Result<Templates> {
+        let dir = dir.into();
+        self.tera = load_tera(Some(&dir))?;
+        self.override_dir = Some(dir);
+        Ok(self)
+    }
+
+    /// Names of the built-in templates the override folder replaces, sorted.
+    pub fn overridden(&self) -> tera::Result<Vec<String>> {
+        let Some(dir) = &self.override_dir else {
+            return Ok(Vec::new());
+        };
+        let mut names: Vec<String> = read_overrides(dir)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| TemplateFiles::get(name).is_some())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Gives every page the instance's `branding`.
+    pub fn with_branding(mut self, branding: &Branding) -> Templates {
+        self.globals.insert("branding", branding);
+        self
+    }
+
+    /// Renders on tokio's blocking thread pool: a long item list takes a
+    /// while, and on a single core it would hold up every other request.
+    pub async fn render(
+        self: &Arc<Self>,
+        name: &'static str,
+        context: Context,
+    ) -> tera::Result<String> {
+        let templates = Arc::clone(self);
+        tokio::task::spawn_blocking(move || templates.render_now(name, context))
+            .await
+            .map_err(|e| tera::Error::msg(format!("rendering {name} failed: {e}")))?
+    }
+
+    /// How many templates were loaded, partials included.
+    pub fn count(&self) -> usize {
+        self.tera.get_template_names().count()
+    }
+
+    /// Renders on the calling thread.
+    pub fn render_now(&self, name: &str, mut context: Context) -> tera::Result<String> {
+        context.extend(self.globals.clone());
+        if cfg!(debug_assertions) {
+            return load_tera(self.override_dir.as_deref())?.render(name, &context);
+        }
+        self.tera.render(name, &context)
+    }
+}
+
+fn load_tera(override_dir: Option<&FsPath>) -> tera::Result<Tera> {
+    let mut templates: HashMap<String, String> = TemplateFiles::iter()
+        .filter_map(|name| {
+            let file = TemplateFiles::get(&name)?;
+            let source = String::from_utf8_lossy(&file.data).into_owned();
+            Some((name.into_owned(), source))
+        })
+        .collect();
+    // All at once, so a built-in page can extend or include an override
+    if let Some(dir) = override_dir {
+        templates.extend(read_overrides(dir)?);
+    }
+    let mut tera = Tera::default();
+    tera.add_raw_templates(templates)?;
+    tera.register_function("static_url", static_url);
+    crate::filters::register(&mut tera);
+    Ok(tera)
+}
+
+/// Every file under `dir`, named by its path relative to it.
+fn read_overrides(dir: &FsPath) -> tera::Result<Vec<(String, String)>> {
+    fn visit(dir: &FsPath, prefix: &str, found: &mut Vec<(String, String)>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = format!("{prefix}{}", entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                visit(&entry.path(), &format!("{name}/"), found)?;
+            } else {
+                found.push((name, fs::read_to_string(entry.path())?));
+            }
+        }
+        Ok(())
+    }
+
+    let mut found = Vec::new();
+    visit(dir, "", &mut found).map_err(|e| {
+        tera::Error::msg(format!(
+            "cannot read template overrides in {}: {e}",
+            dir.display()
+        ))
+    })?;
+    Ok(found)
+}
+
+/// `static_url(path="style.css")` in templates: the file's URL (without the
+/// base path) with its version, so it can be cached for good.
+fn static_url(args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let path = args
+        .get("path")
+        .and_then(Value::as_str)
+        .ok_or("static_url needs a `path`")?;
+    let url = versioned_url(path).ok_or_else(|| format!("no static file named {path:?}"))?;
+    Ok(Value::from(url))
+}
+
+/// The URL of a static file with its version, as `static_url` gives it.
+pub fn versioned_url(path: &str) -> Option<String> {
+    let version = static_version(path)?;
+    Some(format!("/static/{path}?v={version}"))
+}
+
+/// `versioned_url` of every static file.
+pub fn versioned_urls() -> Vec<String> {
+    StaticFiles::iter()
+        .filter_map(|path| versioned_url(&path))
+        .collect()
+}
+
+/// Changes whenever a static file or a template does, to tell apart the
+/// caches of different builds.
+pub fn bundle_version() -> String {
+    let mut hasher = DefaultHasher::new();
+    for path in StaticFiles::iter() {
+        if let Some(file) = StaticFiles::get(&path) {
+            file.metadata.sha256_hash().hash(&mut hasher);
+        }
+    }
+    for path in TemplateFiles::iter() {
+        if let Some(file) = TemplateFiles::get(&path) {
+            file.metadata.sha256_hash().hash(&mut hasher);
+        }
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Start of the file's SHA-256, enough to tell versions apart.
+fn static_version(path: &str) -> Option<String> {
+    let file = StaticFiles::get(path)?;
+    let hash = file.metadata.sha256_hash();
+    Some(hash[..8].iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StaticQuery {
+    pub(crate) v: Option<String>,
+}
+
+/// `Cache-Control` of a static URL, depending on whether its `?v=` is the
+/// file's current version.
+pub(crate) fn cache_control(current: bool) -> &'static str {
+    if current { IMMUTABLE } else { REVALIDATE }
+}
+
+/// GET /static/{*path}
+pub async fn static_file(
+    Path(path): Path<String>,
+    Query(query): Query<StaticQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let (Some(file), Some(version)) = (StaticFiles::get(&path), static_version(&path)) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let etag = HeaderValue::from_str(&format!("\"{version}\""))
+        .expect("a hex string is a valid header value");
+    // An outdated version in the URL still gets the current file, just not
+    // for keeps
+    let cache_control = cache_control(query.v.as_deref() == Some(version.as_str()));
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(cache_control),
+        ),
+    ];
+    if conditional::is_fresh(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    (
+        cache_headers,
+        [(header::CONTENT_TYPE, file.metadata.mimetype().to_string())],
+        file.data,
+    )
+        .into_response()
+}
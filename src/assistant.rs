@@ -0,0 +1,160 @@
+//! Fulfillment for voice assistants (an Alexa skill, a Google Assistant
+//! action): the assistant recognises one of a few intents and the item name,
+//! and this turns it into the same changes the web UI makes, with a sentence
+//! to say back. Like the Home Assistant endpoints it takes an API token.
+
+use crate::models::{
+    AssistantIntent, AssistantReply, CreateItemPayload, Item, PurchaseItemPayload,
+    UpdateItemPayload,
+};
+use crate::{db, errors::AppError, validation::Validate};
+use sqlx::PgPool;
+
+/// Carries out `intent` for `user_id`. Items are looked up by name, ignoring
+/// case; adding one that doesn't exist yet creates it.
+pub async fn handle(
+    pool: &PgPool,
+    user_id: i32,
+    intent: AssistantIntent,
+) -> Result<AssistantReply, AppError> {
+    intent.validate()?;
+    let existing = match db::find_item_by_name(pool, user_id, intent.item()).await? {
+        Some(item_id) => db::get_item_by_id(pool, user_id, item_id).await?,
+        None => None,
+    };
+    match intent {
+        AssistantIntent::AddItem { item, quantity } => {
+            add_item(pool, user_id, existing, &item, quantity.unwrap_or(1)).await
+        }
+        AssistantIntent::CheckStock { item } => Ok(match existing {
+            Some(found) if found.quantity == 0 => AssistantReply {
+                speech: format!("{}: nic nie zostało", found.name),
+                item: Some(found),
+            },
+            Some(found) => AssistantReply {
+                speech: format!("{}: {}", found.name, amount(&found, found.quantity)),
+                item: Some(found),
+            },
+            None => AssistantReply {
+                speech: format!("Nie ma niczego o nazwie {}", item.trim()),
+                item: None,
+            },
+        }),
+        AssistantIntent::AddToShoppingList { item } => {
+            add_to_shopping_list(pool, user_id, existing, &item).await
+        }
+    }
+}
+
+/// Buys `quantity` more of an existing item, or creates it with that much.
+async fn add_item(
+    pool: &PgPool,
+    user_id: i32,
+    existing: Option<Item>,
+    name: &str,
+    quantity: i32,
+) -> Result<AssistantReply, AppError> {
+    let item = match existing {
+        Some(item) => {
+            let payload = PurchaseItemPayload {
+                quantity,
+                expires_on: None,
+                price: None,
+                store_id: None,
+            };
+            db::purchase_item(pool, user_id, user_id, item.id, payload)
+                .await?
+                .ok_or(AppError::ItemNotFound)?
+        }
+        None => db::create_item(pool, user_id, new_item(name, quantity)).await?,
+    };
+    Ok(AssistantReply {
+        speech: format!(
+            "Dodano {}: {}, jest teraz {}",
+            item.name,
+            amount(&item, quantity),
+            amount(&item, item.quantity)
+        ),
+        item: Some(item),
+    })
+}
+
+/// The shopping list is made of the items below their restock threshold, so
+/// an item above it gets its threshold raised to just over what is left.
+/// One that doesn't exist yet is created with none in stock.
+async fn add_to_shopping_list(
+    pool: &PgPool,
+    user_id: i32,
+    existing: Option<Item>,
+    name: &str,
+) -> Result<AssistantReply, AppError> {
+    let item = match existing {
+        Some(item) if item.quantity < item.restock_threshold => {
+            return Ok(AssistantReply {
+                speech: format!("{} już jest na liście zakupów", item.name),
+                item: Some(item),
+            });
+        }
+        Some(item) => {
+            // Everything else as it is; the update replaces the optional fields
+            let payload = UpdateItemPayload {
+                name: None,
+                quantity: None,
+                restock_threshold: Some(item.quantity + 1),
+                restock_to: item.restock_to,
+                unit: item.unit,
+                location: item.location,
+                barcode: item.barcode,
+                notes: item.notes,
+                category_id: item.category.map(|category| category.id),
+                preferred_store_id: item.preferred_store_id,
+                quantity_step: None,
+                attributes: None,
+                version: item.version,
+            };
+            db::update_item(pool, user_id, item.id, payload)
+                .await?
+                .ok_or(AppError::ItemNotFound)?
+        }
+        None => db::create_item(pool, user_id, new_item(name, 0)).await?,
+    };
+    Ok(AssistantReply {
+        speech: format!("Dodano {} do listy zakupów", item.name),
+        item: Some(item),
+    })
+}
+
+fn new_item(name: &str, quantity: i32) -> CreateItemPayload {
+    CreateItemPayload {
+        name: capitalize(name.trim()),
+        quantity,
+        restock_threshold: None,
+        restock_to: None,
+        unit: None,
+        location: None,
+        barcode: None,
+        notes: None,
+        expires_on: None,
+        category_id: None,
+        preferred_store_id: None,
+        quantity_step: None,
+        attributes: None,
+    }
+}
+
+/// Speech recognition writes names in lower case, item names start upper case.
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// `quantity` in the item's unit, e.g. "3 l"; pieces go without one.
+fn amount(item: &Item, quantity: i32) -> String {
+    match item.unit.as_deref() {
+        Some(unit) if !unit.is_empty() => format!("{quantity} {unit}"),
+        _ => quantity.to_string(),
+    }
+}
@@ -0,0 +1,234 @@
+//! Custom item fields. Users define the fields (a name and a type) and every
+//! item keeps its values in a JSON object keyed by field name, which is checked
+//! against the definitions whenever it's written.
+
+use crate::db as db_queries;
+use crate::errors::AppError;
+use crate::models::{AttributeField, AttributeKind, Attributes};
+use crate::validation::{MAX_TEXT_LEN, ValidationErrors};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use time::Date;
+use time::macros::format_description;
+
+/// Longest field name; the column is VARCHAR(64).
+pub const MAX_ATTRIBUTE_NAME_LEN: usize = 64;
+
+/// Prefix of the `GET /api/items` query parameters that filter by a field,
+/// as in `?attr.Marka=Łaciate`.
+pub const FILTER_PREFIX: &str = "attr.";
+
+/// Prefix of the item form inputs, followed by the field id.
+const INPUT_PREFIX: &str = "attr_";
+
+impl AttributeKind {
+    /// Checks a value sent as JSON. Text is trimmed; blank text and nulls
+    /// come back as `None`, meaning no value.
+    fn check(self, value: Value) -> Result<Option<Value>, &'static str> {
+        match (self, value) {
+            (_, Value::Null) => Ok(None),
+            (AttributeKind::Text, Value::String(text)) => text_value(&text),
+            (AttributeKind::Number, value @ Value::Number(_)) => Ok(Some(value)),
+            (AttributeKind::Boolean, value @ Value::Bool(_)) => Ok(Some(value)),
+            (AttributeKind::Date, Value::String(text)) => date_value(&text),
+            (AttributeKind::Text, _) => Err("must be text"),
+            (AttributeKind::Number, _) => Err("must be a number"),
+            (AttributeKind::Boolean, _) => Err("must be true or false"),
+            (AttributeKind::Date, _) => Err("must be a date (YYYY-MM-DD)"),
+        }
+    }
+
+    /// Reads a value typed into a form or query string. A blank one is `None`.
+    fn parse(self, input: &str) -> Result<Option<Value>, &'static str> {
+        let input = input.trim();
+        match self {
+            AttributeKind::Text => text_value(input),
+            AttributeKind::Date => date_value(input),
+            _ if input.is_empty() => Ok(None),
+            AttributeKind::Number => input
+                .replace(',', ".")
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(|number| Some(number_value(number)))
+                .ok_or("must be a number"),
+            AttributeKind::Boolean => match input {
+                "true" | "on" | "1" => Ok(Some(Value::Bool(true))),
+                "false" | "0" => Ok(Some(Value::Bool(false))),
+                _ => Err("must be true or false"),
+            },
+        }
+    }
+}
+
+fn text_value(text: &str) -> Result<Option<Value>, &'static str> {
+    let text = text.trim();
+    if text.is_empty() {
+        Ok(None)
+    } else if text.chars().count() > MAX_TEXT_LEN {
+        Err("is too long")
+    } else {
+        Ok(Some(Value::from(text)))
+    }
+}
+
+fn date_value(text: &str) -> Result<Option<Value>, &'static str> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    Date::parse(text, format_description!("[year]-[month]-[day]"))
+        .map(|_| Some(Value::from(text)))
+        .map_err(|_| "must be a date (YYYY-MM-DD)")
+}
+
+/// Whole numbers are kept as integers, so `2` typed into a form matches `2`
+/// sent as JSON.
+fn number_value(number: serde_json::Number) -> Value {
+    match number.as_f64() {
+        Some(float) if float.fract() == 0.0 && float.abs() < i64::MAX as f64 => {
+            Value::from(float as i64)
+        }
+        _ => Value::Number(number),
+    }
+}
+
+fn find<'a>(fields: &'a [AttributeField], name: &str) -> Option<&'a AttributeField> {
+    fields.iter().find(|field| field.name == name)
+}
+
+/// Checks attributes sent as JSON against the user's fields: every key must
+/// be a field and every value of the field's type. Nulls and blank text are
+/// dropped, so they clear a value.
+pub fn normalize(
+    fields: &[AttributeField],
+    attributes: Attributes,
+) -> Result<Attributes, ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    let mut normalized = Attributes::new();
+    for (name, value) in attributes {
+        let Some(field) = find(fields, &name) else {
+            errors.add("attributes", format!("{name}: no such field"));
+            continue;
+        };
+        match field.kind.check(value) {
+            Ok(Some(value)) => {
+                normalized.insert(name, value);
+            }
+            Ok(None) => {}
+            Err(message) => errors.add("attributes", format!("{name}: {message}")),
+        }
+    }
+    errors.into_result().map(|()| normalized)
+}
+
+/// Checks attributes against the fields `user_id` has defined. `None`, for
+/// "leave them as they are", passes through.
+pub async fn check(
+    pool: &PgPool,
+    user_id: i32,
+    attributes: Option<Attributes>,
+) -> Result<Option<Attributes>, AppError> {
+    let Some(attributes) = attributes else {
+        return Ok(None);
+    };
+    let fields = db_queries::list_attribute_fields(pool, user_id).await?;
+    Ok(Some(normalize(&fields, attributes)?))
+}
+
+/// Reads the `attr_{field id}` inputs of a submitted item form. An unticked
+/// checkbox isn't sent, so a boolean field without an input is `false`.
+pub fn from_form(
+    fields: &[AttributeField],
+    form: &HashMap<String, String>,
+) -> Result<Attributes, ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    let mut attributes = Attributes::new();
+    for field in fields {
+        let input = form.get(&format!("{INPUT_PREFIX}{}", field.id));
+        let parsed = match (field.kind, input) {
+            (AttributeKind::Boolean, None) => Ok(Some(Value::Bool(false))),
+            (_, None) => Ok(None),
+            (kind, Some(input)) => kind.parse(input),
+        };
+        match parsed {
+            Ok(Some(value)) => {
+                attributes.insert(field.name.clone(), value);
+            }
+            Ok(None) => {}
+            Err(message) => errors.add("attributes", format!("{}: {message}", field.name)),
+        }
+    }
+    errors.into_result().map(|()| attributes)
+}
+
+/// The `attr.{name}` parameters of a query string as attributes an item's
+/// must contain. Values are read by the field's type, so `attr.Rozmiar=2`
+/// matches the number 2.
+pub fn filter_from_query(
+    fields: &[AttributeField],
+    query: &HashMap<String, String>,
+) -> Result<Attributes, ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    let mut filter = Attributes::new();
+    for (key, input) in query {
+        let Some(name) = key.strip_prefix(FILTER_PREFIX) else {
+            continue;
+        };
+        let Some(field) = find(fields, name) else {
+            errors.add("attributes", format!("{name}: no such field"));
+            continue;
+        };
+        match field.kind.parse(input) {
+            Ok(Some(value)) => {
+                filter.insert(field.name.clone(), value);
+            }
+            Ok(None) => errors.add("attributes", format!("{name}: must not be empty")),
+            Err(message) => errors.add("attributes", format!("{name}: {message}")),
+        }
+    }
+    errors.into_result().map(|()| filter)
+}
+
+/// One custom field input of an item form.
+#[derive(Debug, Serialize)]
+pub struct AttributeInput {
+    pub id: i32,
+    pub name: String,
+    pub kind: AttributeKind,
+    /// What the input shows: the stored value, or what was submitted.
+    pub value: String,
+}
+
+/// The custom field inputs of an item form, filled in with `stored` or, when
+/// a submission is shown again, with the `submitted` values.
+pub fn form_inputs(
+    fields: &[AttributeField],
+    stored: &Attributes,
+    submitted: Option<&HashMap<String, String>>,
+) -> Vec<AttributeInput> {
+    fields
+        .iter()
+        .map(|field| {
+            let value = match submitted {
+                Some(form) => form
+                    .get(&format!("{INPUT_PREFIX}{}", field.id))
+                    .cloned()
+                    .unwrap_or_default(),
+                None => match stored.get(&field.name) {
+                    Some(Value::String(text)) => text.clone(),
+                    Some(Value::Bool(false)) | None => String::new(),
+                    Some(value) => value.to_string(),
+                },
+            };
+            AttributeInput {
+                id: field.id,
+                name: field.name.clone(),
+                kind: field.kind,
+                value,
+            }
+        })
+        .collect()
+}
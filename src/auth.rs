@@ -0,0 +1,333 @@
+use crate::{
+    AppState,
+    config::{Config, CookieSameSite, CookieSecure},
+    db,
+    errors::AppError,
+    households::Membership,
+    proxy::Client,
+    reporting, scheduler,
+};
+use axum::{
+    body::Body,
+    extract::{FromRequestParts, OriginalUri, State},
+    http::{Method, Request, header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use sqlx::PgPool;
+use std::{convert::Infallible, sync::Arc};
+use time::{Duration, OffsetDateTime};
+
+pub const SESSION_COOKIE: &str = "session";
+
+/// A session used again within this long isn't written back, so browsing
+/// doesn't cost an UPDATE per request.
+const TOUCH_INTERVAL: Duration = Duration::minutes(1);
+
+/// How long logins last without being used. Each request pushes the expiry
+/// ahead again, so only idle sessions run out.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionSettings {
+    pub ttl: Duration,
+    /// For logins with "remember me" checked.
+    pub remember_ttl: Duration,
+    pub secure: CookieSecure,
+    pub same_site: CookieSameSite,
+}
+
+impl SessionSettings {
+    pub fn from_config(config: &Config) -> SessionSettings {
+        SessionSettings {
+            ttl: Duration::hours(config.session_hours as i64),
+            remember_ttl: Duration::days(config.remember_days as i64),
+            secure: config.cookie_secure,
+            same_site: config.cookie_same_site,
+        }
+    }
+
+    pub fn lifetime(&self, remember: bool) -> Duration {
+        if remember {
+            self.remember_ttl
+        } else {
+            self.ttl
+        }
+    }
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        SessionSettings {
+            ttl: Duration::hours(12),
+            remember_ttl: Duration::days(30),
+            secure: CookieSecure::default(),
+            same_site: CookieSameSite::default(),
+        }
+    }
+}
+
+/// The signed-in user, put into the request by `load_session`. Rejects with
+/// 401; web pages never get that far, since `require_login` redirects to the
+/// login page first. While the session works in someone else's household,
+/// this is the owner of the household, whose inventory is then used.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthUser(pub i32);
+
+/// The account that signed in, even while working in someone else's
+/// household. Set by `load_session` together with `AuthUser`.
+#[derive(Debug, Clone, Copy)]
+pub struct SignedInUser(pub i32);
+
+impl<S: Send + Sync> FromRequestParts<S> for SignedInUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<SignedInUser>()
+            .copied()
+            .ok_or(AppError::Unauthorized)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthUser>()
+            .copied()
+            .ok_or(AppError::Unauthorized)
+    }
+}
+
+/// Id of the session making the request, for telling it apart in the list of
+/// sessions. Set by `load_session` together with `AuthUser`.
+#[derive(Debug, Clone, Copy)]
+pub struct CurrentSession(pub i32);
+
+impl<S: Send + Sync> FromRequestParts<S> for CurrentSession {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CurrentSession>()
+            .copied()
+            .ok_or(AppError::Unauthorized)
+    }
+}
+
+/// Where a login comes from, stored with the session so the user can
+/// recognise their devices.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let ip_address = Client::from_request_parts(parts, state)
+            .await?
+            .ip
+            .map(|ip| ip.to_string());
+        Ok(ClientInfo {
+            user_agent,
+            ip_address,
+        })
+    }
+}
+
+/// 32 random bytes in hex, for tokens that are the only thing needed to get
+/// in.
+pub fn random_token() -> Result<String, AppError> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Creates a session for `user_id` and returns its token.
+pub async fn start_session(
+    pool: &PgPool,
+    user_id: i32,
+    remember: bool,
+    client: &ClientInfo,
+    settings: &SessionSettings,
+) -> Result<String, AppError> {
+    let token = random_token()?;
+    db::create_session(
+        pool,
+        &token,
+        user_id,
+        remember,
+        settings.lifetime(remember),
+        client.user_agent.as_deref(),
+        client.ip_address.as_deref(),
+    )
+    .await?;
+    Ok(token)
+}
+
+/// The cookie holding a session token. Without "remember me" it is a browser
+/// session cookie; otherwise it lasts as long as the session itself.
+/// `Secure` and `SameSite` follow the settings; by default it is `Secure`
+/// when the browser reached the app over HTTPS.
+pub fn session_cookie(
+    token: String,
+    remember: bool,
+    settings: &SessionSettings,
+    client: Client,
+) -> Cookie<'static> {
+    let cookie = Cookie::build((SESSION_COOKIE, token))
+        .path("/")
+        .http_only(true)
+        .secure(match settings.secure {
+            CookieSecure::Auto => client.https,
+            CookieSecure::Always => true,
+            CookieSecure::Never => false,
+        })
+        .same_site(match settings.same_site {
+            CookieSameSite::Lax => SameSite::Lax,
+            CookieSameSite::Strict => SameSite::Strict,
+            CookieSameSite::None => SameSite::None,
+        });
+    if remember {
+        cookie.max_age(settings.remember_ttl).build()
+    } else {
+        cookie.build()
+    }
+}
+
+/// Looks up the session of the `session` cookie and, if it is valid, makes the
+/// user available to `AuthUser`, `SignedInUser`, `CurrentSession` and the request's
+/// log lines, with the household's `Membership` while the session works in
+/// someone else's household. Also slides the
+/// expiry forward, on the server and for remembered logins in the browser.
+pub async fn load_session(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(token) = jar.get(SESSION_COOKIE).map(|c| c.value().to_string()) else {
+        return next.run(req).await;
+    };
+    let session = match db::get_session(&state.db_pool, &token).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return next.run(req).await,
+        Err(e) => return AppError::from(e).into_response(),
+    };
+    tracing::Span::current().record("user_id", session.user_id);
+    reporting::set_user(session.user_id);
+    req.extensions_mut().insert(SignedInUser(session.user_id));
+    req.extensions_mut().insert(CurrentSession(session.id));
+    match (session.household_id, session.household_role) {
+        (Some(household_id), Some(role)) => {
+            req.extensions_mut().insert(AuthUser(household_id));
+            req.extensions_mut()
+                .insert(Membership { household_id, role });
+        }
+        _ => {
+            req.extensions_mut().insert(AuthUser(session.user_id));
+        }
+    }
+
+    let client = req
+        .extensions()
+        .get::<Client>()
+        .copied()
+        .unwrap_or_default();
+    let lifetime = state.sessions.lifetime(session.remember);
+    let touch = OffsetDateTime::now_utc() - session.last_seen_at > TOUCH_INTERVAL;
+    if touch && let Err(e) = db::touch_session(&state.db_pool, &token, lifetime).await {
+        return AppError::from(e).into_response();
+    }
+    let response = next.run(req).await;
+
+    // Unless the handler replaced the cookie itself, e.g. on logout
+    let sets_session = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .any(|value| value.as_bytes().starts_with(b"session="));
+    if touch && session.remember && !sets_session {
+        let jar = CookieJar::new().add(session_cookie(token, true, &state.sessions, client));
+        (jar, response).into_response()
+    } else {
+        response
+    }
+}
+
+/// Removes expired sessions every hour.
+pub fn spawn_session_cleanup(pool: PgPool) {
+    scheduler::spawn_exclusive(
+        "session cleanup",
+        std::time::Duration::from_secs(3600),
+        pool.clone(),
+        move || {
+            let pool = pool.clone();
+            async move {
+                let removed = db::delete_expired_sessions(&pool).await?;
+                if removed > 0 {
+                    tracing::info!("Removed {} expired sessions", removed);
+                }
+                Ok(())
+            }
+        },
+    );
+}
+
+/// Auth guard for web routes. Without a session, GET requests are sent to the
+/// login page with `next` set, so the user comes back after logging in.
+pub async fn require_login(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if req.extensions().get::<AuthUser>().is_some() {
+        return next.run(req).await;
+    }
+    let login_path = format!("{}/web/login", state.base_path);
+    // A form post can't be replayed after login, so only pages are returned to
+    if req.method() != Method::GET {
+        return Redirect::to(&login_path).into_response();
+    }
+    let uri = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or(req.uri(), |original| &original.0);
+    let target = uri.path_and_query().map_or(uri.path(), |p| p.as_str());
+    Redirect::to(&format!(
+        "{}?next={}",
+        login_path,
+        utf8_percent_encode(target, NON_ALPHANUMERIC)
+    ))
+    .into_response()
+}
+
+/// Where to go after logging in: `next` if it is a page of this app, so the
+/// login form can't be used to redirect to another site.
+pub fn redirect_after_login(base_path: &str, next: Option<&str>) -> String {
+    let home = format!("{}/web", base_path);
+    match next {
+        Some(next)
+            if (next == home
+                || next.starts_with(&format!("{}/", home))
+                || next.starts_with(&format!("{}?", home)))
+                && !next.contains(['\\', '\r', '\n']) =>
+        {
+            next.to_string()
+        }
+        _ => home,
+    }
+}
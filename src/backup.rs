@@ -0,0 +1,334 @@
+use crate::attributes;
+use crate::categories;
+use crate::models::{
+    AttributeField, AttributeKind, Attributes, ConsumptionRulePayload, UserPreferences,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use std::collections::HashSet;
+use time::{Date, OffsetDateTime};
+
+time::serde::format_description!(date_format, Date, "[year]-[month]-[day]");
+
+/// Format version written by `GET /api/backup`; restore rejects other versions.
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Everything a user owns, as exported by `GET /api/backup`. Ids are the ones
+/// of the exporting instance and are only used to link records together.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub exported_at: OffsetDateTime,
+    pub preferences: UserPreferences,
+    pub categories: Vec<BackupCategory>,
+    /// Missing from backups made before stores existed.
+    #[serde(default)]
+    pub stores: Vec<BackupStore>,
+    /// Missing from backups made before custom item fields existed.
+    #[serde(default)]
+    pub attribute_fields: Vec<BackupAttributeField>,
+    pub items: Vec<BackupItem>,
+    pub batches: Vec<BackupBatch>,
+    /// Item history, used by the dashboard statistics.
+    pub events: Vec<BackupEvent>,
+    pub recipes: Vec<BackupRecipe>,
+    pub meal_plans: Vec<BackupMealPlan>,
+    /// Missing from backups made before consumption rules existed.
+    #[serde(default)]
+    pub consumption_rules: Vec<BackupConsumptionRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupCategory {
+    pub id: i32,
+    pub name: String,
+    pub color: String,
+    pub parent_id: Option<i32>,
+    pub icon: Option<String>,
+    pub sort_order: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupStore {
+    pub id: i32,
+    pub name: String,
+    pub sort_order: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupAttributeField {
+    pub name: String,
+    pub kind: AttributeKind,
+    pub sort_order: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupItem {
+    pub id: i32,
+    pub name: String,
+    pub quantity: i32,
+    pub restock_threshold: i32,
+    pub restock_to: Option<i32>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub barcode: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub preferred_store_id: Option<i32>,
+    #[serde(default = "default_quantity_step")]
+    pub quantity_step: i32,
+    #[serde(default)]
+    pub attributes: Json<Attributes>,
+    pub sort_order: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupBatch {
+    pub item_id: i32,
+    pub quantity: i32,
+    #[serde(with = "date_format")]
+    pub purchased_on: Date,
+    #[serde(with = "date_format::option")]
+    pub expires_on: Option<Date>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupEvent {
+    /// `None` once the item has been deleted; `item_name` is kept.
+    pub item_id: Option<i32>,
+    pub item_name: String,
+    pub kind: String,
+    pub quantity_delta: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// What a `purchased` event cost, in hundredths.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_cents: Option<i32>,
+    /// Where a `purchased` event was bought.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_id: Option<i32>,
+    /// What the stock thrown away by a waste event was worth, in hundredths.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_cents: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupRecipe {
+    pub id: i32,
+    pub name: String,
+    pub instructions: Option<String>,
+    pub ingredients: Vec<BackupIngredient>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupIngredient {
+    pub item_id: i32,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupMealPlan {
+    pub recipe_id: i32,
+    #[serde(with = "date_format")]
+    pub planned_for: Date,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub cooked_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupConsumptionRule {
+    pub item_id: i32,
+    pub quantity: i32,
+    pub every_days: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub next_run_at: OffsetDateTime,
+}
+
+/// Backups from before items had a pack size count them one by one.
+fn default_quantity_step() -> i32 {
+    1
+}
+
+/// Counts of what `POST /api/restore` imported.
+#[derive(Debug, Serialize)]
+pub struct RestoreSummary {
+    pub categories: usize,
+    pub stores: usize,
+    pub items: usize,
+    pub events: usize,
+    pub recipes: usize,
+    pub meal_plans: usize,
+}
+
+/// Checks that a backup is internally consistent before anything is deleted:
+/// ids are unique and every reference points at a record in the backup.
+pub fn validate(backup: &Backup) -> Result<(), String> {
+    if backup.version != BACKUP_VERSION {
+        return Err(format!(
+            "unsupported backup version {} (expected {})",
+            backup.version, BACKUP_VERSION
+        ));
+    }
+
+    let category_ids = unique_ids(backup.categories.iter().map(|c| c.id), "category")?;
+    let store_ids = unique_ids(backup.stores.iter().map(|s| s.id), "store")?;
+    unique_ids(backup.stores.iter().map(|s| s.name.as_str()), "store name")?;
+    unique_ids(
+        backup.attribute_fields.iter().map(|f| f.name.as_str()),
+        "field name",
+    )?;
+    let item_ids = unique_ids(backup.items.iter().map(|i| i.id), "item")?;
+    let recipe_ids = unique_ids(backup.recipes.iter().map(|r| r.id), "recipe")?;
+    // Item names are unique per user
+    unique_ids(backup.items.iter().map(|i| i.name.as_str()), "item name")?;
+
+    for category in &backup.categories {
+        if !categories::is_hex_color(&category.color) {
+            return Err(format!("category {} has an invalid color", category.id));
+        }
+        categories::validate_icon(category.icon.as_deref())?;
+        let Some(parent_id) = category.parent_id else {
+            continue;
+        };
+        let parent = backup
+            .categories
+            .iter()
+            .find(|c| c.id == parent_id)
+            .ok_or_else(|| format!("category {} has an unknown parent", category.id))?;
+        if parent.parent_id.is_some() || parent_id == category.id {
+            return Err(format!(
+                "category {} is nested more than two levels deep",
+                category.id
+            ));
+        }
+    }
+    // Only names and types matter to the values, the ids are new on restore
+    let fields: Vec<AttributeField> = backup
+        .attribute_fields
+        .iter()
+        .map(|field| AttributeField {
+            id: 0,
+            name: field.name.clone(),
+            kind: field.kind,
+        })
+        .collect();
+    for field in &fields {
+        let length = field.name.trim().chars().count();
+        if length == 0 || length > attributes::MAX_ATTRIBUTE_NAME_LEN {
+            return Err(format!("field name {:?} is invalid", field.name));
+        }
+    }
+    for item in &backup.items {
+        if item.quantity < 0 {
+            return Err(format!("item {} has a negative quantity", item.id));
+        }
+        if item
+            .category_id
+            .is_some_and(|id| !category_ids.contains(&id))
+        {
+            return Err(format!("item {} has an unknown category", item.id));
+        }
+        if item
+            .preferred_store_id
+            .is_some_and(|id| !store_ids.contains(&id))
+        {
+            return Err(format!("item {} has an unknown store", item.id));
+        }
+        if item.quantity_step <= 0 {
+            return Err(format!("item {} has an invalid quantity step", item.id));
+        }
+        if let Err(errors) = attributes::normalize(&fields, item.attributes.0.clone()) {
+            return Err(format!(
+                "item {} has invalid attributes: {}",
+                item.id, errors
+            ));
+        }
+    }
+    for batch in &backup.batches {
+        if !item_ids.contains(&batch.item_id) || batch.quantity <= 0 {
+            return Err(format!("invalid batch of item {}", batch.item_id));
+        }
+    }
+    for event in &backup.events {
+        if event.item_id.is_some_and(|id| !item_ids.contains(&id)) {
+            return Err(format!("event of {} has an unknown item", event.item_name));
+        }
+        if event.store_id.is_some_and(|id| !store_ids.contains(&id)) {
+            return Err(format!(
+                "purchase of {} has an unknown store",
+                event.item_name
+            ));
+        }
+        if event.price_cents.is_some_and(|price| price < 0) {
+            return Err(format!(
+                "purchase of {} has a negative price",
+                event.item_name
+            ));
+        }
+        if event.value_cents.is_some_and(|value| value < 0) {
+            return Err(format!("waste of {} has a negative value", event.item_name));
+        }
+    }
+    for recipe in &backup.recipes {
+        unique_ids(recipe.ingredients.iter().map(|i| i.item_id), "ingredient")?;
+        if recipe
+            .ingredients
+            .iter()
+            .any(|i| !item_ids.contains(&i.item_id) || i.quantity <= 0)
+        {
+            return Err(format!("recipe {} has an invalid ingredient", recipe.id));
+        }
+    }
+    unique_ids(
+        backup.consumption_rules.iter().map(|r| r.item_id),
+        "consumption rule",
+    )?;
+    for rule in &backup.consumption_rules {
+        if !item_ids.contains(&rule.item_id)
+            || rule.quantity <= 0
+            || !(1..=ConsumptionRulePayload::MAX_EVERY_DAYS).contains(&rule.every_days)
+        {
+            return Err(format!("invalid consumption rule of item {}", rule.item_id));
+        }
+    }
+    if let Some(plan) = backup
+        .meal_plans
+        .iter()
+        .find(|p| !recipe_ids.contains(&p.recipe_id))
+    {
+        return Err(format!(
+            "meal plan for {} has an unknown recipe",
+            plan.planned_for
+        ));
+    }
+    Ok(())
+}
+
+fn unique_ids<T: Eq + std::hash::Hash>(
+    ids: impl Iterator<Item = T>,
+    what: &str,
+) -> Result<HashSet<T>, String> {
+    let mut seen = HashSet::new();
+    for id in ids {
+        if !seen.insert(id) {
+            return Err(format!("duplicate {what} in backup"));
+        }
+    }
+    Ok(seen)
+}
@@ -0,0 +1,256 @@
+//! Terminal dashboard for the console of the machine the server runs on: the
+//! items running low, with keys to use one pack or buy what is missing.
+//!
+//! It talks to the database directly, with the same settings as the server,
+//! and listens for the notifications PostgreSQL sends on every change to
+//! items or categories (`db::listen_for_item_changes`), so changes made in
+//! the web UI show up as soon as they are saved. In case one went unheard,
+//! `db::get_last_change` is checked every `--refresh` seconds too.
+
+use clap::Parser;
+use dotenvy::dotenv;
+use household_inventory::config::Config;
+use household_inventory::db;
+use household_inventory::models::{Item, PurchaseItemPayload};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+/// Low-stock dashboard for one account.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Email of the account to show
+    #[arg(long = "user", value_name = "EMAIL", env = "DASHBOARD_USER")]
+    email: String,
+    /// Seconds between checks for changes made elsewhere, on top of the
+    /// notifications
+    #[arg(long, default_value_t = 30)]
+    refresh: u64,
+}
+
+/// How long to wait for a key before looking at the clock again.
+const KEY_POLL: Duration = Duration::from_millis(250);
+
+struct Dashboard {
+    pool: PgPool,
+    user_id: i32,
+    user_name: String,
+    items: Vec<Item>,
+    table: TableState,
+    /// `db::get_last_change` as of the last reload.
+    last_change: Option<OffsetDateTime>,
+    /// Outcome of the last key press, or the last error.
+    status: String,
+}
+
+impl Dashboard {
+    /// Reloads the list if anything changed since the last time, or always
+    /// with `force`.
+    async fn refresh(&mut self, force: bool) -> Result<(), sqlx::Error> {
+        let last_change = db::get_last_change(&self.pool, self.user_id).await?;
+        if !force && last_change == self.last_change {
+            return Ok(());
+        }
+        self.items = db::get_items_to_restock(&self.pool, self.user_id).await?;
+        self.last_change = last_change;
+        let selected = match self.items.len() {
+            0 => None,
+            len => Some(self.table.selected().unwrap_or(0).min(len - 1)),
+        };
+        self.table.select(selected);
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<&Item> {
+        self.table.selected().and_then(|i| self.items.get(i))
+    }
+
+    /// Uses one pack of the selected item.
+    async fn use_selected(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(item) = self.selected() else {
+            return Ok(());
+        };
+        let (id, step) = (item.id, item.quantity_step.max(1));
+        self.status = match db::use_item(&self.pool, self.user_id, self.user_id, id, step).await? {
+            Some(item) => format!("Zużyto {} ×{step}, zostało {}", item.name, item.quantity),
+            None => "Tej rzeczy już nie ma".into(),
+        };
+        self.refresh(true).await?;
+        Ok(())
+    }
+
+    /// Buys what tops the selected item up to its target, in its usual store.
+    async fn purchase_selected(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(item) = self.selected() else {
+            return Ok(());
+        };
+        let (id, quantity) = (item.id, item.suggested_purchase);
+        let payload = PurchaseItemPayload {
+            quantity,
+            expires_on: None,
+            price: None,
+            store_id: None,
+        };
+        let purchased = db::purchase_item(&self.pool, self.user_id, self.user_id, id, payload)
+            .await
+            .map_err(|e| e.into_parts().1.message)?;
+        self.status = match purchased {
+            Some(item) => format!("Kupiono {} ×{quantity}, jest {}", item.name, item.quantity),
+            None => "Tej rzeczy już nie ma".into(),
+        };
+        self.refresh(true).await?;
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list, status, help] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let rows = self.items.iter().map(|item| {
+            let unit = item.unit.as_deref().unwrap_or("szt.");
+            Row::new([
+                item.name.clone(),
+                item.category
+                    .as_ref()
+                    .map(|c| c.name.clone())
+                    .unwrap_or_default(),
+                format!("{} {unit}", item.quantity),
+                item.restock_threshold.to_string(),
+                item.suggested_purchase.to_string(),
+            ])
+        });
+        let title = format!(" Do kupienia: {} — {} ", self.items.len(), self.user_name);
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(3),
+                Constraint::Fill(2),
+                Constraint::Length(12),
+                Constraint::Length(6),
+                Constraint::Length(7),
+            ],
+        )
+        .header(Row::new(["Nazwa", "Kategoria", "Stan", "Próg", "Kupić"]).bold())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title(title));
+        frame.render_stateful_widget(table, list, &mut self.table);
+
+        frame.render_widget(Paragraph::new(self.status.as_str()), status);
+        frame.render_widget(
+            Line::from("↑↓ wybór  u zużyj  k kup  r odśwież  q wyjdź").dim(),
+            help,
+        );
+    }
+}
+
+/// Sets `changed` whenever the user's items or categories change, or the
+/// listener had to reconnect and may have missed a change. Gives up on a
+/// database error, leaving the periodic check to notice changes.
+async fn watch_changes(mut listener: PgListener, user_id: i32, changed: Arc<AtomicBool>) {
+    let user_id = user_id.to_string();
+    loop {
+        match listener.try_recv().await {
+            Ok(Some(notification)) if notification.payload() != user_id => {}
+            Ok(_) => changed.store(true, Ordering::Relaxed),
+            Err(_) => return,
+        }
+    }
+}
+
+async fn run(
+    terminal: &mut DefaultTerminal,
+    dashboard: &mut Dashboard,
+    changed: &AtomicBool,
+    refresh: Duration,
+) {
+    let mut checked = Instant::now();
+    loop {
+        if let Err(e) = terminal.draw(|frame| dashboard.draw(frame)) {
+            dashboard.status = format!("Błąd: {e}");
+        }
+
+        let key = match event::poll(KEY_POLL).and_then(|ready| ready.then(event::read).transpose())
+        {
+            Ok(Some(Event::Key(key))) if key.kind == KeyEventKind::Press => Some(key.code),
+            _ => None,
+        };
+        let result = match key {
+            Some(KeyCode::Char('q') | KeyCode::Esc) => return,
+            Some(KeyCode::Down) => {
+                dashboard.table.select_next();
+                Ok(())
+            }
+            Some(KeyCode::Up) => {
+                dashboard.table.select_previous();
+                Ok(())
+            }
+            Some(KeyCode::Char('u')) => dashboard.use_selected().await,
+            Some(KeyCode::Char('k')) => dashboard.purchase_selected().await,
+            Some(KeyCode::Char('r')) => dashboard.refresh(true).await.map_err(Into::into),
+            _ if changed.swap(false, Ordering::Relaxed) || checked.elapsed() >= refresh => {
+                checked = Instant::now();
+                dashboard.refresh(false).await.map_err(Into::into)
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            dashboard.status = format!("Błąd: {e}");
+        }
+    }
+}
+
+// More than one thread, so the listener runs while the loop waits for a key
+#[tokio::main(worker_threads = 2)]
+async fn main() -> Result<(), Box<dyn Error>> {
+    dotenv().ok();
+    let cli = Cli::parse();
+    let config = Config::load()?;
+    let pool = db::create_pool(
+        &config.database_url,
+        &db::PoolSettings::from_config(&config),
+    )
+    .await?;
+    let account = db::get_account_by_email(&pool, &cli.email)
+        .await?
+        .ok_or_else(|| format!("no account with email {}", cli.email))?;
+
+    let mut dashboard = Dashboard {
+        pool,
+        user_id: account.id,
+        user_name: account.name,
+        items: Vec::new(),
+        table: TableState::default(),
+        last_change: None,
+        status: String::new(),
+    };
+    let changed = Arc::new(AtomicBool::new(false));
+    let listener = db::listen_for_item_changes(&dashboard.pool).await?;
+    tokio::spawn(watch_changes(listener, account.id, changed.clone()));
+    dashboard.refresh(true).await?;
+
+    let mut terminal = ratatui::init();
+    run(
+        &mut terminal,
+        &mut dashboard,
+        &changed,
+        Duration::from_secs(cli.refresh.max(1)),
+    )
+    .await;
+    ratatui::restore();
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Branding of an instance, set by whoever hosts it: the accent color of the
+//! buttons and a stylesheet of their own, loaded after the built-in one so
+//! its rules win.
+
+use crate::AppState;
+use crate::assets::{self, StaticQuery};
+use crate::config::Config;
+use crate::filters::text_color_for_bg;
+use axum::{
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+/// What pages get as `branding`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Branding {
+    pub accent: Option<Accent>,
+    /// URL of the custom stylesheet with its version, without the base path.
+    pub custom_css_url: Option<String>,
+    #[serde(skip)]
+    custom_css: Option<String>,
+}
+
+/// Button colors derived from the configured accent.
+#[derive(Debug, Clone, Serialize)]
+pub struct Accent {
+    pub color: String,
+    /// Buttons under the pointer: a third darker, like the built-in green.
+    pub hover: String,
+    /// Button text that reads on `color`.
+    pub text: String,
+}
+
+impl Accent {
+    /// `color` is a `#rrggbb` color, as the config checks.
+    fn new(color: &str) -> Accent {
+        let hover: String = (0..3)
+            .map(|i| {
+                let channel = u8::from_str_radix(&color[1 + 2 * i..3 + 2 * i], 16).unwrap_or(0);
+                format!("{:02x}", channel as u32 * 2 / 3)
+            })
+            .collect();
+        Accent {
+            color: color.to_string(),
+            hover: format!("#{hover}"),
+            text: text_color_for_bg(color),
+        }
+    }
+}
+
+impl Branding {
+    pub fn new(accent_color: Option<&str>, custom_css: Option<String>) -> Branding {
+        let custom_css_url = custom_css.as_deref().map(|css| {
+            let mut hasher = DefaultHasher::new();
+            css.hash(&mut hasher);
+            format!("/custom.css?v={:016x}", hasher.finish())
+        });
+        Branding {
+            accent: accent_color.map(Accent::new),
+            custom_css_url,
+            custom_css,
+        }
+    }
+
+    /// Reads the custom stylesheet once, at startup; a restart picks up edits.
+    pub fn from_config(config: &Config) -> Result<Branding, Box<dyn std::error::Error>> {
+        let custom_css = match &config.custom_css_file {
+            Some(path) => Some(
+                fs::read_to_string(path)
+                    .map_err(|e| format!("cannot read custom CSS file {path}: {e}"))?,
+            ),
+            None => None,
+        };
+        Ok(Branding::new(config.accent_color.as_deref(), custom_css))
+    }
+}
+
+/// GET /custom.css
+pub async fn custom_css(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StaticQuery>,
+) -> Response {
+    let (Some(css), Some(url)) = (&state.branding.custom_css, &state.branding.custom_css_url)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let current = url.ends_with(&format!("?v={}", query.v.as_deref().unwrap_or_default()));
+    (
+        [
+            (header::CONTENT_TYPE, "text/css; charset=utf-8"),
+            (header::CACHE_CONTROL, assets::cache_control(current)),
+        ],
+        css.clone(),
+    )
+        .into_response()
+}
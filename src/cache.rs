@@ -0,0 +1,136 @@
+//! Per-user cache of lookups almost every page makes but that rarely change:
+//! the account, its categories and its preferences. Handlers that change
+//! them forget the user's entries; entries also expire after a while, so
+//! changes made by another instance or by hand show up eventually.
+
+use crate::{
+    config::Config,
+    db::{self, DBResult},
+    models::{Account, Category, UserPreferences},
+};
+use moka::future::Cache;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Users whose lookups are kept at once.
+const MAX_USERS: u64 = 10_000;
+
+#[derive(Clone)]
+pub struct LookupCache {
+    /// `None` when caching is turned off.
+    caches: Option<Arc<Caches>>,
+}
+
+struct Caches {
+    users: Cache<i32, Option<Account>>,
+    categories: Cache<i32, Vec<Category>>,
+    preferences: Cache<i32, UserPreferences>,
+}
+
+fn per_user<V: Clone + Send + Sync + 'static>(ttl: Duration) -> Cache<i32, V> {
+    Cache::builder()
+        .max_capacity(MAX_USERS)
+        .time_to_live(ttl)
+        .build()
+}
+
+impl Default for LookupCache {
+    fn default() -> Self {
+        LookupCache::new(Some(Duration::from_secs(300)))
+    }
+}
+
+impl LookupCache {
+    /// Entries live for `ttl`; `None` caches nothing.
+    pub fn new(ttl: Option<Duration>) -> LookupCache {
+        LookupCache {
+            caches: ttl.map(|ttl| {
+                Arc::new(Caches {
+                    users: per_user(ttl),
+                    categories: per_user(ttl),
+                    preferences: per_user(ttl),
+                })
+            }),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> LookupCache {
+        LookupCache::new(
+            (config.cache_ttl_secs > 0).then(|| Duration::from_secs(config.cache_ttl_secs)),
+        )
+    }
+
+    /// `db::get_user_by_id`, cached.
+    pub async fn user(&self, pool: &PgPool, user_id: i32) -> DBResult<Option<Account>> {
+        let Some(caches) = &self.caches else {
+            return db::get_user_by_id(pool, user_id).await;
+        };
+        if let Some(user) = caches.users.get(&user_id).await {
+            return Ok(user);
+        }
+        let user = db::get_user_by_id(pool, user_id).await?;
+        caches.users.insert(user_id, user.clone()).await;
+        Ok(user)
+    }
+
+    /// `db::get_all_categories`, cached.
+    pub async fn categories(&self, pool: &PgPool, user_id: i32) -> DBResult<Vec<Category>> {
+        let Some(caches) = &self.caches else {
+            return db::get_all_categories(pool, user_id).await;
+        };
+        if let Some(categories) = caches.categories.get(&user_id).await {
+            return Ok(categories);
+        }
+        let categories = db::get_all_categories(pool, user_id).await?;
+        caches.categories.insert(user_id, categories.clone()).await;
+        Ok(categories)
+    }
+
+    /// `db::get_user_preferences`, cached.
+    pub async fn preferences(&self, pool: &PgPool, user_id: i32) -> DBResult<UserPreferences> {
+        let Some(caches) = &self.caches else {
+            return db::get_user_preferences(pool, user_id).await;
+        };
+        if let Some(preferences) = caches.preferences.get(&user_id).await {
+            return Ok(preferences);
+        }
+        let preferences = db::get_user_preferences(pool, user_id).await?;
+        caches
+            .preferences
+            .insert(user_id, preferences.clone())
+            .await;
+        Ok(preferences)
+    }
+
+    /// Call after changing the user's categories.
+    pub async fn forget_categories(&self, user_id: i32) {
+        if let Some(caches) = &self.caches {
+            caches.categories.invalidate(&user_id).await;
+        }
+    }
+
+    /// Call after changing the user's preferences.
+    pub async fn forget_preferences(&self, user_id: i32) {
+        if let Some(caches) = &self.caches {
+            caches.preferences.invalidate(&user_id).await;
+        }
+    }
+
+    /// Call after replacing everything of the user, e.g. restoring a backup.
+    pub async fn forget_user(&self, user_id: i32) {
+        if let Some(caches) = &self.caches {
+            caches.users.invalidate(&user_id).await;
+            caches.categories.invalidate(&user_id).await;
+            caches.preferences.invalidate(&user_id).await;
+        }
+    }
+
+    pub fn forget_everyone(&self) {
+        if let Some(caches) = &self.caches {
+            caches.users.invalidate_all();
+            caches.categories.invalidate_all();
+            caches.preferences.invalidate_all();
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Expiry dates as an iCalendar feed, for the family calendar: a share link
+//! with the `calendar` scope serves `/calendar/{token}.ics`, which calendar
+//! apps subscribe to and fetch again on their own. Each batch with an expiry
+//! date is an all-day event on that date. With `?run_out=true`, items used
+//! lately also get an event on the day they will likely run out.
+
+use crate::db::{self, DBResult};
+use crate::models::{ExpiringBatch, ItemUsage};
+use sqlx::PgPool;
+use time::{Date, Duration, OffsetDateTime, macros::format_description};
+
+/// How many days of use the run-out dates are worked out from.
+pub const USAGE_DAYS: i32 = 30;
+/// How often calendar apps are asked to fetch the feed again.
+const REFRESH_INTERVAL: &str = "PT1H";
+/// Keeps the events' UIDs apart from other calendars'.
+const UID_DOMAIN: &str = "household-inventory";
+
+/// The day `quantity` runs out when `used` units go every `days` days, or
+/// `None` if nothing was used.
+pub fn run_out_date(quantity: i32, used: i64, days: i32, today: Date) -> Option<Date> {
+    if used <= 0 || days <= 0 {
+        return None;
+    }
+    let days_left = i64::from(quantity) * i64::from(days) / used;
+    today.checked_add(Duration::days(days_left))
+}
+
+/// The user's feed, as served.
+pub async fn feed(pool: &PgPool, user_id: i32, run_out: bool) -> DBResult<String> {
+    let batches = db::get_dated_batches(pool, user_id).await?;
+    let usage = if run_out {
+        db::get_item_usage(pool, user_id, USAGE_DAYS).await?
+    } else {
+        Vec::new()
+    };
+    Ok(ics(&batches, &usage, OffsetDateTime::now_utc()))
+}
+
+/// The calendar with an event per batch and per item in `usage`.
+pub fn ics(batches: &[ExpiringBatch], usage: &[ItemUsage], now: OffsetDateTime) -> String {
+    let stamp = now
+        .format(format_description!(
+            "[year][month][day]T[hour][minute][second]Z"
+        ))
+        .unwrap_or_default();
+    let mut calendar = Calendar::default();
+    calendar.line("BEGIN:VCALENDAR");
+    calendar.line("VERSION:2.0");
+    calendar.line("PRODID:-//household-inventory//Inwentarz//PL");
+    calendar.line("CALSCALE:GREGORIAN");
+    calendar.line("METHOD:PUBLISH");
+    calendar.line("X-WR-CALNAME:Inwentarz");
+    calendar.line(&format!(
+        "REFRESH-INTERVAL;VALUE=DURATION:{REFRESH_INTERVAL}"
+    ));
+    calendar.line(&format!("X-PUBLISHED-TTL:{REFRESH_INTERVAL}"));
+    for batch in batches {
+        calendar.event(
+            &format!("expiry-{}@{UID_DOMAIN}", batch.id),
+            &stamp,
+            batch.expires_on,
+            &format!(
+                "Termin ważności: {} ({} szt.)",
+                batch.item_name, batch.quantity
+            ),
+        );
+    }
+    let today = now.date();
+    for item in usage {
+        if let Some(date) = run_out_date(item.quantity, item.used, USAGE_DAYS, today) {
+            calendar.event(
+                &format!("run-out-{}@{UID_DOMAIN}", item.item_id),
+                &stamp,
+                date,
+                &format!("Prawdopodobnie skończy się: {}", item.item_name),
+            );
+        }
+    }
+    calendar.line("END:VCALENDAR");
+    calendar.0
+}
+
+#[derive(Default)]
+struct Calendar(String);
+
+impl Calendar {
+    /// An all-day event on `date`.
+    fn event(&mut self, uid: &str, stamp: &str, date: Date, summary: &str) {
+        let day = format_description!("[year][month][day]");
+        let start = date.format(day).unwrap_or_default();
+        let end = date
+            .next_day()
+            .and_then(|end| end.format(day).ok())
+            .unwrap_or_default();
+        self.line("BEGIN:VEVENT");
+        self.line(&format!("UID:{uid}"));
+        self.line(&format!("DTSTAMP:{stamp}"));
+        self.line(&format!("DTSTART;VALUE=DATE:{start}"));
+        self.line(&format!("DTEND;VALUE=DATE:{end}"));
+        self.line(&format!("SUMMARY:{}", escape(summary)));
+        self.line("TRANSP:TRANSPARENT");
+        self.line("END:VEVENT");
+    }
+
+    /// Adds a content line, folded after 75 bytes as RFC 5545 asks, without
+    /// splitting a character.
+    fn line(&mut self, line: &str) {
+        let mut length = 0;
+        for c in line.chars() {
+            if length + c.len_utf8() > 75 {
+                self.0.push_str("\r\n ");
+                // The space that starts the continuation counts too
+                length = 1;
+            }
+            self.0.push(c);
+            length += c.len_utf8();
+        }
+        self.0.push_str("\r\n");
+    }
+}
+
+/// Escapes text values: backslashes, commas, semicolons and line breaks.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ',' => escaped.push_str("\\,"),
+            ';' => escaped.push_str("\\;"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
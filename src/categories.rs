@@ -0,0 +1,117 @@
+use crate::models::{Category, CategoryTreeNode, CategoryWithCount, CategoryWithItems};
+use std::collections::HashMap;
+
+/// Orders grouped items for the index page: each top-level category is
+/// followed directly by its subcategories, both keeping their input order
+/// (the user's manual order). Also fills in `total_items`, so a parent's
+/// header can show the rollup of its children.
+pub fn nest_for_display(groups: Vec<CategoryWithItems>) -> Vec<CategoryWithItems> {
+    let ids: Vec<i32> = groups.iter().map(|g| g.id).collect();
+    let (roots, children): (Vec<_>, Vec<_>) = groups.into_iter().partition(|g| {
+        g.parent_id
+            .is_none_or(|parent_id| !ids.contains(&parent_id))
+    });
+
+    let mut children_by_parent: HashMap<i32, Vec<CategoryWithItems>> = HashMap::new();
+    for mut child in children {
+        child.total_items = child.items.len();
+        children_by_parent
+            .entry(child.parent_id.unwrap_or_default())
+            .or_default()
+            .push(child);
+    }
+
+    let mut ordered = Vec::new();
+    for mut root in roots {
+        let children = children_by_parent.remove(&root.id).unwrap_or_default();
+        root.total_items = root.items.len() + children.iter().map(|c| c.total_items).sum::<usize>();
+        ordered.push(root);
+        ordered.extend(children);
+    }
+    ordered
+}
+
+/// Builds the two-level category tree returned by `GET /api/categories/tree`.
+pub fn build_tree(categories: Vec<CategoryWithCount>) -> Vec<CategoryTreeNode> {
+    let ids: Vec<i32> = categories.iter().map(|c| c.id).collect();
+    let (roots, children): (Vec<_>, Vec<_>) = categories.into_iter().partition(|c| {
+        c.parent_id
+            .is_none_or(|parent_id| !ids.contains(&parent_id))
+    });
+
+    let mut children_by_parent: HashMap<i32, Vec<CategoryTreeNode>> = HashMap::new();
+    for child in children {
+        children_by_parent
+            .entry(child.parent_id.unwrap_or_default())
+            .or_default()
+            .push(CategoryTreeNode {
+                id: child.id,
+                name: child.name,
+                color: child.color,
+                icon: child.icon,
+                item_count: child.item_count,
+                total_item_count: child.item_count,
+                children: vec![],
+            });
+    }
+
+    roots
+        .into_iter()
+        .map(|root| {
+            let children = children_by_parent.remove(&root.id).unwrap_or_default();
+            CategoryTreeNode {
+                id: root.id,
+                name: root.name,
+                color: root.color,
+                icon: root.icon,
+                item_count: root.item_count,
+                total_item_count: root.item_count
+                    + children.iter().map(|c| c.total_item_count).sum::<i64>(),
+                children,
+            }
+        })
+        .collect()
+}
+
+/// Checks that `parent_id` is a valid parent for `category_id` (`None` for a
+/// new category). Nesting is limited to two levels: the parent must be a
+/// top-level category, and a category with subcategories can't be nested.
+pub fn validate_parent(
+    categories: &[Category],
+    category_id: Option<i32>,
+    parent_id: Option<i32>,
+) -> Result<(), &'static str> {
+    let Some(parent_id) = parent_id else {
+        return Ok(());
+    };
+    if Some(parent_id) == category_id {
+        return Err("A category cannot be its own parent");
+    }
+    let parent = categories
+        .iter()
+        .find(|c| c.id == parent_id)
+        .ok_or("Parent category not found")?;
+    if parent.parent_id.is_some() {
+        return Err("Subcategories cannot have subcategories");
+    }
+    if category_id.is_some_and(|id| categories.iter().any(|c| c.parent_id == Some(id))) {
+        return Err("A category with subcategories cannot be nested");
+    }
+    Ok(())
+}
+
+/// Longest accepted category icon, in characters. Enough for emoji built
+/// from several code points (flags, skin tones).
+pub const MAX_ICON_LEN: usize = 8;
+
+pub fn validate_icon(icon: Option<&str>) -> Result<(), &'static str> {
+    match icon {
+        Some(icon) if icon.chars().count() > MAX_ICON_LEN => Err("Category icon is too long"),
+        _ => Ok(()),
+    }
+}
+
+/// Colors are stored as `#rrggbb`, the format of `<input type="color">`.
+pub fn is_hex_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
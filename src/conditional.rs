@@ -0,0 +1,117 @@
+//! Conditional GETs: clients that send back the validator of their copy get
+//! `304 Not Modified` instead of the same body again.
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::LazyLock;
+use time::macros::format_description;
+use time::{
+    Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset, format_description::FormatItem,
+};
+
+/// Responses depend on the session, so shared caches must not keep them and
+/// browsers must check back before reusing them. `Vary: Cookie` keeps a copy
+/// cached for one login from being revalidated by another.
+const CACHE_HEADERS: [(HeaderName, HeaderValue); 2] = [
+    (
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-cache"),
+    ),
+    (header::VARY, HeaderValue::from_static("cookie")),
+];
+
+/// `Sun, 06 Nov 1994 08:49:37 GMT`, the only date format HTTP sends.
+const HTTP_DATE: &[FormatItem<'static>] = format_description!(
+    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+
+/// Pages rendered by an earlier run may come from older templates, so nothing
+/// counts as unmodified since before this process served its first page.
+static STARTED_AT: LazyLock<OffsetDateTime> = LazyLock::new(OffsetDateTime::now_utc);
+
+/// A weak ETag for whatever identifies the state of a resource, e.g. its
+/// version or the newest change time and count of a list.
+pub fn weak_etag(state: impl Hash) -> HeaderValue {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    HeaderValue::from_str(&format!("W/\"{:016x}\"", hasher.finish()))
+        .expect("a hex string is a valid header value")
+}
+
+/// Whether `If-None-Match` lists `etag` (or is `*`). ETags are compared
+/// weakly, as RFC 9110 requires for this header.
+pub fn is_fresh(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let Some(ours) = etag.to_str().ok().map(opaque) else {
+        return false;
+    };
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == ours)
+}
+
+/// `304 Not Modified` for a client whose copy is current.
+pub fn not_modified(etag: HeaderValue) -> Response {
+    (
+        StatusCode::NOT_MODIFIED,
+        CACHE_HEADERS,
+        [(header::ETAG, etag)],
+    )
+        .into_response()
+}
+
+/// `body` with its ETag, for the client to send back next time.
+pub fn with_etag(etag: HeaderValue, body: impl IntoResponse) -> Response {
+    (CACHE_HEADERS, [(header::ETAG, etag)], body).into_response()
+}
+
+/// The `Last-Modified` of a page built from data last changed at
+/// `last_change`, never earlier than this process's first page. `None` when
+/// the change is under a second old: HTTP dates have whole seconds, so
+/// another change in the same second would go unnoticed.
+pub fn last_modified(last_change: OffsetDateTime) -> Option<OffsetDateTime> {
+    let now = OffsetDateTime::now_utc();
+    let last_modified = last_change.max(*STARTED_AT);
+    (now - last_modified >= Duration::SECOND).then_some(last_modified)
+}
+
+/// Whether `If-Modified-Since` is at or after `last_modified`.
+pub fn is_unmodified_since(headers: &HeaderMap, last_modified: OffsetDateTime) -> bool {
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| PrimitiveDateTime::parse(value, HTTP_DATE).ok())
+        .is_some_and(|since| since.assume_utc() >= last_modified.replace_nanosecond(0).unwrap())
+}
+
+/// `304 Not Modified` for a page that hasn't changed since the client's copy.
+pub fn not_modified_since(last_modified: OffsetDateTime) -> Response {
+    let header = [(header::LAST_MODIFIED, http_date(last_modified))];
+    (StatusCode::NOT_MODIFIED, CACHE_HEADERS, header).into_response()
+}
+
+/// `body` with its `Last-Modified`, if there is one.
+pub fn with_last_modified(
+    last_modified: Option<OffsetDateTime>,
+    body: impl IntoResponse,
+) -> Response {
+    match last_modified {
+        Some(last_modified) => {
+            let header = [(header::LAST_MODIFIED, http_date(last_modified))];
+            (CACHE_HEADERS, header, body).into_response()
+        }
+        None => (CACHE_HEADERS, body).into_response(),
+    }
+}
+
+fn http_date(time: OffsetDateTime) -> HeaderValue {
+    let formatted = time
+        .to_offset(UtcOffset::UTC)
+        .format(HTTP_DATE)
+        .expect("every date can be formatted");
+    HeaderValue::from_str(&formatted).expect("an HTTP date is a valid header value")
+}
@@ -0,0 +1,32 @@
+//! Consumption rules: staples that are used up at a steady pace, such as
+//! dishwasher tabs or pet food, take their units on their own, e.g. one
+//! every day or two a week. A background job takes what is due as ordinary
+//! `used` adjustments, so the history, the restock alerts and the shopping
+//! list follow as if someone had clicked "use".
+
+use crate::db;
+use crate::scheduler::{self, JobResult};
+use sqlx::PgPool;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// How often the job looks for rules that are due.
+pub const CHECK_PERIOD: Duration = Duration::from_secs(900);
+
+/// Takes the units of every rule that is due at `now`.
+pub async fn apply_due(pool: &PgPool, now: OffsetDateTime) -> JobResult {
+    for (user_id, item_id, quantity) in db::take_due_consumption_rules(pool, now).await? {
+        // The rule has moved on already; a failure only skips this run
+        if let Err(e) = db::use_item(pool, user_id, user_id, item_id, quantity).await {
+            tracing::error!(user_id, item_id, "consumption rule failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+pub fn spawn_runner(pool: PgPool) {
+    scheduler::spawn_exclusive("consumption rules", CHECK_PERIOD, pool.clone(), move || {
+        let pool = pool.clone();
+        async move { apply_due(&pool, OffsetDateTime::now_utc()).await }
+    });
+}
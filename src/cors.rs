@@ -0,0 +1,61 @@
+//! CORS for the JSON API, so a web app hosted elsewhere or a browser extension
+//! can call it. Nothing is allowed unless origins are configured.
+
+use crate::config::Config;
+use axum::http::{HeaderName, HeaderValue, Method, header};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// How long browsers may cache a preflight answer.
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Default)]
+pub struct CorsSettings {
+    /// `*` allows any origin.
+    pub allowed_origins: Vec<String>,
+    pub allow_credentials: bool,
+    /// Besides `Content-Type`, which JSON requests always need.
+    pub allowed_headers: Vec<String>,
+}
+
+impl CorsSettings {
+    pub fn from_config(config: &Config) -> CorsSettings {
+        CorsSettings {
+            allowed_origins: config.cors_allowed_origins.clone(),
+            allow_credentials: config.cors_allow_credentials,
+            allowed_headers: config.cors_allowed_headers.clone(),
+        }
+    }
+
+    /// The layer for the API routes, or `None` when no origin is allowed.
+    /// Entries that aren't valid header values are skipped; the config
+    /// rejects them at startup.
+    pub fn layer(&self) -> Option<CorsLayer> {
+        if self.allowed_origins.is_empty() {
+            return None;
+        }
+        let origins = if self.allowed_origins.iter().any(|origin| origin == "*") {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(
+                self.allowed_origins
+                    .iter()
+                    .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+            )
+        };
+        let headers = self
+            .allowed_headers
+            .iter()
+            .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+            .chain([header::CONTENT_TYPE]);
+        Some(
+            CorsLayer::new()
+                .allow_origin(origins)
+                .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+                .allow_headers(headers.collect::<Vec<_>>())
+                .allow_credentials(self.allow_credentials)
+                .expose_headers([HeaderName::from_static("x-request-id")])
+                .max_age(PREFLIGHT_MAX_AGE),
+        )
+    }
+}
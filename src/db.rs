@@ -1,23 +1,248 @@
 use crate::{
-    handlers::web_handlers::get_text_color_for_bg,
+    backup::{
+        self, Backup, BackupAttributeField, BackupBatch, BackupCategory, BackupConsumptionRule,
+        BackupEvent, BackupIngredient, BackupItem, BackupMealPlan, BackupRecipe, BackupStore,
+        RestoreSummary,
+    },
+    cache::LookupCache,
+    categories,
+    config::Config,
+    errors::AppError,
+    export::{ExportError, JsonWriter},
+    grocy::{self, GrocyImportSummary, ImportedItem},
     models::{
-        Account, Category, CategoryWithItems, CreateCategoryPayload, CreateItemPayload,
-        GroupedItems, Item, PurchaseItemPayload, UpdateItemPayload,
+        Account, AdjustItemPayload, AdjustmentReason, ApiToken, AttributeField, AttributeKind,
+        Attributes, CatalogProduct, Category, CategoryDeletePolicy, CategoryStats,
+        CategoryWithCount, CategoryWithItems, ChannelDelivery, ChannelKind, CheckoutPayload,
+        ConsumptionPoint, ConsumptionRule, ConsumptionRulePayload, CreateCategoryPayload,
+        CreateItemPayload, CreateMealPlanPayload, CreateNotificationChannelPayload,
+        CreateRecipePayload, DashboardData, DeleteCategoryOutcome, DiscardItemPayload,
+        ExpiringBatch, GroupedItems, Household, HouseholdInvitation, HouseholdMember,
+        HouseholdRole, Item, ItemBatch, ItemEvent, ItemFilter, ItemLayout, ItemSort, ItemUsage,
+        ItemsFingerprint, Language, MealPlanEntry, MergeCategoryOutcome, MergeItemOutcome, Month,
+        MonthlySummary, NotificationChannel, NotificationSettings, Price, Purchase,
+        PurchaseItemPayload, Receipt, Recipe, RecipeIngredient, RecipeWithIngredients, SessionInfo,
+        ShareLink, ShareScope, ShoppingListEntry, StatsOverview, Stocktake, StocktakeCount,
+        StocktakeEntry, StocktakeWithEntries, Store, StorePrice, SummaryLine, SummaryRecipient,
+        Theme, UpdateItemPayload, UpdatePreferencesPayload, UrgentRestock, UserPreferences,
+        UserSession, WasteByReason, WasteMonth, WasteReport, WastedItem,
     },
+    pagination::Cursor,
+    recipes::{self, CookOutcome},
+    scheduler,
+    seed::SeedCategory,
+    sync::{SyncChanges, SyncDeleted},
+    validation::Validate,
+};
+use futures_util::TryStreamExt;
+use futures_util::stream::BoxStream;
+use serde::Serialize;
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::postgres::{PgConnectOptions, PgListener, PgPoolOptions};
+use sqlx::{
+    Error as SqlxError, PgConnection, PgExecutor, PgPool, Postgres, Transaction, prelude::FromRow,
+    types::Json,
 };
-use sqlx::{Error as SqlxError, PgPool, postgres::PgPoolOptions, prelude::FromRow};
-use std::{collections::HashMap, env};
+use std::collections::HashMap;
+use std::time::Duration;
+use time::Date;
+use time::format_description::well_known::Rfc3339;
 
 pub type DBResult<T, E = SqlxError> = Result<T, E>;
 
-pub async fn create_pool() -> Result<PgPool, SqlxError> {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+/// Sizing and timeouts of the connection pool.
+#[derive(Debug, Clone)]
+pub struct PoolSettings {
+    /// Connections kept open even when idle.
+    pub min_connections: u32,
+    pub max_connections: u32,
+    /// How long a request waits for a free connection before failing.
+    pub acquire_timeout: Duration,
+    /// Idle connections above the minimum are closed after this; `None`
+    /// keeps them.
+    pub idle_timeout: Option<Duration>,
+    /// Queries running longer are cancelled by PostgreSQL; `None` lets them run.
+    pub statement_timeout: Option<Duration>,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        PoolSettings {
+            min_connections: 0,
+            max_connections: 5,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(600)),
+            statement_timeout: None,
+        }
+    }
+}
+
+impl PoolSettings {
+    pub fn from_config(config: &Config) -> PoolSettings {
+        PoolSettings {
+            min_connections: config.db_min_connections,
+            max_connections: config.db_max_connections,
+            acquire_timeout: Duration::from_secs(config.db_acquire_timeout_secs),
+            idle_timeout: config.db_idle_timeout_secs.map(Duration::from_secs),
+            statement_timeout: config.db_statement_timeout_ms.map(Duration::from_millis),
+        }
+    }
+}
+
+pub async fn create_pool(database_url: &str, settings: &PoolSettings) -> Result<PgPool, SqlxError> {
+    let mut options: PgConnectOptions = database_url.parse()?;
+    if let Some(timeout) = settings.statement_timeout {
+        let millis = timeout.as_millis().to_string();
+        options = options.options([("statement_timeout", millis.as_str())]);
+    }
     PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
+        .min_connections(settings.min_connections)
+        .max_connections(settings.max_connections)
+        .acquire_timeout(settings.acquire_timeout)
+        .idle_timeout(settings.idle_timeout)
+        .connect_with(options)
+        .await
+}
+
+/// Claims this period's run of a job shared by all instances: locks the
+/// job's row unless another instance holds it, and marks it as run if it
+/// last ran at least `period` ago. Commit the transaction once the job has
+/// succeeded; dropping it leaves the run to the next tick of any instance.
+pub async fn claim_job(
+    pool: &PgPool,
+    name: &str,
+    period: Duration,
+) -> DBResult<Option<Transaction<'static, Postgres>>> {
+    // Checking first: inserting would wait for an instance running the job
+    sqlx::query!(
+        "INSERT INTO scheduled_jobs (name)
+         SELECT $1::VARCHAR WHERE NOT EXISTS (SELECT 1 FROM scheduled_jobs WHERE name = $1)
+         ON CONFLICT (name) DO NOTHING",
+        name
+    )
+    .execute(pool)
+    .await?;
+    let mut tx = pool.begin().await?;
+    let claimed = sqlx::query!(
+        "UPDATE scheduled_jobs SET last_run_at = NOW()
+         WHERE name = (
+             SELECT name FROM scheduled_jobs
+             WHERE name = $1
+               AND (last_run_at IS NULL OR last_run_at <= NOW() - $2 * INTERVAL '1 second')
+             FOR UPDATE SKIP LOCKED
+         )",
+        name,
+        period.as_secs_f64()
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    Ok((claimed == 1).then_some(tx))
+}
+
+/// Whether maintenance mode is switched on in the database.
+pub async fn get_maintenance_mode(pool: &PgPool) -> DBResult<bool> {
+    sqlx::query_scalar!("SELECT enabled FROM maintenance_mode")
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn set_maintenance_mode(pool: &PgPool, enabled: bool) -> DBResult<()> {
+    sqlx::query!(
+        "UPDATE maintenance_mode SET enabled = $1, changed_at = NOW()",
+        enabled
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Logs how busy the pool is every five minutes, to help size it: requests
+/// wait for a connection when all `max` are in use.
+pub fn spawn_pool_metrics(pool: PgPool) {
+    scheduler::spawn_every("pool metrics", Duration::from_secs(300), move || {
+        let pool = pool.clone();
+        async move {
+            let size = pool.size();
+            let idle = pool.num_idle() as u32;
+            tracing::info!(
+                open = size,
+                in_use = size.saturating_sub(idle),
+                idle,
+                max = pool.options().get_max_connections(),
+                "database pool"
+            );
+            Ok(())
+        }
+    });
+}
+
+/// The plan PostgreSQL picks for `sql`, one line per node as `EXPLAIN` prints
+/// it. `analyze` also runs the query and adds the actual times and row
+/// counts, so only analyze writes inside a transaction that is rolled back.
+#[cfg(feature = "explain")]
+pub async fn explain(
+    executor: impl PgExecutor<'_>,
+    sql: &str,
+    arguments: sqlx::postgres::PgArguments,
+    analyze: bool,
+) -> DBResult<Vec<String>> {
+    let options = if analyze { "ANALYZE, BUFFERS" } else { "COSTS" };
+    sqlx::query_scalar_with(&format!("EXPLAIN ({options}) {sql}"), arguments)
+        .fetch_all(executor)
         .await
 }
 
+/// The migrations from `migrations/`, embedded in the binary.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Applies pending migrations. Already applied ones are skipped, so this is
+/// safe to run on every start.
+pub async fn run_migrations(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// A round trip to the database.
+pub async fn ping(pool: &PgPool) -> DBResult<()> {
+    sqlx::query!("SELECT 1 AS one").fetch_one(pool).await?;
+    Ok(())
+}
+
+/// Versions of the embedded migrations the database hasn't applied yet.
+pub async fn pending_migrations(pool: &PgPool) -> DBResult<Vec<i64>> {
+    // Not a checked query: the table only exists once sqlx created it
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await?;
+    Ok(MIGRATOR
+        .iter()
+        .map(|m| m.version)
+        .filter(|version| !applied.contains(version))
+        .collect())
+}
+
+/// Records the migrations up to `version` as applied without running them,
+/// for databases whose schema was created by hand from `migrations/`.
+pub async fn baseline_migrations(pool: &PgPool, version: i64) -> Result<(), MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    for migration in MIGRATOR.iter().filter(|m| m.version <= version) {
+        // Not a checked query: the table only exists once sqlx created it
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+             VALUES ($1, $2, TRUE, $3, 0)
+             ON CONFLICT (version) DO NOTHING",
+        )
+        .bind(migration.version)
+        .bind(&*migration.description)
+        .bind(&*migration.checksum)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
 // Helper struct for SQLx mapping when category might be NULL
 #[derive(FromRow, Debug)]
 struct FlatItemRow {
@@ -25,19 +250,29 @@ struct FlatItemRow {
     name: String,
     quantity: i32,
     restock_threshold: i32,
+    restock_to: Option<i32>,
+    unit: Option<String>,
+    location: Option<String>,
+    barcode: Option<String>,
+    notes: Option<String>,
+    preferred_store_id: Option<i32>,
+    quantity_step: i32,
+    attributes: Json<Attributes>,
+    version: i32,
     created_at: time::OffsetDateTime,
     updated_at: time::OffsetDateTime,
     category_id: Option<i32>,
     category_name: Option<String>,
     category_color: Option<String>,
+    category_parent_id: Option<i32>,
+    category_icon: Option<String>,
 }
 
 /// Fetches all items for a user and groups them by category.
 /// Uncategorized items are returned in a separate list.
 pub async fn get_items_grouped_by_category(pool: &PgPool, user_id: i32) -> DBResult<GroupedItems> {
-    // The query fetches all items, joining category data if it exists.
-    // IMPORTANT: We ORDER BY category_name to ensure items of the same
-    // category are processed sequentially, which is efficient for grouping.
+    // The query fetches all items, joining category data if it exists,
+    // in the user's manual order (alphabetical within equal positions).
     let rows = sqlx::query_as!(
         FlatItemRow,
         r#"
@@ -46,22 +281,35 @@ pub async fn get_items_grouped_by_category(pool: &PgPool, user_id: i32) -> DBRes
             i.name,
             i.quantity,
             i.restock_threshold,
+            i.restock_to,
+            i.unit,
+            i.location,
+            i.barcode,
+            i.notes,
+            i.preferred_store_id,
+            i.quantity_step,
+            i.attributes AS "attributes: Json<Attributes>",
+            i.version,
             i.created_at,
             i.updated_at,
             c.id AS "category_id: Option<i32>",
             c.name AS "category_name: Option<String>",
-            c.color AS "category_color: Option<String>"
+            c.color AS "category_color: Option<String>",
+            c.parent_id AS category_parent_id,
+            c.icon AS category_icon
         FROM items i
         LEFT JOIN categories c ON i.category_id = c.id AND i.user_id = c.user_id
         WHERE i.user_id = $1
-        ORDER BY c.name, i.name
+        ORDER BY c.sort_order, c.name, i.sort_order, i.name
         "#,
         user_id
     )
     .fetch_all(pool)
     .await?;
 
-    let mut categorized_map: HashMap<i32, CategoryWithItems> = HashMap::new();
+    // Groups in first-seen order, which follows the query's ORDER BY
+    let mut categorized_items: Vec<CategoryWithItems> = Vec::new();
+    let mut group_index: HashMap<i32, usize> = HashMap::new();
     let mut uncategorized_items: Vec<Item> = Vec::new();
 
     for row in rows {
@@ -73,6 +321,8 @@ pub async fn get_items_grouped_by_category(pool: &PgPool, user_id: i32) -> DBRes
                 id,
                 name: name.clone(),
                 color: color.clone(),
+                parent_id: row.category_parent_id,
+                icon: row.category_icon.clone(),
             })
         } else {
             None
@@ -84,32 +334,47 @@ pub async fn get_items_grouped_by_category(pool: &PgPool, user_id: i32) -> DBRes
             name: row.name,
             quantity: row.quantity,
             restock_threshold: row.restock_threshold,
+            restock_to: row.restock_to,
+            suggested_purchase: Item::suggested_purchase(
+                row.quantity,
+                row.restock_threshold,
+                row.restock_to,
+                row.quantity_step,
+            ),
+            unit: row.unit,
+            location: row.location,
+            barcode: row.barcode,
+            notes: row.notes,
+            preferred_store_id: row.preferred_store_id,
+            quantity_step: row.quantity_step,
+            attributes: row.attributes.0,
             category: category_data,
+            version: row.version,
             created_at: row.created_at,
             updated_at: row.updated_at,
         };
 
         // Add the item to the correct group
         if let Some(cat_id) = row.category_id {
-            // Use the HashMap's entry API for efficient insertion/update
-            let text_color = get_text_color_for_bg(&row.category_color.clone().unwrap());
-            let category_group = categorized_map.entry(cat_id).or_insert_with(|| {
-                CategoryWithItems {
+            let index = *group_index.entry(cat_id).or_insert_with(|| {
+                categorized_items.push(CategoryWithItems {
                     id: cat_id,
                     name: row.category_name.unwrap(), // Safe due to check
                     color: row.category_color.unwrap(), // Safe due to check
-                    text_color,
+                    parent_id: row.category_parent_id,
+                    icon: row.category_icon,
                     items: Vec::new(),
-                }
+                    total_items: 0,
+                });
+                categorized_items.len() - 1
             });
-            category_group.items.push(item);
+            categorized_items[index].items.push(item);
         } else {
             uncategorized_items.push(item);
         }
     }
 
-    // Convert the map's values into the final vector
-    let categorized_items = categorized_map.into_values().collect();
+    let categorized_items = categories::nest_for_display(categorized_items);
 
     Ok(GroupedItems {
         categorized: categorized_items,
@@ -122,7 +387,13 @@ impl From<FlatItemRow> for Item {
         let category = if let (Some(id), Some(name), Some(color)) =
             (row.category_id, row.category_name, row.category_color)
         {
-            Some(Category { id, name, color })
+            Some(Category {
+                id,
+                name,
+                color,
+                parent_id: row.category_parent_id,
+                icon: row.category_icon,
+            })
         } else {
             None
         };
@@ -131,14 +402,34 @@ impl From<FlatItemRow> for Item {
             name: row.name,
             quantity: row.quantity,
             restock_threshold: row.restock_threshold,
+            restock_to: row.restock_to,
+            suggested_purchase: Item::suggested_purchase(
+                row.quantity,
+                row.restock_threshold,
+                row.restock_to,
+                row.quantity_step,
+            ),
+            unit: row.unit,
+            location: row.location,
+            barcode: row.barcode,
+            notes: row.notes,
+            preferred_store_id: row.preferred_store_id,
+            quantity_step: row.quantity_step,
+            attributes: row.attributes.0,
             category,
+            version: row.version,
             created_at: row.created_at,
             updated_at: row.updated_at,
         }
     }
 }
 
-pub async fn get_all_items(pool: &PgPool, user_id: i32) -> DBResult<Vec<Item>> {
+pub async fn get_all_items(
+    pool: &PgPool,
+    user_id: i32,
+    sort: ItemSort,
+    filter: ItemFilter,
+) -> DBResult<Vec<Item>> {
     let rows = sqlx::query_as!(
         FlatItemRow,
         r#"
@@ -147,23 +438,118 @@ pub async fn get_all_items(pool: &PgPool, user_id: i32) -> DBResult<Vec<Item>> {
             i.name,
             i.quantity,
             i.restock_threshold,
+            i.restock_to,
+            i.unit,
+            i.location,
+            i.barcode,
+            i.notes,
+            i.preferred_store_id,
+            i.quantity_step,
+            i.attributes AS "attributes: Json<Attributes>",
+            i.version,
             i.created_at,
             i.updated_at,
             c.id AS "category_id: Option<i32>",
             c.name AS "category_name: Option<String>",
-            c.color AS "category_color: Option<String>"
+            c.color AS "category_color: Option<String>",
+            c.parent_id AS category_parent_id,
+            c.icon AS category_icon
         FROM items i
         LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
         WHERE i.user_id = $1
-        ORDER BY i.name
+          AND CASE $3
+                WHEN 'low' THEN i.quantity < i.restock_threshold
+                WHEN 'out' THEN i.quantity = 0
+                WHEN 'expiring' THEN EXISTS (
+                    SELECT 1 FROM item_batches b
+                    WHERE b.item_id = i.id AND b.expires_on <= CURRENT_DATE + $4::INTEGER
+                )
+                ELSE TRUE
+              END
+        ORDER BY
+            CASE WHEN $2 = 'name' THEN LOWER(i.name) END,
+            CASE WHEN $2 = 'quantity' THEN i.quantity END,
+            CASE WHEN $2 = 'updated' THEN i.updated_at END DESC,
+            CASE WHEN $2 = 'stock' THEN i.quantity::REAL / NULLIF(i.restock_threshold, 0) END NULLS LAST,
+            i.sort_order, i.name
         "#,
-        user_id
+        user_id,
+        sort.as_str(),
+        filter.as_str(),
+        EXPIRY_WARNING_DAYS
     )
     .fetch_all(pool)
     .await?;
     Ok(rows.into_iter().map(Item::from).collect())
 }
 
+pub async fn get_items_fingerprint(pool: &PgPool, user_id: i32) -> DBResult<ItemsFingerprint> {
+    sqlx::query_as!(
+        ItemsFingerprint,
+        r#"SELECT
+               (SELECT COUNT(*) FROM items WHERE user_id = $1) AS "items!",
+               (SELECT MAX(changed_at) FROM items WHERE user_id = $1) AS items_changed_at,
+               (SELECT COUNT(*) FROM categories WHERE user_id = $1) AS "categories!",
+               (SELECT MAX(changed_at) FROM categories WHERE user_id = $1) AS categories_changed_at"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// When anything on the item list page last changed: items, categories,
+/// deletions, the account or its preferences.
+pub async fn get_last_change(
+    pool: &PgPool,
+    user_id: i32,
+) -> DBResult<Option<time::OffsetDateTime>> {
+    sqlx::query_scalar!(
+        "SELECT GREATEST(
+             (SELECT MAX(changed_at) FROM items WHERE user_id = $1),
+             (SELECT MAX(changed_at) FROM categories WHERE user_id = $1),
+             (SELECT MAX(deleted_at) FROM deletions WHERE user_id = $1),
+             (SELECT updated_at FROM users WHERE id = $1),
+             (SELECT updated_at FROM user_preferences WHERE user_id = $1)
+         )",
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Channel on which PostgreSQL announces changes to items and categories,
+/// with the id of their user as the payload.
+pub const ITEM_CHANGES_CHANNEL: &str = "item_changes";
+
+/// A connection of its own that hears of every committed change to items or
+/// categories, of any user, on `ITEM_CHANGES_CHANNEL`. Notifications sent
+/// while it reconnects are lost.
+pub async fn listen_for_item_changes(pool: &PgPool) -> DBResult<PgListener> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(ITEM_CHANGES_CHANNEL).await?;
+    Ok(listener)
+}
+
+/// Version of an item and the last change of its category, which together
+/// cover everything `get_item_by_id` returns.
+pub async fn get_item_fingerprint(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: i32,
+) -> DBResult<Option<(i32, Option<time::OffsetDateTime>)>> {
+    let row = sqlx::query!(
+        r#"SELECT i.version, c.changed_at AS "category_changed_at?"
+           FROM items i
+           LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
+           WHERE i.user_id = $1 AND i.id = $2"#,
+        user_id,
+        item_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| (row.version, row.category_changed_at)))
+}
+
 pub async fn get_item_by_id(pool: &PgPool, user_id: i32, item_id: i32) -> DBResult<Option<Item>> {
     let row = sqlx::query_as!(
         FlatItemRow,
@@ -173,11 +559,22 @@ pub async fn get_item_by_id(pool: &PgPool, user_id: i32, item_id: i32) -> DBResu
             i.name,
             i.quantity,
             i.restock_threshold,
+            i.restock_to,
+            i.unit,
+            i.location,
+            i.barcode,
+            i.notes,
+            i.preferred_store_id,
+            i.quantity_step,
+            i.attributes AS "attributes: Json<Attributes>",
+            i.version,
             i.created_at,
             i.updated_at,
             c.id AS "category_id: Option<i32>",
             c.name AS "category_name: Option<String>",
-            c.color AS "category_color: Option<String>"
+            c.color AS "category_color: Option<String>",
+            c.parent_id AS category_parent_id,
+            c.icon AS category_icon
         FROM items i
         LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
         WHERE i.user_id = $1 AND i.id = $2
@@ -190,6 +587,7 @@ pub async fn get_item_by_id(pool: &PgPool, user_id: i32, item_id: i32) -> DBResu
     Ok(row.map(Item::from))
 }
 
+/// One statement: the item, its first batch and the category it returns with.
 pub async fn create_item(
     pool: &PgPool,
     user_id: i32,
@@ -197,150 +595,477 @@ pub async fn create_item(
 ) -> DBResult<Item> {
     let threshold = payload.restock_threshold.unwrap_or(1);
 
-    // Insert the item
-    let inserted_item_id: i32 = sqlx::query_scalar!(
-        "INSERT INTO items (user_id, name, quantity, restock_threshold, category_id)
-         VALUES ($1, $2, $3, $4, $5)
-         RETURNING id",
+    let row = sqlx::query_as!(
+        FlatItemRow,
+        r#"
+        WITH inserted AS (
+            INSERT INTO items (user_id, name, quantity, restock_threshold, restock_to, unit, location,
+                               category_id, preferred_store_id, quantity_step, barcode, attributes,
+                               notes, sort_order)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $10, $11, $12, $13, $14,
+                    (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM items WHERE user_id = $1))
+            RETURNING *
+        ),
+        -- The initial stock is the first batch
+        first_batch AS (
+            INSERT INTO item_batches (item_id, quantity, expires_on)
+            SELECT id, quantity, $9 FROM inserted WHERE quantity > 0
+        )
+        SELECT
+            i.id AS "id!",
+            i.name AS "name!",
+            i.quantity AS "quantity!",
+            i.restock_threshold AS "restock_threshold!",
+            i.restock_to,
+            i.unit,
+            i.location,
+            i.barcode,
+            i.notes,
+            i.preferred_store_id,
+            i.quantity_step AS "quantity_step!",
+            i.attributes AS "attributes!: Json<Attributes>",
+            i.version AS "version!",
+            i.created_at AS "created_at!",
+            i.updated_at AS "updated_at!",
+            c.id AS "category_id?",
+            c.name AS "category_name?",
+            c.color AS "category_color?",
+            c.parent_id AS "category_parent_id?",
+            c.icon AS "category_icon?"
+        FROM inserted i
+        LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
+        "#,
         user_id,
         payload.name,
         payload.quantity,
         threshold,
-        payload.category_id // This can be Option<i32>
+        payload.restock_to,
+        payload.unit,
+        payload.location,
+        payload.category_id,
+        payload.expires_on,
+        payload.preferred_store_id,
+        payload.quantity_step.unwrap_or(1),
+        payload.barcode,
+        Json(payload.attributes.unwrap_or_default()) as _,
+        payload.notes
     )
     .fetch_one(pool)
     .await?;
-
-    // Fetch the newly created item with its category details
-    // This ensures the returned Item struct is fully populated.
-    get_item_by_id(pool, user_id, inserted_item_id)
-        .await
-        .and_then(|opt_item| opt_item.ok_or_else(|| SqlxError::RowNotFound)) // Convert Option<Item> to Result<Item, Error>
+    Ok(Item::from(row))
 }
 
-pub async fn update_item(
-    pool: &PgPool,
-    user_id: i32,
-    item_id: i32,
-    payload: UpdateItemPayload,
-) -> DBResult<Option<Item>> {
-    // Fetch current item to know its existing values
-    let current_item_row = sqlx::query!(
-        "SELECT name, quantity, restock_threshold, category_id FROM items WHERE user_id = $1 AND id = $2",
+/// Creates a copy of an item to start a near-identical product from: its
+/// details without the stock, named like `Mleko (kopia)` (or `(kopia 2)` and
+/// so on when that is taken). It shares the original's place in the manual
+/// order, so it's listed right after it.
+pub async fn clone_item(pool: &PgPool, user_id: i32, item_id: i32) -> DBResult<Option<Item>> {
+    let mut tx = pool.begin().await?;
+    let original = sqlx::query_scalar!(
+        "SELECT name FROM items WHERE user_id = $1 AND id = $2",
         user_id,
         item_id
     )
-    .fetch_optional(pool)
+    .fetch_optional(&mut *tx)
     .await?;
-
-    if current_item_row.is_none() {
+    let Some(original) = original else {
         return Ok(None);
-    }
-    let current_item_data = current_item_row.unwrap();
+    };
+    let base = copy_base_name(&original);
+    let taken = sqlx::query_scalar!(
+        "SELECT name FROM items WHERE user_id = $1 AND LEFT(name, LENGTH($2)) = $2",
+        user_id,
+        base
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let name = (1..)
+        .map(|n| match n {
+            1 => format!("{base} (kopia)"),
+            n => format!("{base} (kopia {n})"),
+        })
+        .find(|name| !taken.contains(name))
+        .expect("some copy number is free");
+
+    let id = sqlx::query_scalar!(
+        "INSERT INTO items (user_id, name, quantity, restock_threshold, restock_to, unit, location,
+                            barcode, category_id, preferred_store_id, quantity_step, attributes,
+                            notes, sort_order)
+         SELECT user_id, $3, 0, restock_threshold, restock_to, unit, location,
+                barcode, category_id, preferred_store_id, quantity_step, attributes,
+                notes, sort_order
+         FROM items WHERE user_id = $1 AND id = $2
+         RETURNING id",
+        user_id,
+        item_id,
+        name
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    get_item_by_id(pool, user_id, id).await
+}
 
-    let name = payload.name.unwrap_or(current_item_data.name);
-    let quantity = payload.quantity.unwrap_or(current_item_data.quantity);
-    let restock_threshold = payload
-        .restock_threshold
-        .unwrap_or(current_item_data.restock_threshold);
+/// The name a copy's suffix is added to, shortened so that the copy's name
+/// still fits and without the suffix when copying a copy.
+fn copy_base_name(name: &str) -> String {
+    let name = match name.rfind(" (kopia") {
+        Some(at) if name.ends_with(')') => &name[..at],
+        _ => name,
+    };
+    name.chars()
+        .take(crate::validation::MAX_TEXT_LEN - " (kopia 999)".len())
+        .collect()
+}
 
+/// Validates the payload first, so no caller can store negative quantities;
+/// the table's CHECK constraints back this up. Fails with a conflict when the
+/// item changed since the client read `payload.version`.
+///
+/// Fields left out keep their value. Unless the quantity changes this is a
+/// single statement; otherwise the batches are brought in line with it in
+/// the same transaction.
+pub async fn update_item(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: i32,
+    payload: UpdateItemPayload,
+) -> DBResult<Option<Item>, AppError> {
+    payload.validate()?;
     tracing::info!("Updating item with ID {} for user {}", item_id, user_id);
-    tracing::info!(
-        "New item details: name={}, quantity={}, restock_threshold={}, category_id={:?}",
-        name,
-        quantity,
-        restock_threshold,
-        payload.category_id
-    );
 
-    let updated_rows = sqlx::query!(
-        "UPDATE items
-         SET name = $1, quantity = $2, restock_threshold = $3, category_id = $4, updated_at = NOW()
-         WHERE user_id = $5 AND id = $6",
-        name,
-        quantity,
-        restock_threshold,
-        payload.category_id, // Use the determined category_id
+    let row = if payload.quantity.is_some() {
+        let mut tx = pool.begin().await?;
+        let row = update_item_row(&mut *tx, user_id, item_id, &payload).await?;
+        if row.is_some() {
+            sync_item_batches(&mut tx, &[item_id]).await?;
+        }
+        tx.commit().await?;
+        row
+    } else {
+        update_item_row(pool, user_id, item_id, &payload).await?
+    };
+    match row {
+        Some(row) => Ok(Some(Item::from(row))),
+        None => {
+            let exists = sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM items WHERE user_id = $1 AND id = $2) AS "exists!""#,
+                user_id,
+                item_id
+            )
+            .fetch_one(pool)
+            .await?;
+            // The item is there, so someone else got in first
+            if exists {
+                Err(AppError::version_conflict())
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// The update of `update_item`, returning the item joined with its category.
+async fn update_item_row(
+    executor: impl PgExecutor<'_>,
+    user_id: i32,
+    item_id: i32,
+    payload: &UpdateItemPayload,
+) -> DBResult<Option<FlatItemRow>> {
+    sqlx::query_as!(
+        FlatItemRow,
+        r#"
+        WITH updated AS (
+            UPDATE items
+            SET name = COALESCE($1, name),
+                quantity = COALESCE($2, quantity),
+                restock_threshold = COALESCE($3, restock_threshold),
+                restock_to = $4, unit = $5, location = $6, category_id = $7,
+                preferred_store_id = $11, quantity_step = COALESCE($12, quantity_step),
+                barcode = $13, attributes = COALESCE($14, attributes), notes = $15,
+                updated_at = NOW()
+            WHERE user_id = $8 AND id = $9 AND version = $10
+            RETURNING *
+        )
+        SELECT
+            i.id AS "id!",
+            i.name AS "name!",
+            i.quantity AS "quantity!",
+            i.restock_threshold AS "restock_threshold!",
+            i.restock_to,
+            i.unit,
+            i.location,
+            i.barcode,
+            i.notes,
+            i.preferred_store_id,
+            i.quantity_step AS "quantity_step!",
+            i.attributes AS "attributes!: Json<Attributes>",
+            i.version AS "version!",
+            i.created_at AS "created_at!",
+            i.updated_at AS "updated_at!",
+            c.id AS "category_id?",
+            c.name AS "category_name?",
+            c.color AS "category_color?",
+            c.parent_id AS "category_parent_id?",
+            c.icon AS "category_icon?"
+        FROM updated i
+        LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
+        "#,
+        payload.name,
+        payload.quantity,
+        payload.restock_threshold,
+        payload.restock_to,
+        payload.unit,
+        payload.location,
+        payload.category_id,
         user_id,
-        item_id
+        item_id,
+        payload.version,
+        payload.preferred_store_id,
+        payload.quantity_step,
+        payload.barcode,
+        payload.attributes.as_ref().map(Json) as _,
+        payload.notes
     )
-    .execute(pool)
-    .await?
-    .rows_affected();
+    .fetch_optional(executor)
+    .await
+}
 
-    if updated_rows > 0 {
-        // Fetch and return the updated item with category details
-        get_item_by_id(pool, user_id, item_id).await
-    } else {
-        Ok(None) // Or an error if an update was expected but didn't happen
+/// Uses up `quantity` units of an item, stopping at zero.
+pub async fn use_item(
+    pool: &PgPool,
+    user_id: i32,
+    actor_id: i32,
+    item_id: i32,
+    quantity: i32,
+) -> DBResult<Option<Item>> {
+    let payload = AdjustItemPayload {
+        delta: -quantity,
+        steps: 0,
+        reason: AdjustmentReason::Used,
+    };
+    adjust_item(pool, user_id, actor_id, item_id, payload).await
+}
+
+/// Throws away `quantity` units of an item (one by default), stopping at zero.
+pub async fn discard_item(
+    pool: &PgPool,
+    user_id: i32,
+    actor_id: i32,
+    item_id: i32,
+    payload: DiscardItemPayload,
+) -> DBResult<Option<Item>> {
+    let payload = AdjustItemPayload {
+        delta: -payload.quantity.unwrap_or(1),
+        steps: 0,
+        reason: payload.reason.into(),
+    };
+    adjust_item(pool, user_id, actor_id, item_id, payload).await
+}
+
+/// Adds purchased units as a new batch. A quantity that isn't positive is
+/// rejected rather than ignored; taking units away goes through `adjust_item`.
+pub async fn purchase_item(
+    pool: &PgPool,
+    user_id: i32,
+    actor_id: i32,
+    item_id: i32,
+    payload: PurchaseItemPayload,
+) -> DBResult<Option<Item>, AppError> {
+    payload.validate()?;
+
+    let mut tx = pool.begin().await?;
+    if !add_purchase(&mut tx, user_id, actor_id, item_id, &payload).await? {
+        return Ok(None); // Item not found or no rows updated
     }
+    tx.commit().await?;
+    Ok(get_item_by_id(pool, user_id, item_id).await?)
 }
 
-pub async fn use_item(pool: &PgPool, user_id: i32, item_id: i32) -> DBResult<Option<Item>> {
-    // First, get the current quantity to ensure we don't go below 0
-    let current_quantity_opt: Option<i32> = sqlx::query_scalar!(
-        "SELECT quantity FROM items WHERE user_id = $1 AND id = $2",
+/// Tops up the user's items below their restock threshold to their target,
+/// each as a purchase in its preferred store, all in one transaction. With
+/// `item_ids`, only those of them. Returns the restocked items by name.
+pub async fn restock_items(
+    pool: &PgPool,
+    user_id: i32,
+    actor_id: i32,
+    item_ids: Option<&[i32]>,
+) -> DBResult<Vec<Item>> {
+    let mut tx = pool.begin().await?;
+    let low = sqlx::query!(
+        "SELECT id, quantity, restock_threshold, restock_to, quantity_step FROM items
+         WHERE user_id = $1 AND quantity < restock_threshold
+           AND ($2::INTEGER[] IS NULL OR id = ANY($2))
+         ORDER BY id
+         FOR UPDATE",
         user_id,
-        item_id
+        item_ids
     )
-    .fetch_optional(pool)
+    .fetch_all(&mut *tx)
     .await?;
-
-    if current_quantity_opt.is_none() {
-        return Ok(None); // Item not found
+    for item in &low {
+        let payload = PurchaseItemPayload {
+            quantity: Item::suggested_purchase(
+                item.quantity,
+                item.restock_threshold,
+                item.restock_to,
+                item.quantity_step,
+            ),
+            expires_on: None,
+            price: None,
+            store_id: None,
+        };
+        add_purchase(&mut tx, user_id, actor_id, item.id, &payload).await?;
     }
-    let current_quantity = current_quantity_opt.unwrap();
-    if current_quantity == 0 {
-        // Already at 0, no change, just return the item
-        return get_item_by_id(pool, user_id, item_id).await;
+    tx.commit().await?;
+
+    let restocked: Vec<i32> = low.iter().map(|item| item.id).collect();
+    let mut items = get_all_items(pool, user_id, ItemSort::Name, ItemFilter::All).await?;
+    items.retain(|item| restocked.contains(&item.id));
+    Ok(items)
+}
+
+/// Records everything bought on a shopping trip in one transaction: either
+/// all of the purchases are applied or, if an item doesn't exist, none.
+/// Returns the bought items by name.
+pub async fn checkout(
+    pool: &PgPool,
+    user_id: i32,
+    actor_id: i32,
+    payload: &CheckoutPayload,
+) -> DBResult<Option<Vec<Item>>> {
+    let mut tx = pool.begin().await?;
+    for line in &payload.items {
+        let purchase = PurchaseItemPayload {
+            quantity: line.quantity,
+            expires_on: None,
+            price: line.price,
+            store_id: payload.store_id,
+        };
+        if !add_purchase(&mut tx, user_id, actor_id, line.item_id, &purchase).await? {
+            return Ok(None);
+        }
     }
-    let new_quantity = current_quantity - 1;
+    tx.commit().await?;
+
+    let bought: Vec<i32> = payload.items.iter().map(|line| line.item_id).collect();
+    let mut items = get_all_items(pool, user_id, ItemSort::Name, ItemFilter::All).await?;
+    items.retain(|item| bought.contains(&item.id));
+    Ok(Some(items))
+}
 
+/// Adds purchased units to an item as a new batch and records the purchase.
+/// `false` if the user has no such item.
+async fn add_purchase(
+    conn: &mut PgConnection,
+    user_id: i32,
+    actor_id: i32,
+    item_id: i32,
+    payload: &PurchaseItemPayload,
+) -> DBResult<bool> {
     let affected_rows = sqlx::query!(
-        "UPDATE items SET quantity = $1, updated_at = NOW() WHERE user_id = $2 AND id = $3",
-        new_quantity,
+        "UPDATE items SET quantity = quantity + $1, updated_at = NOW() WHERE user_id = $2 AND id = $3",
+        payload.quantity,
         user_id,
         item_id
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await?
     .rows_affected();
-
-    if affected_rows > 0 {
-        get_item_by_id(pool, user_id, item_id).await
-    } else {
-        // This case should ideally not be reached if the item was found initially
-        // but could happen in a race condition if the item is deleted between the select and update.
-        Ok(None)
+    if affected_rows == 0 {
+        return Ok(false);
     }
+
+    sqlx::query!(
+        "INSERT INTO item_batches (item_id, quantity, expires_on) VALUES ($1, $2, $3)",
+        item_id,
+        payload.quantity,
+        payload.expires_on
+    )
+    .execute(&mut *conn)
+    .await?;
+    let event_id = record_item_event(
+        &mut *conn,
+        user_id,
+        actor_id,
+        item_id,
+        "purchased",
+        payload.quantity,
+    )
+    .await?;
+    // Without a store given it was bought where it usually is
+    sqlx::query!(
+        "INSERT INTO purchases (id, user_id, item_id, item_name, quantity, price_cents, store_id, purchased_at)
+         SELECT e.id, e.user_id, e.item_id, e.item_name, e.quantity_delta, $2,
+                COALESCE($3, i.preferred_store_id), e.created_at
+         FROM item_events e JOIN items i ON i.id = e.item_id
+         WHERE e.id = $1",
+        event_id,
+        payload.price as Option<Price>,
+        payload.store_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(true)
 }
 
-pub async fn purchase_item(
+/// Applies a signed quantity change and records it in the history under the
+/// given reason. The quantity never drops below zero; the recorded delta is
+/// the change that was actually applied.
+pub async fn adjust_item(
     pool: &PgPool,
     user_id: i32,
+    actor_id: i32,
     item_id: i32,
-    payload: PurchaseItemPayload,
+    payload: AdjustItemPayload,
 ) -> DBResult<Option<Item>> {
-    if payload.quantity <= 0 {
-        // Or return an error like AppError::BadRequest
-        return get_item_by_id(pool, user_id, item_id).await; // No change
-    }
+    let mut tx = pool.begin().await?;
 
-    let affected_rows = sqlx::query!(
-        "UPDATE items SET quantity = quantity + $1, updated_at = NOW() WHERE user_id = $2 AND id = $3",
-        payload.quantity, // Use the payload quantity directly
+    let current = sqlx::query!(
+        "SELECT quantity, quantity_step FROM items WHERE user_id = $1 AND id = $2 FOR UPDATE",
         user_id,
         item_id
     )
-    .execute(pool)
-    .await?
-    .rows_affected();
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(current) = current else {
+        return Ok(None);
+    };
+    let current_quantity = current.quantity;
 
-    if affected_rows > 0 {
-        get_item_by_id(pool, user_id, item_id).await
-    } else {
-        Ok(None) // Item not found or no rows updated
+    let delta = payload
+        .delta
+        .saturating_add(payload.steps.saturating_mul(current.quantity_step));
+    let new_quantity = current_quantity.saturating_add(delta).max(0);
+    let applied_delta = new_quantity - current_quantity;
+    if applied_delta != 0 {
+        sqlx::query!(
+            "UPDATE items SET quantity = $1, updated_at = NOW() WHERE user_id = $2 AND id = $3",
+            new_quantity,
+            user_id,
+            item_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sync_item_batches(&mut tx, &[item_id]).await?;
+        let event_id = record_item_event(
+            &mut *tx,
+            user_id,
+            actor_id,
+            item_id,
+            payload.reason.as_str(),
+            applied_delta,
+        )
+        .await?;
+        if payload.reason.is_waste()
+            && let Some(event_id) = event_id
+        {
+            estimate_waste_value(&mut *tx, event_id).await?;
+        }
     }
+
+    tx.commit().await?;
+    get_item_by_id(pool, user_id, item_id).await
 }
 
 pub async fn delete_item(pool: &PgPool, user_id: i32, item_id: i32) -> DBResult<u64> {
@@ -354,8 +1079,55 @@ pub async fn delete_item(pool: &PgPool, user_id: i32, item_id: i32) -> DBResult<
     .map(|r| r.rows_affected())
 }
 
-// For checking items that need restocking
-pub async fn get_items_to_restock(pool: &PgPool, user_id: i32) -> DBResult<Vec<Item>> {
+/// Deletes an item unless it changed since the client read `version`.
+pub async fn delete_item_at_version(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: i32,
+    version: i32,
+) -> DBResult<(), AppError> {
+    let deleted = sqlx::query!(
+        "DELETE FROM items WHERE user_id = $1 AND id = $2 AND version = $3",
+        user_id,
+        item_id,
+        version
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if deleted > 0 {
+        return Ok(());
+    }
+    match get_item_by_id(pool, user_id, item_id).await? {
+        Some(_) => Err(AppError::version_conflict()),
+        None => Err(AppError::ItemNotFound),
+    }
+}
+
+/// Items and categories changed after `since` (all of them without it) and
+/// the ids deleted since then, read from one snapshot.
+pub async fn get_changes_since(
+    pool: &PgPool,
+    user_id: i32,
+    since: Option<time::OffsetDateTime>,
+) -> DBResult<SyncChanges> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+        .execute(&mut *tx)
+        .await?;
+
+    // Rows get the start time of the transaction that wrote them, so one
+    // still running may commit changes older than NOW(). Going back to the
+    // oldest such transaction means the next sync still picks them up.
+    let server_time = sqlx::query_scalar!(
+        r#"SELECT LEAST(
+               NOW(),
+               (SELECT MIN(xact_start) FROM pg_stat_activity WHERE backend_xid IS NOT NULL)
+           ) AS "server_time!""#
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
     let rows = sqlx::query_as!(
         FlatItemRow,
         r#"
@@ -364,155 +1136,4114 @@ pub async fn get_items_to_restock(pool: &PgPool, user_id: i32) -> DBResult<Vec<I
             i.name,
             i.quantity,
             i.restock_threshold,
+            i.restock_to,
+            i.unit,
+            i.location,
+            i.barcode,
+            i.notes,
+            i.preferred_store_id,
+            i.quantity_step,
+            i.attributes AS "attributes: Json<Attributes>",
+            i.version,
             i.created_at,
             i.updated_at,
             c.id AS "category_id: Option<i32>",
             c.name AS "category_name: Option<String>",
-            c.color AS "category_color: Option<String>"
+            c.color AS "category_color: Option<String>",
+            c.parent_id AS category_parent_id,
+            c.icon AS category_icon
         FROM items i
         LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
-        WHERE i.user_id = $1 AND i.quantity < i.restock_threshold
-        ORDER BY i.name
+        WHERE i.user_id = $1 AND ($2::TIMESTAMPTZ IS NULL OR i.changed_at >= $2)
+        ORDER BY i.id
         "#,
-        user_id
+        user_id,
+        since
     )
-    .fetch_all(pool)
+    .fetch_all(&mut *tx)
     .await?;
-    Ok(rows.into_iter().map(Item::from).collect())
-}
 
-//
-// Account management
-//
+    let categories = sqlx::query_as!(
+        Category,
+        "SELECT id, name, color, parent_id, icon FROM categories
+         WHERE user_id = $1 AND ($2::TIMESTAMPTZ IS NULL OR changed_at >= $2)
+         ORDER BY id",
+        user_id,
+        since
+    )
+    .fetch_all(&mut *tx)
+    .await?;
 
-/// Create a new account
-pub async fn create_account(
-    pool: &PgPool,
-    name: &str,
-    email: &str,
-    hashed_password: &str,
-) -> DBResult<Account> {
+    let mut deleted = SyncDeleted::default();
+    if since.is_some() {
+        let deletions = sqlx::query!(
+            "SELECT record_type, record_id FROM deletions
+             WHERE user_id = $1 AND deleted_at >= $2
+             ORDER BY id",
+            user_id,
+            since
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for deletion in deletions {
+            match deletion.record_type.as_str() {
+                "item" => deleted.items.push(deletion.record_id),
+                _ => deleted.categories.push(deletion.record_id),
+            }
+        }
+    }
+    tx.commit().await?;
+
+    Ok(SyncChanges {
+        server_time,
+        items: rows.into_iter().map(Item::from).collect(),
+        categories,
+        deleted,
+    })
+}
+
+/// Stores a manual order: each item gets its position in `item_ids`.
+/// Items not in the list keep their current position.
+pub async fn reorder_items(pool: &PgPool, user_id: i32, item_ids: &[i32]) -> DBResult<u64> {
+    sqlx::query!(
+        "UPDATE items i
+         SET sort_order = o.position - 1
+         FROM UNNEST($2::INTEGER[]) WITH ORDINALITY AS o(id, position)
+         WHERE i.id = o.id AND i.user_id = $1",
+        user_id,
+        item_ids
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+//
+// Batches
+//
+
+/// Brings the batches of the given items in line with `items.quantity`.
+/// Surplus units are taken from the oldest batches first (FIFO); missing
+/// units become a new undated batch. Call after every quantity change.
+async fn sync_item_batches(conn: &mut sqlx::PgConnection, item_ids: &[i32]) -> DBResult<()> {
+    // Batches used up entirely
+    sqlx::query!(
+        r#"
+        WITH running AS (
+            SELECT
+                b.id,
+                SUM(b.quantity) OVER (PARTITION BY b.item_id ORDER BY b.purchased_on, b.id) AS used_up_to,
+                SUM(b.quantity) OVER (PARTITION BY b.item_id) - i.quantity AS surplus
+            FROM item_batches b
+            JOIN items i ON i.id = b.item_id
+            WHERE b.item_id = ANY($1)
+        )
+        DELETE FROM item_batches b
+        USING running r
+        WHERE b.id = r.id AND r.surplus >= r.used_up_to
+        "#,
+        item_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    // The oldest remaining batch covers whatever surplus is left
+    sqlx::query!(
+        r#"
+        WITH running AS (
+            SELECT
+                b.id,
+                SUM(b.quantity) OVER (PARTITION BY b.item_id ORDER BY b.purchased_on, b.id) - b.quantity AS used_before,
+                SUM(b.quantity) OVER (PARTITION BY b.item_id) - i.quantity AS surplus
+            FROM item_batches b
+            JOIN items i ON i.id = b.item_id
+            WHERE b.item_id = ANY($1)
+        )
+        UPDATE item_batches b
+        SET quantity = b.quantity - (r.surplus - r.used_before)
+        FROM running r
+        WHERE b.id = r.id AND r.surplus > r.used_before
+        "#,
+        item_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO item_batches (item_id, quantity)
+        SELECT i.id, i.quantity - COALESCE(SUM(b.quantity), 0)
+        FROM items i
+        LEFT JOIN item_batches b ON b.item_id = i.id
+        WHERE i.id = ANY($1)
+        GROUP BY i.id, i.quantity
+        HAVING i.quantity > COALESCE(SUM(b.quantity), 0)
+        "#,
+        item_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Batches of an item, oldest first (the order they are used in).
+pub async fn get_item_batches(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: i32,
+) -> DBResult<Vec<ItemBatch>> {
     sqlx::query_as!(
-        Account,
-        "INSERT INTO users (name, email, password) VALUES ($1, $2, $3)
-         RETURNING id, name, email, password, created_at, updated_at",
-        name,
-        email,
-        hashed_password
+        ItemBatch,
+        "SELECT b.id, b.item_id, b.quantity, b.purchased_on, b.expires_on
+         FROM item_batches b
+         JOIN items i ON i.id = b.item_id
+         WHERE i.user_id = $1 AND b.item_id = $2
+         ORDER BY b.purchased_on, b.id",
+        user_id,
+        item_id
     )
-    .fetch_one(pool)
+    .fetch_all(pool)
     .await
 }
 
-/// Fetch an account by email (for login)
-pub async fn get_account_by_email(pool: &PgPool, email: &str) -> DBResult<Option<Account>> {
+/// Removes a whole batch from stock, e.g. when it has gone off.
+/// Recorded in the history as spoiled.
+pub async fn discard_batch(
+    pool: &PgPool,
+    user_id: i32,
+    actor_id: i32,
+    item_id: i32,
+    batch_id: i32,
+) -> DBResult<Option<Item>> {
+    let mut tx = pool.begin().await?;
+
+    let quantity = sqlx::query_scalar!(
+        "DELETE FROM item_batches b
+         USING items i
+         WHERE i.id = b.item_id AND i.user_id = $1 AND b.item_id = $2 AND b.id = $3
+         RETURNING b.quantity",
+        user_id,
+        item_id,
+        batch_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(quantity) = quantity else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        "UPDATE items SET quantity = quantity - $1, updated_at = NOW() WHERE user_id = $2 AND id = $3",
+        quantity,
+        user_id,
+        item_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    if let Some(event_id) =
+        record_item_event(&mut *tx, user_id, actor_id, item_id, "spoiled", -quantity).await?
+    {
+        estimate_waste_value(&mut *tx, event_id).await?;
+    }
+
+    tx.commit().await?;
+    get_item_by_id(pool, user_id, item_id).await
+}
+
+/// How many days ahead expiry notifications look.
+pub const EXPIRY_WARNING_DAYS: i32 = 3;
+
+/// Batches that have expired or expire within `days` days, soonest first.
+pub async fn get_expiring_batches(
+    pool: &PgPool,
+    user_id: i32,
+    days: i32,
+) -> DBResult<Vec<ExpiringBatch>> {
     sqlx::query_as!(
-        Account,
-        "SELECT id, name, email, password, created_at, updated_at FROM users WHERE email = $1",
-        email
+        ExpiringBatch,
+        r#"
+        SELECT
+            b.id,
+            b.item_id,
+            i.name AS item_name,
+            b.quantity,
+            b.expires_on AS "expires_on!"
+        FROM item_batches b
+        JOIN items i ON i.id = b.item_id
+        WHERE i.user_id = $1 AND b.expires_on <= CURRENT_DATE + $2::INTEGER
+        ORDER BY b.expires_on, i.name
+        "#,
+        user_id,
+        days
     )
-    .fetch_optional(pool)
+    .fetch_all(pool)
     .await
 }
 
-pub async fn get_user_by_id(pool: &PgPool, id: i32) -> DBResult<Option<Account>> {
+/// Every batch with an expiry date, soonest first, for the calendar feed.
+pub async fn get_dated_batches(pool: &PgPool, user_id: i32) -> DBResult<Vec<ExpiringBatch>> {
     sqlx::query_as!(
-        Account,
-        "SELECT id, name, email, password, created_at, updated_at FROM users WHERE id = $1",
-        id
+        ExpiringBatch,
+        r#"
+        SELECT
+            b.id,
+            b.item_id,
+            i.name AS item_name,
+            b.quantity,
+            b.expires_on AS "expires_on!"
+        FROM item_batches b
+        JOIN items i ON i.id = b.item_id
+        WHERE i.user_id = $1 AND b.expires_on IS NOT NULL
+        ORDER BY b.expires_on, i.name
+        "#,
+        user_id
     )
-    .fetch_optional(pool)
+    .fetch_all(pool)
     .await
 }
 
-// --- Category DB Functions ---
-pub async fn create_category(
-    pool: &PgPool,
-    user_id: i32,
-    payload: CreateCategoryPayload,
-) -> DBResult<Category> {
+/// How much of each item in stock was used in the last `days` days, for
+/// items used at all.
+pub async fn get_item_usage(pool: &PgPool, user_id: i32, days: i32) -> DBResult<Vec<ItemUsage>> {
     sqlx::query_as!(
-        Category,
-        "INSERT INTO categories (user_id, name, color) VALUES ($1, $2, $3)
-         RETURNING id, name, color", // user_id is not part of Category struct here
+        ItemUsage,
+        r#"
+        SELECT i.id AS item_id, i.name AS item_name, i.quantity,
+               SUM(-e.quantity_delta) AS "used!"
+        FROM items i
+        JOIN item_events e ON e.item_id = i.id AND e.user_id = i.user_id
+        WHERE i.user_id = $1 AND i.quantity > 0
+          AND e.kind = 'used' AND e.created_at >= NOW() - make_interval(days => $2)
+        GROUP BY i.id
+        ORDER BY i.name
+        "#,
         user_id,
-        payload.name,
-        payload.color
+        days
     )
-    .fetch_one(pool)
+    .fetch_all(pool)
     .await
 }
 
-pub async fn get_all_categories(pool: &PgPool, user_id: i32) -> DBResult<Vec<Category>> {
+// For checking items that need restocking
+/// The user's items that have been below their restock threshold for at
+/// least `restock_escalation_days`; none while escalation is off.
+pub async fn get_urgent_restocks(pool: &PgPool, user_id: i32) -> DBResult<Vec<UrgentRestock>> {
     sqlx::query_as!(
-        Category,
-        "SELECT id, name, color FROM categories WHERE user_id = $1 ORDER BY name",
+        UrgentRestock,
+        r#"SELECT i.id AS item_id, EXTRACT(DAY FROM NOW() - i.low_since)::INTEGER AS "days_low!"
+           FROM items i
+           JOIN user_preferences p ON p.user_id = i.user_id
+           WHERE i.user_id = $1
+             AND p.restock_escalation_days > 0
+             AND i.low_since <= NOW() - make_interval(days => p.restock_escalation_days)
+           ORDER BY i.id"#,
         user_id
     )
     .fetch_all(pool)
     .await
 }
 
-pub async fn get_category_by_id(
+pub async fn get_items_to_restock(pool: &PgPool, user_id: i32) -> DBResult<Vec<Item>> {
+    let rows = sqlx::query_as!(
+        FlatItemRow,
+        r#"
+        SELECT
+            i.id,
+            i.name,
+            i.quantity,
+            i.restock_threshold,
+            i.restock_to,
+            i.unit,
+            i.location,
+            i.barcode,
+            i.notes,
+            i.preferred_store_id,
+            i.quantity_step,
+            i.attributes AS "attributes: Json<Attributes>",
+            i.version,
+            i.created_at,
+            i.updated_at,
+            c.id AS "category_id: Option<i32>",
+            c.name AS "category_name: Option<String>",
+            c.color AS "category_color: Option<String>",
+            c.parent_id AS category_parent_id,
+            c.icon AS category_icon
+        FROM items i
+        LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
+        WHERE i.user_id = $1 AND i.quantity < i.restock_threshold
+        ORDER BY i.name
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(Item::from).collect())
+}
+
+/// Loads the item list page's data over several connections at once, the
+/// account and categories from `cache` when they are there. Unless the filter
+/// hides some of them, the items to restock are picked from the item list
+/// rather than queried again.
+pub async fn get_dashboard_data(
     pool: &PgPool,
+    cache: &LookupCache,
     user_id: i32,
-    category_id: i32,
-) -> DBResult<Option<Category>> {
-    sqlx::query_as!(
-        Category,
-        "SELECT id, name, color FROM categories WHERE user_id = $1 AND id = $2",
+    sort: ItemSort,
+    filter: ItemFilter,
+) -> DBResult<DashboardData> {
+    let lists_all_low = matches!(filter, ItemFilter::All | ItemFilter::Low);
+    let to_restock = async {
+        if lists_all_low {
+            Ok(vec![])
+        } else {
+            get_items_to_restock(pool, user_id).await
+        }
+    };
+    let (user, items, categories, to_restock, expiring) = tokio::try_join!(
+        cache.user(pool, user_id),
+        get_all_items(pool, user_id, sort, filter),
+        cache.categories(pool, user_id),
+        to_restock,
+        get_expiring_batches(pool, user_id, EXPIRY_WARNING_DAYS),
+    )?;
+    let to_restock = if lists_all_low {
+        let mut to_restock: Vec<Item> = items
+            .iter()
+            .filter(|item| item.quantity < item.restock_threshold)
+            .cloned()
+            .collect();
+        to_restock.sort_by(|a, b| a.name.cmp(&b.name));
+        to_restock
+    } else {
+        to_restock
+    };
+    Ok(DashboardData {
+        user,
+        items,
+        categories,
+        to_restock,
+        expiring,
+    })
+}
+
+/// Appends a quantity change to the item history and returns its id.
+/// The item name is copied so the entry stays readable after the item is deleted.
+/// `actor_id` is whoever made the change, the owner or a household member.
+async fn record_item_event(
+    executor: impl PgExecutor<'_>,
+    user_id: i32,
+    actor_id: i32,
+    item_id: i32,
+    kind: &str,
+    quantity_delta: i32,
+) -> DBResult<Option<i32>> {
+    sqlx::query_scalar!(
+        "INSERT INTO item_events (user_id, item_id, item_name, kind, quantity_delta, actor_id)
+         SELECT user_id, id, name, $3, $4, $5 FROM items WHERE user_id = $1 AND id = $2
+         RETURNING id",
         user_id,
-        category_id
+        item_id,
+        kind,
+        quantity_delta,
+        actor_id
     )
-    .fetch_optional(pool)
+    .fetch_optional(executor)
     .await
 }
 
-pub async fn update_category(
+/// Puts a value on the stock a waste event threw away: its units at the
+/// unit price of the item's latest purchase with a price. Items never bought
+/// with one stay without a value.
+async fn estimate_waste_value(executor: impl PgExecutor<'_>, event_id: i32) -> DBResult<()> {
+    sqlx::query!(
+        "UPDATE item_events e
+         SET value_cents = (
+             SELECT ROUND(p.price_cents::NUMERIC * -e.quantity_delta / p.quantity)::INTEGER
+             FROM purchases p
+             WHERE p.item_id = e.item_id AND p.price_cents IS NOT NULL AND p.quantity > 0
+             ORDER BY p.purchased_at DESC, p.id DESC
+             LIMIT 1
+         )
+         WHERE e.id = $1",
+        event_id
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// One page of the user's history, or of one item's, newest first: up to
+/// `limit` events sorting after `after`. See `pagination::Page`.
+pub async fn get_item_events(
     pool: &PgPool,
     user_id: i32,
-    category_id: i32,
-    name: Option<String>,
-    color: Option<String>,
-) -> DBResult<Option<Category>> {
-    let current_category = get_category_by_id(pool, user_id, category_id).await?;
-    if current_category.is_none() {
-        return Ok(None);
+    item_id: Option<i32>,
+    after: Option<Cursor>,
+    limit: i64,
+) -> DBResult<Vec<ItemEvent>> {
+    // Starting past the newest possible event keeps the first page on the
+    // same plan as the others; an `IS NULL OR` would keep off the index.
+    let (after_created_at, after_id) = after.map(|c| (c.created_at, c.id)).unzip();
+    match item_id {
+        Some(item_id) => {
+            sqlx::query_as!(
+                ItemEvent,
+                "SELECT id, item_id, item_name, kind, quantity_delta, created_at
+                 FROM item_events
+                 WHERE item_id = $2 AND user_id = $1
+                   AND (created_at, id) < (COALESCE($3::TIMESTAMPTZ, 'infinity'), COALESCE($4::INT, 2147483647))
+                 ORDER BY created_at DESC, id DESC
+                 LIMIT $5",
+                user_id,
+                item_id,
+                after_created_at,
+                after_id,
+                limit
+            )
+            .fetch_all(pool)
+            .await
+        }
+        None => {
+            sqlx::query_as!(
+                ItemEvent,
+                "SELECT id, item_id, item_name, kind, quantity_delta, created_at
+                 FROM item_events
+                 WHERE user_id = $1
+                   AND (created_at, id) < (COALESCE($2::TIMESTAMPTZ, 'infinity'), COALESCE($3::INT, 2147483647))
+                 ORDER BY created_at DESC, id DESC
+                 LIMIT $4",
+                user_id,
+                after_created_at,
+                after_id,
+                limit
+            )
+            .fetch_all(pool)
+            .await
+        }
     }
-    let current_category = current_category.unwrap();
+}
 
-    let name_to_set = name.unwrap_or(current_category.name);
-    let color_to_set = color.unwrap_or(current_category.color);
+/// One page of the activity feed of the user's inventory, like
+/// `get_item_events` but with who made each change, and optionally only the
+/// changes of `actor_id`. The name is `None` once their account is deleted.
+pub async fn get_activity(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: Option<i32>,
+    actor_id: Option<i32>,
+    after: Option<Cursor>,
+    limit: i64,
+) -> DBResult<Vec<(ItemEvent, Option<i32>, Option<String>)>> {
+    let (after_created_at, after_id) = after.map(|c| (c.created_at, c.id)).unzip();
+    let rows = sqlx::query!(
+        r#"SELECT e.id, e.item_id, e.item_name, e.kind, e.quantity_delta, e.created_at,
+                  e.actor_id, u.name AS "actor_name?"
+           FROM item_events e
+           LEFT JOIN users u ON u.id = e.actor_id
+           WHERE e.user_id = $1
+             AND ($2::INT IS NULL OR e.item_id = $2)
+             AND ($3::INT IS NULL OR e.actor_id = $3)
+             AND (e.created_at, e.id) < (COALESCE($4::TIMESTAMPTZ, 'infinity'), COALESCE($5::INT, 2147483647))
+           ORDER BY e.created_at DESC, e.id DESC
+           LIMIT $6"#,
+        user_id,
+        item_id,
+        actor_id,
+        after_created_at,
+        after_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let event = ItemEvent {
+                id: row.id,
+                item_id: row.item_id,
+                item_name: row.item_name,
+                kind: row.kind,
+                quantity_delta: row.quantity_delta,
+                created_at: row.created_at,
+            };
+            (event, row.actor_id, row.actor_name)
+        })
+        .collect())
+}
 
-    let affected_rows = sqlx::query!(
-        "UPDATE categories SET name = $1, color = $2 WHERE user_id = $3 AND id = $4",
-        name_to_set,
-        color_to_set,
+/// The newest `per_item` events of each of `item_ids`, newest first within
+/// an item; for listing items with their latest changes in one query.
+pub async fn get_latest_item_events(
+    pool: &PgPool,
+    user_id: i32,
+    item_ids: &[i32],
+    per_item: i64,
+) -> DBResult<Vec<ItemEvent>> {
+    sqlx::query_as!(
+        ItemEvent,
+        r#"SELECT id AS "id!", item_id, item_name AS "item_name!", kind AS "kind!",
+                  quantity_delta AS "quantity_delta!", created_at AS "created_at!"
+           FROM (
+               SELECT *, ROW_NUMBER() OVER (PARTITION BY item_id ORDER BY created_at DESC, id DESC) AS rank
+               FROM item_events
+               WHERE user_id = $1 AND item_id = ANY($2)
+           ) latest
+           WHERE rank <= $3
+           ORDER BY item_id, created_at DESC, id DESC"#,
         user_id,
-        category_id
+        item_ids,
+        per_item
     )
-    .execute(pool)
-    .await?
-    .rows_affected();
+    .fetch_all(pool)
+    .await
+}
 
-    if affected_rows > 0 {
-        get_category_by_id(pool, user_id, category_id).await
-    } else {
-        Ok(None)
+/// Events of every user newer than `after_id`, oldest first, with the user
+/// each belongs to. The last couple of seconds are left for the next call,
+/// so events whose transaction hasn't committed yet aren't skipped.
+pub async fn get_item_events_after(
+    pool: &PgPool,
+    after_id: i32,
+    limit: i64,
+) -> DBResult<Vec<(i32, ItemEvent)>> {
+    let rows = sqlx::query!(
+        "SELECT user_id, id, item_id, item_name, kind, quantity_delta, created_at
+         FROM item_events
+         WHERE id > $1 AND created_at < NOW() - INTERVAL '2 seconds'
+         ORDER BY id
+         LIMIT $2",
+        after_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let event = ItemEvent {
+                id: row.id,
+                item_id: row.item_id,
+                item_name: row.item_name,
+                kind: row.kind,
+                quantity_delta: row.quantity_delta,
+                created_at: row.created_at,
+            };
+            (row.user_id, event)
+        })
+        .collect())
+}
+
+/// ID of the last item event published over MQTT.
+pub async fn get_mqtt_cursor(pool: &PgPool) -> DBResult<i32> {
+    sqlx::query_scalar!("SELECT last_event_id FROM mqtt_cursor")
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn set_mqtt_cursor(pool: &PgPool, last_event_id: i32) -> DBResult<()> {
+    sqlx::query!("UPDATE mqtt_cursor SET last_event_id = $1", last_event_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+//
+// Purchases
+//
+
+/// One page of the user's purchases, or of one item's, newest first. See
+/// `get_item_events`.
+pub async fn get_purchases(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: Option<i32>,
+    after: Option<Cursor>,
+    limit: i64,
+) -> DBResult<Vec<Purchase>> {
+    let (after_purchased_at, after_id) = after.map(|c| (c.created_at, c.id)).unzip();
+    match item_id {
+        Some(item_id) => {
+            sqlx::query_as!(
+                Purchase,
+                r#"SELECT p.id, p.item_id, p.item_name, p.quantity, p.price_cents AS "price: Price",
+                          ROUND(p.price_cents::NUMERIC / NULLIF(p.quantity, 0))::INTEGER AS "unit_price: Price",
+                          s.id AS "store_id?", s.name AS "store_name?",
+                          EXISTS (SELECT 1 FROM receipts r WHERE r.event_id = p.id) AS "has_receipt!",
+                          p.purchased_at
+                   FROM purchases p
+                   LEFT JOIN stores s ON s.id = p.store_id AND s.user_id = p.user_id
+                   WHERE p.item_id = $2 AND p.user_id = $1
+                     AND (p.purchased_at, p.id) < (COALESCE($3::TIMESTAMPTZ, 'infinity'), COALESCE($4::INT, 2147483647))
+                   ORDER BY p.purchased_at DESC, p.id DESC
+                   LIMIT $5"#,
+                user_id,
+                item_id,
+                after_purchased_at,
+                after_id,
+                limit
+            )
+            .fetch_all(pool)
+            .await
+        }
+        None => {
+            sqlx::query_as!(
+                Purchase,
+                r#"SELECT p.id, p.item_id, p.item_name, p.quantity, p.price_cents AS "price: Price",
+                          ROUND(p.price_cents::NUMERIC / NULLIF(p.quantity, 0))::INTEGER AS "unit_price: Price",
+                          s.id AS "store_id?", s.name AS "store_name?",
+                          EXISTS (SELECT 1 FROM receipts r WHERE r.event_id = p.id) AS "has_receipt!",
+                          p.purchased_at
+                   FROM purchases p
+                   LEFT JOIN stores s ON s.id = p.store_id AND s.user_id = p.user_id
+                   WHERE p.user_id = $1
+                     AND (p.purchased_at, p.id) < (COALESCE($2::TIMESTAMPTZ, 'infinity'), COALESCE($3::INT, 2147483647))
+                   ORDER BY p.purchased_at DESC, p.id DESC
+                   LIMIT $4"#,
+                user_id,
+                after_purchased_at,
+                after_id,
+                limit
+            )
+            .fetch_all(pool)
+            .await
+        }
     }
 }
 
-pub async fn delete_category(pool: &PgPool, user_id: i32, category_id: i32) -> DBResult<u64> {
-    // Consider what happens to items in this category based on your ON DELETE constraint.
-    // If it's SET NULL, items.category_id will become NULL.
-    // If it's CASCADE, items will be deleted.
-    // If it's RESTRICT, this will fail if items exist in the category.
-    sqlx::query!(
-        "DELETE FROM categories WHERE user_id = $1 AND id = $2",
+/// Unit prices of an item by store, cheapest first, over the purchases that
+/// have a price.
+pub async fn get_store_prices(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: i32,
+) -> DBResult<Vec<StorePrice>> {
+    sqlx::query_as!(
+        StorePrice,
+        r#"WITH priced AS (
+               SELECT s.id AS store_id, s.name AS store_name, p.purchased_at,
+                      p.price_cents::NUMERIC / p.quantity AS unit_price
+               FROM purchases p
+               LEFT JOIN stores s ON s.id = p.store_id AND s.user_id = p.user_id
+               WHERE p.user_id = $1 AND p.item_id = $2
+                 AND p.price_cents IS NOT NULL AND p.quantity > 0
+           )
+           SELECT store_id, store_name, COUNT(*) AS "purchases!",
+                  ROUND(MIN(unit_price))::INTEGER AS "lowest!: Price",
+                  ROUND(AVG(unit_price))::INTEGER AS "average!: Price",
+                  ROUND((ARRAY_AGG(unit_price ORDER BY purchased_at DESC))[1])::INTEGER AS "latest!: Price",
+                  MAX(purchased_at) AS "latest_at!"
+           FROM priced
+           GROUP BY store_id, store_name
+           ORDER BY 5, store_name NULLS LAST"#,
         user_id,
-        category_id
+        item_id
     )
-    .execute(pool)
+    .fetch_all(pool)
     .await
-    .map(|r| r.rows_affected())
+}
+
+//
+// Receipts
+//
+
+/// One of the user's events, if it is a purchase.
+pub async fn get_purchase_event(
+    pool: &PgPool,
+    user_id: i32,
+    event_id: i32,
+) -> DBResult<Option<ItemEvent>> {
+    sqlx::query_as!(
+        ItemEvent,
+        "SELECT id, item_id, item_name, kind, quantity_delta, created_at
+         FROM item_events
+         WHERE id = $1 AND user_id = $2 AND kind = 'purchased'",
+        event_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Records the receipt of a purchase, replacing any earlier one, and returns
+/// the key of the file it replaced.
+pub async fn save_receipt(
+    pool: &PgPool,
+    user_id: i32,
+    event_id: i32,
+    storage_key: &str,
+    content_type: &str,
+    size_bytes: i32,
+) -> DBResult<Option<String>> {
+    let mut tx = pool.begin().await?;
+    let replaced = sqlx::query_scalar!(
+        "SELECT storage_key FROM receipts WHERE event_id = $1 FOR UPDATE",
+        event_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "INSERT INTO receipts (event_id, user_id, storage_key, content_type, size_bytes)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (event_id) DO UPDATE
+         SET storage_key = EXCLUDED.storage_key,
+             content_type = EXCLUDED.content_type,
+             size_bytes = EXCLUDED.size_bytes,
+             created_at = NOW()",
+        event_id,
+        user_id,
+        storage_key,
+        content_type,
+        size_bytes
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(replaced)
+}
+
+/// The receipt of one of the user's purchases, with the key of its file.
+pub async fn get_receipt(
+    pool: &PgPool,
+    user_id: i32,
+    event_id: i32,
+) -> DBResult<Option<(Receipt, String)>> {
+    let row = sqlx::query!(
+        "SELECT event_id, storage_key, content_type, size_bytes, created_at
+         FROM receipts
+         WHERE event_id = $1 AND user_id = $2",
+        event_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| {
+        let receipt = Receipt {
+            event_id: row.event_id,
+            content_type: row.content_type,
+            size_bytes: row.size_bytes,
+            created_at: row.created_at,
+        };
+        (receipt, row.storage_key)
+    }))
+}
+
+/// Receipts of the given events that have one.
+pub async fn get_receipts(
+    pool: &PgPool,
+    user_id: i32,
+    event_ids: &[i32],
+) -> DBResult<Vec<Receipt>> {
+    sqlx::query_as!(
+        Receipt,
+        "SELECT event_id, content_type, size_bytes, created_at
+         FROM receipts
+         WHERE user_id = $1 AND event_id = ANY($2)",
+        user_id,
+        event_ids
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Forgets the receipt of a purchase and returns the key of its file.
+pub async fn delete_receipt(
+    pool: &PgPool,
+    user_id: i32,
+    event_id: i32,
+) -> DBResult<Option<String>> {
+    sqlx::query_scalar!(
+        "DELETE FROM receipts WHERE event_id = $1 AND user_id = $2 RETURNING storage_key",
+        event_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+//
+// Statistics
+//
+
+/// Aggregates used by the dashboard: stock totals, per-category breakdown
+/// and daily consumption for the last `days` days (including today).
+pub async fn get_stats_overview(pool: &PgPool, user_id: i32, days: i32) -> DBResult<StatsOverview> {
+    let totals = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "total_items!",
+            COALESCE(SUM(quantity), 0) AS "total_quantity!",
+            COUNT(*) FILTER (WHERE quantity < restock_threshold) AS "low_stock_count!",
+            COUNT(*) FILTER (WHERE quantity = 0) AS "out_of_stock_count!"
+        FROM items
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let by_category = sqlx::query_as!(
+        CategoryStats,
+        r#"
+        SELECT
+            c.id AS "id: Option<i32>",
+            c.name AS "name: Option<String>",
+            c.color AS "color: Option<String>",
+            COUNT(*) AS "item_count!",
+            COALESCE(SUM(i.quantity), 0) AS "total_quantity!"
+        FROM items i
+        LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
+        WHERE i.user_id = $1
+        GROUP BY c.id, c.name, c.color
+        ORDER BY c.name NULLS LAST
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let consumption = sqlx::query_as!(
+        ConsumptionPoint,
+        r#"
+        SELECT
+            to_char(d.day, 'YYYY-MM-DD') AS "day!",
+            COALESCE(SUM(-e.quantity_delta) FILTER (WHERE e.kind = 'used'), 0) AS "used!",
+            COALESCE(SUM(e.quantity_delta) FILTER (WHERE e.kind = 'purchased'), 0) AS "purchased!",
+            COALESCE(SUM(-e.quantity_delta) FILTER (WHERE e.kind IN ('expired', 'spoiled', 'lost')), 0) AS "wasted!"
+        FROM generate_series(
+            (CURRENT_DATE - ($2::INTEGER - 1))::TIMESTAMP,
+            CURRENT_DATE::TIMESTAMP,
+            INTERVAL '1 day'
+        ) AS d(day)
+        LEFT JOIN item_events e ON e.user_id = $1 AND e.created_at::DATE = d.day::DATE
+        GROUP BY d.day
+        ORDER BY d.day
+        "#,
+        user_id,
+        days
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(StatsOverview {
+        total_items: totals.total_items,
+        total_quantity: totals.total_quantity,
+        low_stock_count: totals.low_stock_count,
+        out_of_stock_count: totals.out_of_stock_count,
+        by_category,
+        consumption,
+    })
+}
+
+//
+// Waste
+//
+
+/// What was thrown away in `month`: in total, by reason and by item, with the
+/// totals of the eleven months before it.
+pub async fn get_waste_report(pool: &PgPool, user_id: i32, month: Month) -> DBResult<WasteReport> {
+    let start = month.0;
+    let totals = sqlx::query!(
+        r#"SELECT COALESCE(SUM(-quantity_delta), 0) AS "quantity!",
+                  COALESCE(SUM(value_cents), 0)::INTEGER AS "value!: Price",
+                  COALESCE(SUM(-quantity_delta) FILTER (WHERE value_cents IS NULL), 0) AS "unpriced_quantity!"
+           FROM item_events
+           WHERE user_id = $1 AND kind IN ('expired', 'spoiled', 'lost')
+             AND created_at >= $2::DATE AND created_at < $2::DATE + INTERVAL '1 month'"#,
+        user_id,
+        start
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let reasons = sqlx::query_as!(
+        WasteByReason,
+        r#"SELECT kind AS reason, COALESCE(SUM(-quantity_delta), 0) AS "quantity!",
+                  COALESCE(SUM(value_cents), 0)::INTEGER AS "value!: Price"
+           FROM item_events
+           WHERE user_id = $1 AND kind IN ('expired', 'spoiled', 'lost')
+             AND created_at >= $2::DATE AND created_at < $2::DATE + INTERVAL '1 month'
+           GROUP BY kind
+           ORDER BY 3 DESC, 2 DESC"#,
+        user_id,
+        start
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Events of a deleted item are told apart by its name
+    let items = sqlx::query_as!(
+        WastedItem,
+        r#"SELECT item_id, item_name, COALESCE(SUM(-quantity_delta), 0) AS "quantity!",
+                  SUM(value_cents)::INTEGER AS "value: Price"
+           FROM item_events
+           WHERE user_id = $1 AND kind IN ('expired', 'spoiled', 'lost')
+             AND created_at >= $2::DATE AND created_at < $2::DATE + INTERVAL '1 month'
+           GROUP BY item_id, item_name
+           ORDER BY 4 DESC NULLS LAST, 3 DESC, item_name"#,
+        user_id,
+        start
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let months = sqlx::query_as!(
+        WasteMonth,
+        r#"SELECT to_char(m.month, 'YYYY-MM') AS "month!",
+                  COALESCE(SUM(-e.quantity_delta), 0) AS "quantity!",
+                  COALESCE(SUM(e.value_cents), 0)::INTEGER AS "value!: Price"
+           FROM generate_series($2::DATE - INTERVAL '11 months', $2::DATE, INTERVAL '1 month') AS m(month)
+           LEFT JOIN item_events e
+             ON e.user_id = $1 AND e.kind IN ('expired', 'spoiled', 'lost')
+            AND e.created_at >= m.month AND e.created_at < m.month + INTERVAL '1 month'
+           GROUP BY m.month
+           ORDER BY m.month"#,
+        user_id,
+        start
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(WasteReport {
+        month,
+        quantity: totals.quantity,
+        value: totals.value,
+        unpriced_quantity: totals.unpriced_quantity,
+        reasons,
+        items,
+        months,
+    })
+}
+
+/// The user's `month` in numbers, with the `top` items and categories used
+/// up most.
+pub async fn get_monthly_summary(
+    pool: &PgPool,
+    user_id: i32,
+    month: Month,
+    top: i64,
+) -> DBResult<MonthlySummary> {
+    let start = month.0;
+    let events = sqlx::query!(
+        r#"SELECT COALESCE(SUM(-quantity_delta) FILTER (WHERE kind = 'used'), 0) AS "consumed!",
+                  COALESCE(SUM(-quantity_delta) FILTER (WHERE kind IN ('expired', 'spoiled', 'lost')), 0) AS "wasted!",
+                  COALESCE(SUM(value_cents) FILTER (WHERE kind IN ('expired', 'spoiled', 'lost')), 0)::INTEGER
+                      AS "waste_value!: Price"
+           FROM item_events
+           WHERE user_id = $1
+             AND created_at >= $2::DATE AND created_at < $2::DATE + INTERVAL '1 month'"#,
+        user_id,
+        start
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let purchases = sqlx::query!(
+        r#"SELECT COUNT(*) AS "purchases!", COALESCE(SUM(price_cents), 0)::INTEGER AS "spent!: Price"
+           FROM purchases
+           WHERE user_id = $1
+             AND purchased_at >= $2::DATE AND purchased_at < $2::DATE + INTERVAL '1 month'"#,
+        user_id,
+        start
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let top_items = sqlx::query_as!(
+        SummaryLine,
+        r#"SELECT item_name AS name, SUM(-quantity_delta) AS "quantity!"
+           FROM item_events
+           WHERE user_id = $1 AND kind = 'used'
+             AND created_at >= $2::DATE AND created_at < $2::DATE + INTERVAL '1 month'
+           GROUP BY item_name
+           ORDER BY 2 DESC, item_name
+           LIMIT $3"#,
+        user_id,
+        start,
+        top
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Deleted items no longer have a category, so they are left out here
+    let top_categories = sqlx::query_as!(
+        SummaryLine,
+        r#"SELECT c.name, SUM(-e.quantity_delta) AS "quantity!"
+           FROM item_events e
+           JOIN items i ON i.id = e.item_id
+           JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
+           WHERE e.user_id = $1 AND e.kind = 'used'
+             AND e.created_at >= $2::DATE AND e.created_at < $2::DATE + INTERVAL '1 month'
+           GROUP BY c.id, c.name
+           ORDER BY 2 DESC, c.name
+           LIMIT $3"#,
+        user_id,
+        start,
+        top
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(MonthlySummary {
+        month,
+        consumed: events.consumed,
+        purchases: purchases.purchases,
+        spent: purchases.spent,
+        wasted: events.wasted,
+        waste_value: events.waste_value,
+        top_items,
+        top_categories,
+    })
+}
+
+/// Users who asked for the monthly summary and haven't been sent the one of
+/// `month`; only those who already had an account then.
+pub async fn get_monthly_summary_recipients(
+    pool: &PgPool,
+    month: Month,
+) -> DBResult<Vec<SummaryRecipient>> {
+    sqlx::query_as!(
+        SummaryRecipient,
+        r#"SELECT u.id, u.name, u.email
+           FROM users u
+           JOIN user_preferences p ON p.user_id = u.id
+           WHERE p.monthly_summary
+             AND u.created_at < $1::DATE + INTERVAL '1 month'
+             AND NOT EXISTS (
+                 SELECT 1 FROM monthly_summaries_sent s WHERE s.user_id = u.id AND s.month = $1
+             )
+           ORDER BY u.id"#,
+        month.0
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn mark_monthly_summary_sent(pool: &PgPool, user_id: i32, month: Month) -> DBResult<()> {
+    sqlx::query!(
+        "INSERT INTO monthly_summaries_sent (user_id, month) VALUES ($1, $2)
+         ON CONFLICT DO NOTHING",
+        user_id,
+        month.0
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+//
+// Consumption rules
+//
+
+pub async fn list_consumption_rules(pool: &PgPool, user_id: i32) -> DBResult<Vec<ConsumptionRule>> {
+    sqlx::query_as!(
+        ConsumptionRule,
+        "SELECT r.id, r.item_id, i.name AS item_name, r.quantity, r.every_days, r.next_run_at
+         FROM consumption_rules r
+         JOIN items i ON i.id = r.item_id
+         WHERE r.user_id = $1
+         ORDER BY r.next_run_at, i.name",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_consumption_rule(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: i32,
+) -> DBResult<Option<ConsumptionRule>> {
+    sqlx::query_as!(
+        ConsumptionRule,
+        "SELECT r.id, r.item_id, i.name AS item_name, r.quantity, r.every_days, r.next_run_at
+         FROM consumption_rules r
+         JOIN items i ON i.id = r.item_id
+         WHERE r.user_id = $1 AND r.item_id = $2",
+        user_id,
+        item_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Sets the rule of one of the user's items, replacing the one it had. The
+/// first units are taken one period from now. `None` if there is no such
+/// item.
+pub async fn set_consumption_rule(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: i32,
+    payload: &ConsumptionRulePayload,
+) -> DBResult<Option<ConsumptionRule>> {
+    sqlx::query_as!(
+        ConsumptionRule,
+        r#"WITH rule AS (
+               INSERT INTO consumption_rules (user_id, item_id, quantity, every_days, next_run_at)
+               SELECT user_id, id, $3, $4, NOW() + make_interval(days => $4)
+               FROM items WHERE user_id = $1 AND id = $2
+               ON CONFLICT (item_id) DO UPDATE
+               SET quantity = EXCLUDED.quantity,
+                   every_days = EXCLUDED.every_days,
+                   next_run_at = EXCLUDED.next_run_at
+               RETURNING id, item_id, quantity, every_days, next_run_at
+           )
+           SELECT rule.id, rule.item_id, i.name AS item_name, rule.quantity, rule.every_days,
+                  rule.next_run_at
+           FROM rule
+           JOIN items i ON i.id = rule.item_id"#,
+        user_id,
+        item_id,
+        payload.quantity,
+        payload.every_days
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn delete_consumption_rule(pool: &PgPool, user_id: i32, item_id: i32) -> DBResult<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM consumption_rules WHERE user_id = $1 AND item_id = $2",
+        user_id,
+        item_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Moves every rule that is due at `now` past it and returns what each one
+/// takes as `(user_id, item_id, quantity)`. A rule that missed runs, e.g.
+/// while the server was down, takes the units of all of them at once.
+pub async fn take_due_consumption_rules(
+    pool: &PgPool,
+    now: time::OffsetDateTime,
+) -> DBResult<Vec<(i32, i32, i32)>> {
+    let rows = sqlx::query!(
+        r#"WITH due AS (
+               SELECT id,
+                      1 + FLOOR(EXTRACT(EPOCH FROM $1 - next_run_at) / (every_days * 86400))::INTEGER
+                          AS runs
+               FROM consumption_rules
+               WHERE next_run_at <= $1
+               FOR UPDATE
+           )
+           UPDATE consumption_rules r
+           SET next_run_at = r.next_run_at + make_interval(days => r.every_days * due.runs)
+           FROM due
+           WHERE r.id = due.id
+           RETURNING r.user_id, r.item_id, r.quantity * due.runs AS "quantity!""#,
+        now
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.user_id, row.item_id, row.quantity))
+        .collect())
+}
+
+//
+// Stocktakes
+//
+
+pub async fn get_stocktakes(pool: &PgPool, user_id: i32) -> DBResult<Vec<Stocktake>> {
+    sqlx::query_as!(
+        Stocktake,
+        "SELECT id, status, created_at, completed_at FROM stocktakes
+         WHERE user_id = $1
+         ORDER BY created_at DESC",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Deletes all of a user's stocktakes, e.g. when the demo account is reset.
+pub async fn delete_stocktakes(pool: &PgPool, user_id: i32) -> DBResult<()> {
+    sqlx::query!("DELETE FROM stocktakes WHERE user_id = $1", user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Starts a stocktake by snapshotting the current quantity of every item.
+/// If a stocktake is already in progress, that one is returned instead.
+pub async fn start_stocktake(pool: &PgPool, user_id: i32) -> DBResult<Stocktake> {
+    let mut tx = pool.begin().await?;
+
+    // Two starts at once (a double click, two devices) both get here; the
+    // second waits for the first and then inserts nothing
+    let started = sqlx::query_as!(
+        Stocktake,
+        "INSERT INTO stocktakes (user_id) VALUES ($1)
+         ON CONFLICT (user_id) WHERE status = 'open' DO NOTHING
+         RETURNING id, status, created_at, completed_at",
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(stocktake) = started else {
+        tx.rollback().await?;
+        return sqlx::query_as!(
+            Stocktake,
+            "SELECT id, status, created_at, completed_at FROM stocktakes
+             WHERE user_id = $1 AND status = 'open'",
+            user_id
+        )
+        .fetch_one(pool)
+        .await;
+    };
+
+    sqlx::query!(
+        "INSERT INTO stocktake_entries (stocktake_id, item_id, item_name, expected_quantity)
+         SELECT $1, id, name, quantity FROM items WHERE user_id = $2",
+        stocktake.id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(stocktake)
+}
+
+pub async fn get_stocktake(
+    pool: &PgPool,
+    user_id: i32,
+    stocktake_id: i32,
+) -> DBResult<Option<StocktakeWithEntries>> {
+    let stocktake = sqlx::query_as!(
+        Stocktake,
+        "SELECT id, status, created_at, completed_at FROM stocktakes
+         WHERE user_id = $1 AND id = $2",
+        user_id,
+        stocktake_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(stocktake) = stocktake else {
+        return Ok(None);
+    };
+
+    // Entries are ordered by category so the count can follow the shelves
+    let entries = sqlx::query_as!(
+        StocktakeEntry,
+        r#"
+        SELECT
+            se.item_id,
+            se.item_name,
+            c.name AS "category_name: Option<String>",
+            se.expected_quantity,
+            se.counted_quantity,
+            se.counted_quantity - se.expected_quantity AS "variance"
+        FROM stocktake_entries se
+        LEFT JOIN items i ON i.id = se.item_id
+        LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
+        WHERE se.stocktake_id = $1
+        ORDER BY c.name NULLS LAST, se.item_name
+        "#,
+        stocktake.id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some(StocktakeWithEntries { stocktake, entries }))
+}
+
+/// Saves counted quantities for an open stocktake.
+/// Returns `false` if the stocktake does not exist or is no longer open.
+pub async fn update_stocktake_counts(
+    pool: &PgPool,
+    user_id: i32,
+    stocktake_id: i32,
+    counts: &[StocktakeCount],
+) -> DBResult<bool> {
+    let is_open = sqlx::query_scalar!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM stocktakes WHERE user_id = $1 AND id = $2 AND status = 'open'
+        ) AS "exists!""#,
+        user_id,
+        stocktake_id
+    )
+    .fetch_one(pool)
+    .await?;
+    if !is_open {
+        return Ok(false);
+    }
+
+    let item_ids: Vec<i32> = counts.iter().map(|c| c.item_id).collect();
+    let counted: Vec<i32> = counts.iter().map(|c| c.counted_quantity).collect();
+    sqlx::query!(
+        "UPDATE stocktake_entries se
+         SET counted_quantity = c.counted_quantity
+         FROM UNNEST($2::INTEGER[], $3::INTEGER[]) AS c(item_id, counted_quantity)
+         WHERE se.stocktake_id = $1 AND se.item_id = c.item_id",
+        stocktake_id,
+        &item_ids,
+        &counted
+    )
+    .execute(pool)
+    .await?;
+    Ok(true)
+}
+
+/// Applies all counted quantities in one transaction and closes the stocktake.
+/// Expected quantities are refreshed first, so the variance report reflects
+/// any use/purchase that happened while the count was in progress.
+pub async fn complete_stocktake(
+    pool: &PgPool,
+    user_id: i32,
+    actor_id: i32,
+    stocktake_id: i32,
+) -> DBResult<Option<StocktakeWithEntries>> {
+    let mut tx = pool.begin().await?;
+
+    let locked = sqlx::query_scalar!(
+        "SELECT id FROM stocktakes WHERE user_id = $1 AND id = $2 AND status = 'open' FOR UPDATE",
+        user_id,
+        stocktake_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if locked.is_none() {
+        return Ok(None);
+    }
+
+    sqlx::query!(
+        "UPDATE stocktake_entries se
+         SET expected_quantity = i.quantity
+         FROM items i
+         WHERE se.stocktake_id = $1 AND i.id = se.item_id AND i.user_id = $2",
+        stocktake_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO item_events (user_id, item_id, item_name, kind, quantity_delta, actor_id)
+         SELECT i.user_id, i.id, i.name, 'stocktake', se.counted_quantity - i.quantity, $3
+         FROM stocktake_entries se
+         JOIN items i ON i.id = se.item_id AND i.user_id = $2
+         WHERE se.stocktake_id = $1
+           AND se.counted_quantity IS NOT NULL
+           AND se.counted_quantity <> i.quantity",
+        stocktake_id,
+        user_id,
+        actor_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE items i
+         SET quantity = se.counted_quantity, updated_at = NOW()
+         FROM stocktake_entries se
+         WHERE se.stocktake_id = $1
+           AND i.id = se.item_id
+           AND i.user_id = $2
+           AND se.counted_quantity IS NOT NULL
+           AND se.counted_quantity <> i.quantity",
+        stocktake_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let item_ids = sqlx::query_scalar!(
+        r#"SELECT item_id AS "item_id!" FROM stocktake_entries WHERE stocktake_id = $1 AND item_id IS NOT NULL"#,
+        stocktake_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    sync_item_batches(&mut tx, &item_ids).await?;
+
+    sqlx::query!(
+        "UPDATE stocktakes SET status = 'completed', completed_at = NOW() WHERE id = $1",
+        stocktake_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    get_stocktake(pool, user_id, stocktake_id).await
+}
+
+/// Discards an open stocktake without touching item quantities.
+pub async fn cancel_stocktake(pool: &PgPool, user_id: i32, stocktake_id: i32) -> DBResult<u64> {
+    sqlx::query!(
+        "DELETE FROM stocktakes WHERE user_id = $1 AND id = $2 AND status = 'open'",
+        user_id,
+        stocktake_id
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+//
+// Recipes
+//
+
+fn assemble_recipe(recipe: Recipe, ingredients: Vec<RecipeIngredient>) -> RecipeWithIngredients {
+    let availability = recipes::check_availability(&ingredients);
+    RecipeWithIngredients {
+        recipe,
+        ingredients,
+        availability,
+    }
+}
+
+pub async fn get_recipes(pool: &PgPool, user_id: i32) -> DBResult<Vec<RecipeWithIngredients>> {
+    let recipe_rows = sqlx::query_as!(
+        Recipe,
+        "SELECT id, name, instructions FROM recipes WHERE user_id = $1 ORDER BY name",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let ingredient_rows = sqlx::query_as!(
+        RecipeIngredient,
+        r#"
+        SELECT
+            ri.recipe_id,
+            ri.item_id,
+            i.name AS item_name,
+            ri.quantity,
+            i.quantity AS available_quantity
+        FROM recipe_ingredients ri
+        JOIN recipes r ON r.id = ri.recipe_id
+        JOIN items i ON i.id = ri.item_id
+        WHERE r.user_id = $1
+        ORDER BY i.name
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut ingredients_by_recipe: HashMap<i32, Vec<RecipeIngredient>> = HashMap::new();
+    for ingredient in ingredient_rows {
+        ingredients_by_recipe
+            .entry(ingredient.recipe_id)
+            .or_default()
+            .push(ingredient);
+    }
+
+    Ok(recipe_rows
+        .into_iter()
+        .map(|recipe| {
+            let ingredients = ingredients_by_recipe.remove(&recipe.id).unwrap_or_default();
+            assemble_recipe(recipe, ingredients)
+        })
+        .collect())
+}
+
+pub async fn get_recipe(
+    pool: &PgPool,
+    user_id: i32,
+    recipe_id: i32,
+) -> DBResult<Option<RecipeWithIngredients>> {
+    let recipe = sqlx::query_as!(
+        Recipe,
+        "SELECT id, name, instructions FROM recipes WHERE user_id = $1 AND id = $2",
+        user_id,
+        recipe_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(recipe) = recipe else {
+        return Ok(None);
+    };
+
+    let ingredients = sqlx::query_as!(
+        RecipeIngredient,
+        r#"
+        SELECT
+            ri.recipe_id,
+            ri.item_id,
+            i.name AS item_name,
+            ri.quantity,
+            i.quantity AS available_quantity
+        FROM recipe_ingredients ri
+        JOIN items i ON i.id = ri.item_id
+        WHERE ri.recipe_id = $1
+        ORDER BY i.name
+        "#,
+        recipe.id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some(assemble_recipe(recipe, ingredients)))
+}
+
+/// Replaces the ingredient list of a recipe.
+/// Items that do not belong to the user are silently skipped.
+async fn replace_recipe_ingredients(
+    conn: &mut sqlx::PgConnection,
+    user_id: i32,
+    recipe_id: i32,
+    payload: &CreateRecipePayload,
+) -> DBResult<()> {
+    sqlx::query!(
+        "DELETE FROM recipe_ingredients WHERE recipe_id = $1",
+        recipe_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let item_ids: Vec<i32> = payload.ingredients.iter().map(|i| i.item_id).collect();
+    let quantities: Vec<i32> = payload.ingredients.iter().map(|i| i.quantity).collect();
+    sqlx::query!(
+        "INSERT INTO recipe_ingredients (recipe_id, item_id, quantity)
+         SELECT $1, i.id, u.quantity
+         FROM UNNEST($3::INTEGER[], $4::INTEGER[]) AS u(item_id, quantity)
+         JOIN items i ON i.id = u.item_id AND i.user_id = $2
+         ON CONFLICT (recipe_id, item_id) DO UPDATE SET quantity = EXCLUDED.quantity",
+        recipe_id,
+        user_id,
+        &item_ids,
+        &quantities
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn create_recipe(
+    pool: &PgPool,
+    user_id: i32,
+    payload: CreateRecipePayload,
+) -> DBResult<RecipeWithIngredients> {
+    let mut tx = pool.begin().await?;
+    let recipe_id = sqlx::query_scalar!(
+        "INSERT INTO recipes (user_id, name, instructions) VALUES ($1, $2, $3) RETURNING id",
+        user_id,
+        payload.name,
+        payload.instructions
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    replace_recipe_ingredients(&mut tx, user_id, recipe_id, &payload).await?;
+    tx.commit().await?;
+
+    get_recipe(pool, user_id, recipe_id)
+        .await
+        .and_then(|opt_recipe| opt_recipe.ok_or(SqlxError::RowNotFound))
+}
+
+pub async fn update_recipe(
+    pool: &PgPool,
+    user_id: i32,
+    recipe_id: i32,
+    payload: CreateRecipePayload,
+) -> DBResult<Option<RecipeWithIngredients>> {
+    let mut tx = pool.begin().await?;
+    let affected_rows = sqlx::query!(
+        "UPDATE recipes SET name = $1, instructions = $2 WHERE user_id = $3 AND id = $4",
+        payload.name,
+        payload.instructions,
+        user_id,
+        recipe_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if affected_rows == 0 {
+        return Ok(None);
+    }
+    replace_recipe_ingredients(&mut tx, user_id, recipe_id, &payload).await?;
+    tx.commit().await?;
+
+    get_recipe(pool, user_id, recipe_id).await
+}
+
+pub async fn delete_recipe(pool: &PgPool, user_id: i32, recipe_id: i32) -> DBResult<u64> {
+    sqlx::query!(
+        "DELETE FROM recipes WHERE user_id = $1 AND id = $2",
+        user_id,
+        recipe_id
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+/// Decrements every ingredient of a recipe in a single transaction and
+/// logs each decrement as a `used` item event. Nothing is changed if any
+/// ingredient is short.
+pub async fn cook_recipe(
+    pool: &PgPool,
+    user_id: i32,
+    actor_id: i32,
+    recipe_id: i32,
+) -> DBResult<CookOutcome> {
+    let mut tx = pool.begin().await?;
+    let outcome = cook_recipe_in_tx(&mut tx, user_id, actor_id, recipe_id).await?;
+    if let CookOutcome::Cooked = outcome {
+        tx.commit().await?;
+    }
+    Ok(outcome)
+}
+
+/// Does the work of `cook_recipe` on a connection that is already inside a
+/// transaction. The caller is responsible for committing.
+async fn cook_recipe_in_tx(
+    conn: &mut sqlx::PgConnection,
+    user_id: i32,
+    actor_id: i32,
+    recipe_id: i32,
+) -> DBResult<CookOutcome> {
+    let exists = sqlx::query_scalar!(
+        "SELECT id FROM recipes WHERE user_id = $1 AND id = $2",
+        user_id,
+        recipe_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    if exists.is_none() {
+        return Ok(CookOutcome::RecipeNotFound);
+    }
+
+    // Lock the ingredient rows so concurrent use/purchase can't invalidate the check
+    let ingredients = sqlx::query_as!(
+        RecipeIngredient,
+        r#"
+        SELECT
+            ri.recipe_id,
+            ri.item_id,
+            i.name AS item_name,
+            ri.quantity,
+            i.quantity AS available_quantity
+        FROM recipe_ingredients ri
+        JOIN items i ON i.id = ri.item_id
+        WHERE ri.recipe_id = $1
+        ORDER BY i.id
+        FOR UPDATE OF i
+        "#,
+        recipe_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let availability = recipes::check_availability(&ingredients);
+    if !availability.can_make {
+        return Ok(CookOutcome::MissingIngredients(availability.missing));
+    }
+
+    sqlx::query!(
+        "UPDATE items i
+         SET quantity = i.quantity - ri.quantity, updated_at = NOW()
+         FROM recipe_ingredients ri
+         WHERE ri.recipe_id = $1 AND i.id = ri.item_id",
+        recipe_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    let item_ids: Vec<i32> = ingredients.iter().map(|i| i.item_id).collect();
+    sync_item_batches(conn, &item_ids).await?;
+
+    sqlx::query!(
+        "INSERT INTO item_events (user_id, item_id, item_name, kind, quantity_delta, actor_id)
+         SELECT i.user_id, i.id, i.name, 'used', -ri.quantity, $2
+         FROM recipe_ingredients ri
+         JOIN items i ON i.id = ri.item_id
+         WHERE ri.recipe_id = $1",
+        recipe_id,
+        actor_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(CookOutcome::Cooked)
+}
+
+//
+// Meal planning
+//
+
+pub async fn get_meal_plan(
+    pool: &PgPool,
+    user_id: i32,
+    from: Date,
+    to: Date,
+) -> DBResult<Vec<MealPlanEntry>> {
+    sqlx::query_as!(
+        MealPlanEntry,
+        r#"
+        SELECT
+            mp.id,
+            mp.recipe_id,
+            r.name AS recipe_name,
+            mp.planned_for,
+            mp.cooked_at
+        FROM meal_plans mp
+        JOIN recipes r ON r.id = mp.recipe_id
+        WHERE mp.user_id = $1 AND mp.planned_for BETWEEN $2 AND $3
+        ORDER BY mp.planned_for, mp.id
+        "#,
+        user_id,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Plans a recipe for a given day.
+/// Returns `None` if the recipe does not belong to the user.
+pub async fn add_meal_plan_entry(
+    pool: &PgPool,
+    user_id: i32,
+    payload: CreateMealPlanPayload,
+) -> DBResult<Option<MealPlanEntry>> {
+    sqlx::query_as!(
+        MealPlanEntry,
+        r#"
+        WITH inserted AS (
+            INSERT INTO meal_plans (user_id, recipe_id, planned_for)
+            SELECT $1, r.id, $3 FROM recipes r WHERE r.user_id = $1 AND r.id = $2
+            RETURNING id, recipe_id, planned_for, cooked_at
+        )
+        SELECT
+            inserted.id,
+            inserted.recipe_id,
+            r.name AS recipe_name,
+            inserted.planned_for,
+            inserted.cooked_at
+        FROM inserted
+        JOIN recipes r ON r.id = inserted.recipe_id
+        "#,
+        user_id,
+        payload.recipe_id,
+        payload.planned_for
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn delete_meal_plan_entry(pool: &PgPool, user_id: i32, entry_id: i32) -> DBResult<u64> {
+    sqlx::query!(
+        "DELETE FROM meal_plans WHERE user_id = $1 AND id = $2",
+        user_id,
+        entry_id
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+/// Cooks the recipe of a planned meal and marks the meal as cooked, releasing its reservation.
+/// Returns `None` if the meal does not exist or has already been cooked.
+pub async fn cook_planned_meal(
+    pool: &PgPool,
+    user_id: i32,
+    actor_id: i32,
+    entry_id: i32,
+) -> DBResult<Option<CookOutcome>> {
+    let mut tx = pool.begin().await?;
+
+    let recipe_id = sqlx::query_scalar!(
+        "SELECT recipe_id FROM meal_plans
+         WHERE user_id = $1 AND id = $2 AND cooked_at IS NULL
+         FOR UPDATE",
+        user_id,
+        entry_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(recipe_id) = recipe_id else {
+        return Ok(None);
+    };
+
+    let outcome = cook_recipe_in_tx(&mut tx, user_id, actor_id, recipe_id).await?;
+    if let CookOutcome::Cooked = outcome {
+        sqlx::query!(
+            "UPDATE meal_plans SET cooked_at = NOW() WHERE id = $1",
+            entry_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+    }
+    Ok(Some(outcome))
+}
+
+//
+// Product catalog
+//
+
+/// The user's catalog products matching `query`: the one with that exact
+/// barcode first, then names starting with it, then names containing it,
+/// the most recently used first within each.
+pub async fn search_catalog(
+    pool: &PgPool,
+    user_id: i32,
+    query: &str,
+    limit: i64,
+) -> DBResult<Vec<CatalogProduct>> {
+    sqlx::query_as!(
+        CatalogProduct,
+        r#"
+        SELECT p.id, p.name, p.barcode, p.category_id, p.unit, p.restock_threshold,
+               i.id AS "item_id?", p.last_used_at
+        FROM catalog_products p
+        LEFT JOIN items i ON i.user_id = p.user_id AND i.name = p.name
+        WHERE p.user_id = $1
+          AND ($2 = '' OR p.barcode = $2 OR STRPOS(LOWER(p.name), LOWER($2)) > 0)
+        ORDER BY COALESCE(p.barcode = $2, FALSE) DESC,
+                 STRPOS(LOWER(p.name), LOWER($2)) = 1 DESC,
+                 p.last_used_at DESC, p.name
+        LIMIT $3
+        "#,
+        user_id,
+        query,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Removes a product from the catalog; its item, if any, stays.
+pub async fn delete_catalog_product(pool: &PgPool, user_id: i32, id: i32) -> DBResult<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM catalog_products WHERE user_id = $1 AND id = $2",
+        user_id,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+//
+// Stores
+//
+
+/// The user's stores in the order they are visited.
+pub async fn list_stores(pool: &PgPool, user_id: i32) -> DBResult<Vec<Store>> {
+    sqlx::query_as!(
+        Store,
+        "SELECT id, name FROM stores WHERE user_id = $1 ORDER BY sort_order, name",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Adds a store, visited after all the others.
+pub async fn create_store(pool: &PgPool, user_id: i32, name: &str) -> DBResult<Store> {
+    sqlx::query_as!(
+        Store,
+        "INSERT INTO stores (user_id, name, sort_order)
+         VALUES ($1, $2, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM stores WHERE user_id = $1))
+         RETURNING id, name",
+        user_id,
+        name
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn rename_store(
+    pool: &PgPool,
+    user_id: i32,
+    store_id: i32,
+    name: &str,
+) -> DBResult<Option<Store>> {
+    sqlx::query_as!(
+        Store,
+        "UPDATE stores SET name = $3 WHERE user_id = $1 AND id = $2 RETURNING id, name",
+        user_id,
+        store_id,
+        name
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Stores a new visiting order, see [`reorder_items`].
+pub async fn reorder_stores(pool: &PgPool, user_id: i32, store_ids: &[i32]) -> DBResult<u64> {
+    sqlx::query!(
+        "UPDATE stores s
+         SET sort_order = o.position - 1
+         FROM UNNEST($2::INTEGER[]) WITH ORDINALITY AS o(id, position)
+         WHERE s.id = o.id AND s.user_id = $1",
+        user_id,
+        store_ids
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+/// Deletes a store; its items no longer have a preferred one.
+pub async fn delete_store(pool: &PgPool, user_id: i32, store_id: i32) -> DBResult<u64> {
+    sqlx::query!(
+        "DELETE FROM stores WHERE user_id = $1 AND id = $2",
+        user_id,
+        store_id
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+//
+// Custom item fields
+//
+
+pub async fn list_attribute_fields(pool: &PgPool, user_id: i32) -> DBResult<Vec<AttributeField>> {
+    sqlx::query_as!(
+        AttributeField,
+        r#"SELECT id, name, kind AS "kind: AttributeKind" FROM attribute_fields
+           WHERE user_id = $1 ORDER BY sort_order, name"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Adds a field, shown after all the others.
+pub async fn create_attribute_field(
+    pool: &PgPool,
+    user_id: i32,
+    name: &str,
+    kind: AttributeKind,
+) -> DBResult<AttributeField> {
+    sqlx::query_as!(
+        AttributeField,
+        r#"INSERT INTO attribute_fields (user_id, name, kind, sort_order)
+           VALUES ($1, $2, $3, (SELECT COALESCE(MAX(sort_order) + 1, 0)
+                                FROM attribute_fields WHERE user_id = $1))
+           RETURNING id, name, kind AS "kind: AttributeKind""#,
+        user_id,
+        name.trim(),
+        kind as AttributeKind
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Deletes a field together with its values on every item.
+pub async fn delete_attribute_field(pool: &PgPool, user_id: i32, field_id: i32) -> DBResult<u64> {
+    let mut tx = pool.begin().await?;
+    let Some(name) = sqlx::query_scalar!(
+        "DELETE FROM attribute_fields WHERE user_id = $1 AND id = $2 RETURNING name",
+        user_id,
+        field_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(0);
+    };
+    sqlx::query!(
+        "UPDATE items SET attributes = attributes - $2, updated_at = NOW()
+         WHERE user_id = $1 AND attributes ? $2",
+        user_id,
+        name
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(1)
+}
+
+/// Items whose custom field values include all of `filter`, in manual order.
+pub async fn get_items_with_attributes(
+    pool: &PgPool,
+    user_id: i32,
+    filter: &Attributes,
+) -> DBResult<Vec<Item>> {
+    let rows = sqlx::query_as!(
+        FlatItemRow,
+        r#"
+        SELECT
+            i.id,
+            i.name,
+            i.quantity,
+            i.restock_threshold,
+            i.restock_to,
+            i.unit,
+            i.location,
+            i.barcode,
+            i.notes,
+            i.preferred_store_id,
+            i.quantity_step,
+            i.attributes AS "attributes: Json<Attributes>",
+            i.version,
+            i.created_at,
+            i.updated_at,
+            c.id AS "category_id: Option<i32>",
+            c.name AS "category_name: Option<String>",
+            c.color AS "category_color: Option<String>",
+            c.parent_id AS category_parent_id,
+            c.icon AS category_icon
+        FROM items i
+        LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
+        WHERE i.user_id = $1 AND i.attributes @> $2
+        ORDER BY i.sort_order, i.name
+        "#,
+        user_id,
+        Json(filter) as _
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(Item::from).collect())
+}
+
+//
+// Shopping list
+//
+
+/// Items that need buying: everything below its restock threshold plus
+/// ingredients reserved by upcoming meals that current stock doesn't cover.
+/// `to_buy` brings the item up to its restock target (or threshold) after the
+/// planned meals are cooked.
+pub async fn get_shopping_list(pool: &PgPool, user_id: i32) -> DBResult<Vec<ShoppingListEntry>> {
+    sqlx::query_as!(
+        ShoppingListEntry,
+        r#"
+        WITH reserved AS (
+            SELECT ri.item_id, SUM(ri.quantity) AS quantity
+            FROM meal_plans mp
+            JOIN recipe_ingredients ri ON ri.recipe_id = mp.recipe_id
+            WHERE mp.user_id = $1 AND mp.cooked_at IS NULL AND mp.planned_for >= CURRENT_DATE
+            GROUP BY ri.item_id
+        )
+        SELECT
+            i.id AS item_id,
+            i.name AS item_name,
+            c.name AS "category_name: Option<String>",
+            c.color AS "category_color: Option<String>",
+            s.name AS "store_name: Option<String>",
+            i.quantity,
+            i.unit,
+            i.restock_threshold,
+            i.restock_to,
+            COALESCE(r.quantity, 0) AS "reserved!",
+            COALESCE(r.quantity, 0) + GREATEST(i.restock_to, i.restock_threshold) - i.quantity AS "to_buy!"
+        FROM items i
+        LEFT JOIN reserved r ON r.item_id = i.id
+        LEFT JOIN categories c ON c.id = i.category_id AND c.user_id = i.user_id
+        LEFT JOIN stores s ON s.id = i.preferred_store_id AND s.user_id = i.user_id
+        WHERE i.user_id = $1
+          AND COALESCE(r.quantity, 0) + i.restock_threshold - i.quantity > 0
+        -- Stores in the order they are visited, then as before within each
+        ORDER BY s.sort_order NULLS LAST, s.name, c.name NULLS LAST, i.name
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+//
+// Account management
+//
+
+/// Create a new account
+pub async fn create_account(
+    pool: &PgPool,
+    name: &str,
+    email: &str,
+    hashed_password: &str,
+) -> DBResult<Account> {
+    sqlx::query_as!(
+        Account,
+        "INSERT INTO users (name, email, password) VALUES ($1, $2, $3)
+         RETURNING id, name, email, password, created_at, updated_at",
+        name,
+        email,
+        hashed_password
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// The user's preferences, or the defaults if they never saved any.
+pub async fn get_user_preferences(
+    executor: impl PgExecutor<'_>,
+    user_id: i32,
+) -> DBResult<UserPreferences> {
+    let preferences = sqlx::query_as!(
+        UserPreferences,
+        r#"SELECT group_by_category, sort AS "sort: _", language AS "language: _",
+                  theme AS "theme: _", layout AS "layout: _", default_location,
+                  monthly_summary, restock_escalation_days, collapsed_category_ids, timezone
+           FROM user_preferences WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(preferences.unwrap_or_default())
+}
+
+/// Folds or unfolds a category group of the item list. Ids of categories
+/// deleted since are dropped on the way. Returns `false` when the user has no
+/// such category.
+pub async fn set_category_collapsed(
+    pool: &PgPool,
+    user_id: i32,
+    category_id: i32,
+    collapsed: bool,
+) -> DBResult<bool> {
+    let saved = sqlx::query_scalar!(
+        r#"INSERT INTO user_preferences (user_id, collapsed_category_ids)
+           SELECT $1, ARRAY(
+               SELECT c.id FROM categories c
+               WHERE c.user_id = $1
+                 AND CASE WHEN c.id = $2 THEN $3
+                          ELSE c.id = ANY(COALESCE(
+                              (SELECT collapsed_category_ids FROM user_preferences WHERE user_id = $1),
+                              '{}'))
+                     END
+               ORDER BY c.id)
+           WHERE EXISTS (SELECT 1 FROM categories WHERE user_id = $1 AND id = $2)
+           ON CONFLICT (user_id) DO UPDATE
+           SET collapsed_category_ids = EXCLUDED.collapsed_category_ids, updated_at = NOW()
+           RETURNING user_id"#,
+        user_id,
+        category_id,
+        collapsed
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(saved.is_some())
+}
+
+pub async fn update_user_preferences(
+    pool: &PgPool,
+    user_id: i32,
+    payload: UpdatePreferencesPayload,
+) -> DBResult<UserPreferences> {
+    let mut preferences = get_user_preferences(pool, user_id).await?;
+    preferences.apply(payload);
+    sqlx::query_as!(
+        UserPreferences,
+        r#"INSERT INTO user_preferences (user_id, group_by_category, sort, language, theme, layout,
+                                        default_location, monthly_summary, restock_escalation_days,
+                                        timezone)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+           ON CONFLICT (user_id) DO UPDATE
+           SET group_by_category = EXCLUDED.group_by_category,
+               sort = EXCLUDED.sort,
+               language = EXCLUDED.language,
+               theme = EXCLUDED.theme,
+               layout = EXCLUDED.layout,
+               default_location = EXCLUDED.default_location,
+               monthly_summary = EXCLUDED.monthly_summary,
+               restock_escalation_days = EXCLUDED.restock_escalation_days,
+               timezone = EXCLUDED.timezone,
+               updated_at = NOW()
+           RETURNING group_by_category, sort AS "sort: _", language AS "language: _",
+                     theme AS "theme: _", layout AS "layout: _", default_location,
+                     monthly_summary, restock_escalation_days, collapsed_category_ids, timezone"#,
+        user_id,
+        preferences.group_by_category,
+        preferences.sort as ItemSort,
+        preferences.language as Language,
+        preferences.theme as Theme,
+        preferences.layout as ItemLayout,
+        preferences.default_location,
+        preferences.monthly_summary,
+        preferences.restock_escalation_days,
+        preferences.timezone
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Fetch an account by email (for login)
+pub async fn get_account_by_email(pool: &PgPool, email: &str) -> DBResult<Option<Account>> {
+    sqlx::query_as!(
+        Account,
+        "SELECT id, name, email, password, created_at, updated_at FROM users WHERE email = $1",
+        email
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Replaces the password hash of the account with `email`; false if there is none.
+pub async fn update_password(pool: &PgPool, email: &str, hashed_password: &str) -> DBResult<bool> {
+    let result = sqlx::query!(
+        "UPDATE users SET password = $2, updated_at = NOW() WHERE email = $1",
+        email,
+        hashed_password
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_user_by_id(pool: &PgPool, id: i32) -> DBResult<Option<Account>> {
+    sqlx::query_as!(
+        Account,
+        "SELECT id, name, email, password, created_at, updated_at FROM users WHERE id = $1",
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+//
+// Sessions
+//
+
+pub async fn create_session(
+    pool: &PgPool,
+    token: &str,
+    user_id: i32,
+    remember: bool,
+    lifetime: time::Duration,
+    user_agent: Option<&str>,
+    ip_address: Option<&str>,
+) -> DBResult<()> {
+    sqlx::query!(
+        "INSERT INTO sessions (token, user_id, remember, expires_at, user_agent, ip_address)
+         VALUES ($1, $2, $3, NOW() + make_interval(secs => $4), $5, $6)",
+        token,
+        user_id,
+        remember,
+        lifetime.as_seconds_f64(),
+        user_agent,
+        ip_address
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The session with `token`, unless it has expired. The household it works
+/// in is left out once the user is no longer a member of it.
+pub async fn get_session(pool: &PgPool, token: &str) -> DBResult<Option<UserSession>> {
+    sqlx::query_as!(
+        UserSession,
+        r#"SELECT s.id, s.user_id, s.remember, s.last_seen_at,
+                  m.household_id AS "household_id?",
+                  m.role AS "household_role?: HouseholdRole"
+           FROM sessions s
+           LEFT JOIN household_members m
+               ON m.household_id = s.household_id AND m.user_id = s.user_id
+           WHERE s.token = $1 AND s.expires_at > NOW()"#,
+        token
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Marks the session as used now and pushes its expiry `lifetime` ahead.
+pub async fn touch_session(pool: &PgPool, token: &str, lifetime: time::Duration) -> DBResult<()> {
+    sqlx::query!(
+        "UPDATE sessions
+         SET last_seen_at = NOW(), expires_at = NOW() + make_interval(secs => $2)
+         WHERE token = $1",
+        token,
+        lifetime.as_seconds_f64()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The user's unexpired sessions, most recently used first, with
+/// `current_id` marked.
+pub async fn list_sessions(
+    pool: &PgPool,
+    user_id: i32,
+    current_id: i32,
+) -> DBResult<Vec<SessionInfo>> {
+    sqlx::query_as!(
+        SessionInfo,
+        r#"SELECT id, user_agent, ip_address, remember, created_at, last_seen_at, expires_at,
+                  id = $2 AS "current!"
+           FROM sessions
+           WHERE user_id = $1 AND expires_at > NOW()
+           ORDER BY last_seen_at DESC"#,
+        user_id,
+        current_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn delete_session(pool: &PgPool, token: &str) -> DBResult<()> {
+    sqlx::query!("DELETE FROM sessions WHERE token = $1", token)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Logs out one of the user's sessions. Returns the number of rows deleted.
+pub async fn revoke_session(pool: &PgPool, user_id: i32, session_id: i32) -> DBResult<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM sessions WHERE id = $1 AND user_id = $2",
+        session_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Logs out every session of the user except `keep_id`.
+pub async fn revoke_other_sessions(pool: &PgPool, user_id: i32, keep_id: i32) -> DBResult<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM sessions WHERE user_id = $1 AND id <> $2",
+        user_id,
+        keep_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn delete_expired_sessions(pool: &PgPool) -> DBResult<u64> {
+    let result = sqlx::query!("DELETE FROM sessions WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+//
+// Households
+//
+
+/// The households `user_id` is a member of, the one `session_id` works in
+/// marked.
+pub async fn list_households(
+    pool: &PgPool,
+    user_id: i32,
+    session_id: i32,
+) -> DBResult<Vec<Household>> {
+    sqlx::query_as!(
+        Household,
+        r#"SELECT m.household_id AS id, u.name AS owner, m.role AS "role: HouseholdRole",
+                  s.household_id IS NOT NULL AS "current!"
+           FROM household_members m
+           JOIN users u ON u.id = m.household_id
+           LEFT JOIN sessions s ON s.id = $2 AND s.household_id = m.household_id
+           WHERE m.user_id = $1
+           ORDER BY u.name, m.household_id"#,
+        user_id,
+        session_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The role of `user_id` in the household of `household_id`, if they are a
+/// member.
+pub async fn get_household_role(
+    pool: &PgPool,
+    household_id: i32,
+    user_id: i32,
+) -> DBResult<Option<HouseholdRole>> {
+    sqlx::query_scalar!(
+        r#"SELECT role AS "role: HouseholdRole" FROM household_members
+           WHERE household_id = $1 AND user_id = $2"#,
+        household_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn list_household_members(
+    pool: &PgPool,
+    household_id: i32,
+) -> DBResult<Vec<HouseholdMember>> {
+    sqlx::query_as!(
+        HouseholdMember,
+        r#"SELECT u.id AS user_id, u.name, u.email, m.role AS "role: HouseholdRole", m.created_at
+           FROM household_members m
+           JOIN users u ON u.id = m.user_id
+           WHERE m.household_id = $1
+           ORDER BY u.name, u.id"#,
+        household_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Invites the account with `email` into the household, or changes the role
+/// of its invitation.
+pub async fn invite_household_member(
+    pool: &PgPool,
+    household_id: i32,
+    email: &str,
+    role: HouseholdRole,
+) -> DBResult<HouseholdInvitation> {
+    sqlx::query_as!(
+        HouseholdInvitation,
+        r#"WITH invitation AS (
+               INSERT INTO household_invitations (household_id, email, role)
+               VALUES ($1, $2, $3)
+               ON CONFLICT (household_id, email) DO UPDATE SET role = EXCLUDED.role
+               RETURNING id, household_id, email, role, created_at
+           )
+           SELECT i.id, i.household_id, u.name AS owner, i.email,
+                  i.role AS "role: HouseholdRole", i.created_at
+           FROM invitation i
+           JOIN users u ON u.id = i.household_id"#,
+        household_id,
+        email,
+        role as HouseholdRole
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Whether the account with `email` is a member of the household already.
+pub async fn is_household_member_email(
+    pool: &PgPool,
+    household_id: i32,
+    email: &str,
+) -> DBResult<bool> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+               SELECT 1 FROM household_members m JOIN users u ON u.id = m.user_id
+               WHERE m.household_id = $1 AND u.email = $2
+           ) AS "exists!""#,
+        household_id,
+        email
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Invitations of the household not accepted yet.
+pub async fn list_household_invitations(
+    pool: &PgPool,
+    household_id: i32,
+) -> DBResult<Vec<HouseholdInvitation>> {
+    sqlx::query_as!(
+        HouseholdInvitation,
+        r#"SELECT i.id, i.household_id, u.name AS owner, i.email,
+                  i.role AS "role: HouseholdRole", i.created_at
+           FROM household_invitations i
+           JOIN users u ON u.id = i.household_id
+           WHERE i.household_id = $1
+           ORDER BY i.created_at, i.id"#,
+        household_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Invitations for the account with `email`, from every household.
+pub async fn list_invitations_for(
+    pool: &PgPool,
+    email: &str,
+) -> DBResult<Vec<HouseholdInvitation>> {
+    sqlx::query_as!(
+        HouseholdInvitation,
+        r#"SELECT i.id, i.household_id, u.name AS owner, i.email,
+                  i.role AS "role: HouseholdRole", i.created_at
+           FROM household_invitations i
+           JOIN users u ON u.id = i.household_id
+           WHERE i.email = $1 AND i.household_id <> (SELECT id FROM users WHERE email = $1)
+           ORDER BY i.created_at, i.id"#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Makes `user_id`, whose email is `email`, a member of the household of
+/// the invitation. `false` if there is no such invitation for them.
+pub async fn accept_household_invitation(
+    pool: &PgPool,
+    invitation_id: i32,
+    user_id: i32,
+    email: &str,
+) -> DBResult<bool> {
+    let mut tx = pool.begin().await?;
+    let invitation = sqlx::query!(
+        r#"DELETE FROM household_invitations
+           WHERE id = $1 AND email = $2 AND household_id <> $3
+           RETURNING household_id, role AS "role: HouseholdRole""#,
+        invitation_id,
+        email,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(invitation) = invitation else {
+        return Ok(false);
+    };
+    sqlx::query!(
+        "INSERT INTO household_members (household_id, user_id, role)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (household_id, user_id) DO UPDATE SET role = EXCLUDED.role",
+        invitation.household_id,
+        user_id,
+        invitation.role as HouseholdRole
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Withdraws an invitation of the household. Returns the number of rows
+/// deleted.
+pub async fn cancel_household_invitation(
+    pool: &PgPool,
+    household_id: i32,
+    invitation_id: i32,
+) -> DBResult<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM household_invitations WHERE id = $1 AND household_id = $2",
+        invitation_id,
+        household_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Turns down an invitation for the account with `email`. Returns the
+/// number of rows deleted.
+pub async fn decline_household_invitation(
+    pool: &PgPool,
+    email: &str,
+    invitation_id: i32,
+) -> DBResult<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM household_invitations WHERE id = $1 AND email = $2",
+        invitation_id,
+        email
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Changes the role of a member; `None` if `user_id` isn't one.
+pub async fn update_household_member(
+    pool: &PgPool,
+    household_id: i32,
+    user_id: i32,
+    role: HouseholdRole,
+) -> DBResult<Option<HouseholdMember>> {
+    sqlx::query_as!(
+        HouseholdMember,
+        r#"WITH member AS (
+               UPDATE household_members SET role = $3
+               WHERE household_id = $1 AND user_id = $2
+               RETURNING user_id, role, created_at
+           )
+           SELECT u.id AS user_id, u.name, u.email, m.role AS "role: HouseholdRole", m.created_at
+           FROM member m
+           JOIN users u ON u.id = m.user_id"#,
+        household_id,
+        user_id,
+        role as HouseholdRole
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Takes `user_id` out of the household. Their sessions working in it go
+/// back to their own inventory. Returns the number of rows deleted.
+pub async fn remove_household_member(
+    pool: &PgPool,
+    household_id: i32,
+    user_id: i32,
+) -> DBResult<u64> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query!(
+        "DELETE FROM household_members WHERE household_id = $1 AND user_id = $2",
+        household_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE sessions SET household_id = NULL, household_switched_at = NOW()
+         WHERE household_id = $1 AND user_id = $2",
+        household_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
+
+/// Makes the session work in the household of `household_id`, or in the
+/// user's own inventory for `None`.
+pub async fn set_session_household(
+    pool: &PgPool,
+    session_id: i32,
+    household_id: Option<i32>,
+) -> DBResult<()> {
+    sqlx::query!(
+        "UPDATE sessions SET household_id = $2, household_switched_at = NOW() WHERE id = $1",
+        session_id,
+        household_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// When the session last moved between inventories, if it ever did.
+pub async fn get_household_switched_at(
+    pool: &PgPool,
+    session_id: i32,
+) -> DBResult<Option<time::OffsetDateTime>> {
+    let switched_at = sqlx::query_scalar!(
+        "SELECT household_switched_at FROM sessions WHERE id = $1",
+        session_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(switched_at.flatten())
+}
+
+//
+// Share links
+//
+
+/// Stores a new link; category IDs that aren't the user's are dropped.
+pub async fn create_share_link(
+    pool: &PgPool,
+    user_id: i32,
+    token: &str,
+    label: &str,
+    scope: ShareScope,
+    category_ids: &[i32],
+) -> DBResult<ShareLink> {
+    let category_ids = match scope {
+        ShareScope::Categories => category_ids,
+        ShareScope::ShoppingList | ShareScope::Calendar | ShareScope::RestockFeed => &[],
+    };
+    sqlx::query_as!(
+        ShareLink,
+        r#"INSERT INTO share_links (token, user_id, label, scope, category_ids)
+           VALUES ($1, $2, $3, $4,
+                   ARRAY(SELECT id FROM categories WHERE user_id = $2 AND id = ANY($5) ORDER BY id))
+           RETURNING id, user_id, token, label, scope AS "scope: ShareScope", category_ids,
+                     created_at, last_viewed_at"#,
+        token,
+        user_id,
+        label.trim(),
+        scope as ShareScope,
+        category_ids
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn list_share_links(pool: &PgPool, user_id: i32) -> DBResult<Vec<ShareLink>> {
+    sqlx::query_as!(
+        ShareLink,
+        r#"SELECT id, user_id, token, label, scope AS "scope: ShareScope", category_ids,
+                  created_at, last_viewed_at
+           FROM share_links
+           WHERE user_id = $1
+           ORDER BY created_at DESC, id DESC"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The link with `token`, noting that it was used.
+pub async fn view_share_link(pool: &PgPool, token: &str) -> DBResult<Option<ShareLink>> {
+    sqlx::query_as!(
+        ShareLink,
+        r#"UPDATE share_links SET last_viewed_at = NOW()
+           WHERE token = $1
+           RETURNING id, user_id, token, label, scope AS "scope: ShareScope", category_ids,
+                     created_at, last_viewed_at"#,
+        token
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Revokes a link; returns 0 if the user has none with this ID.
+pub async fn delete_share_link(pool: &PgPool, user_id: i32, link_id: i32) -> DBResult<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM share_links WHERE id = $1 AND user_id = $2",
+        link_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn create_notification_channel(
+    pool: &PgPool,
+    user_id: i32,
+    payload: &CreateNotificationChannelPayload,
+) -> DBResult<NotificationChannel> {
+    let (room_id, access_token) = match payload.kind {
+        ChannelKind::Matrix => (
+            payload.room_id.as_deref().map(str::trim),
+            payload.access_token.as_deref().map(str::trim),
+        ),
+        ChannelKind::Apprise | ChannelKind::Url => (None, None),
+    };
+    sqlx::query_as!(
+        NotificationChannel,
+        r#"INSERT INTO notification_channels (user_id, label, kind, url, room_id, access_token,
+                                             escalation_only, delivery)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+           RETURNING id, label, kind AS "kind: ChannelKind", url, room_id, access_token,
+                     created_at, last_sent_at, last_error, escalation_only,
+                     delivery AS "delivery: ChannelDelivery""#,
+        user_id,
+        payload.label.trim(),
+        payload.kind as ChannelKind,
+        payload.url.trim(),
+        room_id,
+        access_token,
+        payload.escalation_only,
+        payload.delivery as ChannelDelivery
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn list_notification_channels(
+    pool: &PgPool,
+    user_id: i32,
+) -> DBResult<Vec<NotificationChannel>> {
+    sqlx::query_as!(
+        NotificationChannel,
+        r#"SELECT id, label, kind AS "kind: ChannelKind", url, room_id, access_token,
+                  created_at, last_sent_at, last_error, escalation_only,
+                  delivery AS "delivery: ChannelDelivery"
+           FROM notification_channels
+           WHERE user_id = $1
+           ORDER BY created_at, id"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_notification_channel(
+    pool: &PgPool,
+    user_id: i32,
+    channel_id: i32,
+) -> DBResult<Option<NotificationChannel>> {
+    sqlx::query_as!(
+        NotificationChannel,
+        r#"SELECT id, label, kind AS "kind: ChannelKind", url, room_id, access_token,
+                  created_at, last_sent_at, last_error, escalation_only,
+                  delivery AS "delivery: ChannelDelivery"
+           FROM notification_channels
+           WHERE id = $1 AND user_id = $2"#,
+        channel_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns 0 if the user has no channel with this ID.
+pub async fn delete_notification_channel(
+    pool: &PgPool,
+    user_id: i32,
+    channel_id: i32,
+) -> DBResult<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM notification_channels WHERE id = $1 AND user_id = $2",
+        channel_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Notes how sending to a channel went: `error` is `None` on success.
+pub async fn record_channel_attempt(
+    pool: &PgPool,
+    channel_id: i32,
+    error: Option<&str>,
+) -> DBResult<()> {
+    sqlx::query!(
+        "UPDATE notification_channels
+         SET last_sent_at = CASE WHEN $2::TEXT IS NULL THEN NOW() ELSE last_sent_at END,
+             last_error = $2
+         WHERE id = $1",
+        channel_id,
+        error
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The user's notification settings, the defaults if they never changed
+/// them.
+pub async fn get_notification_settings(
+    pool: &PgPool,
+    user_id: i32,
+) -> DBResult<NotificationSettings> {
+    let settings = sqlx::query_as!(
+        NotificationSettings,
+        "SELECT quiet_start, quiet_end, digest_time, timezone
+         FROM notification_settings WHERE user_id = $1",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(settings.unwrap_or_default())
+}
+
+pub async fn update_notification_settings(
+    pool: &PgPool,
+    user_id: i32,
+    settings: &NotificationSettings,
+) -> DBResult<NotificationSettings> {
+    sqlx::query_as!(
+        NotificationSettings,
+        "INSERT INTO notification_settings (user_id, quiet_start, quiet_end, digest_time, timezone)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (user_id) DO UPDATE
+         SET quiet_start = EXCLUDED.quiet_start,
+             quiet_end = EXCLUDED.quiet_end,
+             digest_time = EXCLUDED.digest_time,
+             timezone = EXCLUDED.timezone
+         RETURNING quiet_start, quiet_end, digest_time, timezone",
+        user_id,
+        settings.quiet_start,
+        settings.quiet_end,
+        settings.digest_time,
+        settings.timezone
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// When the user's last digest went out, if one ever did.
+pub async fn get_last_digest(
+    pool: &PgPool,
+    user_id: i32,
+) -> DBResult<Option<time::OffsetDateTime>> {
+    let last_digest_at = sqlx::query_scalar!(
+        "SELECT last_digest_at FROM notification_settings WHERE user_id = $1",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(last_digest_at.flatten())
+}
+
+pub async fn record_digest(pool: &PgPool, user_id: i32, at: time::OffsetDateTime) -> DBResult<()> {
+    sqlx::query!(
+        "INSERT INTO notification_settings (user_id, last_digest_at) VALUES ($1, $2)
+         ON CONFLICT (user_id) DO UPDATE SET last_digest_at = EXCLUDED.last_digest_at",
+        user_id,
+        at
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Users with at least one notification channel.
+pub async fn get_users_with_notification_channels(pool: &PgPool) -> DBResult<Vec<i32>> {
+    sqlx::query_scalar!("SELECT DISTINCT user_id FROM notification_channels ORDER BY user_id")
+        .fetch_all(pool)
+        .await
+}
+
+/// Keys of the alerts already sent to the user.
+pub async fn get_sent_alerts(pool: &PgPool, user_id: i32) -> DBResult<Vec<String>> {
+    sqlx::query_scalar!(
+        "SELECT alert_key FROM sent_alerts WHERE user_id = $1",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Forgets the sent alerts that are no longer `current`, so they are sent
+/// again if they come back, e.g. when an item runs low a second time.
+pub async fn forget_sent_alerts(pool: &PgPool, user_id: i32, current: &[String]) -> DBResult<()> {
+    sqlx::query!(
+        "DELETE FROM sent_alerts WHERE user_id = $1 AND alert_key <> ALL($2)",
+        user_id,
+        current
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn mark_alerts_sent(pool: &PgPool, user_id: i32, keys: &[String]) -> DBResult<()> {
+    sqlx::query!(
+        "INSERT INTO sent_alerts (user_id, alert_key)
+         SELECT $1, UNNEST($2::TEXT[])
+         ON CONFLICT DO NOTHING",
+        user_id,
+        keys
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+//
+// API tokens
+//
+
+pub async fn create_api_token(
+    pool: &PgPool,
+    user_id: i32,
+    token_hash: &str,
+    prefix: &str,
+    label: &str,
+) -> DBResult<ApiToken> {
+    sqlx::query_as!(
+        ApiToken,
+        "INSERT INTO api_tokens (token_hash, token_prefix, user_id, label)
+         VALUES ($1, $2, $3, $4)
+         RETURNING id, token_prefix AS prefix, label, created_at, last_used_at",
+        token_hash,
+        prefix,
+        user_id,
+        label.trim()
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn list_api_tokens(pool: &PgPool, user_id: i32) -> DBResult<Vec<ApiToken>> {
+    sqlx::query_as!(
+        ApiToken,
+        "SELECT id, token_prefix AS prefix, label, created_at, last_used_at
+         FROM api_tokens
+         WHERE user_id = $1
+         ORDER BY created_at DESC, id DESC",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The owner of the token hashing to `token_hash`, noting that it was used.
+pub async fn use_api_token(pool: &PgPool, token_hash: &str) -> DBResult<Option<i32>> {
+    sqlx::query_scalar!(
+        "UPDATE api_tokens SET last_used_at = NOW()
+         WHERE token_hash = $1
+         RETURNING user_id",
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Revokes a token; returns 0 if the user has none with this ID.
+pub async fn delete_api_token(pool: &PgPool, user_id: i32, token_id: i32) -> DBResult<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM api_tokens WHERE id = $1 AND user_id = $2",
+        token_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// The user's item called `name`, ignoring case.
+pub async fn find_item_by_name(pool: &PgPool, user_id: i32, name: &str) -> DBResult<Option<i32>> {
+    sqlx::query_scalar!(
+        "SELECT id FROM items
+         WHERE user_id = $1 AND LOWER(name) = LOWER($2)
+         ORDER BY id
+         LIMIT 1",
+        user_id,
+        name.trim()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// --- Category DB Functions ---
+pub async fn create_category(
+    pool: &PgPool,
+    user_id: i32,
+    payload: CreateCategoryPayload,
+) -> DBResult<Category> {
+    sqlx::query_as!(
+        Category,
+        "INSERT INTO categories (user_id, name, color, parent_id, icon, sort_order)
+         VALUES ($1, $2, $3, $4, $5,
+                 (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM categories WHERE user_id = $1))
+         RETURNING id, name, color, parent_id, icon", // user_id is not part of Category struct here
+        user_id,
+        payload.name,
+        payload.color,
+        payload.parent_id,
+        payload.icon
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Creates the starter categories for a new account, in the order given.
+pub async fn seed_categories(pool: &PgPool, user_id: i32, seed: &[SeedCategory]) -> DBResult<()> {
+    let names: Vec<String> = seed.iter().map(|c| c.name.clone()).collect();
+    let colors: Vec<String> = seed.iter().map(|c| c.color.clone()).collect();
+    let icons: Vec<Option<String>> = seed.iter().map(|c| c.icon.clone()).collect();
+    sqlx::query!(
+        "INSERT INTO categories (user_id, name, color, icon, sort_order)
+         SELECT $1, s.name, s.color, s.icon, s.ord - 1
+         FROM UNNEST($2::VARCHAR[], $3::VARCHAR[], $4::VARCHAR[]) WITH ORDINALITY AS s(name, color, icon, ord)",
+        user_id,
+        &names,
+        &colors,
+        &icons as &[Option<String>]
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_all_categories(pool: &PgPool, user_id: i32) -> DBResult<Vec<Category>> {
+    sqlx::query_as!(
+        Category,
+        "SELECT id, name, color, parent_id, icon FROM categories WHERE user_id = $1 ORDER BY sort_order, name",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// All categories with the number of items in each, for the management page.
+/// Subcategories follow their parent.
+pub async fn get_categories_with_counts(
+    pool: &PgPool,
+    user_id: i32,
+) -> DBResult<Vec<CategoryWithCount>> {
+    sqlx::query_as!(
+        CategoryWithCount,
+        r#"
+        SELECT c.id, c.name, c.color, c.parent_id, c.icon, COUNT(i.id) AS "item_count!"
+        FROM categories c
+        LEFT JOIN categories p ON p.id = c.parent_id
+        LEFT JOIN items i ON i.category_id = c.id AND i.user_id = c.user_id
+        WHERE c.user_id = $1
+        GROUP BY c.id, c.name, c.color, c.parent_id, c.icon, c.sort_order, p.name, p.sort_order
+        -- Subcategories directly after their parent
+        ORDER BY
+            COALESCE(p.sort_order, c.sort_order),
+            COALESCE(p.name, c.name),
+            c.parent_id NULLS FIRST,
+            c.sort_order,
+            c.name
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_category_by_id(
+    pool: &PgPool,
+    user_id: i32,
+    category_id: i32,
+) -> DBResult<Option<Category>> {
+    sqlx::query_as!(
+        Category,
+        "SELECT id, name, color, parent_id, icon FROM categories WHERE user_id = $1 AND id = $2",
+        user_id,
+        category_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn update_category(
+    pool: &PgPool,
+    user_id: i32,
+    category_id: i32,
+    name: Option<String>,
+    color: Option<String>,
+    parent_id: Option<i32>,
+    icon: Option<String>,
+) -> DBResult<Option<Category>> {
+    let current_category = get_category_by_id(pool, user_id, category_id).await?;
+    if current_category.is_none() {
+        return Ok(None);
+    }
+    let current_category = current_category.unwrap();
+
+    let name_to_set = name.unwrap_or(current_category.name);
+    let color_to_set = color.unwrap_or(current_category.color);
+
+    let affected_rows = sqlx::query!(
+        "UPDATE categories SET name = $1, color = $2, parent_id = $3, icon = $4 WHERE user_id = $5 AND id = $6",
+        name_to_set,
+        color_to_set,
+        parent_id,
+        icon,
+        user_id,
+        category_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    if affected_rows > 0 {
+        get_category_by_id(pool, user_id, category_id).await
+    } else {
+        Ok(None)
+    }
+}
+
+/// Stores a manual order of categories, see [`reorder_items`].
+pub async fn reorder_categories(
+    pool: &PgPool,
+    user_id: i32,
+    category_ids: &[i32],
+) -> DBResult<u64> {
+    sqlx::query!(
+        "UPDATE categories c
+         SET sort_order = o.position - 1
+         FROM UNNEST($2::INTEGER[]) WITH ORDINALITY AS o(id, position)
+         WHERE c.id = o.id AND c.user_id = $1",
+        user_id,
+        category_ids
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+/// Deletes a category in one transaction, first moving its items according
+/// to `policy`. Nothing changes unless the whole operation succeeds.
+pub async fn delete_category_with_policy(
+    pool: &PgPool,
+    user_id: i32,
+    category_id: i32,
+    policy: CategoryDeletePolicy,
+) -> DBResult<DeleteCategoryOutcome> {
+    let mut tx = pool.begin().await?;
+
+    let exists = sqlx::query_scalar!(
+        "SELECT id FROM categories WHERE user_id = $1 AND id = $2 FOR UPDATE",
+        user_id,
+        category_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if exists.is_none() {
+        return Ok(DeleteCategoryOutcome::NotFound);
+    }
+
+    let reassign_to = match policy {
+        CategoryDeletePolicy::Uncategorize => None,
+        CategoryDeletePolicy::ReassignTo(target_id) => {
+            // Lock the target so it can't be deleted while items move into it
+            let target = sqlx::query_scalar!(
+                "SELECT id FROM categories WHERE user_id = $1 AND id = $2 AND id <> $3 FOR UPDATE",
+                user_id,
+                target_id,
+                category_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+            if target.is_none() {
+                return Ok(DeleteCategoryOutcome::InvalidTarget);
+            }
+            Some(target_id)
+        }
+    };
+
+    let moved_items = sqlx::query!(
+        "UPDATE items SET category_id = $3, updated_at = NOW() WHERE user_id = $1 AND category_id = $2",
+        user_id,
+        category_id,
+        reassign_to
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query!(
+        "DELETE FROM categories WHERE user_id = $1 AND id = $2",
+        user_id,
+        category_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(DeleteCategoryOutcome::Deleted { moved_items })
+}
+
+/// Merges `category_id` into `target_id`: its items move to the target and it
+/// is deleted, in one transaction. Its subcategories move under the target
+/// when that is a top-level category, otherwise they become top-level.
+pub async fn merge_category(
+    pool: &PgPool,
+    user_id: i32,
+    category_id: i32,
+    target_id: i32,
+) -> DBResult<MergeCategoryOutcome> {
+    let mut tx = pool.begin().await?;
+
+    let exists = sqlx::query_scalar!(
+        "SELECT id FROM categories WHERE user_id = $1 AND id = $2 FOR UPDATE",
+        user_id,
+        category_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if exists.is_none() {
+        return Ok(MergeCategoryOutcome::NotFound);
+    }
+
+    let Some(target) = sqlx::query!(
+        "SELECT parent_id FROM categories WHERE user_id = $1 AND id = $2 AND id <> $3 FOR UPDATE",
+        user_id,
+        target_id,
+        category_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(MergeCategoryOutcome::InvalidTarget);
+    };
+
+    // Nesting is two levels deep, so only a top-level target can adopt children
+    let new_parent = target.parent_id.is_none().then_some(target_id);
+    sqlx::query!(
+        "UPDATE categories SET parent_id = $4 WHERE user_id = $1 AND parent_id = $2 AND id <> $3",
+        user_id,
+        category_id,
+        target_id,
+        new_parent
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let moved_items = sqlx::query!(
+        "UPDATE items SET category_id = $3, updated_at = NOW() WHERE user_id = $1 AND category_id = $2",
+        user_id,
+        category_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query!(
+        "DELETE FROM categories WHERE user_id = $1 AND id = $2",
+        user_id,
+        category_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(MergeCategoryOutcome::Merged { moved_items })
+}
+
+/// Merges a duplicate into another item in one transaction: the target
+/// gets its stock, batches, history, purchases, recipe ingredients,
+/// stocktake counts and, if it has none, its consumption rule, as well as
+/// the barcode, unit, location, notes and custom field values it lacks.
+/// Then the duplicate is deleted.
+pub async fn merge_item(
+    pool: &PgPool,
+    user_id: i32,
+    item_id: i32,
+    target_id: i32,
+) -> DBResult<MergeItemOutcome> {
+    let mut tx = pool.begin().await?;
+
+    let Some(source) = sqlx::query!(
+        "SELECT quantity, barcode, unit, location, notes, attributes FROM items
+         WHERE user_id = $1 AND id = $2 FOR UPDATE",
+        user_id,
+        item_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(MergeItemOutcome::NotFound);
+    };
+    let target = sqlx::query_scalar!(
+        "SELECT id FROM items WHERE user_id = $1 AND id = $2 AND id <> $3 FOR UPDATE",
+        user_id,
+        target_id,
+        item_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if target.is_none() {
+        return Ok(MergeItemOutcome::InvalidTarget);
+    }
+
+    sqlx::query!(
+        "UPDATE item_batches SET item_id = $2 WHERE item_id = $1",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE item_events SET item_id = $2 WHERE item_id = $1",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE purchases SET item_id = $2 WHERE item_id = $1",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // A recipe or stocktake can list an item only once, so where both are
+    // listed the duplicate's amounts are added to the target's
+    sqlx::query!(
+        "UPDATE recipe_ingredients t SET quantity = t.quantity + s.quantity
+         FROM recipe_ingredients s
+         WHERE s.item_id = $1 AND t.item_id = $2 AND t.recipe_id = s.recipe_id",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM recipe_ingredients s WHERE s.item_id = $1 AND EXISTS (
+             SELECT 1 FROM recipe_ingredients t WHERE t.recipe_id = s.recipe_id AND t.item_id = $2)",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE recipe_ingredients SET item_id = $2 WHERE item_id = $1",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE stocktake_entries t
+         SET expected_quantity = t.expected_quantity + s.expected_quantity,
+             counted_quantity = CASE
+                 WHEN t.counted_quantity IS NULL AND s.counted_quantity IS NULL THEN NULL
+                 ELSE COALESCE(t.counted_quantity, 0) + COALESCE(s.counted_quantity, 0)
+             END
+         FROM stocktake_entries s
+         WHERE s.item_id = $1 AND t.item_id = $2 AND t.stocktake_id = s.stocktake_id",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM stocktake_entries s WHERE s.item_id = $1 AND EXISTS (
+             SELECT 1 FROM stocktake_entries t
+             WHERE t.stocktake_id = s.stocktake_id AND t.item_id = $2)",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE stocktake_entries SET item_id = $2 WHERE item_id = $1",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE consumption_rules SET item_id = $2
+         WHERE item_id = $1
+           AND NOT EXISTS (SELECT 1 FROM consumption_rules WHERE item_id = $2)",
+        item_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE items
+         SET quantity = quantity + $3, barcode = COALESCE(barcode, $4),
+             unit = COALESCE(unit, $5), location = COALESCE(location, $6),
+             notes = COALESCE(notes, $8), attributes = $7 || attributes, updated_at = NOW()
+         WHERE user_id = $1 AND id = $2",
+        user_id,
+        target_id,
+        source.quantity,
+        source.barcode,
+        source.unit,
+        source.location,
+        source.attributes,
+        source.notes
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM items WHERE user_id = $1 AND id = $2",
+        user_id,
+        item_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    match get_item_by_id(pool, user_id, target_id).await? {
+        Some(item) => Ok(MergeItemOutcome::Merged(Box::new(item))),
+        None => Ok(MergeItemOutcome::NotFound),
+    }
+}
+
+// Backup and restore
+
+/// A read-only transaction that sees the database as it was when it began,
+/// so the parts of a backup agree even if the user changes something while
+/// it is read.
+///
+/// A download is read as fast as the client takes it, so a client that
+/// stops reading would hold the transaction, its connection and the rows
+/// vacuum must keep for as long as it stays connected. Instead PostgreSQL
+/// cancels a query that has taken 15 minutes to send, and ends the session
+/// once a minute goes by between two queries, either of which aborts the
+/// download. The 15 minutes replace the pool's `statement_timeout`, which is
+/// meant for requests answered at once.
+pub async fn begin_snapshot(pool: &PgPool) -> DBResult<Transaction<'static, Postgres>> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("SET LOCAL statement_timeout = '15min'")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("SET LOCAL idle_in_transaction_session_timeout = '1min'")
+        .execute(&mut *tx)
+        .await?;
+    Ok(tx)
+}
+
+pub async fn export_backup(pool: &PgPool, user_id: i32) -> DBResult<Backup> {
+    let mut tx = begin_snapshot(pool).await?;
+    let preferences = get_user_preferences(&mut *tx, user_id).await?;
+    let categories = backup_categories(&mut *tx, user_id).try_collect().await?;
+    let stores = backup_stores(&mut *tx, user_id).try_collect().await?;
+    let attribute_fields = backup_attribute_fields(&mut *tx, user_id)
+        .try_collect()
+        .await?;
+    let items = backup_items(&mut *tx, user_id).try_collect().await?;
+    let batches = backup_batches(&mut *tx, user_id).try_collect().await?;
+    let events = backup_events(&mut *tx, user_id).try_collect().await?;
+    let recipes = backup_recipes(&mut tx, user_id).await?;
+    let meal_plans = backup_meal_plans(&mut *tx, user_id).try_collect().await?;
+    let consumption_rules = backup_consumption_rules(&mut *tx, user_id)
+        .try_collect()
+        .await?;
+
+    Ok(Backup {
+        version: backup::BACKUP_VERSION,
+        exported_at: time::OffsetDateTime::now_utc(),
+        preferences,
+        categories,
+        stores,
+        attribute_fields,
+        items,
+        batches,
+        events,
+        recipes,
+        meal_plans,
+        consumption_rules,
+    })
+}
+
+/// Writes the same JSON as `export_backup` to `out`, passing the records on
+/// as they are read instead of collecting them first.
+pub async fn write_backup(
+    mut snapshot: Transaction<'static, Postgres>,
+    user_id: i32,
+    exported_at: time::OffsetDateTime,
+    out: &mut JsonWriter,
+) -> Result<(), ExportError> {
+    let preferences = get_user_preferences(&mut *snapshot, user_id).await?;
+    out.begin_object();
+    out.field("version", &backup::BACKUP_VERSION).await?;
+    let exported_at = exported_at
+        .format(&Rfc3339)
+        .expect("every date can be formatted");
+    out.field("exported_at", &exported_at).await?;
+    out.field("preferences", &preferences).await?;
+    write_rows(
+        out,
+        "categories",
+        backup_categories(&mut *snapshot, user_id),
+    )
+    .await?;
+    write_rows(out, "stores", backup_stores(&mut *snapshot, user_id)).await?;
+    write_rows(
+        out,
+        "attribute_fields",
+        backup_attribute_fields(&mut *snapshot, user_id),
+    )
+    .await?;
+    write_rows(out, "items", backup_items(&mut *snapshot, user_id)).await?;
+    write_rows(out, "batches", backup_batches(&mut *snapshot, user_id)).await?;
+    write_rows(out, "events", backup_events(&mut *snapshot, user_id)).await?;
+    let recipes = backup_recipes(&mut snapshot, user_id).await?;
+    out.field("recipes", &recipes).await?;
+    write_rows(
+        out,
+        "meal_plans",
+        backup_meal_plans(&mut *snapshot, user_id),
+    )
+    .await?;
+    write_rows(
+        out,
+        "consumption_rules",
+        backup_consumption_rules(&mut *snapshot, user_id),
+    )
+    .await?;
+    out.end_object();
+    Ok(())
+}
+
+async fn write_rows<T: Serialize>(
+    out: &mut JsonWriter,
+    name: &str,
+    mut rows: BoxStream<'_, DBResult<T>>,
+) -> Result<(), ExportError> {
+    out.begin_array(name);
+    while let Some(row) = rows.try_next().await? {
+        out.element(&row).await?;
+    }
+    out.end_array();
+    Ok(())
+}
+
+fn backup_categories<'e>(
+    executor: impl PgExecutor<'e> + 'e,
+    user_id: i32,
+) -> BoxStream<'e, DBResult<BackupCategory>> {
+    sqlx::query_as!(
+        BackupCategory,
+        "SELECT id, name, color, parent_id, icon, sort_order
+         FROM categories WHERE user_id = $1 ORDER BY id",
+        user_id
+    )
+    .fetch(executor)
+}
+
+fn backup_stores<'e>(
+    executor: impl PgExecutor<'e> + 'e,
+    user_id: i32,
+) -> BoxStream<'e, DBResult<BackupStore>> {
+    sqlx::query_as!(
+        BackupStore,
+        "SELECT id, name, sort_order FROM stores WHERE user_id = $1 ORDER BY id",
+        user_id
+    )
+    .fetch(executor)
+}
+
+fn backup_attribute_fields<'e>(
+    executor: impl PgExecutor<'e> + 'e,
+    user_id: i32,
+) -> BoxStream<'e, DBResult<BackupAttributeField>> {
+    sqlx::query_as!(
+        BackupAttributeField,
+        r#"SELECT name, kind AS "kind: AttributeKind", sort_order
+           FROM attribute_fields WHERE user_id = $1 ORDER BY id"#,
+        user_id
+    )
+    .fetch(executor)
+}
+
+fn backup_items<'e>(
+    executor: impl PgExecutor<'e> + 'e,
+    user_id: i32,
+) -> BoxStream<'e, DBResult<BackupItem>> {
+    sqlx::query_as!(
+        BackupItem,
+        r#"SELECT id, name, quantity, restock_threshold, restock_to, unit, location, barcode,
+                  notes, category_id, preferred_store_id, quantity_step,
+                  attributes AS "attributes: Json<Attributes>", sort_order, created_at, updated_at
+           FROM items WHERE user_id = $1 ORDER BY id"#,
+        user_id
+    )
+    .fetch(executor)
+}
+
+fn backup_batches<'e>(
+    executor: impl PgExecutor<'e> + 'e,
+    user_id: i32,
+) -> BoxStream<'e, DBResult<BackupBatch>> {
+    sqlx::query_as!(
+        BackupBatch,
+        "SELECT b.item_id, b.quantity, b.purchased_on, b.expires_on
+         FROM item_batches b JOIN items i ON i.id = b.item_id
+         WHERE i.user_id = $1 ORDER BY b.id",
+        user_id
+    )
+    .fetch(executor)
+}
+
+fn backup_events<'e>(
+    executor: impl PgExecutor<'e> + 'e,
+    user_id: i32,
+) -> BoxStream<'e, DBResult<BackupEvent>> {
+    sqlx::query_as!(
+        BackupEvent,
+        r#"SELECT e.item_id, e.item_name, e.kind, e.quantity_delta, e.created_at,
+                  p.price_cents AS "price_cents?", p.store_id AS "store_id?", e.value_cents
+           FROM item_events e LEFT JOIN purchases p ON p.id = e.id
+           WHERE e.user_id = $1 ORDER BY e.id"#,
+        user_id
+    )
+    .fetch(executor)
+}
+
+fn backup_meal_plans<'e>(
+    executor: impl PgExecutor<'e> + 'e,
+    user_id: i32,
+) -> BoxStream<'e, DBResult<BackupMealPlan>> {
+    sqlx::query_as!(
+        BackupMealPlan,
+        "SELECT recipe_id, planned_for, cooked_at, created_at
+         FROM meal_plans WHERE user_id = $1 ORDER BY id",
+        user_id
+    )
+    .fetch(executor)
+}
+
+fn backup_consumption_rules<'e>(
+    executor: impl PgExecutor<'e> + 'e,
+    user_id: i32,
+) -> BoxStream<'e, DBResult<BackupConsumptionRule>> {
+    sqlx::query_as!(
+        BackupConsumptionRule,
+        "SELECT item_id, quantity, every_days, next_run_at
+         FROM consumption_rules WHERE user_id = $1 ORDER BY id",
+        user_id
+    )
+    .fetch(executor)
+}
+
+/// Recipes with their ingredients; there are few enough to collect.
+async fn backup_recipes(conn: &mut PgConnection, user_id: i32) -> DBResult<Vec<BackupRecipe>> {
+    let ingredient_rows = sqlx::query!(
+        "SELECT ri.recipe_id, ri.item_id, ri.quantity
+         FROM recipe_ingredients ri JOIN recipes r ON r.id = ri.recipe_id
+         WHERE r.user_id = $1 ORDER BY ri.id",
+        user_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let mut ingredients: HashMap<i32, Vec<BackupIngredient>> = HashMap::new();
+    for row in ingredient_rows {
+        ingredients
+            .entry(row.recipe_id)
+            .or_default()
+            .push(BackupIngredient {
+                item_id: row.item_id,
+                quantity: row.quantity,
+            });
+    }
+    let recipes = sqlx::query!(
+        "SELECT id, name, instructions, created_at, updated_at
+         FROM recipes WHERE user_id = $1 ORDER BY id",
+        user_id
+    )
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|row| BackupRecipe {
+        ingredients: ingredients.remove(&row.id).unwrap_or_default(),
+        id: row.id,
+        name: row.name,
+        instructions: row.instructions,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+    .collect();
+    Ok(recipes)
+}
+
+/// Replaces all of the user's data with the contents of `backup`, in one
+/// transaction. Restoring the same backup twice gives the same result.
+/// Stocktakes are not part of a backup; their entries lose the link to the
+/// replaced items but keep the item names. Neither are receipts: they go
+/// with the replaced purchases, and the storage keys of their files are
+/// returned for the caller to delete once the restore is committed.
+pub async fn restore_backup(
+    pool: &PgPool,
+    user_id: i32,
+    backup: Backup,
+) -> DBResult<(RestoreSummary, Vec<String>)> {
+    let mut tx = pool.begin().await?;
+
+    // Children first; batches, receipts and recipe ingredients go with their
+    // parents
+    let receipt_keys = sqlx::query_scalar!(
+        "SELECT storage_key FROM receipts WHERE user_id = $1",
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    sqlx::query!("DELETE FROM meal_plans WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM recipes WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM item_events WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM items WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM categories WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM stores WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM attribute_fields WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Ids of the backup mapped to the newly inserted rows
+    let mut category_ids: HashMap<i32, i32> = HashMap::new();
+    for category in &backup.categories {
+        let id = sqlx::query_scalar!(
+            "INSERT INTO categories (user_id, name, color, icon, sort_order)
+             VALUES ($1, $2, $3, $4, $5) RETURNING id",
+            user_id,
+            category.name,
+            category.color,
+            category.icon,
+            category.sort_order
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        category_ids.insert(category.id, id);
+    }
+    for category in &backup.categories {
+        if let Some(parent_id) = category.parent_id {
+            sqlx::query!(
+                "UPDATE categories SET parent_id = $2 WHERE id = $1",
+                category_ids[&category.id],
+                category_ids[&parent_id]
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    let mut store_ids: HashMap<i32, i32> = HashMap::new();
+    for store in &backup.stores {
+        let id = sqlx::query_scalar!(
+            "INSERT INTO stores (user_id, name, sort_order) VALUES ($1, $2, $3) RETURNING id",
+            user_id,
+            store.name,
+            store.sort_order
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        store_ids.insert(store.id, id);
+    }
+    for field in &backup.attribute_fields {
+        sqlx::query!(
+            "INSERT INTO attribute_fields (user_id, name, kind, sort_order) VALUES ($1, $2, $3, $4)",
+            user_id,
+            field.name,
+            field.kind as AttributeKind,
+            field.sort_order
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let mut item_ids: HashMap<i32, i32> = HashMap::new();
+    for item in &backup.items {
+        let id = sqlx::query_scalar!(
+            "INSERT INTO items (user_id, name, quantity, restock_threshold, restock_to, unit,
+                                location, barcode, category_id, preferred_store_id,
+                                quantity_step, attributes, sort_order, created_at, updated_at,
+                                notes)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+             RETURNING id",
+            user_id,
+            item.name,
+            item.quantity,
+            item.restock_threshold,
+            item.restock_to,
+            item.unit,
+            item.location,
+            item.barcode,
+            item.category_id.map(|id| category_ids[&id]),
+            item.preferred_store_id.map(|id| store_ids[&id]),
+            item.quantity_step,
+            &item.attributes as _,
+            item.sort_order,
+            item.created_at,
+            item.updated_at,
+            item.notes
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        item_ids.insert(item.id, id);
+    }
+    for batch in &backup.batches {
+        sqlx::query!(
+            "INSERT INTO item_batches (item_id, quantity, purchased_on, expires_on)
+             VALUES ($1, $2, $3, $4)",
+            item_ids[&batch.item_id],
+            batch.quantity,
+            batch.purchased_on,
+            batch.expires_on
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    // Older or hand-edited backups may not match the quantities exactly
+    let new_item_ids: Vec<i32> = item_ids.values().copied().collect();
+    sync_item_batches(&mut tx, &new_item_ids).await?;
+
+    for event in &backup.events {
+        let event_id = sqlx::query_scalar!(
+            "INSERT INTO item_events (user_id, item_id, item_name, kind, quantity_delta, created_at, value_cents, actor_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $1) RETURNING id",
+            user_id,
+            event.item_id.map(|id| item_ids[&id]),
+            event.item_name,
+            event.kind,
+            event.quantity_delta,
+            event.created_at,
+            event.value_cents
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        // Every purchase event has its purchase, priced or not
+        if event.kind == "purchased" {
+            sqlx::query!(
+                "INSERT INTO purchases (id, user_id, item_id, item_name, quantity, price_cents, store_id, purchased_at)
+                 SELECT id, user_id, item_id, item_name, quantity_delta, $2, $3, created_at
+                 FROM item_events WHERE id = $1",
+                event_id,
+                event.price_cents,
+                event.store_id.map(|id| store_ids[&id])
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    let mut recipe_ids: HashMap<i32, i32> = HashMap::new();
+    for recipe in &backup.recipes {
+        let id = sqlx::query_scalar!(
+            "INSERT INTO recipes (user_id, name, instructions, created_at, updated_at)
+             VALUES ($1, $2, $3, $4, $5) RETURNING id",
+            user_id,
+            recipe.name,
+            recipe.instructions,
+            recipe.created_at,
+            recipe.updated_at
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        recipe_ids.insert(recipe.id, id);
+        for ingredient in &recipe.ingredients {
+            sqlx::query!(
+                "INSERT INTO recipe_ingredients (recipe_id, item_id, quantity) VALUES ($1, $2, $3)",
+                id,
+                item_ids[&ingredient.item_id],
+                ingredient.quantity
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    for plan in &backup.meal_plans {
+        sqlx::query!(
+            "INSERT INTO meal_plans (user_id, recipe_id, planned_for, cooked_at, created_at)
+             VALUES ($1, $2, $3, $4, $5)",
+            user_id,
+            recipe_ids[&plan.recipe_id],
+            plan.planned_for,
+            plan.cooked_at,
+            plan.created_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Their old rules went with the items
+    for rule in &backup.consumption_rules {
+        sqlx::query!(
+            "INSERT INTO consumption_rules (user_id, item_id, quantity, every_days, next_run_at)
+             VALUES ($1, $2, $3, $4, $5)",
+            user_id,
+            item_ids[&rule.item_id],
+            rule.quantity,
+            rule.every_days,
+            rule.next_run_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let preferences = &backup.preferences;
+    let collapsed_category_ids: Vec<i32> = preferences
+        .collapsed_category_ids
+        .iter()
+        .filter_map(|id| category_ids.get(id).copied())
+        .collect();
+    sqlx::query!(
+        r#"INSERT INTO user_preferences (user_id, group_by_category, sort, language, theme, layout,
+                                        default_location, monthly_summary, restock_escalation_days,
+                                        collapsed_category_ids, timezone)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+           ON CONFLICT (user_id) DO UPDATE
+           SET group_by_category = EXCLUDED.group_by_category,
+               sort = EXCLUDED.sort,
+               language = EXCLUDED.language,
+               theme = EXCLUDED.theme,
+               layout = EXCLUDED.layout,
+               default_location = EXCLUDED.default_location,
+               monthly_summary = EXCLUDED.monthly_summary,
+               restock_escalation_days = EXCLUDED.restock_escalation_days,
+               collapsed_category_ids = EXCLUDED.collapsed_category_ids,
+               timezone = EXCLUDED.timezone,
+               updated_at = NOW()"#,
+        user_id,
+        preferences.group_by_category,
+        preferences.sort as ItemSort,
+        preferences.language as Language,
+        preferences.theme as Theme,
+        preferences.layout as ItemLayout,
+        preferences.default_location,
+        preferences.monthly_summary,
+        preferences.restock_escalation_days,
+        &collapsed_category_ids,
+        preferences.timezone
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    let summary = RestoreSummary {
+        categories: backup.categories.len(),
+        stores: backup.stores.len(),
+        items: backup.items.len(),
+        events: backup.events.len(),
+        recipes: backup.recipes.len(),
+        meal_plans: backup.meal_plans.len(),
+    };
+    Ok((summary, receipt_keys))
+}
+
+// Grocy import
+/// Inserts the imported items in one transaction. Product groups become
+/// categories, reusing existing ones with the same name; items whose name is
+/// already taken are skipped, so running the same import again is harmless.
+pub async fn import_grocy_items(
+    pool: &PgPool,
+    user_id: i32,
+    items: Vec<ImportedItem>,
+) -> DBResult<GrocyImportSummary> {
+    let mut tx = pool.begin().await?;
+    let mut summary = GrocyImportSummary::default();
+
+    let mut category_ids: HashMap<String, i32> = sqlx::query!(
+        "SELECT id, name FROM categories WHERE user_id = $1",
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|row| (row.name.to_lowercase(), row.id))
+    .collect();
+
+    let mut imported_ids = Vec::new();
+    for item in items {
+        let category_id = match &item.category {
+            Some(name) => match category_ids.get(&name.to_lowercase()) {
+                Some(id) => Some(*id),
+                None => {
+                    let id = sqlx::query_scalar!(
+                        "INSERT INTO categories (user_id, name, color, sort_order)
+                         VALUES ($1, $2, $3,
+                                 (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM categories WHERE user_id = $1))
+                         RETURNING id",
+                        user_id,
+                        name,
+                        grocy::category_color(summary.categories_created)
+                    )
+                    .fetch_one(&mut *tx)
+                    .await?;
+                    category_ids.insert(name.to_lowercase(), id);
+                    summary.categories_created += 1;
+                    Some(id)
+                }
+            },
+            None => None,
+        };
+
+        let quantity: i32 = item.batches.iter().map(|b| b.quantity).sum();
+        let item_id = sqlx::query_scalar!(
+            "INSERT INTO items (user_id, name, quantity, restock_threshold, unit, location, category_id, sort_order)
+             VALUES ($1, $2, $3, $4, $5, $6, $7,
+                     (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM items WHERE user_id = $1))
+             ON CONFLICT (user_id, name) DO NOTHING
+             RETURNING id",
+            user_id,
+            item.name,
+            quantity,
+            item.restock_threshold,
+            item.unit,
+            item.location,
+            category_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(item_id) = item_id else {
+            summary.items_skipped += 1;
+            continue;
+        };
+        summary.items_created += 1;
+
+        for batch in &item.batches {
+            sqlx::query!(
+                "INSERT INTO item_batches (item_id, quantity, purchased_on, expires_on)
+                 VALUES ($1, $2, COALESCE($3, CURRENT_DATE), $4)",
+                item_id,
+                batch.quantity,
+                batch.purchased_on,
+                batch.expires_on
+            )
+            .execute(&mut *tx)
+            .await?;
+            summary.batches_created += 1;
+        }
+        imported_ids.push(item_id);
+    }
+    sync_item_batches(&mut tx, &imported_ids).await?;
+
+    tx.commit().await?;
+    Ok(summary)
 }
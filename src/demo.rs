@@ -0,0 +1,285 @@
+//! Demo mode: a shared account with a sample pantry, put back to the same
+//! state on startup and then periodically, for hosting a public demo.
+
+use crate::{
+    backup::{
+        BACKUP_VERSION, Backup, BackupBatch, BackupCategory, BackupEvent, BackupIngredient,
+        BackupItem, BackupMealPlan, BackupRecipe, BackupStore, RestoreSummary,
+    },
+    cache::LookupCache,
+    db,
+    models::UserPreferences,
+    scheduler,
+};
+use bcrypt::{DEFAULT_COST, hash};
+use sqlx::PgPool;
+use std::time::Duration;
+use time::{Date, OffsetDateTime};
+
+/// Login of the demo account, shown on the login page in demo mode.
+pub const DEMO_EMAIL: &str = "demo@example.com";
+pub const DEMO_PASSWORD: &str = "demo";
+
+/// Creates the demo account if it doesn't exist and replaces everything in it
+/// with the sample data.
+pub async fn reset(
+    pool: &PgPool,
+) -> Result<RestoreSummary, Box<dyn std::error::Error + Send + Sync>> {
+    let account = match db::get_account_by_email(pool, DEMO_EMAIL).await? {
+        Some(account) => account,
+        None => {
+            let password = hash(DEMO_PASSWORD, DEFAULT_COST)?;
+            db::create_account(pool, "Demo", DEMO_EMAIL, &password).await?
+        }
+    };
+    // Receipts are disabled in the demo, so there are no files to delete
+    let (summary, _) =
+        db::restore_backup(pool, account.id, sample_data(OffsetDateTime::now_utc())).await?;
+    db::delete_stocktakes(pool, account.id).await?;
+    Ok(summary)
+}
+
+/// Resets the demo account every `every`. The first reset is left to the
+/// caller, so the account exists before the server starts.
+pub fn spawn_resets(pool: PgPool, cache: LookupCache, every: Duration) {
+    scheduler::spawn_exclusive("demo reset", every, pool.clone(), move || {
+        let pool = pool.clone();
+        let cache = cache.clone();
+        async move {
+            let summary = reset(&pool).await?;
+            // The demo server is for trying things out, so forgetting every
+            // account's entries is no loss
+            cache.forget_everyone();
+            tracing::info!("Reset the demo account: {:?}", summary);
+            Ok(())
+        }
+    });
+}
+
+struct SampleItem {
+    name: &'static str,
+    /// Index into `CATEGORIES`.
+    category: usize,
+    quantity: i32,
+    restock_threshold: i32,
+    restock_to: Option<i32>,
+    unit: &'static str,
+    location: &'static str,
+    /// Pack size it's bought in.
+    quantity_step: i32,
+    /// Days from today; negative means already expired.
+    expires_in: Option<i64>,
+}
+
+/// Name, color, icon and parent (index into this list).
+const CATEGORIES: &[(&str, &str, &str, Option<usize>)] = &[
+    ("Jedzenie", "#a6b93c", "🍎", None),
+    ("Nabiał", "#e0d8b0", "🧀", Some(0)),
+    ("Napoje", "#4a90c8", "🧃", None),
+    ("Środki czystości", "#8fd3d3", "🧹", None),
+    ("Łazienka", "#c8a2d6", "🧼", None),
+    ("Apteczka", "#c85656", "💊", None),
+];
+
+/// In the order they are visited; medicines come from the pharmacy, the
+/// rest from the supermarket.
+const STORES: &[&str] = &["Lidl", "Apteka"];
+const PHARMACY_CATEGORY: usize = 5;
+
+#[rustfmt::skip]
+const ITEMS: &[SampleItem] = &[
+    SampleItem { name: "Mleko", category: 1, quantity: 1, restock_threshold: 2, restock_to: Some(4), unit: "l", location: "Lodówka", quantity_step: 1, expires_in: Some(3) },
+    SampleItem { name: "Jajka", category: 1, quantity: 6, restock_threshold: 6, restock_to: Some(10), unit: "szt.", location: "Lodówka", quantity_step: 10, expires_in: Some(12) },
+    SampleItem { name: "Masło", category: 1, quantity: 1, restock_threshold: 1, restock_to: Some(2), unit: "kostka", location: "Lodówka", quantity_step: 1, expires_in: Some(20) },
+    SampleItem { name: "Ser żółty", category: 1, quantity: 0, restock_threshold: 1, restock_to: None, unit: "opak.", location: "Lodówka", quantity_step: 1, expires_in: None },
+    SampleItem { name: "Makaron", category: 0, quantity: 3, restock_threshold: 2, restock_to: None, unit: "opak.", location: "Spiżarnia", quantity_step: 1, expires_in: Some(200) },
+    SampleItem { name: "Ryż", category: 0, quantity: 1, restock_threshold: 2, restock_to: Some(3), unit: "kg", location: "Spiżarnia", quantity_step: 1, expires_in: Some(300) },
+    SampleItem { name: "Pomidory w puszce", category: 0, quantity: 4, restock_threshold: 2, restock_to: None, unit: "puszka", location: "Spiżarnia", quantity_step: 1, expires_in: Some(400) },
+    SampleItem { name: "Kawa", category: 2, quantity: 1, restock_threshold: 1, restock_to: Some(2), unit: "opak.", location: "Kuchnia", quantity_step: 1, expires_in: Some(90) },
+    SampleItem { name: "Woda mineralna", category: 2, quantity: 8, restock_threshold: 6, restock_to: Some(12), unit: "butelka", location: "Piwnica", quantity_step: 6, expires_in: None },
+    SampleItem { name: "Sok pomarańczowy", category: 2, quantity: 2, restock_threshold: 1, restock_to: None, unit: "l", location: "Spiżarnia", quantity_step: 1, expires_in: Some(1) },
+    SampleItem { name: "Płyn do naczyń", category: 3, quantity: 1, restock_threshold: 1, restock_to: Some(2), unit: "butelka", location: "Pod zlewem", quantity_step: 1, expires_in: None },
+    SampleItem { name: "Proszek do prania", category: 3, quantity: 0, restock_threshold: 1, restock_to: Some(1), unit: "opak.", location: "Łazienka", quantity_step: 1, expires_in: None },
+    SampleItem { name: "Papier toaletowy", category: 4, quantity: 4, restock_threshold: 4, restock_to: Some(8), unit: "rolka", location: "Łazienka", quantity_step: 8, expires_in: None },
+    SampleItem { name: "Pasta do zębów", category: 4, quantity: 2, restock_threshold: 1, restock_to: None, unit: "tubka", location: "Łazienka", quantity_step: 1, expires_in: None },
+    SampleItem { name: "Paracetamol", category: 5, quantity: 1, restock_threshold: 1, restock_to: None, unit: "opak.", location: "Apteczka", quantity_step: 1, expires_in: Some(-5) },
+    SampleItem { name: "Plastry", category: 5, quantity: 1, restock_threshold: 1, restock_to: None, unit: "opak.", location: "Apteczka", quantity_step: 1, expires_in: None },
+];
+
+struct SampleRecipe {
+    name: &'static str,
+    instructions: &'static str,
+    /// Item index and quantity.
+    ingredients: &'static [(usize, i32)],
+}
+
+const RECIPES: &[SampleRecipe] = &[
+    SampleRecipe {
+        name: "Jajecznica",
+        instructions: "Roztop masło na patelni, wbij jajka i mieszaj do ścięcia.",
+        ingredients: &[(1, 3), (2, 1)],
+    },
+    SampleRecipe {
+        name: "Spaghetti z pomidorami",
+        instructions: "Ugotuj makaron, podgrzej pomidory z przyprawami i wymieszaj.",
+        ingredients: &[(4, 1), (6, 2)],
+    },
+];
+
+/// The sample pantry as a backup, with expiry dates, history and the meal
+/// plan relative to `now` so the demo never goes stale.
+pub fn sample_data(now: OffsetDateTime) -> Backup {
+    let today: Date = now.date();
+    let days = |n: i64| time::Duration::days(n);
+    // Ids only link records within the backup; restore assigns new ones
+    let id = |index: usize| index as i32 + 1;
+
+    let categories = CATEGORIES
+        .iter()
+        .enumerate()
+        .map(|(i, (name, color, icon, parent))| BackupCategory {
+            id: id(i),
+            name: name.to_string(),
+            color: color.to_string(),
+            parent_id: parent.map(id),
+            icon: Some(icon.to_string()),
+            sort_order: i as i32,
+        })
+        .collect();
+
+    let store_id = |item: &SampleItem| id(usize::from(item.category == PHARMACY_CATEGORY));
+    let stores = STORES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| BackupStore {
+            id: id(i),
+            name: name.to_string(),
+            sort_order: i as i32,
+        })
+        .collect();
+
+    let items = ITEMS
+        .iter()
+        .enumerate()
+        .map(|(i, item)| BackupItem {
+            id: id(i),
+            name: item.name.to_string(),
+            quantity: item.quantity,
+            restock_threshold: item.restock_threshold,
+            restock_to: item.restock_to,
+            unit: Some(item.unit.to_string()),
+            location: Some(item.location.to_string()),
+            barcode: None,
+            notes: None,
+            category_id: Some(id(item.category)),
+            preferred_store_id: Some(store_id(item)),
+            quantity_step: item.quantity_step,
+            attributes: Default::default(),
+            sort_order: i as i32,
+            created_at: now - days(60),
+            updated_at: now - days(i as i64 % 5),
+        })
+        .collect();
+
+    let batches = ITEMS
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.quantity > 0)
+        .map(|(i, item)| BackupBatch {
+            item_id: id(i),
+            quantity: item.quantity,
+            purchased_on: today - days(7 + i as i64 % 10),
+            expires_on: item.expires_in.map(|n| today + days(n)),
+        })
+        .collect();
+
+    // A month of history for the dashboard: each item is used every few days
+    // and bought again every ten
+    let mut events = Vec::new();
+    for (i, item) in ITEMS.iter().enumerate() {
+        for day in (1..30).step_by(2 + i % 4) {
+            events.push(BackupEvent {
+                item_id: Some(id(i)),
+                item_name: item.name.to_string(),
+                kind: "used".to_string(),
+                quantity_delta: -1,
+                created_at: now - days(day as i64),
+                price_cents: None,
+                store_id: None,
+                value_cents: None,
+            });
+        }
+        for day in (5..30).step_by(10) {
+            events.push(BackupEvent {
+                item_id: Some(id(i)),
+                item_name: item.name.to_string(),
+                kind: "purchased".to_string(),
+                quantity_delta: 3,
+                created_at: now - days(day as i64),
+                price_cents: None,
+                store_id: Some(store_id(item)),
+                value_cents: None,
+            });
+        }
+    }
+    events.sort_by_key(|event| event.created_at);
+
+    let recipes = RECIPES
+        .iter()
+        .enumerate()
+        .map(|(i, recipe)| BackupRecipe {
+            id: id(i),
+            name: recipe.name.to_string(),
+            instructions: Some(recipe.instructions.to_string()),
+            ingredients: recipe
+                .ingredients
+                .iter()
+                .map(|&(item, quantity)| BackupIngredient {
+                    item_id: id(item),
+                    quantity,
+                })
+                .collect(),
+            created_at: now - days(30),
+            updated_at: now - days(30),
+        })
+        .collect();
+
+    let meal_plans = vec![
+        BackupMealPlan {
+            recipe_id: id(1),
+            planned_for: today - days(1),
+            cooked_at: Some(now - days(1)),
+            created_at: now - days(3),
+        },
+        BackupMealPlan {
+            recipe_id: id(0),
+            planned_for: today + days(1),
+            cooked_at: None,
+            created_at: now - days(1),
+        },
+        BackupMealPlan {
+            recipe_id: id(1),
+            planned_for: today + days(3),
+            cooked_at: None,
+            created_at: now - days(1),
+        },
+    ];
+
+    Backup {
+        version: BACKUP_VERSION,
+        exported_at: now,
+        preferences: UserPreferences {
+            default_location: Some("Spiżarnia".to_string()),
+            ..UserPreferences::default()
+        },
+        categories,
+        stores,
+        attribute_fields: Vec::new(),
+        items,
+        batches,
+        events,
+        recipes,
+        meal_plans,
+        consumption_rules: Vec::new(),
+    }
+}
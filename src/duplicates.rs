@@ -0,0 +1,63 @@
+//! Finds items that are probably one product entered twice, such as
+//! "Mleko 2%" and "mleko 2 %", or two items with the same barcode, so they
+//! can be merged.
+
+use crate::models::{DuplicateGroup, DuplicateReason, Item};
+use std::collections::HashMap;
+
+/// A name reduced to what tells products apart: lowercase letters and digits
+/// without Polish diacritics, words separated by single spaces.
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.chars().flat_map(char::to_lowercase) {
+        let c = match c {
+            'ą' => 'a',
+            'ć' => 'c',
+            'ę' => 'e',
+            'ł' => 'l',
+            'ń' => 'n',
+            'ó' => 'o',
+            'ś' => 's',
+            'ź' | 'ż' => 'z',
+            c => c,
+        };
+        if c.is_alphanumeric() {
+            normalized.push(c);
+        } else if !normalized.is_empty() && !normalized.ends_with(' ') {
+            normalized.push(' ');
+        }
+    }
+    normalized.truncate(normalized.trim_end().len());
+    normalized
+}
+
+/// Groups of two or more items with the same barcode or, for items whose
+/// barcode no other item has, the same normalized name. Groups and the items
+/// in them keep the order of `items`.
+pub fn find_duplicates(items: Vec<Item>) -> Vec<DuplicateGroup> {
+    let mut barcode_counts: HashMap<String, usize> = HashMap::new();
+    for barcode in items.iter().filter_map(|item| item.barcode.clone()) {
+        *barcode_counts.entry(barcode).or_default() += 1;
+    }
+
+    let mut group_index: HashMap<(DuplicateReason, String), usize> = HashMap::new();
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    for item in items {
+        let key = match &item.barcode {
+            Some(barcode) if barcode_counts[barcode] > 1 => {
+                (DuplicateReason::Barcode, barcode.clone())
+            }
+            _ => (DuplicateReason::Name, normalize_name(&item.name)),
+        };
+        let index = *group_index.entry(key).or_insert_with_key(|(reason, _)| {
+            groups.push(DuplicateGroup {
+                reason: *reason,
+                items: Vec::new(),
+            });
+            groups.len() - 1
+        });
+        groups[index].items.push(item);
+    }
+    groups.retain(|group| group.items.len() > 1);
+    groups
+}
@@ -1,59 +1,401 @@
+use crate::{AppState, reporting, storage::StorageError, validation::ValidationErrors};
 use axum::{
-    Json,
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    Extension, Json,
+    body::Body,
+    extract::{FromRequest, OptionalFromRequest, State, rejection::JsonRejection},
+    http::{HeaderValue, Request, StatusCode, header},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
 };
-use serde_json::json;
-use sqlx::Error as SqlxError;
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{Error as SqlxError, error::ErrorKind};
+use std::sync::Arc;
+use tera::Context;
 use tera::Error as TeraError;
 
+/// Seconds clients are asked to wait during maintenance before retrying.
+const MAINTENANCE_RETRY_AFTER: u32 = 60;
+
 #[derive(Debug)]
 pub enum AppError {
     SqlxError(SqlxError),
     TeraError(TeraError),
     ItemNotFound,
+    NotFound(String),
     BadRequest(String),
+    Unauthorized,
+    /// 403: signed in, but the user's role doesn't allow the request.
+    Forbidden(String),
+    /// 409: the request clashes with existing data, e.g. a taken email.
+    Conflict {
+        code: ErrorCode,
+        field: Option<&'static str>,
+        message: String,
+    },
+    /// 422: fields of the payload failed validation.
+    Validation(ValidationErrors),
+    /// 422: the request refers to something that doesn't exist.
+    Unprocessable {
+        field: Option<&'static str>,
+        message: String,
+    },
+    /// The JSON body was malformed or of the wrong shape; keeps the status
+    /// axum chose for the rejection.
+    InvalidJson(StatusCode, String),
+    /// 413: the body is over the limit of the route.
+    PayloadTooLarge(String),
+    /// 408: the request wasn't done within the configured time.
+    Timeout,
+    /// 503: maintenance mode is on.
+    Maintenance,
     InternalServerError(String),
 }
 
+/// Stable codes for API clients to branch on, sent as `"code"` with every
+/// error; the messages are meant for people and may change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    BadRequest,
+    InvalidJson,
+    Unauthenticated,
+    Forbidden,
+    NotFound,
+    ItemNotFound,
+    AlreadyExists,
+    InUse,
+    VersionConflict,
+    ValidationFailed,
+    ReferenceNotFound,
+    PayloadTooLarge,
+    RequestTimeout,
+    Maintenance,
+    InternalError,
+}
+
+/// Message and offending field of an error response, kept in its extensions
+/// so web routes can render the error as a page instead of JSON.
+#[derive(Debug, Clone)]
+pub struct ErrorInfo {
+    pub message: String,
+    pub field: Option<&'static str>,
+}
+
 impl From<SqlxError> for AppError {
     fn from(err: SqlxError) -> Self {
         match err {
             SqlxError::RowNotFound => AppError::ItemNotFound,
+            SqlxError::Database(ref db_err) => {
+                let constraint = db_err.constraint().unwrap_or_default();
+                match db_err.kind() {
+                    ErrorKind::UniqueViolation => {
+                        let (field, message) = unique_violation(constraint);
+                        AppError::Conflict {
+                            code: ErrorCode::AlreadyExists,
+                            field,
+                            message: message.to_string(),
+                        }
+                    }
+                    // Deleting a row something else still points at
+                    ErrorKind::ForeignKeyViolation
+                        if db_err.message().starts_with("update or delete") =>
+                    {
+                        AppError::Conflict {
+                            code: ErrorCode::InUse,
+                            field: None,
+                            message: "This record is still in use".to_string(),
+                        }
+                    }
+                    ErrorKind::ForeignKeyViolation => {
+                        let (field, message) = foreign_key_violation(constraint);
+                        AppError::Unprocessable {
+                            field,
+                            message: message.to_string(),
+                        }
+                    }
+                    ErrorKind::CheckViolation => {
+                        let (field, message) = check_violation(constraint);
+                        let mut errors = ValidationErrors::new();
+                        errors.add(field, message);
+                        AppError::Validation(errors)
+                    }
+                    _ => AppError::SqlxError(err),
+                }
+            }
             _ => AppError::SqlxError(err),
         }
     }
 }
 
+/// Field and message for a violated unique constraint or index, by name.
+fn unique_violation(constraint: &str) -> (Option<&'static str>, &'static str) {
+    match constraint {
+        "users_email_key" => (Some("email"), "An account with this email already exists"),
+        "idx_items_account_id_name" => (Some("name"), "An item with this name already exists"),
+        "idx_stores_user_id_name" => (Some("name"), "A store with this name already exists"),
+        "idx_attribute_fields_user_id_name" => {
+            (Some("name"), "A field with this name already exists")
+        }
+        "idx_stocktakes_user_id_open" => (None, "A stocktake is already in progress"),
+        _ => (None, "This record already exists"),
+    }
+}
+
+/// Field and message for a reference to a missing row, by constraint name.
+fn foreign_key_violation(constraint: &str) -> (Option<&'static str>, &'static str) {
+    match constraint {
+        "fk_category_items" => (Some("category_id"), "The category does not exist"),
+        "fk_store_items" => (Some("preferred_store_id"), "The store does not exist"),
+        "fk_store_purchases" => (Some("store_id"), "The store does not exist"),
+        "categories_parent_id_fkey" => (Some("parent_id"), "The parent category does not exist"),
+        "fk_item_recipe_ingredients" => (Some("ingredients"), "An ingredient item does not exist"),
+        "fk_recipe_meal_plans" => (Some("recipe_id"), "The recipe does not exist"),
+        "fk_item_item_events" | "fk_item_stocktake_entries" | "item_batches_item_id_fkey" => {
+            (Some("item_id"), "The item does not exist")
+        }
+        _ => (None, "A referenced record does not exist"),
+    }
+}
+
+/// Field and message for a value a CHECK constraint rejected, by name.
+fn check_violation(constraint: &str) -> (&'static str, &'static str) {
+    match constraint {
+        "items_quantity_non_negative" => ("quantity", "must not be negative"),
+        "items_restock_threshold_non_negative" => ("restock_threshold", "must not be negative"),
+        "items_restock_to_non_negative" => ("restock_to", "must not be negative"),
+        "items_quantity_step_positive" => ("quantity_step", "must be greater than zero"),
+        "purchases_price_non_negative" => ("price", "must not be negative"),
+        "item_batches_quantity_check" | "recipe_ingredients_quantity_check" => {
+            ("quantity", "must be greater than zero")
+        }
+        _ => ("form", "contains a value out of range"),
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        AppError::Validation(errors)
+    }
+}
+
 impl From<TeraError> for AppError {
     fn from(err: TeraError) -> Self {
         AppError::TeraError(err)
     }
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+impl From<StorageError> for AppError {
+    fn from(err: StorageError) -> Self {
+        AppError::InternalServerError(format!("File storage failed: {err}"))
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge(rejection.body_text()),
+            status => AppError::InvalidJson(status, rejection.body_text()),
+        }
+    }
+}
+
+impl AppError {
+    /// The record changed since the client read the version it sent.
+    pub fn version_conflict() -> Self {
+        AppError::Conflict {
+            code: ErrorCode::VersionConflict,
+            field: Some("version"),
+            message: "The item was changed in the meantime; reload it and try again".to_string(),
+        }
+    }
+
+    /// Status, page info and JSON envelope of the error; also used for the
+    /// per-change results of a sync batch.
+    pub fn into_parts(self) -> (StatusCode, ErrorInfo, Value) {
+        let (status, code, message) = match self {
             AppError::SqlxError(e) => {
                 tracing::error!("SQLx error: {:?}", e);
+                reporting::capture_error(&e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
                     "Database error".to_string(),
                 )
             }
             AppError::TeraError(e) => {
                 tracing::error!("Tera error: {:?}", e);
+                reporting::capture_error(&e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
                     "Template rendering error".to_string(),
                 )
             }
-            AppError::ItemNotFound => (StatusCode::NOT_FOUND, "Item not found".to_string()),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::InternalServerError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+            AppError::ItemNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::ItemNotFound,
+                "Item not found".to_string(),
+            ),
+            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, ErrorCode::NotFound, msg),
+            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, ErrorCode::BadRequest, msg),
+            AppError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthenticated,
+                "Authentication required".to_string(),
+            ),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, ErrorCode::Forbidden, msg),
+            AppError::Conflict {
+                code,
+                field,
+                message,
+            } => {
+                return field_error(StatusCode::CONFLICT, code, field, message);
+            }
+            AppError::Validation(errors) => {
+                let info = ErrorInfo {
+                    message: format!("Invalid input: {}", errors),
+                    field: None,
+                };
+                let body = json!({
+                    "error": "Validation failed",
+                    "code": ErrorCode::ValidationFailed,
+                    "details": { "fields": errors },
+                });
+                return (StatusCode::UNPROCESSABLE_ENTITY, info, body);
+            }
+            AppError::Unprocessable { field, message } => {
+                return field_error(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    ErrorCode::ReferenceNotFound,
+                    field,
+                    message,
+                );
+            }
+            AppError::InvalidJson(status, msg) => (status, ErrorCode::InvalidJson, msg),
+            AppError::PayloadTooLarge(msg) => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                ErrorCode::PayloadTooLarge,
+                msg,
+            ),
+            AppError::Timeout => (
+                StatusCode::REQUEST_TIMEOUT,
+                ErrorCode::RequestTimeout,
+                "The request took too long".to_string(),
+            ),
+            AppError::Maintenance => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::Maintenance,
+                "Down for maintenance, please try again in a few minutes".to_string(),
+            ),
+            AppError::InternalServerError(msg) => {
+                reporting::capture_message(&msg);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
+                    msg,
+                )
+            }
         };
 
-        let body = Json(json!({ "error": error_message }));
-        (status, body).into_response()
+        let info = ErrorInfo {
+            message: message.clone(),
+            field: None,
+        };
+        let body = json!({ "error": message, "code": code });
+        (status, info, body)
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let retry_after = matches!(self, AppError::Maintenance).then_some(MAINTENANCE_RETRY_AFTER);
+        let (status, info, body) = self.into_parts();
+        let mut response = (status, Extension(info), Json(body)).into_response();
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
+    }
+}
+
+/// An error about one input, named in `details.field` when it is known.
+fn field_error(
+    status: StatusCode,
+    code: ErrorCode,
+    field: Option<&'static str>,
+    message: String,
+) -> (StatusCode, ErrorInfo, Value) {
+    let body = match field {
+        Some(field) => json!({ "error": message, "code": code, "details": { "field": field } }),
+        None => json!({ "error": message, "code": code }),
+    };
+    let info = ErrorInfo { message, field };
+    (status, info, body)
+}
+
+/// `axum::Json`, but a body that can't be read is answered with the usual
+/// error envelope instead of plain text.
+#[derive(Debug, Default)]
+pub struct ApiJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        Ok(ApiJson(value))
+    }
+}
+
+impl<T, S> OptionalFromRequest<S> for ApiJson<T>
+where
+    Json<T>: OptionalFromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request<Body>, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        let value = <Json<T> as OptionalFromRequest<S>>::from_request(req, state).await?;
+        Ok(value.map(|Json(value)| ApiJson(value)))
+    }
+}
+
+/// Turns error responses of web routes into an error page, so a failed form
+/// submission doesn't leave the user looking at JSON.
+pub async fn html_errors(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let response = next.run(req).await;
+    let Some(info) = response.extensions().get::<ErrorInfo>() else {
+        return response;
+    };
+    let status = response.status();
+    let mut context = Context::new();
+    context.insert("base_path", &state.base_path);
+    context.insert("status", &status.as_u16());
+    context.insert("message", &info.message);
+    context.insert("field", &info.field);
+    match state.tera.render("error.html", context).await {
+        Ok(page) => {
+            let mut page = (status, Html(page)).into_response();
+            if let Some(retry_after) = response.headers().get(header::RETRY_AFTER) {
+                page.headers_mut()
+                    .insert(header::RETRY_AFTER, retry_after.clone());
+            }
+            page
+        }
+        Err(e) => {
+            tracing::error!("Tera error: {:?}", e);
+            response
+        }
     }
 }
@@ -0,0 +1,150 @@
+//! Downloads written while the client reads them. Rows go from the database
+//! into the response a chunk at a time, so exporting years of history takes
+//! no more memory than exporting a week of it.
+
+use axum::body::{Body, Bytes};
+use serde::Serialize;
+use sqlx::Error as SqlxError;
+use std::future::Future;
+use std::io;
+use tokio::sync::mpsc;
+
+/// Size at which the buffered JSON is handed to the response.
+const CHUNK_BYTES: usize = 64 * 1024;
+/// Chunks waiting for a slow client before the writer waits too.
+const BUFFERED_CHUNKS: usize = 2;
+
+#[derive(Debug)]
+pub enum ExportError {
+    Database(SqlxError),
+    /// The client went away; there is nobody left to write to.
+    Disconnected,
+}
+
+impl From<SqlxError> for ExportError {
+    fn from(err: SqlxError) -> Self {
+        ExportError::Database(err)
+    }
+}
+
+/// A response body that `write` fills in a task of its own. `write` must end
+/// with `JsonWriter::finish`. Headers have been sent by the time the body
+/// fails, so a database error aborts the download instead of answering 500.
+pub fn json_body<F, Fut>(write: F) -> Body
+where
+    F: FnOnce(JsonWriter) -> Fut,
+    Fut: Future<Output = Result<(), ExportError>> + Send + 'static,
+{
+    let (sender, mut receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    let writing = write(JsonWriter::new(sender.clone()));
+    tokio::spawn(async move {
+        match writing.await {
+            Ok(()) | Err(ExportError::Disconnected) => {}
+            Err(ExportError::Database(err)) => {
+                tracing::error!("Export failed: {:?}", err);
+                let _ = sender.send(Err(io::Error::other(err))).await;
+            }
+        }
+    });
+    Body::from_stream(futures_util::stream::poll_fn(move |cx| {
+        receiver.poll_recv(cx)
+    }))
+}
+
+/// Writes one JSON document in pieces: objects and arrays are opened and
+/// closed explicitly, and only their members are serialized whole.
+pub struct JsonWriter {
+    sender: mpsc::Sender<io::Result<Bytes>>,
+    buffer: Vec<u8>,
+    /// For each open object or array, whether it has a member yet.
+    nonempty: Vec<bool>,
+}
+
+impl JsonWriter {
+    fn new(sender: mpsc::Sender<io::Result<Bytes>>) -> JsonWriter {
+        JsonWriter {
+            sender,
+            buffer: Vec::with_capacity(CHUNK_BYTES),
+            nonempty: vec![],
+        }
+    }
+
+    pub fn begin_object(&mut self) {
+        self.separate();
+        self.buffer.push(b'{');
+        self.nonempty.push(false);
+    }
+
+    pub fn end_object(&mut self) {
+        self.nonempty.pop();
+        self.buffer.push(b'}');
+    }
+
+    /// Opens an array as the `name` member of the current object.
+    pub fn begin_array(&mut self, name: &str) {
+        self.key(name);
+        self.buffer.push(b'[');
+        self.nonempty.push(false);
+    }
+
+    pub fn end_array(&mut self) {
+        self.nonempty.pop();
+        self.buffer.push(b']');
+    }
+
+    /// Adds a member to the current object.
+    pub async fn field(&mut self, name: &str, value: &impl Serialize) -> Result<(), ExportError> {
+        self.key(name);
+        self.value(value);
+        self.flush_if_full().await
+    }
+
+    /// Adds an element to the current array.
+    pub async fn element(&mut self, value: &impl Serialize) -> Result<(), ExportError> {
+        self.separate();
+        self.value(value);
+        self.flush_if_full().await
+    }
+
+    /// Sends what is left of the document.
+    pub async fn finish(mut self) -> Result<(), ExportError> {
+        self.flush().await
+    }
+
+    fn separate(&mut self) {
+        if let Some(nonempty) = self.nonempty.last_mut() {
+            if *nonempty {
+                self.buffer.push(b',');
+            }
+            *nonempty = true;
+        }
+    }
+
+    fn key(&mut self, name: &str) {
+        self.separate();
+        self.value(name);
+        self.buffer.push(b':');
+    }
+
+    fn value(&mut self, value: &(impl Serialize + ?Sized)) {
+        serde_json::to_writer(&mut self.buffer, value).expect("exported records serialize to JSON");
+    }
+
+    async fn flush_if_full(&mut self) -> Result<(), ExportError> {
+        if self.buffer.len() >= CHUNK_BYTES {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), ExportError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_BYTES));
+        self.sender
+            .send(Ok(Bytes::from(chunk)))
+            .await
+            .map_err(|_| ExportError::Disconnected)
+    }
+}
@@ -0,0 +1,42 @@
+//! Items to restock as an Atom feed, for people who follow their feed reader
+//! rather than email: a share link with the `restock_feed` scope serves
+//! `/feeds/{token}/restock.atom`. Each item below its threshold is an entry,
+//! dated by its last change, so one that keeps being used while low comes
+//! up again with the new quantity.
+
+use crate::models::Item;
+use serde::Serialize;
+use tera::Context;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+#[derive(Debug, Serialize)]
+struct Entry<'a> {
+    item: &'a Item,
+    /// Tells this state of the item apart from earlier ones.
+    version: i64,
+    updated: String,
+}
+
+/// What `restock_feed.xml` needs, besides the base path.
+pub fn restock_context(items: &[Item], token: &str, now: OffsetDateTime) -> Context {
+    let format = |at: OffsetDateTime| at.format(&Rfc3339).unwrap_or_default();
+    let entries: Vec<Entry> = items
+        .iter()
+        .map(|item| Entry {
+            item,
+            version: item.updated_at.unix_timestamp(),
+            updated: format(item.updated_at),
+        })
+        .collect();
+    // An empty feed is as new as the request
+    let updated = items
+        .iter()
+        .map(|item| item.updated_at)
+        .max()
+        .unwrap_or(now);
+    let mut context = Context::new();
+    context.insert("token", token);
+    context.insert("updated", &format(updated));
+    context.insert("entries", &entries);
+    context
+}
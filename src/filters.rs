@@ -0,0 +1,135 @@
+//! Filters the templates share, so handlers pass plain data and a page
+//! formats it itself: `{{ category.color | text_color }}`,
+//! `{{ entry.created_at | humantime(tz=timezone) }}`,
+//! `{{ item.updated_at | timeago }}` and
+//! `{{ item.quantity | fmt_qty(unit=item.unit) }}`.
+
+use std::collections::HashMap;
+use tera::{Tera, Value};
+use time::{
+    Date, Duration, OffsetDateTime, format_description::well_known::Rfc3339,
+    macros::format_description,
+};
+use time_tz::{OffsetDateTimeExt, TimeZone, timezones};
+
+pub fn register(tera: &mut Tera) {
+    tera.register_filter("text_color", text_color);
+    tera.register_filter("humantime", humantime);
+    tera.register_filter("timeago", timeago);
+    tera.register_filter("fmt_qty", fmt_qty);
+}
+
+/// Whether `name` is a time zone `humantime` can convert to, e.g. `Europe/Warsaw`.
+pub fn is_timezone(name: &str) -> bool {
+    timezones::get_by_name(name).is_some()
+}
+
+/// The names of all time zones, sorted, for the settings page to pick from.
+pub fn timezone_names() -> Vec<&'static str> {
+    let mut names: Vec<&str> = timezones::iter().map(|tz| tz.name()).collect();
+    names.sort_unstable();
+    names
+}
+
+/// Black or white, whichever reads better on the `#rrggbb` background.
+pub fn text_color_for_bg(hex_color: &str) -> String {
+    let hex_color = hex_color.trim_start_matches('#');
+    if hex_color.len() != 6 {
+        return "#000000".to_string(); // Default to black for invalid colors
+    }
+
+    let r = u8::from_str_radix(&hex_color[0..2], 16).unwrap_or(0);
+    let g = u8::from_str_radix(&hex_color[2..4], 16).unwrap_or(0);
+    let b = u8::from_str_radix(&hex_color[4..6], 16).unwrap_or(0);
+
+    // Formula for perceived brightness
+    let brightness = ((r as u32 * 299) + (g as u32 * 587) + (b as u32 * 114)) / 1000;
+
+    if brightness > 150 {
+        // Threshold can be adjusted
+        "#000000".to_string() // Black text for light backgrounds
+    } else {
+        "#FFFFFF".to_string() // White text for dark backgrounds
+    }
+}
+
+/// `text_color`: the text color for a background color.
+fn text_color(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let color = value.as_str().ok_or("text_color needs a color")?;
+    Ok(Value::from(text_color_for_bg(color)))
+}
+
+/// `humantime(tz=timezone)`: an RFC 3339 timestamp as `2025-09-27 14:05`,
+/// in the time zone `tz` if given; a plain date stays as it is.
+fn humantime(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let text = value.as_str().ok_or("humantime needs a timestamp")?;
+    if let Ok(mut at) = OffsetDateTime::parse(text, &Rfc3339) {
+        if let Some(name) = args.get("tz").and_then(Value::as_str) {
+            let tz = timezones::get_by_name(name).ok_or(format!("unknown time zone {name:?}"))?;
+            at = at.to_timezone(tz);
+        }
+        let formatted = at
+            .format(format_description!("[year]-[month]-[day] [hour]:[minute]"))
+            .map_err(|e| format!("cannot format {text:?}: {e}"))?;
+        return Ok(Value::from(formatted));
+    }
+    Date::parse(text, format_description!("[year]-[month]-[day]"))
+        .map_err(|_| format!("humantime got {text:?}, not a timestamp"))?;
+    Ok(Value::from(text))
+}
+
+/// `timeago`: how long ago an RFC 3339 timestamp was, as `3 godziny temu`,
+/// or how far ahead it is, as `za 2 dni`.
+fn timeago(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let text = value.as_str().ok_or("timeago needs a timestamp")?;
+    let at = OffsetDateTime::parse(text, &Rfc3339)
+        .map_err(|_| format!("timeago got {text:?}, not a timestamp"))?;
+    Ok(Value::from(relative_time(at - OffsetDateTime::now_utc())))
+}
+
+/// `offset` from now in words, negative for the past.
+fn relative_time(offset: Duration) -> String {
+    let span = offset.abs();
+    let amount = if span < Duration::MINUTE {
+        return "przed chwilą".to_string();
+    } else if span < Duration::HOUR {
+        counted(span.whole_minutes(), ["minutę", "minuty", "minut"])
+    } else if span < Duration::DAY {
+        counted(span.whole_hours(), ["godzinę", "godziny", "godzin"])
+    } else if span < Duration::days(30) {
+        counted(span.whole_days(), ["dzień", "dni", "dni"])
+    } else if span < Duration::days(365) {
+        counted(span.whole_days() / 30, ["miesiąc", "miesiące", "miesięcy"])
+    } else {
+        counted(span.whole_days() / 365, ["rok", "lata", "lat"])
+    };
+    if offset.is_negative() {
+        format!("{amount} temu")
+    } else {
+        format!("za {amount}")
+    }
+}
+
+/// `n` with the Polish form of the noun that goes with it: one, a few, many.
+fn counted(n: i64, [one, few, many]: [&str; 3]) -> String {
+    if n == 1 {
+        return one.to_string();
+    }
+    let noun = match (n % 10, n % 100) {
+        (2..=4, tens) if !(12..=14).contains(&tens) => few,
+        _ => many,
+    };
+    format!("{n} {noun}")
+}
+
+/// `fmt_qty(unit=item.unit)`: a quantity followed by its unit, if it has one.
+fn fmt_qty(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    if !value.is_number() {
+        return Err("fmt_qty needs a number".into());
+    }
+    let formatted = match args.get("unit").and_then(Value::as_str) {
+        Some(unit) if !unit.is_empty() => format!("{value} {unit}"),
+        _ => value.to_string(),
+    };
+    Ok(Value::from(formatted))
+}
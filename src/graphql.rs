@@ -0,0 +1,554 @@
+//! `POST /api/graphql`, for clients that want nested data in one request,
+//! e.g. the items with their category and latest changes. It reads and
+//! writes through the same queries as the REST endpoints, as the signed-in
+//! user, and reports their errors with the same `code` in `extensions`.
+
+use crate::{
+    AppState, attributes,
+    auth::{AuthUser, SignedInUser},
+    db,
+    errors::AppError,
+    models::{
+        Attributes, Category, CreateItemPayload, Item, ItemEvent, ItemFilter, ItemSort, Price,
+        PurchaseItemPayload, UpdateItemPayload, UseItemPayload,
+    },
+    pagination::{Page, PageQuery},
+    validation::{Validate, ValidationErrors},
+};
+use async_graphql::{
+    Context, EmptySubscription, Enum, Error, ErrorExtensions, InputObject, Json, Object, Request,
+    Response, Schema,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use time::{Date, OffsetDateTime};
+
+/// Deepest nesting a query may have; the schema has no cycles deeper than this.
+const MAX_DEPTH: usize = 8;
+const MAX_COMPLEXITY: usize = 500;
+/// Most events `Item.latestEvents` returns.
+const MAX_LATEST_EVENTS: i32 = 20;
+
+pub type InventorySchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+static SCHEMA: LazyLock<InventorySchema> = LazyLock::new(|| {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+});
+
+/// Runs `request` on the inventory of `user_id`, with changes made by
+/// `actor_id`, the same account or a member of its household.
+pub async fn execute(
+    state: Arc<AppState>,
+    user_id: i32,
+    actor_id: i32,
+    request: Request,
+) -> Response {
+    let request = request
+        .data(state)
+        .data(AuthUser(user_id))
+        .data(SignedInUser(actor_id));
+    SCHEMA.execute(request).await
+}
+
+/// State and user a resolver runs with, put there by `execute`.
+fn viewer<'a>(ctx: &Context<'a>) -> (&'a AppState, i32) {
+    let state = ctx.data_unchecked::<Arc<AppState>>();
+    let AuthUser(user_id) = *ctx.data_unchecked::<AuthUser>();
+    (state, user_id)
+}
+
+/// The account making the changes of a mutation, put there by `execute`.
+fn actor(ctx: &Context<'_>) -> i32 {
+    let SignedInUser(actor_id) = *ctx.data_unchecked::<SignedInUser>();
+    actor_id
+}
+
+/// The message of the REST error envelope, with its `code` and `details`
+/// as extensions.
+fn api_error(err: impl Into<AppError>) -> Error {
+    let (_, _, body) = err.into().into_parts();
+    let message = body["error"].as_str().unwrap_or_default().to_string();
+    Error::new(message).extend_with(|_, extensions| {
+        for key in ["code", "details"] {
+            if let Ok(value) = async_graphql::Value::from_json(body[key].clone())
+                && value != async_graphql::Value::Null
+            {
+                extensions.set(key, value);
+            }
+        }
+    })
+}
+
+/// Stock filters of `Query.items`, as on the item list page.
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StockFilter {
+    #[default]
+    All,
+    Low,
+    Out,
+    Expiring,
+}
+
+impl From<StockFilter> for ItemFilter {
+    fn from(filter: StockFilter) -> Self {
+        match filter {
+            StockFilter::All => ItemFilter::All,
+            StockFilter::Low => ItemFilter::Low,
+            StockFilter::Out => ItemFilter::Out,
+            StockFilter::Expiring => ItemFilter::Expiring,
+        }
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The user's items in their manual order, optionally of one category.
+    async fn items(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] filter: StockFilter,
+        category_id: Option<i32>,
+    ) -> async_graphql::Result<Vec<ItemNode>> {
+        let (state, user_id) = viewer(ctx);
+        let items = db::get_all_items(&state.db_pool, user_id, ItemSort::Manual, filter.into())
+            .await
+            .map_err(api_error)?;
+        let items: Vec<Item> = items
+            .into_iter()
+            .filter(|item| {
+                category_id.is_none()
+                    || item.category.as_ref().map(|category| category.id) == category_id
+            })
+            .collect();
+        // One query for the events of all items instead of one per item
+        if !ctx.look_ahead().field("latestEvents").exists() {
+            return Ok(items.into_iter().map(ItemNode::new).collect());
+        }
+        let ids: Vec<i32> = items.iter().map(|item| item.id).collect();
+        let events =
+            db::get_latest_item_events(&state.db_pool, user_id, &ids, MAX_LATEST_EVENTS.into())
+                .await
+                .map_err(api_error)?;
+        let mut events_by_item: HashMap<i32, Vec<ItemEvent>> = HashMap::new();
+        for event in events {
+            if let Some(item_id) = event.item_id {
+                events_by_item.entry(item_id).or_default().push(event);
+            }
+        }
+        Ok(items
+            .into_iter()
+            .map(|item| {
+                let events = events_by_item.remove(&item.id).unwrap_or_default();
+                ItemNode {
+                    item,
+                    latest_events: Some(events),
+                }
+            })
+            .collect())
+    }
+
+    /// One item, or `null` if the user has none with this id.
+    async fn item(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Option<ItemNode>> {
+        let (state, user_id) = viewer(ctx);
+        let item = db::get_item_by_id(&state.db_pool, user_id, id)
+            .await
+            .map_err(api_error)?;
+        Ok(item.map(ItemNode::new))
+    }
+
+    /// The user's categories, subcategories after their parent.
+    async fn categories(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Category>> {
+        let (state, user_id) = viewer(ctx);
+        state
+            .cache
+            .categories(&state.db_pool, user_id)
+            .await
+            .map_err(api_error)
+    }
+
+    /// Quantity changes, newest first, of all items or of one. Pages like
+    /// `GET /api/history`: pass `nextCursor` as `after` for the next one.
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        item_id: Option<i32>,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<HistoryPage> {
+        let (state, user_id) = viewer(ctx);
+        let query = PageQuery {
+            after,
+            limit: limit.map(i64::from),
+        };
+        let limit = query.limit();
+        let cursor = query.cursor().map_err(api_error)?;
+        let events = db::get_item_events(&state.db_pool, user_id, item_id, cursor, limit + 1)
+            .await
+            .map_err(api_error)?;
+        Ok(HistoryPage(Page::new(events, limit, ItemEvent::cursor)))
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn create_item(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateItemInput,
+    ) -> async_graphql::Result<ItemNode> {
+        let (state, user_id) = viewer(ctx);
+        let mut payload = CreateItemPayload::from(input);
+        payload.validate().map_err(api_error)?;
+        payload.attributes = attributes::check(&state.db_pool, user_id, payload.attributes.take())
+            .await
+            .map_err(api_error)?;
+        let item = db::create_item(&state.db_pool, user_id, payload)
+            .await
+            .map_err(api_error)?;
+        Ok(ItemNode::new(item))
+    }
+
+    /// Changes the fields given in `input`; `version` must be the item's
+    /// current one, as with `PUT /api/items/{id}`.
+    async fn update_item(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        input: UpdateItemInput,
+    ) -> async_graphql::Result<ItemNode> {
+        let (state, user_id) = viewer(ctx);
+        let mut payload = UpdateItemPayload::from(input);
+        payload.attributes = attributes::check(&state.db_pool, user_id, payload.attributes.take())
+            .await
+            .map_err(api_error)?;
+        let item = db::update_item(&state.db_pool, user_id, id, payload)
+            .await
+            .map_err(api_error)?
+            .ok_or_else(|| api_error(AppError::ItemNotFound))?;
+        Ok(ItemNode::new(item))
+    }
+
+    /// Whether there was such an item.
+    async fn delete_item(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<bool> {
+        let (state, user_id) = viewer(ctx);
+        let deleted = db::delete_item(&state.db_pool, user_id, id)
+            .await
+            .map_err(api_error)?;
+        Ok(deleted > 0)
+    }
+
+    /// Takes `quantity` units, the oldest batches first.
+    async fn use_item(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        #[graphql(default = 1)] quantity: i32,
+    ) -> async_graphql::Result<ItemNode> {
+        let (state, user_id) = viewer(ctx);
+        let payload = UseItemPayload {
+            quantity: Some(quantity),
+        };
+        payload.validate().map_err(api_error)?;
+        let item = db::use_item(&state.db_pool, user_id, actor(ctx), id, quantity)
+            .await
+            .map_err(api_error)?
+            .ok_or_else(|| api_error(AppError::ItemNotFound))?;
+        Ok(ItemNode::new(item))
+    }
+
+    /// Adds a batch of `quantity` units; `price` is what all of them cost,
+    /// e.g. `"12.99"`.
+    async fn purchase_item(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        quantity: i32,
+        expires_on: Option<Date>,
+        price: Option<String>,
+        store_id: Option<i32>,
+    ) -> async_graphql::Result<ItemNode> {
+        let (state, user_id) = viewer(ctx);
+        let price = match price {
+            Some(price) => Some(price.parse::<Price>().map_err(|message| {
+                let mut errors = ValidationErrors::new();
+                errors.add("price", message);
+                api_error(errors)
+            })?),
+            None => None,
+        };
+        let payload = PurchaseItemPayload {
+            quantity,
+            expires_on,
+            price,
+            store_id,
+        };
+        let item = db::purchase_item(&state.db_pool, user_id, actor(ctx), id, payload)
+            .await
+            .map_err(api_error)?
+            .ok_or_else(|| api_error(AppError::ItemNotFound))?;
+        Ok(ItemNode::new(item))
+    }
+}
+
+/// An item, with its latest events when `Query.items` fetched them already.
+pub struct ItemNode {
+    item: Item,
+    latest_events: Option<Vec<ItemEvent>>,
+}
+
+impl ItemNode {
+    fn new(item: Item) -> Self {
+        ItemNode {
+            item,
+            latest_events: None,
+        }
+    }
+}
+
+#[Object(name = "Item")]
+impl ItemNode {
+    async fn id(&self) -> i32 {
+        self.item.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.item.name
+    }
+
+    async fn quantity(&self) -> i32 {
+        self.item.quantity
+    }
+
+    async fn restock_threshold(&self) -> i32 {
+        self.item.restock_threshold
+    }
+
+    async fn restock_to(&self) -> Option<i32> {
+        self.item.restock_to
+    }
+
+    /// Unit of `quantity`, e.g. "kg"; `null` means pieces.
+    async fn unit(&self) -> Option<&str> {
+        self.item.unit.as_deref()
+    }
+
+    async fn location(&self) -> Option<&str> {
+        self.item.location.as_deref()
+    }
+
+    async fn barcode(&self) -> Option<&str> {
+        self.item.barcode.as_deref()
+    }
+
+    async fn notes(&self) -> Option<&str> {
+        self.item.notes.as_deref()
+    }
+
+    async fn preferred_store_id(&self) -> Option<i32> {
+        self.item.preferred_store_id
+    }
+
+    async fn quantity_step(&self) -> i32 {
+        self.item.quantity_step
+    }
+
+    /// Values of the user's own fields, by field name.
+    async fn attributes(&self) -> Json<&Attributes> {
+        Json(&self.item.attributes)
+    }
+
+    /// How many to buy to reach the restock target.
+    async fn suggested_purchase(&self) -> i32 {
+        self.item.suggested_purchase
+    }
+
+    async fn category(&self) -> Option<&Category> {
+        self.item.category.as_ref()
+    }
+
+    /// To send back with `updateItem`.
+    async fn version(&self) -> i32 {
+        self.item.version
+    }
+
+    async fn created_at(&self) -> OffsetDateTime {
+        self.item.created_at
+    }
+
+    async fn updated_at(&self) -> OffsetDateTime {
+        self.item.updated_at
+    }
+
+    /// The newest quantity changes, newest first; at most 20.
+    async fn latest_events(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 5)] limit: i32,
+    ) -> async_graphql::Result<Vec<ItemEvent>> {
+        let limit = limit.clamp(0, MAX_LATEST_EVENTS);
+        if let Some(events) = &self.latest_events {
+            return Ok(events.iter().take(limit as usize).cloned().collect());
+        }
+        let (state, user_id) = viewer(ctx);
+        db::get_item_events(
+            &state.db_pool,
+            user_id,
+            Some(self.item.id),
+            None,
+            limit.into(),
+        )
+        .await
+        .map_err(api_error)
+    }
+}
+
+#[Object]
+impl Category {
+    async fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `#rrggbb`.
+    async fn color(&self) -> &str {
+        &self.color
+    }
+
+    /// Top-level category this one is nested under.
+    async fn parent_id(&self) -> Option<i32> {
+        self.parent_id
+    }
+
+    async fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+}
+
+#[Object(name = "ItemEvent")]
+impl ItemEvent {
+    async fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// `null` once the item has been deleted.
+    async fn item_id(&self) -> Option<i32> {
+        self.item_id
+    }
+
+    async fn item_name(&self) -> &str {
+        &self.item_name
+    }
+
+    /// What happened, e.g. `purchased` or `used`.
+    async fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    async fn quantity_delta(&self) -> i32 {
+        self.quantity_delta
+    }
+
+    async fn created_at(&self) -> OffsetDateTime {
+        self.created_at
+    }
+}
+
+pub struct HistoryPage(Page<ItemEvent>);
+
+#[Object]
+impl HistoryPage {
+    async fn events(&self) -> &[ItemEvent] {
+        &self.0.items
+    }
+
+    /// `null` on the last page.
+    async fn next_cursor(&self) -> Option<&str> {
+        self.0.next_cursor.as_deref()
+    }
+}
+
+#[derive(InputObject)]
+pub struct CreateItemInput {
+    name: String,
+    quantity: i32,
+    restock_threshold: Option<i32>,
+    restock_to: Option<i32>,
+    unit: Option<String>,
+    location: Option<String>,
+    barcode: Option<String>,
+    notes: Option<String>,
+    /// Expiry date of the initial batch.
+    expires_on: Option<Date>,
+    category_id: Option<i32>,
+    preferred_store_id: Option<i32>,
+    quantity_step: Option<i32>,
+    attributes: Option<Json<Attributes>>,
+}
+
+impl From<CreateItemInput> for CreateItemPayload {
+    fn from(input: CreateItemInput) -> Self {
+        CreateItemPayload {
+            name: input.name,
+            quantity: input.quantity,
+            restock_threshold: input.restock_threshold,
+            restock_to: input.restock_to,
+            unit: input.unit,
+            location: input.location,
+            barcode: input.barcode,
+            notes: input.notes,
+            expires_on: input.expires_on,
+            category_id: input.category_id,
+            preferred_store_id: input.preferred_store_id,
+            quantity_step: input.quantity_step,
+            attributes: input.attributes.map(|Json(attributes)| attributes),
+        }
+    }
+}
+
+#[derive(InputObject)]
+pub struct UpdateItemInput {
+    name: Option<String>,
+    quantity: Option<i32>,
+    restock_threshold: Option<i32>,
+    restock_to: Option<i32>,
+    unit: Option<String>,
+    location: Option<String>,
+    barcode: Option<String>,
+    notes: Option<String>,
+    category_id: Option<i32>,
+    preferred_store_id: Option<i32>,
+    quantity_step: Option<i32>,
+    attributes: Option<Json<Attributes>>,
+    /// The item's `version` when the client read it.
+    version: i32,
+}
+
+impl From<UpdateItemInput> for UpdateItemPayload {
+    fn from(input: UpdateItemInput) -> Self {
+        UpdateItemPayload {
+            name: input.name,
+            quantity: input.quantity,
+            restock_threshold: input.restock_threshold,
+            restock_to: input.restock_to,
+            unit: input.unit,
+            location: input.location,
+            barcode: input.barcode,
+            notes: input.notes,
+            category_id: input.category_id,
+            preferred_store_id: input.preferred_store_id,
+            quantity_step: input.quantity_step,
+            attributes: input.attributes.map(|Json(attributes)| attributes),
+            version: input.version,
+        }
+    }
+}
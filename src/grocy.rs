@@ -0,0 +1,236 @@
+use serde::{Deserialize, Deserializer, Serialize, de};
+use std::{collections::HashMap, time::Duration};
+use time::{Date, macros::format_description};
+
+/// Body of `POST /api/import/grocy`: either the address of a Grocy instance or
+/// the JSON its `/api/objects/{entity}` endpoints return.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum GrocyImportPayload {
+    /// Fetch everything from a running Grocy instance.
+    Api { url: String, api_key: String },
+    /// Objects exported earlier, keyed like the Grocy entities.
+    Export(GrocyExport),
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GrocyExport {
+    pub products: Vec<GrocyProduct>,
+    #[serde(default)]
+    pub quantity_units: Vec<GrocyNamed>,
+    #[serde(default)]
+    pub locations: Vec<GrocyNamed>,
+    #[serde(default)]
+    pub product_groups: Vec<GrocyNamed>,
+    /// Stock entries (`/api/objects/stock`), one per purchase.
+    #[serde(default)]
+    pub stock: Vec<GrocyStockEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrocyNamed {
+    #[serde(deserialize_with = "grocy_id")]
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrocyProduct {
+    #[serde(deserialize_with = "grocy_id")]
+    pub id: i64,
+    pub name: String,
+    #[serde(default, deserialize_with = "grocy_optional_id")]
+    pub location_id: Option<i64>,
+    #[serde(default, deserialize_with = "grocy_optional_id")]
+    pub qu_id_stock: Option<i64>,
+    #[serde(default, deserialize_with = "grocy_optional_id")]
+    pub product_group_id: Option<i64>,
+    #[serde(default, deserialize_with = "grocy_number")]
+    pub min_stock_amount: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrocyStockEntry {
+    #[serde(deserialize_with = "grocy_id")]
+    pub product_id: i64,
+    #[serde(deserialize_with = "grocy_number")]
+    pub amount: f64,
+    #[serde(default)]
+    pub best_before_date: Option<String>,
+    #[serde(default)]
+    pub purchased_date: Option<String>,
+}
+
+/// An item ready to be inserted, with names instead of Grocy ids.
+#[derive(Debug, PartialEq)]
+pub struct ImportedItem {
+    pub name: String,
+    pub unit: Option<String>,
+    pub location: Option<String>,
+    pub restock_threshold: i32,
+    pub category: Option<String>,
+    pub batches: Vec<ImportedBatch>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImportedBatch {
+    pub quantity: i32,
+    pub purchased_on: Option<Date>,
+    pub expires_on: Option<Date>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct GrocyImportSummary {
+    pub categories_created: usize,
+    pub items_created: usize,
+    /// Products whose name already exists; importing again skips them all.
+    pub items_skipped: usize,
+    pub batches_created: usize,
+}
+
+#[derive(Debug)]
+pub enum GrocyError {
+    Http(reqwest::Error),
+    InvalidUrl(String),
+}
+
+impl std::fmt::Display for GrocyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrocyError::Http(e) => write!(f, "could not fetch from Grocy: {e}"),
+            GrocyError::InvalidUrl(url) => write!(f, "invalid Grocy URL: {url}"),
+        }
+    }
+}
+
+/// Downloads the objects the import needs from a Grocy instance.
+pub async fn fetch_export(url: &str, api_key: &str) -> Result<GrocyExport, GrocyError> {
+    let base = url.trim_end_matches('/');
+    if !(base.starts_with("http://") || base.starts_with("https://")) {
+        return Err(GrocyError::InvalidUrl(url.to_string()));
+    }
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(GrocyError::Http)?;
+
+    async fn fetch<T: de::DeserializeOwned>(
+        client: &reqwest::Client,
+        base: &str,
+        api_key: &str,
+        entity: &str,
+    ) -> Result<Vec<T>, GrocyError> {
+        client
+            .get(format!("{base}/api/objects/{entity}"))
+            .header("GROCY-API-KEY", api_key)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(GrocyError::Http)?
+            .json()
+            .await
+            .map_err(GrocyError::Http)
+    }
+
+    Ok(GrocyExport {
+        products: fetch(&client, base, api_key, "products").await?,
+        quantity_units: fetch(&client, base, api_key, "quantity_units").await?,
+        locations: fetch(&client, base, api_key, "locations").await?,
+        product_groups: fetch(&client, base, api_key, "product_groups").await?,
+        stock: fetch(&client, base, api_key, "stock").await?,
+    })
+}
+
+/// Maps Grocy objects onto items. Fractional amounts are rounded up, so an
+/// opened package still counts as one unit.
+pub fn plan_import(export: GrocyExport) -> Vec<ImportedItem> {
+    let names = |objects: Vec<GrocyNamed>| -> HashMap<i64, String> {
+        objects.into_iter().map(|o| (o.id, o.name)).collect()
+    };
+    let units = names(export.quantity_units);
+    let locations = names(export.locations);
+    let groups = names(export.product_groups);
+
+    let mut batches: HashMap<i64, Vec<ImportedBatch>> = HashMap::new();
+    for entry in export.stock {
+        let quantity = entry.amount.ceil() as i32;
+        if quantity <= 0 {
+            continue;
+        }
+        batches
+            .entry(entry.product_id)
+            .or_default()
+            .push(ImportedBatch {
+                quantity,
+                purchased_on: entry.purchased_date.as_deref().and_then(parse_date),
+                expires_on: entry.best_before_date.as_deref().and_then(parse_date),
+            });
+    }
+
+    export
+        .products
+        .into_iter()
+        .map(|product| ImportedItem {
+            name: product.name.trim().to_string(),
+            unit: product.qu_id_stock.and_then(|id| units.get(&id).cloned()),
+            location: product
+                .location_id
+                .and_then(|id| locations.get(&id).cloned()),
+            restock_threshold: product.min_stock_amount.ceil().max(0.0) as i32,
+            category: product
+                .product_group_id
+                .and_then(|id| groups.get(&id).cloned()),
+            batches: batches.remove(&product.id).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Colors given to categories created from Grocy product groups, in turn.
+const CATEGORY_COLORS: &[&str] = &[
+    "#a6b93c", "#4a90c8", "#8fd3d3", "#c8a2d6", "#e0a458", "#c85656", "#7fb77e",
+];
+
+pub fn category_color(index: usize) -> &'static str {
+    CATEGORY_COLORS[index % CATEGORY_COLORS.len()]
+}
+
+/// Grocy dates are `YYYY-MM-DD`; `2999-12-31` means "never expires".
+fn parse_date(value: &str) -> Option<Date> {
+    let date = Date::parse(
+        value.get(..10)?,
+        format_description!("[year]-[month]-[day]"),
+    )
+    .ok()?;
+    (date.year() < 2999).then_some(date)
+}
+
+// Depending on the version and database, Grocy returns numbers as JSON
+// numbers or as strings, and empty references as "" or null.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrText {
+    Number(f64),
+    Text(String),
+}
+
+fn grocy_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    match Option::<NumberOrText>::deserialize(deserializer)? {
+        None => Ok(0.0),
+        Some(NumberOrText::Number(n)) => Ok(n),
+        Some(NumberOrText::Text(s)) if s.trim().is_empty() => Ok(0.0),
+        Some(NumberOrText::Text(s)) => s.trim().parse().map_err(de::Error::custom),
+    }
+}
+
+fn grocy_optional_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i64>, D::Error> {
+    match Option::<NumberOrText>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrText::Number(n)) => Ok(Some(n as i64)),
+        Some(NumberOrText::Text(s)) if s.trim().is_empty() => Ok(None),
+        Some(NumberOrText::Text(s)) => s.trim().parse().map(Some).map_err(de::Error::custom),
+    }
+}
+
+fn grocy_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    grocy_optional_id(deserializer)?.ok_or_else(|| de::Error::custom("missing Grocy id"))
+}
@@ -1,138 +1,427 @@
 use crate::{
+    activity, assistant, attributes,
+    auth::{AuthUser, CurrentSession, SignedInUser},
+    backup::{self, Backup},
+    categories, conditional,
     db::{self as db_queries},
-    errors::AppError,
-    models::{CreateItemPayload, Item, Notification, PurchaseItemPayload, UpdateItemPayload},
+    duplicates,
+    errors::{ApiJson, AppError},
+    export, graphql,
+    grocy::{self, GrocyImportPayload},
+    home_assistant, households,
+    models::{
+        ActivityQuery, AdjustItemPayload, AssistantIntent, AttributeFieldPayload, CatalogQuery,
+        CheckoutPayload, CollapseCategoryPayload, ConsumptionRulePayload, CreateApiTokenPayload,
+        CreateItemPayload, CreateMealPlanPayload, CreateNotificationChannelPayload,
+        CreateRecipePayload, CreateShareLinkPayload, DeleteCategoryOutcome, DeleteCategoryPayload,
+        DiscardItemPayload, HaConsumePayload, InviteHouseholdMemberPayload, ItemEvent, ItemFilter,
+        ItemSort, MealPlanQuery, MergeCategoryOutcome, MergeCategoryPayload, MergeItemOutcome,
+        MergeItemPayload, Notification, NotificationKind, PurchaseItemPayload, PurchaseQuery,
+        ReorderPayload, RestockItemsPayload, ShoppingListExportQuery, StatsQuery, StorePayload,
+        SwitchHouseholdPayload, UpdateHouseholdMemberPayload, UpdateItemPayload,
+        UpdateNotificationSettingsPayload, UpdatePreferencesPayload, UpdateStocktakeCountsPayload,
+        UseItemPayload, WasteQuery,
+    },
+    notify,
+    pagination::{Page, PageQuery},
+    proxy::Client,
+    purchases, qr,
+    receipts::{self, ReceiptFile},
+    recipes::{self, CookOutcome},
+    sharing, shopping_list,
+    sync::{self, SyncBatch, SyncQuery},
+    validation::Validate,
+    waste,
 };
 use axum::{
     Json,
-    extract::{Json as AxumJson, Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
 };
-use axum_extra::extract::CookieJar;
+use serde_json::json;
 use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use time::OffsetDateTime;
 
 use crate::AppState;
 use std::sync::Arc;
 
 // Helper to check and prepare notifications for API
 async fn get_api_notifications(pool: &PgPool, user_id: i32) -> Vec<Notification> {
-    match db_queries::get_items_to_restock(pool, user_id).await {
+    let urgent = db_queries::get_urgent_restocks(pool, user_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to get urgent restocks for API: {:?}", e);
+            vec![]
+        });
+    let mut notifications = match db_queries::get_items_to_restock(pool, user_id).await {
         Ok(items_to_restock) => items_to_restock
             .into_iter()
             .map(|item| Notification {
+                kind: NotificationKind::Restock,
                 item_name: item.name.clone(),
                 message: format!(
-                    "Item '{}' needs restocking. Current: {}, Threshold: {}.",
-                    item.name, item.quantity, item.restock_threshold
+                    "Item '{}' needs restocking. Current: {}, Threshold: {}. Buy {}.",
+                    item.name, item.quantity, item.restock_threshold, item.suggested_purchase
                 ),
+                urgent: urgent.iter().any(|u| u.item_id == item.id),
             })
             .collect(),
         Err(e) => {
             tracing::error!("Failed to get items to restock for API: {:?}", e);
             vec![]
         }
+    };
+
+    let today = OffsetDateTime::now_utc().date();
+    match db_queries::get_expiring_batches(pool, user_id, db_queries::EXPIRY_WARNING_DAYS).await {
+        Ok(batches) => notifications.extend(batches.into_iter().map(|batch| {
+            let verb = if batch.expires_on < today {
+                "expired"
+            } else {
+                "expire"
+            };
+            Notification {
+                kind: NotificationKind::Expiry,
+                item_name: batch.item_name.clone(),
+                message: format!(
+                    "{} unit(s) of '{}' {} on {}.",
+                    batch.quantity, batch.item_name, verb, batch.expires_on
+                ),
+                urgent: false,
+            }
+        })),
+        Err(e) => tracing::error!("Failed to get expiring batches for API: {:?}", e),
     }
+    notifications
 }
 
+/// Answers `304` to clients polling an unchanged list, without loading it.
+/// GET /api/items. `attr.{field}={value}` parameters keep only the items
+/// with those custom field values.
 pub async fn list_items_api(
     State(app_state): State<Arc<AppState>>,
-    jar: CookieJar,
-) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or(AppError::BadRequest("Authentication required".into()))?;
-    let items = db_queries::get_all_items(&app_state.db_pool, user_id).await?;
-    Ok(Json(items))
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let filter: BTreeMap<_, _> = query
+        .iter()
+        .filter(|(key, _)| key.starts_with(attributes::FILTER_PREFIX))
+        .collect();
+    let fingerprint = db_queries::get_items_fingerprint(&app_state.db_pool, user_id).await?;
+    let etag = conditional::weak_etag(("items", fingerprint, &filter));
+    if conditional::is_fresh(&headers, &etag) {
+        return Ok(conditional::not_modified(etag));
+    }
+    let items = if filter.is_empty() {
+        db_queries::get_all_items(
+            &app_state.db_pool,
+            user_id,
+            ItemSort::Manual,
+            ItemFilter::All,
+        )
+        .await?
+    } else {
+        let fields = db_queries::list_attribute_fields(&app_state.db_pool, user_id).await?;
+        let filter = attributes::filter_from_query(&fields, &query)?;
+        db_queries::get_items_with_attributes(&app_state.db_pool, user_id, &filter).await?
+    };
+    Ok(conditional::with_etag(etag, Json(items)))
 }
 
 pub async fn get_item_api(
     State(app_state): State<Arc<AppState>>,
-    jar: CookieJar,
+    AuthUser(user_id): AuthUser,
     Path(item_id): Path<i32>,
-) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or(AppError::BadRequest("Authentication required".into()))?;
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let fingerprint = db_queries::get_item_fingerprint(&app_state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let etag = conditional::weak_etag(("item", item_id, fingerprint));
+    if conditional::is_fresh(&headers, &etag) {
+        return Ok(conditional::not_modified(etag));
+    }
     let item = db_queries::get_item_by_id(&app_state.db_pool, user_id, item_id)
         .await?
         .ok_or(AppError::ItemNotFound)?;
-    Ok(Json(item))
+    Ok(conditional::with_etag(etag, Json(item)))
+}
+
+pub async fn get_item_qr_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+    client: Client,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::get_item_by_id(&app_state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let origin = client
+        .origin(&headers)
+        .ok_or_else(|| AppError::BadRequest("Missing Host header".into()))?;
+    let image = qr::png(&qr::use_url(&origin, &app_state.base_path, item_id))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, "private, max-age=86400"),
+        ],
+        image,
+    ))
 }
 
 pub async fn create_item_api(
     State(app_state): State<Arc<AppState>>,
-    jar: CookieJar,
-    AxumJson(payload): AxumJson<CreateItemPayload>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(mut payload): ApiJson<CreateItemPayload>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or(AppError::BadRequest("Authentication required".into()))?;
+    payload.validate()?;
+    payload.attributes =
+        attributes::check(&app_state.db_pool, user_id, payload.attributes.take()).await?;
     let item = db_queries::create_item(&app_state.db_pool, user_id, payload).await?;
-    let notifications = get_api_notifications(&app_state.db_pool, user_id).await;
     Ok((StatusCode::CREATED, Json(item)))
 }
 
 pub async fn update_item_api(
     State(app_state): State<Arc<AppState>>,
-    jar: CookieJar,
+    AuthUser(user_id): AuthUser,
     Path(item_id): Path<i32>,
-    AxumJson(payload): AxumJson<UpdateItemPayload>,
+    ApiJson(mut payload): ApiJson<UpdateItemPayload>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or(AppError::BadRequest("Authentication required".into()))?;
+    payload.attributes =
+        attributes::check(&app_state.db_pool, user_id, payload.attributes.take()).await?;
     let item = db_queries::update_item(&app_state.db_pool, user_id, item_id, payload)
         .await?
         .ok_or(AppError::ItemNotFound)?;
-    let notifications = get_api_notifications(&app_state.db_pool, user_id).await;
     Ok(Json(item))
 }
 
 pub async fn use_item_api(
     State(app_state): State<Arc<AppState>>,
-    jar: CookieJar,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
     Path(item_id): Path<i32>,
+    payload: Option<ApiJson<UseItemPayload>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or(AppError::BadRequest("Authentication required".into()))?;
-    let item = db_queries::use_item(&app_state.db_pool, user_id, item_id).await?;
-    let notifications = get_api_notifications(&app_state.db_pool, user_id).await;
+    let ApiJson(payload) = payload.unwrap_or_default();
+    payload.validate()?;
+    let quantity = payload.quantity.unwrap_or(1);
+    let item = db_queries::use_item(&app_state.db_pool, user_id, actor_id, item_id, quantity)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    Ok(Json(item))
+}
+
+/// POST /api/items/restock-all: buys every low item up to its target in
+/// one go, e.g. after a big shopping trip.
+pub async fn restock_items_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    payload: Option<ApiJson<RestockItemsPayload>>,
+) -> Result<impl IntoResponse, AppError> {
+    let ApiJson(payload) = payload.unwrap_or_default();
+    let items = db_queries::restock_items(
+        &app_state.db_pool,
+        user_id,
+        actor_id,
+        payload.item_ids.as_deref(),
+    )
+    .await?;
+    Ok(Json(items))
+}
+
+/// POST /api/items/{id}/discard: throws units away as expired or spoiled.
+pub async fn discard_item_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(item_id): Path<i32>,
+    ApiJson(payload): ApiJson<DiscardItemPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let item = db_queries::discard_item(&app_state.db_pool, user_id, actor_id, item_id, payload)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
     Ok(Json(item))
 }
 
+/// GET /api/consumption-rules: the user's rules, the next one due first.
+pub async fn list_consumption_rules_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let rules = db_queries::list_consumption_rules(&app_state.db_pool, user_id).await?;
+    Ok(Json(rules))
+}
+
+pub async fn get_consumption_rule_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let rule = db_queries::get_consumption_rule(&app_state.db_pool, user_id, item_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Consumption rule not found".into()))?;
+    Ok(Json(rule))
+}
+
+/// PUT /api/items/{id}/consumption-rule: uses `quantity` units of the item
+/// every `every_days` days from now on.
+pub async fn set_consumption_rule_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+    ApiJson(payload): ApiJson<ConsumptionRulePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let rule = db_queries::set_consumption_rule(&app_state.db_pool, user_id, item_id, &payload)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    Ok(Json(rule))
+}
+
+pub async fn delete_consumption_rule_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows =
+        db_queries::delete_consumption_rule(&app_state.db_pool, user_id, item_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Consumption rule not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn purchase_item_api(
     State(app_state): State<Arc<AppState>>,
-    jar: CookieJar,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
     Path(item_id): Path<i32>,
-    AxumJson(payload): AxumJson<PurchaseItemPayload>,
+    ApiJson(payload): ApiJson<PurchaseItemPayload>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or(AppError::BadRequest("Authentication required".into()))?;
-    let item = db_queries::purchase_item(&app_state.db_pool, user_id, item_id, payload)
+    let item = db_queries::purchase_item(&app_state.db_pool, user_id, actor_id, item_id, payload)
         .await?
         .ok_or(AppError::ItemNotFound)?;
-    let notifications = get_api_notifications(&app_state.db_pool, user_id).await;
     Ok(Json(item))
 }
 
+fn validate_reorder_payload(payload: &ReorderPayload) -> Result<(), AppError> {
+    let mut ids = payload.ids.clone();
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.len() != payload.ids.len() {
+        return Err(AppError::BadRequest(
+            "ids must not contain duplicates".into(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn reorder_items_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<ReorderPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_reorder_payload(&payload)?;
+    db_queries::reorder_items(&app_state.db_pool, user_id, &payload.ids).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_item_batches_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::get_item_by_id(&app_state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let batches = db_queries::get_item_batches(&app_state.db_pool, user_id, item_id).await?;
+    Ok(Json(batches))
+}
+
+pub async fn discard_batch_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path((item_id, batch_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = db_queries::discard_batch(&app_state.db_pool, user_id, actor_id, item_id, batch_id)
+        .await?
+        .ok_or(AppError::NotFound("Batch not found".into()))?;
+    Ok(Json(item))
+}
+
+pub async fn adjust_item_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(item_id): Path<i32>,
+    ApiJson(payload): ApiJson<AdjustItemPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let item = db_queries::adjust_item(&app_state.db_pool, user_id, actor_id, item_id, payload)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    Ok(Json(item))
+}
+
+/// Copies an item's details, without its stock, into a new item.
+pub async fn clone_item_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = db_queries::clone_item(&app_state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+/// Items that are probably the same product, for merging.
+pub async fn list_duplicate_items_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let items =
+        db_queries::get_all_items(&app_state.db_pool, user_id, ItemSort::Name, ItemFilter::All)
+            .await?;
+    Ok(Json(duplicates::find_duplicates(items)))
+}
+
+pub async fn merge_item_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+    Query(query): Query<MergeItemPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::merge_item(&app_state.db_pool, user_id, item_id, query.into).await? {
+        MergeItemOutcome::Merged(item) => {
+            tracing::info!(
+                "Merged item {} into {} for user {}",
+                item_id,
+                query.into,
+                user_id
+            );
+            Ok(Json(item))
+        }
+        MergeItemOutcome::NotFound => Err(AppError::ItemNotFound),
+        MergeItemOutcome::InvalidTarget => Err(AppError::BadRequest(
+            "into must be another existing item".into(),
+        )),
+    }
+}
+
 pub async fn delete_item_api(
     State(app_state): State<Arc<AppState>>,
-    jar: CookieJar,
+    AuthUser(user_id): AuthUser,
     Path(item_id): Path<i32>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or(AppError::BadRequest("Authentication required".into()))?;
     let affected_rows = db_queries::delete_item(&app_state.db_pool, user_id, item_id).await?;
     if affected_rows == 0 {
         return Err(AppError::ItemNotFound);
@@ -140,15 +429,1076 @@ pub async fn delete_item_api(
     Ok(StatusCode::NO_CONTENT)
 }
 
+pub async fn get_category_tree_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let categories = db_queries::get_categories_with_counts(&app_state.db_pool, user_id).await?;
+    Ok(Json(categories::build_tree(categories)))
+}
+
+pub async fn reorder_categories_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<ReorderPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_reorder_payload(&payload)?;
+    db_queries::reorder_categories(&app_state.db_pool, user_id, &payload.ids).await?;
+    app_state.cache.forget_categories(user_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_category_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+    Query(query): Query<DeleteCategoryPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::delete_category_with_policy(
+        &app_state.db_pool,
+        user_id,
+        category_id,
+        query.into(),
+    )
+    .await?
+    {
+        DeleteCategoryOutcome::Deleted { moved_items } => {
+            tracing::info!(
+                "Deleted category {} for user {}, {} item(s) moved",
+                category_id,
+                user_id,
+                moved_items
+            );
+            app_state.cache.forget_categories(user_id).await;
+            Ok(StatusCode::NO_CONTENT)
+        }
+        DeleteCategoryOutcome::NotFound => Err(AppError::NotFound("Category not found".into())),
+        DeleteCategoryOutcome::InvalidTarget => Err(AppError::BadRequest(
+            "reassign_to must be another existing category".into(),
+        )),
+    }
+}
+
+pub async fn merge_category_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+    Query(query): Query<MergeCategoryPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::merge_category(&app_state.db_pool, user_id, category_id, query.into).await? {
+        MergeCategoryOutcome::Merged { moved_items } => {
+            tracing::info!(
+                "Merged category {} into {} for user {}, {} item(s) moved",
+                category_id,
+                query.into,
+                user_id,
+                moved_items
+            );
+            app_state.cache.forget_categories(user_id).await;
+            Ok(StatusCode::NO_CONTENT)
+        }
+        MergeCategoryOutcome::NotFound => Err(AppError::NotFound("Category not found".into())),
+        MergeCategoryOutcome::InvalidTarget => Err(AppError::BadRequest(
+            "into must be another existing category".into(),
+        )),
+    }
+}
+
+/// PUT /api/categories/{id}/collapsed: folds or unfolds the category's group
+/// on the item list, for every device of the user.
+pub async fn set_category_collapsed_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+    ApiJson(payload): ApiJson<CollapseCategoryPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let found = db_queries::set_category_collapsed(
+        &app_state.db_pool,
+        user_id,
+        category_id,
+        payload.collapsed,
+    )
+    .await?;
+    if !found {
+        return Err(AppError::NotFound("Category not found".into()));
+    }
+    app_state.cache.forget_preferences(user_id).await;
+    let preferences = app_state
+        .cache
+        .preferences(&app_state.db_pool, user_id)
+        .await?;
+    Ok(Json(preferences))
+}
+
+pub async fn list_stores_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let stores = db_queries::list_stores(&app_state.db_pool, user_id).await?;
+    Ok(Json(stores))
+}
+
+pub async fn create_store_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<StorePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let store = db_queries::create_store(&app_state.db_pool, user_id, &payload.name).await?;
+    Ok((StatusCode::CREATED, Json(store)))
+}
+
+/// PUT /api/stores/{id}: renames the store.
+pub async fn update_store_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(store_id): Path<i32>,
+    ApiJson(payload): ApiJson<StorePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let store = db_queries::rename_store(&app_state.db_pool, user_id, store_id, &payload.name)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Store not found".into()))?;
+    Ok(Json(store))
+}
+
+/// POST /api/stores/reorder, with the ids in the order the stores are visited.
+pub async fn reorder_stores_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<ReorderPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_reorder_payload(&payload)?;
+    db_queries::reorder_stores(&app_state.db_pool, user_id, &payload.ids).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_store_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(store_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows = db_queries::delete_store(&app_state.db_pool, user_id, store_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Store not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_attribute_fields_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let fields = db_queries::list_attribute_fields(&app_state.db_pool, user_id).await?;
+    Ok(Json(fields))
+}
+
+pub async fn create_attribute_field_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<AttributeFieldPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let field = db_queries::create_attribute_field(
+        &app_state.db_pool,
+        user_id,
+        &payload.name,
+        payload.kind,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(field)))
+}
+
+/// DELETE /api/attribute-fields/{id}, which also clears its values on all items.
+pub async fn delete_attribute_field_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(field_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows =
+        db_queries::delete_attribute_field(&app_state.db_pool, user_id, field_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Field not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn search_catalog_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<CatalogQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let products =
+        db_queries::search_catalog(&app_state.db_pool, user_id, query.q.trim(), query.limit())
+            .await?;
+    Ok(Json(products))
+}
+
+pub async fn delete_catalog_product_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(product_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows =
+        db_queries::delete_catalog_product(&app_state.db_pool, user_id, product_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Product not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn get_notifications_api(
     State(app_state): State<Arc<AppState>>,
 
-    jar: CookieJar,
+    AuthUser(user_id): AuthUser,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or(AppError::BadRequest("Authentication required".into()))?;
     let notifications = get_api_notifications(&app_state.db_pool, user_id).await;
     Ok(Json(notifications))
 }
+
+pub async fn get_preferences_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let preferences = app_state
+        .cache
+        .preferences(&app_state.db_pool, user_id)
+        .await?;
+    Ok(Json(preferences))
+}
+
+pub async fn update_preferences_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<UpdatePreferencesPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let preferences =
+        db_queries::update_user_preferences(&app_state.db_pool, user_id, payload).await?;
+    app_state.cache.forget_preferences(user_id).await;
+    Ok(Json(preferences))
+}
+
+pub async fn get_backup_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    // Taken before answering, so a database that is down still gets a 500
+    let snapshot = db_queries::begin_snapshot(&app_state.db_pool).await?;
+    let exported_at = OffsetDateTime::now_utc();
+    let disposition = format!(
+        "attachment; filename=\"inventory-backup-{}.json\"",
+        exported_at.date()
+    );
+    let body = export::json_body(move |mut out| async move {
+        db_queries::write_backup(snapshot, user_id, exported_at, &mut out).await?;
+        out.finish().await
+    });
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
+
+pub async fn restore_backup_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(backup): ApiJson<Backup>,
+) -> Result<impl IntoResponse, AppError> {
+    backup::validate(&backup).map_err(AppError::BadRequest)?;
+    let (summary, receipt_keys) =
+        db_queries::restore_backup(&app_state.db_pool, user_id, backup).await?;
+    receipts::forget_files(&app_state, &receipt_keys).await;
+    app_state.cache.forget_user(user_id).await;
+    tracing::info!("Restored backup for user {}: {:?}", user_id, summary);
+    Ok(Json(summary))
+}
+
+pub async fn import_grocy_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<GrocyImportPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let export = match payload {
+        GrocyImportPayload::Api { url, api_key } => grocy::fetch_export(&url, &api_key)
+            .await
+            .map_err(|e| AppError::BadRequest(e.to_string()))?,
+        GrocyImportPayload::Export(export) => export,
+    };
+    let items = grocy::plan_import(export);
+    let summary = db_queries::import_grocy_items(&app_state.db_pool, user_id, items).await?;
+    app_state.cache.forget_categories(user_id).await;
+    tracing::info!("Imported from Grocy for user {}: {:?}", user_id, summary);
+    Ok(Json(summary))
+}
+
+pub async fn get_stats_overview_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<StatsQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let days = query.days.unwrap_or(30).clamp(1, 365);
+    let stats = db_queries::get_stats_overview(&app_state.db_pool, user_id, days).await?;
+    Ok(Json(stats))
+}
+
+/// GET /api/waste?month=2025-08
+pub async fn get_waste_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<WasteQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let report = waste::report(&app_state, user_id, query.month).await?;
+    Ok(Json(report))
+}
+
+/// PUT /api/purchases/{id}/receipt: the body is the photo or PDF.
+pub async fn put_receipt_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(event_id): Path<i32>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let receipt = receipts::attach(&app_state, user_id, event_id, &body).await?;
+    Ok(Json(receipt))
+}
+
+/// GET /api/purchases/{id}/receipt: the file, or a redirect to it in the
+/// bucket.
+pub async fn get_receipt_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(event_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let (receipt, file) = receipts::download(&app_state, user_id, event_id).await?;
+    let bytes = match file {
+        ReceiptFile::Bytes(bytes) => bytes,
+        // The link expires, so the redirect must not be kept either
+        ReceiptFile::Link(url) => {
+            return Ok((
+                StatusCode::TEMPORARY_REDIRECT,
+                [
+                    (header::LOCATION, url),
+                    (header::CACHE_CONTROL, "no-store".to_string()),
+                ],
+            )
+                .into_response());
+        }
+    };
+    let disposition = receipts::disposition(&receipt);
+    Ok((
+        [
+            (header::CONTENT_TYPE, receipt.content_type),
+            (header::CONTENT_DISPOSITION, disposition),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::CACHE_CONTROL, "private, no-cache".to_string()),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+pub async fn delete_receipt_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(event_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    receipts::remove(&app_state, user_id, event_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/history: quantity changes of all items, newest first.
+pub async fn get_history_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = query.limit();
+    let events = db_queries::get_item_events(
+        &app_state.db_pool,
+        user_id,
+        None,
+        query.cursor()?,
+        limit + 1,
+    )
+    .await?;
+    Ok(Json(Page::new(events, limit, ItemEvent::cursor)))
+}
+
+/// GET /api/activity: the history as lines of a feed, newest first,
+/// optionally of one item.
+pub async fn get_activity_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(filter): Query<ActivityQuery>,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let page = activity::page(&app_state, user_id, &filter, &query).await?;
+    Ok(Json(page))
+}
+
+/// GET /api/purchases?item_id=&limit=&after=
+pub async fn list_purchases_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(filter): Query<PurchaseQuery>,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let page = purchases::page(&app_state, user_id, filter.item_id, &query).await?;
+    Ok(Json(page))
+}
+
+/// GET /api/items/{id}/prices
+pub async fn get_item_prices_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let prices = purchases::store_prices(&app_state, user_id, item_id).await?;
+    Ok(Json(prices))
+}
+
+/// GET /api/items/{id}/history
+pub async fn get_item_history_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = query.limit();
+    let cursor = query.cursor()?;
+    db_queries::get_item_fingerprint(&app_state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let events = db_queries::get_item_events(
+        &app_state.db_pool,
+        user_id,
+        Some(item_id),
+        cursor,
+        limit + 1,
+    )
+    .await?;
+    Ok(Json(Page::new(events, limit, ItemEvent::cursor)))
+}
+
+pub async fn list_sessions_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    CurrentSession(session_id): CurrentSession,
+) -> Result<impl IntoResponse, AppError> {
+    let sessions = db_queries::list_sessions(&app_state.db_pool, user_id, session_id).await?;
+    Ok(Json(sessions))
+}
+
+pub async fn revoke_session_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(session_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows = db_queries::revoke_session(&app_state.db_pool, user_id, session_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Session not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_share_links_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let links = db_queries::list_share_links(&app_state.db_pool, user_id).await?;
+    Ok(Json(links))
+}
+
+pub async fn create_share_link_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<CreateShareLinkPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let link = sharing::create_link(&app_state, user_id, payload).await?;
+    Ok((StatusCode::CREATED, Json(link)))
+}
+
+pub async fn revoke_share_link_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(link_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows = db_queries::delete_share_link(&app_state.db_pool, user_id, link_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Share link not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /households: the households the user is a member of.
+pub async fn list_households_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    CurrentSession(session_id): CurrentSession,
+) -> Result<impl IntoResponse, AppError> {
+    let households =
+        db_queries::list_households(&app_state.db_pool, account_id, session_id).await?;
+    Ok(Json(households))
+}
+
+/// PUT /households/current
+pub async fn switch_household_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    CurrentSession(session_id): CurrentSession,
+    ApiJson(payload): ApiJson<SwitchHouseholdPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    households::switch(&app_state, account_id, session_id, payload.household_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_household_members_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path(household_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    households::check_member(&app_state, account_id, household_id).await?;
+    let members = db_queries::list_household_members(&app_state.db_pool, household_id).await?;
+    Ok(Json(members))
+}
+
+pub async fn list_household_invitations_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path(household_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    households::check_owner(&app_state, account_id, household_id).await?;
+    let invitations =
+        db_queries::list_household_invitations(&app_state.db_pool, household_id).await?;
+    Ok(Json(invitations))
+}
+
+/// POST /households/{id}/invitations: 202 whether or not the email has an
+/// account.
+pub async fn invite_household_member_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path(household_id): Path<i32>,
+    ApiJson(payload): ApiJson<InviteHouseholdMemberPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    households::check_owner(&app_state, account_id, household_id).await?;
+    let invitation = households::invite(&app_state, household_id, payload).await?;
+    Ok((StatusCode::ACCEPTED, Json(invitation)))
+}
+
+pub async fn cancel_household_invitation_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path((household_id, invitation_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse, AppError> {
+    households::check_owner(&app_state, account_id, household_id).await?;
+    let affected_rows =
+        db_queries::cancel_household_invitation(&app_state.db_pool, household_id, invitation_id)
+            .await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Invitation not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /households/invitations: those for the user, from every household.
+pub async fn list_my_invitations_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+) -> Result<impl IntoResponse, AppError> {
+    let email = households::email_of(&app_state, account_id).await?;
+    let invitations = db_queries::list_invitations_for(&app_state.db_pool, &email).await?;
+    Ok(Json(invitations))
+}
+
+pub async fn accept_household_invitation_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path(invitation_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let email = households::email_of(&app_state, account_id).await?;
+    if !db_queries::accept_household_invitation(
+        &app_state.db_pool,
+        invitation_id,
+        account_id,
+        &email,
+    )
+    .await?
+    {
+        return Err(AppError::NotFound("Invitation not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn decline_household_invitation_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path(invitation_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let email = households::email_of(&app_state, account_id).await?;
+    let affected_rows =
+        db_queries::decline_household_invitation(&app_state.db_pool, &email, invitation_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Invitation not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// PATCH /households/{id}/members/{user}
+pub async fn update_household_member_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path((household_id, member_id)): Path<(i32, i32)>,
+    ApiJson(payload): ApiJson<UpdateHouseholdMemberPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    households::check_owner(&app_state, account_id, household_id).await?;
+    let member = db_queries::update_household_member(
+        &app_state.db_pool,
+        household_id,
+        member_id,
+        payload.role,
+    )
+    .await?
+    .ok_or_else(|| AppError::NotFound("Member not found".into()))?;
+    Ok(Json(member))
+}
+
+/// DELETE /households/{id}/members/{user}: by the owner, or by the member
+/// leaving.
+pub async fn remove_household_member_api(
+    State(app_state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path((household_id, member_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse, AppError> {
+    if member_id != account_id {
+        households::check_owner(&app_state, account_id, household_id).await?;
+    }
+    let affected_rows =
+        db_queries::remove_household_member(&app_state.db_pool, household_id, member_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Member not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_notification_channels_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let channels = db_queries::list_notification_channels(&app_state.db_pool, user_id).await?;
+    Ok(Json(channels))
+}
+
+pub async fn create_notification_channel_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<CreateNotificationChannelPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let channel = notify::create_channel(&app_state, user_id, payload).await?;
+    Ok((StatusCode::CREATED, Json(channel)))
+}
+
+pub async fn delete_notification_channel_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(channel_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows =
+        db_queries::delete_notification_channel(&app_state.db_pool, user_id, channel_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Notification channel not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/notification-channels/{id}/test: `400` with the reason when the
+/// message didn't go through.
+pub async fn test_notification_channel_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(channel_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    notify::test_channel(&app_state.db_pool, user_id, channel_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_notification_settings_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let settings = db_queries::get_notification_settings(&app_state.db_pool, user_id).await?;
+    Ok(Json(settings))
+}
+
+pub async fn update_notification_settings_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<UpdateNotificationSettingsPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let settings = notify::update_settings(&app_state.db_pool, user_id, payload).await?;
+    Ok(Json(settings))
+}
+
+pub async fn list_api_tokens_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let tokens = db_queries::list_api_tokens(&app_state.db_pool, user_id).await?;
+    Ok(Json(tokens))
+}
+
+pub async fn create_api_token_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<CreateApiTokenPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = home_assistant::create_token(&app_state, user_id, payload).await?;
+    Ok((StatusCode::CREATED, Json(token)))
+}
+
+pub async fn revoke_api_token_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(token_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows = db_queries::delete_api_token(&app_state.db_pool, user_id, token_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("API token not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/ha/sensors, with an API token.
+pub async fn get_ha_sensors_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let sensors = home_assistant::sensors(&app_state.db_pool, user_id).await?;
+    Ok(Json(sensors))
+}
+
+/// POST /api/ha/consume, with an API token.
+pub async fn ha_consume_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<HaConsumePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = home_assistant::consume(&app_state.db_pool, user_id, payload).await?;
+    Ok(Json(item))
+}
+
+/// POST /api/assistant, with an API token.
+pub async fn assistant_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(intent): ApiJson<AssistantIntent>,
+) -> Result<impl IntoResponse, AppError> {
+    let reply = assistant::handle(&app_state.db_pool, user_id, intent).await?;
+    Ok(Json(reply))
+}
+
+/// Logs out everywhere but here.
+pub async fn revoke_other_sessions_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    CurrentSession(session_id): CurrentSession,
+) -> Result<impl IntoResponse, AppError> {
+    let revoked =
+        db_queries::revoke_other_sessions(&app_state.db_pool, user_id, session_id).await?;
+    Ok(Json(json!({ "revoked": revoked })))
+}
+
+pub async fn list_stocktakes_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let stocktakes = db_queries::get_stocktakes(&app_state.db_pool, user_id).await?;
+    Ok(Json(stocktakes))
+}
+
+pub async fn start_stocktake_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let stocktake = db_queries::start_stocktake(&app_state.db_pool, user_id).await?;
+    let stocktake = db_queries::get_stocktake(&app_state.db_pool, user_id, stocktake.id)
+        .await?
+        .ok_or(AppError::NotFound("Stocktake not found".into()))?;
+    Ok((StatusCode::CREATED, Json(stocktake)))
+}
+
+pub async fn get_stocktake_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(stocktake_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let stocktake = db_queries::get_stocktake(&app_state.db_pool, user_id, stocktake_id)
+        .await?
+        .ok_or(AppError::NotFound("Stocktake not found".into()))?;
+    Ok(Json(stocktake))
+}
+
+pub async fn update_stocktake_counts_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(stocktake_id): Path<i32>,
+    ApiJson(payload): ApiJson<UpdateStocktakeCountsPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let updated = db_queries::update_stocktake_counts(
+        &app_state.db_pool,
+        user_id,
+        stocktake_id,
+        &payload.counts,
+    )
+    .await?;
+    if !updated {
+        return Err(AppError::NotFound("Open stocktake not found".into()));
+    }
+    let stocktake = db_queries::get_stocktake(&app_state.db_pool, user_id, stocktake_id)
+        .await?
+        .ok_or(AppError::NotFound("Stocktake not found".into()))?;
+    Ok(Json(stocktake))
+}
+
+pub async fn complete_stocktake_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(stocktake_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let report =
+        db_queries::complete_stocktake(&app_state.db_pool, user_id, actor_id, stocktake_id)
+            .await?
+            .ok_or(AppError::NotFound("Open stocktake not found".into()))?;
+    Ok(Json(report))
+}
+
+pub async fn cancel_stocktake_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(stocktake_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows =
+        db_queries::cancel_stocktake(&app_state.db_pool, user_id, stocktake_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Open stocktake not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_recipes_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let recipes = db_queries::get_recipes(&app_state.db_pool, user_id).await?;
+    Ok(Json(recipes))
+}
+
+pub async fn create_recipe_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<CreateRecipePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let recipe = db_queries::create_recipe(&app_state.db_pool, user_id, payload).await?;
+    Ok((StatusCode::CREATED, Json(recipe)))
+}
+
+pub async fn get_recipe_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(recipe_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let recipe = db_queries::get_recipe(&app_state.db_pool, user_id, recipe_id)
+        .await?
+        .ok_or(AppError::NotFound("Recipe not found".into()))?;
+    Ok(Json(recipe))
+}
+
+pub async fn update_recipe_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(recipe_id): Path<i32>,
+    ApiJson(payload): ApiJson<CreateRecipePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let recipe = db_queries::update_recipe(&app_state.db_pool, user_id, recipe_id, payload)
+        .await?
+        .ok_or(AppError::NotFound("Recipe not found".into()))?;
+    Ok(Json(recipe))
+}
+
+pub async fn delete_recipe_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(recipe_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows = db_queries::delete_recipe(&app_state.db_pool, user_id, recipe_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Recipe not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_recipe_availability_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(recipe_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let recipe = db_queries::get_recipe(&app_state.db_pool, user_id, recipe_id)
+        .await?
+        .ok_or(AppError::NotFound("Recipe not found".into()))?;
+    Ok(Json(recipe.availability))
+}
+
+/// POST /recipes/{id}/cook
+/// Responds with 409 and the list of missing ingredients if stock is insufficient.
+pub async fn cook_recipe_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(recipe_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::cook_recipe(&app_state.db_pool, user_id, actor_id, recipe_id).await? {
+        CookOutcome::Cooked => {
+            let recipe = db_queries::get_recipe(&app_state.db_pool, user_id, recipe_id)
+                .await?
+                .ok_or(AppError::NotFound("Recipe not found".into()))?;
+            Ok(Json(recipe).into_response())
+        }
+        CookOutcome::RecipeNotFound => Err(AppError::NotFound("Recipe not found".into())),
+        CookOutcome::MissingIngredients(missing) => Ok((
+            StatusCode::CONFLICT,
+            Json(json!({ "error": "Not enough ingredients", "missing": missing })),
+        )
+            .into_response()),
+    }
+}
+
+pub async fn get_meal_plan_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<MealPlanQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let today = OffsetDateTime::now_utc().date();
+    let (monday, sunday) = recipes::week_bounds(query.week.unwrap_or(today));
+    let entries = db_queries::get_meal_plan(&app_state.db_pool, user_id, monday, sunday).await?;
+    Ok(Json(recipes::group_meals_by_day(monday, today, entries)))
+}
+
+pub async fn add_meal_plan_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(payload): ApiJson<CreateMealPlanPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let entry = db_queries::add_meal_plan_entry(&app_state.db_pool, user_id, payload)
+        .await?
+        .ok_or(AppError::NotFound("Recipe not found".into()))?;
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+pub async fn delete_meal_plan_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(entry_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows =
+        db_queries::delete_meal_plan_entry(&app_state.db_pool, user_id, entry_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Meal plan entry not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn cook_meal_plan_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(entry_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::cook_planned_meal(&app_state.db_pool, user_id, actor_id, entry_id).await? {
+        Some(CookOutcome::Cooked) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Some(CookOutcome::MissingIngredients(missing)) => Ok((
+            StatusCode::CONFLICT,
+            Json(json!({ "error": "Not enough ingredients", "missing": missing })),
+        )
+            .into_response()),
+        Some(CookOutcome::RecipeNotFound) | None => Err(AppError::NotFound(
+            "Meal plan entry not found or already cooked".into(),
+        )),
+    }
+}
+
+pub async fn get_shopping_list_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let shopping_list = db_queries::get_shopping_list(&app_state.db_pool, user_id).await?;
+    Ok(Json(shopping_list))
+}
+
+/// POST /api/shopping-list/checkout: the purchases of a shopping trip, all
+/// or nothing. The bought items drop off the list once they are restocked.
+pub async fn checkout_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    ApiJson(payload): ApiJson<CheckoutPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let items = db_queries::checkout(&app_state.db_pool, user_id, actor_id, &payload)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    Ok(Json(items))
+}
+
+pub async fn export_shopping_list_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<ShoppingListExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let entries = db_queries::get_shopping_list(&app_state.db_pool, user_id).await?;
+    let today = OffsetDateTime::now_utc().date();
+    let (content_type, extension, body) = shopping_list::export(&entries, query.format, today);
+    let disposition = format!("attachment; filename=\"lista-zakupow-{today}.{extension}\"");
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
+
+pub async fn get_sync_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<SyncQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let changes = db_queries::get_changes_since(&app_state.db_pool, user_id, query.since).await?;
+    Ok(Json(changes))
+}
+
+pub async fn post_sync_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    ApiJson(batch): ApiJson<SyncBatch>,
+) -> Result<impl IntoResponse, AppError> {
+    let results = sync::apply(&app_state.db_pool, user_id, batch).await?;
+    Ok(Json(json!({ "results": results })))
+}
+
+/// POST /api/graphql, see `graphql` for the schema.
+pub async fn graphql_api(
+    State(app_state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    ApiJson(request): ApiJson<async_graphql::Request>,
+) -> impl IntoResponse {
+    Json(graphql::execute(app_state, user_id, actor_id, request).await)
+}
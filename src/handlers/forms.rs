@@ -0,0 +1,68 @@
+//! Web form submissions that fail validation are shown again with the
+//! problems next to their inputs, instead of an error page.
+
+use crate::validation::{Validate, ValidationErrors};
+use axum::http::StatusCode;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::collections::HashMap;
+use tera::Context;
+
+/// A submission that didn't validate, with what the user typed.
+#[derive(Debug)]
+pub struct InvalidForm {
+    pub errors: ValidationErrors,
+    pub values: HashMap<String, String>,
+}
+
+impl InvalidForm {
+    /// `record` as JSON with the submitted values written over its fields, so
+    /// an edit form shows the user's input rather than the stored values.
+    /// Numbers stay numbers, for templates that test `is number`.
+    pub fn overlay<T: Serialize>(&self, record: &T) -> Value {
+        let mut value = serde_json::to_value(record).unwrap_or(Value::Null);
+        if let Value::Object(fields) = &mut value {
+            for (key, submitted) in &self.values {
+                let submitted = match submitted.parse::<i64>() {
+                    Ok(number) => Value::from(number),
+                    Err(_) => Value::from(submitted.as_str()),
+                };
+                fields.insert(key.clone(), submitted);
+            }
+        }
+        value
+    }
+}
+
+/// Parses and validates a form body. A body that doesn't parse at all, e.g.
+/// text in a number field, is reported under the `form` key.
+pub fn parse_form<T: DeserializeOwned + Validate>(body: &[u8]) -> Result<T, InvalidForm> {
+    let values: HashMap<String, String> = serde_urlencoded::from_bytes(body).unwrap_or_default();
+    let errors = match serde_urlencoded::from_bytes::<T>(body) {
+        Ok(payload) => match payload.validate() {
+            Ok(()) => return Ok(payload),
+            Err(errors) => errors,
+        },
+        Err(e) => {
+            let mut errors = ValidationErrors::new();
+            errors.add("form", e.to_string());
+            errors
+        }
+    };
+    Err(InvalidForm { errors, values })
+}
+
+/// Adds the `errors` of a failed submission to the context (empty when the
+/// form is shown the first time) and returns the status to render it with.
+pub fn insert_errors(context: &mut Context, invalid: Option<&InvalidForm>) -> StatusCode {
+    match invalid {
+        Some(invalid) => {
+            context.insert("errors", &invalid.errors);
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        None => {
+            context.insert("errors", &ValidationErrors::new());
+            StatusCode::OK
+        }
+    }
+}
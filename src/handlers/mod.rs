@@ -1,2 +1,3 @@
 pub mod api_handlers;
+pub mod forms;
 pub mod web_handlers;
@@ -1,94 +1,221 @@
 use crate::AppState;
-use crate::db::get_all_categories;
+use crate::activity;
+use crate::attributes;
+use crate::auth::{self, AuthUser, ClientInfo, CurrentSession, SignedInUser};
+use crate::calendar;
+use crate::categories;
+use crate::duplicates;
+use crate::feeds;
+use crate::filters;
+use crate::handlers::forms::{self, InvalidForm};
+use crate::home_assistant;
+use crate::households::{self, Membership};
+use crate::labels;
 use crate::models::{
-    CategoryWithItems, CreateCategoryPayload, GroupedItems, Item, PurchaseItemPayload,
+    ActivityQuery, AttributeFieldPayload, Attributes, CalendarQuery, CategoryWithItems,
+    CheckoutLine, CheckoutPayload, ConsumptionRulePayload, CreateApiTokenPayload,
+    CreateCategoryPayload, CreateMealPlanPayload, CreateNotificationChannelPayload,
+    CreateRecipePayload, CreateShareLinkPayload, DashboardData, DeleteCategoryOutcome,
+    DeleteCategoryPayload, DiscardItemPayload, ExpiringBatch, GroupedItems, IndexQuery,
+    InviteHouseholdMemberPayload, Item, ItemFilter, ItemSort, LabelLayout, LabelSheetQuery,
+    MealPlanQuery, MergeCategoryOutcome, MergeCategoryPayload, MergeItemOutcome, MergeItemPayload,
+    NewApiToken, PurchaseItemPayload, PurchaseQuery, RecipeIngredientPayload, ShareScope,
+    StocktakeCount, StorePayload, SwitchHouseholdPayload, Theme, UpdateCategoryPayload,
+    UpdateHouseholdMemberPayload, UpdateNotificationSettingsPayload, UpdatePreferencesPayload,
+    UseItemPayload, WasteQuery,
 };
+use crate::notify;
+use crate::pagination::PageQuery;
+use crate::proxy::Client;
+use crate::purchases;
+use crate::qr;
+use crate::receipts;
+use crate::recipes::{self, CookOutcome};
+use crate::sharing;
+use crate::shopping_list;
+use crate::validation::{Validate, ValidationErrors};
+use crate::waste;
 use crate::{
+    conditional,
     db::{self as db_queries},
     errors::AppError,
     models::{
-        CreateAccountPayload, CreateItemPayload, LoginPayload, Notification, UpdateItemPayload,
+        CreateAccountPayload, CreateItemPayload, LoginPayload, LoginQuery, Notification,
+        NotificationKind, UpdateItemPayload, UrgentRestock,
     },
 };
 use axum::debug_handler;
-use axum::extract::Query;
 use axum::{
-    extract::{Form, Path, State},
-    response::{Html, IntoResponse, Redirect},
+    Extension,
+    extract::{Form, Multipart, Path, Query, RawForm, State, multipart::MultipartError},
+    http::{HeaderMap, header},
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar};
 use bcrypt::{DEFAULT_COST, hash, verify};
-use serde::Deserialize;
 use sqlx::PgPool;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tera::Context;
+use time::{Duration, OffsetDateTime};
 
-pub fn get_text_color_for_bg(hex_color: &str) -> String {
-    let hex_color = hex_color.trim_start_matches('#');
-    if hex_color.len() != 6 {
-        return "#000000".to_string(); // Default to black for invalid colors
-    }
-
-    let r = u8::from_str_radix(&hex_color[0..2], 16).unwrap_or(0);
-    let g = u8::from_str_radix(&hex_color[2..4], 16).unwrap_or(0);
-    let b = u8::from_str_radix(&hex_color[4..6], 16).unwrap_or(0);
+// Helper to check and prepare notifications
+async fn get_notifications(pool: &PgPool, user_id: i32) -> Vec<Notification> {
+    let to_restock = db_queries::get_items_to_restock(pool, user_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to get items to restock: {:?}", e);
+            vec![] // Return empty on error
+        });
+    let expiring = db_queries::get_expiring_batches(pool, user_id, db_queries::EXPIRY_WARNING_DAYS)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to get expiring batches: {:?}", e);
+            vec![]
+        });
+    let urgent = get_urgent_restocks(pool, user_id).await;
+    notifications(to_restock, &urgent, expiring)
+}
 
-    // Formula for perceived brightness
-    let brightness = ((r as u32 * 299) + (g as u32 * 587) + (b as u32 * 114)) / 1000;
+/// The user's theme, which base.html picks the stylesheets by.
+async fn user_theme(state: &AppState, user_id: i32) -> Result<Theme, AppError> {
+    Ok(state
+        .cache
+        .preferences(&state.db_pool, user_id)
+        .await?
+        .theme)
+}
 
-    if brightness > 150 {
-        // Threshold can be adjusted
-        "#000000".to_string() // Black text for light backgrounds
-    } else {
-        "#FFFFFF".to_string() // White text for dark backgrounds
-    }
+async fn get_urgent_restocks(pool: &PgPool, user_id: i32) -> Vec<UrgentRestock> {
+    db_queries::get_urgent_restocks(pool, user_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to get urgent restocks: {:?}", e);
+            vec![]
+        })
 }
 
-// Helper to check and prepare notifications
-async fn get_notifications(pool: &PgPool, user_id: i32) -> Vec<Notification> {
-    match db_queries::get_items_to_restock(pool, user_id).await {
-        Ok(items_to_restock) => items_to_restock
-            .into_iter()
-            .map(|item| Notification {
+/// The page banner; urgent restocks come first.
+fn notifications(
+    to_restock: Vec<Item>,
+    urgent: &[UrgentRestock],
+    expiring: Vec<ExpiringBatch>,
+) -> Vec<Notification> {
+    let mut notifications: Vec<Notification> = to_restock
+        .into_iter()
+        .map(|item| {
+            let mut message = format!(
+                "Aktualna ilość: {}, próg uzupełnienia: {}. Kup {}!",
+                item.quantity, item.restock_threshold, item.suggested_purchase
+            );
+            let urgent = urgent.iter().find(|u| u.item_id == item.id);
+            if let Some(urgent) = urgent {
+                message.push_str(&format!(" Czeka od {} dni.", urgent.days_low));
+            }
+            Notification {
+                kind: NotificationKind::Restock,
                 item_name: item.name.clone(),
-                message: format!(
-                    "Aktualna ilość: {}, próg uzupełnienia: {}. Proszę uzupełnij!",
-                    item.quantity, item.restock_threshold
-                ),
-            })
-            .collect(),
-        Err(e) => {
-            tracing::error!("Failed to get items to restock: {:?}", e);
-            vec![] // Return empty on error
+                message,
+                urgent: urgent.is_some(),
+            }
+        })
+        .collect();
+    // Stable, so the rest stay by name
+    notifications.sort_by_key(|notification| !notification.urgent);
+
+    let today = OffsetDateTime::now_utc().date();
+    notifications.extend(expiring.into_iter().map(|batch| {
+        let status = if batch.expires_on < today {
+            "przeterminowane od"
+        } else {
+            "ważne do"
+        };
+        Notification {
+            kind: NotificationKind::Expiry,
+            item_name: batch.item_name,
+            message: format!("{} szt. {} {}", batch.quantity, status, batch.expires_on),
+            urgent: false,
         }
-    }
+    }));
+    notifications
 }
 
+/// Answers `If-Modified-Since` with `304` while nothing shown on the page
+/// changed, before loading or rendering anything.
 pub async fn root_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
-
-    let group_by_category: bool = jar
-        .get("group")
-        .and_then(|g| g.value().parse().ok())
-        .unwrap_or(true);
+    AuthUser(user_id): AuthUser,
+    CurrentSession(session_id): CurrentSession,
+    membership: Option<Extension<Membership>>,
+    Query(query): Query<IndexQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let mut preferences = state.cache.preferences(&state.db_pool, user_id).await?;
+    if let Some(sort) = query.sort.filter(|sort| *sort != preferences.sort) {
+        if membership.is_some() {
+            // The preferences are the owner's, so members only sort this page
+            preferences.sort = sort;
+        } else {
+            // A sort picked from the dropdown becomes the new default
+            let payload = UpdatePreferencesPayload {
+                sort: Some(sort),
+                ..Default::default()
+            };
+            preferences =
+                db_queries::update_user_preferences(&state.db_pool, user_id, payload).await?;
+            state.cache.forget_preferences(user_id).await;
+        }
+    }
+    // Expiry warnings count days from today, so the page changes at midnight,
+    // and a session switched to another household sees another inventory
+    let today = OffsetDateTime::now_utc().replace_time(time::Time::MIDNIGHT);
+    let switched_at = db_queries::get_household_switched_at(&state.db_pool, session_id).await?;
+    let last_change = db_queries::get_last_change(&state.db_pool, user_id)
+        .await?
+        .into_iter()
+        .chain(switched_at)
+        .fold(today, OffsetDateTime::max);
+    let last_modified = conditional::last_modified(last_change);
+    if let Some(last_modified) = last_modified
+        && conditional::is_unmodified_since(&headers, last_modified)
+    {
+        return Ok(conditional::not_modified_since(last_modified));
+    }
 
-    let user = db_queries::get_user_by_id(&state.db_pool, user_id).await?;
-    let items = db_queries::get_all_items(&state.db_pool, user_id).await?;
-    let categories = get_all_categories(&state.db_pool, user_id).await?;
-    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let group_by_category = preferences.group_by_category;
+    let DashboardData {
+        user,
+        items,
+        categories,
+        to_restock,
+        expiring,
+    } = db_queries::get_dashboard_data(
+        &state.db_pool,
+        &state.cache,
+        user_id,
+        preferences.sort,
+        query.filter,
+    )
+    .await?;
+    let urgent = get_urgent_restocks(&state.db_pool, user_id).await;
+    let urgent_item_ids: Vec<i32> = urgent.iter().map(|u| u.item_id).collect();
+    let notifications = notifications(to_restock, &urgent, expiring);
 
     let mut context = Context::new();
     context.insert("notifications", &notifications);
+    context.insert("urgent_item_ids", &urgent_item_ids);
     context.insert("user", &user);
+    context.insert("theme", &preferences.theme);
     context.insert("group_by_category", &group_by_category);
+    context.insert("sort", &preferences.sort);
+    context.insert("filter", &query.filter);
+    context.insert("layout", &query.layout.unwrap_or(preferences.layout));
+    context.insert("layout_override", &query.layout);
     context.insert("categories", &categories);
+    context.insert(
+        "collapsed_category_ids",
+        &preferences.collapsed_category_ids,
+    );
     context.insert("base_path", &state.base_path);
     context.insert("item_amount", &items.len());
 
@@ -96,15 +223,16 @@ pub async fn root_handler(
         let mut categorized_map: HashMap<i32, CategoryWithItems> = HashMap::new();
         // Use the already fetched categories
         for category in &categories {
-            let text_color = get_text_color_for_bg(&category.color);
             categorized_map.insert(
                 category.id,
                 CategoryWithItems {
                     id: category.id,
                     name: category.name.clone(),
                     color: category.color.clone(),
-                    text_color,
+                    parent_id: category.parent_id,
+                    icon: category.icon.clone(),
                     items: vec![],
+                    total_items: 0,
                 },
             );
         }
@@ -121,8 +249,13 @@ pub async fn root_handler(
             }
         }
 
-        let mut categorized_items: Vec<CategoryWithItems> = categorized_map.into_values().collect();
-        categorized_items.sort_by(|a, b| a.name.cmp(&b.name));
+        // Keep the categories' manual order
+        let categorized_items = categories::nest_for_display(
+            categories
+                .iter()
+                .filter_map(|category| categorized_map.remove(&category.id))
+                .collect(),
+        );
 
         let grouped_items = GroupedItems {
             categorized: categorized_items,
@@ -133,245 +266,1990 @@ pub async fn root_handler(
         context.insert("items", &items);
     }
 
-    let rendered = state.tera.render("index.html", &context)?;
-    Ok(Html(rendered))
+    let rendered = state.tera.render("index.html", context).await?;
+    Ok(conditional::with_last_modified(
+        last_modified,
+        Html(rendered),
+    ))
 }
 
-pub async fn show_add_item_form(
+/// POST /categories/{id}/toggle, folding or unfolding the category's group
+/// on the item list.
+pub async fn toggle_category_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let preferences = state.cache.preferences(&state.db_pool, user_id).await?;
+    let collapsed = !preferences.collapsed_category_ids.contains(&category_id);
+    let found =
+        db_queries::set_category_collapsed(&state.db_pool, user_id, category_id, collapsed).await?;
+    if !found {
+        return Err(AppError::NotFound("Category not found".into()));
+    }
+    state.cache.forget_preferences(user_id).await;
+    Ok(Redirect::to(&format!(
+        "{}/web#category-{}",
+        &state.base_path, category_id
+    )))
+}
+
+pub async fn show_settings_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    CurrentSession(session_id): CurrentSession,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
+    settings_page(&state, user_id, session_id, None).await
+}
 
-    let user = db_queries::get_user_by_id(&state.db_pool, user_id).await?;
+/// The settings page; `new_api_token` is a token just made, shown this once.
+async fn settings_page(
+    state: &AppState,
+    user_id: i32,
+    session_id: i32,
+    new_api_token: Option<NewApiToken>,
+) -> Result<Html<String>, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let preferences = state.cache.preferences(&state.db_pool, user_id).await?;
+    let sessions = db_queries::list_sessions(&state.db_pool, user_id, session_id).await?;
+    let share_links = db_queries::list_share_links(&state.db_pool, user_id).await?;
+    let channels = db_queries::list_notification_channels(&state.db_pool, user_id).await?;
+    let notification_settings =
+        db_queries::get_notification_settings(&state.db_pool, user_id).await?;
+    let api_tokens = db_queries::list_api_tokens(&state.db_pool, user_id).await?;
+    let categories = state.cache.categories(&state.db_pool, user_id).await?;
+    let stores = db_queries::list_stores(&state.db_pool, user_id).await?;
+    let attribute_fields = db_queries::list_attribute_fields(&state.db_pool, user_id).await?;
     let notifications = get_notifications(&state.db_pool, user_id).await;
-    let categories = get_all_categories(&state.db_pool, user_id).await?;
+
     let mut context = Context::new();
     context.insert("notifications", &notifications);
+    context.insert("user", &user);
+    context.insert("theme", &preferences.theme);
+    context.insert("timezone", &preferences.timezone);
+    context.insert("timezones", &filters::timezone_names());
+    context.insert("preferences", &preferences);
+    context.insert("stores", &stores);
+    context.insert("attribute_fields", &attribute_fields);
+    context.insert("sessions", &sessions);
+    context.insert("share_links", &share_links);
+    context.insert("channels", &channels);
+    context.insert("notification_settings", &notification_settings);
+    context.insert("api_tokens", &api_tokens);
+    context.insert("new_api_token", &new_api_token);
     context.insert("categories", &categories);
     context.insert("base_path", &state.base_path);
-    context.insert("user", &user);
-    let rendered = state.tera.render("add_item.html", &context)?;
+    let rendered = state.tera.render("settings.html", context).await?;
     Ok(Html(rendered))
 }
 
-pub async fn add_item_handler(
+pub async fn settings_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-    Form(payload): Form<CreateItemPayload>,
+    AuthUser(user_id): AuthUser,
+    Form(payload): Form<UpdatePreferencesPayload>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
-
-    db_queries::create_item(&state.db_pool, user_id, payload).await?;
+    payload.validate()?;
+    db_queries::update_user_preferences(&state.db_pool, user_id, payload).await?;
+    state.cache.forget_preferences(user_id).await;
     let redirect_url = format!("{}/web", &state.base_path);
     Ok(Redirect::to(&redirect_url))
 }
 
-pub async fn add_category_handler(
+/// POST /settings/sessions/{id}/revoke
+pub async fn revoke_session_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-    Form(payload): Form<CreateCategoryPayload>,
+    AuthUser(user_id): AuthUser,
+    Path(session_id): Path<i32>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
+    db_queries::revoke_session(&state.db_pool, user_id, session_id).await?;
+    Ok(Redirect::to(&format!("{}/web/settings", &state.base_path)))
+}
 
-    db_queries::create_category(&state.db_pool, user_id, payload).await?;
-    let redirect_url = format!("{}/web", &state.base_path);
-    Ok(Redirect::to(&redirect_url))
+/// POST /settings/share-links. The chosen categories come as
+/// `category_{id}` checkboxes.
+pub async fn create_share_link_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let scope = match form.get("scope").map(String::as_str) {
+        Some("categories") => ShareScope::Categories,
+        Some("shopping_list") => ShareScope::ShoppingList,
+        Some("calendar") => ShareScope::Calendar,
+        Some("restock_feed") => ShareScope::RestockFeed,
+        _ => return Err(AppError::BadRequest("Nieprawidłowy zakres".into())),
+    };
+    let category_ids = form
+        .keys()
+        .filter_map(|key| key.strip_prefix("category_")?.parse().ok())
+        .collect();
+    let payload = CreateShareLinkPayload {
+        label: form.get("label").cloned().unwrap_or_default(),
+        scope,
+        category_ids,
+    };
+    sharing::create_link(&state, user_id, payload).await?;
+    Ok(Redirect::to(&format!("{}/web/settings", &state.base_path)))
 }
 
-pub async fn show_add_category_form(
+/// POST /settings/share-links/{id}/revoke
+pub async fn revoke_share_link_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
+    AuthUser(user_id): AuthUser,
+    Path(link_id): Path<i32>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
+    db_queries::delete_share_link(&state.db_pool, user_id, link_id).await?;
+    Ok(Redirect::to(&format!("{}/web/settings", &state.base_path)))
+}
 
-    let user = db_queries::get_user_by_id(&state.db_pool, user_id).await?;
+/// GET /households: the households the user is in, and the members of their
+/// own.
+pub async fn households_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(account_id): SignedInUser,
+    CurrentSession(session_id): CurrentSession,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, account_id).await?;
+    let households = db_queries::list_households(&state.db_pool, account_id, session_id).await?;
+    let current = households.iter().find(|household| household.current);
+    let members = db_queries::list_household_members(&state.db_pool, account_id).await?;
+    let invitations = db_queries::list_household_invitations(&state.db_pool, account_id).await?;
+    let email = households::email_of(&state, account_id).await?;
+    let received_invitations = db_queries::list_invitations_for(&state.db_pool, &email).await?;
     let notifications = get_notifications(&state.db_pool, user_id).await;
+
     let mut context = Context::new();
     context.insert("notifications", &notifications);
-    context.insert("base_path", &state.base_path);
     context.insert("user", &user);
-    let rendered = state.tera.render("add_category.html", &context)?;
+    context.insert("theme", &user_theme(&state, account_id).await?);
+    context.insert("households", &households);
+    context.insert("current", &current);
+    context.insert("members", &members);
+    context.insert("invitations", &invitations);
+    context.insert("received_invitations", &received_invitations);
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("households.html", context).await?;
     Ok(Html(rendered))
 }
 
-/// GET /signup
-pub async fn show_signup_form(
+/// POST /households/current
+pub async fn switch_household_handler(
     State(state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    CurrentSession(session_id): CurrentSession,
+    Form(payload): Form<SwitchHouseholdPayload>,
 ) -> Result<impl IntoResponse, AppError> {
-    let mut context = Context::new();
-    context.insert("base_path", &state.base_path);
-    let rendered = state.tera.render("signup.html", &context)?;
-    Ok(Html(rendered))
+    households::switch(&state, account_id, session_id, payload.household_id).await?;
+    Ok(Redirect::to(&format!("{}/web", &state.base_path)))
 }
 
-/// POST /signup
-pub async fn signup_handler(
+/// POST /households/{id}/leave
+pub async fn leave_household_handler(
     State(state): State<Arc<AppState>>,
-    Form(payload): Form<CreateAccountPayload>,
+    SignedInUser(account_id): SignedInUser,
+    Path(household_id): Path<i32>,
 ) -> Result<impl IntoResponse, AppError> {
-    let hashed_password_string = hash(&payload.password, DEFAULT_COST)
-        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
-    db_queries::create_account(
-        &state.db_pool,
-        payload.name.as_str(),
-        payload.email.as_str(),
-        &hashed_password_string,
-    )
-    .await?;
-    let redirect_url = format!("{}/web/login", &state.base_path);
-    Ok(Redirect::to(&redirect_url))
+    db_queries::remove_household_member(&state.db_pool, household_id, account_id).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/households",
+        &state.base_path
+    )))
 }
 
-/// GET /login
-pub async fn show_login_form(
+/// POST /households/invitations
+pub async fn invite_household_member_handler(
     State(state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Form(payload): Form<InviteHouseholdMemberPayload>,
 ) -> Result<impl IntoResponse, AppError> {
-    let mut context = Context::new();
-    context.insert("base_path", &state.base_path);
-    let rendered = state.tera.render("login.html", &context)?;
-    Ok(Html(rendered))
+    households::invite(&state, account_id, payload).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/households#members",
+        &state.base_path
+    )))
 }
 
-/// POST /login
-#[debug_handler]
-pub async fn login_handler(
+/// POST /households/invitations/{id}/cancel
+pub async fn cancel_household_invitation_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-    Form(payload): Form<LoginPayload>,
+    SignedInUser(account_id): SignedInUser,
+    Path(invitation_id): Path<i32>,
 ) -> Result<impl IntoResponse, AppError> {
-    let acct = db_queries::get_account_by_email(&state.db_pool, &payload.email)
+    db_queries::cancel_household_invitation(&state.db_pool, account_id, invitation_id).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/households#members",
+        &state.base_path
+    )))
+}
+
+/// POST /households/invitations/{id}/accept
+pub async fn accept_household_invitation_handler(
+    State(state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path(invitation_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let email = households::email_of(&state, account_id).await?;
+    db_queries::accept_household_invitation(&state.db_pool, invitation_id, account_id, &email)
+        .await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/households",
+        &state.base_path
+    )))
+}
+
+/// POST /households/invitations/{id}/decline
+pub async fn decline_household_invitation_handler(
+    State(state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path(invitation_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let email = households::email_of(&state, account_id).await?;
+    db_queries::decline_household_invitation(&state.db_pool, &email, invitation_id).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/households",
+        &state.base_path
+    )))
+}
+
+/// POST /households/members/{user}/role
+pub async fn update_household_member_handler(
+    State(state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path(member_id): Path<i32>,
+    Form(payload): Form<UpdateHouseholdMemberPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::update_household_member(&state.db_pool, account_id, member_id, payload.role)
+        .await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/households#members",
+        &state.base_path
+    )))
+}
+
+/// POST /households/members/{user}/delete
+pub async fn remove_household_member_handler(
+    State(state): State<Arc<AppState>>,
+    SignedInUser(account_id): SignedInUser,
+    Path(member_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::remove_household_member(&state.db_pool, account_id, member_id).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/households#members",
+        &state.base_path
+    )))
+}
+
+/// POST /settings/stores
+pub async fn create_store_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Form(payload): Form<StorePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    db_queries::create_store(&state.db_pool, user_id, &payload.name).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/settings#stores",
+        &state.base_path
+    )))
+}
+
+/// POST /settings/stores/{id}/rename
+pub async fn rename_store_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(store_id): Path<i32>,
+    Form(payload): Form<StorePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    db_queries::rename_store(&state.db_pool, user_id, store_id, &payload.name).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/settings#stores",
+        &state.base_path
+    )))
+}
+
+/// POST /settings/stores/{id}/up: visits the store one earlier.
+pub async fn move_store_up_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(store_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut ids: Vec<i32> = db_queries::list_stores(&state.db_pool, user_id)
         .await?
-        .ok_or(AppError::BadRequest("Nieprawidłowe dane logowania".into()))?;
-    if verify(&payload.password, &acct.password)
-        .map_err(|e| AppError::InternalServerError(e.to_string()))?
+        .into_iter()
+        .map(|store| store.id)
+        .collect();
+    if let Some(index) = ids.iter().position(|&id| id == store_id)
+        && index > 0
     {
-        let session_cookie = Cookie::build(("session", acct.id.to_string()))
-            .path("/")
-            .http_only(true);
-        // .secure(true) // Uncomment if served over HTTPS
-        let jar = jar.add(session_cookie);
-        let redirect_url = format!("{}/web", &state.base_path);
-        Ok((jar, Redirect::to(&redirect_url)))
-    } else {
-        Err(AppError::BadRequest("Nieprawidłowe dane logowania".into()))
+        ids.swap(index - 1, index);
+        db_queries::reorder_stores(&state.db_pool, user_id, &ids).await?;
     }
+    Ok(Redirect::to(&format!(
+        "{}/web/settings#stores",
+        &state.base_path
+    )))
 }
 
-/// GET /logout
-pub async fn logout_handler(
+/// POST /settings/stores/{id}/delete
+pub async fn delete_store_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-) -> Result<(CookieJar, Redirect), AppError> {
-    // Remove the cookie by setting its path and making it expire.
-    // axum-extra's `remove` method sets Max-Age=0 and clears the value.
-    // Ensure the path matches the one used during cookie creation.
-    let jar = jar.remove(Cookie::build("session").path("/").build());
-    let redirect_url = format!("{}/web/login", &state.base_path);
-    Ok((jar, Redirect::to(&redirect_url)))
+    AuthUser(user_id): AuthUser,
+    Path(store_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::delete_store(&state.db_pool, user_id, store_id).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/settings#stores",
+        &state.base_path
+    )))
 }
 
-pub async fn show_edit_item_form(
+/// POST /settings/attribute-fields
+pub async fn create_attribute_field_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-    Path(item_id): Path<i32>,
+    AuthUser(user_id): AuthUser,
+    Form(payload): Form<AttributeFieldPayload>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
+    payload.validate()?;
+    db_queries::create_attribute_field(&state.db_pool, user_id, &payload.name, payload.kind)
+        .await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/settings#attribute-fields",
+        &state.base_path
+    )))
+}
 
-    let user = db_queries::get_user_by_id(&state.db_pool, user_id).await?;
-    let item = db_queries::get_item_by_id(&state.db_pool, user_id, item_id)
+/// POST /settings/attribute-fields/{id}/delete
+pub async fn delete_attribute_field_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(field_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::delete_attribute_field(&state.db_pool, user_id, field_id).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/settings#attribute-fields",
+        &state.base_path
+    )))
+}
+
+/// POST /settings/api-tokens. Answers with the settings page rather than a
+/// redirect, as it is the only time the new token can be shown.
+pub async fn create_api_token_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    CurrentSession(session_id): CurrentSession,
+    Form(payload): Form<CreateApiTokenPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let new_api_token = home_assistant::create_token(&state, user_id, payload).await?;
+    settings_page(&state, user_id, session_id, Some(new_api_token)).await
+}
+
+/// POST /settings/api-tokens/{id}/revoke
+pub async fn revoke_api_token_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(token_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::delete_api_token(&state.db_pool, user_id, token_id).await?;
+    Ok(Redirect::to(&format!("{}/web/settings", &state.base_path)))
+}
+
+/// POST /settings/notification-channels
+pub async fn create_notification_channel_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Form(payload): Form<CreateNotificationChannelPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    notify::create_channel(&state, user_id, payload).await?;
+    Ok(Redirect::to(&format!("{}/web/settings", &state.base_path)))
+}
+
+/// POST /settings/notification-settings
+pub async fn update_notification_settings_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Form(payload): Form<UpdateNotificationSettingsPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    notify::update_settings(&state.db_pool, user_id, payload).await?;
+    Ok(Redirect::to(&format!(
+        "{}/web/settings#notification-settings",
+        &state.base_path
+    )))
+}
+
+/// POST /settings/notification-channels/{id}/delete
+pub async fn delete_notification_channel_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(channel_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::delete_notification_channel(&state.db_pool, user_id, channel_id).await?;
+    Ok(Redirect::to(&format!("{}/web/settings", &state.base_path)))
+}
+
+/// POST /settings/notification-channels/{id}/test. A failure shows up next
+/// to the channel on the settings page.
+pub async fn test_notification_channel_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(channel_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    match notify::test_channel(&state.db_pool, user_id, channel_id).await {
+        Ok(()) | Err(AppError::BadRequest(_)) => {}
+        Err(e) => return Err(e),
+    }
+    Ok(Redirect::to(&format!("{}/web/settings", &state.base_path)))
+}
+
+/// GET /share/{token}, the read-only page of a share link
+pub async fn share_handler(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let link = db_queries::view_share_link(&state.db_pool, &token)
         .await?
-        .ok_or(AppError::ItemNotFound)?;
+        .ok_or(AppError::NotFound("Link wygasł lub nie istnieje".into()))?;
+    let mut context = Context::new();
+    context.insert("link", &link);
+    context.insert("base_path", &state.base_path);
+    match link.scope {
+        ShareScope::Categories => {
+            let grouped =
+                db_queries::get_items_grouped_by_category(&state.db_pool, link.user_id).await?;
+            context.insert(
+                "categories",
+                &sharing::shared_categories(grouped, &link.category_ids),
+            );
+        }
+        ShareScope::ShoppingList => {
+            let shopping_list = db_queries::get_shopping_list(&state.db_pool, link.user_id).await?;
+            context.insert("sections", &shopping_list::group_by_store(&shopping_list));
+        }
+        // The page only says how to subscribe
+        ShareScope::Calendar | ShareScope::RestockFeed => {}
+    }
+    let rendered = state.tera.render("share.html", context).await?;
+    // Keeps the token out of search engines and out of the Referer of links
+    Ok((
+        [
+            ("x-robots-tag", "noindex"),
+            ("referrer-policy", "no-referrer"),
+        ],
+        Html(rendered),
+    ))
+}
+
+/// GET /calendar/{token}.ics, the feed of a calendar share link
+pub async fn calendar_handler(
+    State(state): State<Arc<AppState>>,
+    Path(file): Path<String>,
+    Query(query): Query<CalendarQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let not_found = || AppError::NotFound("Link wygasł lub nie istnieje".into());
+    let token = file.strip_suffix(".ics").ok_or_else(not_found)?;
+    let link = db_queries::view_share_link(&state.db_pool, token)
+        .await?
+        .filter(|link| link.scope == ShareScope::Calendar)
+        .ok_or_else(not_found)?;
+    let feed = calendar::feed(&state.db_pool, link.user_id, query.run_out).await?;
+    Ok((
+        [
+            ("content-type", "text/calendar; charset=utf-8"),
+            ("cache-control", "no-cache"),
+            ("x-robots-tag", "noindex"),
+        ],
+        feed,
+    ))
+}
+
+/// GET /feeds/{token}/restock.atom, the feed of a restock feed share link
+pub async fn restock_feed_handler(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let link = db_queries::view_share_link(&state.db_pool, &token)
+        .await?
+        .filter(|link| link.scope == ShareScope::RestockFeed)
+        .ok_or(AppError::NotFound("Link wygasł lub nie istnieje".into()))?;
+    let items = db_queries::get_items_to_restock(&state.db_pool, link.user_id).await?;
+    let mut context = feeds::restock_context(&items, &token, OffsetDateTime::now_utc());
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("restock_feed.xml", context).await?;
+    Ok((
+        [
+            ("content-type", "application/atom+xml; charset=utf-8"),
+            ("cache-control", "no-cache"),
+            ("x-robots-tag", "noindex"),
+        ],
+        rendered,
+    ))
+}
+
+/// POST /settings/sessions/revoke-others
+pub async fn revoke_other_sessions_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    CurrentSession(session_id): CurrentSession,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::revoke_other_sessions(&state.db_pool, user_id, session_id).await?;
+    Ok(Redirect::to(&format!("{}/web/settings", &state.base_path)))
+}
+
+pub async fn dashboard_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let stats = db_queries::get_stats_overview(&state.db_pool, user_id, 30).await?;
     let notifications = get_notifications(&state.db_pool, user_id).await;
-    let categories = get_all_categories(&state.db_pool, user_id).await?;
+
     let mut context = Context::new();
-    context.insert("item", &item);
     context.insert("notifications", &notifications);
-    context.insert("categories", &categories);
-    context.insert("selected_category", &item.category.map(|c| c.id));
-    context.insert("base_path", &state.base_path);
     context.insert("user", &user);
-    let rendered = state.tera.render("edit_item.html", &context)?;
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    context.insert("stats", &stats);
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("dashboard.html", context).await?;
     Ok(Html(rendered))
 }
 
-pub async fn edit_item_handler(
+/// GET /waste, what was thrown away in a month
+pub async fn waste_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-    Path(item_id): Path<i32>,
-    Form(payload): Form<UpdateItemPayload>,
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<WasteQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    tracing::info!("UpdateItemPayload: {:?}", payload);
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let report = waste::report(&state, user_id, query.month).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
 
-    db_queries::update_item(&state.db_pool, user_id, item_id, payload).await?;
-    let redirect_url = format!("{}/web", &state.base_path);
-    Ok(Redirect::to(&redirect_url))
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    context.insert("report", &report);
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("waste.html", context).await?;
+    Ok(Html(rendered))
 }
 
-pub async fn purchase_item_handler(
+/// GET /activity, the feed of the history, optionally of one item or of
+/// one member of the household
+pub async fn activity_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-    Path(item_id): Path<i32>,
-    Form(payload): Form<PurchaseItemPayload>,
+    AuthUser(user_id): AuthUser,
+    Query(filter): Query<ActivityQuery>,
+    Query(query): Query<PageQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let preferences = state.cache.preferences(&state.db_pool, user_id).await?;
+    let page = activity::page(&state, user_id, &filter, &query).await?;
+    let items =
+        db_queries::get_all_items(&state.db_pool, user_id, ItemSort::Name, ItemFilter::All).await?;
+    let members = db_queries::list_household_members(&state.db_pool, user_id).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
 
-    db_queries::purchase_item(&state.db_pool, user_id, item_id, payload).await?;
-    let redirect_url = format!("{}/web", &state.base_path);
-    Ok(Redirect::to(&redirect_url))
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("user", &user);
+    context.insert("theme", &preferences.theme);
+    context.insert("timezone", &preferences.timezone);
+    context.insert("entries", &page.items);
+    context.insert("next_cursor", &page.next_cursor);
+    context.insert("items", &items);
+    context.insert("item_id", &filter.item_id);
+    context.insert("members", &members);
+    context.insert("user_id", &filter.user_id);
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("activity.html", context).await?;
+    Ok(Html(rendered))
 }
 
-pub async fn use_item_handler(
+/// GET /purchases, what was bought for how much, optionally of one item
+/// with its prices in each store
+pub async fn purchases_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-    Path(item_id): Path<i32>,
+    AuthUser(user_id): AuthUser,
+    Query(filter): Query<PurchaseQuery>,
+    Query(query): Query<PageQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let preferences = state.cache.preferences(&state.db_pool, user_id).await?;
+    let page = purchases::page(&state, user_id, filter.item_id, &query).await?;
+    let store_prices = match filter.item_id {
+        Some(item_id) => purchases::store_prices(&state, user_id, item_id).await?,
+        None => Vec::new(),
+    };
+    let items =
+        db_queries::get_all_items(&state.db_pool, user_id, ItemSort::Name, ItemFilter::All).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
 
-    db_queries::use_item(&state.db_pool, user_id, item_id).await?;
-    let redirect_url = format!("{}/web", &state.base_path);
-    Ok(Redirect::to(&redirect_url))
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("user", &user);
+    context.insert("theme", &preferences.theme);
+    context.insert("timezone", &preferences.timezone);
+    context.insert("purchases", &page.items);
+    context.insert("next_cursor", &page.next_cursor);
+    context.insert("store_prices", &store_prices);
+    context.insert("items", &items);
+    context.insert("item_id", &filter.item_id);
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("purchases.html", context).await?;
+    Ok(Html(rendered))
 }
 
-pub async fn delete_item_handler(
+/// Where a receipt form sends the user back to: the history of the item
+/// that was bought.
+async fn purchase_history_url(
+    state: &AppState,
+    user_id: i32,
+    event_id: i32,
+) -> Result<String, AppError> {
+    let event = db_queries::get_purchase_event(&state.db_pool, user_id, event_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Purchase not found".into()))?;
+    Ok(match event.item_id {
+        Some(item_id) => format!("{}/web/activity?item_id={item_id}", state.base_path),
+        None => format!("{}/web/activity", state.base_path),
+    })
+}
+
+pub async fn upload_receipt_handler(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-    Path(item_id): Path<i32>,
+    AuthUser(user_id): AuthUser,
+    Path(event_id): Path<i32>,
+    mut multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id: i32 = jar
-        .get("session")
-        .and_then(|c| c.value().parse().ok())
-        .ok_or_else(|| AppError::BadRequest("Authentication required".into()))?;
-
-    let affected_rows = db_queries::delete_item(&state.db_pool, user_id, item_id).await?;
-    if affected_rows == 0 {
-        return Err(AppError::ItemNotFound);
+    let redirect_url = purchase_history_url(&state, user_id, event_id).await?;
+    let invalid = |e: MultipartError| AppError::BadRequest(e.body_text());
+    let mut file = None;
+    while let Some(field) = multipart.next_field().await.map_err(invalid)? {
+        if field.name() == Some("receipt") {
+            file = Some(field.bytes().await.map_err(invalid)?);
+        }
     }
-    let redirect_url = format!("{}/web", &state.base_path);
+    let file = file
+        .filter(|file| !file.is_empty())
+        .ok_or_else(|| AppError::BadRequest("Wybierz plik z paragonem".into()))?;
+    receipts::attach(&state, user_id, event_id, &file).await?;
     Ok(Redirect::to(&redirect_url))
 }
+
+pub async fn delete_receipt_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(event_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let redirect_url = purchase_history_url(&state, user_id, event_id).await?;
+    receipts::remove(&state, user_id, event_id).await?;
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn show_add_item_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<Response, AppError> {
+    render_add_item_form(&state, user_id, None).await
+}
+
+async fn render_add_item_form(
+    state: &AppState,
+    user_id: i32,
+    invalid: Option<InvalidForm>,
+) -> Result<Response, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let categories = state.cache.categories(&state.db_pool, user_id).await?;
+    let stores = db_queries::list_stores(&state.db_pool, user_id).await?;
+    let fields = db_queries::list_attribute_fields(&state.db_pool, user_id).await?;
+    let preferences = state.cache.preferences(&state.db_pool, user_id).await?;
+    let defaults = serde_json::json!({
+        "quantity": 1,
+        "restock_threshold": 1,
+        "location": preferences.default_location,
+    });
+    let mut context = Context::new();
+    let status = forms::insert_errors(&mut context, invalid.as_ref());
+    match &invalid {
+        Some(invalid) => context.insert("form", &invalid.overlay(&defaults)),
+        None => context.insert("form", &defaults),
+    }
+    context.insert("notifications", &notifications);
+    context.insert("categories", &categories);
+    context.insert("stores", &stores);
+    let submitted = invalid.as_ref().map(|invalid| &invalid.values);
+    let attribute_inputs = attributes::form_inputs(&fields, &Attributes::new(), submitted);
+    context.insert("attribute_inputs", &attribute_inputs);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(state, user_id).await?);
+    let rendered = state.tera.render("add_item.html", context).await?;
+    Ok((status, Html(rendered)).into_response())
+}
+
+pub async fn add_item_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    RawForm(body): RawForm,
+) -> Result<Response, AppError> {
+    let mut payload = match forms::parse_form::<CreateItemPayload>(&body) {
+        Ok(payload) => payload,
+        Err(invalid) => return render_add_item_form(&state, user_id, Some(invalid)).await,
+    };
+    payload.attributes = match attributes_from_form(&state, user_id, &body).await? {
+        Ok(attributes) => Some(attributes),
+        Err(invalid) => return render_add_item_form(&state, user_id, Some(invalid)).await,
+    };
+    db_queries::create_item(&state.db_pool, user_id, payload).await?;
+    let redirect_url = format!("{}/web", &state.base_path);
+    Ok(Redirect::to(&redirect_url).into_response())
+}
+
+/// The custom field inputs of a submitted item form, or the form to show
+/// again when one of them doesn't fit its field.
+async fn attributes_from_form(
+    state: &AppState,
+    user_id: i32,
+    body: &[u8],
+) -> Result<Result<Attributes, InvalidForm>, AppError> {
+    let fields = db_queries::list_attribute_fields(&state.db_pool, user_id).await?;
+    let values: HashMap<String, String> = serde_urlencoded::from_bytes(body).unwrap_or_default();
+    Ok(attributes::from_form(&fields, &values).map_err(|errors| InvalidForm { errors, values }))
+}
+
+pub async fn add_category_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    RawForm(body): RawForm,
+) -> Result<Response, AppError> {
+    let payload = match forms::parse_form::<CreateCategoryPayload>(&body) {
+        Ok(payload) => payload,
+        Err(invalid) => return render_add_category_form(&state, user_id, Some(invalid)).await,
+    };
+    let categories = state.cache.categories(&state.db_pool, user_id).await?;
+    categories::validate_parent(&categories, None, payload.parent_id)
+        .map_err(|e| AppError::BadRequest(e.into()))?;
+
+    db_queries::create_category(&state.db_pool, user_id, payload).await?;
+    state.cache.forget_categories(user_id).await;
+    let redirect_url = format!("{}/web", &state.base_path);
+    Ok(Redirect::to(&redirect_url).into_response())
+}
+
+pub async fn show_add_category_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<Response, AppError> {
+    render_add_category_form(&state, user_id, None).await
+}
+
+async fn render_add_category_form(
+    state: &AppState,
+    user_id: i32,
+    invalid: Option<InvalidForm>,
+) -> Result<Response, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let parent_candidates: Vec<_> = state
+        .cache
+        .categories(&state.db_pool, user_id)
+        .await?
+        .into_iter()
+        .filter(|c| c.parent_id.is_none())
+        .collect();
+    let mut context = Context::new();
+    let status = forms::insert_errors(&mut context, invalid.as_ref());
+    let form = invalid.map(|invalid| invalid.values).unwrap_or_default();
+    context.insert("form", &form);
+    context.insert("notifications", &notifications);
+    context.insert("parent_candidates", &parent_candidates);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(state, user_id).await?);
+    let rendered = state.tera.render("add_category.html", context).await?;
+    Ok((status, Html(rendered)).into_response())
+}
+
+/// GET /signup
+pub async fn categories_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let categories = db_queries::get_categories_with_counts(&state.db_pool, user_id).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let mut context = Context::new();
+    context.insert("categories", &categories);
+    context.insert("notifications", &notifications);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("categories.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn show_edit_category_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+) -> Result<Response, AppError> {
+    render_edit_category_form(&state, user_id, category_id, None).await
+}
+
+async fn render_edit_category_form(
+    state: &AppState,
+    user_id: i32,
+    category_id: i32,
+    invalid: Option<InvalidForm>,
+) -> Result<Response, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let categories = state.cache.categories(&state.db_pool, user_id).await?;
+    let category = categories
+        .iter()
+        .find(|c| c.id == category_id)
+        .cloned()
+        .ok_or(AppError::NotFound("Category not found".into()))?;
+    let has_children = categories.iter().any(|c| c.parent_id == Some(category_id));
+    let parent_candidates: Vec<_> = categories
+        .into_iter()
+        .filter(|c| c.parent_id.is_none() && c.id != category_id)
+        .collect();
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let mut context = Context::new();
+    let status = forms::insert_errors(&mut context, invalid.as_ref());
+    match &invalid {
+        Some(invalid) => context.insert("category", &invalid.overlay(&category)),
+        None => context.insert("category", &category),
+    }
+    context.insert("has_children", &has_children);
+    context.insert("parent_candidates", &parent_candidates);
+    context.insert("notifications", &notifications);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(state, user_id).await?);
+    let rendered = state.tera.render("edit_category.html", context).await?;
+    Ok((status, Html(rendered)).into_response())
+}
+
+pub async fn edit_category_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+    RawForm(body): RawForm,
+) -> Result<Response, AppError> {
+    let payload = match forms::parse_form::<UpdateCategoryPayload>(&body) {
+        Ok(payload) => payload,
+        Err(invalid) => {
+            return render_edit_category_form(&state, user_id, category_id, Some(invalid)).await;
+        }
+    };
+    let categories = state.cache.categories(&state.db_pool, user_id).await?;
+    categories::validate_parent(&categories, Some(category_id), payload.parent_id)
+        .map_err(|e| AppError::BadRequest(e.into()))?;
+    db_queries::update_category(
+        &state.db_pool,
+        user_id,
+        category_id,
+        payload.name,
+        payload.color,
+        payload.parent_id,
+        payload.icon,
+    )
+    .await?
+    .ok_or(AppError::NotFound("Category not found".into()))?;
+    state.cache.forget_categories(user_id).await;
+    let redirect_url = format!("{}/web/categories", &state.base_path);
+    Ok(Redirect::to(&redirect_url).into_response())
+}
+
+pub async fn show_delete_category_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let categories = db_queries::get_categories_with_counts(&state.db_pool, user_id).await?;
+    let (category, other_categories): (Vec<_>, Vec<_>) =
+        categories.into_iter().partition(|c| c.id == category_id);
+    let category = category
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound("Category not found".into()))?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let mut context = Context::new();
+    context.insert("category", &category);
+    context.insert("other_categories", &other_categories);
+    context.insert("notifications", &notifications);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("delete_category.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn delete_category_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+    Form(payload): Form<DeleteCategoryPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::delete_category_with_policy(
+        &state.db_pool,
+        user_id,
+        category_id,
+        payload.into(),
+    )
+    .await?
+    {
+        DeleteCategoryOutcome::Deleted { moved_items } => {
+            tracing::info!(
+                "Deleted category {} for user {}, {} item(s) moved",
+                category_id,
+                user_id,
+                moved_items
+            );
+            state.cache.forget_categories(user_id).await;
+        }
+        DeleteCategoryOutcome::NotFound => {
+            return Err(AppError::NotFound("Category not found".into()));
+        }
+        DeleteCategoryOutcome::InvalidTarget => {
+            return Err(AppError::BadRequest("Invalid target category".into()));
+        }
+    }
+    let redirect_url = format!("{}/web/categories", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn show_merge_category_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let categories = db_queries::get_categories_with_counts(&state.db_pool, user_id).await?;
+    let (category, other_categories): (Vec<_>, Vec<_>) =
+        categories.into_iter().partition(|c| c.id == category_id);
+    let category = category
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound("Category not found".into()))?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let mut context = Context::new();
+    context.insert("category", &category);
+    context.insert("other_categories", &other_categories);
+    context.insert("notifications", &notifications);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("merge_category.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn merge_category_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(category_id): Path<i32>,
+    Form(payload): Form<MergeCategoryPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::merge_category(&state.db_pool, user_id, category_id, payload.into).await? {
+        MergeCategoryOutcome::Merged { moved_items } => {
+            tracing::info!(
+                "Merged category {} into {} for user {}, {} item(s) moved",
+                category_id,
+                payload.into,
+                user_id,
+                moved_items
+            );
+            state.cache.forget_categories(user_id).await;
+        }
+        MergeCategoryOutcome::NotFound => {
+            return Err(AppError::NotFound("Category not found".into()));
+        }
+        MergeCategoryOutcome::InvalidTarget => {
+            return Err(AppError::BadRequest("Invalid target category".into()));
+        }
+    }
+    let redirect_url = format!("{}/web/categories", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn show_signup_form(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    render_signup_form(&state, None).await
+}
+
+async fn render_signup_form(
+    state: &AppState,
+    invalid: Option<InvalidForm>,
+) -> Result<Response, AppError> {
+    let mut context = Context::new();
+    let status = forms::insert_errors(&mut context, invalid.as_ref());
+    let mut form = invalid.map(|invalid| invalid.values).unwrap_or_default();
+    // Never send the password back
+    form.remove("password");
+    context.insert("form", &form);
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("signup.html", context).await?;
+    Ok((status, Html(rendered)).into_response())
+}
+
+/// POST /signup
+pub async fn signup_handler(
+    State(state): State<Arc<AppState>>,
+    RawForm(body): RawForm,
+) -> Result<Response, AppError> {
+    let payload = match forms::parse_form::<CreateAccountPayload>(&body) {
+        Ok(payload) => payload,
+        Err(invalid) => return render_signup_form(&state, Some(invalid)).await,
+    };
+    let hashed_password_string = hash(&payload.password, DEFAULT_COST)
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    let account = db_queries::create_account(
+        &state.db_pool,
+        payload.name.as_str(),
+        payload.email.as_str(),
+        &hashed_password_string,
+    )
+    .await?;
+    if let Err(e) =
+        db_queries::seed_categories(&state.db_pool, account.id, &state.category_seed).await
+    {
+        // The account exists already; a missing starter set shouldn't fail signup.
+        tracing::error!("failed to seed categories for user {}: {}", account.id, e);
+    }
+    let redirect_url = format!("{}/web/login", &state.base_path);
+    Ok(Redirect::to(&redirect_url).into_response())
+}
+
+/// GET /login
+pub async fn show_login_form(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LoginQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut context = Context::new();
+    context.insert("base_path", &state.base_path);
+    context.insert("next", &query.next);
+    if state.demo_mode {
+        context.insert("demo_email", crate::demo::DEMO_EMAIL);
+        context.insert("demo_password", crate::demo::DEMO_PASSWORD);
+    }
+    let rendered = state.tera.render("login.html", context).await?;
+    Ok(Html(rendered))
+}
+
+/// POST /login
+#[debug_handler]
+pub async fn login_handler(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    client: ClientInfo,
+    connection: Client,
+    Form(payload): Form<LoginPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let acct = db_queries::get_account_by_email(&state.db_pool, &payload.email)
+        .await?
+        .ok_or(AppError::BadRequest("Nieprawidłowe dane logowania".into()))?;
+    if verify(&payload.password, &acct.password)
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?
+    {
+        let token = auth::start_session(
+            &state.db_pool,
+            acct.id,
+            payload.remember,
+            &client,
+            &state.sessions,
+        )
+        .await?;
+        let jar = jar.add(auth::session_cookie(
+            token,
+            payload.remember,
+            &state.sessions,
+            connection,
+        ));
+        let redirect_url = auth::redirect_after_login(&state.base_path, payload.next.as_deref());
+        Ok((jar, Redirect::to(&redirect_url)))
+    } else {
+        Err(AppError::BadRequest("Nieprawidłowe dane logowania".into()))
+    }
+}
+
+/// GET /logout
+pub async fn logout_handler(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Result<(CookieJar, Redirect), AppError> {
+    if let Some(cookie) = jar.get(auth::SESSION_COOKIE) {
+        db_queries::delete_session(&state.db_pool, cookie.value()).await?;
+    }
+    // Remove the cookie by setting its path and making it expire.
+    // axum-extra's `remove` method sets Max-Age=0 and clears the value.
+    // Ensure the path matches the one used during cookie creation.
+    let jar = jar.remove(Cookie::build(auth::SESSION_COOKIE).path("/").build());
+    let redirect_url = format!("{}/web/login", &state.base_path);
+    Ok((jar, Redirect::to(&redirect_url)))
+}
+
+pub async fn show_item(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let preferences = state.cache.preferences(&state.db_pool, user_id).await?;
+    let item = db_queries::get_item_by_id(&state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let batches = db_queries::get_item_batches(&state.db_pool, user_id, item_id).await?;
+    let consumption_rule =
+        db_queries::get_consumption_rule(&state.db_pool, user_id, item_id).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let today = OffsetDateTime::now_utc().date();
+    let mut context = Context::new();
+    context.insert("item", &item);
+    context.insert("batches", &batches);
+    context.insert("consumption_rule", &consumption_rule);
+    context.insert("today", &today.to_string());
+    context.insert(
+        "expiry_warning_date",
+        &(today + Duration::days(db_queries::EXPIRY_WARNING_DAYS.into())).to_string(),
+    );
+    context.insert("notifications", &notifications);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &preferences.theme);
+    context.insert("timezone", &preferences.timezone);
+    let rendered = state.tera.render("item.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn add_batch_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(item_id): Path<i32>,
+    Form(payload): Form<PurchaseItemPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::purchase_item(&state.db_pool, user_id, actor_id, item_id, payload).await?;
+    let redirect_url = format!("{}/web/items/{}", &state.base_path, item_id);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn discard_item_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(item_id): Path<i32>,
+    Form(payload): Form<DiscardItemPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    db_queries::discard_item(&state.db_pool, user_id, actor_id, item_id, payload)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let redirect_url = format!("{}/web/items/{}", &state.base_path, item_id);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn set_consumption_rule_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+    Form(payload): Form<ConsumptionRulePayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    db_queries::set_consumption_rule(&state.db_pool, user_id, item_id, &payload)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let redirect_url = format!("{}/web/items/{}", &state.base_path, item_id);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn delete_consumption_rule_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::delete_consumption_rule(&state.db_pool, user_id, item_id).await?;
+    let redirect_url = format!("{}/web/items/{}", &state.base_path, item_id);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn discard_batch_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path((item_id, batch_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::discard_batch(&state.db_pool, user_id, actor_id, item_id, batch_id).await?;
+    let redirect_url = format!("{}/web/items/{}", &state.base_path, item_id);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn show_edit_item_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<Response, AppError> {
+    render_edit_item_form(&state, user_id, item_id, None).await
+}
+
+async fn render_edit_item_form(
+    state: &AppState,
+    user_id: i32,
+    item_id: i32,
+    invalid: Option<InvalidForm>,
+) -> Result<Response, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let item = db_queries::get_item_by_id(&state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let categories = state.cache.categories(&state.db_pool, user_id).await?;
+    let stores = db_queries::list_stores(&state.db_pool, user_id).await?;
+    let fields = db_queries::list_attribute_fields(&state.db_pool, user_id).await?;
+    let mut context = Context::new();
+    let status = forms::insert_errors(&mut context, invalid.as_ref());
+    let submitted = invalid.as_ref().map(|invalid| &invalid.values);
+    let attribute_inputs = attributes::form_inputs(&fields, &item.attributes, submitted);
+    context.insert("attribute_inputs", &attribute_inputs);
+    let selected = |field: &str, current: Option<i32>| match &invalid {
+        Some(invalid) => invalid.values.get(field).and_then(|id| id.parse().ok()),
+        None => current,
+    };
+    let selected_category = selected("category_id", item.category.as_ref().map(|c| c.id));
+    let selected_store = selected("preferred_store_id", item.preferred_store_id);
+    match &invalid {
+        Some(invalid) => context.insert("item", &invalid.overlay(&item)),
+        None => context.insert("item", &item),
+    }
+    context.insert("notifications", &notifications);
+    context.insert("categories", &categories);
+    context.insert("selected_category", &selected_category);
+    context.insert("stores", &stores);
+    context.insert("selected_store", &selected_store);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(state, user_id).await?);
+    let rendered = state.tera.render("edit_item.html", context).await?;
+    Ok((status, Html(rendered)).into_response())
+}
+
+pub async fn edit_item_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+    RawForm(body): RawForm,
+) -> Result<Response, AppError> {
+    let mut payload = match forms::parse_form::<UpdateItemPayload>(&body) {
+        Ok(payload) => payload,
+        Err(invalid) => {
+            return render_edit_item_form(&state, user_id, item_id, Some(invalid)).await;
+        }
+    };
+    payload.attributes = match attributes_from_form(&state, user_id, &body).await? {
+        Ok(attributes) => Some(attributes),
+        Err(invalid) => {
+            return render_edit_item_form(&state, user_id, item_id, Some(invalid)).await;
+        }
+    };
+    tracing::info!("UpdateItemPayload: {:?}", payload);
+
+    db_queries::update_item(&state.db_pool, user_id, item_id, payload).await?;
+    let redirect_url = format!("{}/web", &state.base_path);
+    Ok(Redirect::to(&redirect_url).into_response())
+}
+
+/// Duplicates an item and opens the copy for editing.
+pub async fn clone_item_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let copy = db_queries::clone_item(&state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    Ok(Redirect::to(&format!(
+        "{}/web/items/edit/{}",
+        state.base_path, copy.id
+    )))
+}
+
+/// GET /items/duplicates, items that look like one product, to merge them
+pub async fn duplicate_items_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let items =
+        db_queries::get_all_items(&state.db_pool, user_id, ItemSort::Name, ItemFilter::All).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+
+    let mut context = Context::new();
+    context.insert("groups", &duplicates::find_duplicates(items));
+    context.insert("notifications", &notifications);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("duplicates.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn merge_item_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+    Form(payload): Form<MergeItemPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::merge_item(&state.db_pool, user_id, item_id, payload.into).await? {
+        MergeItemOutcome::Merged(_) => {
+            tracing::info!(
+                "Merged item {} into {} for user {}",
+                item_id,
+                payload.into,
+                user_id
+            );
+        }
+        MergeItemOutcome::NotFound => return Err(AppError::ItemNotFound),
+        MergeItemOutcome::InvalidTarget => {
+            return Err(AppError::BadRequest("Invalid target item".into()));
+        }
+    }
+    let redirect_url = format!("{}/web/items/duplicates", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn purchase_item_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(item_id): Path<i32>,
+    Form(payload): Form<PurchaseItemPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    db_queries::purchase_item(&state.db_pool, user_id, actor_id, item_id, payload).await?;
+    let redirect_url = format!("{}/web", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn use_item_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(item_id): Path<i32>,
+    Form(payload): Form<UseItemPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+    let quantity = payload.quantity.unwrap_or(1);
+    db_queries::use_item(&state.db_pool, user_id, actor_id, item_id, quantity).await?;
+    let redirect_url = format!("{}/web", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+/// Where a shelf label's QR code leads: the item, and a button to use it.
+pub async fn show_use_item_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let item = db_queries::get_item_by_id(&state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let mut context = Context::new();
+    context.insert("item", &item);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("use_item.html", context).await?;
+    Ok(Html(rendered))
+}
+
+/// The items picked on the labels page, which come as `item_<id>` fields;
+/// all of them when none are picked.
+async fn selected_items(
+    pool: &PgPool,
+    user_id: i32,
+    fields: &HashMap<String, String>,
+) -> Result<Vec<Item>, AppError> {
+    let selected: Vec<i32> = fields
+        .keys()
+        .filter_map(|key| key.strip_prefix("item_")?.parse().ok())
+        .collect();
+    let mut items =
+        db_queries::get_all_items(pool, user_id, ItemSort::Name, ItemFilter::All).await?;
+    if !selected.is_empty() {
+        items.retain(|item| selected.contains(&item.id));
+    }
+    Ok(items)
+}
+
+pub async fn labels_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let items =
+        db_queries::get_all_items(&state.db_pool, user_id, ItemSort::Name, ItemFilter::All).await?;
+    let layouts: Vec<(LabelLayout, usize)> =
+        [LabelLayout::L7160, LabelLayout::L7163, LabelLayout::L7165]
+            .into_iter()
+            .map(|layout| (layout, labels::per_sheet(layout)))
+            .collect();
+    let mut context = Context::new();
+    context.insert("items", &items);
+    context.insert("layouts", &layouts);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("labels.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn print_labels_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    client: Client,
+    headers: HeaderMap,
+    Query(fields): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let items = selected_items(&state.db_pool, user_id, &fields).await?;
+    let origin = client
+        .origin(&headers)
+        .ok_or_else(|| AppError::BadRequest("Missing Host header".into()))?;
+    let mut context = Context::new();
+    context.insert("labels", &qr::labels(items, &origin, &state.base_path)?);
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("labels_print.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn labels_pdf_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    client: Client,
+    headers: HeaderMap,
+    Query(query): Query<LabelSheetQuery>,
+    Query(fields): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let items = selected_items(&state.db_pool, user_id, &fields).await?;
+    let origin = client
+        .origin(&headers)
+        .ok_or_else(|| AppError::BadRequest("Missing Host header".into()))?;
+    let document = labels::pdf(&items, query.layout, query.skip, &origin, &state.base_path)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf"),
+            (
+                header::CONTENT_DISPOSITION,
+                "inline; filename=\"etykiety.pdf\"",
+            ),
+        ],
+        document,
+    ))
+}
+
+pub async fn delete_item_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(item_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows = db_queries::delete_item(&state.db_pool, user_id, item_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::ItemNotFound);
+    }
+    let redirect_url = format!("{}/web", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn stocktakes_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let preferences = state.cache.preferences(&state.db_pool, user_id).await?;
+    let stocktakes = db_queries::get_stocktakes(&state.db_pool, user_id).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("stocktakes", &stocktakes);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &preferences.theme);
+    context.insert("timezone", &preferences.timezone);
+    let rendered = state.tera.render("stocktakes.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn start_stocktake_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let stocktake = db_queries::start_stocktake(&state.db_pool, user_id).await?;
+    let redirect_url = format!("{}/web/stocktakes/{}", &state.base_path, stocktake.id);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn show_stocktake(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(stocktake_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let preferences = state.cache.preferences(&state.db_pool, user_id).await?;
+    let stocktake = db_queries::get_stocktake(&state.db_pool, user_id, stocktake_id)
+        .await?
+        .ok_or(AppError::NotFound("Stocktake not found".into()))?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("stocktake", &stocktake);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &preferences.theme);
+    context.insert("timezone", &preferences.timezone);
+    let rendered = state.tera.render("stocktake.html", context).await?;
+    Ok(Html(rendered))
+}
+
+/// POST /stocktakes/{id}
+/// Counts arrive as `count_<item_id>` fields; empty fields are left uncounted.
+/// Submitting with `action=complete` applies the stocktake after saving.
+pub async fn save_stocktake_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(stocktake_id): Path<i32>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut counts = Vec::new();
+    for (key, value) in &form {
+        let Some(item_id) = key.strip_prefix("count_").and_then(|id| id.parse().ok()) else {
+            continue;
+        };
+        if value.is_empty() {
+            continue;
+        }
+        let counted_quantity: i32 = value
+            .parse()
+            .ok()
+            .filter(|q| *q >= 0)
+            .ok_or_else(|| AppError::BadRequest("Nieprawidłowa ilość".into()))?;
+        counts.push(StocktakeCount {
+            item_id,
+            counted_quantity,
+        });
+    }
+
+    let updated =
+        db_queries::update_stocktake_counts(&state.db_pool, user_id, stocktake_id, &counts).await?;
+    if !updated {
+        return Err(AppError::NotFound("Open stocktake not found".into()));
+    }
+    if form.get("action").map(String::as_str) == Some("complete") {
+        db_queries::complete_stocktake(&state.db_pool, user_id, actor_id, stocktake_id).await?;
+    }
+
+    let redirect_url = format!("{}/web/stocktakes/{}", &state.base_path, stocktake_id);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn cancel_stocktake_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(stocktake_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows = db_queries::cancel_stocktake(&state.db_pool, user_id, stocktake_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Open stocktake not found".into()));
+    }
+    let redirect_url = format!("{}/web/stocktakes", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn recipes_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let recipes = db_queries::get_recipes(&state.db_pool, user_id).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("recipes", &recipes);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("recipes.html", context).await?;
+    Ok(Html(rendered))
+}
+
+/// Builds a recipe payload from the recipe form.
+/// Ingredient quantities arrive as `ingredient_<item_id>` fields; empty or zero fields are skipped.
+fn recipe_payload_from_form(
+    form: &HashMap<String, String>,
+) -> Result<CreateRecipePayload, AppError> {
+    let name = form.get("name").cloned().unwrap_or_default();
+    if name.trim().is_empty() {
+        return Err(AppError::BadRequest("Nazwa przepisu jest wymagana".into()));
+    }
+    let instructions = form
+        .get("instructions")
+        .filter(|i| !i.trim().is_empty())
+        .cloned();
+
+    let mut ingredients = Vec::new();
+    for (key, value) in form {
+        let Some(item_id) = key
+            .strip_prefix("ingredient_")
+            .and_then(|id| id.parse().ok())
+        else {
+            continue;
+        };
+        if value.is_empty() {
+            continue;
+        }
+        let quantity: i32 = value
+            .parse()
+            .ok()
+            .filter(|q| *q >= 0)
+            .ok_or_else(|| AppError::BadRequest("Nieprawidłowa ilość".into()))?;
+        if quantity > 0 {
+            ingredients.push(RecipeIngredientPayload { item_id, quantity });
+        }
+    }
+
+    Ok(CreateRecipePayload {
+        name,
+        instructions,
+        ingredients,
+    })
+}
+
+pub async fn show_add_recipe_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let items =
+        db_queries::get_all_items(&state.db_pool, user_id, ItemSort::Manual, ItemFilter::All)
+            .await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("items", &items);
+    context.insert("ingredients", &HashMap::<String, i32>::new());
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("recipe_form.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn add_recipe_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let payload = recipe_payload_from_form(&form)?;
+    payload.validate()?;
+    db_queries::create_recipe(&state.db_pool, user_id, payload).await?;
+    let redirect_url = format!("{}/web/recipes", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn show_edit_recipe_form(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(recipe_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let recipe = db_queries::get_recipe(&state.db_pool, user_id, recipe_id)
+        .await?
+        .ok_or(AppError::NotFound("Recipe not found".into()))?;
+    let items =
+        db_queries::get_all_items(&state.db_pool, user_id, ItemSort::Manual, ItemFilter::All)
+            .await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    // Keyed by item id as a string, since Tera map lookups use string keys
+    let ingredients: HashMap<String, i32> = recipe
+        .ingredients
+        .iter()
+        .map(|i| (i.item_id.to_string(), i.quantity))
+        .collect();
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("recipe", &recipe);
+    context.insert("items", &items);
+    context.insert("ingredients", &ingredients);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("recipe_form.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn edit_recipe_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(recipe_id): Path<i32>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let payload = recipe_payload_from_form(&form)?;
+    payload.validate()?;
+    db_queries::update_recipe(&state.db_pool, user_id, recipe_id, payload)
+        .await?
+        .ok_or(AppError::NotFound("Recipe not found".into()))?;
+    let redirect_url = format!("{}/web/recipes", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn delete_recipe_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(recipe_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows = db_queries::delete_recipe(&state.db_pool, user_id, recipe_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Recipe not found".into()));
+    }
+    let redirect_url = format!("{}/web/recipes", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn cook_recipe_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(recipe_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::cook_recipe(&state.db_pool, user_id, actor_id, recipe_id).await? {
+        CookOutcome::Cooked => {
+            let redirect_url = format!("{}/web/recipes", &state.base_path);
+            Ok(Redirect::to(&redirect_url))
+        }
+        CookOutcome::RecipeNotFound => Err(AppError::NotFound("Recipe not found".into())),
+        CookOutcome::MissingIngredients(missing) => {
+            let names: Vec<String> = missing.into_iter().map(|m| m.item_name).collect();
+            Err(AppError::BadRequest(format!(
+                "Brakuje składników: {}",
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+pub async fn meal_plan_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Query(query): Query<MealPlanQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let today = OffsetDateTime::now_utc().date();
+    let (monday, sunday) = recipes::week_bounds(query.week.unwrap_or(today));
+
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let entries = db_queries::get_meal_plan(&state.db_pool, user_id, monday, sunday).await?;
+    let recipes = db_queries::get_recipes(&state.db_pool, user_id).await?;
+    let shortages: Vec<_> = db_queries::get_shopping_list(&state.db_pool, user_id)
+        .await?
+        .into_iter()
+        .filter(|entry| entry.reserved > 0)
+        .collect();
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("days", &recipes::group_meals_by_day(monday, today, entries));
+    context.insert("recipes", &recipes);
+    context.insert("shortages", &shortages);
+    context.insert("week", &monday.to_string());
+    context.insert("previous_week", &(monday - Duration::weeks(1)).to_string());
+    context.insert("next_week", &(monday + Duration::weeks(1)).to_string());
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("meal_plan.html", context).await?;
+    Ok(Html(rendered))
+}
+
+pub async fn add_meal_plan_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Form(payload): Form<CreateMealPlanPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let entry = db_queries::add_meal_plan_entry(&state.db_pool, user_id, payload)
+        .await?
+        .ok_or(AppError::NotFound("Recipe not found".into()))?;
+    let redirect_url = format!(
+        "{}/web/meal-plan?week={}",
+        &state.base_path, entry.planned_for
+    );
+    Ok(Redirect::to(&redirect_url))
+}
+
+pub async fn delete_meal_plan_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    Path(entry_id): Path<i32>,
+    Query(query): Query<MealPlanQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_rows =
+        db_queries::delete_meal_plan_entry(&state.db_pool, user_id, entry_id).await?;
+    if affected_rows == 0 {
+        return Err(AppError::NotFound("Meal plan entry not found".into()));
+    }
+    Ok(Redirect::to(&meal_plan_url(&state.base_path, &query)))
+}
+
+pub async fn cook_meal_plan_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Path(entry_id): Path<i32>,
+    Query(query): Query<MealPlanQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    match db_queries::cook_planned_meal(&state.db_pool, user_id, actor_id, entry_id).await? {
+        Some(CookOutcome::Cooked) => Ok(Redirect::to(&meal_plan_url(&state.base_path, &query))),
+        Some(CookOutcome::MissingIngredients(missing)) => {
+            let names: Vec<String> = missing.into_iter().map(|m| m.item_name).collect();
+            Err(AppError::BadRequest(format!(
+                "Brakuje składników: {}",
+                names.join(", ")
+            )))
+        }
+        Some(CookOutcome::RecipeNotFound) | None => Err(AppError::NotFound(
+            "Meal plan entry not found or already cooked".into(),
+        )),
+    }
+}
+
+fn meal_plan_url(base_path: &str, query: &MealPlanQuery) -> String {
+    match query.week {
+        Some(week) => format!("{}/web/meal-plan?week={}", base_path, week),
+        None => format!("{}/web/meal-plan", base_path),
+    }
+}
+
+pub async fn shopping_list_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let shopping_list = db_queries::get_shopping_list(&state.db_pool, user_id).await?;
+    let notifications = get_notifications(&state.db_pool, user_id).await;
+    let mut context = Context::new();
+    context.insert("notifications", &notifications);
+    context.insert("shopping_list", &shopping_list);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("shopping_list.html", context).await?;
+    Ok(Html(rendered))
+}
+
+/// POST /items/restock-all from the shopping list, with the items ticked
+/// as `item_<id>` fields; nothing is bought when none are.
+pub async fn restock_items_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let selected: Vec<i32> = form
+        .keys()
+        .filter_map(|key| key.strip_prefix("item_")?.parse().ok())
+        .collect();
+    if !selected.is_empty() {
+        db_queries::restock_items(&state.db_pool, user_id, actor_id, Some(&selected)).await?;
+    }
+    let redirect_url = format!("{}/web/shopping-list", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+/// GET /shopping-list/checkout, the list to tick off in the store
+pub async fn checkout_page_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.cache.user(&state.db_pool, user_id).await?;
+    let shopping_list = db_queries::get_shopping_list(&state.db_pool, user_id).await?;
+    let stores = db_queries::list_stores(&state.db_pool, user_id).await?;
+    let mut context = Context::new();
+    context.insert("shopping_list", &shopping_list);
+    context.insert("stores", &stores);
+    context.insert("base_path", &state.base_path);
+    context.insert("user", &user);
+    context.insert("theme", &user_theme(&state, user_id).await?);
+    let rendered = state.tera.render("checkout.html", context).await?;
+    Ok(Html(rendered))
+}
+
+/// POST /shopping-list/checkout. Ticked entries come as `item_<id>`, with
+/// `quantity_<id>` and an optional `price_<id>` next to them.
+pub async fn checkout_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+    SignedInUser(actor_id): SignedInUser,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let payload = checkout_payload(&form)?;
+    payload.validate()?;
+    db_queries::checkout(&state.db_pool, user_id, actor_id, &payload)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    let redirect_url = format!("{}/web/shopping-list", &state.base_path);
+    Ok(Redirect::to(&redirect_url))
+}
+
+fn checkout_payload(form: &HashMap<String, String>) -> Result<CheckoutPayload, ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    let field = |name: String| form.get(&name).map(|value| value.trim()).unwrap_or("");
+    let mut items = Vec::new();
+    for item_id in form
+        .keys()
+        .filter_map(|key| key.strip_prefix("item_")?.parse::<i32>().ok())
+    {
+        let quantity = field(format!("quantity_{item_id}"))
+            .parse()
+            .unwrap_or_else(|_| {
+                errors.add("quantity", "must be a whole number");
+                0
+            });
+        let price = match field(format!("price_{item_id}")) {
+            "" => None,
+            price => price.parse().map_or_else(
+                |e: String| {
+                    errors.add("price", e);
+                    None
+                },
+                Some,
+            ),
+        };
+        items.push(CheckoutLine {
+            item_id,
+            quantity,
+            price,
+        });
+    }
+    // The order the boxes were ticked in doesn't matter, but stays the same
+    items.sort_by_key(|line| line.item_id);
+    let store_id = field("store_id".to_string()).parse().ok();
+    errors.into_result()?;
+    Ok(CheckoutPayload { store_id, items })
+}
+
+/// GET /shopping-list/print, the list on its own for printing
+pub async fn print_shopping_list_handler(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user_id): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let shopping_list = db_queries::get_shopping_list(&state.db_pool, user_id).await?;
+    let mut context = Context::new();
+    context.insert("sections", &shopping_list::group_by_store(&shopping_list));
+    context.insert("today", &OffsetDateTime::now_utc().date().to_string());
+    context.insert("base_path", &state.base_path);
+    let rendered = state
+        .tera
+        .render("shopping_list_print.html", context)
+        .await?;
+    Ok(Html(rendered))
+}
@@ -0,0 +1,111 @@
+//! Probes for orchestrators and uptime monitors. `/health/live` only says the
+//! process is serving requests, so a failing database doesn't get it
+//! restarted; `/health/ready` says whether it can do its job, so a load
+//! balancer can send requests elsewhere until it can.
+
+use crate::{AppState, db};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long the database may take to answer before the app counts as not
+/// ready. Probes usually give up after a second or two themselves.
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Unavailable,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Check {
+    fn ok() -> Check {
+        Check {
+            status: Status::Ok,
+            error: None,
+        }
+    }
+
+    fn failed(error: impl ToString) -> Check {
+        Check {
+            status: Status::Unavailable,
+            error: Some(error.to_string()),
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        matches!(self.status, Status::Ok)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: Status,
+    pub database: Check,
+    pub migrations: Check,
+    pub templates: Check,
+}
+
+/// GET /health/live (and `/health`)
+pub async fn live() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": Status::Ok }))
+}
+
+/// GET /health/ready: `200` when the database answers, every migration is
+/// applied and the templates are loaded, `503` otherwise.
+pub async fn ready(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let database = match tokio::time::timeout(DATABASE_TIMEOUT, db::ping(&state.db_pool)).await {
+        Ok(Ok(())) => Check::ok(),
+        Ok(Err(e)) => Check::failed(e),
+        Err(_) => Check::failed(format!("no answer within {:?}", DATABASE_TIMEOUT)),
+    };
+    let migrations = if !database.is_ok() {
+        Check::failed("database unavailable")
+    } else {
+        match db::pending_migrations(&state.db_pool).await {
+            Ok(pending) if pending.is_empty() => Check::ok(),
+            Ok(pending) => Check::failed(format!("pending migrations: {:?}", pending)),
+            Err(e) => Check::failed(e),
+        }
+    };
+    let templates = if state.tera.count() > 0 {
+        Check::ok()
+    } else {
+        Check::failed("no templates loaded")
+    };
+
+    let is_ready = database.is_ok() && migrations.is_ok() && templates.is_ok();
+    if !is_ready {
+        tracing::warn!(
+            "not ready: database {:?}, migrations {:?}, templates {:?}",
+            database.error,
+            migrations.error,
+            templates.error
+        );
+    }
+    let status = if is_ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let readiness = Readiness {
+        status: if is_ready {
+            Status::Ok
+        } else {
+            Status::Unavailable
+        },
+        database,
+        migrations,
+        templates,
+    };
+    (status, Json(readiness))
+}
@@ -0,0 +1,130 @@
+//! Endpoints for Home Assistant and other home automation: sensors to poll
+//! for how the stock looks, and a way for automations to use up items, e.g.
+//! a coffee pod each time the coffee machine runs. They take a long-lived
+//! token from the settings page instead of the session cookie, as
+//! `Authorization: Bearer {token}`, as does `/api/assistant` (see
+//! `assistant`) and nothing else in the API.
+
+use crate::models::{CreateApiTokenPayload, HaConsumePayload, HaSensors, Item, NewApiToken};
+use crate::{
+    AppState,
+    auth::{self, AuthUser},
+    db::{self, DBResult},
+    errors::AppError,
+    reporting, shopping_list,
+    validation::Validate,
+};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Lets through requests with a valid token, as its owner.
+pub async fn authenticate(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    let Some(token) = token else {
+        return AppError::Unauthorized.into_response();
+    };
+    let user_id = match db::use_api_token(&state.db_pool, &token_hash(&token)).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => return AppError::Unauthorized.into_response(),
+        Err(e) => return AppError::from(e).into_response(),
+    };
+    tracing::Span::current().record("user_id", user_id);
+    reporting::set_user(user_id);
+    req.extensions_mut().insert(AuthUser(user_id));
+    next.run(req).await
+}
+
+/// How many characters of a token the settings page shows.
+const TOKEN_PREFIX_LEN: usize = 8;
+
+/// Validates and stores a new token for `user_id`. The token is in the
+/// answer and nowhere else; the database only keeps its hash.
+pub async fn create_token(
+    state: &AppState,
+    user_id: i32,
+    payload: CreateApiTokenPayload,
+) -> Result<NewApiToken, AppError> {
+    payload.validate()?;
+    let token = auth::random_token()?;
+    let api_token = db::create_api_token(
+        &state.db_pool,
+        user_id,
+        &token_hash(&token),
+        &token[..TOKEN_PREFIX_LEN],
+        &payload.label,
+    )
+    .await?;
+    Ok(NewApiToken { api_token, token })
+}
+
+/// What a token is looked up by: its SHA-256 in hex. The tokens are random,
+/// so a plain hash is as good as a slow one.
+fn token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub async fn sensors(pool: &PgPool, user_id: i32) -> DBResult<HaSensors> {
+    let overview = db::get_stats_overview(pool, user_id, 1).await?;
+    let to_restock = db::get_items_to_restock(pool, user_id).await?;
+    let expiring = db::get_expiring_batches(pool, user_id, db::EXPIRY_WARNING_DAYS).await?;
+
+    let mut categories = BTreeMap::new();
+    for category in overview.by_category {
+        let name = category
+            .name
+            .unwrap_or_else(|| shopping_list::UNCATEGORIZED.to_string());
+        // Subcategories of different parents may share a name
+        *categories.entry(name).or_default() += category.item_count;
+    }
+    Ok(HaSensors {
+        items: overview.total_items,
+        low_stock: overview.low_stock_count,
+        out_of_stock: overview.out_of_stock_count,
+        expiring: expiring.len() as i64,
+        low_stock_items: to_restock.into_iter().map(|item| item.name).collect(),
+        categories,
+    })
+}
+
+/// Uses up units of the item named in `payload`, like the "Użyj" button.
+pub async fn consume(
+    pool: &PgPool,
+    user_id: i32,
+    payload: HaConsumePayload,
+) -> Result<Item, AppError> {
+    payload.validate()?;
+    let item_id = match payload.item_id {
+        Some(item_id) => item_id,
+        None => {
+            let name = payload.name.as_deref().unwrap_or_default();
+            db::find_item_by_name(pool, user_id, name)
+                .await?
+                .ok_or(AppError::ItemNotFound)?
+        }
+    };
+    let quantity = payload.quantity.unwrap_or(1);
+    db::use_item(pool, user_id, user_id, item_id, quantity)
+        .await?
+        .ok_or(AppError::ItemNotFound)
+}
@@ -0,0 +1,167 @@
+//! Households: a user lets other accounts into their inventory, as viewers,
+//! who only look, or editors, who change it too. A member switches a session
+//! to the household, after which `auth::load_session` makes `AuthUser` the
+//! owner, so every handler works on the owner's inventory as it is, and
+//! `guard` keeps the member to what their role allows. Their own id stays
+//! available as `SignedInUser`, which the member management below goes by.
+//! Members join by accepting an invitation sent to the email of their
+//! account.
+
+use crate::errors::{AppError, ErrorCode};
+use crate::models::{HouseholdInvitation, HouseholdRole, InviteHouseholdMemberPayload};
+use crate::validation::{Validate, ValidationErrors};
+use crate::{AppState, db};
+use axum::{
+    body::Body,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Put into the request by `auth::load_session` while the session works in
+/// someone else's household.
+#[derive(Debug, Clone, Copy)]
+pub struct Membership {
+    /// The id of the owner.
+    pub household_id: i32,
+    pub role: HouseholdRole,
+}
+
+/// Routes of the account rather than of the inventory, which stay the
+/// owner's: logins, share links, alerts, tokens, preferences, the settings
+/// page, and the restore that replaces everything.
+const OWNER_PATHS: &[&str] = &[
+    "/sessions",
+    "/share-links",
+    "/notification-channels",
+    "/notification-settings",
+    "/tokens",
+    "/preferences",
+    "/restore",
+    "/settings",
+];
+
+/// Keeps members working in a household to their role: nobody but the owner
+/// uses the account's routes, and viewers only read. The household routes
+/// are let through, as they go by `SignedInUser` and check it themselves. Paths
+/// are those within `/api` and `/web`.
+pub async fn guard(req: Request<Body>, next: Next) -> Response {
+    let Some(membership) = req.extensions().get::<Membership>().copied() else {
+        return next.run(req).await;
+    };
+    let path = req.uri().path();
+    if is_under(path, "/households") {
+        return next.run(req).await;
+    }
+    if OWNER_PATHS.iter().any(|prefix| is_under(path, prefix)) {
+        return AppError::Forbidden(
+            "Only the owner of the household can do this; switch back to your own inventory first"
+                .into(),
+        )
+        .into_response();
+    }
+    if membership.role == HouseholdRole::Viewer && !req.method().is_safe() {
+        return AppError::Forbidden("Viewers can't change the inventory".into()).into_response();
+    }
+    next.run(req).await
+}
+
+fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Checks that `account_id` may manage the members of `household_id`, which
+/// only its owner may. Other members get 403; anyone else 404, as for
+/// anything else that isn't theirs.
+pub async fn check_owner(
+    state: &AppState,
+    account_id: i32,
+    household_id: i32,
+) -> Result<(), AppError> {
+    if account_id == household_id {
+        return Ok(());
+    }
+    match db::get_household_role(&state.db_pool, household_id, account_id).await? {
+        Some(_) => Err(AppError::Forbidden(
+            "Only the owner can manage the members of a household".into(),
+        )),
+        None => Err(AppError::NotFound("Household not found".into())),
+    }
+}
+
+/// Checks that `account_id` owns or is a member of `household_id`.
+pub async fn check_member(
+    state: &AppState,
+    account_id: i32,
+    household_id: i32,
+) -> Result<(), AppError> {
+    if account_id == household_id
+        || db::get_household_role(&state.db_pool, household_id, account_id)
+            .await?
+            .is_some()
+    {
+        Ok(())
+    } else {
+        Err(AppError::NotFound("Household not found".into()))
+    }
+}
+
+/// Invites the account with the payload's email into the household of
+/// `owner_id`. The answer is the same whether or not the email has an
+/// account, so owners can't find out who is registered.
+pub async fn invite(
+    state: &AppState,
+    owner_id: i32,
+    payload: InviteHouseholdMemberPayload,
+) -> Result<HouseholdInvitation, AppError> {
+    payload.validate()?;
+    let email = payload.email.trim();
+    let owner = email_of(state, owner_id).await?;
+    if email == owner {
+        let mut errors = ValidationErrors::new();
+        errors.add("email", "must be someone else's");
+        return Err(AppError::Validation(errors));
+    }
+    // Only tells the owner what their list of members does
+    if db::is_household_member_email(&state.db_pool, owner_id, email).await? {
+        return Err(AppError::Conflict {
+            code: ErrorCode::AlreadyExists,
+            field: Some("email"),
+            message: "This account is already a member".into(),
+        });
+    }
+    let invitation =
+        db::invite_household_member(&state.db_pool, owner_id, email, payload.role).await?;
+    Ok(invitation)
+}
+
+/// The email of the account, which invitations are for.
+pub async fn email_of(state: &AppState, account_id: i32) -> Result<String, AppError> {
+    let account = state
+        .cache
+        .user(&state.db_pool, account_id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+    Ok(account.email)
+}
+
+/// Makes the session work in the household of `household_id`, which
+/// `account_id` must be a member of, or in their own inventory for `None`.
+pub async fn switch(
+    state: &AppState,
+    account_id: i32,
+    session_id: i32,
+    household_id: Option<i32>,
+) -> Result<(), AppError> {
+    let household_id = household_id.filter(|id| *id != account_id);
+    if let Some(household_id) = household_id
+        && db::get_household_role(&state.db_pool, household_id, account_id)
+            .await?
+            .is_none()
+    {
+        return Err(AppError::NotFound("Household not found".into()));
+    }
+    db::set_session_household(&state.db_pool, session_id, household_id).await?;
+    Ok(())
+}
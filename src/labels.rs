@@ -0,0 +1,233 @@
+//! Shelf labels as a PDF laid out for Avery A4 sheets, to print on sticky
+//! labels rather than cut out of paper. Each label has a bar in the item's
+//! category color, the QR code of `qr` and the item's name, category and
+//! location.
+
+use crate::categories;
+use crate::errors::AppError;
+use crate::models::{Item, LabelLayout};
+use crate::pdf::{self, Document, Font};
+use crate::qr;
+
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+/// Kept clear inside each label, as sheets never feed perfectly straight.
+const PADDING_MM: f32 = 2.5;
+const COLOR_BAR_MM: f32 = 3.0;
+/// The bar of items without a category.
+const NO_CATEGORY_COLOR: [u8; 3] = [0xdb, 0xd1, 0xdb];
+const NAME_SIZE: f32 = 10.0;
+const NAME_LINES: usize = 3;
+const DETAIL_SIZE: f32 = 8.0;
+
+/// Where the labels are on a sheet, in millimetres from its top left corner.
+struct Sheet {
+    columns: usize,
+    rows: usize,
+    width: f32,
+    height: f32,
+    top: f32,
+    left: f32,
+    column_pitch: f32,
+    row_pitch: f32,
+}
+
+fn sheet(layout: LabelLayout) -> Sheet {
+    match layout {
+        LabelLayout::L7160 => Sheet {
+            columns: 3,
+            rows: 7,
+            width: 63.5,
+            height: 38.1,
+            top: 15.15,
+            left: 7.25,
+            column_pitch: 66.04,
+            row_pitch: 38.1,
+        },
+        LabelLayout::L7163 => Sheet {
+            columns: 2,
+            rows: 7,
+            width: 99.1,
+            height: 38.1,
+            top: 15.15,
+            left: 4.65,
+            column_pitch: 101.6,
+            row_pitch: 38.1,
+        },
+        LabelLayout::L7165 => Sheet {
+            columns: 2,
+            rows: 4,
+            width: 99.1,
+            height: 67.7,
+            top: 13.1,
+            left: 4.65,
+            column_pitch: 101.6,
+            row_pitch: 67.7,
+        },
+    }
+}
+
+fn mm(length: f32) -> f32 {
+    length * POINTS_PER_MM
+}
+
+/// How many labels a sheet of `layout` has.
+pub fn per_sheet(layout: LabelLayout) -> usize {
+    let sheet = sheet(layout);
+    sheet.columns * sheet.rows
+}
+
+/// The labels of `items`, in order, starting `skip` labels into the first
+/// sheet. The codes link to the server at `origin`.
+pub fn pdf(
+    items: &[Item],
+    layout: LabelLayout,
+    skip: usize,
+    origin: &str,
+    base_path: &str,
+) -> Result<Vec<u8>, AppError> {
+    let sheet = sheet(layout);
+    let per_sheet = sheet.columns * sheet.rows;
+    if skip >= per_sheet {
+        return Err(AppError::BadRequest(format!(
+            "A sheet has only {per_sheet} labels"
+        )));
+    }
+    let mut document = Document::new();
+    for (index, item) in items.iter().enumerate() {
+        let position = skip + index;
+        if index > 0 && position.is_multiple_of(per_sheet) {
+            document.new_page();
+        }
+        let slot = position % per_sheet;
+        let left = mm(sheet.left + (slot % sheet.columns) as f32 * sheet.column_pitch);
+        let top =
+            pdf::PAGE_HEIGHT - mm(sheet.top + (slot / sheet.columns) as f32 * sheet.row_pitch);
+        let url = qr::use_url(origin, base_path, item.id);
+        label(&mut document, &sheet, left, top, item, &url)?;
+    }
+    Ok(document.finish())
+}
+
+/// One label with its top left corner at `left`, `top`.
+fn label(
+    document: &mut Document,
+    sheet: &Sheet,
+    left: f32,
+    top: f32,
+    item: &Item,
+    url: &str,
+) -> Result<(), AppError> {
+    let width = mm(sheet.width);
+    let height = mm(sheet.height);
+    let padding = mm(PADDING_MM);
+    let color = item
+        .category
+        .as_ref()
+        .and_then(|category| rgb(&category.color))
+        .unwrap_or(NO_CATEGORY_COLOR);
+    document.rect_at(left, top - height, mm(COLOR_BAR_MM), height, color);
+
+    // The code's quiet zone doubles as the space around it
+    let (modules, dark) = qr::modules(url)?;
+    let code_box = height - 2.0 * padding;
+    let quiet_zone = code_box * qr::QUIET_ZONE as f32 / (modules + 2 * qr::QUIET_ZONE) as f32;
+    let code_left = left + mm(COLOR_BAR_MM);
+    document.modules_at(
+        code_left + quiet_zone,
+        top - height + padding + quiet_zone,
+        code_box - 2.0 * quiet_zone,
+        modules,
+        &dark,
+    );
+
+    let text_left = code_left + code_box;
+    let text_width = left + width - padding - text_left;
+    let mut y = top - padding - NAME_SIZE;
+    for line in wrap(&item.name, max_chars(text_width, NAME_SIZE), NAME_LINES) {
+        document.text_at(text_left, y, Font::Bold, NAME_SIZE, &line, usize::MAX);
+        y -= NAME_SIZE * 1.2;
+    }
+    let details = [
+        item.category
+            .as_ref()
+            .map(|category| category.name.as_str()),
+        item.location.as_deref(),
+    ];
+    let detail_chars = max_chars(text_width, DETAIL_SIZE);
+    for detail in details.into_iter().flatten() {
+        y -= DETAIL_SIZE * 0.4;
+        if y < top - height + padding {
+            break;
+        }
+        document.text_at(
+            text_left,
+            y,
+            Font::Regular,
+            DETAIL_SIZE,
+            detail,
+            detail_chars,
+        );
+        y -= DETAIL_SIZE * 1.2;
+    }
+    Ok(())
+}
+
+/// Roughly how many characters fit in `width`, as nothing measures the text.
+fn max_chars(width: f32, size: f32) -> usize {
+    (width / (size * 0.55)).max(1.0) as usize
+}
+
+/// `text` broken between words into at most `max_lines` lines of up to
+/// `max_chars`; the rest is cut with an ellipsis.
+pub fn wrap(text: &str, max_chars: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word;
+        loop {
+            let length = current.chars().count();
+            let needed = if length == 0 { 0 } else { length + 1 };
+            if needed + word.chars().count() <= max_chars {
+                if length > 0 {
+                    current.push(' ');
+                }
+                current.push_str(word);
+                break;
+            }
+            if length > 0 {
+                lines.push(std::mem::take(&mut current));
+                continue;
+            }
+            // A word longer than a line is split
+            let split = word
+                .char_indices()
+                .nth(max_chars)
+                .map_or(word.len(), |(index, _)| index);
+            lines.push(word[..split].to_string());
+            word = &word[split..];
+            if word.is_empty() {
+                break;
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            let kept: String = last.chars().take(max_chars.saturating_sub(1)).collect();
+            *last = format!("{kept}…");
+        }
+    }
+    lines
+}
+
+/// `#rrggbb` as its red, green and blue.
+fn rgb(color: &str) -> Option<[u8; 3]> {
+    if !categories::is_hex_color(color) {
+        return None;
+    }
+    let channel = |range| u8::from_str_radix(&color[range], 16).ok();
+    Some([channel(1..3)?, channel(3..5)?, channel(5..7)?])
+}
@@ -0,0 +1,776 @@
+use axum::Router;
+use axum::body::Body;
+use axum::extract::DefaultBodyLimit;
+use axum::http::{Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::IntoResponse;
+use axum::response::Redirect;
+use axum::routing::{delete, get, patch, post, put};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tower_http::compression::CompressionLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+
+pub mod activity;
+pub mod assets;
+pub mod assistant;
+pub mod attributes;
+pub mod auth;
+pub mod backup;
+pub mod branding;
+pub mod cache;
+pub mod calendar;
+pub mod categories;
+pub mod conditional;
+pub mod config;
+pub mod consumption;
+pub mod cors;
+pub mod db;
+pub mod demo;
+pub mod duplicates;
+pub mod errors;
+pub mod export;
+pub mod feeds;
+pub mod filters;
+pub mod graphql;
+pub mod grocy;
+pub mod handlers;
+pub mod health;
+pub mod home_assistant;
+pub mod households;
+pub mod labels;
+pub mod limits;
+pub mod mail;
+pub mod maintenance;
+pub mod models;
+pub mod mqtt;
+pub mod notify;
+pub mod pagination;
+pub mod pdf;
+pub mod proxy;
+pub mod purchases;
+pub mod pwa;
+pub mod qr;
+pub mod receipts;
+pub mod recipes;
+pub mod reporting;
+pub mod scheduler;
+pub mod seed;
+pub mod sharing;
+pub mod shopping_list;
+pub mod storage;
+pub mod summary;
+pub mod sync;
+#[cfg(feature = "test-utils")]
+pub mod testing;
+pub mod tls;
+#[cfg(unix)]
+pub mod unix_socket;
+pub mod validation;
+pub mod waste;
+
+use handlers::{api_handlers, web_handlers};
+
+/// Backups carry the whole item history, so they may exceed `max_body_kb`.
+const MAX_RESTORE_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub tera: Arc<assets::Templates>,
+    pub db_pool: PgPool,
+    pub base_path: String,
+    pub category_seed: Vec<seed::SeedCategory>,
+    /// Shows the demo login on the login page.
+    pub demo_mode: bool,
+    pub sessions: auth::SessionSettings,
+    pub cors: cors::CorsSettings,
+    pub limits: limits::RequestLimits,
+    pub cache: cache::LookupCache,
+    pub maintenance: maintenance::Maintenance,
+    pub proxy: proxy::ProxySettings,
+    pub files: storage::FileStore,
+    /// Accent color and custom stylesheet of the instance.
+    pub branding: branding::Branding,
+}
+
+impl AppState {
+    /// Loads the templates and the category seed named by the config.
+    /// Migrations are left to the caller.
+    pub fn from_config(
+        config: &config::Config,
+        db_pool: PgPool,
+    ) -> Result<AppState, Box<dyn std::error::Error>> {
+        let branding = branding::Branding::from_config(config)?;
+        let mut templates = assets::Templates::load()?;
+        if let Some(dir) = &config.template_dir {
+            templates = templates.with_overrides(dir)?;
+            let overridden = templates.overridden()?;
+            if overridden.is_empty() {
+                tracing::warn!("{} overrides none of the built-in templates", dir);
+            } else {
+                tracing::info!(
+                    "Templates overridden from {}: {}",
+                    dir,
+                    overridden.join(", ")
+                );
+            }
+        }
+        Ok(AppState {
+            tera: Arc::new(templates.with_branding(&branding)),
+            db_pool,
+            base_path: config.base_path.clone(),
+            category_seed: seed::load_category_seed(config.category_seed_file.as_deref())?,
+            demo_mode: config.demo_mode,
+            sessions: auth::SessionSettings::from_config(config),
+            cors: cors::CorsSettings::from_config(config),
+            limits: limits::RequestLimits::from_config(config),
+            cache: cache::LookupCache::from_config(config),
+            maintenance: maintenance::Maintenance::from_config(config),
+            proxy: proxy::ProxySettings::from_config(config),
+            files: storage::FileStore::from_config(config)?,
+            branding,
+        })
+    }
+}
+
+async fn strip_trailing_slash(req: Request<Body>, next: Next) -> impl IntoResponse {
+    let uri = req.uri();
+    let path = uri.path();
+
+    if path.len() > 1 && path.ends_with('/') {
+        // remove the trailing slash and redirect
+        let new_path = path.trim_end_matches('/');
+        let new_uri_string = if let Some(query) = uri.query() {
+            format!("{}?{}", new_path, query)
+        } else {
+            new_path.to_string()
+        };
+
+        // Use a permanent redirect
+        return Redirect::permanent(&new_uri_string).into_response();
+    }
+
+    // If no trailing slash, just continue
+    next.run(req).await
+}
+
+/// Span of each request, tagged with its `x-request-id` (generated unless the
+/// client or a proxy sent one). `client_ip` is filled in by `proxy::resolve`
+/// and `user_id` by `auth::load_session`.
+fn request_span(request: &Request<Body>) -> tracing::Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id,
+        client_ip = tracing::field::Empty,
+        user_id = tracing::field::Empty,
+    )
+}
+
+/// Builds the full application router: web UI, JSON API and static files,
+/// nested under `base_path` when the app runs on a subpath.
+pub fn build_app(shared_state: Arc<AppState>) -> Router {
+    // Authenticated by API token rather than the session cookie
+    let home_assistant_routes = Router::new()
+        .route("/sensors", get(api_handlers::get_ha_sensors_api))
+        .route("/consume", post(api_handlers::ha_consume_api))
+        .route_layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            home_assistant::authenticate,
+        ));
+    let api_routes = Router::new()
+        .route(
+            "/items",
+            get(api_handlers::list_items_api).post(api_handlers::create_item_api),
+        )
+        .route(
+            "/items/{id}",
+            get(api_handlers::get_item_api)
+                .put(api_handlers::update_item_api)
+                .delete(api_handlers::delete_item_api),
+        )
+        .route("/items/reorder", post(api_handlers::reorder_items_api))
+        .route("/items/restock-all", post(api_handlers::restock_items_api))
+        .route(
+            "/items/duplicates",
+            get(api_handlers::list_duplicate_items_api),
+        )
+        .route("/items/{id}/use", post(api_handlers::use_item_api))
+        .route("/items/{id}/qr.png", get(api_handlers::get_item_qr_api))
+        .route(
+            "/items/{id}/purchase",
+            post(api_handlers::purchase_item_api),
+        )
+        .route("/items/{id}/adjust", post(api_handlers::adjust_item_api))
+        .route("/items/{id}/discard", post(api_handlers::discard_item_api))
+        .route("/items/{id}/clone", post(api_handlers::clone_item_api))
+        .route("/items/{id}/merge", post(api_handlers::merge_item_api))
+        .route(
+            "/items/{id}/consumption-rule",
+            get(api_handlers::get_consumption_rule_api)
+                .put(api_handlers::set_consumption_rule_api)
+                .delete(api_handlers::delete_consumption_rule_api),
+        )
+        .route(
+            "/consumption-rules",
+            get(api_handlers::list_consumption_rules_api),
+        )
+        .route(
+            "/items/{id}/history",
+            get(api_handlers::get_item_history_api),
+        )
+        .route("/items/{id}/prices", get(api_handlers::get_item_prices_api))
+        .route(
+            "/items/{id}/batches",
+            get(api_handlers::list_item_batches_api).post(api_handlers::purchase_item_api),
+        )
+        .route(
+            "/items/{id}/batches/{batch_id}",
+            delete(api_handlers::discard_batch_api),
+        )
+        .route("/categories/tree", get(api_handlers::get_category_tree_api))
+        .route(
+            "/categories/reorder",
+            post(api_handlers::reorder_categories_api),
+        )
+        .route(
+            "/categories/{id}",
+            delete(api_handlers::delete_category_api),
+        )
+        .route(
+            "/categories/{id}/merge",
+            post(api_handlers::merge_category_api),
+        )
+        .route(
+            "/categories/{id}/collapsed",
+            put(api_handlers::set_category_collapsed_api),
+        )
+        .route(
+            "/stores",
+            get(api_handlers::list_stores_api).post(api_handlers::create_store_api),
+        )
+        .route("/stores/reorder", post(api_handlers::reorder_stores_api))
+        .route(
+            "/stores/{id}",
+            put(api_handlers::update_store_api).delete(api_handlers::delete_store_api),
+        )
+        .route(
+            "/attribute-fields",
+            get(api_handlers::list_attribute_fields_api)
+                .post(api_handlers::create_attribute_field_api),
+        )
+        .route(
+            "/attribute-fields/{id}",
+            delete(api_handlers::delete_attribute_field_api),
+        )
+        .route("/catalog", get(api_handlers::search_catalog_api))
+        .route(
+            "/catalog/{id}",
+            delete(api_handlers::delete_catalog_product_api),
+        )
+        .route("/notifications", get(api_handlers::get_notifications_api))
+        .route("/backup", get(api_handlers::get_backup_api))
+        .route("/import/grocy", post(api_handlers::import_grocy_api))
+        .route(
+            "/restore",
+            post(api_handlers::restore_backup_api).layer(DefaultBodyLimit::max(MAX_RESTORE_BYTES)),
+        )
+        .route(
+            "/preferences",
+            get(api_handlers::get_preferences_api).put(api_handlers::update_preferences_api),
+        )
+        .route("/stats/overview", get(api_handlers::get_stats_overview_api))
+        .route("/waste", get(api_handlers::get_waste_api))
+        .route("/history", get(api_handlers::get_history_api))
+        .route("/purchases", get(api_handlers::list_purchases_api))
+        .route(
+            "/purchases/{id}/receipt",
+            get(api_handlers::get_receipt_api)
+                .put(api_handlers::put_receipt_api)
+                .delete(api_handlers::delete_receipt_api)
+                .layer(shared_state.limits.upload_limit()),
+        )
+        .route("/activity", get(api_handlers::get_activity_api))
+        .route("/graphql", post(api_handlers::graphql_api))
+        .route(
+            "/sync",
+            get(api_handlers::get_sync_api).post(api_handlers::post_sync_api),
+        )
+        .route("/sessions", get(api_handlers::list_sessions_api))
+        .route(
+            "/sessions/revoke-others",
+            post(api_handlers::revoke_other_sessions_api),
+        )
+        .route("/sessions/{id}", delete(api_handlers::revoke_session_api))
+        .route(
+            "/share-links",
+            get(api_handlers::list_share_links_api).post(api_handlers::create_share_link_api),
+        )
+        .route(
+            "/share-links/{id}",
+            delete(api_handlers::revoke_share_link_api),
+        )
+        .route("/households", get(api_handlers::list_households_api))
+        .route(
+            "/households/current",
+            put(api_handlers::switch_household_api),
+        )
+        .route(
+            "/households/invitations",
+            get(api_handlers::list_my_invitations_api),
+        )
+        .route(
+            "/households/invitations/{id}",
+            delete(api_handlers::decline_household_invitation_api),
+        )
+        .route(
+            "/households/invitations/{id}/accept",
+            post(api_handlers::accept_household_invitation_api),
+        )
+        .route(
+            "/households/{id}/members",
+            get(api_handlers::list_household_members_api),
+        )
+        .route(
+            "/households/{id}/invitations",
+            get(api_handlers::list_household_invitations_api)
+                .post(api_handlers::invite_household_member_api),
+        )
+        .route(
+            "/households/{id}/invitations/{invitation}",
+            delete(api_handlers::cancel_household_invitation_api),
+        )
+        .route(
+            "/households/{id}/members/{user}",
+            patch(api_handlers::update_household_member_api)
+                .delete(api_handlers::remove_household_member_api),
+        )
+        .route(
+            "/notification-channels",
+            get(api_handlers::list_notification_channels_api)
+                .post(api_handlers::create_notification_channel_api),
+        )
+        .route(
+            "/notification-channels/{id}",
+            delete(api_handlers::delete_notification_channel_api),
+        )
+        .route(
+            "/notification-channels/{id}/test",
+            post(api_handlers::test_notification_channel_api),
+        )
+        .route(
+            "/notification-settings",
+            get(api_handlers::get_notification_settings_api)
+                .put(api_handlers::update_notification_settings_api),
+        )
+        .route(
+            "/tokens",
+            get(api_handlers::list_api_tokens_api).post(api_handlers::create_api_token_api),
+        )
+        .route("/tokens/{id}", delete(api_handlers::revoke_api_token_api))
+        .nest("/ha", home_assistant_routes)
+        .route(
+            "/assistant",
+            post(api_handlers::assistant_api).route_layer(middleware::from_fn_with_state(
+                shared_state.clone(),
+                home_assistant::authenticate,
+            )),
+        )
+        .route(
+            "/stocktakes",
+            get(api_handlers::list_stocktakes_api).post(api_handlers::start_stocktake_api),
+        )
+        .route(
+            "/stocktakes/{id}",
+            get(api_handlers::get_stocktake_api).delete(api_handlers::cancel_stocktake_api),
+        )
+        .route(
+            "/stocktakes/{id}/counts",
+            put(api_handlers::update_stocktake_counts_api),
+        )
+        .route(
+            "/stocktakes/{id}/complete",
+            post(api_handlers::complete_stocktake_api),
+        )
+        .route(
+            "/recipes",
+            get(api_handlers::list_recipes_api).post(api_handlers::create_recipe_api),
+        )
+        .route(
+            "/recipes/{id}",
+            get(api_handlers::get_recipe_api)
+                .put(api_handlers::update_recipe_api)
+                .delete(api_handlers::delete_recipe_api),
+        )
+        .route(
+            "/recipes/{id}/availability",
+            get(api_handlers::get_recipe_availability_api),
+        )
+        .route("/recipes/{id}/cook", post(api_handlers::cook_recipe_api))
+        .route(
+            "/meal-plan",
+            get(api_handlers::get_meal_plan_api).post(api_handlers::add_meal_plan_api),
+        )
+        .route(
+            "/meal-plan/{id}",
+            delete(api_handlers::delete_meal_plan_api),
+        )
+        .route(
+            "/meal-plan/{id}/cook",
+            post(api_handlers::cook_meal_plan_api),
+        )
+        .route("/shopping-list", get(api_handlers::get_shopping_list_api))
+        .route("/shopping-list/checkout", post(api_handlers::checkout_api))
+        .route(
+            "/shopping-list/export",
+            get(api_handlers::export_shopping_list_api),
+        );
+
+    // Routes that require authentication
+    let protected_web_routes = Router::new()
+        .route("/", get(web_handlers::root_handler))
+        .route("/dashboard", get(web_handlers::dashboard_handler))
+        .route("/waste", get(web_handlers::waste_handler))
+        .route("/activity", get(web_handlers::activity_handler))
+        .route("/purchases", get(web_handlers::purchases_handler))
+        .route(
+            "/purchases/{id}/receipt",
+            post(web_handlers::upload_receipt_handler).layer(shared_state.limits.upload_limit()),
+        )
+        .route(
+            "/purchases/{id}/receipt/delete",
+            post(web_handlers::delete_receipt_handler),
+        )
+        .route(
+            "/settings",
+            get(web_handlers::show_settings_form).post(web_handlers::settings_handler),
+        )
+        .route(
+            "/settings/sessions/{id}/revoke",
+            post(web_handlers::revoke_session_handler),
+        )
+        .route(
+            "/settings/sessions/revoke-others",
+            post(web_handlers::revoke_other_sessions_handler),
+        )
+        .route(
+            "/settings/share-links",
+            post(web_handlers::create_share_link_handler),
+        )
+        .route(
+            "/settings/share-links/{id}/revoke",
+            post(web_handlers::revoke_share_link_handler),
+        )
+        .route(
+            "/settings/notification-channels",
+            post(web_handlers::create_notification_channel_handler),
+        )
+        .route(
+            "/settings/notification-channels/{id}/delete",
+            post(web_handlers::delete_notification_channel_handler),
+        )
+        .route(
+            "/settings/notification-channels/{id}/test",
+            post(web_handlers::test_notification_channel_handler),
+        )
+        .route(
+            "/settings/notification-settings",
+            post(web_handlers::update_notification_settings_handler),
+        )
+        .route("/settings/stores", post(web_handlers::create_store_handler))
+        .route(
+            "/settings/attribute-fields",
+            post(web_handlers::create_attribute_field_handler),
+        )
+        .route(
+            "/settings/attribute-fields/{id}/delete",
+            post(web_handlers::delete_attribute_field_handler),
+        )
+        .route(
+            "/settings/stores/{id}/rename",
+            post(web_handlers::rename_store_handler),
+        )
+        .route(
+            "/settings/stores/{id}/up",
+            post(web_handlers::move_store_up_handler),
+        )
+        .route(
+            "/settings/stores/{id}/delete",
+            post(web_handlers::delete_store_handler),
+        )
+        .route("/households", get(web_handlers::households_handler))
+        .route(
+            "/households/current",
+            post(web_handlers::switch_household_handler),
+        )
+        .route(
+            "/households/{id}/leave",
+            post(web_handlers::leave_household_handler),
+        )
+        .route(
+            "/households/invitations",
+            post(web_handlers::invite_household_member_handler),
+        )
+        .route(
+            "/households/invitations/{id}/cancel",
+            post(web_handlers::cancel_household_invitation_handler),
+        )
+        .route(
+            "/households/invitations/{id}/accept",
+            post(web_handlers::accept_household_invitation_handler),
+        )
+        .route(
+            "/households/invitations/{id}/decline",
+            post(web_handlers::decline_household_invitation_handler),
+        )
+        .route(
+            "/households/members/{user}/role",
+            post(web_handlers::update_household_member_handler),
+        )
+        .route(
+            "/households/members/{user}/delete",
+            post(web_handlers::remove_household_member_handler),
+        )
+        .route(
+            "/settings/api-tokens",
+            post(web_handlers::create_api_token_handler),
+        )
+        .route(
+            "/settings/api-tokens/{id}/revoke",
+            post(web_handlers::revoke_api_token_handler),
+        )
+        .route("/logout", get(web_handlers::logout_handler))
+        .route("/categories", get(web_handlers::categories_handler))
+        .route(
+            "/categories/add",
+            get(web_handlers::show_add_category_form).post(web_handlers::add_category_handler),
+        )
+        .route(
+            "/categories/edit/{id}",
+            get(web_handlers::show_edit_category_form).post(web_handlers::edit_category_handler),
+        )
+        .route(
+            "/categories/delete/{id}",
+            get(web_handlers::show_delete_category_form)
+                .post(web_handlers::delete_category_handler),
+        )
+        .route(
+            "/categories/merge/{id}",
+            get(web_handlers::show_merge_category_form).post(web_handlers::merge_category_handler),
+        )
+        .route(
+            "/categories/{id}/toggle",
+            post(web_handlers::toggle_category_handler),
+        )
+        .route(
+            "/items/add",
+            get(web_handlers::show_add_item_form).post(web_handlers::add_item_handler),
+        )
+        .route("/items/{id}", get(web_handlers::show_item))
+        .route("/items/{id}/batches", post(web_handlers::add_batch_handler))
+        .route(
+            "/items/{id}/batches/{batch_id}/discard",
+            post(web_handlers::discard_batch_handler),
+        )
+        .route(
+            "/items/{id}/discard",
+            post(web_handlers::discard_item_handler),
+        )
+        .route(
+            "/items/{id}/consumption-rule",
+            post(web_handlers::set_consumption_rule_handler),
+        )
+        .route(
+            "/items/{id}/consumption-rule/delete",
+            post(web_handlers::delete_consumption_rule_handler),
+        )
+        .route(
+            "/items/edit/{id}",
+            get(web_handlers::show_edit_item_form).post(web_handlers::edit_item_handler),
+        )
+        .route(
+            "/items/delete/{id}",
+            post(web_handlers::delete_item_handler),
+        )
+        .route("/items/{id}/clone", post(web_handlers::clone_item_handler))
+        .route("/items/{id}/merge", post(web_handlers::merge_item_handler))
+        .route(
+            "/items/duplicates",
+            get(web_handlers::duplicate_items_handler),
+        )
+        .route("/items/use/{id}", post(web_handlers::use_item_handler))
+        .route(
+            "/items/restock-all",
+            post(web_handlers::restock_items_handler),
+        )
+        .route("/items/{id}/use", get(web_handlers::show_use_item_form))
+        .route("/labels", get(web_handlers::labels_handler))
+        .route("/labels/print", get(web_handlers::print_labels_handler))
+        .route("/labels.pdf", get(web_handlers::labels_pdf_handler))
+        .route(
+            "/items/purchase/{id}",
+            post(web_handlers::purchase_item_handler),
+        )
+        .route("/stocktakes", get(web_handlers::stocktakes_handler))
+        .route(
+            "/stocktakes/start",
+            post(web_handlers::start_stocktake_handler),
+        )
+        .route(
+            "/stocktakes/{id}",
+            get(web_handlers::show_stocktake).post(web_handlers::save_stocktake_handler),
+        )
+        .route(
+            "/stocktakes/{id}/cancel",
+            post(web_handlers::cancel_stocktake_handler),
+        )
+        .route("/recipes", get(web_handlers::recipes_handler))
+        .route(
+            "/recipes/add",
+            get(web_handlers::show_add_recipe_form).post(web_handlers::add_recipe_handler),
+        )
+        .route(
+            "/recipes/edit/{id}",
+            get(web_handlers::show_edit_recipe_form).post(web_handlers::edit_recipe_handler),
+        )
+        .route(
+            "/recipes/delete/{id}",
+            post(web_handlers::delete_recipe_handler),
+        )
+        .route(
+            "/recipes/cook/{id}",
+            post(web_handlers::cook_recipe_handler),
+        )
+        .route(
+            "/meal-plan",
+            get(web_handlers::meal_plan_handler).post(web_handlers::add_meal_plan_handler),
+        )
+        .route(
+            "/meal-plan/delete/{id}",
+            post(web_handlers::delete_meal_plan_handler),
+        )
+        .route(
+            "/meal-plan/cook/{id}",
+            post(web_handlers::cook_meal_plan_handler),
+        )
+        .route("/shopping-list", get(web_handlers::shopping_list_handler))
+        .route(
+            "/shopping-list/checkout",
+            get(web_handlers::checkout_page_handler).post(web_handlers::checkout_handler),
+        )
+        .route(
+            "/shopping-list/print",
+            get(web_handlers::print_shopping_list_handler),
+        )
+        .layer(middleware::from_fn(households::guard))
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            auth::require_login,
+        ));
+
+    // Public routes that do not require authentication
+    let public_web_routes = Router::new()
+        .route(
+            "/signup",
+            get(web_handlers::show_signup_form).post(web_handlers::signup_handler),
+        )
+        .route(
+            "/login",
+            get(web_handlers::show_login_form).post(web_handlers::login_handler),
+        );
+
+    let web_routes = Router::new()
+        .merge(protected_web_routes)
+        .merge(public_web_routes);
+
+    // Static files don't need the session, so it is only looked up for these
+    let load_session = middleware::from_fn_with_state(shared_state.clone(), auth::load_session);
+    // Inside the error page, so a page that times out still gets one
+    let timeout = middleware::from_fn_with_state(shared_state.clone(), limits::timeout);
+    // Before the session is looked up, as the database may be busy
+    let maintenance = middleware::from_fn_with_state(shared_state.clone(), maintenance::guard);
+    let web_routes = web_routes
+        .layer(load_session.clone())
+        .layer(timeout.clone())
+        .layer(maintenance.clone())
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            errors::html_errors,
+        ));
+    // Public pages for share links: no session, but error pages all the same
+    let share_routes = Router::new()
+        .route("/{token}", get(web_handlers::share_handler))
+        .layer(timeout.clone())
+        .layer(maintenance.clone())
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            errors::html_errors,
+        ));
+    // Fetched by calendar apps and feed readers, which only show that it failed
+    let calendar_routes = Router::new()
+        .route("/{file}", get(web_handlers::calendar_handler))
+        .layer(timeout.clone())
+        .layer(maintenance.clone());
+    let feed_routes = Router::new()
+        .route(
+            "/{token}/restock.atom",
+            get(web_handlers::restock_feed_handler),
+        )
+        .layer(timeout.clone())
+        .layer(maintenance.clone());
+    let mut api_routes = api_routes
+        .layer(middleware::from_fn(households::guard))
+        .layer(load_session)
+        .layer(timeout)
+        .layer(maintenance);
+    // Outside the session check, so preflight requests are answered without a
+    // login and errors still carry the CORS headers
+    if let Some(cors) = shared_state.cors.layer() {
+        api_routes = api_routes.layer(cors);
+    }
+
+    let base_path = shared_state.base_path.clone();
+    let home = format!("{}/web", base_path);
+    let app_routes = Router::new()
+        .route("/", get(move || async move { Redirect::permanent(&home) }))
+        .nest("/web", web_routes)
+        .nest("/api", api_routes)
+        .nest("/share", share_routes)
+        .nest("/calendar", calendar_routes)
+        .nest("/feeds", feed_routes)
+        .route("/static/{*path}", get(assets::static_file))
+        .route("/custom.css", get(branding::custom_css))
+        .route("/manifest.json", get(pwa::manifest))
+        .route("/service-worker.js", get(pwa::service_worker))
+        .route("/offline", get(pwa::offline));
+
+    if base_path.is_empty() {
+        app_routes
+    } else {
+        Router::new().nest(&base_path, app_routes)
+    }
+    .route("/health", get(health::live))
+    .route("/health/live", get(health::live))
+    .route("/health/ready", get(health::ready))
+    .layer(middleware::from_fn(reporting::request_scope))
+    .layer(middleware::from_fn_with_state(
+        shared_state.clone(),
+        proxy::resolve,
+    ))
+    .layer(shared_state.limits.body_limit())
+    .with_state(shared_state)
+    .fallback(|| async { (StatusCode::NOT_FOUND, "Route Not Found") })
+    // gzip or brotli, whichever the browser takes; skips tiny bodies and images
+    .layer(CompressionLayer::new())
+    .layer(TraceLayer::new_for_http().make_span_with(request_span))
+    .layer(middleware::from_fn(strip_trailing_slash))
+    .layer(PropagateRequestIdLayer::x_request_id())
+    .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
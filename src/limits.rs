@@ -0,0 +1,75 @@
+//! Limits on how long a request may take and how large its body may be, so a
+//! slow client or an oversized upload can't hold on to a worker and one of
+//! the few database connections.
+
+use crate::{AppState, config::Config, errors::AppError};
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, State},
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RequestLimits {
+    /// Time to receive the request and produce the response headers.
+    pub request_timeout: Duration,
+    /// Largest JSON or form body.
+    pub max_body_bytes: usize,
+    /// Largest body of routes taking photos.
+    pub max_upload_bytes: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits {
+            request_timeout: Duration::from_secs(30),
+            max_body_bytes: 1024 * 1024,
+            max_upload_bytes: 20 * 1024 * 1024,
+        }
+    }
+}
+
+impl RequestLimits {
+    pub fn from_config(config: &Config) -> RequestLimits {
+        RequestLimits {
+            request_timeout: Duration::from_secs(config.request_timeout_secs),
+            max_body_bytes: kib(config.max_body_kb),
+            max_upload_bytes: kib(config.max_upload_mb.saturating_mul(1024)),
+        }
+    }
+
+    /// Cap on the bodies of every route.
+    pub fn body_limit(&self) -> DefaultBodyLimit {
+        DefaultBodyLimit::max(self.max_body_bytes)
+    }
+
+    /// Raises the cap for a route taking photos.
+    pub fn upload_limit(&self) -> DefaultBodyLimit {
+        DefaultBodyLimit::max(self.max_upload_bytes)
+    }
+}
+
+fn kib(count: u64) -> usize {
+    usize::try_from(count.saturating_mul(1024)).unwrap_or(usize::MAX)
+}
+
+/// Answers `408 Request Timeout` for a request that isn't done in time. The
+/// handler is dropped, which rolls back any transaction it had open.
+pub async fn timeout(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let limit = state.limits.request_timeout;
+    match tokio::time::timeout(limit, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!("request timed out after {:?}", limit);
+            AppError::Timeout.into_response()
+        }
+    }
+}
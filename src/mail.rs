@@ -0,0 +1,95 @@
+//! Outgoing email, such as the monthly summary: through an SMTP server, or
+//! written to a directory to see what would have been sent. Every email has
+//! an HTML part and a plain text one for clients that don't show HTML.
+
+use crate::config::{MailSettings, MailTransport};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+/// Why an email couldn't be sent.
+#[derive(Debug)]
+pub struct MailError(String);
+
+impl std::fmt::Display for MailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for MailError {}
+
+impl From<lettre::error::Error> for MailError {
+    fn from(e: lettre::error::Error) -> Self {
+        MailError(e.to_string())
+    }
+}
+
+impl From<lettre::address::AddressError> for MailError {
+    fn from(e: lettre::address::AddressError) -> Self {
+        MailError(e.to_string())
+    }
+}
+
+/// An email ready to go out.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+#[derive(Clone)]
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    Dir(AsyncFileTransport<Tokio1Executor>),
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    transport: Transport,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn from_settings(settings: &MailSettings) -> Result<Mailer, MailError> {
+        let transport = match &settings.transport {
+            MailTransport::Smtp(url) => Transport::Smtp(
+                AsyncSmtpTransport::<Tokio1Executor>::from_url(url)
+                    // Without the URL, which holds the password
+                    .map_err(|e| MailError(format!("invalid SMTP_URL: {e}")))?
+                    .build(),
+            ),
+            MailTransport::Dir(dir) => {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| MailError(format!("cannot create {dir}: {e}")))?;
+                Transport::Dir(AsyncFileTransport::new(dir))
+            }
+        };
+        Ok(Mailer {
+            transport,
+            from: settings.from.parse()?,
+        })
+    }
+
+    /// Sends `email` to `name <address>`.
+    pub async fn send(&self, name: &str, address: &str, email: Email) -> Result<(), MailError> {
+        let to = Mailbox::new(Some(name.to_string()), address.parse()?);
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject)
+            .multipart(MultiPart::alternative_plain_html(email.text, email.html))?;
+        match &self.transport {
+            Transport::Smtp(transport) => transport
+                .send(message)
+                .await
+                .map(drop)
+                .map_err(|e| MailError(e.to_string())),
+            Transport::Dir(transport) => transport
+                .send(message)
+                .await
+                .map(drop)
+                .map_err(|e| MailError(e.to_string())),
+        }
+    }
+}
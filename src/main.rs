@@ -1,197 +1,187 @@
-use axum::body::{Body, HttpBody};
-use axum::extract::State;
-use axum::http::{Request, StatusCode, Uri};
-use axum::middleware::{self, Next};
-use axum::response::IntoResponse;
-use axum::response::Redirect;
-use axum::routing::{delete, get, post, put};
-use axum::{Router, serve};
+use axum::serve;
+use bcrypt::{DEFAULT_COST, hash};
+use clap::{Parser, Subcommand, ValueEnum};
 use dotenvy::dotenv;
+use household_inventory::config::{Config, CookieSecure, Listen, LogFormat};
+use household_inventory::{
+    AppState, auth, build_app, consumption, db, demo, mail, maintenance, mqtt, notify, reporting,
+    seed, summary, tls,
+};
 use sqlx::PgPool;
-use std::{env, net::SocketAddr, sync::Arc};
-use tera::Tera;
+use std::io::{BufRead, IsTerminal};
+use std::{error::Error, fs, net::SocketAddr, sync::Arc, time::Duration};
 use tokio::net::TcpListener;
-use tower_http::services::ServeDir;
-use tower_http::trace::TraceLayer;
+use tracing_subscriber::fmt::{self, writer::BoxMakeWriter};
 use tracing_subscriber::{filter::EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
 
-mod db;
-mod errors;
-mod handlers;
-mod models;
-
-use handlers::{api_handlers, web_handlers};
-
-#[derive(Clone)]
-pub struct AppState {
-    pub tera: Arc<Tera>,
-    pub db_pool: PgPool,
-    pub base_path: String,
+/// Household inventory server and administration commands. Settings come from
+/// the same config file and environment variables as the server.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-async fn strip_trailing_slash(req: Request<Body>, next: Next) -> impl IntoResponse {
-    let uri = req.uri();
-    let path = uri.path();
-
-    if path.len() > 1 && path.ends_with('/') {
-        // remove the trailing slash and redirect
-        let new_path = path.trim_end_matches('/');
-        let new_uri_string = if let Some(query) = uri.query() {
-            format!("{}?{}", new_path, query)
-        } else {
-            new_path.to_string()
-        };
-
-        // Use a permanent redirect
-        return Redirect::permanent(&new_uri_string).into_response();
-    }
-
-    // If no trailing slash, just continue
-    next.run(req).await
-}
-
-// Auth guard for web routes
-async fn auth(
-    State(state): State<Arc<AppState>>,
-    req: axum::http::Request<Body>,
-    next: Next,
-) -> impl IntoResponse {
-    let base_path = &state.base_path;
-    let login_path = format!("{}/web/login", base_path);
-
-    let is_auth = req
-        .headers()
-        .get("cookie")
-        .and_then(|h| h.to_str().ok())
-        .map_or(false, |s| s.contains("session="));
-
-    if is_auth {
-        next.run(req).await
-    } else {
-        Redirect::to(&login_path).into_response()
-    }
+#[derive(Subcommand)]
+enum Command {
+    /// Run the web server (the default)
+    Serve,
+    /// Apply pending database migrations and exit
+    Migrate,
+    /// Create an account; the password is prompted for, or read from stdin
+    CreateUser {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        email: String,
+    },
+    /// Set a new password for an account
+    ResetPassword { email: String },
+    /// Write an account's backup as JSON, the same as `GET /api/backup`
+    Export {
+        #[arg(long = "user", value_name = "EMAIL")]
+        email: String,
+        /// File to write to instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Switch maintenance mode on or off for every server on the database
+    Maintenance { state: Switch },
 }
 
-async fn health_check() -> impl IntoResponse {
-    (StatusCode::OK, "OK")
+#[derive(Clone, Copy, ValueEnum)]
+enum Switch {
+    On,
+    Off,
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<(), Box<dyn Error>> {
     dotenv().ok();
+    let cli = Cli::parse();
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Configuration error: {e}");
+            std::process::exit(1);
+        }
+    };
 
+    let command = cli.command.unwrap_or(Command::Serve);
+    // The server logs to stdout; the other commands print their results there,
+    // so their logs go to stderr
+    let log_writer = || match command {
+        Command::Serve => BoxMakeWriter::new(std::io::stdout),
+        _ => BoxMakeWriter::new(std::io::stderr),
+    };
+    // LOG_FORMAT=json writes one JSON object per line, for log collectors
+    let json_logs = config.log_format == LogFormat::Json;
     tracing_subscriber::registry()
         .with(
             EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "household_inventory=info,tower_http=info".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(json_logs.then(|| fmt::layer().json().with_writer(log_writer())))
+        .with((!json_logs).then(|| fmt::layer().with_writer(log_writer())))
         .init();
+    let _reporting = reporting::init(&config);
 
-    let tera = Tera::new("templates/**/*")?;
-    let db_pool = db::create_pool().await?;
+    let pool = db::create_pool(
+        &config.database_url,
+        &db::PoolSettings::from_config(&config),
+    )
+    .await?;
+    match command {
+        Command::Serve => serve_app(config, pool).await,
+        Command::Migrate => {
+            migrate(&config, &pool, true).await?;
+            println!("Database is up to date");
+            Ok(())
+        }
+        Command::CreateUser { name, email } => create_user(&config, &pool, &name, &email).await,
+        Command::ResetPassword { email } => {
+            let password = read_new_password()?;
+            let hashed = hash(&password, DEFAULT_COST)?;
+            if !db::update_password(&pool, &email, &hashed).await? {
+                return Err(format!("no account with email {email}").into());
+            }
+            println!("Password changed for {email}");
+            Ok(())
+        }
+        Command::Export { email, output } => {
+            let account = db::get_account_by_email(&pool, &email)
+                .await?
+                .ok_or_else(|| format!("no account with email {email}"))?;
+            let backup = db::export_backup(&pool, account.id).await?;
+            let json = serde_json::to_string_pretty(&backup)?;
+            match output {
+                Some(path) => fs::write(path, json)?,
+                None => println!("{json}"),
+            }
+            Ok(())
+        }
+        Command::Maintenance { state } => {
+            let on = matches!(state, Switch::On);
+            db::set_maintenance_mode(&pool, on).await?;
+            println!(
+                "Maintenance mode {}; servers notice within {} seconds",
+                if on { "on" } else { "off" },
+                maintenance::REFRESH_PERIOD.as_secs()
+            );
+            Ok(())
+        }
+    }
+}
 
-    let run_on_subpath =
-        env::var("RUN_ON_SUBPATH").unwrap_or_else(|_| "false".to_string()) == "true";
-    let base_path = if run_on_subpath {
-        "/inventory".to_string()
+/// Applies migrations unless `RUN_MIGRATIONS=false`; `force` ignores that
+/// setting, for the `migrate` command.
+async fn migrate(config: &Config, pool: &PgPool, force: bool) -> Result<(), Box<dyn Error>> {
+    if let Some(version) = config.migrations_baseline {
+        db::baseline_migrations(pool, version).await?;
+        tracing::info!("Marked migrations up to {} as applied", version);
+    }
+    if config.run_migrations || force {
+        db::run_migrations(pool).await?;
     } else {
-        "".to_string()
-    };
-
-    let shared_state = Arc::new(AppState {
-        tera: Arc::new(tera),
-        db_pool,
-        base_path,
-    });
-
-    let static_service = ServeDir::new("static");
-
-    let api_routes = Router::new()
-        .route(
-            "/items",
-            get(api_handlers::list_items_api).post(api_handlers::create_item_api),
-        )
-        .route(
-            "/items/{id}",
-            get(api_handlers::get_item_api)
-                .put(api_handlers::update_item_api)
-                .delete(api_handlers::delete_item_api),
-        )
-        .route("/notifications", get(api_handlers::get_notifications_api));
-
-    // Routes that require authentication
-    let protected_web_routes = Router::new()
-        .route("/", get(web_handlers::root_handler))
-        .route("/logout", get(web_handlers::logout_handler))
-        .route(
-            "/categories/add",
-            get(web_handlers::show_add_category_form).post(web_handlers::add_category_handler),
-        )
-        .route(
-            "/items/add",
-            get(web_handlers::show_add_item_form).post(web_handlers::add_item_handler),
-        )
-        .route(
-            "/items/edit/{id}",
-            get(web_handlers::show_edit_item_form).post(web_handlers::edit_item_handler),
-        )
-        .route(
-            "/items/delete/{id}",
-            post(web_handlers::delete_item_handler),
-        )
-        .route("/items/use/{id}", post(web_handlers::use_item_handler))
-        .route(
-            "/items/purchase/{id}",
-            post(web_handlers::purchase_item_handler),
-        )
-        .layer(middleware::from_fn_with_state(shared_state.clone(), auth));
+        tracing::info!("RUN_MIGRATIONS=false, not applying database migrations");
+    }
+    Ok(())
+}
 
-    // Public routes that do not require authentication
-    let public_web_routes = Router::new()
-        .route(
-            "/signup",
-            get(web_handlers::show_signup_form).post(web_handlers::signup_handler),
-        )
-        .route(
-            "/login",
-            get(web_handlers::show_login_form).post(web_handlers::login_handler),
+async fn serve_app(config: Config, pool: PgPool) -> Result<(), Box<dyn Error>> {
+    warn_if_insecure(&config);
+    migrate(&config, &pool, false).await?;
+    auth::spawn_session_cleanup(pool.clone());
+    notify::spawn_dispatcher(pool.clone());
+    consumption::spawn_runner(pool.clone());
+    if let Some(mqtt) = &config.mqtt {
+        mqtt::spawn_publisher(pool.clone(), mqtt.clone());
+    }
+    db::spawn_pool_metrics(pool.clone());
+    let shared_state = Arc::new(AppState::from_config(&config, pool.clone())?);
+    if let Some(mail) = &config.mail {
+        let mailer = mail::Mailer::from_settings(mail)?;
+        summary::spawn_mailer(pool.clone(), shared_state.tera.clone(), mailer);
+    }
+    maintenance::spawn_refresh(pool.clone(), shared_state.maintenance.clone()).await?;
+    if config.demo_mode {
+        demo::reset(&pool).await.map_err(|e| e as Box<dyn Error>)?;
+        demo::spawn_resets(
+            pool,
+            shared_state.cache.clone(),
+            Duration::from_secs(config.demo_reset_minutes * 60),
+        );
+        tracing::info!(
+            "Demo mode: log in as {} / {}, reset every {} minutes",
+            demo::DEMO_EMAIL,
+            demo::DEMO_PASSWORD,
+            config.demo_reset_minutes
         );
-
-    let web_routes = Router::new()
-        .merge(protected_web_routes)
-        .merge(public_web_routes);
-
-    let nested = env::var("RUN_ON_SUBPATH").unwrap_or_else(|_| "false".to_string()) == "true";
-
-    let app = if nested {
-        Router::new().nest(
-            "/inventory",
-            Router::new()
-                .route("/", get(|| async { Redirect::permanent("/inventory/web") }))
-                .nest("/web", web_routes)
-                .nest("/api", api_routes)
-                .nest_service("/static", static_service),
-        )
-    } else {
-        Router::new()
-            .route("/", get(|| async { Redirect::permanent("/web") }))
-            .nest("/web", web_routes)
-            .nest("/api", api_routes)
-            .nest_service("/static", static_service)
     }
-    .route("/health", get(health_check))
-    .with_state(shared_state)
-    .fallback(|| async { (StatusCode::NOT_FOUND, "Route Not Found") })
-    .layer(TraceLayer::new_for_http())
-    .layer(middleware::from_fn(strip_trailing_slash));
 
-    let port: u16 = env::var("APP_PORT")
-        .unwrap_or_else(|_| "3000".into())
-        .parse()?;
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-
-    let listener = TcpListener::bind(addr).await?;
+    let app = build_app(shared_state);
 
     println!(
         r#"
@@ -207,12 +197,97 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         "#
     );
-    if !nested {
-        tracing::info!("listening on {}", addr);
-    } else {
-        tracing::info!("listening on {}/inventory", addr);
+    let addr = match &config.listen {
+        Listen::Tcp(addr) => *addr,
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            let listener = household_inventory::unix_socket::bind(path, config.socket_mode)?;
+            tracing::info!("listening on {}{}", config.listen, config.base_path);
+            serve(listener, app).await?;
+            return Ok(());
+        }
+        #[cfg(not(unix))]
+        Listen::Unix(_) => return Err("Unix sockets are not supported on this platform".into()),
+    };
+    let listener = TcpListener::bind(addr).await?;
+    // Connection info gives the client address recorded with each login
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let Some(tls_files) = &config.tls else {
+        tracing::info!("listening on {}{}", addr, config.base_path);
+        serve(listener, app).await?;
+        return Ok(());
+    };
+
+    let tls_config = tls::load(tls_files).await?;
+    if let Some(port) = config.http_redirect_port {
+        let redirect_addr = SocketAddr::new(addr.ip(), port);
+        let redirect_listener = TcpListener::bind(redirect_addr).await?;
+        tracing::info!("redirecting http://{} to HTTPS", redirect_addr);
+        tokio::spawn(async move {
+            let redirect = tls::redirect_to_https(addr.port());
+            if let Err(e) = serve(redirect_listener, redirect).await {
+                tracing::error!("HTTP redirect stopped: {}", e);
+            }
+        });
+    }
+    tracing::info!("listening on https://{}{}", addr, config.base_path);
+    axum_server::from_tcp_rustls(listener.into_std()?, tls_config)
+        .serve(app)
+        .await?;
+
+    Ok(())
+}
+
+/// Logs a warning when session cookies may travel over plain HTTP, where
+/// anyone on the network can read them and take over the login.
+fn warn_if_insecure(config: &Config) {
+    let behind_proxy =
+        !config.trusted_proxies.is_empty() || matches!(config.listen, Listen::Unix(_));
+    match config.cookie_secure {
+        CookieSecure::Never => {
+            tracing::warn!("COOKIE_SECURE=never: session cookies are sent over plain HTTP too")
+        }
+        CookieSecure::Auto if config.tls.is_none() && !behind_proxy => tracing::warn!(
+            "Serving plain HTTP: session cookies are not marked Secure. Set TLS_CERT and \
+             TLS_KEY, or TRUSTED_PROXIES for the HTTPS proxy in front of the app"
+        ),
+        _ => {}
     }
-    serve(listener, app).await?;
+}
 
+async fn create_user(
+    config: &Config,
+    pool: &PgPool,
+    name: &str,
+    email: &str,
+) -> Result<(), Box<dyn Error>> {
+    if db::get_account_by_email(pool, email).await?.is_some() {
+        return Err(format!("an account with email {email} already exists").into());
+    }
+    let password = read_new_password()?;
+    let account = db::create_account(pool, name, email, &hash(&password, DEFAULT_COST)?).await?;
+    let category_seed = seed::load_category_seed(config.category_seed_file.as_deref())?;
+    db::seed_categories(pool, account.id, &category_seed).await?;
+    println!("Created account {} for {}", account.id, email);
     Ok(())
 }
+
+/// Prompts twice on a terminal; otherwise reads one line from stdin, so
+/// scripts can pipe the password in.
+fn read_new_password() -> Result<String, Box<dyn Error>> {
+    let password = if std::io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("New password: ")?;
+        if rpassword::prompt_password("Repeat password: ")? != password {
+            return Err("passwords do not match".into());
+        }
+        password
+    } else {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    if password.is_empty() {
+        return Err("the password must not be empty".into());
+    }
+    Ok(password)
+}
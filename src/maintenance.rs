@@ -0,0 +1,73 @@
+//! Maintenance mode, for backups and migrations: pages answer with a notice
+//! and the API with `503`, while `/health` and the static files keep being
+//! served. It is on when `maintenance_mode` is set in the config, or while
+//! `household-inventory maintenance on` has switched it on in the database.
+
+use crate::{AppState, config::Config, db, errors::AppError, scheduler};
+use axum::{
+    body::Body,
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// How often each instance looks at the switch in the database.
+pub const REFRESH_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Default)]
+pub struct Maintenance {
+    /// Set by the config; can't be switched off without a restart.
+    forced: bool,
+    /// The switch in the database, as last seen.
+    switched_on: Arc<AtomicBool>,
+}
+
+impl Maintenance {
+    pub fn from_config(config: &Config) -> Maintenance {
+        Maintenance {
+            forced: config.maintenance_mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.forced || self.switched_on.load(Ordering::Relaxed)
+    }
+
+    /// Records the state of the switch in the database.
+    pub fn set(&self, on: bool) {
+        let was_on = self.switched_on.swap(on, Ordering::Relaxed);
+        if was_on != on {
+            tracing::info!("Maintenance mode {}", if on { "on" } else { "off" });
+        }
+    }
+}
+
+/// Reads the switch in the database now and every `REFRESH_PERIOD`. Until the
+/// database answers, the last state seen stays in effect.
+pub async fn spawn_refresh(pool: PgPool, maintenance: Maintenance) -> Result<(), sqlx::Error> {
+    maintenance.set(db::get_maintenance_mode(&pool).await?);
+    scheduler::spawn_every("maintenance refresh", REFRESH_PERIOD, move || {
+        let pool = pool.clone();
+        let maintenance = maintenance.clone();
+        async move {
+            maintenance.set(db::get_maintenance_mode(&pool).await?);
+            Ok(())
+        }
+    });
+    Ok(())
+}
+
+/// Answers every request with `AppError::Maintenance` while maintenance mode
+/// is on; web routes render it as a page.
+pub async fn guard(State(state): State<Arc<AppState>>, req: Request<Body>, next: Next) -> Response {
+    if state.maintenance.is_on() {
+        return AppError::Maintenance.into_response();
+    }
+    next.run(req).await
+}
@@ -1,13 +1,33 @@
+use crate::{
+    attributes::MAX_ATTRIBUTE_NAME_LEN,
+    categories::MAX_ICON_LEN,
+    filters,
+    pagination::Cursor,
+    validation::{
+        MAX_BARCODE_LEN, MAX_NOTES_LEN, MAX_TEXT_LEN, MAX_UNIT_LEN, MAX_URL_LEN, Validate,
+        ValidationErrors,
+    },
+};
 use serde::{Deserialize, Deserializer, Serialize, de};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
 use std::str::FromStr;
-use time::OffsetDateTime;
+use time::{Date, OffsetDateTime, Time};
+
+// Dates are exchanged as `YYYY-MM-DD` strings
+time::serde::format_description!(date_format, Date, "[year]-[month]-[day]");
+// Times of day as `HH:MM`
+time::serde::format_description!(clock_time, Time, "[hour]:[minute]");
 
 #[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct Category {
     pub id: i32,
     pub name: String,
     pub color: String,
+    /// Top-level category this one is nested under.
+    pub parent_id: Option<i32>,
+    /// Emoji shown next to the name.
+    pub icon: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
@@ -16,19 +36,88 @@ pub struct Item {
     pub name: String,
     pub quantity: i32,
     pub restock_threshold: i32,
+    pub restock_to: Option<i32>,
+    /// Unit of `quantity`, e.g. "kg"; `None` means pieces.
+    pub unit: Option<String>,
+    /// Where the item is kept, e.g. "Spiżarnia".
+    pub location: Option<String>,
+    /// Barcode printed on the package, e.g. an EAN.
+    pub barcode: Option<String>,
+    /// Free-form remarks, e.g. "the blue cap version, not the green one".
+    pub notes: Option<String>,
+    /// Store the item is usually bought in; groups the shopping list.
+    pub preferred_store_id: Option<i32>,
+    /// Pack size the item comes in, e.g. 10 for eggs; the ± buttons move by it.
+    pub quantity_step: i32,
+    /// Values of the user's own fields, by field name, e.g. `{"Marka": "Łaciate"}`.
+    #[sqlx(json)]
+    pub attributes: Attributes,
+    /// How many to buy to reach the restock target; pre-fills the purchase form.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub suggested_purchase: i32,
     #[sqlx(flatten)]
     pub category: Option<Category>,
+    /// Bumped on every change; updates must send the version they were based on.
+    pub version: i32,
+    #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
     pub updated_at: OffsetDateTime,
 }
 
+/// Counts and newest changes of a user's items and categories, which move
+/// whenever the item list does. Hashed into ETags instead of the list itself.
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub struct ItemsFingerprint {
+    pub items: i64,
+    pub items_changed_at: Option<OffsetDateTime>,
+    pub categories: i64,
+    pub categories_changed_at: Option<OffsetDateTime>,
+}
+
+impl Item {
+    /// Quantity that tops an item up to `restock_to` (or to its threshold when no
+    /// target is set), in whole packs of `quantity_step`. Never less than one
+    /// pack, so it can be used as a form default.
+    pub fn suggested_purchase(
+        quantity: i32,
+        restock_threshold: i32,
+        restock_to: Option<i32>,
+        quantity_step: i32,
+    ) -> i32 {
+        let target = restock_to
+            .unwrap_or(restock_threshold)
+            .max(restock_threshold);
+        let step = quantity_step.max(1);
+        let missing = (target - quantity).max(1);
+        let packs = missing / step + i32::from(missing % step != 0);
+        packs.saturating_mul(step)
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct CategoryWithItems {
     pub id: i32,
     pub name: String,
     pub color: String,
-    pub text_color: String,
+    pub parent_id: Option<i32>,
+    pub icon: Option<String>,
+    pub items: Vec<Item>,
+    /// Items in this category and all its subcategories.
+    pub total_items: usize,
+}
+
+/// What the item list page shows, from `db::get_dashboard_data`.
+#[derive(Debug)]
+pub struct DashboardData {
+    pub user: Option<Account>,
+    /// Filtered and sorted as asked.
     pub items: Vec<Item>,
+    pub categories: Vec<Category>,
+    /// Items below their restock threshold, by name.
+    pub to_restock: Vec<Item>,
+    pub expiring: Vec<ExpiringBatch>,
 }
 
 #[derive(Debug, Serialize)]
@@ -37,24 +126,256 @@ pub struct GroupedItems {
     pub uncategorized: Vec<Item>,
 }
 
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct CategoryWithCount {
+    pub id: i32,
+    pub name: String,
+    pub color: String,
+    pub parent_id: Option<i32>,
+    pub icon: Option<String>,
+    pub item_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryTreeNode {
+    pub id: i32,
+    pub name: String,
+    pub color: String,
+    pub icon: Option<String>,
+    pub item_count: i64,
+    /// Items in this category and all its subcategories.
+    pub total_item_count: i64,
+    pub children: Vec<CategoryTreeNode>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateCategoryPayload {
     pub name: String,
     pub color: String,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub parent_id: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub icon: Option<String>,
+}
+
+impl Validate for CreateCategoryPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("name", &self.name, MAX_TEXT_LEN);
+        errors.color("color", &self.color);
+        errors.optional_text("icon", self.icon.as_deref(), MAX_ICON_LEN);
+        errors.into_result()
+    }
+}
+
+/// New manual order for `POST /api/{items,categories,stores}/reorder`.
+#[derive(Debug, Deserialize)]
+pub struct ReorderPayload {
+    pub ids: Vec<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCategoryPayload {
+    pub name: Option<String>,
+    pub color: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub parent_id: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub icon: Option<String>,
+}
+
+impl Validate for UpdateCategoryPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(name) = &self.name {
+            errors.text("name", name, MAX_TEXT_LEN);
+        }
+        if let Some(color) = &self.color {
+            errors.color("color", color);
+        }
+        errors.optional_text("icon", self.icon.as_deref(), MAX_ICON_LEN);
+        errors.into_result()
+    }
 }
 
-// Custom deserializer for optional fields from form data
+// Custom deserializer for optional fields from form data.
+// Also accepts plain JSON values, so API clients can send numbers or null.
 fn deserialize_empty_string_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
 where
     D: Deserializer<'de>,
-    T: FromStr,
+    T: FromStr + Deserialize<'de>,
     T::Err: std::fmt::Display,
 {
-    let s = String::deserialize(deserializer)?;
-    if s.is_empty() {
-        Ok(None)
-    } else {
-        s.parse::<T>().map(Some).map_err(de::Error::custom)
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawValue<T> {
+        // Tried first, so empty strings are caught even when `T` is `String`
+        Text(String),
+        Value(T),
+    }
+
+    match Option::<RawValue<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(RawValue::Value(value)) => Ok(Some(value)),
+        Some(RawValue::Text(s)) if s.is_empty() => Ok(None),
+        Some(RawValue::Text(s)) => s.parse::<T>().map(Some).map_err(de::Error::custom),
+    }
+}
+
+// Same as above for `YYYY-MM-DD` dates, which have no `FromStr` impl.
+fn deserialize_optional_date<'de, D>(deserializer: D) -> Result<Option<Date>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(s) if s.is_empty() => Ok(None),
+        Some(s) => Date::parse(
+            &s,
+            time::macros::format_description!("[year]-[month]-[day]"),
+        )
+        .map(Some)
+        .map_err(de::Error::custom),
+    }
+}
+
+/// Used both as the web form body and as the `?reassign_to=` API query.
+#[derive(Debug, Deserialize)]
+pub struct DeleteCategoryPayload {
+    /// Category that receives the items of the deleted one; `None` leaves them uncategorized.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub reassign_to: Option<i32>,
+}
+
+/// What happens to the items of a deleted category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CategoryDeletePolicy {
+    Uncategorize,
+    ReassignTo(i32),
+}
+
+impl From<DeleteCategoryPayload> for CategoryDeletePolicy {
+    fn from(payload: DeleteCategoryPayload) -> Self {
+        match payload.reassign_to {
+            Some(target_id) => CategoryDeletePolicy::ReassignTo(target_id),
+            None => CategoryDeletePolicy::Uncategorize,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DeleteCategoryOutcome {
+    Deleted {
+        moved_items: u64,
+    },
+    NotFound,
+    /// The reassignment target is missing or is the category being deleted.
+    InvalidTarget,
+}
+
+/// Used both as the web form body and as the `?into=` API query.
+#[derive(Debug, Deserialize)]
+pub struct MergeCategoryPayload {
+    /// Category that receives the items (and subcategories) of the merged one.
+    pub into: i32,
+}
+
+#[derive(Debug)]
+pub enum MergeCategoryOutcome {
+    Merged {
+        moved_items: u64,
+    },
+    NotFound,
+    /// The target is missing or is the category being merged.
+    InvalidTarget,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeItemPayload {
+    /// Item that receives the stock and history of the merged one.
+    pub into: i32,
+}
+
+#[derive(Debug)]
+pub enum MergeItemOutcome {
+    /// The target as it is after the merge.
+    Merged(Box<Item>),
+    NotFound,
+    /// The target is missing or is the item being merged.
+    InvalidTarget,
+}
+
+/// Why the items of a `DuplicateGroup` look like the same product.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateReason {
+    Barcode,
+    Name,
+}
+
+/// Items that are probably one product entered more than once.
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub reason: DuplicateReason,
+    pub items: Vec<Item>,
+}
+
+/// A shop the user buys in. Stores come in the order the user visits them.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Store {
+    pub id: i32,
+    pub name: String,
+}
+
+/// Values of an item's custom fields, keyed by the field's name.
+pub type Attributes = serde_json::Map<String, serde_json::Value>;
+
+/// Type of a custom item field, which its values must have.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum AttributeKind {
+    Text,
+    Number,
+    Boolean,
+    /// A `YYYY-MM-DD` string.
+    Date,
+}
+
+/// A field the user added to all their items, such as "Marka" or "Dawka".
+#[derive(Debug, Serialize, Clone)]
+pub struct AttributeField {
+    pub id: i32,
+    pub name: String,
+    pub kind: AttributeKind,
+}
+
+/// Body of `POST /api/attribute-fields`.
+#[derive(Debug, Deserialize)]
+pub struct AttributeFieldPayload {
+    pub name: String,
+    pub kind: AttributeKind,
+}
+
+impl Validate for AttributeFieldPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("name", &self.name, MAX_ATTRIBUTE_NAME_LEN);
+        errors.into_result()
+    }
+}
+
+/// Body of `POST /api/stores` and `PUT /api/stores/{id}`.
+#[derive(Debug, Deserialize)]
+pub struct StorePayload {
+    pub name: String,
+}
+
+impl Validate for StorePayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("name", &self.name, MAX_TEXT_LEN);
+        errors.into_result()
     }
 }
 
@@ -63,8 +384,45 @@ pub struct CreateItemPayload {
     pub name: String,
     pub quantity: i32,
     pub restock_threshold: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub restock_to: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub unit: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub location: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub barcode: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub notes: Option<String>,
+    /// Expiry date of the initial batch.
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub expires_on: Option<Date>,
     #[serde(deserialize_with = "deserialize_empty_string_as_none")]
     pub category_id: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub preferred_store_id: Option<i32>,
+    /// Pack size; defaults to one.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub quantity_step: Option<i32>,
+    /// Values of the user's custom fields; web forms send `attr_{field id}` inputs instead.
+    #[serde(default)]
+    pub attributes: Option<Attributes>,
+}
+
+impl Validate for CreateItemPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("name", &self.name, MAX_TEXT_LEN);
+        errors.non_negative("quantity", Some(self.quantity));
+        errors.non_negative("restock_threshold", self.restock_threshold);
+        errors.non_negative("restock_to", self.restock_to);
+        errors.positive("quantity_step", self.quantity_step);
+        errors.optional_text("unit", self.unit.as_deref(), MAX_UNIT_LEN);
+        errors.optional_text("location", self.location.as_deref(), MAX_TEXT_LEN);
+        errors.optional_text("barcode", self.barcode.as_deref(), MAX_BARCODE_LEN);
+        errors.optional_text("notes", self.notes.as_deref(), MAX_NOTES_LEN);
+        errors.into_result()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -72,40 +430,1671 @@ pub struct UpdateItemPayload {
     pub name: Option<String>,
     pub quantity: Option<i32>,
     pub restock_threshold: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub restock_to: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub unit: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub location: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub barcode: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub notes: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
     pub category_id: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub preferred_store_id: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub quantity_step: Option<i32>,
+    /// Replaces all custom field values; left out, they are kept.
+    #[serde(default)]
+    pub attributes: Option<Attributes>,
+    /// The item's `version` when the client read it.
+    pub version: i32,
+}
+
+impl Validate for UpdateItemPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(name) = &self.name {
+            errors.text("name", name, MAX_TEXT_LEN);
+        }
+        errors.non_negative("quantity", self.quantity);
+        errors.non_negative("restock_threshold", self.restock_threshold);
+        errors.non_negative("restock_to", self.restock_to);
+        errors.positive("quantity_step", self.quantity_step);
+        errors.optional_text("unit", self.unit.as_deref(), MAX_UNIT_LEN);
+        errors.optional_text("location", self.location.as_deref(), MAX_TEXT_LEN);
+        errors.optional_text("barcode", self.barcode.as_deref(), MAX_BARCODE_LEN);
+        errors.optional_text("notes", self.notes.as_deref(), MAX_NOTES_LEN);
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct UseItemPayload {
+    /// How many units to use; defaults to one.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub quantity: Option<i32>,
+}
+
+impl Validate for UseItemPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.positive("quantity", self.quantity);
+        errors.into_result()
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct PurchaseItemPayload {
     pub quantity: i32,
+    /// Purchased units are stored as a new batch with this expiry date.
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub expires_on: Option<Date>,
+    /// What all of `quantity` cost.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub price: Option<Price>,
+    /// Where it was bought; defaults to the item's preferred store.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub store_id: Option<i32>,
 }
 
-// For notifications
-#[derive(Debug, Serialize, Clone)]
-pub struct Notification {
+impl Validate for PurchaseItemPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.positive("quantity", Some(self.quantity));
+        errors.into_result()
+    }
+}
+
+/// Body of `POST /api/items/restock-all`; without `item_ids` every item
+/// below its restock threshold is restocked.
+#[derive(Debug, Deserialize, Default)]
+pub struct RestockItemsPayload {
+    #[serde(default)]
+    pub item_ids: Option<Vec<i32>>,
+}
+
+/// An amount of money in hundredths (grosze), written as a decimal such as
+/// `"12.99"`. Read from a string, with a dot or a comma, or from a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct Price(pub i32);
+
+impl FromStr for Price {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid price: {s}");
+        let s = s.trim();
+        let (whole, fraction) = s.split_once(['.', ',']).unwrap_or((s, ""));
+        let mut digits = whole.chars().chain(fraction.chars()).peekable();
+        if digits.peek().is_none() || fraction.len() > 2 || !digits.all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let whole: i32 = match whole {
+            "" => 0,
+            whole => whole.parse().map_err(|_| invalid())?,
+        };
+        // "12,5" is 12.50
+        let fraction: i32 = format!("{fraction:0<2}").parse().map_err(|_| invalid())?;
+        whole
+            .checked_mul(100)
+            .and_then(|cents| cents.checked_add(fraction))
+            .map(Price)
+            .ok_or_else(invalid)
+    }
+}
+
+impl std::fmt::Display for Price {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:02}", self.0 / 100, self.0 % 100)
+    }
+}
+
+impl Serialize for Price {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawPrice {
+            Number(f64),
+            Text(String),
+        }
+
+        match RawPrice::deserialize(deserializer)? {
+            RawPrice::Text(text) => text.parse().map_err(de::Error::custom),
+            RawPrice::Number(number) => {
+                let cents = (number * 100.0).round();
+                if !(0.0..=f64::from(i32::MAX)).contains(&cents) {
+                    return Err(de::Error::custom(format!("invalid price: {number}")));
+                }
+                Ok(Price(cents as i32))
+            }
+        }
+    }
+}
+
+/// A purchase with what it cost and where, from `GET /api/purchases`.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Purchase {
+    /// Also the id of its `purchased` event, which its receipt belongs to.
+    pub id: i32,
+    /// `None` once the item has been deleted.
+    pub item_id: Option<i32>,
     pub item_name: String,
-    pub message: String,
+    pub quantity: i32,
+    /// What all of `quantity` cost.
+    pub price: Option<Price>,
+    /// `price` per unit, to compare packs of different sizes.
+    pub unit_price: Option<Price>,
+    pub store_id: Option<i32>,
+    pub store_name: Option<String>,
+    pub has_receipt: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub purchased_at: OffsetDateTime,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
-pub struct Account {
+impl Purchase {
+    pub fn cursor(&self) -> Cursor {
+        Cursor {
+            created_at: self.purchased_at,
+            id: self.id,
+        }
+    }
+}
+
+/// Query string of `GET /api/purchases`, besides its `PageQuery`.
+#[derive(Debug, Deserialize)]
+pub struct PurchaseQuery {
+    /// Only the purchases of this item.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub item_id: Option<i32>,
+}
+
+/// What an item costs per unit in one store, over its purchases with a price.
+#[derive(Debug, Serialize, FromRow)]
+pub struct StorePrice {
+    /// `None` for purchases without a store.
+    pub store_id: Option<i32>,
+    pub store_name: Option<String>,
+    pub purchases: i64,
+    pub lowest: Price,
+    pub average: Price,
+    /// The unit price of the newest purchase.
+    pub latest: Price,
+    #[serde(with = "time::serde::rfc3339")]
+    pub latest_at: OffsetDateTime,
+}
+
+// Batches (lots) of an item
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct ItemBatch {
+    pub id: i32,
+    pub item_id: i32,
+    pub quantity: i32,
+    #[serde(with = "date_format")]
+    pub purchased_on: Date,
+    #[serde(with = "date_format::option")]
+    pub expires_on: Option<Date>,
+}
+
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct ExpiringBatch {
+    pub id: i32,
+    pub item_id: i32,
+    pub item_name: String,
+    pub quantity: i32,
+    #[serde(with = "date_format")]
+    pub expires_on: Date,
+}
+
+/// How much of an item was used lately, from `db::get_item_usage`.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct ItemUsage {
+    pub item_id: i32,
+    pub item_name: String,
+    pub quantity: i32,
+    pub used: i64,
+}
+
+/// Why a quantity changed; stored as the history event kind.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AdjustmentReason {
+    Used,
+    Expired,
+    Spoiled,
+    Lost,
+    Correction,
+}
+
+impl AdjustmentReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AdjustmentReason::Used => "used",
+            AdjustmentReason::Expired => "expired",
+            AdjustmentReason::Spoiled => "spoiled",
+            AdjustmentReason::Lost => "lost",
+            AdjustmentReason::Correction => "correction",
+        }
+    }
+
+    /// Only corrections may increase the quantity; everything else removes stock.
+    pub fn allows_increase(self) -> bool {
+        self == AdjustmentReason::Correction
+    }
+
+    /// Stock removed for these reasons was thrown away rather than used.
+    pub fn is_waste(self) -> bool {
+        matches!(
+            self,
+            AdjustmentReason::Expired | AdjustmentReason::Spoiled | AdjustmentReason::Lost
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdjustItemPayload {
+    /// Signed quantity change, e.g. `-2` for two spoiled units.
+    #[serde(default)]
+    pub delta: i32,
+    /// Signed change in packs of the item's `quantity_step`, instead of `delta`.
+    #[serde(default)]
+    pub steps: i32,
+    pub reason: AdjustmentReason,
+}
+
+impl Validate for AdjustItemPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let (field, change) = if self.steps != 0 {
+            ("steps", self.steps)
+        } else {
+            ("delta", self.delta)
+        };
+        if self.delta != 0 && self.steps != 0 {
+            errors.add("steps", "can't be combined with delta");
+        } else if change == 0 {
+            errors.add(field, "must not be zero");
+        } else if change > 0 && !self.reason.allows_increase() {
+            errors.add(
+                field,
+                format!("must be negative for reason '{}'", self.reason.as_str()),
+            );
+        }
+        errors.into_result()
+    }
+}
+
+/// Why stock is thrown away.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscardReason {
+    Expired,
+    Spoiled,
+}
+
+impl From<DiscardReason> for AdjustmentReason {
+    fn from(reason: DiscardReason) -> Self {
+        match reason {
+            DiscardReason::Expired => AdjustmentReason::Expired,
+            DiscardReason::Spoiled => AdjustmentReason::Spoiled,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscardItemPayload {
+    /// How many units are thrown away; defaults to one.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub quantity: Option<i32>,
+    pub reason: DiscardReason,
+}
+
+impl Validate for DiscardItemPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.positive("quantity", self.quantity);
+        errors.into_result()
+    }
+}
+
+/// Stock of an item that is used up on its own, `quantity` units every
+/// `every_days` days.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct ConsumptionRule {
     pub id: i32,
+    pub item_id: i32,
+    pub item_name: String,
+    pub quantity: i32,
+    pub every_days: i32,
+    /// When the units are taken next.
+    #[serde(with = "time::serde::rfc3339")]
+    pub next_run_at: OffsetDateTime,
+}
+
+/// Body of `PUT /api/items/{id}/consumption-rule`, e.g. `2` every `7` days.
+#[derive(Debug, Deserialize)]
+pub struct ConsumptionRulePayload {
+    pub quantity: i32,
+    pub every_days: i32,
+}
+
+impl ConsumptionRulePayload {
+    /// A rule runs at least once a year.
+    pub const MAX_EVERY_DAYS: i32 = 366;
+}
+
+impl Validate for ConsumptionRulePayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.positive("quantity", Some(self.quantity));
+        errors.positive("every_days", Some(self.every_days));
+        if self.every_days > Self::MAX_EVERY_DAYS {
+            errors.add(
+                "every_days",
+                format!("must be at most {}", Self::MAX_EVERY_DAYS),
+            );
+        }
+        errors.into_result()
+    }
+}
+
+/// Query string of `GET /api/waste`.
+#[derive(Debug, Deserialize)]
+pub struct WasteQuery {
+    /// The month to report on; the current one without it.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub month: Option<Month>,
+}
+
+/// A calendar month, written `2025-08`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Month(pub Date);
+
+impl Month {
+    pub fn containing(date: Date) -> Month {
+        Month(date.replace_day(1).expect("every month has a first day"))
+    }
+}
+
+impl FromStr for Month {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid month: {s}, expected YYYY-MM");
+        let (year, month) = s.trim().split_once('-').ok_or_else(invalid)?;
+        let year: i32 = year.parse().map_err(|_| invalid())?;
+        let month: u8 = month.parse().map_err(|_| invalid())?;
+        let month = time::Month::try_from(month).map_err(|_| invalid())?;
+        Date::from_calendar_date(year, month, 1)
+            .map(Month)
+            .map_err(|_| invalid())
+    }
+}
+
+impl std::fmt::Display for Month {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}", self.0.year(), u8::from(self.0.month()))
+    }
+}
+
+impl Serialize for Month {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Month {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// How much was thrown away in a month, from `GET /api/waste`.
+#[derive(Debug, Serialize)]
+pub struct WasteReport {
+    pub month: Month,
+    pub quantity: i64,
+    /// What the thrown away stock was worth, as far as its prices are known.
+    pub value: Price,
+    /// Units thrown away of items never bought with a price.
+    pub unpriced_quantity: i64,
+    pub reasons: Vec<WasteByReason>,
+    /// The most wasted items first, by value, then by quantity.
+    pub items: Vec<WastedItem>,
+    /// The totals of the twelve months up to `month`, oldest first.
+    pub months: Vec<WasteMonth>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WasteByReason {
+    /// `expired`, `spoiled` or `lost`.
+    pub reason: String,
+    pub quantity: i64,
+    pub value: Price,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WastedItem {
+    /// `None` once the item has been deleted.
+    pub item_id: Option<i32>,
+    pub item_name: String,
+    pub quantity: i64,
+    pub value: Option<Price>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WasteMonth {
+    pub month: String,
+    pub quantity: i64,
+    pub value: Price,
+}
+
+/// A month in numbers, for the summary email.
+#[derive(Debug, Serialize)]
+pub struct MonthlySummary {
+    pub month: Month,
+    /// Units used up.
+    pub consumed: i64,
+    pub purchases: i64,
+    /// What the purchases with a price cost.
+    pub spent: Price,
+    /// Units thrown away; see `WasteReport`.
+    pub wasted: i64,
+    pub waste_value: Price,
+    /// The items used up most, most first.
+    pub top_items: Vec<SummaryLine>,
+    /// The categories whose items were used up most, most first.
+    pub top_categories: Vec<SummaryLine>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SummaryLine {
     pub name: String,
-    pub email: String,
-    pub password: String,
-    pub created_at: OffsetDateTime,
-    pub updated_at: OffsetDateTime,
+    pub quantity: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
-pub struct CreateAccountPayload {
+/// Someone who asked for the monthly summary.
+#[derive(Debug, FromRow)]
+pub struct SummaryRecipient {
+    pub id: i32,
     pub name: String,
     pub email: String,
-    pub password: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
-pub struct LoginPayload {
-    pub email: String,
-    pub password: String,
+// For notifications
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationKind {
+    Restock,
+    Expiry,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub item_name: String,
+    pub message: String,
+    /// A restock that has waited longer than the user allows.
+    pub urgent: bool,
+}
+
+/// An item below its restock threshold for at least the user's
+/// `restock_escalation_days`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UrgentRestock {
+    pub item_id: i32,
+    pub days_low: i32,
+}
+
+// For the dashboard
+#[derive(Debug, Serialize, FromRow)]
+pub struct CategoryStats {
+    pub id: Option<i32>,
+    pub name: Option<String>,
+    pub color: Option<String>,
+    pub item_count: i64,
+    pub total_quantity: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ConsumptionPoint {
+    pub day: String,
+    pub used: i64,
+    pub purchased: i64,
+    /// Spoiled or lost units.
+    pub wasted: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsOverview {
+    pub total_items: i64,
+    pub total_quantity: i64,
+    pub low_stock_count: i64,
+    pub out_of_stock_count: i64,
+    pub by_category: Vec<CategoryStats>,
+    pub consumption: Vec<ConsumptionPoint>,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    pub days: Option<i32>,
+}
+
+// Item history
+
+/// A change to an item's quantity, newest first in the history.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct ItemEvent {
+    pub id: i32,
+    /// `None` once the item has been deleted.
+    pub item_id: Option<i32>,
+    pub item_name: String,
+    pub kind: String,
+    pub quantity_delta: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl ItemEvent {
+    pub fn cursor(&self) -> Cursor {
+        Cursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
+// Activity feed
+
+/// Query string of the activity feed, besides its `PageQuery`.
+#[derive(Debug, Deserialize)]
+pub struct ActivityQuery {
+    /// Only the events of this item.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub item_id: Option<i32>,
+    /// Only the changes made by this account, the owner or a member of the
+    /// household.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub user_id: Option<i32>,
+}
+
+/// A history event as a line of the activity feed.
+#[derive(Debug, Serialize)]
+pub struct ActivityEntry {
+    #[serde(flatten)]
+    pub event: ItemEvent,
+    /// The account that made the change; `None` once it is deleted.
+    pub user_id: Option<i32>,
+    /// Who made the change.
+    pub user_name: String,
+    /// The whole line, e.g. `Ala: kupiono Baterie ×4`.
+    pub text: String,
+    /// The receipt attached to a purchase.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<Receipt>,
+}
+
+/// A photo or PDF of the receipt for a purchase event; the file is served by
+/// `GET /api/purchases/{event_id}/receipt`.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Receipt {
+    pub event_id: i32,
+    pub content_type: String,
+    pub size_bytes: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+// Stocktake (inventory count)
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Stocktake {
+    pub id: i32,
+    pub status: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub completed_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct StocktakeEntry {
+    pub item_id: Option<i32>,
+    pub item_name: String,
+    pub category_name: Option<String>,
+    pub expected_quantity: i32,
+    pub counted_quantity: Option<i32>,
+    pub variance: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StocktakeWithEntries {
+    #[serde(flatten)]
+    pub stocktake: Stocktake,
+    pub entries: Vec<StocktakeEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StocktakeCount {
+    pub item_id: i32,
+    pub counted_quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateStocktakeCountsPayload {
+    pub counts: Vec<StocktakeCount>,
+}
+
+impl Validate for UpdateStocktakeCountsPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if self.counts.iter().any(|c| c.counted_quantity < 0) {
+            errors.add("counts", "counted quantities must not be negative");
+        }
+        errors.into_result()
+    }
+}
+
+// Recipes
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Recipe {
+    pub id: i32,
+    pub name: String,
+    pub instructions: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct RecipeIngredient {
+    pub recipe_id: i32,
+    pub item_id: i32,
+    pub item_name: String,
+    pub quantity: i32,
+    pub available_quantity: i32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MissingIngredient {
+    pub item_id: i32,
+    pub item_name: String,
+    pub required: i32,
+    pub available: i32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RecipeAvailability {
+    pub can_make: bool,
+    /// How many times the recipe can be made from current stock.
+    /// `None` for recipes without ingredients.
+    pub max_servings: Option<i32>,
+    pub missing: Vec<MissingIngredient>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecipeWithIngredients {
+    #[serde(flatten)]
+    pub recipe: Recipe,
+    pub ingredients: Vec<RecipeIngredient>,
+    pub availability: RecipeAvailability,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecipeIngredientPayload {
+    pub item_id: i32,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRecipePayload {
+    pub name: String,
+    pub instructions: Option<String>,
+    pub ingredients: Vec<RecipeIngredientPayload>,
+}
+
+impl Validate for CreateRecipePayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("name", &self.name, MAX_TEXT_LEN);
+        if self.ingredients.iter().any(|i| i.quantity <= 0) {
+            errors.add("ingredients", "quantities must be greater than zero");
+        }
+        let mut seen = std::collections::HashSet::new();
+        if !self.ingredients.iter().all(|i| seen.insert(i.item_id)) {
+            errors.add("ingredients", "must not list an item twice");
+        }
+        errors.into_result()
+    }
+}
+
+// Meal planning
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct MealPlanEntry {
+    pub id: i32,
+    pub recipe_id: i32,
+    pub recipe_name: String,
+    #[serde(with = "date_format")]
+    pub planned_for: Date,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub cooked_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MealPlanDay {
+    #[serde(with = "date_format")]
+    pub date: Date,
+    pub weekday: String,
+    pub is_today: bool,
+    pub meals: Vec<MealPlanEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMealPlanPayload {
+    pub recipe_id: i32,
+    #[serde(with = "date_format")]
+    pub planned_for: Date,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MealPlanQuery {
+    /// Any day of the requested week; defaults to the current week.
+    #[serde(default, with = "date_format::option")]
+    pub week: Option<Date>,
+}
+
+// Shopping list
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct ShoppingListEntry {
+    pub item_id: i32,
+    pub item_name: String,
+    pub category_name: Option<String>,
+    pub category_color: Option<String>,
+    /// The item's preferred store.
+    pub store_name: Option<String>,
+    pub quantity: i32,
+    pub unit: Option<String>,
+    pub restock_threshold: i32,
+    pub restock_to: Option<i32>,
+    /// Quantity reserved by upcoming, not yet cooked meals.
+    pub reserved: i64,
+    pub to_buy: i64,
+}
+
+/// Body of `POST /api/shopping-list/checkout`: what was bought on one
+/// shopping trip.
+#[derive(Debug, Deserialize)]
+pub struct CheckoutPayload {
+    /// Where everything was bought; each item's preferred store without it.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub store_id: Option<i32>,
+    pub items: Vec<CheckoutLine>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckoutLine {
+    pub item_id: i32,
+    pub quantity: i32,
+    /// What all of `quantity` cost.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub price: Option<Price>,
+}
+
+impl Validate for CheckoutPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if self.items.is_empty() {
+            errors.add("items", "must not be empty");
+        }
+        let mut seen = std::collections::HashSet::new();
+        for line in &self.items {
+            errors.positive("quantity", Some(line.quantity));
+            if !seen.insert(line.item_id) {
+                errors.add("items", "must not list an item twice");
+            }
+        }
+        errors.into_result()
+    }
+}
+
+/// `GET /api/shopping-list/export?format=`
+#[derive(Debug, Deserialize)]
+pub struct ShoppingListExportQuery {
+    pub format: ShoppingListFormat,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShoppingListFormat {
+    Md,
+    Txt,
+    Pdf,
+}
+
+/// `GET /web/labels.pdf`; the items to print come as `item_<id>` fields.
+#[derive(Debug, Default, Deserialize)]
+pub struct LabelSheetQuery {
+    #[serde(default)]
+    pub layout: LabelLayout,
+    /// Labels already peeled off the first sheet, which are left blank.
+    #[serde(default)]
+    pub skip: usize,
+}
+
+/// Avery A4 label sheets the PDF is laid out for.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelLayout {
+    /// 21 labels, 63.5 × 38.1 mm.
+    #[default]
+    L7160,
+    /// 14 labels, 99.1 × 38.1 mm.
+    L7163,
+    /// 8 labels, 99.1 × 67.7 mm.
+    L7165,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Account {
+    pub id: i32,
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct CreateAccountPayload {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+}
+
+impl Validate for CreateAccountPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("name", &self.name, MAX_TEXT_LEN);
+        errors.email("email", &self.email);
+        if self.password.is_empty() {
+            errors.add("password", "must not be empty");
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct LoginPayload {
+    pub email: String,
+    pub password: String,
+    /// Page to return to, carried over from `LoginQuery`.
+    #[serde(default)]
+    pub next: Option<String>,
+    /// "Remember me": keep the session for days instead of hours.
+    #[serde(default)]
+    pub remember: bool,
+}
+
+/// A valid (not expired) login session.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserSession {
+    pub id: i32,
+    pub user_id: i32,
+    pub remember: bool,
+    pub last_seen_at: OffsetDateTime,
+    /// The household the session works in, while the user is still a member
+    /// of it; `None` for their own inventory.
+    pub household_id: Option<i32>,
+    pub household_role: Option<HouseholdRole>,
+}
+
+// Households
+
+/// What a member may do in someone else's household. Only the owner manages
+/// its members, and its account-wide settings such as tokens and channels.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum HouseholdRole {
+    /// Sees the inventory without changing anything.
+    Viewer,
+    /// Changes the inventory too.
+    Editor,
+}
+
+/// A household the user is a member of, as listed by `GET /api/households`.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Household {
+    /// The id of the owner.
+    pub id: i32,
+    /// The name of the owner.
+    pub owner: String,
+    pub role: HouseholdRole,
+    /// Whether the session making the request works in it.
+    pub current: bool,
+}
+
+/// A member of the user's household, as listed on the household page and by
+/// `GET /api/households/{id}/members`.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct HouseholdMember {
+    pub user_id: i32,
+    pub name: String,
+    pub email: String,
+    pub role: HouseholdRole,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// An invitation into a household, for the account registered with `email`
+/// to accept.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct HouseholdInvitation {
+    pub id: i32,
+    pub household_id: i32,
+    /// The name of the owner.
+    pub owner: String,
+    pub email: String,
+    pub role: HouseholdRole,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// Invites whoever has the account registered with `email`.
+#[derive(Debug, Deserialize)]
+pub struct InviteHouseholdMemberPayload {
+    pub email: String,
+    pub role: HouseholdRole,
+}
+
+impl Validate for InviteHouseholdMemberPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.email("email", &self.email);
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateHouseholdMemberPayload {
+    pub role: HouseholdRole,
+}
+
+/// Body of `PUT /api/households/current`; `None` goes back to the user's own
+/// inventory.
+#[derive(Debug, Deserialize)]
+pub struct SwitchHouseholdPayload {
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub household_id: Option<i32>,
+}
+
+// Product catalog
+
+/// A product the user has had, kept to fill in the add-item form when it's
+/// bought again.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct CatalogProduct {
+    pub id: i32,
+    pub name: String,
+    pub barcode: Option<String>,
+    pub category_id: Option<i32>,
+    pub unit: Option<String>,
+    pub restock_threshold: i32,
+    /// The item of this name, while there is one.
+    pub item_id: Option<i32>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_used_at: OffsetDateTime,
+}
+
+/// Query string of `GET /api/catalog`.
+#[derive(Debug, Deserialize)]
+pub struct CatalogQuery {
+    /// Part of the name, or the whole barcode; everything without it.
+    #[serde(default)]
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+impl CatalogQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(10).clamp(1, 100)
+    }
+}
+
+// Share links
+
+/// What a share link shows.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ShareScope {
+    /// Items of the chosen categories and their subcategories.
+    Categories,
+    ShoppingList,
+    /// Expiry dates as a calendar feed, at `/calendar/{token}.ics`.
+    Calendar,
+    /// Items to restock as an Atom feed, at `/feeds/{token}/restock.atom`.
+    RestockFeed,
+}
+
+/// A read-only link, as listed on the settings page and by
+/// `GET /api/share-links`.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct ShareLink {
+    pub id: i32,
+    #[serde(skip)]
+    pub user_id: i32,
+    /// The last part of `/share/{token}`.
+    pub token: String,
+    pub label: String,
+    pub scope: ShareScope,
+    pub category_ids: Vec<i32>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_viewed_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateShareLinkPayload {
+    pub label: String,
+    pub scope: ShareScope,
+    /// Required for `categories`, ignored otherwise.
+    #[serde(default)]
+    pub category_ids: Vec<i32>,
+}
+
+impl Validate for CreateShareLinkPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("label", &self.label, MAX_TEXT_LEN);
+        if self.scope == ShareScope::Categories && self.category_ids.is_empty() {
+            errors.add("category_ids", "must name at least one category");
+        }
+        errors.into_result()
+    }
+}
+
+/// Query of `GET /calendar/{token}.ics`.
+#[derive(Debug, Default, Deserialize)]
+pub struct CalendarQuery {
+    /// Also lists when items will likely run out, from the last 30 days of
+    /// use.
+    #[serde(default)]
+    pub run_out: bool,
+}
+
+// Notification channels
+
+/// How a notification channel delivers.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum ChannelKind {
+    /// The notify URL of an Apprise API server, which passes messages on to
+    /// Discord, Slack, Matrix, Pushover and the rest.
+    Apprise,
+    /// Any URL, POSTed the message as plain text, with `{title}` and
+    /// `{message}` in it filled in.
+    Url,
+    /// A Matrix room; `url` is the homeserver.
+    Matrix,
+}
+
+/// When a channel gets alerts.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum ChannelDelivery {
+    /// Each alert as it comes up.
+    #[default]
+    Immediate,
+    /// Everything that needs attention, once a day at the user's digest time.
+    Digest,
+}
+
+/// Where alerts go, as listed on the settings page and by
+/// `GET /api/notification-channels`.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct NotificationChannel {
+    pub id: i32,
+    pub label: String,
+    pub kind: ChannelKind,
+    pub url: String,
+    /// For Matrix, e.g. `!abcdef:matrix.org`.
+    pub room_id: Option<String>,
+    /// For Matrix, of the account that posts; never sent back.
+    #[serde(skip)]
+    pub access_token: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_sent_at: Option<OffsetDateTime>,
+    /// Why the last attempt failed; `None` once one succeeds.
+    pub last_error: Option<String>,
+    /// Gets only urgent alerts, e.g. the phone of another household member.
+    pub escalation_only: bool,
+    pub delivery: ChannelDelivery,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateNotificationChannelPayload {
+    pub label: String,
+    pub kind: ChannelKind,
+    pub url: String,
+    /// Required for Matrix, ignored otherwise.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub room_id: Option<String>,
+    /// Required for Matrix, ignored otherwise.
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub access_token: Option<String>,
+    #[serde(default)]
+    pub escalation_only: bool,
+    #[serde(default)]
+    pub delivery: ChannelDelivery,
+}
+
+impl Validate for CreateNotificationChannelPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("label", &self.label, MAX_TEXT_LEN);
+        errors.url("url", &self.url);
+        if self.kind == ChannelKind::Matrix {
+            // Aliases such as `#kitchen:matrix.org` would need resolving first
+            match self.room_id.as_deref().map(str::trim) {
+                Some(room_id) if room_id.starts_with('!') && room_id.contains(':') => {
+                    errors.optional_text("room_id", Some(room_id), MAX_TEXT_LEN)
+                }
+                _ => errors.add("room_id", "must be a room ID like !abcdef:matrix.org"),
+            }
+            match self.access_token.as_deref() {
+                Some(token) => errors.text("access_token", token, MAX_URL_LEN),
+                None => errors.add("access_token", "must not be empty"),
+            }
+        }
+        errors.into_result()
+    }
+}
+
+/// When the user's alerts go out, as `GET /api/notification-settings`
+/// answers.
+#[derive(Debug, Serialize, Clone, FromRow)]
+pub struct NotificationSettings {
+    /// Nothing is sent from `quiet_start` until `quiet_end`, which may be on
+    /// the next day; what comes up meanwhile goes out after.
+    #[serde(with = "clock_time::option")]
+    pub quiet_start: Option<Time>,
+    #[serde(with = "clock_time::option")]
+    pub quiet_end: Option<Time>,
+    /// When channels set to `digest` get the day's summary.
+    #[serde(with = "clock_time")]
+    pub digest_time: Time,
+    /// IANA name of the zone of the times above; `None` for the zone pages
+    /// show times in.
+    pub timezone: Option<String>,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            quiet_start: None,
+            quiet_end: None,
+            digest_time: Time::from_hms(8, 0, 0).expect("08:00 is a time"),
+            timezone: None,
+        }
+    }
+}
+
+impl NotificationSettings {
+    /// Whether `at`, a local time, falls in the quiet hours.
+    pub fn is_quiet(&self, at: Time) -> bool {
+        match (self.quiet_start, self.quiet_end) {
+            (Some(start), Some(end)) if start <= end => start <= at && at < end,
+            (Some(start), Some(end)) => at >= start || at < end,
+            _ => false,
+        }
+    }
+}
+
+/// Body of `PUT /api/notification-settings` and of the settings form; times
+/// as `HH:MM`. Replaces all of the settings, so leaving out the quiet hours
+/// turns them off.
+#[derive(Debug, Deserialize)]
+pub struct UpdateNotificationSettingsPayload {
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub quiet_start: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub quiet_end: Option<String>,
+    pub digest_time: String,
+    #[serde(default, deserialize_with = "deserialize_empty_string_as_none")]
+    pub timezone: Option<String>,
+}
+
+/// `HH:MM` as a time of day; seconds, which time inputs may add, are dropped.
+pub fn parse_clock_time(text: &str) -> Option<Time> {
+    use time::macros::format_description;
+    let text = text.trim();
+    let time = Time::parse(text, format_description!("[hour]:[minute]"))
+        .or_else(|_| Time::parse(text, format_description!("[hour]:[minute]:[second]")))
+        .ok()?;
+    time.replace_second(0).ok()
+}
+
+impl UpdateNotificationSettingsPayload {
+    /// The settings this asks for; call `validate` first.
+    pub fn settings(&self) -> NotificationSettings {
+        let parse = |text: &Option<String>| text.as_deref().and_then(parse_clock_time);
+        NotificationSettings {
+            quiet_start: parse(&self.quiet_start),
+            quiet_end: parse(&self.quiet_end),
+            digest_time: parse_clock_time(&self.digest_time).unwrap_or(Time::MIDNIGHT),
+            timezone: self.timezone.as_deref().map(|tz| tz.trim().to_string()),
+        }
+    }
+}
+
+impl Validate for UpdateNotificationSettingsPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for (field, value) in [
+            ("quiet_start", self.quiet_start.as_deref()),
+            ("quiet_end", self.quiet_end.as_deref()),
+            ("digest_time", Some(self.digest_time.as_str())),
+        ] {
+            if value.is_some_and(|value| parse_clock_time(value).is_none()) {
+                errors.add(field, "must be a time such as 22:30");
+            }
+        }
+        match (&self.quiet_start, &self.quiet_end) {
+            (Some(_), None) => errors.add("quiet_end", "must be set with quiet_start"),
+            (None, Some(_)) => errors.add("quiet_start", "must be set with quiet_end"),
+            (Some(start), Some(end)) if parse_clock_time(start) == parse_clock_time(end) => {
+                errors.add("quiet_end", "must differ from quiet_start")
+            }
+            _ => {}
+        }
+        if let Some(timezone) = &self.timezone
+            && !filters::is_timezone(timezone.trim())
+        {
+            errors.add("timezone", "must be a time zone such as Europe/Warsaw");
+        }
+        errors.into_result()
+    }
+}
+
+// API tokens
+
+/// A long-lived token for Home Assistant, as listed on the settings page and
+/// by `GET /api/tokens`. Only its hash is stored, so the token itself is
+/// shown once, when it is made.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct ApiToken {
+    pub id: i32,
+    /// The first characters of the token, to tell tokens apart.
+    pub prefix: String,
+    pub label: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+/// A token just made, as `POST /api/tokens` answers.
+#[derive(Debug, Serialize)]
+pub struct NewApiToken {
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+    /// Sent as `Authorization: Bearer {token}`.
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiTokenPayload {
+    pub label: String,
+}
+
+impl Validate for CreateApiTokenPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("label", &self.label, MAX_TEXT_LEN);
+        errors.into_result()
+    }
+}
+
+/// What Home Assistant polls from `GET /api/ha/sensors`, flat enough for a
+/// REST sensor's `value_template`.
+#[derive(Debug, Serialize)]
+pub struct HaSensors {
+    pub items: i64,
+    pub low_stock: i64,
+    pub out_of_stock: i64,
+    /// Batches that expire within the warning window or did already.
+    pub expiring: i64,
+    /// Names of the items below their restock threshold.
+    pub low_stock_items: Vec<String>,
+    /// Number of items per category name, uncategorized ones under
+    /// "Bez kategorii".
+    pub categories: BTreeMap<String, i64>,
+}
+
+/// Body of `POST /api/ha/consume`: the item, by ID or by name, and how many
+/// units were used, one by default.
+#[derive(Debug, Default, Deserialize)]
+pub struct HaConsumePayload {
+    pub item_id: Option<i32>,
+    pub name: Option<String>,
+    pub quantity: Option<i32>,
+}
+
+impl Validate for HaConsumePayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match (self.item_id, self.name.as_deref()) {
+            (Some(_), None) => {}
+            (None, Some(name)) => errors.text("name", name, MAX_TEXT_LEN),
+            _ => errors.add("item_id", "give either item_id or name"),
+        }
+        errors.positive("quantity", self.quantity);
+        errors.into_result()
+    }
+}
+
+/// Body of `POST /api/assistant`: a voice request that the assistant's
+/// language model already turned into an intent and an item name.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "intent", rename_all = "snake_case")]
+pub enum AssistantIntent {
+    /// "Add two milks to the pantry"; one unit by default.
+    AddItem { item: String, quantity: Option<i32> },
+    /// "How many eggs do we have?"
+    CheckStock { item: String },
+    /// "Put coffee on the shopping list"
+    AddToShoppingList { item: String },
+}
+
+impl AssistantIntent {
+    /// The item name as spoken, e.g. "mleko".
+    pub fn item(&self) -> &str {
+        match self {
+            AssistantIntent::AddItem { item, .. }
+            | AssistantIntent::CheckStock { item }
+            | AssistantIntent::AddToShoppingList { item } => item,
+        }
+    }
+}
+
+impl Validate for AssistantIntent {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.text("item", self.item(), MAX_TEXT_LEN);
+        if let AssistantIntent::AddItem { quantity, .. } = self {
+            errors.positive("quantity", *quantity);
+        }
+        errors.into_result()
+    }
+}
+
+/// Answer of `POST /api/assistant`.
+#[derive(Debug, Serialize)]
+pub struct AssistantReply {
+    /// A sentence for the assistant to say back, e.g. "Mleko: 3 l".
+    pub speech: String,
+    /// The item as it is now; `None` when asked about one that doesn't exist.
+    pub item: Option<Item>,
+}
+
+/// A session as listed on the settings page and by `GET /api/sessions`.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct SessionInfo {
+    pub id: i32,
+    pub user_agent: Option<String>,
+    /// Address the login came from.
+    pub ip_address: Option<String>,
+    pub remember: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_seen_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+    /// The session making the request.
+    pub current: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    pub next: Option<String>,
+}
+
+// User preferences, stored as lowercase text in `user_preferences`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum ItemSort {
+    /// The drag-and-drop order.
+    #[default]
+    Manual,
+    Name,
+    /// Fewest units first.
+    Quantity,
+    /// Most recently changed first.
+    Updated,
+    /// Lowest quantity relative to the restock threshold first; items
+    /// without a threshold go last.
+    Stock,
+}
+
+impl ItemSort {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ItemSort::Manual => "manual",
+            ItemSort::Name => "name",
+            ItemSort::Quantity => "quantity",
+            ItemSort::Updated => "updated",
+            ItemSort::Stock => "stock",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Pl,
+    En,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Follows the browser's color scheme.
+    #[default]
+    Auto,
+}
+
+/// How the item list is drawn.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum ItemLayout {
+    /// One dense row per item, for wide screens.
+    #[default]
+    Table,
+    /// A card per item with large buttons, for phones.
+    Cards,
+}
+
+/// Quick filters of the index page.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemFilter {
+    #[default]
+    All,
+    /// Below the restock threshold.
+    Low,
+    /// Quantity is zero.
+    Out,
+    /// Has a batch that is expired or expires within `EXPIRY_WARNING_DAYS`.
+    Expiring,
+}
+
+impl ItemFilter {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ItemFilter::All => "all",
+            ItemFilter::Low => "low",
+            ItemFilter::Out => "out",
+            ItemFilter::Expiring => "expiring",
+        }
+    }
+}
+
+/// Body of `PUT /api/categories/{id}/collapsed`.
+#[derive(Debug, Deserialize)]
+pub struct CollapseCategoryPayload {
+    pub collapsed: bool,
+}
+
+/// Query string of the index page.
+#[derive(Debug, Deserialize)]
+pub struct IndexQuery {
+    pub sort: Option<ItemSort>,
+    #[serde(default)]
+    pub filter: ItemFilter,
+    /// Draws this one page with another layout than the saved one.
+    pub layout: Option<ItemLayout>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct UserPreferences {
+    pub group_by_category: bool,
+    pub sort: ItemSort,
+    pub language: Language,
+    pub theme: Theme,
+    #[serde(default)]
+    pub layout: ItemLayout,
+    pub default_location: Option<String>,
+    /// Emails a summary of each month at its end.
+    #[serde(default)]
+    pub monthly_summary: bool,
+    /// Days below the restock threshold after which an item is urgent and
+    /// its alert goes out again; 0 for never.
+    #[serde(default)]
+    pub restock_escalation_days: i32,
+    /// Categories shown folded on the item list, with only their header.
+    #[serde(default)]
+    pub collapsed_category_ids: Vec<i32>,
+    /// IANA name of the zone pages show times in, e.g. `Europe/Warsaw`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        UserPreferences {
+            group_by_category: true,
+            sort: ItemSort::default(),
+            language: Language::default(),
+            theme: Theme::default(),
+            layout: ItemLayout::default(),
+            default_location: None,
+            monthly_summary: false,
+            restock_escalation_days: 0,
+            collapsed_category_ids: Vec::new(),
+            timezone: default_timezone(),
+        }
+    }
+}
+
+impl UserPreferences {
+    /// Applies the fields present in `payload`; an empty `default_location` clears it.
+    pub fn apply(&mut self, payload: UpdatePreferencesPayload) {
+        if let Some(group_by_category) = payload.group_by_category {
+            self.group_by_category = group_by_category;
+        }
+        if let Some(sort) = payload.sort {
+            self.sort = sort;
+        }
+        if let Some(language) = payload.language {
+            self.language = language;
+        }
+        if let Some(theme) = payload.theme {
+            self.theme = theme;
+        }
+        if let Some(layout) = payload.layout {
+            self.layout = layout;
+        }
+        if let Some(monthly_summary) = payload.monthly_summary {
+            self.monthly_summary = monthly_summary;
+        }
+        if let Some(days) = payload.restock_escalation_days {
+            self.restock_escalation_days = days;
+        }
+        if let Some(timezone) = payload.timezone {
+            self.timezone = timezone;
+        }
+        if let Some(location) = payload.default_location {
+            let location = location.trim();
+            self.default_location = (!location.is_empty()).then(|| location.to_string());
+        }
+    }
+}
+
+/// Body of `PUT /api/preferences` and of the settings form. Missing fields keep
+/// their current value.
+#[derive(Debug, Deserialize, Default)]
+pub struct UpdatePreferencesPayload {
+    pub group_by_category: Option<bool>,
+    pub sort: Option<ItemSort>,
+    pub language: Option<Language>,
+    pub theme: Option<Theme>,
+    pub layout: Option<ItemLayout>,
+    pub default_location: Option<String>,
+    pub monthly_summary: Option<bool>,
+    pub restock_escalation_days: Option<i32>,
+    pub timezone: Option<String>,
+}
+
+impl Validate for UpdatePreferencesPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.optional_text(
+            "default_location",
+            self.default_location.as_deref(),
+            MAX_TEXT_LEN,
+        );
+        errors.non_negative("restock_escalation_days", self.restock_escalation_days);
+        if self
+            .restock_escalation_days
+            .is_some_and(|days| days > MAX_ESCALATION_DAYS)
+        {
+            errors.add(
+                "restock_escalation_days",
+                format!("must be at most {MAX_ESCALATION_DAYS}"),
+            );
+        }
+        if let Some(timezone) = &self.timezone
+            && !filters::is_timezone(timezone)
+        {
+            errors.add("timezone", "must be a time zone such as Europe/Warsaw");
+        }
+        errors.into_result()
+    }
+}
+
+const MAX_ESCALATION_DAYS: i32 = 365;
@@ -0,0 +1,171 @@
+//! Item changes published to an MQTT broker, for smart home dashboards that
+//! would rather be told than poll the API. Each change to an item's quantity
+//! goes out as it is recorded in the history, items that run low are
+//! announced, and a retained summary of the stock is kept up to date:
+//!
+//! - `{prefix}/{user_id}/events`: every item event, as `GET /api/history`
+//!   lists them;
+//! - `{prefix}/{user_id}/low_stock`: an item that has just dropped below its
+//!   restock threshold;
+//! - `{prefix}/{user_id}/status` (retained): the counts of
+//!   `GET /api/ha/sensors`.
+//!
+//! The events are read from the database every few seconds, by one instance
+//! at a time, so each is published once however many instances there are.
+
+use crate::config::MqttSettings;
+use crate::{db, home_assistant, scheduler};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS, TlsConfiguration, Transport};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// How often new events are looked for.
+pub const PUBLISH_PERIOD: Duration = Duration::from_secs(5);
+/// Most events published in one run; the rest wait for the next.
+const BATCH_SIZE: i64 = 200;
+/// Messages the client holds while they are being sent.
+const QUEUE_SIZE: usize = 1000;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: String,
+    pub retain: bool,
+}
+
+/// Messages for the events after `after_id`, and the ID of the last one
+/// they cover; `None` when there are no new events.
+pub async fn next_batch(
+    pool: &PgPool,
+    topic_prefix: &str,
+    after_id: i32,
+) -> Result<Option<(Vec<Message>, i32)>, Box<dyn std::error::Error + Send + Sync>> {
+    let events = db::get_item_events_after(pool, after_id, BATCH_SIZE).await?;
+    let Some(last_id) = events.last().map(|(_, event)| event.id) else {
+        return Ok(None);
+    };
+
+    let mut messages = Vec::new();
+    // Per user, how much each item changed in this batch
+    let mut changes: BTreeMap<i32, BTreeMap<i32, i32>> = BTreeMap::new();
+    for (user_id, event) in &events {
+        messages.push(Message {
+            topic: format!("{topic_prefix}/{user_id}/events"),
+            payload: serde_json::to_string(event)?,
+            retain: false,
+        });
+        let items = changes.entry(*user_id).or_default();
+        if let Some(item_id) = event.item_id {
+            *items.entry(item_id).or_default() += event.quantity_delta;
+        }
+    }
+    for (user_id, items) in changes {
+        for item in db::get_items_to_restock(pool, user_id).await? {
+            // Low now, but not before this batch
+            let low_before = items
+                .get(&item.id)
+                .is_none_or(|delta| item.quantity - delta < item.restock_threshold);
+            if !low_before {
+                messages.push(Message {
+                    topic: format!("{topic_prefix}/{user_id}/low_stock"),
+                    payload: serde_json::to_string(&item)?,
+                    retain: false,
+                });
+            }
+        }
+        let sensors = home_assistant::sensors(pool, user_id).await?;
+        messages.push(Message {
+            topic: format!("{topic_prefix}/{user_id}/status"),
+            payload: serde_json::to_string(&sensors)?,
+            retain: true,
+        });
+    }
+    Ok(Some((messages, last_id)))
+}
+
+/// Connects to the broker, keeping the connection up in the background, and
+/// publishes new events every `PUBLISH_PERIOD` while it is connected.
+pub fn spawn_publisher(pool: PgPool, settings: MqttSettings) {
+    // Tells instances apart, as the broker drops a client whose ID reconnects
+    let client_id = format!(
+        "household-inventory-{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos()
+    );
+    let mut options = MqttOptions::new(client_id, settings.host.as_str(), settings.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &settings.username {
+        options.set_credentials(username, settings.password.as_deref().unwrap_or_default());
+    }
+    if settings.tls {
+        // sqlx and reqwest already bring in ring, so it does this TLS too
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let roots = rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        options.set_transport(Transport::tls_with_config(TlsConfiguration::Rustls(
+            Arc::new(config),
+        )));
+    }
+    let (client, mut event_loop) = AsyncClient::new(options, QUEUE_SIZE);
+
+    let connected = Arc::new(AtomicBool::new(false));
+    let status = connected.clone();
+    let broker = format!("{}:{}", settings.host, settings.port);
+    tokio::spawn(async move {
+        loop {
+            match event_loop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    tracing::info!("connected to the MQTT broker at {}", broker);
+                    status.store(true, Ordering::Relaxed);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    if status.swap(false, Ordering::Relaxed) {
+                        tracing::warn!("lost the MQTT broker at {}: {}", broker, e);
+                    } else {
+                        tracing::debug!("cannot reach the MQTT broker at {}: {}", broker, e);
+                    }
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    });
+
+    let topic_prefix = settings.topic_prefix;
+    scheduler::spawn_exclusive("mqtt", PUBLISH_PERIOD, pool.clone(), move || {
+        let pool = pool.clone();
+        let client = client.clone();
+        let connected = connected.clone();
+        let topic_prefix = topic_prefix.clone();
+        async move {
+            // Left for an instance that is connected, or for later
+            if !connected.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let after_id = db::get_mqtt_cursor(&pool).await?;
+            let Some((messages, last_id)) = next_batch(&pool, &topic_prefix, after_id).await?
+            else {
+                return Ok(());
+            };
+            for message in &messages {
+                client.try_publish(
+                    &message.topic,
+                    QoS::AtLeastOnce,
+                    message.retain,
+                    message.payload.as_bytes(),
+                )?;
+            }
+            db::set_mqtt_cursor(&pool, last_id).await?;
+            tracing::debug!("published {} MQTT messages", messages.len());
+            Ok(())
+        }
+    });
+}
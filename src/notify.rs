@@ -0,0 +1,369 @@
+//! Restock and expiry alerts sent out of the app, through the channels each
+//! user sets up in the settings. Rather than talking to every push service,
+//! a channel is either an Apprise API server, which reaches Discord, Slack,
+//! Pushover and dozens more, or a plain URL that takes the message as a
+//! POST, such as an ntfy topic or a home automation webhook. Matrix rooms
+//! are posted to directly, as households often coordinate in one already.
+//!
+//! A background job looks at every user with a channel every few minutes
+//! and sends what is new since the last look. Each alert goes out once while
+//! it lasts: an item that runs low is announced again only after it has been
+//! restocked and runs low again. Users can have it escalate instead: an item
+//! still low after their `restock_escalation_days` becomes urgent and is
+//! announced again each time that many more days pass, also to the channels
+//! kept for urgent alerts only, such as another household member's phone.
+//!
+//! Users pick quiet hours in which nothing is sent, and a channel can take a
+//! daily digest of everything that needs attention instead of each alert as
+//! it comes up (`NotificationSettings`, `ChannelDelivery`).
+
+use crate::models::{
+    ChannelDelivery, ChannelKind, CreateNotificationChannelPayload, ExpiringBatch, Item,
+    NotificationChannel, NotificationSettings, UpdateNotificationSettingsPayload, UrgentRestock,
+};
+use crate::{AppState, db, errors::AppError, scheduler, validation::Validate};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::time::Duration;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
+use time_tz::{OffsetDateTimeExt, timezones};
+
+/// How often the job looks for new alerts.
+pub const CHECK_PERIOD: Duration = Duration::from_secs(300);
+/// How long a channel has to take a message.
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+const TITLE: &str = "Inwentarz";
+/// First line of a digest.
+const DIGEST_HEADING: &str = "Podsumowanie dnia:";
+
+/// Something the user should hear about once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    /// Tells the alert apart from others while it lasts, e.g. `restock:12`.
+    pub key: String,
+    pub message: String,
+    /// Also goes to the channels that only get urgent alerts.
+    pub urgent: bool,
+}
+
+/// The alerts for items below their restock threshold and batches that
+/// expire soon or did already, in the wording of the page banner. Items in
+/// `urgent` get one more alert for every `escalation_days` they wait.
+pub fn alerts(
+    to_restock: &[Item],
+    urgent: &[UrgentRestock],
+    escalation_days: i32,
+    expiring: &[ExpiringBatch],
+    today: Date,
+) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+    for item in to_restock {
+        alerts.push(Alert {
+            key: format!("restock:{}", item.id),
+            message: format!(
+                "Potrzeba uzupełnienia: {}. Aktualna ilość: {}, próg uzupełnienia: {}. Kup {}!",
+                item.name, item.quantity, item.restock_threshold, item.suggested_purchase
+            ),
+            urgent: false,
+        });
+        if let Some(restock) = urgent.iter().find(|u| u.item_id == item.id) {
+            let round = restock.days_low / escalation_days.max(1);
+            alerts.push(Alert {
+                key: format!("urgent:{}:{round}", item.id),
+                message: format!(
+                    "PILNE: {} czeka na uzupełnienie od {} dni. Aktualna ilość: {}. Kup {}!",
+                    item.name, restock.days_low, item.quantity, item.suggested_purchase
+                ),
+                urgent: true,
+            });
+        }
+    }
+    alerts.extend(expiring.iter().map(|batch| {
+        let (key, status) = if batch.expires_on < today {
+            ("expired", "przeterminowane od")
+        } else {
+            ("expiry", "ważne do")
+        };
+        Alert {
+            // An expiring batch is announced again on the day it expires
+            key: format!("{key}:{}", batch.id),
+            message: format!(
+                "Kończy się termin ważności: {}. {} szt. {} {}",
+                batch.item_name, batch.quantity, status, batch.expires_on
+            ),
+            urgent: false,
+        }
+    }));
+    alerts
+}
+
+/// Why a message didn't go through.
+#[derive(Debug)]
+pub struct SendError(String);
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SendError {}
+
+impl From<reqwest::Error> for SendError {
+    fn from(e: reqwest::Error) -> Self {
+        // Without the URL, which may hold a token and ends up on the page
+        SendError(e.without_url().to_string())
+    }
+}
+
+pub fn client() -> Result<reqwest::Client, SendError> {
+    Ok(reqwest::Client::builder().timeout(SEND_TIMEOUT).build()?)
+}
+
+/// Sends one message through `channel`.
+pub async fn send(
+    client: &reqwest::Client,
+    channel: &NotificationChannel,
+    message: &str,
+) -> Result<(), SendError> {
+    let request = match channel.kind {
+        // The body of Apprise's `/notify/{key}`; `type` picks the icon
+        ChannelKind::Apprise => client.post(channel.url.trim()).json(&serde_json::json!({
+            "title": TITLE,
+            "body": message,
+            "type": "warning",
+        })),
+        ChannelKind::Url => client
+            .post(fill_template(channel.url.trim(), TITLE, message))
+            .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(message.to_string()),
+        ChannelKind::Matrix => {
+            let room_id = channel.room_id.as_deref().unwrap_or_default();
+            // Lets the homeserver drop a message retried after a timeout
+            let transaction_id = format!(
+                "inventory-{}-{}",
+                channel.id,
+                OffsetDateTime::now_utc().unix_timestamp_nanos()
+            );
+            let url = format!(
+                "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{transaction_id}",
+                channel.url.trim().trim_end_matches('/'),
+                utf8_percent_encode(room_id, NON_ALPHANUMERIC)
+            );
+            client
+                .put(url)
+                .bearer_auth(channel.access_token.as_deref().unwrap_or_default())
+                .json(&serde_json::json!({
+                    "msgtype": "m.text",
+                    "body": format!("{TITLE}\n{message}"),
+                }))
+        }
+    };
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+/// `{title}` and `{message}` in the URL, percent-encoded.
+fn fill_template(url: &str, title: &str, message: &str) -> String {
+    let encode = |text: &str| utf8_percent_encode(text, NON_ALPHANUMERIC).to_string();
+    url.replace("{title}", &encode(title))
+        .replace("{message}", &encode(message))
+}
+
+/// Validates and stores a new channel for `user_id`. The public demo takes
+/// none, as it would let anyone make the server send requests anywhere.
+pub async fn create_channel(
+    state: &AppState,
+    user_id: i32,
+    payload: CreateNotificationChannelPayload,
+) -> Result<NotificationChannel, AppError> {
+    payload.validate()?;
+    if state.demo_mode {
+        return Err(AppError::BadRequest(
+            "Notification channels are disabled in the demo".into(),
+        ));
+    }
+    Ok(db::create_notification_channel(&state.db_pool, user_id, &payload).await?)
+}
+
+/// Validates and stores the user's quiet hours and digest time.
+pub async fn update_settings(
+    pool: &PgPool,
+    user_id: i32,
+    payload: UpdateNotificationSettingsPayload,
+) -> Result<NotificationSettings, AppError> {
+    payload.validate()?;
+    Ok(db::update_notification_settings(pool, user_id, &payload.settings()).await?)
+}
+
+/// Sends a test message through one of the user's channels, noting the
+/// result like a real one.
+pub async fn test_channel(pool: &PgPool, user_id: i32, channel_id: i32) -> Result<(), AppError> {
+    let channel = db::get_notification_channel(pool, user_id, channel_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Notification channel not found".into()))?;
+    let client = client().map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    let result = send(
+        &client,
+        &channel,
+        "Wiadomość testowa: powiadomienia działają.",
+    )
+    .await;
+    let error = result.as_ref().err().map(|e| e.to_string());
+    db::record_channel_attempt(pool, channel.id, error.as_deref()).await?;
+    result.map_err(|e| AppError::BadRequest(format!("Nie udało się wysłać: {e}")))
+}
+
+/// Sends the user's alerts as of `now`. Nothing goes out in their quiet
+/// hours. Otherwise the new alerts go to each channel set to `immediate` as
+/// one message, and once a day, at the first check after the digest time,
+/// all current alerts go to the channels set to `digest`. Channels for
+/// urgent alerts only get just those. An alert counts as sent once any
+/// immediate channel took it; the others note why they failed, which the
+/// settings page shows.
+pub async fn notify_user(
+    pool: &PgPool,
+    client: &reqwest::Client,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let preferences = db::get_user_preferences(pool, user_id).await?;
+    let settings = db::get_notification_settings(pool, user_id).await?;
+    let timezone = settings
+        .timezone
+        .as_deref()
+        .unwrap_or(&preferences.timezone);
+    let local = local_time(now, timezone);
+    if settings.is_quiet(local.time()) {
+        return Ok(());
+    }
+
+    let to_restock = db::get_items_to_restock(pool, user_id).await?;
+    let urgent = db::get_urgent_restocks(pool, user_id).await?;
+    let expiring = db::get_expiring_batches(pool, user_id, db::EXPIRY_WARNING_DAYS).await?;
+    let alerts = alerts(
+        &to_restock,
+        &urgent,
+        preferences.restock_escalation_days,
+        &expiring,
+        now.date(),
+    );
+    let current: Vec<String> = alerts.iter().map(|alert| alert.key.clone()).collect();
+    db::forget_sent_alerts(pool, user_id, &current).await?;
+
+    let sent: HashSet<String> = db::get_sent_alerts(pool, user_id)
+        .await?
+        .into_iter()
+        .collect();
+    let new: Vec<&Alert> = alerts
+        .iter()
+        .filter(|alert| !sent.contains(&alert.key))
+        .collect();
+    let channels = db::list_notification_channels(pool, user_id).await?;
+    let (immediate, digest): (Vec<_>, Vec<_>) = channels
+        .into_iter()
+        .partition(|channel| channel.delivery == ChannelDelivery::Immediate);
+
+    let (delivered, _) = deliver(pool, client, &immediate, &new, "").await?;
+    if !delivered.is_empty() {
+        db::mark_alerts_sent(pool, user_id, &delivered).await?;
+    }
+
+    // A new digest channel waits for the next digest time
+    let last_digest = db::get_last_digest(pool, user_id)
+        .await?
+        .or_else(|| digest.iter().map(|channel| channel.created_at).min());
+    let due = last_digest_due(local, settings.digest_time);
+    if !digest.is_empty() && last_digest.is_some_and(|last| local_time(last, timezone) < due) {
+        let all: Vec<&Alert> = alerts.iter().collect();
+        let (delivered, failed) = deliver(pool, client, &digest, &all, DIGEST_HEADING).await?;
+        // A day without alerts has no digest; one that no channel took is
+        // tried again on the next check
+        if !delivered.is_empty() || !failed {
+            db::record_digest(pool, user_id, now).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Sends `alerts` to each of `channels` as one message, after `heading` if
+/// it isn't empty. Returns the keys of those that any channel took, and
+/// whether a channel failed.
+async fn deliver(
+    pool: &PgPool,
+    client: &reqwest::Client,
+    channels: &[NotificationChannel],
+    alerts: &[&Alert],
+    heading: &str,
+) -> Result<(Vec<String>, bool), sqlx::Error> {
+    let mut delivered: Vec<String> = Vec::new();
+    let mut failed = false;
+    for channel in channels {
+        let alerts: Vec<&Alert> = alerts
+            .iter()
+            .filter(|alert| alert.urgent || !channel.escalation_only)
+            .copied()
+            .collect();
+        if alerts.is_empty() {
+            continue;
+        }
+        let message = std::iter::once(heading)
+            .filter(|heading| !heading.is_empty())
+            .chain(alerts.iter().map(|alert| alert.message.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let result = send(client, channel, &message).await;
+        if let Err(e) = &result {
+            tracing::warn!(channel = channel.id, "notification not sent: {}", e);
+            failed = true;
+        }
+        if result.is_ok() {
+            delivered.extend(alerts.iter().map(|alert| alert.key.clone()));
+        }
+        let error = result.err().map(|e| e.to_string());
+        db::record_channel_attempt(pool, channel.id, error.as_deref()).await?;
+    }
+    delivered.sort_unstable();
+    delivered.dedup();
+    Ok((delivered, failed))
+}
+
+/// `at` on the wall clock of the zone named `timezone`, or in UTC for a name
+/// that isn't one.
+fn local_time(at: OffsetDateTime, timezone: &str) -> PrimitiveDateTime {
+    let at = match timezones::get_by_name(timezone) {
+        Some(tz) => at.to_timezone(tz),
+        None => at,
+    };
+    PrimitiveDateTime::new(at.date(), at.time())
+}
+
+/// The last time the digest was due at or before `local`: today's digest
+/// time, or yesterday's if that hasn't come yet.
+fn last_digest_due(local: PrimitiveDateTime, digest_time: Time) -> PrimitiveDateTime {
+    let today = local.replace_time(digest_time);
+    if today <= local {
+        today
+    } else {
+        today - time::Duration::DAY
+    }
+}
+
+/// Sends new alerts every `CHECK_PERIOD`, from one instance at a time. A user
+/// whose alerts fail to load is tried again on the next run.
+pub fn spawn_dispatcher(pool: PgPool) {
+    scheduler::spawn_exclusive("notifications", CHECK_PERIOD, pool.clone(), move || {
+        let pool = pool.clone();
+        async move {
+            let client = client()?;
+            let now = OffsetDateTime::now_utc();
+            for user_id in db::get_users_with_notification_channels(&pool).await? {
+                if let Err(e) = notify_user(&pool, &client, user_id, now).await {
+                    tracing::error!(user_id, "notifications failed: {}", e);
+                }
+            }
+            Ok(())
+        }
+    });
+}
@@ -0,0 +1,85 @@
+//! Keyset pagination for long, newest-first lists such as the item history.
+//! A page ends with a cursor naming its last row; the next page is whatever
+//! sorts after it, so deep pages cost as little as the first one and rows
+//! added in the meantime don't shift the pages around as with `OFFSET`.
+
+use crate::errors::AppError;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    /// `next_cursor` of the previous page.
+    pub after: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl PageQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    pub fn cursor(&self) -> Result<Option<Cursor>, AppError> {
+        self.after.as_deref().map(Cursor::decode).transpose()
+    }
+}
+
+/// Position of a row in a list sorted by `created_at` and then `id`, both
+/// descending. Clients get it as an opaque string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: OffsetDateTime,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        // PostgreSQL keeps microseconds, so that is all a cursor needs
+        let micros = self.created_at.unix_timestamp_nanos() / 1000;
+        URL_SAFE_NO_PAD.encode(format!("{}.{}", micros, self.id))
+    }
+
+    pub fn decode(value: &str) -> Result<Cursor, AppError> {
+        let invalid = || AppError::BadRequest("invalid cursor".to_string());
+        let decoded = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (micros, id) = decoded.split_once('.').ok_or_else(invalid)?;
+        let micros: i128 = micros.parse().map_err(|_| invalid())?;
+        Ok(Cursor {
+            created_at: OffsetDateTime::from_unix_timestamp_nanos(micros * 1000)
+                .map_err(|_| invalid())?,
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// One page of a list; `next_cursor` is `None` on the last page.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// `rows` should be fetched with a limit of `limit + 1`: the extra row
+    /// only tells whether there is another page and is dropped.
+    pub fn new(mut rows: Vec<T>, limit: i64, cursor_of: impl Fn(&T) -> Cursor) -> Page<T> {
+        let limit = usize::try_from(limit).unwrap_or(0);
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|row| cursor_of(row).encode())
+        } else {
+            None
+        };
+        Page {
+            items: rows,
+            next_cursor,
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! A small PDF writer for printable lists: lines of text on A4 pages, broken
+//! across as many pages as they need. It uses the standard Helvetica fonts,
+//! which every viewer has, so nothing is embedded; their glyphs cover the
+//! Polish letters, which a custom encoding maps to otherwise unused codes.
+//!
+//! Label sheets place their text, color bars and QR codes at fixed spots
+//! instead, with the `*_at` methods, starting each sheet with `new_page`.
+
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+/// Longer lines are cut, as nothing measures the text.
+const MAX_LINE_CHARS: usize = 90;
+
+/// Codes 128 to 143 of the encoding, in place of characters the lists don't
+/// need (the euro sign, `‚`, `ƒ` and so on).
+const POLISH_GLYPHS: [(char, &str); 16] = [
+    ('Ą', "Aogonek"),
+    ('ą', "aogonek"),
+    ('Ć', "Cacute"),
+    ('ć', "cacute"),
+    ('Ę', "Eogonek"),
+    ('ę', "eogonek"),
+    ('Ł', "Lslash"),
+    ('ł', "lslash"),
+    ('Ń', "Nacute"),
+    ('ń', "nacute"),
+    ('Ś', "Sacute"),
+    ('ś', "sacute"),
+    ('Ź', "Zacute"),
+    ('ź', "zacute"),
+    ('Ż', "Zdotaccent"),
+    ('ż', "zdotaccent"),
+];
+
+#[derive(Clone, Copy)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// Pages being written; `finish` turns them into the file.
+pub struct Document {
+    /// Content streams of the pages before the current one.
+    pages: Vec<String>,
+    content: String,
+    /// Baseline of the next line, from the bottom of the page.
+    y: f32,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Document {
+            pages: vec![],
+            content: String::new(),
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+}
+
+impl Document {
+    pub fn new() -> Document {
+        Document::default()
+    }
+
+    pub fn title(&mut self, text: &str) {
+        self.line(text, Font::Bold, 18.0, 8.0, false);
+    }
+
+    /// A heading above headings, kept with the heading and line after it.
+    pub fn section(&mut self, text: &str) {
+        self.make_room(16.0 * 1.5 + 13.0 * 1.5 + 11.0 * 1.5);
+        self.line(text, Font::Bold, 16.0, 12.0, false);
+    }
+
+    /// A bold line, kept on the same page as the line after it.
+    pub fn heading(&mut self, text: &str) {
+        self.make_room(13.0 * 1.5 + 11.0 * 1.5);
+        self.line(text, Font::Bold, 13.0, 6.0, false);
+    }
+
+    /// A line with an empty box in front, to tick off with a pen.
+    pub fn checkbox(&mut self, text: &str) {
+        self.line(text, Font::Regular, 11.0, 0.0, true);
+    }
+
+    pub fn text(&mut self, text: &str) {
+        self.line(text, Font::Regular, 11.0, 0.0, false);
+    }
+
+    /// `space_before` is dropped at the top of a page.
+    fn line(&mut self, text: &str, font: Font, size: f32, space_before: f32, checkbox: bool) {
+        let height = size * 1.5;
+        self.make_room(height + space_before);
+        if !self.content.is_empty() {
+            self.y -= space_before;
+        }
+        self.y -= size;
+        let mut x = MARGIN;
+        if checkbox {
+            self.content
+                .push_str(&format!("0.6 w {:.1} {:.1} 8 8 re S\n", x, self.y - 0.5));
+            x += 16.0;
+        }
+        self.content.push_str(&format!(
+            "BT /{} {} Tf {:.1} {:.1} Td ({}) Tj ET\n",
+            font.resource(),
+            size,
+            x,
+            self.y,
+            encode(text, MAX_LINE_CHARS)
+        ));
+        self.y -= height - size;
+    }
+
+    /// Ends the current page, unless nothing is on it yet.
+    pub fn new_page(&mut self) {
+        self.make_room(PAGE_HEIGHT);
+    }
+
+    /// Text with its baseline starting at `x`, `y`, in points from the
+    /// bottom left corner of the page. Longer text is cut at `max_chars`.
+    pub fn text_at(&mut self, x: f32, y: f32, font: Font, size: f32, text: &str, max_chars: usize) {
+        self.content.push_str(&format!(
+            "BT /{} {} Tf {:.1} {:.1} Td ({}) Tj ET\n",
+            font.resource(),
+            size,
+            x,
+            y,
+            encode(text, max_chars)
+        ));
+    }
+
+    /// A filled rectangle with its bottom left corner at `x`, `y`.
+    pub fn rect_at(&mut self, x: f32, y: f32, width: f32, height: f32, [r, g, b]: [u8; 3]) {
+        let channel = |value: u8| f32::from(value) / 255.0;
+        self.content.push_str(&format!(
+            "{:.3} {:.3} {:.3} rg {:.2} {:.2} {:.2} {:.2} re f 0 g\n",
+            channel(r),
+            channel(g),
+            channel(b),
+            x,
+            y,
+            width,
+            height
+        ));
+    }
+
+    /// A square grid of `width` × `width` modules, such as a QR code, listed
+    /// row by row from the top; the dark ones are filled in black. `size` is
+    /// the side of the whole grid.
+    pub fn modules_at(&mut self, x: f32, y: f32, size: f32, width: usize, dark: &[bool]) {
+        if width == 0 {
+            return;
+        }
+        let module = size / width as f32;
+        for (row, modules) in dark.chunks(width).enumerate() {
+            let bottom = y + size - (row + 1) as f32 * module;
+            // One rectangle per run of dark modules keeps the file small
+            let mut column = 0;
+            while column < width {
+                if !modules[column] {
+                    column += 1;
+                    continue;
+                }
+                let start = column;
+                while column < width && modules[column] {
+                    column += 1;
+                }
+                self.content.push_str(&format!(
+                    "{:.2} {:.2} {:.2} {:.2} re\n",
+                    x + start as f32 * module,
+                    bottom,
+                    (column - start) as f32 * module,
+                    module
+                ));
+            }
+        }
+        self.content.push_str("f\n");
+    }
+
+    /// Starts a new page unless `height` still fits on this one.
+    fn make_room(&mut self, height: f32) {
+        if self.y - height < MARGIN && !self.content.is_empty() {
+            self.pages.push(std::mem::take(&mut self.content));
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.pages.push(self.content);
+        let differences: Vec<String> = POLISH_GLYPHS
+            .iter()
+            .map(|(_, glyph)| format!("/{glyph}"))
+            .collect();
+        let font = |name: &str| {
+            format!("<< /Type /Font /Subtype /Type1 /BaseFont /{name} /Encoding 3 0 R >>")
+        };
+        // Catalog, page tree, encoding and fonts come first, then each page
+        // followed by its content stream
+        let kids: Vec<String> = (0..self.pages.len())
+            .map(|i| format!("{} 0 R", 6 + 2 * i))
+            .collect();
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                self.pages.len()
+            ),
+            format!(
+                "<< /Type /Encoding /BaseEncoding /WinAnsiEncoding /Differences [128 {}] >>",
+                differences.join(" ")
+            ),
+            font("Helvetica"),
+            font("Helvetica-Bold"),
+        ];
+        for (i, content) in self.pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                 /Resources << /Font << /F1 4 0 R /F2 5 0 R >> >> /Contents {} 0 R >>",
+                7 + 2 * i
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{content}\nendstream",
+                content.len()
+            ));
+        }
+
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n{object}\nendobj\n", i + 1).as_bytes());
+        }
+        let xref = out.len();
+        out.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        out.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+                objects.len() + 1
+            )
+            .as_bytes(),
+        );
+        out
+    }
+}
+
+/// The first `max_chars` of the text as the inside of a PDF string in the
+/// fonts' encoding; characters it lacks become `?`.
+fn encode(text: &str, max_chars: usize) -> String {
+    let mut out = String::new();
+    for c in text.chars().take(max_chars) {
+        let code = match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+                continue;
+            }
+            ' '..='~' => c as u32,
+            // Latin-1 letters such as `ó` sit at the same codes
+            '\u{a0}'..='\u{ff}' => c as u32,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '…' => 0x85,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => match POLISH_GLYPHS.iter().position(|(polish, _)| *polish == c) {
+                Some(i) => 0x80 + i as u32,
+                None => '?' as u32,
+            },
+        };
+        if code < 0x80 {
+            out.push(char::from_u32(code).unwrap_or('?'));
+        } else {
+            out.push_str(&format!("\\{code:03o}"));
+        }
+    }
+    out
+}
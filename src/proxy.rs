@@ -0,0 +1,154 @@
+//! Who is on the other end of a request when the app runs behind a reverse
+//! proxy such as nginx or Caddy. The connection then comes from the proxy, and
+//! the client's address and scheme are in `X-Forwarded-For` and
+//! `X-Forwarded-Proto`, which are only believed from the proxies named in
+//! `trusted_proxies` (or over a Unix socket, which only the proxy can reach);
+//! anyone else could send them to pose as another address.
+
+use crate::{
+    AppState,
+    config::{Config, Listen},
+};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{HeaderMap, HeaderName, Request, header, request::Parts, uri::Authority},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+#[derive(Debug, Clone, Default)]
+pub struct ProxySettings {
+    pub trusted: Vec<IpNet>,
+    /// Connections without an address come through the app's Unix socket.
+    pub trust_unix_socket: bool,
+    /// The server speaks HTTPS itself.
+    pub https: bool,
+}
+
+impl ProxySettings {
+    pub fn from_config(config: &Config) -> ProxySettings {
+        ProxySettings {
+            trusted: config.trusted_proxies.clone(),
+            trust_unix_socket: matches!(config.listen, Listen::Unix(_)),
+            https: config.tls.is_some(),
+        }
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|net| net.contains(&ip))
+    }
+
+    /// The client of a request from `peer`, the address of the connection.
+    pub fn client(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Client {
+        let from_proxy = match peer {
+            Some(ip) => self.trusts(ip),
+            None => self.trust_unix_socket,
+        };
+        if !from_proxy {
+            return Client {
+                ip: peer,
+                https: self.https,
+            };
+        }
+        // Each proxy appends the address it got the request from, so the
+        // client is the last one that isn't a trusted proxy
+        let mut ip = peer;
+        for entry in forwarded_values(headers, &X_FORWARDED_FOR).rev() {
+            match entry.parse::<IpAddr>() {
+                Ok(forwarded) => {
+                    ip = Some(forwarded);
+                    if !self.trusts(forwarded) {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        // Set by the proxy nearest to the app, which is the one that counts
+        let https = match forwarded_values(headers, &X_FORWARDED_PROTO).next_back() {
+            Some(proto) => proto.eq_ignore_ascii_case("https"),
+            None => self.https,
+        };
+        Client { ip, https }
+    }
+}
+
+/// The comma-separated entries of every `name` header, in order.
+fn forwarded_values<'a>(
+    headers: &'a HeaderMap,
+    name: &HeaderName,
+) -> impl DoubleEndedIterator<Item = &'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// The client as far as it can be told; put into each request by `resolve`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Client {
+    /// `None` over a Unix socket with no proxy header to go by.
+    pub ip: Option<IpAddr>,
+    /// The browser talks HTTPS, to the app or to the proxy in front of it.
+    pub https: bool,
+}
+
+impl Client {
+    /// Scheme and host the browser reached the app at, such as
+    /// `https://inventory.example.com`, for links that leave the page, like
+    /// the ones in QR codes. `None` without a valid `Host` header.
+    pub fn origin(&self, headers: &HeaderMap) -> Option<String> {
+        let host = headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.parse::<Authority>().ok())?;
+        let scheme = if self.https { "https" } else { "http" };
+        Some(format!("{scheme}://{host}"))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Client {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Client>()
+            .copied()
+            .unwrap_or_default())
+    }
+}
+
+/// Works out the request's `Client` and adds its address to the log lines.
+pub async fn resolve(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    // Only there when served with `into_make_service_with_connect_info`
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    let client = state.proxy.client(peer, req.headers());
+    if let Some(ip) = client.ip {
+        tracing::Span::current().record("client_ip", tracing::field::display(ip));
+    }
+    req.extensions_mut().insert(client);
+    next.run(req).await
+}
@@ -0,0 +1,38 @@
+//! The purchase history: every purchase with what it cost and in which
+//! store, to see how an item's price changes over time and where it is
+//! cheapest. Prices are optional; purchases made before they were recorded
+//! have none.
+
+use crate::models::{Purchase, StorePrice};
+use crate::pagination::{Page, PageQuery};
+use crate::{AppState, db, errors::AppError};
+
+/// One page of the purchases of `user_id`, or of one of their items.
+pub async fn page(
+    state: &AppState,
+    user_id: i32,
+    item_id: Option<i32>,
+    query: &PageQuery,
+) -> Result<Page<Purchase>, AppError> {
+    let limit = query.limit();
+    let cursor = query.cursor()?;
+    if let Some(item_id) = item_id {
+        db::get_item_fingerprint(&state.db_pool, user_id, item_id)
+            .await?
+            .ok_or(AppError::ItemNotFound)?;
+    }
+    let purchases = db::get_purchases(&state.db_pool, user_id, item_id, cursor, limit + 1).await?;
+    Ok(Page::new(purchases, limit, Purchase::cursor))
+}
+
+/// What the item costs per unit in each store it was bought in.
+pub async fn store_prices(
+    state: &AppState,
+    user_id: i32,
+    item_id: i32,
+) -> Result<Vec<StorePrice>, AppError> {
+    db::get_item_fingerprint(&state.db_pool, user_id, item_id)
+        .await?
+        .ok_or(AppError::ItemNotFound)?;
+    Ok(db::get_store_prices(&state.db_pool, user_id, item_id).await?)
+}
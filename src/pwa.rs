@@ -0,0 +1,86 @@
+//! What makes the web UI installable as an app: the manifest, and a service
+//! worker that keeps the static files and an offline page at hand. Pages
+//! themselves are never cached, as they hold the user's data and are only
+//! worth seeing fresh.
+
+use crate::{AppState, assets, errors::AppError};
+use axum::{
+    extract::State,
+    http::header,
+    response::{Html, IntoResponse},
+};
+use serde_json::json;
+use std::sync::Arc;
+use tera::Context;
+
+/// Both are generated for the base path, so browsers must ask each time.
+const REVALIDATE: &str = "no-cache";
+
+/// Matches the navigation bar and page background of `style.css`.
+const THEME_COLOR: &str = "#dbd1db";
+const BACKGROUND_COLOR: &str = "#f6f4f6";
+
+/// GET /manifest.json
+pub async fn manifest(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let base_path = &state.base_path;
+    let icon = |path: &str| {
+        format!(
+            "{base_path}{}",
+            assets::versioned_url(path).unwrap_or_default()
+        )
+    };
+    let manifest = json!({
+        "name": "Inwentarz",
+        "short_name": "Inwentarz",
+        "lang": "pl",
+        "start_url": format!("{base_path}/web"),
+        "scope": format!("{base_path}/"),
+        "display": "standalone",
+        "theme_color": THEME_COLOR,
+        "background_color": BACKGROUND_COLOR,
+        "icons": [
+            { "src": icon("icon-192.png"), "sizes": "192x192", "type": "image/png", "purpose": "any maskable" },
+            { "src": icon("icon-512.png"), "sizes": "512x512", "type": "image/png", "purpose": "any maskable" },
+            { "src": icon("icon.svg"), "sizes": "any", "type": "image/svg+xml" },
+        ],
+    });
+    (
+        [
+            (header::CONTENT_TYPE, "application/manifest+json"),
+            (header::CACHE_CONTROL, REVALIDATE),
+        ],
+        manifest.to_string(),
+    )
+}
+
+/// GET /service-worker.js. Served next to `/web`, so its scope covers the
+/// whole app.
+pub async fn service_worker(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let assets: Vec<String> = assets::versioned_urls()
+        .into_iter()
+        .map(|url| format!("{}{url}", state.base_path))
+        .collect();
+    let mut context = Context::new();
+    context.insert("base_path", &state.base_path);
+    context.insert("cache", &format!("inwentarz-{}", assets::bundle_version()));
+    context.insert("assets", &assets);
+    let script = state.tera.render("service_worker.js", context).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            (header::CACHE_CONTROL, REVALIDATE),
+        ],
+        script,
+    ))
+}
+
+/// GET /offline, which the service worker shows for pages that can't be
+/// loaded without a connection.
+pub async fn offline(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
+    let mut context = Context::new();
+    context.insert("base_path", &state.base_path);
+    let rendered = state.tera.render("offline.html", context).await?;
+    Ok(Html(rendered))
+}
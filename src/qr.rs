@@ -0,0 +1,96 @@
+//! QR codes for shelf labels. Each one holds the address of the item's
+//! confirm-and-use page, so scanning the label with a phone camera opens it
+//! and a tap takes one from the stock.
+
+use crate::errors::AppError;
+use crate::models::Item;
+use qrcode::{Color, EcLevel, QrCode, render::svg};
+use serde::Serialize;
+
+/// Pixels per module of the PNG.
+const MODULE_PIXELS: usize = 8;
+/// Light modules around the code, which scanners need to find it.
+pub const QUIET_ZONE: usize = 4;
+
+/// The page a label of `item_id` opens, on the server at `origin`.
+pub fn use_url(origin: &str, base_path: &str, item_id: i32) -> String {
+    format!("{origin}{base_path}/web/items/{item_id}/use")
+}
+
+fn encode(data: &str) -> Result<QrCode, AppError> {
+    // Medium correction survives a scuffed or creased label
+    QrCode::with_error_correction_level(data, EcLevel::M)
+        .map_err(|e| AppError::InternalServerError(format!("Cannot encode QR code: {e}")))
+}
+
+/// The code as a black and white PNG.
+pub fn png(data: &str) -> Result<Vec<u8>, AppError> {
+    let code = encode(data)?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + 2 * QUIET_ZONE) * MODULE_PIXELS;
+    let mut pixels = vec![u8::MAX; size * size];
+    for (index, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let left = (index % modules + QUIET_ZONE) * MODULE_PIXELS;
+        let top = (index / modules + QUIET_ZONE) * MODULE_PIXELS;
+        for row in top..top + MODULE_PIXELS {
+            pixels[row * size + left..row * size + left + MODULE_PIXELS].fill(0);
+        }
+    }
+
+    let encoding_error =
+        |e: png::EncodingError| AppError::InternalServerError(format!("Cannot write PNG: {e}"));
+    let mut image = Vec::new();
+    let mut encoder = png::Encoder::new(&mut image, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(encoding_error)?;
+    writer.write_image_data(&pixels).map_err(encoding_error)?;
+    writer.finish().map_err(encoding_error)?;
+    Ok(image)
+}
+
+/// The code's modules row by row, `true` for dark ones, and how many there
+/// are to a side; without the quiet zone, which is up to whatever draws it.
+pub fn modules(data: &str) -> Result<(usize, Vec<bool>), AppError> {
+    let code = encode(data)?;
+    let dark = code
+        .to_colors()
+        .into_iter()
+        .map(|color| color == Color::Dark)
+        .collect();
+    Ok((code.width(), dark))
+}
+
+/// The code as an SVG element, scaled by the page it is put in.
+pub fn svg(data: &str) -> Result<String, AppError> {
+    let image = encode(data)?
+        .render::<svg::Color>()
+        .quiet_zone(true)
+        .module_dimensions(1, 1)
+        .build();
+    // Without the XML declaration, which has no place inside HTML
+    let start = image.find("<svg").unwrap_or_default();
+    Ok(image[start..].to_string())
+}
+
+/// An item on the label sheet, with its code as SVG.
+#[derive(Debug, Serialize)]
+pub struct Label {
+    pub item: Item,
+    pub qr: String,
+}
+
+/// Labels for `items`, linking to the server at `origin`.
+pub fn labels(items: Vec<Item>, origin: &str, base_path: &str) -> Result<Vec<Label>, AppError> {
+    items
+        .into_iter()
+        .map(|item| {
+            let qr = svg(&use_url(origin, base_path, item.id))?;
+            Ok(Label { item, qr })
+        })
+        .collect()
+}
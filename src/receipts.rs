@@ -0,0 +1,139 @@
+//! Receipts for purchases: a photo or PDF attached to a `purchased` event,
+//! kept in the file store and shown next to the purchase in the item
+//! history. The type is read from the file itself, never from what the
+//! client says it is, so only pictures and PDFs are ever served back.
+
+use crate::models::Receipt;
+use crate::{AppState, auth, db, errors::AppError};
+
+/// The types receipts may have, by how their files start.
+pub fn detect(bytes: &[u8]) -> Option<&'static str> {
+    let ftyp = bytes.get(4..12);
+    if bytes.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if matches!(ftyp, Some(b"ftypheic" | b"ftypheix" | b"ftypmif1")) {
+        // What phone cameras save
+        Some("image/heic")
+    } else if bytes.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+/// Attaches `bytes` as the receipt of the user's purchase `event_id`,
+/// replacing the one it had.
+pub async fn attach(
+    state: &AppState,
+    user_id: i32,
+    event_id: i32,
+    bytes: &[u8],
+) -> Result<Receipt, AppError> {
+    if state.demo_mode {
+        return Err(AppError::BadRequest(
+            "Receipts are disabled in the demo".into(),
+        ));
+    }
+    db::get_purchase_event(&state.db_pool, user_id, event_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Purchase not found".into()))?;
+    let content_type = detect(bytes).ok_or_else(|| {
+        AppError::BadRequest("A receipt must be a JPEG, PNG, WebP or HEIC photo or a PDF".into())
+    })?;
+    let size = i32::try_from(bytes.len())
+        .map_err(|_| AppError::PayloadTooLarge("The receipt is too large".into()))?;
+
+    // A new key each time, so a download never gets half of the old file
+    // and half of the new one
+    let key = format!("receipts/{user_id}/{}", auth::random_token()?);
+    state.files.put(&key, bytes).await?;
+    let replaced =
+        match db::save_receipt(&state.db_pool, user_id, event_id, &key, content_type, size).await {
+            Ok(replaced) => replaced,
+            Err(e) => {
+                forget_file(state, &key).await;
+                return Err(e.into());
+            }
+        };
+    if let Some(replaced) = replaced {
+        forget_file(state, &replaced).await;
+    }
+    let (receipt, _) = db::get_receipt(&state.db_pool, user_id, event_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Purchase not found".into()))?;
+    Ok(receipt)
+}
+
+/// Where the file of a receipt is served from.
+pub enum ReceiptFile {
+    Bytes(Vec<u8>),
+    /// A short-lived link straight to the bucket, so the app doesn't pass
+    /// the whole file through.
+    Link(String),
+}
+
+/// The receipt of a purchase with its file.
+pub async fn download(
+    state: &AppState,
+    user_id: i32,
+    event_id: i32,
+) -> Result<(Receipt, ReceiptFile), AppError> {
+    let not_found = || AppError::NotFound("Receipt not found".into());
+    let (receipt, key) = db::get_receipt(&state.db_pool, user_id, event_id)
+        .await?
+        .ok_or_else(not_found)?;
+    let link = state
+        .files
+        .download_url(&key, &receipt.content_type, &disposition(&receipt));
+    let file = match link {
+        Some(url) => ReceiptFile::Link(url),
+        None => ReceiptFile::Bytes(state.files.get(&key).await?.ok_or_else(not_found)?),
+    };
+    Ok((receipt, file))
+}
+
+pub async fn remove(state: &AppState, user_id: i32, event_id: i32) -> Result<(), AppError> {
+    let key = db::delete_receipt(&state.db_pool, user_id, event_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Receipt not found".into()))?;
+    forget_file(state, &key).await;
+    Ok(())
+}
+
+/// The name a receipt is downloaded as, e.g. `paragon-812.jpg`.
+pub fn file_name(receipt: &Receipt) -> String {
+    let extension = match receipt.content_type.as_str() {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/webp" => "webp",
+        "image/heic" => "heic",
+        _ => "pdf",
+    };
+    format!("paragon-{}.{extension}", receipt.event_id)
+}
+
+/// `Content-Disposition` of a receipt: shown in the browser, saved under
+/// `file_name`.
+pub fn disposition(receipt: &Receipt) -> String {
+    format!("inline; filename=\"{}\"", file_name(receipt))
+}
+
+/// Deletes the files of receipts that went away with their purchases, e.g.
+/// when a backup replaced the history.
+pub async fn forget_files(state: &AppState, keys: &[String]) {
+    for key in keys {
+        forget_file(state, key).await;
+    }
+}
+
+/// Deletes a file nothing refers to any more. Failing leaves it behind,
+/// which is only wasted space, so it is logged rather than reported.
+async fn forget_file(state: &AppState, key: &str) {
+    if let Err(e) = state.files.delete(key).await {
+        tracing::warn!("cannot delete the stored file {}: {}", key, e);
+    }
+}
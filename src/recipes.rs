@@ -0,0 +1,82 @@
+use crate::models::{
+    MealPlanDay, MealPlanEntry, MissingIngredient, RecipeAvailability, RecipeIngredient,
+};
+use time::{Date, Duration};
+
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Poniedziałek",
+    "Wtorek",
+    "Środa",
+    "Czwartek",
+    "Piątek",
+    "Sobota",
+    "Niedziela",
+];
+
+/// Result of trying to cook a recipe.
+#[derive(Debug)]
+pub enum CookOutcome {
+    Cooked,
+    RecipeNotFound,
+    MissingIngredients(Vec<MissingIngredient>),
+}
+
+/// Checks the ingredients of a recipe against current stock.
+pub fn check_availability(ingredients: &[RecipeIngredient]) -> RecipeAvailability {
+    let missing: Vec<MissingIngredient> = ingredients
+        .iter()
+        .filter(|i| i.available_quantity < i.quantity)
+        .map(|i| MissingIngredient {
+            item_id: i.item_id,
+            item_name: i.item_name.clone(),
+            required: i.quantity,
+            available: i.available_quantity,
+        })
+        .collect();
+
+    let max_servings = ingredients
+        .iter()
+        .map(|i| i.available_quantity.max(0) / i.quantity)
+        .min();
+
+    RecipeAvailability {
+        can_make: missing.is_empty(),
+        max_servings,
+        missing,
+    }
+}
+
+/// Returns the Monday and Sunday of the week containing `day`.
+pub fn week_bounds(day: Date) -> (Date, Date) {
+    let monday = day - Duration::days(day.weekday().number_days_from_monday() as i64);
+    (monday, monday + Duration::days(6))
+}
+
+/// Lays out meal plan entries as seven consecutive days starting at `monday`.
+pub fn group_meals_by_day(
+    monday: Date,
+    today: Date,
+    entries: Vec<MealPlanEntry>,
+) -> Vec<MealPlanDay> {
+    let mut days: Vec<MealPlanDay> = WEEKDAY_NAMES
+        .iter()
+        .enumerate()
+        .map(|(offset, weekday)| {
+            let date = monday + Duration::days(offset as i64);
+            MealPlanDay {
+                date,
+                weekday: weekday.to_string(),
+                is_today: date == today,
+                meals: vec![],
+            }
+        })
+        .collect();
+
+    for entry in entries {
+        let offset = (entry.planned_for - monday).whole_days();
+        if let Some(day) = usize::try_from(offset).ok().and_then(|o| days.get_mut(o)) {
+            day.meals.push(entry);
+        }
+    }
+    days
+}
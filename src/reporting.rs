@@ -0,0 +1,75 @@
+//! Optional error reporting to Sentry (or a compatible service such as
+//! GlitchTip), so failures are noticed before anybody has to mention them.
+//! Only server errors and panics are sent, tagged with the route, the request
+//! ID and the signed-in user's ID; no email addresses, IPs or request bodies.
+
+use crate::config::Config;
+use axum::{body::Body, extract::MatchedPath, http::Request, middleware::Next, response::Response};
+use sentry::{ClientInitGuard, Hub, SentryFutureExt};
+use std::error::Error;
+use std::sync::Arc;
+
+/// Starts reporting when `sentry_dsn` is set; reports are sent until the
+/// returned guard is dropped, which waits for them to go out.
+pub fn init(config: &Config) -> Option<ClientInitGuard> {
+    let dsn = config.sentry_dsn.as_deref()?;
+    let guard = sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            environment: config.sentry_environment.clone().map(Into::into),
+            send_default_pii: false,
+            ..Default::default()
+        },
+    ));
+    if guard.is_enabled() {
+        tracing::info!("Reporting errors to Sentry");
+    } else {
+        tracing::warn!("SENTRY_DSN is set but invalid, not reporting errors");
+    }
+    Some(guard)
+}
+
+/// Gives each request its own scope, tagged with the route it matched, so
+/// reports of concurrent requests don't mix. Does nothing when reporting is
+/// off.
+pub async fn request_scope(req: Request<Body>, next: Next) -> Response {
+    if Hub::current().client().is_none() {
+        return next.run(req).await;
+    }
+    let hub = Arc::new(Hub::new_from_top(Hub::current()));
+    hub.configure_scope(|scope| {
+        scope.set_tag("method", req.method());
+        if let Some(route) = req.extensions().get::<MatchedPath>() {
+            scope.set_tag("route", route.as_str());
+        }
+        if let Some(request_id) = req
+            .headers()
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+        {
+            scope.set_tag("request_id", request_id);
+        }
+    });
+    next.run(req).bind_hub(hub).await
+}
+
+/// Adds the signed-in user to the request's reports, by ID only.
+pub fn set_user(user_id: i32) {
+    sentry::configure_scope(|scope| {
+        scope.set_user(Some(sentry::User {
+            id: Some(user_id.to_string()),
+            ..Default::default()
+        }))
+    });
+}
+
+/// Reports an error that made a request fail.
+pub fn capture_error(err: &(dyn Error + 'static)) {
+    sentry::capture_error(err);
+}
+
+/// Reports a failure that has only a message.
+pub fn capture_message(message: &str) {
+    sentry::capture_message(message, sentry::Level::Error);
+}
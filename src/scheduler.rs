@@ -0,0 +1,58 @@
+use crate::db;
+use sqlx::PgPool;
+use std::{error::Error, future::Future, sync::Arc, time::Duration};
+use tokio::time::{Instant, interval_at};
+
+pub type JobResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+/// Runs `job` in the background every `period`, the first time one period
+/// from now. Failures are logged and the job runs again on the next tick.
+/// Every instance of the app runs it; see `spawn_exclusive`.
+pub fn spawn_every<F, Fut>(name: &'static str, period: Duration, job: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = JobResult> + Send,
+{
+    tokio::spawn(async move {
+        let mut interval = interval_at(Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            if let Err(e) = job().await {
+                tracing::error!("scheduled job {} failed: {}", name, e);
+            }
+        }
+    });
+}
+
+/// Like `spawn_every`, for jobs that change data shared by all instances,
+/// such as cleanups: with several instances on one database, each run
+/// happens on just one of them. A run that fails is tried again on the next
+/// tick of any instance.
+pub fn spawn_exclusive<F, Fut>(name: &'static str, period: Duration, pool: PgPool, job: F)
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = JobResult> + Send,
+{
+    // Ticks of one instance drift by a little, so a run is due somewhat
+    // before a whole period has passed
+    let due_after = period.mul_f64(0.9);
+    let job = Arc::new(job);
+    spawn_every(name, period, move || {
+        let pool = pool.clone();
+        let job = job.clone();
+        async move {
+            let Some(claim) = db::claim_job(&pool, name, due_after).await? else {
+                tracing::debug!("scheduled job {} ran elsewhere", name);
+                return Ok(());
+            };
+            match job().await {
+                Ok(()) => claim.commit().await?,
+                Err(e) => {
+                    claim.rollback().await?;
+                    return Err(e);
+                }
+            }
+            Ok(())
+        }
+    });
+}
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// Starter categories used when `category_seed_file` is not configured.
+const DEFAULT_SEED: &str = include_str!("../seed/categories.toml");
+
+/// A category created for every new account.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeedCategory {
+    pub name: String,
+    pub color: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SeedFile {
+    #[serde(default)]
+    categories: Vec<SeedCategory>,
+}
+
+/// Loads the starter categories. `path` points at a TOML or JSON file (chosen
+/// by extension); an empty path disables seeding and `None` uses the built-in
+/// `seed/categories.toml`.
+pub fn load_category_seed(
+    path: Option<&str>,
+) -> Result<Vec<SeedCategory>, Box<dyn std::error::Error>> {
+    let seed = match path {
+        Some(path) if path.trim().is_empty() => return Ok(vec![]),
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("cannot read category seed file {path}: {e}"))?;
+            parse_seed(Path::new(path), &contents)?
+        }
+        None => toml::from_str(DEFAULT_SEED)?,
+    };
+    for category in &seed.categories {
+        if !crate::categories::is_hex_color(&category.color) {
+            return Err(format!(
+                "invalid color {:?} for seed category {}",
+                category.color, category.name
+            )
+            .into());
+        }
+        crate::categories::validate_icon(category.icon.as_deref())?;
+    }
+    Ok(seed.categories)
+}
+
+fn parse_seed(path: &Path, contents: &str) -> Result<SeedFile, Box<dyn std::error::Error>> {
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    if is_json {
+        Ok(serde_json::from_str(contents)?)
+    } else {
+        Ok(toml::from_str(contents)?)
+    }
+}
@@ -0,0 +1,56 @@
+//! Read-only links for people who won't register, such as a babysitter or a
+//! housemate: `/share/{token}` shows the items of some categories, or the
+//! shopping list, to anyone who has the link. The token is all it takes, so
+//! it is as long as a session's, and the owner can revoke the link from the
+//! settings page.
+
+use crate::models::{
+    CategoryWithItems, CreateShareLinkPayload, GroupedItems, ShareLink, ShareScope,
+};
+use crate::{AppState, auth, db, errors::AppError, validation::Validate};
+
+/// The categories chosen for a link and their subcategories, with their
+/// items, in the order of the item list page. Categories deleted since the
+/// link was made are simply missing.
+pub fn shared_categories(grouped: GroupedItems, category_ids: &[i32]) -> Vec<CategoryWithItems> {
+    grouped
+        .categorized
+        .into_iter()
+        .filter(|category| {
+            category_ids.contains(&category.id)
+                || category
+                    .parent_id
+                    .is_some_and(|parent_id| category_ids.contains(&parent_id))
+        })
+        .collect()
+}
+
+/// Validates and stores a new link for `user_id`.
+pub async fn create_link(
+    state: &AppState,
+    user_id: i32,
+    payload: CreateShareLinkPayload,
+) -> Result<ShareLink, AppError> {
+    payload.validate()?;
+    if payload.scope == ShareScope::Categories {
+        let categories = state.cache.categories(&state.db_pool, user_id).await?;
+        if !payload
+            .category_ids
+            .iter()
+            .all(|id| categories.iter().any(|category| category.id == *id))
+        {
+            return Err(AppError::BadRequest("Category not found".into()));
+        }
+    }
+    let token = auth::random_token()?;
+    let link = db::create_share_link(
+        &state.db_pool,
+        user_id,
+        &token,
+        &payload.label,
+        payload.scope,
+        &payload.category_ids,
+    )
+    .await?;
+    Ok(link)
+}
@@ -0,0 +1,182 @@
+//! The shopping list grouped by store and then by category, as the print
+//! page shows it and as Markdown, plain text or PDF to send to someone
+//! without an account. Stores come in the order the user visits them.
+
+use crate::models::{ShoppingListEntry, ShoppingListFormat};
+use crate::pdf;
+use serde::Serialize;
+use std::fmt::Write;
+use time::Date;
+
+/// Heading of the entries whose item has no category.
+pub const UNCATEGORIZED: &str = "Bez kategorii";
+/// Heading of the entries whose item has no preferred store, when others do.
+pub const ANY_STORE: &str = "Gdziekolwiek";
+
+#[derive(Debug, Serialize)]
+pub struct StoreSection<'a> {
+    /// `None` when no entry has a store, as a list of one shop needs no
+    /// heading.
+    pub store: Option<&'a str>,
+    pub groups: Vec<ShoppingListGroup<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShoppingListGroup<'a> {
+    pub category: &'a str,
+    pub color: Option<&'a str>,
+    pub entries: Vec<&'a ShoppingListEntry>,
+}
+
+/// Entries come sorted by category, with uncategorized ones last, so each
+/// category is one run of them.
+pub fn group_by_category(entries: &[ShoppingListEntry]) -> Vec<ShoppingListGroup<'_>> {
+    let mut groups: Vec<ShoppingListGroup> = vec![];
+    for entry in entries {
+        let category = entry.category_name.as_deref().unwrap_or(UNCATEGORIZED);
+        match groups.last_mut() {
+            Some(group) if group.category == category => group.entries.push(entry),
+            _ => groups.push(ShoppingListGroup {
+                category,
+                color: entry.category_color.as_deref(),
+                entries: vec![entry],
+            }),
+        }
+    }
+    groups
+}
+
+/// Entries come sorted by store, with the ones without a store last, and by
+/// category within each store, so each store is one run of them.
+pub fn group_by_store(entries: &[ShoppingListEntry]) -> Vec<StoreSection<'_>> {
+    let any_store = entries.iter().any(|entry| entry.store_name.is_some());
+    entries
+        .chunk_by(|a, b| a.store_name == b.store_name)
+        .map(|run| StoreSection {
+            store: match run[0].store_name.as_deref() {
+                Some(store) => Some(store),
+                None if any_store => Some(ANY_STORE),
+                None => None,
+            },
+            groups: group_by_category(run),
+        })
+        .collect()
+}
+
+/// How much to buy, e.g. `3 kg`.
+fn amount(entry: &ShoppingListEntry) -> String {
+    match entry.unit.as_deref() {
+        Some(unit) if !unit.is_empty() => format!("{} {}", entry.to_buy, unit),
+        _ => entry.to_buy.to_string(),
+    }
+}
+
+fn title(date: Date) -> String {
+    format!("Lista zakupów ({date})")
+}
+
+/// The list in `format`, with its content type and file extension.
+pub fn export(
+    entries: &[ShoppingListEntry],
+    format: ShoppingListFormat,
+    date: Date,
+) -> (&'static str, &'static str, Vec<u8>) {
+    let sections = group_by_store(entries);
+    match format {
+        ShoppingListFormat::Md => (
+            "text/markdown; charset=utf-8",
+            "md",
+            markdown(&sections, date).into_bytes(),
+        ),
+        ShoppingListFormat::Txt => (
+            "text/plain; charset=utf-8",
+            "txt",
+            text(&sections, date).into_bytes(),
+        ),
+        ShoppingListFormat::Pdf => ("application/pdf", "pdf", pdf(&sections, date)),
+    }
+}
+
+fn markdown(sections: &[StoreSection], date: Date) -> String {
+    let mut out = format!("# {}\n", title(date));
+    if sections.is_empty() {
+        out.push_str("\nNic nie trzeba kupować.\n");
+    }
+    for section in sections {
+        // Categories are one level down under a store
+        let category_level = match section.store {
+            Some(store) => {
+                let _ = write!(out, "\n## {}\n", escape_markdown(store));
+                "###"
+            }
+            None => "##",
+        };
+        for group in &section.groups {
+            let _ = write!(
+                out,
+                "\n{category_level} {}\n\n",
+                escape_markdown(group.category)
+            );
+            for entry in &group.entries {
+                let _ = writeln!(
+                    out,
+                    "- [ ] {} — {}",
+                    escape_markdown(&entry.item_name),
+                    escape_markdown(&amount(entry))
+                );
+            }
+        }
+    }
+    out
+}
+
+/// Backslash before the characters that would turn a name into formatting.
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>#|".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn text(sections: &[StoreSection], date: Date) -> String {
+    let mut out = format!("{}\n", title(date));
+    if sections.is_empty() {
+        out.push_str("\nNic nie trzeba kupować.\n");
+    }
+    for section in sections {
+        if let Some(store) = section.store {
+            let _ = write!(out, "\n=== {store} ===\n");
+        }
+        for group in &section.groups {
+            let _ = write!(out, "\n{}\n", group.category);
+            for entry in &group.entries {
+                let _ = writeln!(out, "  [ ] {}: {}", entry.item_name, amount(entry));
+            }
+        }
+    }
+    out
+}
+
+fn pdf(sections: &[StoreSection], date: Date) -> Vec<u8> {
+    let mut document = pdf::Document::new();
+    document.title(&title(date));
+    if sections.is_empty() {
+        document.text("Nic nie trzeba kupować.");
+    }
+    for section in sections {
+        if let Some(store) = section.store {
+            document.section(store);
+        }
+        for group in &section.groups {
+            document.heading(group.category);
+            for entry in &group.entries {
+                document.checkbox(&format!("{} — {}", entry.item_name, amount(entry)));
+            }
+        }
+    }
+    document.finish()
+}
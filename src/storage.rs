@@ -0,0 +1,326 @@
+//! Files users upload, such as photos of receipts, kept out of the database,
+//! which only stores the key each file is under. `FileStore` is where they
+//! go: a directory on disk (`upload_dir`), which instances behind a load
+//! balancer must share, or an S3-compatible bucket, which survives the
+//! container being rebuilt.
+
+use crate::config::{Config, S3Settings};
+use hmac::{Hmac, Mac};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::{Method, StatusCode, Url, header};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+/// How long a download link to a file in the bucket works.
+pub const DOWNLOAD_LINK_TTL: Duration = Duration::from_secs(300);
+/// Time for one request to the bucket, including the upload of the file.
+const S3_TIMEOUT: Duration = Duration::from_secs(60);
+/// Everything but the characters SigV4 leaves unencoded.
+const S3_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Why a file couldn't be stored or read.
+#[derive(Debug)]
+pub struct StorageError(String);
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<std::io::Error> for StorageError {
+    fn from(e: std::io::Error) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for StorageError {
+    fn from(e: reqwest::Error) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FileStore {
+    /// One file per key under the directory; keys with a `/` make
+    /// subdirectories.
+    Local(PathBuf),
+    /// One object per key in a bucket.
+    S3(S3Store),
+}
+
+impl FileStore {
+    pub fn from_config(config: &Config) -> Result<FileStore, StorageError> {
+        Ok(match &config.s3 {
+            Some(settings) => FileStore::S3(S3Store::new(settings.clone())?),
+            None => FileStore::Local(PathBuf::from(&config.upload_dir)),
+        })
+    }
+
+    /// Stores `bytes` under `key`, replacing what was there. Keys are made
+    /// by the app, never taken from a request.
+    pub async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), StorageError> {
+        match self {
+            FileStore::Local(root) => {
+                let path = root.join(key);
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                // Written aside and moved into place, so a reader never sees
+                // half a file
+                let partial = path.with_extension("partial");
+                tokio::fs::write(&partial, bytes).await?;
+                tokio::fs::rename(&partial, &path).await?;
+                Ok(())
+            }
+            FileStore::S3(store) => {
+                store.send(Method::PUT, key, bytes.to_vec()).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// The file under `key`, or `None` if there is none.
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        match self {
+            FileStore::Local(root) => match tokio::fs::read(root.join(key)).await {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+            FileStore::S3(store) => match store.send(Method::GET, key, Vec::new()).await {
+                Ok(response) => Ok(Some(response.bytes().await?.to_vec())),
+                Err(S3Failure::NotFound) => Ok(None),
+                Err(S3Failure::Other(e)) => Err(e),
+            },
+        }
+    }
+
+    /// Removes the file under `key`; a missing file is no error.
+    pub async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match self {
+            FileStore::Local(root) => match tokio::fs::remove_file(root.join(key)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            FileStore::S3(store) => match store.send(Method::DELETE, key, Vec::new()).await {
+                Ok(_) | Err(S3Failure::NotFound) => Ok(()),
+                Err(S3Failure::Other(e)) => Err(e),
+            },
+        }
+    }
+
+    /// A link the browser can download the file under `key` from directly,
+    /// with `content_type` and `disposition` as its headers, for
+    /// `DOWNLOAD_LINK_TTL`. `None` for files on disk, which the app serves
+    /// itself.
+    pub fn download_url(&self, key: &str, content_type: &str, disposition: &str) -> Option<String> {
+        match self {
+            FileStore::Local(_) => None,
+            FileStore::S3(store) => {
+                Some(store.presigned_url(key, content_type, disposition, OffsetDateTime::now_utc()))
+            }
+        }
+    }
+}
+
+/// Talks to the bucket over its REST API, signing each request with AWS
+/// Signature Version 4 like every S3-compatible service expects.
+#[derive(Debug, Clone)]
+pub struct S3Store {
+    settings: S3Settings,
+    client: reqwest::Client,
+}
+
+enum S3Failure {
+    NotFound,
+    Other(StorageError),
+}
+
+impl From<StorageError> for S3Failure {
+    fn from(e: StorageError) -> Self {
+        S3Failure::Other(e)
+    }
+}
+
+impl From<S3Failure> for StorageError {
+    fn from(e: S3Failure) -> Self {
+        match e {
+            S3Failure::NotFound => StorageError("no such object".into()),
+            S3Failure::Other(e) => e,
+        }
+    }
+}
+
+impl S3Store {
+    pub fn new(settings: S3Settings) -> Result<S3Store, StorageError> {
+        let client = reqwest::Client::builder().timeout(S3_TIMEOUT).build()?;
+        Ok(S3Store { settings, client })
+    }
+
+    /// `{endpoint}/{bucket}/{key}`, each segment of the key encoded.
+    fn url(&self, key: &str) -> Url {
+        let key: Vec<String> = key
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, S3_ENCODE).to_string())
+            .collect();
+        let url = format!(
+            "{}/{}/{}",
+            self.settings.endpoint,
+            utf8_percent_encode(&self.settings.bucket, S3_ENCODE),
+            key.join("/")
+        );
+        // The endpoint was checked to be an http(s) URL when loading the config
+        Url::parse(&url).expect("the object URL is valid")
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, S3Failure> {
+        let url = self.url(key);
+        let now = OffsetDateTime::now_utc();
+        let payload_hash = hex(&Sha256::digest(&body));
+        let amz_date = amz_date(now);
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{}\n\nhost:{}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{payload_hash}",
+            url.path(),
+            host(&url),
+        );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={signed_headers}, Signature={}",
+            self.settings.access_key,
+            self.scope(now),
+            self.signature(now, &canonical_request)
+        );
+
+        let response = self
+            .client
+            .request(method.clone(), url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header(header::AUTHORIZATION, authorization)
+            .body(body)
+            .send()
+            .await
+            .map_err(StorageError::from)?;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::NOT_FOUND => Err(S3Failure::NotFound),
+            status => {
+                let text = response.text().await.unwrap_or_default();
+                Err(StorageError(format!("{method} {key} answered {status}: {text}")).into())
+            }
+        }
+    }
+
+    /// A GET of `key` signed in the query string, which works without any
+    /// headers until `DOWNLOAD_LINK_TTL` after `now`. The bucket answers with
+    /// `content_type` and `disposition` instead of what it stored.
+    pub fn presigned_url(
+        &self,
+        key: &str,
+        content_type: &str,
+        disposition: &str,
+        now: OffsetDateTime,
+    ) -> String {
+        let mut url = self.url(key);
+        let credential = format!("{}/{}", self.settings.access_key, self.scope(now));
+        let expires = DOWNLOAD_LINK_TTL.as_secs().to_string();
+        // Sorted by name, as the signature needs them
+        let params = [
+            ("X-Amz-Algorithm", "AWS4-HMAC-SHA256"),
+            ("X-Amz-Credential", &credential),
+            ("X-Amz-Date", &amz_date(now)),
+            ("X-Amz-Expires", &expires),
+            ("X-Amz-SignedHeaders", "host"),
+            ("response-content-disposition", disposition),
+            ("response-content-type", content_type),
+        ];
+        let query = params
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(name, S3_ENCODE),
+                    utf8_percent_encode(value, S3_ENCODE)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        let canonical_request = format!(
+            "GET\n{}\n{query}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD",
+            url.path(),
+            host(&url)
+        );
+        let signature = self.signature(now, &canonical_request);
+        url.set_query(Some(&format!("{query}&X-Amz-Signature={signature}")));
+        url.to_string()
+    }
+
+    /// The day, region and service the signing key is for.
+    fn scope(&self, now: OffsetDateTime) -> String {
+        format!("{}/{}/s3/aws4_request", day(now), self.settings.region)
+    }
+
+    fn signature(&self, now: OffsetDateTime, canonical_request: &str) -> String {
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date(now),
+            self.scope(now),
+            hex(&Sha256::digest(canonical_request))
+        );
+        let secret = format!("AWS4{}", self.settings.secret_key);
+        let mut key = hmac(secret.as_bytes(), day(now).as_bytes());
+        for part in [self.settings.region.as_str(), "s3", "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        hex(&hmac(&key, string_to_sign.as_bytes()))
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The `Host` header reqwest sends for `url`: the port only when it isn't
+/// the scheme's default.
+fn host(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    }
+}
+
+/// E.g. `20250930T120000Z`.
+fn amz_date(now: OffsetDateTime) -> String {
+    now.format(format_description!(
+        "[year][month][day]T[hour][minute][second]Z"
+    ))
+    .expect("UTC times can be formatted")
+}
+
+/// E.g. `20250930`.
+fn day(now: OffsetDateTime) -> String {
+    now.format(format_description!("[year][month][day]"))
+        .expect("UTC times can be formatted")
+}
@@ -0,0 +1,107 @@
+//! The monthly summary email: what was used up, bought and thrown away in
+//! the month that just ended, for the users who asked for it in the
+//! settings. It goes out once a month per user, early on the 1st, from a
+//! background job that runs only when the server can send email.
+
+use crate::assets::Templates;
+use crate::db;
+use crate::mail::{Email, Mailer};
+use crate::models::{Month, MonthlySummary};
+use crate::scheduler::{self, JobResult};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tera::Context;
+use time::{Date, OffsetDateTime};
+
+/// How often the job looks for summaries to send.
+pub const CHECK_PERIOD: Duration = Duration::from_secs(3600);
+/// How many items and categories the summary lists.
+const TOP: i64 = 5;
+
+const MONTH_NAMES: [&str; 12] = [
+    "styczeń",
+    "luty",
+    "marzec",
+    "kwiecień",
+    "maj",
+    "czerwiec",
+    "lipiec",
+    "sierpień",
+    "wrzesień",
+    "październik",
+    "listopad",
+    "grudzień",
+];
+
+/// The month before the one `today` is in: the last one that is over.
+pub fn previous_month(today: Date) -> Month {
+    let first = Month::containing(today).0;
+    Month::containing(
+        first
+            .previous_day()
+            .expect("dates before year -9999 don't come up"),
+    )
+}
+
+/// `sierpień 2025`.
+pub fn month_name(month: Month) -> String {
+    let index = u8::from(month.0.month()) as usize - 1;
+    format!("{} {}", MONTH_NAMES[index], month.0.year())
+}
+
+/// The email with `summary`, addressed to `name`.
+pub async fn render(
+    templates: &Arc<Templates>,
+    summary: &MonthlySummary,
+    name: &str,
+) -> tera::Result<Email> {
+    let month_name = month_name(summary.month);
+    let mut context = Context::new();
+    context.insert("summary", summary);
+    context.insert("month_name", &month_name);
+    context.insert("name", name);
+    Ok(Email {
+        subject: format!("Podsumowanie miesiąca: {month_name}"),
+        html: templates
+            .render("emails/monthly_summary.html", context.clone())
+            .await?,
+        text: templates
+            .render("emails/monthly_summary.txt", context)
+            .await?,
+    })
+}
+
+/// Sends the summary of last month to everyone who asked for it and hasn't
+/// got it yet. A user whose email fails is tried again on the next run.
+pub async fn send_due(
+    pool: &PgPool,
+    templates: &Arc<Templates>,
+    mailer: &Mailer,
+    today: Date,
+) -> JobResult {
+    let month = previous_month(today);
+    for recipient in db::get_monthly_summary_recipients(pool, month).await? {
+        let summary = db::get_monthly_summary(pool, recipient.id, month, TOP).await?;
+        let email = render(templates, &summary, &recipient.name).await?;
+        match mailer.send(&recipient.name, &recipient.email, email).await {
+            Ok(()) => db::mark_monthly_summary_sent(pool, recipient.id, month).await?,
+            Err(e) => tracing::warn!(user_id = recipient.id, "monthly summary not sent: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Sends the summaries that are due every `CHECK_PERIOD`, from one instance
+/// at a time.
+pub fn spawn_mailer(pool: PgPool, templates: Arc<Templates>, mailer: Mailer) {
+    scheduler::spawn_exclusive("monthly summaries", CHECK_PERIOD, pool.clone(), move || {
+        let pool = pool.clone();
+        let templates = templates.clone();
+        let mailer = mailer.clone();
+        async move {
+            let today = OffsetDateTime::now_utc().date();
+            send_due(&pool, &templates, &mailer, today).await
+        }
+    });
+}
@@ -0,0 +1,155 @@
+//! Delta sync for clients that keep a local copy and work offline: they pull
+//! what changed since their last sync and push the changes they made.
+
+use crate::{
+    db,
+    errors::{AppError, ErrorCode},
+    models::{Category, CreateItemPayload, Item, UpdateItemPayload},
+    validation::Validate,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+/// Most changes accepted in one `POST /api/sync`.
+pub const MAX_BATCH_CHANGES: usize = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct SyncQuery {
+    /// `server_time` of the previous sync; without it everything is returned.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub since: Option<OffsetDateTime>,
+}
+
+/// Response of `GET /api/sync`.
+#[derive(Debug, Serialize)]
+pub struct SyncChanges {
+    /// Pass this as `since` next time. Changes at exactly this time may be
+    /// sent again, so applying them must be idempotent.
+    #[serde(with = "time::serde::rfc3339")]
+    pub server_time: OffsetDateTime,
+    /// Created or updated records, in full.
+    pub items: Vec<Item>,
+    pub categories: Vec<Category>,
+    pub deleted: SyncDeleted,
+}
+
+/// Ids of records deleted since `since`; empty on a full sync.
+#[derive(Debug, Default, Serialize)]
+pub struct SyncDeleted {
+    pub items: Vec<i32>,
+    pub categories: Vec<i32>,
+}
+
+/// Body of `POST /api/sync`.
+#[derive(Debug, Deserialize)]
+pub struct SyncBatch {
+    pub changes: Vec<ItemChange>,
+}
+
+/// A change made on the client. Updates and deletes carry the item version
+/// the client last saw, so changes made on the server since then aren't lost.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ItemChange {
+    Create { item: CreateItemPayload },
+    Update { id: i32, item: UpdateItemPayload },
+    Delete { id: i32, version: i32 },
+}
+
+/// Outcome of one change, in the order the changes were sent.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ChangeResult {
+    /// `item` is the stored item, or `None` after a delete.
+    Applied { id: i32, item: Option<Item> },
+    /// The item changed on the server; `current` is what it looks like now,
+    /// for the client to merge and retry.
+    Conflict {
+        id: i32,
+        #[serde(flatten)]
+        error: Value,
+        current: Option<Item>,
+    },
+    /// The change is invalid by itself, e.g. a blank name or an unknown item.
+    Rejected {
+        #[serde(flatten)]
+        error: Value,
+    },
+}
+
+/// Applies the changes one by one; a failed change doesn't stop the others.
+pub async fn apply(
+    pool: &PgPool,
+    user_id: i32,
+    batch: SyncBatch,
+) -> Result<Vec<ChangeResult>, AppError> {
+    if batch.changes.len() > MAX_BATCH_CHANGES {
+        return Err(AppError::BadRequest(format!(
+            "at most {} changes can be sent at once",
+            MAX_BATCH_CHANGES
+        )));
+    }
+    let mut results = Vec::with_capacity(batch.changes.len());
+    for change in batch.changes {
+        let id = match &change {
+            ItemChange::Create { .. } => None,
+            ItemChange::Update { id, .. } | ItemChange::Delete { id, .. } => Some(*id),
+        };
+        let result = match apply_change(pool, user_id, change).await {
+            Ok(applied) => applied,
+            Err(AppError::SqlxError(e)) => return Err(AppError::SqlxError(e)),
+            Err(err) => {
+                let version_conflict = matches!(
+                    err,
+                    AppError::Conflict {
+                        code: ErrorCode::VersionConflict,
+                        ..
+                    }
+                );
+                let (_, _, error) = err.into_parts();
+                match id {
+                    Some(id) if version_conflict => ChangeResult::Conflict {
+                        id,
+                        error,
+                        current: db::get_item_by_id(pool, user_id, id).await?,
+                    },
+                    _ => ChangeResult::Rejected { error },
+                }
+            }
+        };
+        results.push(result);
+    }
+    Ok(results)
+}
+
+async fn apply_change(
+    pool: &PgPool,
+    user_id: i32,
+    change: ItemChange,
+) -> Result<ChangeResult, AppError> {
+    match change {
+        ItemChange::Create { item } => {
+            item.validate()?;
+            let item = db::create_item(pool, user_id, item).await?;
+            Ok(ChangeResult::Applied {
+                id: item.id,
+                item: Some(item),
+            })
+        }
+        ItemChange::Update { id, item } => {
+            let item = db::update_item(pool, user_id, id, item)
+                .await?
+                .ok_or(AppError::ItemNotFound)?;
+            Ok(ChangeResult::Applied {
+                id,
+                item: Some(item),
+            })
+        }
+        ItemChange::Delete { id, version } => {
+            db::delete_item_at_version(pool, user_id, id, version).await?;
+            Ok(ChangeResult::Applied { id, item: None })
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Helpers for driving the router in tests without a running server, enabled
+//! by the `test-utils` feature. Pair them with `#[sqlx::test]`, which hands
+//! each test a pool to a fresh, migrated database.
+
+use crate::{
+    AppState, assets::Templates, auth::SessionSettings, branding::Branding, build_app,
+    cache::LookupCache, cors::CorsSettings, limits::RequestLimits, maintenance::Maintenance,
+    proxy::ProxySettings, storage::FileStore,
+};
+use axum::Router;
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, Request, StatusCode, header};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// The application router on top of a test database.
+#[derive(Clone)]
+pub struct TestApp {
+    pub router: Router,
+    pub pool: PgPool,
+}
+
+/// The `Cookie` header value of a signed-in user.
+#[derive(Debug, Clone)]
+pub struct Session(pub String);
+
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    /// The body parsed as JSON, or `Null` if it isn't JSON (e.g. 204).
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Target of a redirect.
+    pub fn location(&self) -> Option<&str> {
+        self.headers
+            .get(header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+    }
+}
+
+impl TestApp {
+    /// Serves from the root path with no starter categories, so tests start
+    /// from an empty account.
+    pub fn new(pool: PgPool) -> TestApp {
+        TestApp::with_state(pool, |_| {})
+    }
+
+    /// Like `new`, with the state adjusted by `configure` first.
+    pub fn with_state(pool: PgPool, configure: impl FnOnce(&mut AppState)) -> TestApp {
+        let mut state = AppState {
+            tera: Arc::new(Templates::load().expect("templates must parse")),
+            db_pool: pool.clone(),
+            base_path: String::new(),
+            category_seed: vec![],
+            demo_mode: false,
+            sessions: SessionSettings::default(),
+            cors: CorsSettings::default(),
+            limits: RequestLimits::default(),
+            cache: LookupCache::default(),
+            maintenance: Maintenance::default(),
+            proxy: ProxySettings::default(),
+            // Keys are random, so tests running side by side don't clash
+            files: FileStore::Local(std::env::temp_dir().join("household-inventory-test")),
+            branding: Branding::default(),
+        };
+        configure(&mut state);
+        TestApp {
+            router: build_app(Arc::new(state)),
+            pool,
+        }
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self
+            .router
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("the router is infallible");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("response body");
+        TestResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    pub async fn get(&self, uri: &str, session: Option<&Session>) -> TestResponse {
+        self.send(
+            with_session(Request::get(uri), session)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+    }
+
+    /// Submits a web form, like the browser does.
+    pub async fn post_form(
+        &self,
+        uri: &str,
+        fields: &[(&str, &str)],
+        session: Option<&Session>,
+    ) -> TestResponse {
+        let body = serde_urlencoded::to_string(fields).unwrap();
+        let request = with_session(Request::post(uri), session)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .unwrap();
+        self.send(request).await
+    }
+
+    /// Calls the JSON API as `session`.
+    pub async fn api(
+        &self,
+        session: &Session,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> TestResponse {
+        let request = with_session(Request::builder().method(method).uri(uri), Some(session));
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+        self.send(request).await
+    }
+
+    /// Logs in through the login form and returns the session it sets.
+    pub async fn login(&self, email: &str, password: &str) -> Option<Session> {
+        let response = self
+            .post_form(
+                "/web/login",
+                &[("email", email), ("password", password)],
+                None,
+            )
+            .await;
+        response
+            .headers
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|cookie| cookie.split(';').next())
+            .find(|pair| pair.starts_with("session="))
+            .map(|pair| Session(pair.to_string()))
+    }
+
+    /// A new app with Ala signed up and logged in, for tests of one account.
+    pub async fn signed_in(pool: PgPool) -> (TestApp, Session) {
+        let app = TestApp::new(pool);
+        let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+        (app, session)
+    }
+
+    /// Creates an item through the API and returns its id. `fields` go on top
+    /// of an uncategorised item with nothing in stock, e.g.
+    /// `json!({ "quantity": 3, "restock_threshold": 1 })`.
+    pub async fn create_item(&self, session: &Session, name: &str, fields: Value) -> i64 {
+        let mut item = json!({ "name": name, "quantity": 0, "category_id": null });
+        if let (Some(item), Value::Object(fields)) = (item.as_object_mut(), fields) {
+            item.extend(fields);
+        }
+        let response = self.api(session, "POST", "/api/items", Some(item)).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+        response.json()["id"]
+            .as_i64()
+            .expect("the created item has an id")
+    }
+
+    /// Creates a category through the web form, under `parent` if given, and
+    /// returns its id.
+    pub async fn create_category(&self, session: &Session, name: &str, parent: Option<i32>) -> i32 {
+        let parent = parent.map(|id| id.to_string()).unwrap_or_default();
+        let response = self
+            .post_form(
+                "/web/categories/add",
+                &[("name", name), ("color", "#e0d8b0"), ("parent_id", &parent)],
+                Some(session),
+            )
+            .await;
+        assert!(response.status.is_redirection(), "{}", response.text());
+        sqlx::query_scalar("SELECT id FROM categories WHERE name = $1 ORDER BY id DESC")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await
+            .expect("the category was created")
+    }
+
+    /// Creates a share link through the API and returns it with its token.
+    pub async fn create_link(&self, session: &Session, link: Value) -> Value {
+        let response = self
+            .api(session, "POST", "/api/share-links", Some(link))
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+        response.json()
+    }
+
+    /// Creates an account through the signup form and logs in to it.
+    pub async fn sign_up(&self, name: &str, email: &str, password: &str) -> Session {
+        let response = self
+            .post_form(
+                "/web/signup",
+                &[("name", name), ("email", email), ("password", password)],
+                None,
+            )
+            .await;
+        assert!(
+            response.status.is_redirection(),
+            "signup failed with {}: {}",
+            response.status,
+            response.text()
+        );
+        self.login(email, password)
+            .await
+            .expect("login after signup sets a session")
+    }
+}
+
+fn with_session(
+    builder: axum::http::request::Builder,
+    session: Option<&Session>,
+) -> axum::http::request::Builder {
+    match session {
+        Some(Session(cookie)) => builder.header(header::COOKIE, cookie),
+        None => builder,
+    }
+}
@@ -0,0 +1,60 @@
+//! HTTPS without a reverse proxy, for small deployments: the server reads a
+//! certificate and key (e.g. from certbot), picks up renewed ones on its own,
+//! and can redirect plain HTTP to HTTPS.
+
+use crate::config::TlsFiles;
+use crate::scheduler;
+use axum::{
+    Router,
+    http::{HeaderMap, StatusCode, Uri, header, uri::Authority},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_server::tls_rustls::RustlsConfig;
+use std::time::Duration;
+
+/// How often the files are read again, so a renewed certificate is used
+/// without a restart.
+const RELOAD_PERIOD: Duration = Duration::from_secs(6 * 3600);
+
+/// Reads the certificate and key, and reads them again every
+/// `RELOAD_PERIOD`. A failed reload keeps the certificate in use.
+pub async fn load(files: &TlsFiles) -> std::io::Result<RustlsConfig> {
+    // sqlx and reqwest already bring in ring, so it does the server's TLS too
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let config = RustlsConfig::from_pem_file(&files.cert, &files.key).await?;
+    let reloaded = config.clone();
+    let files = files.clone();
+    scheduler::spawn_every("certificate reload", RELOAD_PERIOD, move || {
+        let config = reloaded.clone();
+        let files = files.clone();
+        async move {
+            config.reload_from_pem_file(&files.cert, &files.key).await?;
+            Ok(())
+        }
+    });
+    Ok(config)
+}
+
+/// Sends every request to the same URL over HTTPS on `https_port`.
+pub fn redirect_to_https(https_port: u16) -> Router {
+    Router::new().fallback(move |uri: Uri, headers: HeaderMap| async move {
+        let Some(host) = headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.parse::<Authority>().ok())
+        else {
+            return (StatusCode::BAD_REQUEST, "Missing Host header").into_response();
+        };
+        https_redirect(host.host(), https_port, &uri)
+    })
+}
+
+fn https_redirect(host: &str, port: u16, uri: &Uri) -> Response {
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let location = if port == 443 {
+        format!("https://{host}{path}")
+    } else {
+        format!("https://{host}:{port}{path}")
+    };
+    Redirect::permanent(&location).into_response()
+}
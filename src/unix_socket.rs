@@ -0,0 +1,26 @@
+//! Serving on a Unix domain socket instead of a TCP port, for a reverse proxy
+//! on the same host: only users the socket's permissions allow can connect,
+//! so other tenants of a shared host can't reach the app directly.
+
+use std::fs::{self, Permissions};
+use std::io;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use tokio::net::UnixListener;
+
+/// Binds `path`, replacing a socket left behind by an earlier run, and sets
+/// its permissions to `mode` if given. Any other file at `path` is an error
+/// rather than something to delete.
+pub fn bind(path: &str, mode: Option<u32>) -> io::Result<UnixListener> {
+    let path = Path::new(path);
+    if let Ok(metadata) = fs::symlink_metadata(path)
+        && metadata.file_type().is_socket()
+    {
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    if let Some(mode) = mode {
+        fs::set_permissions(path, Permissions::from_mode(mode))?;
+    }
+    Ok(listener)
+}
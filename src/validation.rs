@@ -0,0 +1,121 @@
+//! Checks for request payloads. Problems are collected per field, so the API
+//! can list all of them in one 422 and web forms can show each next to its
+//! input.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Longest accepted names, emails and locations; the columns are VARCHAR(255).
+pub const MAX_TEXT_LEN: usize = 255;
+pub const MAX_UNIT_LEN: usize = 32;
+pub const MAX_BARCODE_LEN: usize = 64;
+/// Item notes are a TEXT column; this keeps them to a few paragraphs.
+pub const MAX_NOTES_LEN: usize = 2000;
+pub const MAX_URL_LEN: usize = 2000;
+
+/// Field name to message, the first problem found for each field.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(transparent)]
+pub struct ValidationErrors(BTreeMap<&'static str, String>);
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a problem with `field`, unless it already has one.
+    pub fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.0.entry(field).or_insert_with(|| message.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() { Ok(()) } else { Err(self) }
+    }
+
+    /// A required text field: not blank and at most `max` characters.
+    pub fn text(&mut self, field: &'static str, value: &str, max: usize) {
+        if value.trim().is_empty() {
+            self.add(field, "must not be empty");
+        }
+        self.optional_text(field, Some(value), max);
+    }
+
+    pub fn optional_text(&mut self, field: &'static str, value: Option<&str>, max: usize) {
+        if value.is_some_and(|v| v.trim().chars().count() > max) {
+            self.add(field, format!("must be at most {} characters", max));
+        }
+    }
+
+    pub fn non_negative(&mut self, field: &'static str, value: Option<i32>) {
+        if value.is_some_and(|v| v < 0) {
+            self.add(field, "must not be negative");
+        }
+    }
+
+    pub fn positive(&mut self, field: &'static str, value: Option<i32>) {
+        if value.is_some_and(|v| v <= 0) {
+            self.add(field, "must be greater than zero");
+        }
+    }
+
+    pub fn color(&mut self, field: &'static str, value: &str) {
+        if !crate::categories::is_hex_color(value) {
+            self.add(field, "must be a color like #a6b93c");
+        }
+    }
+
+    /// Only a sanity check (one `@`, something on both sides, a dot in the
+    /// domain); whether the address works is up to the user.
+    pub fn email(&mut self, field: &'static str, value: &str) {
+        let value = value.trim();
+        let valid = match value.split_once('@') {
+            Some((local, domain)) => {
+                !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.contains('.')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+                    && !value.contains(char::is_whitespace)
+            }
+            None => false,
+        };
+        if !valid {
+            self.add(field, "must be a valid email address");
+        } else {
+            self.optional_text(field, Some(value), MAX_TEXT_LEN);
+        }
+    }
+
+    /// An absolute `http` or `https` URL.
+    pub fn url(&mut self, field: &'static str, value: &str) {
+        let value = value.trim();
+        let valid = (value.starts_with("http://") || value.starts_with("https://"))
+            && reqwest::Url::parse(value).is_ok();
+        if !valid {
+            self.add(field, "must be an http or https URL");
+        } else {
+            self.optional_text(field, Some(value), MAX_URL_LEN);
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (field, message)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{} {}", field, message)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+//! What gets thrown away: stock discarded as expired or spoiled, or removed
+//! as lost, added up month by month with what it was worth. The worth is an
+//! estimate from the item's latest purchase with a price, made when the
+//! stock is thrown away, so later price changes don't rewrite old months.
+
+use crate::models::{Month, WasteReport};
+use crate::{AppState, db, errors::AppError};
+use time::OffsetDateTime;
+
+/// The waste of `month`, or of the current month without one.
+pub async fn report(
+    state: &AppState,
+    user_id: i32,
+    month: Option<Month>,
+) -> Result<WasteReport, AppError> {
+    let month = month.unwrap_or_else(|| Month::containing(OffsetDateTime::now_utc().date()));
+    Ok(db::get_waste_report(&state.db_pool, user_id, month).await?)
+}
@@ -1,4 +1,12 @@
 window.addEventListener("load", () => {
+  // The service worker sits next to the manifest, at the root of the app
+  const manifest = document.querySelector('link[rel="manifest"]');
+  if (manifest && "serviceWorker" in navigator) {
+    navigator.serviceWorker
+      .register(new URL("service-worker.js", manifest.href))
+      .catch((error) => console.warn("Service worker not registered", error));
+  }
+
   // Wire up each dialog with its own open and close buttons
   document.querySelectorAll("dialog").forEach((dialog) => {
     const id = dialog.id; // e.g. "dialog-5"
@@ -12,4 +20,96 @@ window.addEventListener("load", () => {
       closeButton.addEventListener("click", () => dialog.close());
     }
   });
+
+  // Name inputs with data-catalog-url suggest products from the catalog as
+  // they are typed. Picking one fills in the fields that are still empty.
+  document.querySelectorAll("input[data-catalog-url]").forEach((input) => {
+    const list = document.getElementById(input.getAttribute("list"));
+    const form = input.form;
+    let products = new Map();
+    const fill = (name, value) => {
+      const field = form.elements[name];
+      if (field && !field.value && value !== null) {
+        field.value = value;
+      }
+    };
+    input.addEventListener("input", () => {
+      const product = products.get(input.value);
+      if (product) {
+        fill("barcode", product.barcode);
+        fill("unit", product.unit);
+        fill("restock_threshold", product.restock_threshold);
+        fill("category_id", product.category_id);
+        return;
+      }
+      const q = input.value.trim();
+      if (q.length < 2) {
+        return;
+      }
+      const url = new URL(input.dataset.catalogUrl, location.href);
+      url.searchParams.set("q", q);
+      fetch(url)
+        .then((response) => (response.ok ? response.json() : []))
+        .then((found) => {
+          products = new Map(found.map((product) => [product.name, product]));
+          list.replaceChildren(
+            ...found.map((product) => {
+              const option = document.createElement("option");
+              option.value = product.name;
+              option.label = [product.barcode, product.unit]
+                .filter(Boolean)
+                .join(" · ");
+              return option;
+            }),
+          );
+        });
+    });
+  });
+
+  // Drag-and-drop reordering: a row with data-reorder-id can be dropped onto
+  // another row of the same data-reorder-group. The new order of the group
+  // is posted to the table's data-reorder-url.
+  document.querySelectorAll("table[data-reorder-url]").forEach((table) => {
+    let dragged = null;
+    table.querySelectorAll("tr[data-reorder-id]").forEach((row) => {
+      row.draggable = true;
+      row.addEventListener("dragstart", (event) => {
+        dragged = row;
+        event.dataTransfer.effectAllowed = "move";
+      });
+      row.addEventListener("dragend", () => {
+        dragged = null;
+      });
+      row.addEventListener("dragover", (event) => {
+        if (
+          dragged &&
+          dragged !== row &&
+          dragged.dataset.reorderGroup === row.dataset.reorderGroup
+        ) {
+          event.preventDefault();
+        }
+      });
+      row.addEventListener("drop", (event) => {
+        event.preventDefault();
+        const rect = row.getBoundingClientRect();
+        const after = event.clientY > rect.top + rect.height / 2;
+        row.parentNode.insertBefore(dragged, after ? row.nextSibling : row);
+
+        const group = row.dataset.reorderGroup;
+        const ids = [...table.querySelectorAll("tr[data-reorder-id]")]
+          .filter((r) => r.dataset.reorderGroup === group)
+          .map((r) => Number(r.dataset.reorderId));
+        fetch(table.dataset.reorderUrl, {
+          method: "POST",
+          headers: { "Content-Type": "application/json" },
+          body: JSON.stringify({ ids }),
+        }).then((response) => {
+          // Reload to show the saved order (or undo the move on failure)
+          if (!response.ok || "reorderReload" in table.dataset) {
+            location.reload();
+          }
+        });
+      });
+    });
+  });
 });
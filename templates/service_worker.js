@@ -0,0 +1,59 @@
+// Generated for this build; a new build gets a new cache and drops the old one
+const CACHE = {{ cache | json_encode() | safe }};
+const BASE_PATH = {{ base_path | json_encode() | safe }};
+const OFFLINE_URL = BASE_PATH + "/offline";
+const PRECACHE = [OFFLINE_URL].concat({{ assets | json_encode() | safe }});
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches
+      .open(CACHE)
+      .then((cache) => cache.addAll(PRECACHE))
+      .then(() => self.skipWaiting()),
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys.filter((key) => key !== CACHE).map((key) => caches.delete(key)),
+        ),
+      )
+      .then(() => self.clients.claim()),
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  if (request.method !== "GET") {
+    return;
+  }
+  // Pages always come from the server; without a connection, the offline page
+  if (request.mode === "navigate") {
+    event.respondWith(fetch(request).catch(() => caches.match(OFFLINE_URL)));
+    return;
+  }
+  // Static files carry their version in the URL, so a cached copy is current
+  const url = new URL(request.url);
+  if (
+    url.origin === self.location.origin &&
+    url.pathname.startsWith(BASE_PATH + "/static/")
+  ) {
+    event.respondWith(
+      caches.match(request).then(
+        (cached) =>
+          cached ||
+          fetch(request).then((response) => {
+            if (response.ok) {
+              const copy = response.clone();
+              caches.open(CACHE).then((cache) => cache.put(request, copy));
+            }
+            return response;
+          }),
+      ),
+    );
+  }
+});
@@ -0,0 +1,167 @@
+use axum::http::StatusCode;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn the_feed_tells_who_did_what_newest_first(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let milk = app
+        .create_item(&session, "Mleko", json!({ "quantity": 10 }))
+        .await;
+    let batteries = app
+        .create_item(&session, "Baterie", json!({ "quantity": 10 }))
+        .await;
+    app.api(&session, "POST", &format!("/api/items/{milk}/use"), None)
+        .await;
+    app.api(
+        &session,
+        "POST",
+        &format!("/api/items/{batteries}/purchase"),
+        Some(json!({ "quantity": 4 })),
+    )
+    .await;
+    app.api(
+        &session,
+        "POST",
+        &format!("/api/items/{milk}/adjust"),
+        Some(json!({ "delta": 2, "reason": "correction" })),
+    )
+    .await;
+
+    let feed = app.api(&session, "GET", "/api/activity", None).await.json();
+    let texts: Vec<&str> = feed["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        texts,
+        [
+            "Ala: poprawiono stan Mleko o +2",
+            "Ala: kupiono Baterie ×4",
+            "Ala: zużyto Mleko ×1",
+        ]
+    );
+    assert_eq!(feed["items"][0]["user_name"], "Ala");
+    assert_eq!(feed["items"][0]["kind"], "correction");
+
+    let feed = app
+        .api(
+            &session,
+            "GET",
+            &format!("/api/activity?item_id={milk}&limit=1"),
+            None,
+        )
+        .await
+        .json();
+    assert_eq!(feed["items"].as_array().unwrap().len(), 1);
+    assert_eq!(feed["items"][0]["item_name"], "Mleko");
+    assert!(feed["next_cursor"].is_string());
+
+    let page = app
+        .get(
+            &format!("/web/activity?item_id={batteries}"),
+            Some(&session),
+        )
+        .await;
+    assert_eq!(page.status, StatusCode::OK);
+    let html = page.text();
+    assert!(html.contains("Ala: kupiono Baterie ×4"), "{html}");
+    assert!(!html.contains("zużyto Mleko"), "{html}");
+    // The empty choice of the filter means every item
+    let html = app
+        .get("/web/activity?item_id=", Some(&session))
+        .await
+        .text();
+    assert!(html.contains("zużyto Mleko"), "{html}");
+}
+
+#[sqlx::test]
+async fn the_feed_of_someone_elses_item_is_not_found(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let ala = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let ola = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let olas = app
+        .create_item(&ola, "Mleko", json!({ "quantity": 10 }))
+        .await;
+
+    let response = app
+        .api(&ala, "GET", &format!("/api/activity?item_id={olas}"), None)
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn the_feed_tells_the_members_of_a_household_apart(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let ala = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let bolek = app.sign_up("Bolek", "bolek@example.com", "hunter2").await;
+    let milk = app
+        .create_item(&ala, "Mleko", json!({ "quantity": 10 }))
+        .await;
+    let household_id = sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE email = $1")
+        .bind("ala@example.com")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let uri = format!("/api/households/{household_id}/invitations");
+    let body = json!({ "email": "bolek@example.com", "role": "editor" });
+    app.api(&ala, "POST", &uri, Some(body)).await;
+    let invitation = app
+        .api(&bolek, "GET", "/api/households/invitations", None)
+        .await
+        .json()[0]["id"]
+        .clone();
+    let uri = format!("/api/households/invitations/{invitation}/accept");
+    app.api(&bolek, "POST", &uri, None).await;
+    let body = json!({ "household_id": household_id });
+    app.api(&bolek, "PUT", "/api/households/current", Some(body))
+        .await;
+
+    app.api(&ala, "POST", &format!("/api/items/{milk}/use"), None)
+        .await;
+    let response = app
+        .api(&bolek, "POST", &format!("/api/items/{milk}/use"), None)
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+
+    let feed = app.api(&ala, "GET", "/api/activity", None).await.json();
+    let texts: Vec<&str> = feed["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(texts, ["Bolek: zużyto Mleko ×1", "Ala: zużyto Mleko ×1"]);
+    let bolek_id = feed["items"][0]["user_id"].clone();
+    assert_ne!(bolek_id, household_id);
+
+    let feed = app
+        .api(
+            &bolek,
+            "GET",
+            &format!("/api/activity?user_id={bolek_id}"),
+            None,
+        )
+        .await
+        .json();
+    assert_eq!(feed["items"].as_array().unwrap().len(), 1);
+    assert_eq!(feed["items"][0]["user_name"], "Bolek");
+    let html = app.get("/web/activity", Some(&ala)).await.text();
+    assert!(
+        html.contains(r#"<label for="user_id">Kto:</label>"#),
+        "{html}"
+    );
+
+    // The change stays, without the name of the account that made it
+    sqlx::query("DELETE FROM users WHERE email = 'bolek@example.com'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let feed = app.api(&ala, "GET", "/api/activity", None).await.json();
+    assert_eq!(feed["items"][0]["text"], "Usunięte konto: zużyto Mleko ×1");
+    assert_eq!(feed["items"][0]["user_id"], serde_json::Value::Null);
+}
@@ -0,0 +1,215 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use household_inventory::assets::Templates;
+use household_inventory::branding::Branding;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// The versioned stylesheet URL the login page links to.
+fn stylesheet_url(page: &str) -> String {
+    let start = page
+        .find("/static/style.css")
+        .expect("the page links the stylesheet");
+    let end = start + page[start..].find('"').unwrap();
+    page[start..end].to_string()
+}
+
+#[sqlx::test]
+async fn versioned_static_files_are_cached_for_good(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let page = app.get("/web/login", None).await.text();
+    let url = stylesheet_url(&page);
+    assert!(url.contains("?v="), "{url}");
+
+    let versioned = app.get(&url, None).await;
+    assert_eq!(versioned.status, StatusCode::OK);
+    assert!(
+        versioned.headers[header::CACHE_CONTROL]
+            .to_str()
+            .unwrap()
+            .contains("immutable")
+    );
+
+    let plain = app.get("/static/style.css", None).await;
+    assert_eq!(plain.headers[header::CACHE_CONTROL], "no-cache");
+    let revalidated = app
+        .send(
+            Request::get("/static/style.css")
+                .header(header::IF_NONE_MATCH, &plain.headers[header::ETAG])
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(revalidated.status, StatusCode::NOT_MODIFIED);
+}
+
+#[sqlx::test]
+async fn pages_are_compressed_for_browsers_that_accept_it(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app
+        .send(
+            Request::get("/web/login")
+                .header(header::ACCEPT_ENCODING, "gzip")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CONTENT_ENCODING], "gzip");
+}
+
+#[sqlx::test]
+async fn the_app_can_be_installed(pool: PgPool) {
+    let app = TestApp::with_state(pool, |state| state.base_path = "/inventory".to_string());
+    let page = app.get("/inventory/web/login", None).await.text();
+    assert!(page.contains(r#"inventory/manifest.json" />"#), "{page}");
+
+    let response = app.get("/inventory/manifest.json", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.headers[header::CONTENT_TYPE],
+        "application/manifest+json"
+    );
+    let manifest = response.json();
+    assert_eq!(manifest["start_url"], "/inventory/web");
+    assert_eq!(manifest["scope"], "/inventory/");
+    for icon in manifest["icons"].as_array().unwrap() {
+        let src = icon["src"].as_str().unwrap();
+        let response = app.get(src, None).await;
+        assert_eq!(response.status, StatusCode::OK, "{src}");
+        assert_eq!(
+            response.headers[header::CONTENT_TYPE],
+            icon["type"].as_str().unwrap()
+        );
+    }
+
+    let response = app.get("/inventory/service-worker.js", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CACHE_CONTROL], "no-cache");
+    let script = response.text();
+    assert!(
+        script.contains(r#"const BASE_PATH = "/inventory";"#),
+        "{script}"
+    );
+    let stylesheet = stylesheet_url(&page);
+    assert!(
+        script.contains(&format!("\"/inventory{stylesheet}\"")),
+        "{script}"
+    );
+
+    let offline = app.get("/inventory/offline", None).await;
+    assert_eq!(offline.status, StatusCode::OK);
+    assert!(offline.text().contains("Brak połączenia"));
+}
+
+#[sqlx::test]
+async fn pages_follow_the_users_theme(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    let page = app.get("/web/settings", Some(&session)).await.text();
+    assert!(
+        page.contains(r#"dark.css"#) && page.contains(r#"media="(prefers-color-scheme: dark)""#),
+        "{page}"
+    );
+
+    let body = json!({ "theme": "dark" });
+    app.api(&session, "PUT", "/api/preferences", Some(body))
+        .await;
+    let page = app.get("/web/shopping-list", Some(&session)).await.text();
+    assert!(page.contains("dark.css"), "{page}");
+    assert!(!page.contains("prefers-color-scheme"), "{page}");
+
+    let body = json!({ "theme": "light" });
+    app.api(&session, "PUT", "/api/preferences", Some(body))
+        .await;
+    let page = app.get("/web/recipes", Some(&session)).await.text();
+    assert!(!page.contains("dark.css"), "{page}");
+}
+
+#[sqlx::test]
+async fn instance_branding_reaches_every_page(pool: PgPool) {
+    let app = TestApp::with_state(pool, |state| {
+        let branding = Branding::new(Some("#3c7cb9"), Some("nav { color: red; }".to_string()));
+        state.tera = Arc::new(Templates::load().unwrap().with_branding(&branding));
+        state.branding = branding;
+    });
+    let page = app.get("/web/login", None).await.text();
+    assert!(
+        page.contains("--accent: #3c7cb9; --accent-hover: #28527b; --accent-text: #FFFFFF;"),
+        "{page}"
+    );
+    let start = page
+        .find("/custom.css?v=")
+        .expect("the page links the custom CSS");
+    let end = start + page[start..].find('"').unwrap();
+
+    let response = app.get(&page[start..end], None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.text(), "nav { color: red; }");
+    assert!(
+        response.headers[header::CACHE_CONTROL]
+            .to_str()
+            .unwrap()
+            .contains("immutable")
+    );
+    let stale = app.get("/custom.css?v=0", None).await;
+    assert_eq!(stale.headers[header::CACHE_CONTROL], "no-cache");
+
+    let plain = TestApp::new(app.pool.clone());
+    assert_eq!(
+        plain.get("/custom.css", None).await.status,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[sqlx::test]
+async fn templates_can_be_overridden_from_a_folder(pool: PgPool) {
+    let dir = std::env::temp_dir().join(format!("inventory-templates-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("partials")).unwrap();
+    std::fs::write(
+        dir.join("login.html"),
+        r#"{% extends "base.html" %}{% block content %}{% include "partials/_welcome.html" %}{% endblock content %}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("partials/_welcome.html"), "Witaj w spiżarni").unwrap();
+
+    let templates = Templates::load().unwrap().with_overrides(&dir).unwrap();
+    assert_eq!(templates.overridden().unwrap(), ["login.html"]);
+    let app = TestApp::with_state(pool, |state| state.tera = Arc::new(templates));
+    let page = app.get("/web/login", None).await.text();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(page.contains("Witaj w spiżarni"), "{page}");
+    // Pages without an override are unchanged, base.html included
+    assert!(page.contains("style.css"), "{page}");
+}
+
+#[test]
+fn templates_can_use_the_shared_filters() {
+    let dir = std::env::temp_dir().join(format!("inventory-filters-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("filters.html"),
+        "{{ dark | text_color }} {{ light | text_color }}|{{ at | humantime }}|\
+         {{ at | humantime(tz=\"America/New_York\") }}|\
+         {{ 3 | fmt_qty(unit=\"kg\") }} {{ 2 | fmt_qty(unit=none) }}",
+    )
+    .unwrap();
+
+    let templates = Templates::load().unwrap().with_overrides(&dir).unwrap();
+    let mut context = tera::Context::new();
+    context.insert("dark", "#1d171d");
+    context.insert("light", "#f5f5dc");
+    context.insert("at", "2025-09-27T14:05:31.5Z");
+    context.insert("none", &Option::<String>::None);
+    let page = templates.render_now("filters.html", context).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        page,
+        "#FFFFFF #000000|2025-09-27 14:05|2025-09-27 10:05|3 kg 2"
+    );
+}
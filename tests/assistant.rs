@@ -0,0 +1,160 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use household_inventory::testing::{Session, TestApp, TestResponse};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+async fn make_token(app: &TestApp, session: &Session) -> String {
+    let response = app
+        .api(
+            session,
+            "POST",
+            "/api/tokens",
+            Some(json!({ "label": "Alexa" })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    response.json()["token"].as_str().unwrap().to_string()
+}
+
+/// Sends an intent the way the voice assistant's fulfillment does.
+async fn ask(app: &TestApp, token: &str, intent: Value) -> TestResponse {
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/assistant")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(intent.to_string()))
+        .unwrap();
+    app.send(request).await
+}
+
+#[sqlx::test]
+async fn intents_update_the_pantry_and_answer_in_words(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Mleko", "quantity": 1, "unit": "l",
+                         "restock_threshold": 1, "category_id": null })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    let token = make_token(&app, &session).await;
+
+    let response = ask(
+        &app,
+        &token,
+        json!({ "intent": "check_stock", "item": "mleko" }),
+    )
+    .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["speech"], "Mleko: 1 l");
+
+    let response = ask(
+        &app,
+        &token,
+        json!({ "intent": "add_item", "item": "mleko", "quantity": 2 }),
+    )
+    .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    let reply = response.json();
+    assert_eq!(reply["speech"], "Dodano Mleko: 2 l, jest teraz 3 l");
+    assert_eq!(reply["item"]["quantity"], 3);
+
+    // Unknown items are created, with the name capitalized
+    let response = ask(
+        &app,
+        &token,
+        json!({ "intent": "add_item", "item": "jajka" }),
+    )
+    .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["item"]["name"], "Jajka");
+    assert_eq!(response.json()["item"]["quantity"], 1);
+
+    for item in ["mleko", "kawa"] {
+        let response = ask(
+            &app,
+            &token,
+            json!({ "intent": "add_to_shopping_list", "item": item }),
+        )
+        .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    }
+    let response = ask(
+        &app,
+        &token,
+        json!({ "intent": "add_to_shopping_list", "item": "Kawa" }),
+    )
+    .await;
+    assert_eq!(response.json()["speech"], "Kawa już jest na liście zakupów");
+
+    let list = app
+        .api(&session, "GET", "/api/shopping-list", None)
+        .await
+        .json();
+    let names: Vec<&str> = list
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["item_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Kawa", "Mleko"]);
+    // Raising the threshold left the rest of the item alone
+    let milk = ask(
+        &app,
+        &token,
+        json!({ "intent": "check_stock", "item": "mleko" }),
+    )
+    .await
+    .json();
+    assert_eq!(milk["item"]["unit"], "l");
+    assert_eq!(milk["item"]["restock_threshold"], 4);
+
+    let response = ask(
+        &app,
+        &token,
+        json!({ "intent": "check_stock", "item": "herbata" }),
+    )
+    .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["item"], Value::Null);
+}
+
+#[sqlx::test]
+async fn the_assistant_needs_a_token_and_a_known_intent(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/assistant",
+            Some(json!({ "intent": "check_stock", "item": "mleko" })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let token = make_token(&app, &session).await;
+    let response = ask(
+        &app,
+        &token,
+        json!({ "intent": "cook_dinner", "item": "zupa" }),
+    )
+    .await;
+    assert!(response.status.is_client_error(), "{}", response.text());
+
+    let response = ask(
+        &app,
+        &token,
+        json!({ "intent": "add_item", "item": "mleko", "quantity": 0 }),
+    )
+    .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.json()["code"], "VALIDATION_FAILED");
+}
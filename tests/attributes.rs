@@ -0,0 +1,198 @@
+use axum::http::StatusCode;
+use household_inventory::testing::TestApp;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn items_have_custom_fields_to_filter_by(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let mut fields = Vec::new();
+    for (name, kind) in [
+        ("Marka", "text"),
+        ("Rozmiar", "number"),
+        ("Bez laktozy", "boolean"),
+    ] {
+        let field = json!({ "name": name, "kind": kind });
+        let response = app
+            .api(&session, "POST", "/api/attribute-fields", Some(field))
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+        fields.push(response.json()["id"].as_i64().unwrap());
+    }
+    let field = json!({ "name": "Marka", "kind": "date" });
+    let response = app
+        .api(&session, "POST", "/api/attribute-fields", Some(field))
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    let mut ids = Vec::new();
+    for (name, attributes) in [
+        (
+            "Mleko",
+            json!({ "Marka": " Mlekovita ", "Rozmiar": 2, "Bez laktozy": true }),
+        ),
+        (
+            "Jogurt",
+            json!({ "Marka": "Mlekovita", "Rozmiar": 0.4, "Bez laktozy": null }),
+        ),
+        ("Masło", json!({})),
+    ] {
+        let item = json!({ "name": name, "quantity": 1, "restock_threshold": 1,
+                           "category_id": null, "attributes": attributes });
+        let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+        ids.push(response.json()["id"].as_i64().unwrap());
+    }
+    let uri = format!("/api/items/{}", ids[1]);
+    let yoghurt = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(
+        yoghurt["attributes"],
+        json!({ "Marka": "Mlekovita", "Rozmiar": 0.4 })
+    );
+
+    // Values must fit a defined field
+    for attributes in [json!({ "Rozmiar": "duży" }), json!({ "Kolor": "biały" })] {
+        let item = json!({ "name": "Ser", "quantity": 1, "restock_threshold": 1,
+                           "category_id": null, "attributes": attributes });
+        let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let names = |items: Value| -> Vec<String> {
+        let items = items.as_array().unwrap();
+        let mut names: Vec<String> = items
+            .iter()
+            .map(|i| i["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+    let found = app
+        .api(&session, "GET", "/api/items?attr.Marka=Mlekovita", None)
+        .await
+        .json();
+    assert_eq!(names(found), ["Jogurt", "Mleko"]);
+    let uri = "/api/items?attr.Marka=Mlekovita&attr.Rozmiar=2";
+    let found = app.api(&session, "GET", uri, None).await.json();
+    assert_eq!(names(found), ["Mleko"]);
+    let uri = "/api/items?attr.Bez%20laktozy=true";
+    let found = app.api(&session, "GET", uri, None).await.json();
+    assert_eq!(names(found), ["Mleko"]);
+    let uri = "/api/items?attr.Rozmiar=dwa";
+    let response = app.api(&session, "GET", uri, None).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // The edit form shows the fields and saves them all
+    let page = app
+        .get(&format!("/web/items/edit/{}", ids[0]), Some(&session))
+        .await
+        .text();
+    assert!(page.contains(r#"value="Mlekovita""#), "{page}");
+    let item = app
+        .api(&session, "GET", &format!("/api/items/{}", ids[0]), None)
+        .await
+        .json();
+    let version = item["version"].to_string();
+    let response = app
+        .post_form(
+            &format!("/web/items/edit/{}", ids[0]),
+            &[
+                ("name", "Mleko"),
+                ("quantity", "1"),
+                ("restock_threshold", "1"),
+                ("category_id", ""),
+                ("version", &version),
+                (&format!("attr_{}", fields[0]), "Łaciate"),
+                (&format!("attr_{}", fields[1]), "1,5"),
+            ],
+            Some(&session),
+        )
+        .await;
+    assert!(response.status.is_redirection(), "{}", response.text());
+    let uri = format!("/api/items/{}", ids[0]);
+    let milk = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(
+        milk["attributes"],
+        json!({ "Marka": "Łaciate", "Rozmiar": 1.5, "Bez laktozy": false })
+    );
+
+    // A deleted field takes its values with it
+    let uri = format!("/api/attribute-fields/{}", fields[0]);
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let uri = format!("/api/items/{}", ids[1]);
+    let yoghurt = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(yoghurt["attributes"], json!({ "Rozmiar": 0.4 }));
+
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let response = app.api(&other, "GET", "/api/attribute-fields", None).await;
+    assert_eq!(response.json(), json!([]));
+    let uri = format!("/api/attribute-fields/{}", fields[1]);
+    let response = app.api(&other, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn fields_and_values_of_an_unknown_or_wrong_type_are_rejected(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+
+    let field = json!({ "name": "Kolor", "kind": "color" });
+    let response = app
+        .api(&session, "POST", "/api/attribute-fields", Some(field))
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let field = json!({ "name": " ", "kind": "text" });
+    let response = app
+        .api(&session, "POST", "/api/attribute-fields", Some(field))
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(response.json()["details"]["fields"]["name"].is_string());
+    let response = app
+        .post_form(
+            "/web/settings/attribute-fields",
+            &[("name", "Kolor"), ("kind", "color")],
+            Some(&session),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let fields = app
+        .api(&session, "GET", "/api/attribute-fields", None)
+        .await
+        .json();
+    assert_eq!(fields, json!([]));
+
+    for (name, kind) in [("Ważne do", "date"), ("Bez laktozy", "boolean")] {
+        let field = json!({ "name": name, "kind": kind });
+        let response = app
+            .api(&session, "POST", "/api/attribute-fields", Some(field))
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    }
+    for attributes in [
+        json!({ "Ważne do": "jutro" }),
+        json!({ "Ważne do": 20261017 }),
+        json!({ "Bez laktozy": "tak" }),
+    ] {
+        let item = json!({ "name": "Ser", "quantity": 1, "category_id": null,
+                           "attributes": attributes });
+        let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+        assert_eq!(
+            response.status,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "{attributes}"
+        );
+    }
+    let items = app.api(&session, "GET", "/api/items", None).await.json();
+    assert_eq!(items, json!([]));
+
+    let attributes = json!({ "Ważne do": "2026-10-17", "Bez laktozy": true });
+    let id = app
+        .create_item(&session, "Ser", json!({ "attributes": attributes }))
+        .await;
+    let cheese = app
+        .api(&session, "GET", &format!("/api/items/{id}"), None)
+        .await
+        .json();
+    assert_eq!(cheese["attributes"], attributes);
+}
@@ -0,0 +1,173 @@
+use axum::http::StatusCode;
+use household_inventory::config::{CookieSameSite, CookieSecure};
+use household_inventory::{db, testing::TestApp};
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn signup_then_login_opens_the_inventory(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    let response = app.get("/web", Some(&session)).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.text().contains("Ala"));
+}
+
+#[sqlx::test]
+async fn signup_with_a_taken_email_shows_an_error_page(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    let response = app
+        .post_form(
+            "/web/signup",
+            &[
+                ("name", "Ala"),
+                ("email", "ala@example.com"),
+                ("password", "other"),
+            ],
+            None,
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert!(
+        response.headers["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html")
+    );
+    assert!(
+        response
+            .text()
+            .contains("An account with this email already exists")
+    );
+}
+
+#[sqlx::test]
+async fn login_with_wrong_password_sets_no_session(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    assert!(app.login("ala@example.com", "wrong").await.is_none());
+    assert!(app.login("nobody@example.com", "hunter2").await.is_none());
+}
+
+#[sqlx::test]
+async fn web_pages_redirect_to_login_without_a_session(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.get("/web/recipes?week=2025-06-02", None).await;
+
+    assert!(response.status.is_redirection());
+    assert_eq!(
+        response.location(),
+        Some("/web/login?next=%2Fweb%2Frecipes%3Fweek%3D2025%2D06%2D02")
+    );
+}
+
+#[sqlx::test]
+async fn api_requests_without_a_session_get_401(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.get("/api/items", None).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json()["error"], "Authentication required");
+    assert_eq!(response.json()["code"], "UNAUTHENTICATED");
+}
+
+#[sqlx::test]
+async fn login_returns_to_the_requested_page(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    assert_eq!(
+        login(&app, "/web/recipes?week=2025-06-02").await,
+        "/web/recipes?week=2025-06-02"
+    );
+    // Only pages of the app, so the form can't redirect elsewhere
+    assert_eq!(login(&app, "https://evil.example").await, "/web");
+    assert_eq!(login(&app, "//evil.example/web").await, "/web");
+}
+
+async fn login(app: &TestApp, next: &str) -> String {
+    let response = app
+        .post_form(
+            "/web/login",
+            &[
+                ("email", "ala@example.com"),
+                ("password", "hunter2"),
+                ("next", next),
+            ],
+            None,
+        )
+        .await;
+    response.location().unwrap().to_string()
+}
+
+#[sqlx::test]
+async fn logout_clears_the_session_cookie(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    let response = app.get("/web/logout", Some(&session)).await;
+
+    assert_eq!(response.location(), Some("/web/login"));
+    let cookie = response.headers["set-cookie"].to_str().unwrap();
+    assert!(cookie.starts_with("session=;"), "{cookie}");
+    // The old token is dead on the server too, not just forgotten
+    let response = app.get("/api/items", Some(&session)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn remember_me_keeps_the_cookie_past_the_browser_session(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    assert!(!login_cookie(&app, &[]).await.contains("Max-Age"));
+    let cookie = login_cookie(&app, &[("remember", "true")]).await;
+    assert!(cookie.contains("Max-Age=2592000"), "{cookie}");
+}
+
+#[sqlx::test]
+async fn cookie_attributes_follow_the_settings(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    // Plain HTTP with no proxy saying otherwise
+    let cookie = login_cookie(&app, &[]).await;
+    assert!(cookie.contains("HttpOnly"), "{cookie}");
+    assert!(cookie.contains("SameSite=Lax"), "{cookie}");
+    assert!(!cookie.contains("Secure"), "{cookie}");
+
+    let app = TestApp::with_state(pool, |state| {
+        state.sessions.secure = CookieSecure::Always;
+        state.sessions.same_site = CookieSameSite::Strict;
+    });
+    let cookie = login_cookie(&app, &[("remember", "true")]).await;
+    assert!(cookie.contains("SameSite=Strict"), "{cookie}");
+    assert!(cookie.contains("Secure"), "{cookie}");
+}
+
+async fn login_cookie(app: &TestApp, extra: &[(&str, &str)]) -> String {
+    let mut form = vec![("email", "ala@example.com"), ("password", "hunter2")];
+    form.extend_from_slice(extra);
+    let response = app.post_form("/web/login", &form, None).await;
+    response.headers["set-cookie"].to_str().unwrap().to_string()
+}
+
+#[sqlx::test]
+async fn expired_sessions_are_rejected_and_cleaned_up(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    sqlx::query("UPDATE sessions SET expires_at = NOW() - INTERVAL '1 minute'")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let response = app.get("/api/items", Some(&session)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    assert_eq!(db::delete_expired_sessions(&pool).await.unwrap(), 1);
+}
@@ -0,0 +1,242 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use futures_util::StreamExt;
+use household_inventory::db;
+use household_inventory::testing::{Session, TestApp};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+const EVENTS: i32 = 20_000;
+
+#[sqlx::test]
+async fn backup_download_streams_the_same_backup_as_the_export(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let created = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Mleko", "quantity": 2, "category_id": null })),
+        )
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED);
+    sqlx::query(
+        "INSERT INTO item_events (user_id, item_id, item_name, kind, quantity_delta)
+         SELECT i.user_id, i.id, i.name, 'used', -1 FROM items i, generate_series(1, $1)",
+    )
+    .bind(EVENTS)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let request = Request::get("/api/backup")
+        .header(header::COOKIE, &session.0)
+        .body(Body::empty())
+        .unwrap();
+    let response = app.router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    let mut chunks = response.into_body().into_data_stream();
+    let mut body = vec![];
+    let mut chunk_count = 0;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.unwrap();
+        // Handed over as it is written rather than in one piece at the end
+        assert!(chunk.len() < 128 * 1024, "{} byte chunk", chunk.len());
+        chunk_count += 1;
+        body.extend_from_slice(&chunk);
+    }
+    assert!(chunk_count > 10, "{chunk_count} chunks");
+
+    let backup: Value = serde_json::from_slice(&body).unwrap();
+    assert!(backup["exported_at"].is_string());
+    let account = db::get_account_by_email(&pool, "ala@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+    let mut exported =
+        serde_json::to_value(db::export_backup(&pool, account.id).await.unwrap()).unwrap();
+    let mut downloaded = backup.clone();
+    downloaded["exported_at"] = Value::Null;
+    exported["exported_at"] = Value::Null;
+    assert_eq!(
+        downloaded["events"].as_array().unwrap().len(),
+        EVENTS as usize
+    );
+    assert_eq!(downloaded, exported);
+
+    let restored = app
+        .api(&session, "POST", "/api/restore", Some(backup))
+        .await;
+    assert_eq!(restored.status, StatusCode::OK);
+    assert_eq!(restored.json()["events"], EVENTS);
+}
+
+/// The backup of the session's account as the API gives it, without the
+/// time it was made.
+async fn backup(app: &TestApp, session: &Session) -> Value {
+    let response = app.api(session, "GET", "/api/backup", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let mut backup = response.json();
+    backup["exported_at"] = Value::Null;
+    backup
+}
+
+/// The backup with its category and item ids numbered from 0 in order, as
+/// a restore gives them new ones.
+fn renumbered(mut backup: Value) -> Value {
+    let mut renumber = |records: &str, references: &[(&str, &str)]| {
+        let ids: Vec<Value> = backup[records]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|record| record["id"].clone())
+            .collect();
+        let position = |id: &Value| json!(ids.iter().position(|other| other == id));
+        for record in backup[records].as_array_mut().unwrap() {
+            record["id"] = position(&record["id"]);
+        }
+        for (collection, field) in references {
+            for record in backup[*collection].as_array_mut().unwrap() {
+                if !record[*field].is_null() {
+                    record[*field] = position(&record[*field]);
+                }
+            }
+        }
+    };
+    renumber(
+        "categories",
+        &[("categories", "parent_id"), ("items", "category_id")],
+    );
+    renumber("items", &[("batches", "item_id"), ("events", "item_id")]);
+    backup
+}
+
+#[sqlx::test]
+async fn restoring_a_backup_twice_gives_the_same_inventory(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let dairy = app.create_category(&session, "Nabiał", None).await;
+    let milk = app
+        .create_item(
+            &session,
+            "Mleko",
+            json!({ "quantity": 3, "category_id": dairy }),
+        )
+        .await;
+    app.create_item(&session, "Ryż", json!({ "quantity": 1 }))
+        .await;
+    let uri = format!("/api/items/{milk}/use");
+    app.api(&session, "POST", &uri, None).await;
+    let mut original = backup(&app, &session).await;
+    let expected = renumbered(original.clone());
+    original["exported_at"] = json!("2026-10-17T12:00:00Z");
+
+    // Changed since the backup was made
+    app.create_item(&session, "Kawa", json!({ "quantity": 1 }))
+        .await;
+    app.api(&session, "POST", &uri, None).await;
+
+    for _ in 0..2 {
+        let response = app
+            .api(&session, "POST", "/api/restore", Some(original.clone()))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        assert_eq!(
+            response.json(),
+            json!({
+                "categories": 1,
+                "stores": 0,
+                "items": 2,
+                "events": 1,
+                "recipes": 0,
+                "meal_plans": 0
+            })
+        );
+        // The same inventory each time, under new ids
+        let restored = backup(&app, &session).await;
+        assert_ne!(restored["items"][0]["id"], original["items"][0]["id"]);
+        assert_eq!(renumbered(restored), expected);
+    }
+
+    let items = app.api(&session, "GET", "/api/items", None).await.json();
+    let items: Vec<_> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| {
+            (
+                item["name"].clone(),
+                item["quantity"].clone(),
+                item["category"]["name"].clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        items,
+        [
+            (json!("Mleko"), json!(2), json!("Nabiał")),
+            (json!("Ryż"), json!(1), Value::Null),
+        ]
+    );
+}
+
+#[sqlx::test]
+async fn an_inconsistent_backup_changes_nothing(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    app.create_item(&session, "Mleko", json!({ "quantity": 2 }))
+        .await;
+    let before = backup(&app, &session).await;
+
+    let mut broken = before.clone();
+    broken["exported_at"] = json!("2026-10-17T12:00:00Z");
+    broken["items"][0]["category_id"] = json!(999_999);
+    let response = app
+        .api(&session, "POST", "/api/restore", Some(broken.clone()))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(
+        response.text().contains("unknown category"),
+        "{}",
+        response.text()
+    );
+    broken["items"][0]["category_id"] = Value::Null;
+    broken["version"] = json!(999);
+    let response = app
+        .api(&session, "POST", "/api/restore", Some(broken))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/restore",
+            Some(json!({ "items": [] })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    assert_eq!(backup(&app, &session).await, before);
+}
+
+#[sqlx::test]
+async fn a_stalled_backup_does_not_hold_its_snapshot_forever(pool: PgPool) {
+    let show = "SELECT current_setting('statement_timeout'),
+                       current_setting('idle_in_transaction_session_timeout')";
+    let mut snapshot = db::begin_snapshot(&pool).await.unwrap();
+    let limits: (String, String) = sqlx::query_as(show)
+        .fetch_one(&mut *snapshot)
+        .await
+        .unwrap();
+    assert_eq!(limits, ("15min".to_string(), "1min".to_string()));
+    snapshot.commit().await.unwrap();
+
+    // Only for the snapshot; the connection goes back to the pool without them
+    let mut connection = pool.acquire().await.unwrap();
+    let limits: (String, String) = sqlx::query_as(show)
+        .fetch_one(&mut *connection)
+        .await
+        .unwrap();
+    assert_eq!(limits, ("0".to_string(), "0".to_string()));
+}
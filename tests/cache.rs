@@ -0,0 +1,35 @@
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn cached_lookups_follow_changes_at_once(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    // Fills the cache with no categories and the default preferences
+    let page = app.get("/web/items/add", Some(&session)).await.text();
+    assert!(!page.contains("Nabiał"));
+
+    app.post_form(
+        "/web/categories/add",
+        &[("name", "Nabiał"), ("color", "#e0d8b0")],
+        Some(&session),
+    )
+    .await;
+    let page = app.get("/web/items/add", Some(&session)).await.text();
+    assert!(page.contains("Nabiał"));
+
+    let preferences = app.api(&session, "GET", "/api/preferences", None).await;
+    assert_eq!(preferences.json()["sort"], "manual");
+    let preferences = app
+        .api(
+            &session,
+            "PUT",
+            "/api/preferences",
+            Some(json!({ "sort": "quantity" })),
+        )
+        .await;
+    assert_eq!(preferences.json()["sort"], "quantity");
+    let preferences = app.api(&session, "GET", "/api/preferences", None).await;
+    assert_eq!(preferences.json()["sort"], "quantity");
+}
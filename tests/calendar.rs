@@ -0,0 +1,86 @@
+use axum::http::StatusCode;
+use household_inventory::calendar;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+use time::macros::date;
+
+#[sqlx::test]
+async fn the_feed_lists_expiry_dates(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let milk = app
+        .create_item(&session, "Mleko; 3,2%", json!({ "restock_threshold": 1 }))
+        .await;
+    let coffee = app
+        .create_item(&session, "Kawa", json!({ "restock_threshold": 1 }))
+        .await;
+    let purchase = json!({ "quantity": 2, "expires_on": "2031-05-04" });
+    let uri = format!("/api/items/{milk}/purchase");
+    app.api(&session, "POST", &uri, Some(purchase)).await;
+    let uri = format!("/api/items/{coffee}/purchase");
+    app.api(&session, "POST", &uri, Some(json!({ "quantity": 10 })))
+        .await;
+    let uri = format!("/api/items/{coffee}/use");
+    app.api(&session, "POST", &uri, Some(json!({ "quantity": 3 })))
+        .await;
+
+    let link = app
+        .create_link(&session, json!({ "label": "Rodzina", "scope": "calendar" }))
+        .await;
+    let token = link["token"].as_str().unwrap();
+    let response = app.get(&format!("/calendar/{token}.ics"), None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.headers["content-type"],
+        "text/calendar; charset=utf-8"
+    );
+    let feed = response.text();
+    assert!(feed.starts_with("BEGIN:VCALENDAR\r\n"), "{feed}");
+    assert!(feed.ends_with("END:VCALENDAR\r\n"), "{feed}");
+    assert!(feed.contains("DTSTART;VALUE=DATE:20310504\r\n"), "{feed}");
+    assert!(feed.contains("DTEND;VALUE=DATE:20310505\r\n"), "{feed}");
+    assert!(
+        feed.contains("SUMMARY:Termin ważności: Mleko\\; 3\\,2% (2 szt.)\r\n"),
+        "{feed}"
+    );
+    assert_eq!(feed.matches("BEGIN:VEVENT").count(), 1, "{feed}");
+
+    let feed = app
+        .get(&format!("/calendar/{token}.ics?run_out=true"), None)
+        .await
+        .text();
+    assert_eq!(feed.matches("BEGIN:VEVENT").count(), 2, "{feed}");
+    assert!(
+        feed.contains("SUMMARY:Prawdopodobnie skończy się: Kawa\r\n"),
+        "{feed}"
+    );
+
+    let page = app.get(&format!("/share/{token}"), None).await;
+    assert!(page.text().contains(&format!("{token}.ics")));
+
+    // Other links aren't feeds, and revoked ones are gone
+    let other = app
+        .create_link(
+            &session,
+            json!({ "label": "Rodzina", "scope": "shopping_list" }),
+        )
+        .await;
+    let uri = format!("/calendar/{}.ics", other["token"].as_str().unwrap());
+    assert_eq!(app.get(&uri, None).await.status, StatusCode::NOT_FOUND);
+    let uri = format!("/api/share-links/{}", link["id"]);
+    app.api(&session, "DELETE", &uri, None).await;
+    let response = app.get(&format!("/calendar/{token}.ics"), None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn items_run_out_at_their_recent_pace() {
+    let today = date!(2025 - 08 - 01);
+    // 3 used in 30 days leaves 7 for 70 more
+    assert_eq!(
+        calendar::run_out_date(7, 3, 30, today),
+        Some(date!(2025 - 10 - 10))
+    );
+    assert_eq!(calendar::run_out_date(7, 0, 30, today), None);
+}
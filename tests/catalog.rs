@@ -0,0 +1,100 @@
+use axum::http::StatusCode;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn finished_products_are_found_in_the_catalog(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    for (name, barcode) in [("Mleko UHT", "5900820000011"), ("Masło", "5900512000027")] {
+        let item = json!({ "name": name, "quantity": 1, "restock_threshold": 2, "unit": "l",
+                           "barcode": barcode, "category_id": null });
+        app.api(&session, "POST", "/api/items", Some(item)).await;
+    }
+    let items = app.api(&session, "GET", "/api/items", None).await.json();
+    let milk = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item["name"] == "Mleko UHT")
+        .unwrap();
+    assert_eq!(milk["barcode"], "5900820000011");
+
+    // The product is remembered after its item is gone
+    let uri = format!("/api/items/{}", milk["id"]);
+    app.api(&session, "DELETE", &uri, None).await;
+    let found = app
+        .api(&session, "GET", "/api/catalog?q=mle", None)
+        .await
+        .json();
+    assert_eq!(found.as_array().unwrap().len(), 1);
+    assert_eq!(found[0]["name"], "Mleko UHT");
+    assert_eq!(found[0]["unit"], "l");
+    assert_eq!(found[0]["restock_threshold"], 2);
+    assert!(found[0]["item_id"].is_null());
+    let found = app
+        .api(&session, "GET", "/api/catalog?q=5900512000027", None)
+        .await
+        .json();
+    assert_eq!(found[0]["name"], "Masło");
+    assert!(found[0]["item_id"].is_number());
+    let all = app.api(&session, "GET", "/api/catalog", None).await.json();
+    assert_eq!(all.as_array().unwrap().len(), 2);
+
+    let page = app.get("/web/items/add", Some(&session)).await.text();
+    assert!(
+        page.contains(r#"data-catalog-url="/api/catalog""#),
+        "{page}"
+    );
+
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let found = app.api(&other, "GET", "/api/catalog?q=mle", None).await;
+    assert_eq!(found.json(), json!([]));
+    let uri = format!("/api/catalog/{}", all[0]["id"]);
+    let response = app.api(&other, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let all = app.api(&session, "GET", "/api/catalog", None).await.json();
+    assert_eq!(all.as_array().unwrap().len(), 1);
+}
+
+#[sqlx::test]
+async fn unknown_barcodes_and_bad_queries_find_nothing(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    for (name, barcode) in [("Mleko", "5900820000011"), ("Masło", "5900512000027")] {
+        app.create_item(&session, name, json!({ "barcode": barcode }))
+            .await;
+    }
+
+    // A barcode only matches whole
+    for q in ["5901234123457", "59005120"] {
+        let found = app
+            .api(&session, "GET", &format!("/api/catalog?q={q}"), None)
+            .await
+            .json();
+        assert_eq!(found, json!([]), "{q}");
+    }
+    let found = app
+        .api(&session, "GET", "/api/catalog?limit=0", None)
+        .await
+        .json();
+    assert_eq!(found.as_array().unwrap().len(), 1);
+    let response = app
+        .api(&session, "GET", "/api/catalog?limit=dużo", None)
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    // Nor is a barcode too long for one remembered
+    let barcode = "5".repeat(200);
+    let item = json!({ "name": "Ser", "quantity": 1, "barcode": barcode, "category_id": null });
+    let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(response.json()["details"]["fields"]["barcode"].is_string());
+    let found = app
+        .api(&session, "GET", "/api/catalog?q=ser", None)
+        .await
+        .json();
+    assert_eq!(found, json!([]));
+}
@@ -0,0 +1,270 @@
+use axum::http::StatusCode;
+use household_inventory::testing::{Session, TestApp};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+/// The category of each item, by item name.
+async fn categories_of_items(app: &TestApp, session: &Session) -> Vec<(String, Value)> {
+    let items = app.api(session, "GET", "/api/items", None).await.json();
+    let mut categories: Vec<(String, Value)> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| {
+            let name = item["name"].as_str().unwrap().to_string();
+            (name, item["category"]["id"].clone())
+        })
+        .collect();
+    categories.sort_by(|a, b| a.0.cmp(&b.0));
+    categories
+}
+
+#[sqlx::test]
+async fn a_deleted_categorys_items_move_or_lose_their_category(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let cleaning = app.create_category(&session, "Cleaning", None).await;
+    let czyszczenie = app.create_category(&session, "Czyszczenie", None).await;
+    let pantry = app.create_category(&session, "Spiżarnia", None).await;
+    for (name, category_id) in [("Płyn", cleaning), ("Mąka", pantry)] {
+        app.create_item(&session, name, json!({ "category_id": category_id }))
+            .await;
+    }
+
+    // Into the category being deleted or one that doesn't exist is refused,
+    // and the category stays as it was
+    for target in [cleaning, 999_999] {
+        let uri = format!("/api/categories/{cleaning}?reassign_to={target}");
+        let response = app.api(&session, "DELETE", &uri, None).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{target}");
+    }
+    let uri = format!("/api/categories/{cleaning}?reassign_to={czyszczenie}");
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+    let uri = format!("/api/categories/{pantry}?reassign_to=");
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+
+    assert_eq!(
+        categories_of_items(&app, &session).await,
+        [
+            ("Mąka".to_string(), Value::Null),
+            ("Płyn".to_string(), json!(czyszczenie)),
+        ]
+    );
+    let tree = app
+        .api(&session, "GET", "/api/categories/tree", None)
+        .await
+        .json();
+    assert_eq!(tree.as_array().unwrap().len(), 1);
+    assert_eq!(tree[0]["name"], "Czyszczenie");
+    let uri = format!("/api/categories/{cleaning}");
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+/// The tree as `(name, [child names])`, in its order.
+async fn tree(app: &TestApp, session: &Session) -> Vec<(String, Vec<String>)> {
+    let tree = app
+        .api(session, "GET", "/api/categories/tree", None)
+        .await
+        .json();
+    let name = |node: &Value| node["name"].as_str().unwrap().to_string();
+    tree.as_array()
+        .unwrap()
+        .iter()
+        .map(|node| {
+            let children = node["children"].as_array().unwrap();
+            (name(node), children.iter().map(name).collect())
+        })
+        .collect()
+}
+
+#[sqlx::test]
+async fn a_merged_category_hands_over_its_items_and_subcategories(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let dairy = app.create_category(&session, "Nabiał", None).await;
+    app.create_category(&session, "Sery", Some(dairy)).await;
+    let products = app.create_category(&session, "Produkty", None).await;
+    let frozen = app.create_category(&session, "Mrożonki", None).await;
+    app.create_category(&session, "Lody", Some(frozen)).await;
+    let vegetables = app
+        .create_category(&session, "Warzywa", Some(products))
+        .await;
+    app.create_item(&session, "Mleko", json!({ "category_id": dairy }))
+        .await;
+    app.create_item(&session, "Groszek", json!({ "category_id": frozen }))
+        .await;
+
+    for target in [dairy, 999_999] {
+        let uri = format!("/api/categories/{dairy}/merge?into={target}");
+        let response = app.api(&session, "POST", &uri, None).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{target}");
+    }
+    let uri = format!("/api/categories/999999/merge?into={products}");
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // A top-level target adopts the subcategories
+    let uri = format!("/api/categories/{dairy}/merge?into={products}");
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+    // A subcategory can't, so they become top-level
+    let uri = format!("/api/categories/{frozen}/merge?into={vegetables}");
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+
+    assert_eq!(
+        categories_of_items(&app, &session).await,
+        [
+            ("Groszek".to_string(), json!(vegetables)),
+            ("Mleko".to_string(), json!(products)),
+        ]
+    );
+    let mut tree = tree(&app, &session).await;
+    for (_, children) in &mut tree {
+        children.sort();
+    }
+    tree.sort();
+    assert_eq!(
+        tree,
+        [
+            ("Lody".to_string(), vec![]),
+            (
+                "Produkty".to_string(),
+                vec!["Sery".to_string(), "Warzywa".to_string()]
+            ),
+        ]
+    );
+}
+
+#[sqlx::test]
+async fn the_tree_nests_subcategories_and_counts_their_items(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let home = app.create_category(&session, "Dom", None).await;
+    let kitchen = app.create_category(&session, "Kuchnia", Some(home)).await;
+    let bathroom = app.create_category(&session, "Łazienka", Some(home)).await;
+    let garden = app.create_category(&session, "Ogród", None).await;
+    for (name, category_id) in [
+        ("Żarówki", home),
+        ("Gąbki", kitchen),
+        ("Folia", kitchen),
+        ("Mydło", bathroom),
+    ] {
+        app.create_item(&session, name, json!({ "category_id": category_id }))
+            .await;
+    }
+
+    let tree = app
+        .api(&session, "GET", "/api/categories/tree", None)
+        .await
+        .json();
+    let roots = tree.as_array().unwrap();
+    assert_eq!(roots.len(), 2, "{tree}");
+    let home_node = roots.iter().find(|node| node["id"] == home).unwrap();
+    assert_eq!(home_node["item_count"], 1);
+    assert_eq!(home_node["total_item_count"], 4);
+    let children: Vec<_> = home_node["children"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| (node["name"].clone(), node["item_count"].clone()))
+        .collect();
+    assert_eq!(children.len(), 2);
+    assert!(children.contains(&(json!("Kuchnia"), json!(2))));
+    assert!(children.contains(&(json!("Łazienka"), json!(1))));
+    let garden_node = roots.iter().find(|node| node["id"] == garden).unwrap();
+    assert_eq!(garden_node["total_item_count"], 0);
+    assert_eq!(garden_node["children"], json!([]));
+
+    // Two levels at most: no subcategory of a subcategory, and a category
+    // with subcategories stays top-level
+    let kitchen = kitchen.to_string();
+    let response = app
+        .post_form(
+            "/web/categories/add",
+            &[
+                ("name", "Szuflady"),
+                ("color", "#e0d8b0"),
+                ("parent_id", &kitchen),
+            ],
+            Some(&session),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let garden = garden.to_string();
+    let response = app
+        .post_form(
+            &format!("/web/categories/edit/{home}"),
+            &[
+                ("name", "Dom"),
+                ("color", "#e0d8b0"),
+                ("parent_id", &garden),
+            ],
+            Some(&session),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let after = app
+        .api(&session, "GET", "/api/categories/tree", None)
+        .await
+        .json();
+    assert_eq!(after, tree);
+}
+
+#[sqlx::test]
+async fn categories_keep_the_order_they_were_dragged_into(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let home = app.create_category(&session, "Dom", None).await;
+    let kitchen = app.create_category(&session, "Kuchnia", Some(home)).await;
+    let bathroom = app.create_category(&session, "Łazienka", Some(home)).await;
+    let garden = app.create_category(&session, "Ogród", None).await;
+    let pantry = app.create_category(&session, "Spiżarnia", None).await;
+
+    let body = json!({ "ids": [pantry, home, pantry] });
+    let response = app
+        .api(&session, "POST", "/api/categories/reorder", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let body = json!({ "ids": [pantry, garden, home, bathroom, kitchen] });
+    let response = app
+        .api(&session, "POST", "/api/categories/reorder", Some(body))
+        .await;
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+
+    assert_eq!(
+        tree(&app, &session).await,
+        [
+            ("Spiżarnia".to_string(), vec![]),
+            ("Ogród".to_string(), vec![]),
+            (
+                "Dom".to_string(),
+                vec!["Łazienka".to_string(), "Kuchnia".to_string()]
+            ),
+        ]
+    );
+}
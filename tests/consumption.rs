@@ -0,0 +1,160 @@
+use axum::http::StatusCode;
+use household_inventory::consumption;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+#[sqlx::test]
+async fn staples_are_used_up_on_their_own(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let item = json!({ "name": "Tabletki do zmywarki", "quantity": 10, "restock_threshold": 3,
+                       "category_id": null });
+    let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+    let tabs = response.json()["id"].as_i64().unwrap();
+
+    let uri = format!("/api/items/{tabs}/consumption-rule");
+    let rule = json!({ "quantity": 2, "every_days": 1 });
+    let response = app.api(&session, "PUT", &uri, Some(rule)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["item_name"], "Tabletki do zmywarki");
+    let response = app
+        .api(&session, "GET", "/api/consumption-rules", None)
+        .await;
+    assert_eq!(response.json()[0]["every_days"], 1);
+
+    // Two days went by, the second run is caught up too
+    let now = OffsetDateTime::now_utc();
+    for _ in 0..2 {
+        consumption::apply_due(&pool, now + Duration::hours(60))
+            .await
+            .unwrap();
+    }
+    let uri = format!("/api/items/{tabs}");
+    let item = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(item["quantity"], 6);
+    let uri = format!("/api/activity?item_id={tabs}");
+    let events = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(events["items"][0]["kind"], "used");
+    assert_eq!(events["items"][0]["quantity_delta"], -4);
+
+    // It stops at nothing left, which is below the restock threshold
+    consumption::apply_due(&pool, now + Duration::days(10))
+        .await
+        .unwrap();
+    let item = app
+        .api(&session, "GET", &format!("/api/items/{tabs}"), None)
+        .await
+        .json();
+    assert_eq!(item["quantity"], 0);
+    let list = app.api(&session, "GET", "/api/shopping-list", None).await;
+    assert!(
+        list.text().contains("Tabletki do zmywarki"),
+        "{}",
+        list.text()
+    );
+
+    let uri = format!("/api/items/{tabs}/consumption-rule");
+    let response = app
+        .api(
+            &session,
+            "PUT",
+            &uri,
+            Some(json!({ "quantity": 1, "every_days": 0 })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let rule = json!({ "quantity": 1, "every_days": 7 });
+    let response = app.api(&other, "PUT", &uri, Some(rule)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // The item page sets and removes it with forms
+    let response = app
+        .post_form(
+            &format!("/web/items/{tabs}/consumption-rule"),
+            &[("quantity", "1"), ("every_days", "7")],
+            Some(&session),
+        )
+        .await;
+    assert!(response.status.is_redirection());
+    let page = app
+        .get(&format!("/web/items/{tabs}"), Some(&session))
+        .await
+        .text();
+    assert!(page.contains("Zużywane 1 szt. co 7 dni"), "{page}");
+    app.post_form(
+        &format!("/web/items/{tabs}/consumption-rule/delete"),
+        &[],
+        Some(&session),
+    )
+    .await;
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn rules_that_cannot_run_are_rejected(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool.clone()).await;
+    let food = app
+        .create_item(&session, "Karma dla kota", json!({ "quantity": 5 }))
+        .await;
+    let uri = format!("/api/items/{food}/consumption-rule");
+    let rule = json!({ "quantity": 1, "every_days": 7 });
+    let response = app.api(&session, "PUT", &uri, Some(rule.clone())).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+
+    for (bad, field) in [
+        (json!({ "quantity": 0, "every_days": 1 }), "quantity"),
+        (json!({ "quantity": -2, "every_days": 1 }), "quantity"),
+        (json!({ "quantity": 1, "every_days": 367 }), "every_days"),
+    ] {
+        let response = app.api(&session, "PUT", &uri, Some(bad.clone())).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY, "{bad}");
+        let fields = &response.json()["details"]["fields"];
+        assert!(fields[field].is_string(), "{bad}: {fields}");
+    }
+    for bad in [
+        json!({ "quantity": 1 }),
+        json!({ "quantity": "dużo", "every_days": 1 }),
+    ] {
+        let response = app.api(&session, "PUT", &uri, Some(bad.clone())).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY, "{bad}");
+    }
+    let response = app
+        .post_form(
+            &format!("/web/items/{food}/consumption-rule"),
+            &[("quantity", "1"), ("every_days", "0")],
+            Some(&session),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let response = app
+        .api(
+            &session,
+            "PUT",
+            "/api/items/999999/consumption-rule",
+            Some(rule),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // The rule set first is still the one that runs
+    let response = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(response["quantity"], 1);
+    assert_eq!(response["every_days"], 7);
+    let rules = app
+        .api(&session, "GET", "/api/consumption-rules", None)
+        .await
+        .json();
+    assert_eq!(rules.as_array().unwrap().len(), 1);
+    consumption::apply_due(&pool, OffsetDateTime::now_utc() + Duration::days(8))
+        .await
+        .unwrap();
+    let item = app
+        .api(&session, "GET", &format!("/api/items/{food}"), None)
+        .await
+        .json();
+    assert_eq!(item["quantity"], 4);
+}
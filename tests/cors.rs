@@ -0,0 +1,75 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use household_inventory::cors::CorsSettings;
+use household_inventory::testing::TestApp;
+use sqlx::PgPool;
+
+const SPA: &str = "https://pantry.example.com";
+
+fn app_allowing_spa(pool: PgPool) -> TestApp {
+    TestApp::with_state(pool, |state| {
+        state.cors = CorsSettings {
+            allowed_origins: vec![SPA.to_string()],
+            allow_credentials: true,
+            allowed_headers: vec![],
+        }
+    })
+}
+
+fn preflight(uri: &str, origin: &str) -> Request<Body> {
+    Request::options(uri)
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn api_preflight_from_an_allowed_origin_succeeds(pool: PgPool) {
+    let app = app_allowing_spa(pool);
+
+    let response = app.send(preflight("/api/items/1", SPA)).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], SPA);
+    assert_eq!(
+        response.headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS],
+        "true"
+    );
+}
+
+#[sqlx::test]
+async fn other_origins_and_web_pages_get_no_cors_headers(pool: PgPool) {
+    let app = app_allowing_spa(pool);
+
+    let response = app
+        .send(preflight("/api/items/1", "https://evil.example.com"))
+        .await;
+    assert!(
+        !response
+            .headers
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+
+    let response = app.send(preflight("/web/login", SPA)).await;
+    assert!(
+        !response
+            .headers
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+}
+
+#[sqlx::test]
+async fn api_errors_carry_cors_headers(pool: PgPool) {
+    let app = app_allowing_spa(pool);
+    let request = Request::get("/api/items")
+        .header(header::ORIGIN, SPA)
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.send(request).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], SPA);
+}
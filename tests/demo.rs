@@ -0,0 +1,32 @@
+use household_inventory::{backup, db, demo};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+#[test]
+fn sample_data_is_a_valid_backup() {
+    backup::validate(&demo::sample_data(OffsetDateTime::now_utc())).unwrap();
+}
+
+#[sqlx::test]
+async fn reset_creates_the_account_and_restores_the_sample_data(pool: PgPool) {
+    demo::reset(&pool).await.unwrap();
+    let account = db::get_account_by_email(&pool, demo::DEMO_EMAIL)
+        .await
+        .unwrap()
+        .expect("demo account");
+    db::delete_item(&pool, account.id, first_item(&pool, account.id).await)
+        .await
+        .unwrap();
+
+    let summary = demo::reset(&pool).await.unwrap();
+
+    let expected = demo::sample_data(OffsetDateTime::now_utc());
+    assert_eq!(summary.items, expected.items.len());
+    let backup = db::export_backup(&pool, account.id).await.unwrap();
+    assert_eq!(backup.items.len(), expected.items.len());
+    assert_eq!(backup.recipes.len(), expected.recipes.len());
+}
+
+async fn first_item(pool: &PgPool, user_id: i32) -> i32 {
+    db::export_backup(pool, user_id).await.unwrap().items[0].id
+}
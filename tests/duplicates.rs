@@ -0,0 +1,108 @@
+use axum::http::StatusCode;
+use household_inventory::duplicates::normalize_name;
+use household_inventory::testing::TestApp;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+#[test]
+fn names_are_compared_without_case_accents_and_punctuation() {
+    assert_eq!(normalize_name("Mleko 2%"), "mleko 2");
+    assert_eq!(normalize_name("  mleko  2 % "), "mleko 2");
+    assert_eq!(normalize_name("Żółty ser, plastry"), "zolty ser plastry");
+    assert_ne!(normalize_name("Masło"), normalize_name("Mleko"));
+}
+
+#[sqlx::test]
+async fn duplicates_are_merged_with_their_stock_and_history(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let mut ids = Vec::new();
+    for (name, barcode) in [
+        ("Mleko 2%", None),
+        ("mleko 2 %", None),
+        ("Kawa ziarnista", Some("5900000000001")),
+        ("Kawa Lavazza", Some("5900000000001")),
+        ("Masło", None),
+    ] {
+        let item = json!({ "name": name, "quantity": 2, "restock_threshold": 1,
+                           "barcode": barcode, "category_id": null });
+        let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+        ids.push(response.json()["id"].as_i64().unwrap());
+    }
+    let (milk, other_milk, coffee) = (ids[0], ids[1], ids[2]);
+
+    let groups = app
+        .api(&session, "GET", "/api/items/duplicates", None)
+        .await
+        .json();
+    let names = |group: &Value| -> Vec<String> {
+        let items = group["items"].as_array().unwrap();
+        let mut names: Vec<String> = items.iter().map(|i| i["name"].to_string()).collect();
+        names.sort();
+        names
+    };
+    assert_eq!(groups.as_array().unwrap().len(), 2, "{groups}");
+    assert_eq!(groups[0]["reason"], "barcode");
+    assert_eq!(names(&groups[0]).len(), 2);
+    assert_eq!(groups[1]["reason"], "name");
+    assert_eq!(names(&groups[1]), [r#""Mleko 2%""#, r#""mleko 2 %""#]);
+
+    // The duplicate's purchases, history and recipes go with it
+    let purchase = json!({ "quantity": 3, "price": "9,00" });
+    let uri = format!("/api/items/{other_milk}/purchase");
+    app.api(&session, "POST", &uri, Some(purchase)).await;
+    let recipe = json!({ "name": "Naleśniki", "instructions": null, "ingredients": [
+        { "item_id": milk, "quantity": 1 }, { "item_id": other_milk, "quantity": 1 }] });
+    let recipe = app
+        .api(&session, "POST", "/api/recipes", Some(recipe))
+        .await;
+    assert!(recipe.status.is_success(), "{}", recipe.text());
+
+    let uri = format!("/api/items/{other_milk}/merge?into={milk}");
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["quantity"], 7);
+    let uri = format!("/api/items/{other_milk}");
+    let response = app.api(&session, "GET", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let uri = format!("/api/purchases?item_id={milk}");
+    let purchases = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(purchases["items"].as_array().unwrap().len(), 1);
+    let uri = format!("/api/items/{milk}/batches");
+    let batches = app.api(&session, "GET", &uri, None).await.json();
+    let total: i64 = batches
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b["quantity"].as_i64().unwrap())
+        .sum();
+    assert_eq!(total, 7);
+    let uri = format!("/api/recipes/{}", recipe.json()["id"]);
+    let recipe = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(recipe["ingredients"][0]["quantity"], 2, "{recipe}");
+
+    let uri = format!("/api/items/{milk}/merge?into={milk}");
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    // The web page lists the rest and merges with a form
+    let page = app
+        .get("/web/items/duplicates", Some(&session))
+        .await
+        .text();
+    assert!(page.contains("Ten sam kod kreskowy"), "{page}");
+    assert!(!page.contains("Podobna nazwa"), "{page}");
+    let response = app
+        .post_form(
+            &format!("/web/items/{}/merge", ids[3]),
+            &[("into", &coffee.to_string())],
+            Some(&session),
+        )
+        .await;
+    assert!(response.status.is_redirection());
+    let groups = app
+        .api(&session, "GET", "/api/items/duplicates", None)
+        .await
+        .json();
+    assert_eq!(groups, json!([]));
+}
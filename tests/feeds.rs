@@ -0,0 +1,55 @@
+use axum::http::StatusCode;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn the_restock_feed_lists_items_below_their_threshold(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    app.create_item(
+        &session,
+        "Mleko & miód",
+        json!({ "quantity": 1, "restock_threshold": 2 }),
+    )
+    .await;
+    app.create_item(
+        &session,
+        "Ryż",
+        json!({ "quantity": 5, "restock_threshold": 2 }),
+    )
+    .await;
+
+    let link = app
+        .create_link(
+            &session,
+            json!({ "label": "Czytnik", "scope": "restock_feed" }),
+        )
+        .await;
+    let token = link["token"].as_str().unwrap();
+    let uri = format!("/feeds/{token}/restock.atom");
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.headers["content-type"],
+        "application/atom+xml; charset=utf-8"
+    );
+    let feed = response.text();
+    assert!(feed.starts_with("<?xml"), "{feed}");
+    assert_eq!(feed.matches("<entry>").count(), 1, "{feed}");
+    assert!(
+        feed.contains("<title>Potrzeba uzupełnienia: Mleko &amp; miód</title>"),
+        "{feed}"
+    );
+    assert!(
+        feed.contains("Aktualna ilość: 1, próg uzupełnienia: 2"),
+        "{feed}"
+    );
+    assert!(!feed.contains("Ryż"), "{feed}");
+
+    let other = app
+        .create_link(&session, json!({ "label": "Czytnik", "scope": "calendar" }))
+        .await;
+    let uri = format!("/feeds/{}/restock.atom", other["token"].as_str().unwrap());
+    assert_eq!(app.get(&uri, None).await.status, StatusCode::NOT_FOUND);
+}
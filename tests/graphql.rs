@@ -0,0 +1,116 @@
+use axum::http::StatusCode;
+use household_inventory::testing::{Session, TestApp};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+/// Runs `query` and returns its `data` and `errors`.
+async fn graphql(app: &TestApp, session: &Session, query: &str, variables: Value) -> Value {
+    let body = json!({ "query": query, "variables": variables });
+    let response = app.api(session, "POST", "/api/graphql", Some(body)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    response.json()
+}
+
+#[sqlx::test]
+async fn items_come_with_their_category_and_latest_events(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool.clone()).await;
+    sqlx::query(
+        "INSERT INTO categories (user_id, name, color) SELECT id, 'Nabiał', '#ffffff' FROM users",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    let categories = graphql(&app, &session, "{ categories { id name } }", json!({})).await;
+    let category = &categories["data"]["categories"][0];
+    assert_eq!(category["name"], "Nabiał", "{categories}");
+    for name in ["Mleko", "Ser"] {
+        let created = graphql(
+            &app,
+            &session,
+            "mutation($input: CreateItemInput!) { createItem(input: $input) { id } }",
+            json!({ "input": { "name": name, "quantity": 4, "restockThreshold": 2, "categoryId": category["id"] } }),
+        )
+        .await;
+        let id = &created["data"]["createItem"]["id"];
+        let used = graphql(
+            &app,
+            &session,
+            "mutation($id: Int!) { useItem(id: $id, quantity: 3) { quantity } }",
+            json!({ "id": id }),
+        )
+        .await;
+        assert_eq!(used["data"]["useItem"]["quantity"], 1, "{used}");
+    }
+
+    let result = graphql(
+        &app,
+        &session,
+        "{ items(filter: LOW) { name category { name } latestEvents(limit: 1) { kind quantityDelta } } }",
+        json!({}),
+    )
+    .await;
+    let items = result["data"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2, "{result}");
+    assert_eq!(items[0]["name"], "Mleko");
+    assert_eq!(items[0]["category"]["name"], "Nabiał");
+    assert_eq!(
+        items[1]["latestEvents"],
+        json!([{ "kind": "used", "quantityDelta": -3 }])
+    );
+
+    let history = graphql(
+        &app,
+        &session,
+        "{ history(limit: 1) { events { itemName } nextCursor } }",
+        json!({}),
+    )
+    .await;
+    assert_eq!(
+        history["data"]["history"]["events"],
+        json!([{ "itemName": "Ser" }])
+    );
+    assert!(
+        history["data"]["history"]["nextCursor"].is_string(),
+        "{history}"
+    );
+}
+
+#[sqlx::test]
+async fn errors_carry_the_api_error_code(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let result = graphql(
+        &app,
+        &session,
+        "mutation { useItem(id: 12345) { id } }",
+        json!({}),
+    )
+    .await;
+    assert_eq!(
+        result["errors"][0]["extensions"]["code"], "ITEM_NOT_FOUND",
+        "{result}"
+    );
+
+    let result = graphql(
+        &app,
+        &session,
+        r#"mutation { createItem(input: { name: "", quantity: -1 }) { id } }"#,
+        json!({}),
+    )
+    .await;
+    let error = &result["errors"][0];
+    assert_eq!(error["extensions"]["code"], "VALIDATION_FAILED", "{result}");
+    assert_eq!(
+        error["extensions"]["details"]["fields"]["quantity"],
+        "must not be negative"
+    );
+}
+
+#[sqlx::test]
+async fn the_endpoint_needs_a_session(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let body = json!({ "query": "{ items { id } }" });
+    let response = app
+        .api(&Session(String::new()), "POST", "/api/graphql", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,141 @@
+use axum::http::StatusCode;
+use household_inventory::testing::TestApp;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+/// Objects the way Grocy's API returns them, numbers as text included.
+fn export() -> Value {
+    json!({
+        "products": [
+            {
+                "id": "1", "name": "Mleko ", "location_id": "1", "qu_id_stock": "2",
+                "product_group_id": "1", "min_stock_amount": "2"
+            },
+            {
+                "id": 2, "name": "Ryż", "location_id": null, "qu_id_stock": 1,
+                "product_group_id": "", "min_stock_amount": 0
+            }
+        ],
+        "quantity_units": [{ "id": 1, "name": "kg" }, { "id": "2", "name": "l" }],
+        "locations": [{ "id": "1", "name": "Lodówka" }],
+        "product_groups": [{ "id": "1", "name": "Nabiał" }],
+        "stock": [
+            {
+                "product_id": "1", "amount": "1.5",
+                "best_before_date": "2026-10-20", "purchased_date": "2026-10-10"
+            },
+            {
+                "product_id": 1, "amount": 1,
+                "best_before_date": "2999-12-31", "purchased_date": "2026-10-12"
+            },
+            { "product_id": "2", "amount": "0" }
+        ]
+    })
+}
+
+#[sqlx::test]
+async fn a_grocy_export_becomes_items_categories_and_batches(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+
+    let response = app
+        .api(&session, "POST", "/api/import/grocy", Some(export()))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(
+        response.json(),
+        json!({
+            "categories_created": 1,
+            "items_created": 2,
+            "items_skipped": 0,
+            "batches_created": 2
+        })
+    );
+
+    let items = app.api(&session, "GET", "/api/items", None).await.json();
+    let milk = &items[0];
+    assert_eq!(milk["name"], "Mleko");
+    // An opened package still counts as one
+    assert_eq!(milk["quantity"], 3);
+    assert_eq!(milk["unit"], "l");
+    assert_eq!(milk["location"], "Lodówka");
+    assert_eq!(milk["restock_threshold"], 2);
+    assert_eq!(milk["category"]["name"], "Nabiał");
+    let rice = &items[1];
+    assert_eq!(rice["name"], "Ryż");
+    assert_eq!(rice["quantity"], 0);
+    assert_eq!(rice["unit"], "kg");
+    assert_eq!(rice["category"], Value::Null);
+
+    let uri = format!("/api/items/{}/batches", milk["id"]);
+    let batches = app.api(&session, "GET", &uri, None).await.json();
+    let batches: Vec<_> = batches
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| {
+            (
+                b["quantity"].clone(),
+                b["purchased_on"].clone(),
+                b["expires_on"].clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        batches,
+        [
+            (json!(2), json!("2026-10-10"), json!("2026-10-20")),
+            (json!(1), json!("2026-10-12"), Value::Null),
+        ]
+    );
+
+    // Importing again leaves what is already there alone
+    let response = app
+        .api(&session, "POST", "/api/import/grocy", Some(export()))
+        .await;
+    assert_eq!(
+        response.json(),
+        json!({
+            "categories_created": 0,
+            "items_created": 0,
+            "items_skipped": 2,
+            "batches_created": 0
+        })
+    );
+    let again = app.api(&session, "GET", "/api/items", None).await.json();
+    assert_eq!(again.as_array().unwrap().len(), 2);
+}
+
+#[sqlx::test]
+async fn unusable_grocy_input_imports_nothing(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+
+    let body = json!({ "url": "ftp://grocy.local", "api_key": "klucz" });
+    let response = app
+        .api(&session, "POST", "/api/import/grocy", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(
+        response.text().contains("invalid Grocy URL"),
+        "{}",
+        response.text()
+    );
+
+    let mut export = export();
+    export["stock"][0]["amount"] = json!("półtora");
+    let response = app
+        .api(&session, "POST", "/api/import/grocy", Some(export))
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/import/grocy",
+            Some(json!({ "items": [] })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let items = app.api(&session, "GET", "/api/items", None).await.json();
+    assert_eq!(items, json!([]));
+}
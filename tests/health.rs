@@ -0,0 +1,54 @@
+use axum::http::StatusCode;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn a_migrated_app_is_live_and_ready(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let live = app.get("/health/live", None).await;
+    assert_eq!(live.status, StatusCode::OK);
+    assert_eq!(live.json(), json!({ "status": "ok" }));
+    assert_eq!(app.get("/health", None).await.status, StatusCode::OK);
+
+    let ready = app.get("/health/ready", None).await;
+    assert_eq!(ready.status, StatusCode::OK);
+    let ready = ready.json();
+    assert_eq!(ready["status"], "ok");
+    assert_eq!(ready["database"]["status"], "ok");
+    assert_eq!(ready["migrations"]["status"], "ok");
+    assert_eq!(ready["templates"]["status"], "ok");
+}
+
+#[sqlx::test]
+async fn pending_migrations_make_the_app_unready(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    sqlx::query(
+        "DELETE FROM _sqlx_migrations
+         WHERE version = (SELECT MAX(version) FROM _sqlx_migrations)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let ready = app.get("/health/ready", None).await;
+
+    assert_eq!(ready.status, StatusCode::SERVICE_UNAVAILABLE);
+    let ready = ready.json();
+    assert_eq!(ready["status"], "unavailable");
+    assert_eq!(ready["database"]["status"], "ok");
+    assert_eq!(ready["migrations"]["status"], "unavailable");
+}
+
+#[sqlx::test]
+async fn losing_the_database_makes_the_app_unready_but_not_dead(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    pool.close().await;
+
+    let ready = app.get("/health/ready", None).await;
+    assert_eq!(ready.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(ready.json()["database"]["status"], "unavailable");
+
+    assert_eq!(app.get("/health/live", None).await.status, StatusCode::OK);
+}
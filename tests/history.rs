@@ -0,0 +1,97 @@
+use axum::http::StatusCode;
+use household_inventory::testing::{Session, TestApp};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+/// Follows `next_cursor` from `uri` to the last page and returns the ids of
+/// the events in order, and how many pages there were.
+async fn walk(app: &TestApp, session: &Session, uri: &str) -> (Vec<i64>, usize) {
+    let mut ids = vec![];
+    let mut pages = 0;
+    let mut next = uri.to_string();
+    loop {
+        let response = app.api(session, "GET", &next, None).await;
+        assert_eq!(response.status, StatusCode::OK);
+        let page = response.json();
+        pages += 1;
+        ids.extend(
+            page["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["id"].as_i64().unwrap()),
+        );
+        match &page["next_cursor"] {
+            Value::String(cursor) => next = format!("{uri}&after={cursor}"),
+            _ => return (ids, pages),
+        }
+    }
+}
+
+#[sqlx::test]
+async fn history_pages_cover_every_event_once(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool.clone()).await;
+    let milk = app
+        .create_item(&session, "Mleko", json!({ "quantity": 10 }))
+        .await;
+    let bread = app
+        .create_item(&session, "Chleb", json!({ "quantity": 10 }))
+        .await;
+    // Events sharing a timestamp must still be split between pages cleanly
+    sqlx::query(
+        "INSERT INTO item_events (user_id, item_id, item_name, kind, quantity_delta, created_at)
+         SELECT i.user_id, i.id, i.name, 'used', -1, '2025-06-01T12:00:00Z'::TIMESTAMPTZ
+         FROM items i, generate_series(1, 4)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    app.api(&session, "POST", &format!("/api/items/{milk}/use"), None)
+        .await;
+
+    let (ids, pages) = walk(&app, &session, "/api/history?limit=3").await;
+
+    let expected: Vec<i64> =
+        sqlx::query_scalar("SELECT id::INT8 FROM item_events ORDER BY created_at DESC, id DESC")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(expected.len(), 9);
+    assert_eq!(ids, expected);
+    assert_eq!(pages, 3);
+
+    let (milk_ids, _) = walk(
+        &app,
+        &session,
+        &format!("/api/items/{milk}/history?limit=2"),
+    )
+    .await;
+    assert_eq!(milk_ids.len(), 5);
+    let (bread_ids, _) = walk(
+        &app,
+        &session,
+        &format!("/api/items/{bread}/history?limit=2"),
+    )
+    .await;
+    assert_eq!(bread_ids.len(), 4);
+    assert!(bread_ids.iter().all(|id| !milk_ids.contains(id)));
+}
+
+#[sqlx::test]
+async fn history_rejects_bad_cursors_and_other_users_items(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let milk = app
+        .create_item(&session, "Mleko", json!({ "quantity": 10 }))
+        .await;
+
+    let response = app
+        .api(&session, "GET", "/api/history?after=nonsense", None)
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let response = app
+        .api(&other, "GET", &format!("/api/items/{milk}/history"), None)
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,156 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use household_inventory::testing::{TestApp, TestResponse};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+/// Calls the Home Assistant endpoints the way it does, with a token instead
+/// of a session.
+async fn with_token(
+    app: &TestApp,
+    token: &str,
+    method: &str,
+    uri: &str,
+    body: Option<Value>,
+) -> TestResponse {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"));
+    let request = match body {
+        Some(body) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+    app.send(request).await
+}
+
+#[sqlx::test]
+async fn a_token_reads_sensors_and_uses_items_until_revoked(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let category_id = app.create_category(&session, "Kuchnia", None).await;
+    let capsules = json!({ "quantity": 3, "restock_threshold": 2, "category_id": category_id });
+    app.create_item(&session, "Kapsułki do kawy", capsules)
+        .await;
+    let salt = app
+        .create_item(&session, "Sól", json!({ "restock_threshold": 1 }))
+        .await;
+
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/tokens",
+            Some(json!({ "label": "Home Assistant" })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    let created = response.json();
+    let token = created["token"].as_str().unwrap();
+    assert_eq!(token.len(), 64);
+
+    let sensors = with_token(&app, token, "GET", "/api/ha/sensors", None).await;
+    assert_eq!(sensors.status, StatusCode::OK, "{}", sensors.text());
+    let sensors = sensors.json();
+    assert_eq!(sensors["items"], 2);
+    assert_eq!(sensors["low_stock"], 1);
+    assert_eq!(sensors["out_of_stock"], 1);
+    assert_eq!(sensors["low_stock_items"], json!(["Sól"]));
+    assert_eq!(
+        sensors["categories"],
+        json!({ "Kuchnia": 1, "Bez kategorii": 1 })
+    );
+
+    // By name, ignoring case, one unit unless told otherwise
+    let consume = json!({ "name": "kapsułki do kawy" });
+    let response = with_token(&app, token, "POST", "/api/ha/consume", Some(consume)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["quantity"], 2);
+    let consume = json!({ "item_id": salt, "quantity": 5 });
+    let response = with_token(&app, token, "POST", "/api/ha/consume", Some(consume)).await;
+    assert_eq!(response.json()["quantity"], 0);
+    let sensors = with_token(&app, token, "GET", "/api/ha/sensors", None)
+        .await
+        .json();
+    assert_eq!(sensors["low_stock"], 1, "2 is not below the threshold of 2");
+
+    let consume = json!({ "name": "Mleko" });
+    let response = with_token(&app, token, "POST", "/api/ha/consume", Some(consume)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = with_token(&app, token, "POST", "/api/ha/consume", Some(json!({}))).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let tokens = app.api(&session, "GET", "/api/tokens", None).await.json();
+    assert!(tokens[0]["last_used_at"].is_string());
+    // The token itself is only ever in the answer that made it
+    assert_eq!(tokens[0]["prefix"], &token[..8]);
+    assert!(tokens[0].get("token").is_none());
+    assert!(!tokens.to_string().contains(token));
+    let page = app.get("/web/settings", Some(&session)).await.text();
+    assert!(page.contains(&token[..8]));
+    assert!(!page.contains(token));
+    let stored: String = sqlx::query_scalar("SELECT token_hash FROM api_tokens")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_ne!(stored, token);
+
+    let uri = format!("/api/tokens/{}", created["id"]);
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = with_token(&app, token, "GET", "/api/ha/sensors", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn the_endpoints_take_only_tokens(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    let response = app.api(&session, "GET", "/api/ha/sensors", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = with_token(&app, &"0".repeat(64), "GET", "/api/ha/sensors", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    // Nor does a token stand in for the session elsewhere
+    let created = app
+        .api(
+            &session,
+            "POST",
+            "/api/tokens",
+            Some(json!({ "label": "HA" })),
+        )
+        .await
+        .json();
+    let token = created["token"].as_str().unwrap();
+    let response = with_token(&app, token, "GET", "/api/items", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn the_settings_page_shows_a_new_token_once(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    let page = app
+        .post_form(
+            "/web/settings/api-tokens",
+            &[("label", "Home Assistant")],
+            Some(&session),
+        )
+        .await;
+    assert_eq!(page.status, StatusCode::OK, "{}", page.text());
+    let page = page.text();
+    let (_, shown) = page
+        .split_once("Nowy token „Home Assistant”: <code>")
+        .expect("the new token is on the page");
+    let token = &shown[..64];
+
+    let response = with_token(&app, token, "GET", "/api/ha/sensors", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let page = app.get("/web/settings", Some(&session)).await.text();
+    assert!(!page.contains(token));
+}
@@ -0,0 +1,275 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use household_inventory::testing::{Session, TestApp};
+use serde_json::json;
+use sqlx::PgPool;
+
+async fn user_id(pool: &PgPool, email: &str) -> i64 {
+    sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE email = $1")
+        .bind(email)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+        .into()
+}
+
+async fn invite(app: &TestApp, owner: &Session, household_id: i64, email: &str, role: &str) {
+    let uri = format!("/api/households/{household_id}/invitations");
+    let body = json!({ "email": email, "role": role });
+    let response = app.api(owner, "POST", &uri, Some(body)).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.text());
+}
+
+/// Ala's household with Bolek in it as `role`, and Bolek's session working
+/// in it. Returns the sessions and the household's id.
+async fn household(app: &TestApp, pool: &PgPool, role: &str) -> (Session, Session, i64) {
+    let owner = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let member = app.sign_up("Bolek", "bolek@example.com", "hunter2").await;
+    let household_id = user_id(pool, "ala@example.com").await;
+    app.create_item(&owner, "Mleko", json!({ "quantity": 2 }))
+        .await;
+
+    invite(app, &owner, household_id, "bolek@example.com", role).await;
+    let invitations = app
+        .api(&member, "GET", "/api/households/invitations", None)
+        .await
+        .json();
+    assert_eq!(invitations[0]["owner"], "Ala");
+    assert_eq!(invitations[0]["role"], role);
+    let uri = format!(
+        "/api/households/invitations/{}/accept",
+        invitations[0]["id"]
+    );
+    let response = app.api(&member, "POST", &uri, None).await;
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+
+    let body = json!({ "household_id": household_id });
+    let response = app
+        .api(&member, "PUT", "/api/households/current", Some(body))
+        .await;
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+    (owner, member, household_id)
+}
+
+#[sqlx::test]
+async fn viewers_see_the_inventory_without_changing_it(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let (_, viewer, household_id) = household(&app, &pool, "viewer").await;
+
+    let items = app.api(&viewer, "GET", "/api/items", None).await;
+    assert_eq!(items.status, StatusCode::OK, "{}", items.text());
+    assert_eq!(items.json()[0]["name"], "Mleko", "{}", items.text());
+    let households = app
+        .api(&viewer, "GET", "/api/households", None)
+        .await
+        .json();
+    assert_eq!(
+        households,
+        json!([{ "id": household_id, "owner": "Ala", "role": "viewer", "current": true }])
+    );
+
+    let item = json!({ "name": "Kawa", "quantity": 1, "category_id": null });
+    let response = app.api(&viewer, "POST", "/api/items", Some(item)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.json()["code"], "FORBIDDEN");
+    let page = app.get("/web", Some(&viewer)).await;
+    assert!(page.text().contains("Mleko"));
+    let response = app
+        .post_form("/web/items/add", &[("name", "Kawa")], Some(&viewer))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    // Back in their own, empty inventory
+    let body = json!({ "household_id": null });
+    app.api(&viewer, "PUT", "/api/households/current", Some(body))
+        .await;
+    let items = app.api(&viewer, "GET", "/api/items", None).await.json();
+    assert_eq!(items, json!([]));
+}
+
+#[sqlx::test]
+async fn editors_change_the_inventory_but_not_the_members(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let (owner, editor, household_id) = household(&app, &pool, "editor").await;
+    let editor_id = user_id(&pool, "bolek@example.com").await;
+
+    let item = json!({ "name": "Kawa", "quantity": 1, "category_id": null });
+    let response = app.api(&editor, "POST", "/api/items", Some(item)).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    let items = app.api(&owner, "GET", "/api/items", None).await.json();
+    assert_eq!(items.as_array().unwrap().len(), 2);
+
+    let uri = format!("/api/households/{household_id}/members/{editor_id}");
+    let response = app
+        .api(&editor, "PATCH", &uri, Some(json!({ "role": "viewer" })))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    // Nor anything of the owner's account
+    for uri in ["/api/tokens", "/api/sessions", "/web/settings"] {
+        let response = app.api(&editor, "GET", uri, None).await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN, "{uri}");
+    }
+
+    let response = app
+        .api(&owner, "PATCH", &uri, Some(json!({ "role": "viewer" })))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["role"], "viewer");
+    let item = json!({ "name": "Herbata", "quantity": 1, "category_id": null });
+    let response = app.api(&editor, "POST", "/api/items", Some(item)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    // Taken out, the session goes back to the member's own inventory
+    let response = app.api(&owner, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let items = app.api(&editor, "GET", "/api/items", None).await.json();
+    assert_eq!(items, json!([]));
+}
+
+#[sqlx::test]
+async fn only_members_get_into_a_household(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let owner = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let stranger = app.sign_up("Lolek", "lolek@example.com", "hunter2").await;
+    let household_id = user_id(&pool, "ala@example.com").await;
+
+    let body = json!({ "household_id": household_id });
+    let response = app
+        .api(&stranger, "PUT", "/api/households/current", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let uri = format!("/api/households/{household_id}/invitations");
+    let body = json!({ "email": "lolek@example.com", "role": "editor" });
+    let response = app.api(&stranger, "POST", &uri, Some(body)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let body = json!({ "email": "ala@example.com", "role": "editor" });
+    let response = app.api(&owner, "POST", &uri, Some(body)).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    // Nothing tells an email with an account from one without
+    let mut answers = Vec::new();
+    for email in ["lolek@example.com", "nikt@example.com"] {
+        let body = json!({ "email": email, "role": "editor" });
+        let response = app.api(&owner, "POST", &uri, Some(body)).await;
+        assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.text());
+        let mut answer = response.json();
+        for field in ["id", "email", "created_at"] {
+            answer.as_object_mut().unwrap().remove(field);
+        }
+        answers.push(answer);
+    }
+    assert_eq!(answers[0], answers[1]);
+
+    // An invitation is only accepted by the account it was for
+    let invitations = app.api(&owner, "GET", &uri, None).await.json();
+    let other = &invitations[1];
+    assert_eq!(other["email"], "nikt@example.com");
+    let accept = format!("/api/households/invitations/{}/accept", other["id"]);
+    let response = app.api(&stranger, "POST", &accept, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let members = format!("/api/households/{household_id}/members");
+    let members = app.api(&owner, "GET", &members, None).await.json();
+    assert_eq!(members, json!([]));
+}
+
+#[sqlx::test]
+async fn the_household_page_manages_roles(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let owner = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let member = app.sign_up("Bolek", "bolek@example.com", "hunter2").await;
+    let member_id = user_id(&pool, "bolek@example.com").await;
+
+    let response = app
+        .post_form(
+            "/web/households/invitations",
+            &[("email", "bolek@example.com"), ("role", "viewer")],
+            Some(&owner),
+        )
+        .await;
+    assert_eq!(
+        response.status,
+        StatusCode::SEE_OTHER,
+        "{}",
+        response.text()
+    );
+    let page = app.get("/web/households", Some(&member)).await.text();
+    assert!(page.contains("Przyjmij"));
+    let invitation = app
+        .api(&member, "GET", "/api/households/invitations", None)
+        .await
+        .json()[0]["id"]
+        .clone();
+    let uri = format!("/web/households/invitations/{invitation}/accept");
+    app.post_form(&uri, &[], Some(&member)).await;
+    let uri = format!("/web/households/members/{member_id}/role");
+    app.post_form(&uri, &[("role", "editor")], Some(&owner))
+        .await;
+
+    let page = app.get("/web/households", Some(&owner)).await.text();
+    assert!(page.contains("bolek@example.com"));
+    assert!(page.contains(r#"<option value="editor" selected>Edytor</option>"#));
+}
+
+#[sqlx::test]
+async fn switching_households_refreshes_a_cached_item_list(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let owner = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let member = app.sign_up("Bolek", "bolek@example.com", "hunter2").await;
+    let household_id = user_id(&pool, "ala@example.com").await;
+    for (session, name) in [(&owner, "Mleko"), (&member, "Kawa")] {
+        app.create_item(session, name, json!({ "quantity": 1 }))
+            .await;
+    }
+    invite(&app, &owner, household_id, "bolek@example.com", "viewer").await;
+    let invitation = app
+        .api(&member, "GET", "/api/households/invitations", None)
+        .await
+        .json()[0]["id"]
+        .clone();
+    let uri = format!("/api/households/invitations/{invitation}/accept");
+    app.api(&member, "POST", &uri, None).await;
+    // Changes less than a second old get no Last-Modified
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let page = app.get("/web", Some(&member)).await;
+    assert!(page.text().contains("Kawa"));
+    let last_modified = page.headers[header::LAST_MODIFIED].clone();
+
+    let household_id = household_id.to_string();
+    let response = app
+        .post_form(
+            "/web/households/current",
+            &[("household_id", &household_id)],
+            Some(&member),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    let request = Request::get("/web")
+        .header(header::COOKIE, &member.0)
+        .header(header::IF_MODIFIED_SINCE, &last_modified)
+        .body(Body::empty())
+        .unwrap();
+    let page = app.send(request).await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(page.text().contains("Mleko"));
+}
+
+#[sqlx::test]
+async fn members_sort_without_changing_the_owners_preferences(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let (owner, editor, _) = household(&app, &pool, "editor").await;
+
+    let page = app.get("/web?sort=quantity", Some(&editor)).await;
+    assert_eq!(page.status, StatusCode::OK);
+    let preferences = app.api(&owner, "GET", "/api/preferences", None).await;
+    assert_eq!(preferences.json()["sort"], "manual");
+}
@@ -0,0 +1,84 @@
+use household_inventory::db;
+use sqlx::postgres::PgArguments;
+use sqlx::{Arguments, Encode, PgPool, Postgres, Type};
+
+/// The plan of `sql` with sequential scans ruled out as far as possible:
+/// tables as small as these are read whole even when an index fits.
+async fn plan(
+    pool: &PgPool,
+    sql: &str,
+    user_id: i32,
+    value: impl Encode<'static, Postgres> + Type<Postgres> + Send + 'static,
+) -> String {
+    let mut arguments = PgArguments::default();
+    arguments.add(user_id).unwrap();
+    arguments.add(value).unwrap();
+    let mut tx = pool.begin().await.unwrap();
+    sqlx::query("SET LOCAL enable_seqscan = off")
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    let plan = db::explain(&mut *tx, sql, arguments, false).await.unwrap();
+    plan.join("\n")
+}
+
+#[sqlx::test]
+async fn page_lookups_use_an_index(pool: PgPool) {
+    // A few thousand items in a few dozen categories, with statistics
+    let account = db::create_account(&pool, "Ala", "ala@example.com", "not-a-hash")
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO categories (user_id, name, color)
+         SELECT $1, 'Category ' || n, '#cccccc' FROM generate_series(1, 40) AS n",
+    )
+    .bind(account.id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO items (user_id, name, quantity, restock_threshold, category_id)
+         SELECT $1, 'Item ' || n, n % 50, 3,
+                (SELECT MIN(id) FROM categories WHERE user_id = $1) + n % 40
+         FROM generate_series(1, 4000) AS n",
+    )
+    .bind(account.id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("ANALYZE items, categories")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let user_id = account.id;
+
+    let restock = plan(
+        &pool,
+        "SELECT id FROM items WHERE user_id = $1 AND quantity = $2",
+        user_id,
+        0,
+    )
+    .await;
+    assert!(restock.contains("idx_items_user_id_quantity"), "{restock}");
+
+    let by_category = plan(
+        &pool,
+        "SELECT id FROM items WHERE user_id = $1 AND category_id = $2",
+        user_id,
+        2,
+    )
+    .await;
+    assert!(
+        by_category.contains("idx_items_user_id_category_id"),
+        "{by_category}"
+    );
+
+    let by_name = plan(
+        &pool,
+        "SELECT id FROM categories WHERE user_id = $1 AND name = $2",
+        user_id,
+        "Category 7",
+    )
+    .await;
+    assert!(by_name.contains("idx_categories_user_id_name"), "{by_name}");
+}
@@ -0,0 +1,142 @@
+//! Round trips and timings of the item writes behind the API. The statement
+//! counts are exact, so a change that adds a query to these paths fails here;
+//! run with `--nocapture` to see the timings.
+
+use household_inventory::db;
+use household_inventory::models::{CreateItemPayload, UpdateItemPayload};
+use sqlx::PgPool;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tracing::instrument::WithSubscriber;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+const RUNS: u32 = 50;
+
+/// Counts the statements sqlx logs, one per round trip.
+struct StatementCounter(Arc<AtomicUsize>);
+
+impl<S: Subscriber> Layer<S> for StatementCounter {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        if event.metadata().target() == "sqlx::query" {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Statements `run` sent per call on average, and how long a call took.
+async fn measure<F, Fut>(name: &str, mut run: F) -> usize
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let statements = Arc::new(AtomicUsize::new(0));
+    let subscriber = tracing_subscriber::registry().with(StatementCounter(statements.clone()));
+    let started = Instant::now();
+    async {
+        for run_number in 0..RUNS {
+            run(run_number).await;
+        }
+    }
+    .with_subscriber(subscriber)
+    .await;
+    let per_call: Duration = started.elapsed() / RUNS;
+    let statements = statements.load(Ordering::Relaxed) / RUNS as usize;
+    eprintln!("{name}: {per_call:?} and {statements} statements per call");
+    statements
+}
+
+fn new_item(name: String) -> CreateItemPayload {
+    CreateItemPayload {
+        name,
+        quantity: 2,
+        restock_threshold: None,
+        restock_to: None,
+        unit: None,
+        location: None,
+        barcode: None,
+        notes: None,
+        category_id: None,
+        preferred_store_id: None,
+        quantity_step: None,
+        attributes: None,
+        expires_on: None,
+    }
+}
+
+fn rename(name: String, version: i32) -> UpdateItemPayload {
+    UpdateItemPayload {
+        name: Some(name),
+        quantity: None,
+        restock_threshold: None,
+        restock_to: None,
+        unit: None,
+        location: None,
+        barcode: None,
+        notes: None,
+        category_id: None,
+        preferred_store_id: None,
+        quantity_step: None,
+        attributes: None,
+        version,
+    }
+}
+
+#[sqlx::test]
+async fn item_writes_take_as_few_statements_as_possible(pool: PgPool) {
+    let account = db::create_account(&pool, "Ala", "ala@example.com", "not-a-hash")
+        .await
+        .unwrap();
+    let user = account.id;
+    // Opens the connection the runs below reuse
+    sqlx::query("SELECT 1").execute(&pool).await.unwrap();
+
+    let created = measure("create_item", |n| {
+        let pool = pool.clone();
+        async move {
+            db::create_item(&pool, user, new_item(format!("Item {n}")))
+                .await
+                .unwrap();
+        }
+    })
+    .await;
+    assert_eq!(created, 1);
+
+    let item = db::create_item(&pool, user, new_item("Mleko".to_string()))
+        .await
+        .unwrap();
+    let mut version = item.version;
+    let renamed = measure("update_item", |n| {
+        let pool = pool.clone();
+        let payload = rename(format!("Mleko {n}"), version);
+        version += 1;
+        async move {
+            db::update_item(&pool, user, item.id, payload)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+    })
+    .await;
+    assert_eq!(renamed, 1);
+
+    // Changing the quantity also brings the batches in line, in a transaction
+    let restocked = measure("update_item with quantity", |n| {
+        let pool = pool.clone();
+        let payload = UpdateItemPayload {
+            quantity: Some(n as i32 % 5),
+            ..rename("Mleko".to_string(), version)
+        };
+        version += 1;
+        async move {
+            db::update_item(&pool, user, item.id, payload)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+    })
+    .await;
+    assert_eq!(restocked, 5);
+}
@@ -0,0 +1,723 @@
+use axum::body::Body;
+use axum::http::{HeaderValue, Request, StatusCode, header};
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn item_crud_round_trip(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+
+    let created = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Mleko", "quantity": 2, "unit": "l", "category_id": null })),
+        )
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED);
+    let id = created.json()["id"].as_i64().unwrap();
+    let uri = format!("/api/items/{id}");
+
+    let fetched = app.api(&session, "GET", &uri, None).await;
+    assert_eq!(fetched.status, StatusCode::OK);
+    assert_eq!(fetched.json()["name"], "Mleko");
+    assert_eq!(fetched.json()["unit"], "l");
+
+    let version = fetched.json()["version"].clone();
+    let updated = app
+        .api(
+            &session,
+            "PUT",
+            &uri,
+            Some(json!({ "quantity": 5, "version": version })),
+        )
+        .await;
+    assert_eq!(updated.status, StatusCode::OK);
+    assert_eq!(updated.json()["quantity"], 5);
+    assert_eq!(updated.json()["name"], "Mleko");
+
+    let listed = app.api(&session, "GET", "/api/items", None).await;
+    assert_eq!(listed.status, StatusCode::OK);
+    let names: Vec<_> = listed
+        .json()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["name"].clone())
+        .collect();
+    assert_eq!(names, vec![json!("Mleko")]);
+
+    let deleted = app.api(&session, "DELETE", &uri, None).await;
+    assert!(deleted.status.is_success());
+    let gone = app.api(&session, "GET", &uri, None).await;
+    assert_eq!(gone.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn items_are_private_to_their_owner(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let created = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Chleb", "quantity": 1, "category_id": null })),
+        )
+        .await;
+    let uri = format!("/api/items/{}", created.json()["id"]);
+
+    assert_eq!(
+        app.api(&other, "GET", &uri, None).await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        app.api(&other, "DELETE", &uri, None).await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        app.api(&session, "GET", &uri, None).await.status,
+        StatusCode::OK
+    );
+}
+
+#[sqlx::test]
+async fn use_without_body_decrements_by_one(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let id = app
+        .create_item(&session, "Jajka", json!({ "quantity": 3 }))
+        .await;
+
+    let response = app
+        .api(&session, "POST", &format!("/api/items/{id}/use"), None)
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["quantity"], 2);
+}
+
+#[sqlx::test]
+async fn use_with_quantity_clamps_at_zero(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let id = app
+        .create_item(&session, "Jajka", json!({ "quantity": 4 }))
+        .await;
+    let uri = format!("/api/items/{id}/use");
+
+    let response = app
+        .api(&session, "POST", &uri, Some(json!({ "quantity": 3 })))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["quantity"], 1);
+
+    let response = app
+        .api(&session, "POST", &uri, Some(json!({ "quantity": 6 })))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["quantity"], 0);
+
+    let response = app
+        .api(&session, "POST", &uri, Some(json!({ "quantity": 0 })))
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[sqlx::test]
+async fn purchase_adds_quantity(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let id = app
+        .create_item(&session, "Jajka", json!({ "quantity": 1 }))
+        .await;
+
+    let response = app
+        .api(
+            &session,
+            "POST",
+            &format!("/api/items/{id}/purchase"),
+            Some(json!({ "quantity": 5 })),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["quantity"], 6);
+}
+
+#[sqlx::test]
+async fn use_and_purchase_are_scoped_to_the_owner(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let id = app
+        .create_item(&session, "Jajka", json!({ "quantity": 2 }))
+        .await;
+
+    let response = app
+        .api(&other, "POST", &format!("/api/items/{id}/use"), None)
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app
+        .api(
+            &other,
+            "POST",
+            &format!("/api/items/{id}/purchase"),
+            Some(json!({ "quantity": 1 })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let item = app
+        .api(&session, "GET", &format!("/api/items/{id}"), None)
+        .await;
+    assert_eq!(item.json()["quantity"], 2);
+}
+
+#[sqlx::test]
+async fn duplicate_item_names_are_rejected(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let item = json!({ "name": "Ryż", "quantity": 1, "category_id": null });
+
+    let first = app
+        .api(&session, "POST", "/api/items", Some(item.clone()))
+        .await;
+    let second = app.api(&session, "POST", "/api/items", Some(item)).await;
+
+    assert_eq!(first.status, StatusCode::CREATED);
+    assert_eq!(second.status, StatusCode::CONFLICT);
+    assert_eq!(second.json()["details"]["field"], "name");
+    assert_eq!(second.json()["code"], "ALREADY_EXISTS");
+}
+
+#[sqlx::test]
+async fn unknown_category_is_unprocessable(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let item = json!({ "name": "Ryż", "quantity": 1, "category_id": 999_999 });
+
+    let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.json()["details"]["field"], "category_id");
+    assert_eq!(response.json()["code"], "REFERENCE_NOT_FOUND");
+}
+
+#[sqlx::test]
+async fn invalid_items_list_every_offending_field(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let item = json!({ "name": " ", "quantity": -1, "restock_threshold": -5, "category_id": null });
+
+    let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.json()["code"], "VALIDATION_FAILED");
+    let fields = &response.json()["details"]["fields"];
+    assert_eq!(fields["name"], "must not be empty");
+    assert_eq!(fields["quantity"], "must not be negative");
+    assert_eq!(fields["restock_threshold"], "must not be negative");
+}
+
+#[sqlx::test]
+async fn invalid_item_form_is_shown_again_with_messages(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+
+    let response = app
+        .post_form(
+            "/web/items/add",
+            &[("name", "Mleko"), ("quantity", "-2"), ("category_id", "")],
+            Some(&session),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let page = response.text();
+    assert!(page.contains(r#"value="Mleko""#), "keeps the input");
+    assert!(page.contains("must not be negative"));
+}
+
+#[sqlx::test]
+async fn negative_purchase_leaves_the_quantity_alone(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let created = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Mleko", "quantity": 2, "category_id": null })),
+        )
+        .await;
+    let id = created.json()["id"].as_i64().unwrap();
+
+    let response = app
+        .api(
+            &session,
+            "POST",
+            &format!("/api/items/{id}/purchase"),
+            Some(json!({ "quantity": -5 })),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        response.json()["details"]["fields"]["quantity"],
+        "must be greater than zero"
+    );
+    let item = app
+        .api(&session, "GET", &format!("/api/items/{id}"), None)
+        .await;
+    assert_eq!(item.json()["quantity"], 2);
+}
+
+#[sqlx::test]
+async fn database_rejects_negative_quantities(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    app.api(
+        &session,
+        "POST",
+        "/api/items",
+        Some(json!({ "name": "Mleko", "quantity": 2, "category_id": null })),
+    )
+    .await;
+
+    let err = sqlx::query("UPDATE items SET quantity = quantity - 3")
+        .execute(&app.pool)
+        .await
+        .unwrap_err();
+
+    let db_err = err.as_database_error().unwrap();
+    assert_eq!(db_err.constraint(), Some("items_quantity_non_negative"));
+}
+
+#[sqlx::test]
+async fn stale_updates_are_rejected(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let created = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Mleko", "quantity": 2, "category_id": null })),
+        )
+        .await;
+    let uri = format!("/api/items/{}", created.json()["id"]);
+    let version = created.json()["version"].as_i64().unwrap();
+
+    // Two people edit the same copy; the second one must not win silently
+    let first = app
+        .api(
+            &session,
+            "PUT",
+            &uri,
+            Some(json!({ "quantity": 5, "version": version })),
+        )
+        .await;
+    assert_eq!(first.status, StatusCode::OK);
+    assert_eq!(first.json()["version"], version + 1);
+    let second = app
+        .api(
+            &session,
+            "PUT",
+            &uri,
+            Some(json!({ "quantity": 0, "version": version })),
+        )
+        .await;
+
+    assert_eq!(second.status, StatusCode::CONFLICT);
+    assert_eq!(second.json()["details"]["field"], "version");
+    assert_eq!(second.json()["code"], "VERSION_CONFLICT");
+    let item = app.api(&session, "GET", &uri, None).await;
+    assert_eq!(item.json()["quantity"], 5);
+}
+
+#[sqlx::test]
+async fn malformed_json_gets_the_error_envelope(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let request = Request::post("/api/items")
+        .header(header::COOKIE, &session.0)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"name": "Mleko", "#))
+        .unwrap();
+
+    let response = app.send(request).await;
+
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["code"], "INVALID_JSON");
+}
+
+#[sqlx::test]
+async fn unchanged_items_are_not_sent_again(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let created = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Mleko", "quantity": 2, "category_id": null })),
+        )
+        .await;
+    let item_uri = format!("/api/items/{}", created.json()["id"]);
+    let get = |uri: &str, etag: &HeaderValue| {
+        Request::get(uri)
+            .header(header::COOKIE, &session.0)
+            .header(header::IF_NONE_MATCH, etag)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    for uri in ["/api/items", item_uri.as_str()] {
+        let first = app.api(&session, "GET", uri, None).await;
+        let etag = first.headers[header::ETAG].clone();
+        assert!(etag.to_str().unwrap().starts_with("W/"));
+
+        let again = app.send(get(uri, &etag)).await;
+        assert_eq!(again.status, StatusCode::NOT_MODIFIED, "{uri}");
+        assert!(again.body.is_empty());
+    }
+
+    let list_etag =
+        app.api(&session, "GET", "/api/items", None).await.headers[header::ETAG].clone();
+    app.api(&session, "POST", &format!("{item_uri}/use"), None)
+        .await;
+
+    let changed = app.send(get("/api/items", &list_etag)).await;
+    assert_eq!(changed.status, StatusCode::OK);
+    assert_eq!(changed.json()[0]["quantity"], 1);
+}
+
+#[sqlx::test]
+async fn unchanged_item_list_page_is_not_rendered_again(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let created = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Mleko", "quantity": 2, "category_id": null })),
+        )
+        .await;
+    app.get("/web", Some(&session)).await;
+    // Changes less than a second old get no Last-Modified
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let page = app.get("/web", Some(&session)).await;
+    let last_modified = page.headers[header::LAST_MODIFIED].clone();
+    let get = || {
+        Request::get("/web")
+            .header(header::COOKIE, &session.0)
+            .header(header::IF_MODIFIED_SINCE, &last_modified)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let again = app.send(get()).await;
+    assert_eq!(again.status, StatusCode::NOT_MODIFIED);
+
+    let uri = format!("/api/items/{}/use", created.json()["id"]);
+    app.api(&session, "POST", &uri, None).await;
+    let changed = app.send(get()).await;
+    assert_eq!(changed.status, StatusCode::OK);
+    assert!(changed.text().contains("Mleko"));
+}
+
+#[sqlx::test]
+async fn collapsed_categories_stay_collapsed(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let dairy = app.create_category(&session, "Nabiał", None).await;
+    app.create_category(&session, "Chemia", None).await;
+    for (name, category_id) in [("Mleko", Some(dairy)), ("Szampon", None)] {
+        let item = json!({ "name": name, "quantity": 1, "restock_threshold": 0,
+                           "category_id": category_id });
+        app.api(&session, "POST", "/api/items", Some(item)).await;
+    }
+
+    let response = app
+        .post_form(
+            &format!("/web/categories/{dairy}/toggle"),
+            &[],
+            Some(&session),
+        )
+        .await;
+    assert!(response.status.is_redirection());
+    let preferences = app.api(&session, "GET", "/api/preferences", None).await;
+    assert_eq!(preferences.json()["collapsed_category_ids"], json!([dairy]));
+    let page = app.get("/web", Some(&session)).await.text();
+    assert!(page.contains("Nabiał"), "{page}");
+    assert!(!page.contains("Mleko"), "{page}");
+    assert!(page.contains("Szampon"), "{page}");
+
+    let uri = format!("/api/categories/{dairy}/collapsed");
+    let body = json!({ "collapsed": false });
+    let response = app.api(&session, "PUT", &uri, Some(body)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["collapsed_category_ids"], json!([]));
+    let page = app.get("/web", Some(&session)).await.text();
+    assert!(page.contains("Mleko"), "{page}");
+
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let body = json!({ "collapsed": true });
+    let response = app.api(&other, "PUT", &uri, Some(body)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn item_list_layout_follows_preference_and_query(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let item =
+        json!({ "name": "Mleko", "quantity": 1, "restock_threshold": 2, "category_id": null });
+    app.api(&session, "POST", "/api/items", Some(item)).await;
+
+    let page = app.get("/web", Some(&session)).await.text();
+    assert!(page.contains("<table"), "{page}");
+    assert!(!page.contains("item-card"), "{page}");
+
+    let page = app.get("/web?layout=cards", Some(&session)).await.text();
+    assert!(page.contains(r#"class="item-card low-stock""#), "{page}");
+    assert!(page.contains("Mleko"), "{page}");
+    assert!(!page.contains("<table"), "{page}");
+    // The override is for this one page
+    let preferences = app.api(&session, "GET", "/api/preferences", None).await;
+    assert_eq!(preferences.json()["layout"], "table");
+
+    let body = json!({ "layout": "cards" });
+    let response = app
+        .api(&session, "PUT", "/api/preferences", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    let page = app.get("/web", Some(&session)).await.text();
+    assert!(page.contains("item-card"), "{page}");
+    let page = app.get("/web?layout=table", Some(&session)).await.text();
+    assert!(page.contains("<table"), "{page}");
+}
+
+#[sqlx::test]
+async fn restock_warnings_do_not_depend_on_the_filter(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    for (name, quantity) in [("Mleko", 0), ("Chleb", 3)] {
+        app.api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({
+                "name": name,
+                "quantity": quantity,
+                "restock_threshold": 2,
+                "category_id": null,
+            })),
+        )
+        .await;
+    }
+
+    for uri in ["/web", "/web?filter=low", "/web?filter=out"] {
+        let page = app.get(uri, Some(&session)).await.text();
+        assert!(page.contains("Potrzeba uzupełnienia"), "{uri}");
+        assert!(page.contains("<b>Mleko</b>: Aktualna ilość: 0"), "{uri}");
+        assert!(!page.contains("<b>Chleb</b>: Aktualna"), "{uri}");
+    }
+    let page = app.get("/web?filter=out", Some(&session)).await.text();
+    assert!(!page.contains("Chleb"));
+}
+
+#[sqlx::test]
+async fn items_sold_in_packs_move_by_the_pack(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let eggs = json!({ "name": "Jajka", "quantity": 3, "restock_threshold": 6,
+                       "restock_to": 12, "quantity_step": 10, "category_id": null });
+    let response = app.api(&session, "POST", "/api/items", Some(eggs)).await;
+    let item = response.json();
+    assert_eq!(item["quantity_step"], 10);
+    // Nine are missing, which is one pack
+    assert_eq!(item["suggested_purchase"], 10);
+    let page = app.get("/web", Some(&session)).await.text();
+    assert!(page.contains(r#"name="quantity" value="10""#), "{page}");
+    assert!(page.contains("+10</button>"), "{page}");
+
+    let uri = format!("/api/items/{}/adjust", item["id"]);
+    let correction = json!({ "steps": 2, "reason": "correction" });
+    let response = app.api(&session, "POST", &uri, Some(correction)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["quantity"], 23);
+    let spoiled = json!({ "steps": -1, "reason": "spoiled" });
+    let response = app.api(&session, "POST", &uri, Some(spoiled)).await;
+    assert_eq!(response.json()["quantity"], 13);
+
+    let both = json!({ "steps": -1, "delta": -1, "reason": "spoiled" });
+    let response = app.api(&session, "POST", &uri, Some(both)).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let upwards = json!({ "steps": 1, "reason": "lost" });
+    let response = app.api(&session, "POST", &uri, Some(upwards)).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let uri = format!("/api/items/{}", item["id"]);
+    let update = json!({ "quantity_step": 0, "version": item["version"] });
+    let response = app.api(&session, "PUT", &uri, Some(update)).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(response.text().contains("quantity_step"));
+}
+
+#[sqlx::test]
+async fn items_keep_notes(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let note = "Ta z niebieską nakrętką,\nnie zieloną";
+    let item = json!({ "name": "Woda", "quantity": 6, "restock_threshold": 2,
+                       "notes": note, "category_id": null });
+    let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    let water = response.json();
+    assert_eq!(water["notes"], note);
+    let page = app
+        .get(&format!("/web/items/{}", water["id"]), Some(&session))
+        .await
+        .text();
+    assert!(page.contains("nie zieloną"), "{page}");
+
+    let too_long = json!({ "name": "Woda", "notes": "x".repeat(2001),
+                           "version": water["version"] });
+    let uri = format!("/api/items/{}", water["id"]);
+    let response = app.api(&session, "PUT", &uri, Some(too_long)).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Emptying the field in the edit form removes the note
+    let version = water["version"].to_string();
+    let response = app
+        .post_form(
+            &format!("/web/items/edit/{}", water["id"]),
+            &[
+                ("name", "Woda"),
+                ("quantity", "6"),
+                ("restock_threshold", "2"),
+                ("category_id", ""),
+                ("notes", ""),
+                ("version", &version),
+            ],
+            Some(&session),
+        )
+        .await;
+    assert!(response.status.is_redirection(), "{}", response.text());
+    let water = app.api(&session, "GET", &uri, None).await.json();
+    assert!(water["notes"].is_null());
+}
+
+#[sqlx::test]
+async fn items_are_cloned_without_their_stock(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let category_id = app.create_category(&session, "Nabiał", None).await;
+    let milk = json!({ "name": "Mleko", "quantity": 3, "restock_threshold": 2,
+                       "unit": "l", "location": "Lodówka", "notes": "Tylko UHT",
+                       "category_id": category_id });
+    let milk = app
+        .api(&session, "POST", "/api/items", Some(milk))
+        .await
+        .json();
+
+    let uri = format!("/api/items/{}/clone", milk["id"]);
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    let copy = response.json();
+    assert_eq!(copy["name"], "Mleko (kopia)");
+    assert_eq!(copy["quantity"], 0);
+    assert_eq!(copy["restock_threshold"], 2);
+    assert_eq!(copy["unit"], "l");
+    assert_eq!(copy["location"], "Lodówka");
+    assert_eq!(copy["notes"], "Tylko UHT");
+    assert_eq!(copy["category"]["name"], "Nabiał");
+    // Cloning again, or cloning the copy, picks the next free name
+    let again = app.api(&session, "POST", &uri, None).await.json();
+    assert_eq!(again["name"], "Mleko (kopia 2)");
+    let uri = format!("/api/items/{}/clone", copy["id"]);
+    let third = app.api(&session, "POST", &uri, None).await.json();
+    assert_eq!(third["name"], "Mleko (kopia 3)");
+
+    let response = app
+        .post_form(
+            &format!("/web/items/{}/clone", milk["id"]),
+            &[],
+            Some(&session),
+        )
+        .await;
+    assert!(response.status.is_redirection());
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let uri = format!("/api/items/{}/clone", milk["id"]);
+    let response = app.api(&other, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn item_pages_show_times_in_the_users_time_zone(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool.clone()).await;
+    let created = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Mleko", "quantity": 1, "restock_threshold": 2, "category_id": null })),
+        )
+        .await;
+    let id = created.json()["id"].as_i64().unwrap();
+    // Past the trigger that stamps updated_at with the time of the update
+    sqlx::raw_sql(
+        "ALTER TABLE items DISABLE TRIGGER USER;
+         UPDATE items SET created_at = '2025-01-15T12:30:00Z',
+                          updated_at = NOW() - INTERVAL '3 hours';
+         ALTER TABLE items ENABLE TRIGGER USER;",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let uri = format!("/web/items/{id}");
+    let page = app.get(&uri, Some(&session)).await.text();
+    assert!(page.contains("Dodano 2025-01-15 12:30"), "{page}");
+    assert!(page.contains("3 godziny temu"), "{page}");
+
+    let body = json!({ "timezone": "Mars/Olympus" });
+    let response = app
+        .api(&session, "PUT", "/api/preferences", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body = json!({ "timezone": "Europe/Warsaw" });
+    let response = app
+        .api(&session, "PUT", "/api/preferences", Some(body))
+        .await;
+    assert_eq!(response.json()["timezone"], "Europe/Warsaw");
+    let page = app.get(&uri, Some(&session)).await.text();
+    assert!(page.contains("Dodano 2025-01-15 13:30"), "{page}");
+}
+
+#[sqlx::test]
+async fn items_keep_the_order_they_were_dragged_into(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool.clone()).await;
+    let mut ids = Vec::new();
+    for name in ["Chleb", "Jajka", "Mleko"] {
+        ids.push(app.create_item(&session, name, json!({})).await);
+    }
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let others = app.create_item(&other, "Kawa", json!({})).await;
+
+    let body = json!({ "ids": [ids[2], ids[0], ids[2]] });
+    let response = app
+        .api(&session, "POST", "/api/items/reorder", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    // Someone else's item in the list is left where it was
+    let body = json!({ "ids": [others, ids[2], ids[0], ids[1]] });
+    let response = app
+        .api(&session, "POST", "/api/items/reorder", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let items = app.api(&session, "GET", "/api/items", None).await.json();
+    let names: Vec<_> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Mleko", "Chleb", "Jajka"]);
+    let page = app.get("/web", Some(&session)).await.text();
+    // The rows of the table, not the restock alerts above it
+    let position = |name: &str| page.find(&format!("\">{name}</a></td>")).unwrap();
+    assert!(position("Mleko") < position("Chleb"));
+    assert!(position("Chleb") < position("Jajka"));
+    let sort_order: i32 = sqlx::query_scalar("SELECT sort_order FROM items WHERE id = $1")
+        .bind(others as i32)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(sort_order, 0);
+}
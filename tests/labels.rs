@@ -0,0 +1,133 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use household_inventory::labels;
+use household_inventory::testing::{Session, TestApp, TestResponse};
+use serde_json::json;
+use sqlx::PgPool;
+
+/// A page or image fetched through a browser, which always sends `Host`.
+async fn get_from(app: &TestApp, session: &Session, host: &str, uri: &str) -> TestResponse {
+    let request = Request::get(uri)
+        .header(header::HOST, host)
+        .header(header::COOKIE, &session.0)
+        .body(Body::empty())
+        .unwrap();
+    app.send(request).await
+}
+
+#[sqlx::test]
+async fn labels_link_to_the_use_page(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let rice = app
+        .create_item(
+            &session,
+            "Ryż",
+            json!({ "quantity": 3, "restock_threshold": 1 }),
+        )
+        .await;
+
+    let uri = format!("/api/items/{rice}/qr.png");
+    let response = get_from(&app, &session, "spizarnia.local:3000", &uri).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["content-type"], "image/png");
+    assert!(response.body.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+    let sheet = get_from(&app, &session, "spizarnia.local:3000", "/web/labels/print").await;
+    assert_eq!(sheet.status, StatusCode::OK);
+    let sheet = sheet.text();
+    assert!(sheet.contains("Ryż"), "{sheet}");
+    assert_eq!(sheet.matches("<svg").count(), 1, "{sheet}");
+
+    let page = app
+        .get(&format!("/web/items/{rice}/use"), Some(&session))
+        .await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(page.text().contains(&format!("/web/items/use/{rice}")));
+    let used = app
+        .post_form(
+            &format!("/web/items/use/{rice}"),
+            &[("quantity", "2")],
+            Some(&session),
+        )
+        .await;
+    assert!(used.status.is_redirection());
+    let item = app
+        .api(&session, "GET", &format!("/api/items/{rice}"), None)
+        .await;
+    assert_eq!(item.json()["quantity"], 1);
+
+    // Someone else's items have no code
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let response = get_from(&app, &other, "spizarnia.local:3000", &uri).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn labels_print_on_avery_sheets(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let rice = app
+        .create_item(
+            &session,
+            "Ryż",
+            json!({ "quantity": 3, "restock_threshold": 1 }),
+        )
+        .await;
+    let flour = app
+        .create_item(
+            &session,
+            "Mąka",
+            json!({ "quantity": 3, "restock_threshold": 1 }),
+        )
+        .await;
+    app.create_item(
+        &session,
+        "Cukier",
+        json!({ "quantity": 3, "restock_threshold": 1 }),
+    )
+    .await;
+
+    let page = app.get("/web/labels", Some(&session)).await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(page.text().contains(&format!("name=\"item_{flour}\"")));
+
+    let uri = format!("/web/labels.pdf?layout=l7163&skip=13&item_{rice}=on&item_{flour}=on");
+    let response = get_from(&app, &session, "spizarnia.local", &uri).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.headers["content-type"], "application/pdf");
+    let pdf = String::from_utf8_lossy(&response.body);
+    assert!(pdf.starts_with("%PDF-"));
+    // The last label of the first sheet, then a second sheet
+    assert!(pdf.contains("/Count 2"), "{pdf}");
+    assert!(pdf.contains("(Ry\\217)"), "{pdf}");
+    assert!(!pdf.contains("(Cukier)"), "{pdf}");
+
+    let selection = format!("/web/labels/print?item_{rice}=on");
+    let sheet = get_from(&app, &session, "spizarnia.local", &selection).await;
+    assert_eq!(sheet.text().matches("<svg").count(), 1);
+
+    let uri = "/web/labels.pdf?layout=l7165&skip=8";
+    let response = get_from(&app, &session, "spizarnia.local", uri).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn long_names_wrap_between_words() {
+    assert_eq!(
+        labels::wrap("Passata pomidorowa z bazylią", 12, 3),
+        ["Passata", "pomidorowa z", "bazylią"]
+    );
+    assert_eq!(
+        labels::wrap("Makaron spaghetti pełnoziarnisty bio", 10, 2),
+        ["Makaron", "spaghetti…"]
+    );
+}
+
+#[test]
+fn codes_hold_the_use_page_address() {
+    let url = household_inventory::qr::use_url("https://example.com", "/inventory", 7);
+    assert_eq!(url, "https://example.com/inventory/web/items/7/use");
+    let svg = household_inventory::qr::svg(&url).unwrap();
+    assert!(svg.starts_with("<svg"), "{svg}");
+}
@@ -0,0 +1,58 @@
+use axum::body::{Body, Bytes};
+use axum::http::{Request, StatusCode, header};
+use household_inventory::limits::RequestLimits;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+use std::time::Duration;
+
+#[sqlx::test]
+async fn oversized_bodies_are_refused(pool: PgPool) {
+    let app = TestApp::with_state(pool, |state| {
+        state.limits = RequestLimits {
+            max_body_bytes: 1024,
+            ..RequestLimits::default()
+        }
+    });
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "x".repeat(2000), "quantity": 1, "category_id": null })),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(response.json()["code"], "PAYLOAD_TOO_LARGE");
+}
+
+#[sqlx::test]
+async fn a_body_that_never_arrives_times_out(pool: PgPool) {
+    // Signing up takes longer than the timeout below
+    let session = TestApp::new(pool.clone())
+        .sign_up("Ala", "ala@example.com", "hunter2")
+        .await;
+    let app = TestApp::with_state(pool, |state| {
+        state.limits = RequestLimits {
+            request_timeout: Duration::from_millis(100),
+            ..RequestLimits::default()
+        }
+    });
+    let stalled = futures_util::stream::pending::<Result<Bytes, std::io::Error>>();
+
+    let response = app
+        .send(
+            Request::post("/api/items")
+                .header(header::COOKIE, &session.0)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from_stream(stalled))
+                .unwrap(),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::REQUEST_TIMEOUT);
+    assert_eq!(response.json()["code"], "REQUEST_TIMEOUT");
+}
@@ -0,0 +1,48 @@
+use axum::http::{StatusCode, header};
+use household_inventory::db;
+use household_inventory::maintenance::{self, Maintenance};
+use household_inventory::testing::TestApp;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn maintenance_mode_answers_everything_but_health_and_static_files(pool: PgPool) {
+    let maintenance = Maintenance::default();
+    let app = TestApp::with_state(pool, |state| state.maintenance = maintenance.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    maintenance.set(true);
+
+    let api = app.api(&session, "GET", "/api/items", None).await;
+    assert_eq!(api.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(api.json()["code"], "MAINTENANCE");
+    assert_eq!(api.headers[header::RETRY_AFTER], "60");
+
+    let page = app.get("/web", Some(&session)).await;
+    assert_eq!(page.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(page.headers[header::RETRY_AFTER], "60");
+    assert!(page.text().contains("Przerwa techniczna"));
+    let login = app.get("/web/login", None).await;
+    assert_eq!(login.status, StatusCode::SERVICE_UNAVAILABLE);
+
+    assert_eq!(app.get("/health", None).await.status, StatusCode::OK);
+    assert_eq!(app.get("/health/ready", None).await.status, StatusCode::OK);
+    assert_eq!(
+        app.get("/static/style.css", None).await.status,
+        StatusCode::OK
+    );
+
+    maintenance.set(false);
+    let api = app.api(&session, "GET", "/api/items", None).await;
+    assert_eq!(api.status, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn the_switch_in_the_database_is_picked_up(pool: PgPool) {
+    let maintenance = Maintenance::default();
+    db::set_maintenance_mode(&pool, true).await.unwrap();
+
+    maintenance::spawn_refresh(pool, maintenance.clone())
+        .await
+        .unwrap();
+
+    assert!(maintenance.is_on());
+}
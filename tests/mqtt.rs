@@ -0,0 +1,102 @@
+use household_inventory::mqtt::{self, Message};
+use household_inventory::testing::TestApp;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+/// Events are only published once they are a couple of seconds old.
+async fn age_events(app: &TestApp) {
+    sqlx::query("UPDATE item_events SET created_at = created_at - INTERVAL '1 minute'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+}
+
+fn payloads(messages: &[Message], topic: &str) -> Vec<Value> {
+    messages
+        .iter()
+        .filter(|message| message.topic == topic)
+        .map(|message| serde_json::from_str(&message.payload).unwrap())
+        .collect()
+}
+
+#[sqlx::test]
+async fn item_changes_become_messages(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let user_id: i32 = sqlx::query_scalar("SELECT id FROM users")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    let milk = app
+        .create_item(
+            &session,
+            "Mleko",
+            json!({ "quantity": 3, "restock_threshold": 2 }),
+        )
+        .await;
+    let rice = app
+        .create_item(
+            &session,
+            "Ryż",
+            json!({ "quantity": 5, "restock_threshold": 2 }),
+        )
+        .await;
+
+    let uri = format!("/api/items/{milk}/use");
+    app.api(&session, "POST", &uri, Some(json!({ "quantity": 2 })))
+        .await;
+    let uri = format!("/api/items/{rice}/use");
+    app.api(&session, "POST", &uri, None).await;
+    let after_id: i32 = sqlx::query_scalar("SELECT last_event_id FROM mqtt_cursor")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert!(
+        mqtt::next_batch(&app.pool, "home/pantry", after_id)
+            .await
+            .unwrap()
+            .is_none(),
+        "too recent to be published"
+    );
+    age_events(&app).await;
+
+    let (messages, last_id) = mqtt::next_batch(&app.pool, "home/pantry", after_id)
+        .await
+        .unwrap()
+        .expect("new events");
+    let events = payloads(&messages, &format!("home/pantry/{user_id}/events"));
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["item_name"], "Mleko");
+    assert_eq!(events[0]["kind"], "used");
+    assert_eq!(events[0]["quantity_delta"], -2);
+    assert_eq!(last_id as i64, events[1]["id"].as_i64().unwrap());
+
+    // Only the milk dropped below its threshold
+    let low = payloads(&messages, &format!("home/pantry/{user_id}/low_stock"));
+    assert_eq!(low.len(), 1);
+    assert_eq!(low[0]["name"], "Mleko");
+    assert_eq!(low[0]["quantity"], 1);
+
+    let status = messages
+        .iter()
+        .find(|message| message.topic == format!("home/pantry/{user_id}/status"))
+        .expect("a status message");
+    assert!(status.retain);
+    let status: Value = serde_json::from_str(&status.payload).unwrap();
+    assert_eq!(status["low_stock"], 1);
+    assert_eq!(status["items"], 2);
+
+    // Used again while already low: an event, but no new announcement
+    let uri = format!("/api/items/{milk}/use");
+    app.api(&session, "POST", &uri, None).await;
+    age_events(&app).await;
+    let (messages, _) = mqtt::next_batch(&app.pool, "home/pantry", last_id)
+        .await
+        .unwrap()
+        .expect("new events");
+    assert_eq!(
+        payloads(&messages, &format!("home/pantry/{user_id}/events")).len(),
+        1
+    );
+    assert!(payloads(&messages, &format!("home/pantry/{user_id}/low_stock")).is_empty());
+}
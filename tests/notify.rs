@@ -0,0 +1,510 @@
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::{OriginalUri, State};
+use axum::http::StatusCode;
+use axum::routing::post;
+use household_inventory::notify;
+use household_inventory::testing::{Session, TestApp};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+use time::macros::datetime;
+
+/// Requests received by the fake channel, as path with query and body.
+type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+/// Serves `/notify/{key}` and `/hook` like a working channel, and `/fail` like
+/// a broken one; returns its address.
+async fn fake_channel(received: Received) -> String {
+    async fn record(
+        State(received): State<Received>,
+        OriginalUri(uri): OriginalUri,
+        body: Bytes,
+    ) -> StatusCode {
+        let body = String::from_utf8_lossy(&body).into_owned();
+        received.lock().unwrap().push((uri.to_string(), body));
+        StatusCode::OK
+    }
+    let app = Router::new()
+        .route("/notify/{key}", post(record))
+        .route("/hook", post(record))
+        .route(
+            "/fail",
+            post(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+        )
+        .with_state(received);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    format!("http://{address}")
+}
+
+async fn add_channel(app: &TestApp, session: &Session, kind: &str, url: &str) -> Value {
+    let response = app
+        .api(
+            session,
+            "POST",
+            "/api/notification-channels",
+            Some(json!({ "label": "Telefon", "kind": kind, "url": url })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    response.json()
+}
+
+/// Runs the dispatcher for the only user.
+async fn dispatch(pool: &PgPool) {
+    dispatch_at(pool, OffsetDateTime::now_utc()).await;
+}
+
+/// Runs the dispatcher for the only user as if it were `now`.
+async fn dispatch_at(pool: &PgPool, now: OffsetDateTime) {
+    let (user_id,): (i32,) = sqlx::query_as("SELECT id FROM users")
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    notify::notify_user(pool, &notify::client().unwrap(), user_id, now)
+        .await
+        .unwrap();
+}
+
+#[sqlx::test]
+async fn each_alert_is_sent_once_while_it_lasts(pool: PgPool) {
+    let received = Received::default();
+    let server = fake_channel(received.clone()).await;
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    add_channel(&app, &session, "apprise", &format!("{server}/notify/dom")).await;
+    let milk = app
+        .api(
+            &session,
+            "POST",
+            "/api/items",
+            Some(json!({ "name": "Mleko", "quantity": 1, "restock_threshold": 2, "category_id": null })),
+        )
+        .await
+        .json()["id"]
+        .clone();
+
+    dispatch(&pool).await;
+    dispatch(&pool).await;
+    {
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1, "{received:?}");
+        let (uri, body) = &received[0];
+        assert_eq!(uri, "/notify/dom");
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["title"], "Inwentarz");
+        assert_eq!(body["type"], "warning");
+        assert!(
+            body["body"]
+                .as_str()
+                .unwrap()
+                .starts_with("Potrzeba uzupełnienia: Mleko."),
+            "{body}"
+        );
+    }
+
+    // Restocked, the alert is over; running low again is a new one
+    app.api(
+        &session,
+        "POST",
+        &format!("/api/items/{milk}/purchase"),
+        Some(json!({ "quantity": 5 })),
+    )
+    .await;
+    dispatch(&pool).await;
+    assert_eq!(received.lock().unwrap().len(), 1);
+    app.api(
+        &session,
+        "POST",
+        &format!("/api/items/{milk}/use"),
+        Some(json!({ "quantity": 6 })),
+    )
+    .await;
+    dispatch(&pool).await;
+    assert_eq!(received.lock().unwrap().len(), 2);
+
+    let channels = app
+        .api(&session, "GET", "/api/notification-channels", None)
+        .await
+        .json();
+    assert!(channels[0]["last_sent_at"].is_string());
+    assert_eq!(channels[0]["last_error"], Value::Null);
+}
+
+#[sqlx::test]
+async fn url_channels_get_the_message_in_the_body_and_the_url(pool: PgPool) {
+    let received = Received::default();
+    let server = fake_channel(received.clone()).await;
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let channel = add_channel(
+        &app,
+        &session,
+        "url",
+        &format!("{server}/hook?t={{title}}&m={{message}}"),
+    )
+    .await;
+
+    let uri = format!("/api/notification-channels/{}/test", channel["id"]);
+    let response = app.api(&session, "POST", &uri, None).await;
+
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+    let received = received.lock().unwrap();
+    let (uri, body) = &received[0];
+    assert_eq!(body, "Wiadomość testowa: powiadomienia działają.");
+    assert!(
+        uri.starts_with("/hook?t=Inwentarz&m=Wiadomo%C5%9B%C4%87%20testowa"),
+        "{uri}"
+    );
+}
+
+#[sqlx::test]
+async fn failures_are_reported_and_the_alerts_kept_for_later(pool: PgPool) {
+    let received = Received::default();
+    let server = fake_channel(received.clone()).await;
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let broken = add_channel(&app, &session, "url", &format!("{server}/fail")).await;
+    app.api(
+        &session,
+        "POST",
+        "/api/items",
+        Some(
+            json!({ "name": "Mleko", "quantity": 0, "restock_threshold": 1, "category_id": null }),
+        ),
+    )
+    .await;
+
+    let uri = format!("/api/notification-channels/{}/test", broken["id"]);
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(response.text().contains("500"), "{}", response.text());
+
+    dispatch(&pool).await;
+    let channels = app
+        .api(&session, "GET", "/api/notification-channels", None)
+        .await
+        .json();
+    assert_eq!(channels[0]["last_sent_at"], Value::Null);
+    assert!(channels[0]["last_error"].is_string());
+    let settings = app.get("/web/settings", Some(&session)).await.text();
+    assert!(settings.contains("Błąd:"), "{settings}");
+
+    // Once a working channel is there, the alert that didn't go out does
+    add_channel(&app, &session, "apprise", &format!("{server}/notify/dom")).await;
+    dispatch(&pool).await;
+    assert_eq!(received.lock().unwrap().len(), 1);
+
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/notification-channels",
+            Some(json!({ "label": "FTP", "kind": "url", "url": "ftp://example.com" })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let response = app
+        .api(&session, "DELETE", &uri.replace("/test", ""), None)
+        .await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+}
+
+#[sqlx::test]
+async fn matrix_channels_post_to_the_room(pool: PgPool) {
+    let received = Received::default();
+    let requests = received.clone();
+    let app_router = Router::new().route(
+        "/_matrix/client/v3/rooms/{room}/send/m.room.message/{txn}",
+        axum::routing::put(
+            move |OriginalUri(uri): OriginalUri, headers: axum::http::HeaderMap, body: Bytes| {
+                let requests = requests.clone();
+                async move {
+                    let auth = headers["authorization"].to_str().unwrap().to_string();
+                    let body = String::from_utf8_lossy(&body).into_owned();
+                    requests
+                        .lock()
+                        .unwrap()
+                        .push((uri.to_string(), format!("{auth} {body}")));
+                    axum::Json(json!({ "event_id": "$1" }))
+                }
+            },
+        ),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let homeserver = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app_router).await });
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/notification-channels",
+            Some(json!({ "label": "Dom", "kind": "matrix", "url": homeserver, "room_id": "#dom:example.org" })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let errors = response.json();
+    assert!(errors.to_string().contains("room_id"), "{errors}");
+    assert!(errors.to_string().contains("access_token"), "{errors}");
+
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/notification-channels",
+            Some(json!({
+                "label": "Dom", "kind": "matrix", "url": format!("{homeserver}/"),
+                "room_id": "!dom:example.org", "access_token": "syt_secret",
+            })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    let channel = response.json();
+    assert_eq!(channel["room_id"], "!dom:example.org");
+    assert!(!response.text().contains("syt_secret"));
+
+    let uri = format!("/api/notification-channels/{}/test", channel["id"]);
+    let response = app.api(&session, "POST", &uri, None).await;
+
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+    let received = received.lock().unwrap();
+    let (uri, request) = &received[0];
+    assert!(
+        uri.starts_with(
+            "/_matrix/client/v3/rooms/%21dom%3Aexample%2Eorg/send/m.room.message/inventory-"
+        ),
+        "{uri}"
+    );
+    let (auth, body) = request.split_once(' ').unwrap().1.split_once(' ').unwrap();
+    assert_eq!(auth, "syt_secret");
+    let body: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(body["msgtype"], "m.text");
+    assert_eq!(
+        body["body"],
+        "Inwentarz\nWiadomość testowa: powiadomienia działają."
+    );
+}
+
+#[sqlx::test]
+async fn items_low_for_too_long_escalate(pool: PgPool) {
+    let received = Received::default();
+    let server = fake_channel(received.clone()).await;
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    add_channel(&app, &session, "url", &format!("{server}/hook")).await;
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/notification-channels",
+            Some(
+                json!({ "label": "Ola", "kind": "url", "url": format!("{server}/notify/ola"),
+                         "escalation_only": true }),
+            ),
+        )
+        .await;
+    assert_eq!(response.json()["escalation_only"], true);
+    let preferences = json!({ "restock_escalation_days": 3 });
+    app.api(&session, "PUT", "/api/preferences", Some(preferences))
+        .await;
+    let item = json!({ "name": "Karma dla kota", "quantity": 0, "restock_threshold": 2,
+                       "category_id": null });
+    app.api(&session, "POST", "/api/items", Some(item)).await;
+
+    // Only the household channel is spared the ordinary alert
+    dispatch(&pool).await;
+    assert_eq!(received.lock().unwrap().len(), 1);
+    assert_eq!(received.lock().unwrap()[0].0, "/hook");
+
+    sqlx::query("UPDATE items SET low_since = NOW() - INTERVAL '4 days'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    dispatch(&pool).await;
+    dispatch(&pool).await;
+    {
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3, "{received:?}");
+        assert!(received[1..].iter().any(|(uri, _)| uri == "/notify/ola"));
+        for (_, body) in &received[1..] {
+            assert!(
+                body.starts_with("PILNE: Karma dla kota czeka na uzupełnienie od 4 dni."),
+                "{body}"
+            );
+        }
+    }
+    let notifications = app.api(&session, "GET", "/api/notifications", None).await;
+    assert_eq!(notifications.json()[0]["urgent"], true);
+    let page = app.get("/web", Some(&session)).await.text();
+    assert!(page.contains("Czeka od 4 dni."), "{page}");
+
+    // Three more days and it is announced again
+    sqlx::query("UPDATE items SET low_since = NOW() - INTERVAL '7 days'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    dispatch(&pool).await;
+    assert_eq!(received.lock().unwrap().len(), 5);
+
+    let response = app
+        .api(
+            &session,
+            "PUT",
+            "/api/preferences",
+            Some(json!({ "restock_escalation_days": -1 })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[sqlx::test]
+async fn nothing_is_sent_in_the_quiet_hours(pool: PgPool) {
+    let received = Received::default();
+    let server = fake_channel(received.clone()).await;
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    add_channel(&app, &session, "url", &format!("{server}/hook")).await;
+    let page = app
+        .post_form(
+            "/web/settings/notification-settings",
+            &[
+                ("quiet_start", "22:00"),
+                ("quiet_end", "07:00"),
+                ("digest_time", "08:00"),
+                ("timezone", "Europe/Warsaw"),
+            ],
+            Some(&session),
+        )
+        .await;
+    assert!(page.status.is_redirection(), "{}", page.text());
+    let settings = app
+        .api(&session, "GET", "/api/notification-settings", None)
+        .await
+        .json();
+    assert_eq!(
+        settings,
+        json!({ "quiet_start": "22:00", "quiet_end": "07:00", "digest_time": "08:00",
+                "timezone": "Europe/Warsaw" })
+    );
+    let item = json!({ "name": "Mleko", "quantity": 0, "restock_threshold": 1,
+                       "category_id": null });
+    app.api(&session, "POST", "/api/items", Some(item)).await;
+
+    // 23:30 and 6:30 in Warsaw
+    dispatch_at(&pool, datetime!(2025-01-15 22:30 UTC)).await;
+    dispatch_at(&pool, datetime!(2025-01-16 05:30 UTC)).await;
+    assert!(received.lock().unwrap().is_empty());
+    dispatch_at(&pool, datetime!(2025-01-16 06:05 UTC)).await;
+    assert_eq!(received.lock().unwrap().len(), 1);
+
+    // Both ends or neither, as times
+    for settings in [
+        json!({ "quiet_start": "22:00", "digest_time": "08:00" }),
+        json!({ "quiet_start": "22:00", "quiet_end": "25:00", "digest_time": "08:00" }),
+        json!({ "digest_time": "8 rano" }),
+        json!({ "digest_time": "08:00", "timezone": "Mars/Olympus" }),
+    ] {
+        let response = app
+            .api(
+                &session,
+                "PUT",
+                "/api/notification-settings",
+                Some(settings),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let response = app
+        .api(
+            &session,
+            "PUT",
+            "/api/notification-settings",
+            Some(json!({ "digest_time": "19:30" })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["quiet_start"], Value::Null);
+    assert_eq!(response.json()["timezone"], Value::Null);
+    let page = app.get("/web/settings", Some(&session)).await.text();
+    assert!(page.contains(r#"value="19:30""#), "{page}");
+    assert!(!page.contains(r#"value="null""#), "{page}");
+}
+
+#[sqlx::test]
+async fn digest_channels_get_a_summary_once_a_day(pool: PgPool) {
+    let received = Received::default();
+    let server = fake_channel(received.clone()).await;
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    add_channel(&app, &session, "url", &format!("{server}/hook")).await;
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/notification-channels",
+            Some(json!({ "label": "Poranek", "kind": "url",
+                         "url": format!("{server}/notify/digest"), "delivery": "digest" })),
+        )
+        .await;
+    assert_eq!(response.json()["delivery"], "digest");
+    let settings = json!({ "digest_time": "08:00", "timezone": "UTC" });
+    app.api(
+        &session,
+        "PUT",
+        "/api/notification-settings",
+        Some(settings),
+    )
+    .await;
+    for name in ["Mleko", "Chleb"] {
+        let item = json!({ "name": name, "quantity": 0, "restock_threshold": 1,
+                           "category_id": null });
+        app.api(&session, "POST", "/api/items", Some(item)).await;
+    }
+    sqlx::query("UPDATE notification_channels SET created_at = '2025-01-14 09:00Z'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let digests = || {
+        received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(uri, _)| uri == "/notify/digest")
+            .map(|(_, body)| body.clone())
+            .collect::<Vec<_>>()
+    };
+
+    // Only the immediate channel hears of them before the digest time
+    dispatch_at(&pool, datetime!(2025-01-15 07:00 UTC)).await;
+    assert_eq!(received.lock().unwrap().len(), 1);
+    assert!(digests().is_empty());
+
+    dispatch_at(&pool, datetime!(2025-01-15 08:05 UTC)).await;
+    dispatch_at(&pool, datetime!(2025-01-15 08:10 UTC)).await;
+    let sent = digests();
+    assert_eq!(sent.len(), 1);
+    let lines: Vec<&str> = sent[0].lines().collect();
+    assert_eq!(lines[0], "Podsumowanie dnia:");
+    assert_eq!(lines.len(), 3, "{lines:?}");
+
+    // The next day again, though nothing is new
+    dispatch_at(&pool, datetime!(2025-01-16 08:05 UTC)).await;
+    assert_eq!(digests().len(), 2);
+    assert_eq!(received.lock().unwrap().len(), 3);
+}
@@ -0,0 +1,22 @@
+use household_inventory::db::{self, PoolSettings};
+use std::time::Duration;
+
+#[tokio::test]
+async fn statement_timeout_cancels_slow_queries() {
+    dotenvy::dotenv().ok();
+    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for tests");
+    let settings = PoolSettings {
+        statement_timeout: Some(Duration::from_millis(100)),
+        ..PoolSettings::default()
+    };
+    let pool = db::create_pool(&url, &settings).await.unwrap();
+
+    sqlx::query("SELECT 1").execute(&pool).await.unwrap();
+    let err = sqlx::query("SELECT pg_sleep(2)")
+        .execute(&pool)
+        .await
+        .unwrap_err();
+
+    let code = err.as_database_error().and_then(|e| e.code());
+    assert_eq!(code.as_deref(), Some("57014"), "{err}");
+}
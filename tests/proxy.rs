@@ -0,0 +1,64 @@
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, header};
+use household_inventory::testing::{Session, TestApp, TestResponse};
+use sqlx::PgPool;
+use std::net::SocketAddr;
+
+/// Logs in over a connection from `peer`, with the given extra headers.
+async fn login_from(app: &TestApp, peer: &str, headers: &[(&str, &str)]) -> TestResponse {
+    let mut request = Request::post("/web/login")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let mut request = request
+        .body(Body::from("email=ala%40example.com&password=hunter2"))
+        .unwrap();
+    let peer: SocketAddr = peer.parse().unwrap();
+    request.extensions_mut().insert(ConnectInfo(peer));
+    app.send(request).await
+}
+
+/// The address recorded with the newest session.
+async fn last_session_ip(app: &TestApp, session: &Session) -> String {
+    let sessions = app.api(session, "GET", "/api/sessions", None).await.json();
+    sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .max_by_key(|s| s["id"].as_i64())
+        .unwrap()["ip_address"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[sqlx::test]
+async fn forwarded_headers_are_believed_from_trusted_proxies_only(pool: PgPool) {
+    let app = TestApp::with_state(pool, |state| {
+        state.proxy.trusted = vec!["10.0.0.0/8".parse().unwrap()];
+    });
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let forwarded = [
+        ("x-forwarded-for", "198.51.100.4, 203.0.113.7, 10.0.0.3"),
+        ("x-forwarded-proto", "https"),
+    ];
+
+    // Through two trusted hops; the first untrusted address from the right is
+    // the client, anything left of it could have been made up
+    let response = login_from(&app, "10.0.0.2:51000", &forwarded).await;
+    let cookie = response.headers[header::SET_COOKIE].to_str().unwrap();
+    assert!(cookie.contains("Secure"), "{cookie}");
+    assert_eq!(last_session_ip(&app, &session).await, "203.0.113.7");
+
+    // Straight from the internet, the headers are ignored
+    let response = login_from(&app, "192.0.2.50:51000", &forwarded).await;
+    let cookie = response.headers[header::SET_COOKIE].to_str().unwrap();
+    assert!(!cookie.contains("Secure"), "{cookie}");
+    assert_eq!(last_session_ip(&app, &session).await, "192.0.2.50");
+
+    // A trusted proxy without the headers is the client itself
+    login_from(&app, "10.0.0.2:51000", &[]).await;
+    assert_eq!(last_session_ip(&app, &session).await, "10.0.0.2");
+}
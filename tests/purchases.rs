@@ -0,0 +1,108 @@
+use axum::http::StatusCode;
+use household_inventory::models::Price;
+use household_inventory::testing::{Session, TestApp};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+async fn create_store(app: &TestApp, session: &Session, name: &str) -> i64 {
+    let response = app
+        .api(
+            session,
+            "POST",
+            "/api/stores",
+            Some(json!({ "name": name })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    response.json()["id"].as_i64().unwrap()
+}
+
+async fn purchase(app: &TestApp, session: &Session, item_id: i64, body: Value) {
+    let uri = format!("/api/items/{item_id}/purchase");
+    let response = app.api(session, "POST", &uri, Some(body)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+}
+
+#[sqlx::test]
+async fn purchases_are_kept_with_their_price_and_store(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let lidl = create_store(&app, &session, "Lidl").await;
+    let biedronka = create_store(&app, &session, "Biedronka").await;
+    let item = json!({ "name": "Kawa", "quantity": 0, "restock_threshold": 1,
+                       "category_id": null, "preferred_store_id": lidl });
+    let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+    let coffee = response.json()["id"].as_i64().unwrap();
+
+    purchase(
+        &app,
+        &session,
+        coffee,
+        json!({ "quantity": 2, "price": "25,98" }),
+    )
+    .await;
+    purchase(
+        &app,
+        &session,
+        coffee,
+        json!({ "quantity": 1, "price": 11.49, "store_id": biedronka }),
+    )
+    .await;
+    purchase(&app, &session, coffee, json!({ "quantity": 1 })).await;
+
+    let response = app.api(&session, "GET", "/api/purchases", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let purchases = response.json()["items"].as_array().unwrap().clone();
+    assert_eq!(purchases.len(), 3);
+    assert_eq!(purchases[0]["price"], Value::Null);
+    assert_eq!(purchases[1]["price"], "11.49");
+    assert_eq!(purchases[1]["store_name"], "Biedronka");
+    // Without a store it was bought in the item's preferred one
+    assert_eq!(purchases[2]["store_name"], "Lidl");
+    assert_eq!(purchases[2]["unit_price"], "12.99");
+
+    let uri = format!("/api/items/{coffee}/prices");
+    let prices = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(prices[0]["store_name"], "Biedronka");
+    assert_eq!(prices[0]["average"], "11.49");
+    assert_eq!(prices[1]["store_name"], "Lidl");
+    assert_eq!(prices[1]["purchases"], 1);
+
+    let page = app
+        .get(&format!("/web/purchases?item_id={coffee}"), Some(&session))
+        .await;
+    assert_eq!(page.status, StatusCode::OK);
+    let page = page.text();
+    assert!(page.contains("Porównanie cen"), "{page}");
+    assert!(page.contains("12.99"), "{page}");
+
+    let uri = format!("/api/items/{coffee}/purchase");
+    let response = app
+        .api(
+            &session,
+            "POST",
+            &uri,
+            Some(json!({ "quantity": 1, "price": "-3" })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Nobody else sees them
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let response = app.api(&other, "GET", "/api/purchases", None).await;
+    assert_eq!(response.json()["items"], json!([]));
+    let response = app
+        .api(&other, "GET", &format!("/api/items/{coffee}/prices"), None)
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn prices_are_read_with_a_comma_or_a_dot() {
+    assert_eq!("12,99".parse(), Ok(Price(1299)));
+    assert_eq!("12.5".parse(), Ok(Price(1250)));
+    assert_eq!("7".parse(), Ok(Price(700)));
+    assert!("1.999".parse::<Price>().is_err());
+    assert!("-3".parse::<Price>().is_err());
+    assert_eq!(Price(1205).to_string(), "12.05");
+}
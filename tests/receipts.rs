@@ -0,0 +1,144 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use household_inventory::receipts;
+use household_inventory::storage::FileStore;
+use household_inventory::testing::{Session, TestApp, TestResponse};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+const JPEG: &[u8] = b"\xff\xd8\xff\xe0\x00\x10JFIF\x00paragon";
+
+/// The newest history events of `item_id`.
+async fn history(app: &TestApp, session: &Session, item_id: i64) -> Vec<Value> {
+    let uri = format!("/api/activity?item_id={item_id}");
+    let response = app.api(session, "GET", &uri, None).await;
+    response.json()["items"].as_array().unwrap().clone()
+}
+
+async fn put_receipt(app: &TestApp, session: &Session, event_id: i64, body: &[u8]) -> TestResponse {
+    let request = Request::put(format!("/api/purchases/{event_id}/receipt"))
+        .header(header::COOKIE, &session.0)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .body(Body::from(body.to_vec()))
+        .unwrap();
+    app.send(request).await
+}
+
+#[sqlx::test]
+async fn purchases_keep_their_receipts(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let item =
+        json!({ "name": "Kawa", "quantity": 0, "restock_threshold": 1, "category_id": null });
+    let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+    let item_id = response.json()["id"].as_i64().unwrap();
+    let uri = format!("/api/items/{item_id}/purchase");
+    app.api(&session, "POST", &uri, Some(json!({ "quantity": 2 })))
+        .await;
+    let uri = format!("/api/items/{item_id}/use");
+    app.api(&session, "POST", &uri, None).await;
+    let events = history(&app, &session, item_id).await;
+    let used = events[0]["id"].as_i64().unwrap();
+    let purchase = events[1]["id"].as_i64().unwrap();
+
+    let response = put_receipt(&app, &session, purchase, JPEG).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["content_type"], "image/jpeg");
+    let events = history(&app, &session, item_id).await;
+    assert_eq!(events[1]["receipt"]["size_bytes"], JPEG.len());
+    assert!(events[0].get("receipt").is_none());
+
+    let uri = format!("/api/purchases/{purchase}/receipt");
+    let response = app.api(&session, "GET", &uri, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["content-type"], "image/jpeg");
+    assert_eq!(&response.body[..], JPEG);
+
+    // Only pictures and PDFs, only for purchases, only your own
+    let response = put_receipt(&app, &session, purchase, b"<html>hi</html>").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = put_receipt(&app, &session, used, JPEG).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    assert_eq!(
+        app.api(&other, "GET", &uri, None).await.status,
+        StatusCode::NOT_FOUND
+    );
+
+    // The history page uploads it as a form
+    let body = "--boundary\r\nContent-Disposition: form-data; name=\"receipt\"; filename=\"paragon.pdf\"\r\n\
+                Content-Type: application/pdf\r\n\r\n%PDF-1.4 paragon\r\n--boundary--\r\n";
+    let request = Request::post(format!("/web/purchases/{purchase}/receipt"))
+        .header(header::COOKIE, &session.0)
+        .header(
+            header::CONTENT_TYPE,
+            "multipart/form-data; boundary=boundary",
+        )
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.send(request).await;
+    assert!(response.status.is_redirection(), "{}", response.text());
+    assert_eq!(
+        response.location(),
+        Some(format!("/web/activity?item_id={item_id}").as_str())
+    );
+    let page = app.get(&uri, Some(&session)).await;
+    assert_eq!(page.headers["content-type"], "application/pdf");
+
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.api(&session, "GET", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn restoring_a_backup_deletes_the_replaced_receipts(pool: PgPool) {
+    let dir = std::env::temp_dir().join(format!("inventory-receipts-{}", std::process::id()));
+    let app = TestApp::with_state(pool, |state| state.files = FileStore::Local(dir.clone()));
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let item =
+        json!({ "name": "Kawa", "quantity": 0, "restock_threshold": 1, "category_id": null });
+    let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+    let item_id = response.json()["id"].as_i64().unwrap();
+    let uri = format!("/api/items/{item_id}/purchase");
+    app.api(&session, "POST", &uri, Some(json!({ "quantity": 1 })))
+        .await;
+    let purchase = history(&app, &session, item_id).await[0]["id"]
+        .as_i64()
+        .unwrap();
+    let response = put_receipt(&app, &session, purchase, JPEG).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    let user_dir = std::fs::read_dir(dir.join("receipts"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    assert_eq!(std::fs::read_dir(&user_dir).unwrap().count(), 1);
+
+    let backup = app.api(&session, "GET", "/api/backup", None).await.json();
+    let response = app
+        .api(&session, "POST", "/api/restore", Some(backup))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+
+    // The purchase came back, under a new id, without its receipt, and the
+    // file is gone
+    let events = app.api(&session, "GET", "/api/activity", None).await.json();
+    let purchased = &events["items"][0];
+    assert_eq!(purchased["kind"], "purchased");
+    assert!(purchased.get("receipt").is_none());
+    let files = std::fs::read_dir(&user_dir).unwrap().count();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(files, 0);
+}
+
+#[test]
+fn receipt_types_are_told_from_the_file() {
+    assert_eq!(receipts::detect(JPEG), Some("image/jpeg"));
+    assert_eq!(
+        receipts::detect(b"\x00\x00\x00\x18ftypheic\x00\x00"),
+        Some("image/heic")
+    );
+    assert_eq!(receipts::detect(b"GIF89a"), None);
+}
@@ -0,0 +1,184 @@
+use axum::http::StatusCode;
+use household_inventory::testing::{Session, TestApp};
+use serde_json::json;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+#[sqlx::test]
+async fn an_item_is_an_ingredient_only_once(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let flour = app
+        .create_item(&session, "Mąka", json!({ "quantity": 10 }))
+        .await;
+
+    let recipe = json!({ "name": "Naleśniki", "instructions": null, "ingredients": [
+        { "item_id": flour, "quantity": 2 },
+        { "item_id": flour, "quantity": 1 },
+    ] });
+    let response = app
+        .api(&session, "POST", "/api/recipes", Some(recipe))
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body = response.json();
+    assert_eq!(body["code"], "VALIDATION_FAILED", "{body}");
+    assert_eq!(
+        body["details"]["fields"]["ingredients"], "must not list an item twice",
+        "{body}"
+    );
+    let recipes = app.api(&session, "GET", "/api/recipes", None).await.json();
+    assert_eq!(recipes, json!([]));
+}
+
+/// Naleśniki from 3 Mąka and 2 Jajka, with 7 and 2 of them in stock.
+/// Returns the ids of the recipe and of both items.
+async fn pancakes(app: &TestApp, session: &Session) -> (i64, i64, i64) {
+    let flour = app
+        .create_item(session, "Mąka", json!({ "quantity": 7 }))
+        .await;
+    let eggs = app
+        .create_item(session, "Jajka", json!({ "quantity": 2 }))
+        .await;
+    let recipe = json!({ "name": "Naleśniki", "instructions": null, "ingredients": [
+        { "item_id": flour, "quantity": 3 },
+        { "item_id": eggs, "quantity": 2 },
+    ] });
+    let response = app.api(session, "POST", "/api/recipes", Some(recipe)).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    (response.json()["id"].as_i64().unwrap(), flour, eggs)
+}
+
+async fn quantity(app: &TestApp, session: &Session, item_id: i64) -> i64 {
+    let item = app
+        .api(session, "GET", &format!("/api/items/{item_id}"), None)
+        .await;
+    item.json()["quantity"].as_i64().unwrap()
+}
+
+#[sqlx::test]
+async fn cooking_takes_the_ingredients_out_of_stock(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let (recipe, flour, eggs) = pancakes(&app, &session).await;
+
+    let uri = format!("/api/recipes/{recipe}/availability");
+    let availability = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(availability["can_make"], true);
+    // Enough flour for two, but eggs for only one
+    assert_eq!(availability["max_servings"], 1);
+    assert_eq!(availability["missing"], json!([]));
+
+    let uri = format!("/api/recipes/{recipe}/cook");
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["availability"]["can_make"], false);
+    assert_eq!(quantity(&app, &session, flour).await, 4);
+    assert_eq!(quantity(&app, &session, eggs).await, 0);
+    let history = app.api(&session, "GET", "/api/history", None).await.json();
+    let used: Vec<(&str, i64)> = history["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| {
+            assert_eq!(event["kind"], "used");
+            let name = event["item_name"].as_str().unwrap();
+            (name, event["quantity_delta"].as_i64().unwrap())
+        })
+        .collect();
+    assert_eq!(used.len(), 2);
+    assert!(used.contains(&("Mąka", -3)) && used.contains(&("Jajka", -2)));
+}
+
+#[sqlx::test]
+async fn a_recipe_short_of_an_ingredient_is_not_cooked(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let (recipe, flour, eggs) = pancakes(&app, &session).await;
+    app.api(
+        &session,
+        "POST",
+        &format!("/api/items/{eggs}/use"),
+        Some(json!({ "quantity": 1 })),
+    )
+    .await;
+
+    let uri = format!("/api/recipes/{recipe}/availability");
+    let availability = app.api(&session, "GET", &uri, None).await.json();
+    assert_eq!(availability["can_make"], false);
+    assert_eq!(availability["max_servings"], 0);
+    let missing = json!([{ "item_id": eggs, "item_name": "Jajka", "required": 2, "available": 1 }]);
+    assert_eq!(availability["missing"], missing);
+
+    let uri = format!("/api/recipes/{recipe}/cook");
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.json()["missing"], missing);
+    // Nothing was taken, not even what there was enough of
+    assert_eq!(quantity(&app, &session, flour).await, 7);
+    assert_eq!(quantity(&app, &session, eggs).await, 1);
+
+    let response = app
+        .api(&session, "POST", "/api/recipes/999999/cook", None)
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn planned_meals_reserve_their_ingredients(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let (recipe, flour, _) = pancakes(&app, &session).await;
+    let today = OffsetDateTime::now_utc().date();
+    let mut meals = Vec::new();
+    for days in [-7, 2, 3] {
+        let planned_for = (today + Duration::days(days)).to_string();
+        let meal = json!({ "recipe_id": recipe, "planned_for": planned_for });
+        let response = app
+            .api(&session, "POST", "/api/meal-plan", Some(meal))
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+        meals.push(response.json()["id"].clone());
+    }
+
+    // The two upcoming meals need 6 Mąka and 4 Jajka; past ones don't count.
+    // Jajka are short by 2, plus the one to keep in stock
+    let list = app
+        .api(&session, "GET", "/api/shopping-list", None)
+        .await
+        .json();
+    let reserved: Vec<(&str, i64, i64)> = list
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            let name = entry["item_name"].as_str().unwrap();
+            (
+                name,
+                entry["reserved"].as_i64().unwrap(),
+                entry["to_buy"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(reserved, [("Jajka", 4, 3)]);
+
+    let uri = format!("/api/meal-plan/{}/cook", meals[1]);
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(
+        response.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        response.text()
+    );
+    assert_eq!(quantity(&app, &session, flour).await, 4);
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // Cooked, it no longer holds anything back
+    let list = app
+        .api(&session, "GET", "/api/shopping-list", None)
+        .await
+        .json();
+    assert_eq!(list[0]["item_name"], "Jajka");
+    assert_eq!(list[0]["reserved"], 2);
+    assert_eq!(list[0]["to_buy"], 3);
+    let uri = format!("/api/meal-plan/{}/cook", meals[2]);
+    let response = app.api(&session, "POST", &uri, None).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.json()["missing"][0]["item_name"], "Jajka");
+}
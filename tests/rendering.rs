@@ -0,0 +1,92 @@
+//! Rendering a large item list must not hold up the other requests of a
+//! server with a single worker thread.
+
+use household_inventory::assets::Templates;
+use household_inventory::testing::TestApp;
+use sqlx::PgPool;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const ITEMS: i32 = 2000;
+/// Far longer than a timer tick, however busy the machine running the tests.
+const STALL_LIMIT: Duration = Duration::from_millis(500);
+
+/// Runs `work` next to a task that wants a turn every millisecond on the same
+/// thread, and returns its output with how often that task got its turn and
+/// the longest it had to wait for one.
+async fn with_ticker<T>(work: impl Future<Output = T>) -> (T, u32, Duration) {
+    let working = Arc::new(AtomicBool::new(true));
+    let ticker = tokio::spawn({
+        let working = working.clone();
+        async move {
+            let mut ticks = 0;
+            let mut longest_stall = Duration::ZERO;
+            let mut last = Instant::now();
+            while working.load(Ordering::Relaxed) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                longest_stall = longest_stall.max(last.elapsed());
+                last = Instant::now();
+                ticks += 1;
+            }
+            (ticks, longest_stall)
+        }
+    });
+    let output = work.await;
+    working.store(false, Ordering::Relaxed);
+    let (ticks, longest_stall) = ticker.await.unwrap();
+    (output, ticks, longest_stall)
+}
+
+/// `#[tokio::test]` runs on a single-threaded runtime, like a server on one core.
+#[tokio::test]
+async fn rendering_leaves_the_thread_to_other_tasks() {
+    let dir = std::env::temp_dir().join(format!("inventory-slow-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("slow.html"),
+        "{% for i in range(end=200000) %}{{ i }}{% endfor %}",
+    )
+    .unwrap();
+    let templates = Arc::new(Templates::load().unwrap().with_overrides(&dir).unwrap());
+
+    let started = Instant::now();
+    let (page, ticks, longest_stall) =
+        with_ticker(templates.render("slow.html", tera::Context::new())).await;
+    let took = started.elapsed();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(page.unwrap().ends_with("199999"));
+    // Rendering on this thread would leave the ticker no turn until it's done
+    assert!(ticks > 0, "nothing else ran during the render");
+    assert!(
+        longest_stall < STALL_LIMIT,
+        "other tasks waited {longest_stall:?} during a render of {took:?}"
+    );
+}
+
+/// End to end timings for the item list; run with
+/// `cargo test --test rendering -- --ignored --nocapture`.
+#[sqlx::test]
+#[ignore = "a benchmark, its timings depend on the machine"]
+async fn rendering_a_large_page(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    sqlx::query(
+        "INSERT INTO items (user_id, name, quantity, restock_threshold, sort_order)
+         SELECT u.id, 'Item ' || n, n % 7, 3, n
+         FROM users u, generate_series(1, $1) AS n",
+    )
+    .bind(ITEMS)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let started = Instant::now();
+    let (page, ticks, longest_stall) = with_ticker(app.get("/web", Some(&session))).await;
+    let took = started.elapsed();
+
+    assert!(page.text().contains(&format!("Item {ITEMS}")));
+    eprintln!("{ITEMS} items rendered in {took:?}, {ticks} ticks, longest stall {longest_stall:?}");
+}
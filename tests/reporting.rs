@@ -0,0 +1,58 @@
+use axum::http::StatusCode;
+use household_inventory::testing::TestApp;
+use sentry::test::TestTransport;
+use sentry::{ClientOptions, Hub, Level};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Reports go to the returned transport instead of a Sentry server.
+fn capture_reports() -> Arc<TestTransport> {
+    let transport = TestTransport::new();
+    let options = ClientOptions {
+        dsn: Some("https://public@sentry.invalid/1".parse().unwrap()),
+        transport: Some(Arc::new(transport.clone())),
+        ..Default::default()
+    };
+    Hub::current().bind_client(Some(Arc::new(options.into())));
+    transport
+}
+
+#[sqlx::test]
+async fn database_errors_are_reported_with_the_route_and_user_id(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let transport = capture_reports();
+    sqlx::query("DROP TABLE item_events CASCADE")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let response = app.api(&session, "GET", "/api/history", None).await;
+    assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let events = transport.fetch_and_clear_events();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.level, Level::Error);
+    assert_eq!(event.tags["route"], "/api/history");
+    assert_eq!(event.tags["method"], "GET");
+    assert!(event.tags.contains_key("request_id"));
+    let user = event.user.as_ref().unwrap();
+    assert!(user.id.is_some());
+    assert_eq!(user.email, None);
+    assert_eq!(user.ip_address, None);
+}
+
+#[sqlx::test]
+async fn client_errors_are_not_reported(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let transport = capture_reports();
+
+    let response = app.api(&session, "GET", "/api/items/999", None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.get("/api/items", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    assert!(transport.fetch_and_clear_events().is_empty());
+}
@@ -0,0 +1,52 @@
+use household_inventory::db;
+use sqlx::PgPool;
+use std::time::Duration;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+#[sqlx::test]
+async fn a_job_runs_once_per_period_across_instances(pool: PgPool) {
+    let running = db::claim_job(&pool, "cleanup", HOUR).await.unwrap();
+    assert!(running.is_some());
+    // Another instance ticking while the job runs skips it
+    assert!(
+        db::claim_job(&pool, "cleanup", HOUR)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    // Other jobs are not held up
+    assert!(
+        db::claim_job(&pool, "digest", HOUR)
+            .await
+            .unwrap()
+            .is_some()
+    );
+
+    running.unwrap().commit().await.unwrap();
+    assert!(
+        db::claim_job(&pool, "cleanup", HOUR)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        db::claim_job(&pool, "cleanup", Duration::ZERO)
+            .await
+            .unwrap()
+            .is_some()
+    );
+}
+
+#[sqlx::test]
+async fn a_failed_run_is_left_for_the_next_tick(pool: PgPool) {
+    let failed = db::claim_job(&pool, "cleanup", HOUR).await.unwrap();
+    failed.unwrap().rollback().await.unwrap();
+
+    assert!(
+        db::claim_job(&pool, "cleanup", HOUR)
+            .await
+            .unwrap()
+            .is_some()
+    );
+}
@@ -0,0 +1,71 @@
+use axum::http::StatusCode;
+use household_inventory::testing::TestApp;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn sessions_list_marks_the_current_one(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let laptop = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    app.login("ala@example.com", "hunter2").await.unwrap();
+
+    let response = app.api(&laptop, "GET", "/api/sessions", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let sessions = response.json();
+    let sessions = sessions.as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions.iter().filter(|s| s["current"] == true).count(), 1);
+    assert!(sessions.iter().all(|s| s.get("token").is_none()));
+}
+
+#[sqlx::test]
+async fn revoked_sessions_are_logged_out(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let laptop = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let phone = app.login("ala@example.com", "hunter2").await.unwrap();
+    let sessions = app.api(&laptop, "GET", "/api/sessions", None).await.json();
+    let phone_id = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["current"] == false)
+        .unwrap()["id"]
+        .as_i64()
+        .unwrap();
+    let uri = format!("/api/sessions/{phone_id}");
+
+    let response = app.api(&laptop, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.api(&phone, "GET", "/api/items", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = app.api(&laptop, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn revoke_others_keeps_the_current_session(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let laptop = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let phone = app.login("ala@example.com", "hunter2").await.unwrap();
+    let tablet = app.login("ala@example.com", "hunter2").await.unwrap();
+    // Someone else's session is out of reach
+    let other = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+
+    let response = app
+        .api(&laptop, "POST", "/api/sessions/revoke-others", None)
+        .await;
+
+    assert_eq!(response.json()["revoked"], 2);
+    for (session, status) in [
+        (&laptop, StatusCode::OK),
+        (&phone, StatusCode::UNAUTHORIZED),
+        (&tablet, StatusCode::UNAUTHORIZED),
+        (&other, StatusCode::OK),
+    ] {
+        assert_eq!(
+            app.api(session, "GET", "/api/items", None).await.status,
+            status
+        );
+    }
+}
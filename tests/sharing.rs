@@ -0,0 +1,143 @@
+use axum::http::{StatusCode, header};
+use household_inventory::testing::TestApp;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn a_link_shows_its_categories_to_anyone_until_revoked(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let pantry = app.create_category(&session, "Spiżarnia", None).await;
+    let pasta = app
+        .create_category(&session, "Makarony", Some(pantry))
+        .await;
+    let cleaning = app.create_category(&session, "Chemia", None).await;
+    app.create_item(
+        &session,
+        "Mąka",
+        json!({ "quantity": 2, "restock_threshold": 3, "category_id": pantry }),
+    )
+    .await;
+    app.create_item(
+        &session,
+        "Spaghetti",
+        json!({ "quantity": 2, "restock_threshold": 3, "category_id": pasta }),
+    )
+    .await;
+    app.create_item(
+        &session,
+        "Płyn do naczyń",
+        json!({ "quantity": 2, "restock_threshold": 3, "category_id": cleaning }),
+    )
+    .await;
+
+    let link = app
+        .create_link(
+            &session,
+            json!({ "label": "Niania", "scope": "categories", "category_ids": [pantry] }),
+        )
+        .await;
+    let token = link["token"].as_str().unwrap();
+    assert_eq!(token.len(), 64);
+    assert_eq!(link["last_viewed_at"], Value::Null);
+
+    let page = app.get(&format!("/share/{token}"), None).await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert_eq!(page.headers["referrer-policy"], "no-referrer");
+    let html = page.text();
+    assert!(html.contains("Mąka"), "{html}");
+    assert!(
+        html.contains("Spaghetti"),
+        "subcategories are included: {html}"
+    );
+    assert!(!html.contains("Płyn do naczyń"), "{html}");
+    // Read-only: none of the forms of the item list
+    assert!(!html.contains("<form"), "{html}");
+
+    let links = app
+        .api(&session, "GET", "/api/share-links", None)
+        .await
+        .json();
+    assert_eq!(links[0]["label"], "Niania");
+    assert!(links[0]["last_viewed_at"].is_string());
+
+    let uri = format!("/api/share-links/{}", link["id"]);
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let page = app.get(&format!("/share/{token}"), None).await;
+    assert_eq!(page.status, StatusCode::NOT_FOUND);
+    assert!(
+        page.headers[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html")
+    );
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn a_link_can_show_the_shopping_list(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let pantry = app.create_category(&session, "Spiżarnia", None).await;
+    app.create_item(
+        &session,
+        "Mąka",
+        json!({ "quantity": 2, "restock_threshold": 3, "category_id": pantry }),
+    )
+    .await;
+
+    // Made through the settings form
+    let response = app
+        .post_form(
+            "/web/settings/share-links",
+            &[("label", "Współlokator"), ("scope", "shopping_list")],
+            Some(&session),
+        )
+        .await;
+    assert_eq!(response.location(), Some("/web/settings"));
+    let links = app
+        .api(&session, "GET", "/api/share-links", None)
+        .await
+        .json();
+    let token = links[0]["token"].as_str().unwrap();
+    let settings = app.get("/web/settings", Some(&session)).await.text();
+    assert!(settings.contains("Współlokator"), "{settings}");
+
+    let html = app.get(&format!("/share/{token}"), None).await.text();
+    assert!(html.contains("Lista zakupów"), "{html}");
+    assert!(html.contains("Mąka — <b>1</b>"), "{html}");
+}
+
+#[sqlx::test]
+async fn links_only_share_the_users_own_categories(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let ala = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let ola = app.sign_up("Ola", "ola@example.com", "hunter2").await;
+    let olas = app.create_category(&ola, "Szafka Oli", None).await;
+
+    let response = app
+        .api(
+            &ala,
+            "POST",
+            "/api/share-links",
+            Some(json!({ "label": "Niania", "scope": "categories", "category_ids": [olas] })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = app
+        .api(
+            &ala,
+            "POST",
+            "/api/share-links",
+            Some(json!({ "label": "Niania", "scope": "categories", "category_ids": [] })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    assert_eq!(
+        app.get("/share/not-a-token", None).await.status,
+        StatusCode::NOT_FOUND
+    );
+}
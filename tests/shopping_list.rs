@@ -0,0 +1,317 @@
+use axum::http::{StatusCode, header};
+use household_inventory::testing::{Session, TestApp};
+use serde_json::json;
+use sqlx::PgPool;
+
+/// Milk in a category and bread without one, both to restock.
+async fn list_with_two_entries(pool: PgPool) -> (TestApp, Session) {
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let dairy = app.create_category(&session, "Nabiał", None).await;
+    for item in [
+        json!({ "name": "Mleko", "quantity": 1, "restock_threshold": 2, "restock_to": 4, "unit": "l", "category_id": dairy }),
+        json!({ "name": "Chleb (żytni)", "quantity": 0, "restock_threshold": 1, "category_id": null }),
+    ] {
+        let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    }
+    (app, session)
+}
+
+#[sqlx::test]
+async fn the_list_exports_as_markdown_and_text_grouped_by_category(pool: PgPool) {
+    let (app, session) = list_with_two_entries(pool).await;
+
+    let response = app
+        .api(&session, "GET", "/api/shopping-list/export?format=md", None)
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.headers[header::CONTENT_TYPE],
+        "text/markdown; charset=utf-8"
+    );
+    let disposition = response.headers[header::CONTENT_DISPOSITION]
+        .to_str()
+        .unwrap();
+    assert!(disposition.starts_with("attachment; filename=\"lista-zakupow-"));
+    assert!(disposition.ends_with(".md\""), "{disposition}");
+    let markdown = response.text();
+    assert!(markdown.starts_with("# Lista zakupów ("), "{markdown}");
+    assert!(
+        markdown.contains(
+            "\n## Nabiał\n\n- [ ] Mleko — 3 l\n\n## Bez kategorii\n\n- [ ] Chleb (żytni) — 1\n"
+        ),
+        "{markdown}"
+    );
+
+    let response = app
+        .api(
+            &session,
+            "GET",
+            "/api/shopping-list/export?format=txt",
+            None,
+        )
+        .await;
+    assert_eq!(
+        response.headers[header::CONTENT_TYPE],
+        "text/plain; charset=utf-8"
+    );
+    assert!(
+        response
+            .text()
+            .contains("\nNabiał\n  [ ] Mleko: 3 l\n\nBez kategorii\n  [ ] Chleb (żytni): 1\n"),
+        "{}",
+        response.text()
+    );
+
+    let response = app
+        .api(
+            &session,
+            "GET",
+            "/api/shopping-list/export?format=doc",
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn the_pdf_export_is_a_pdf_with_the_entries(pool: PgPool) {
+    let (app, session) = list_with_two_entries(pool).await;
+
+    let response = app
+        .api(
+            &session,
+            "GET",
+            "/api/shopping-list/export?format=pdf",
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CONTENT_TYPE], "application/pdf");
+    let pdf = response.text();
+    assert!(pdf.starts_with("%PDF-1.4"));
+    assert!(pdf.trim_end().ends_with("%%EOF"));
+    // Brackets are escaped, Polish letters are in the custom encoding
+    assert!(pdf.contains("(Mleko \\227 3 l) Tj"), "{pdf}");
+    assert!(pdf.contains("(Chleb \\(\\217ytni\\) \\227 1) Tj"), "{pdf}");
+    assert!(pdf.contains("(Nabia\\207) Tj"), "{pdf}");
+}
+
+#[sqlx::test]
+async fn the_print_page_groups_the_list(pool: PgPool) {
+    let (app, session) = list_with_two_entries(pool).await;
+
+    let response = app.get("/web/shopping-list/print", Some(&session)).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let page = response.text();
+    let dairy = page.find("<h2>Nabiał</h2>").unwrap();
+    let milk = page.find("Mleko — <b>3 l</b>").unwrap();
+    let uncategorized = page.find("<h2>Bez kategorii</h2>").unwrap();
+    assert!(dairy < milk && milk < uncategorized, "{page}");
+}
+
+#[sqlx::test]
+async fn the_list_is_grouped_by_store_in_the_order_they_are_visited(pool: PgPool) {
+    let (app, session) = list_with_two_entries(pool).await;
+    let mut stores = vec![];
+    for name in ["Lidl", "Apteka"] {
+        let store = json!({ "name": name });
+        let response = app.api(&session, "POST", "/api/stores", Some(store)).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+        stores.push(response.json()["id"].as_i64().unwrap());
+    }
+    let (lidl, pharmacy) = (stores[0], stores[1]);
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/stores",
+            Some(json!({ "name": "Lidl" })),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    for (name, store) in [("Masło", lidl), ("Plastry", pharmacy)] {
+        let item = json!({ "name": name, "quantity": 0, "restock_threshold": 1, "category_id": null, "preferred_store_id": store });
+        let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+        assert_eq!(response.json()["preferred_store_id"], store);
+    }
+    let order = json!({ "ids": [pharmacy, lidl] });
+    let response = app
+        .api(&session, "POST", "/api/stores/reorder", Some(order))
+        .await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let markdown = app
+        .api(&session, "GET", "/api/shopping-list/export?format=md", None)
+        .await
+        .text();
+    let headings: Vec<&str> = markdown.lines().filter(|l| l.starts_with('#')).collect();
+    assert_eq!(
+        headings[1..],
+        [
+            "## Apteka",
+            "### Bez kategorii",
+            "## Lidl",
+            "### Bez kategorii",
+            "## Gdziekolwiek",
+            "### Nabiał",
+            "### Bez kategorii",
+        ],
+        "{markdown}"
+    );
+
+    // Without the store its items can be bought anywhere
+    let uri = format!("/api/stores/{lidl}");
+    let response = app.api(&session, "DELETE", &uri, None).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let page = app
+        .get("/web/shopping-list/print", Some(&session))
+        .await
+        .text();
+    assert!(!page.contains("Lidl"), "{page}");
+    let anywhere = page
+        .find("<h2 class=\"store-heading\">Gdziekolwiek</h2>")
+        .unwrap();
+    let butter = page.find("Masło — <b>1</b>").unwrap();
+    assert!(anywhere < butter, "{page}");
+}
+
+#[sqlx::test]
+async fn low_items_are_restocked_in_one_go(pool: PgPool) {
+    let (app, session) = list_with_two_entries(pool).await;
+    let items = app.api(&session, "GET", "/api/items", None).await.json();
+    let id_of = |name: &str| {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|item| item["name"] == name)
+            .unwrap()["id"]
+            .as_i64()
+            .unwrap()
+    };
+    let (milk, bread) = (id_of("Mleko"), id_of("Chleb (żytni)"));
+
+    let body = json!({ "item_ids": [milk] });
+    let response = app
+        .api(&session, "POST", "/api/items/restock-all", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    let restocked = response.json();
+    assert_eq!(restocked.as_array().unwrap().len(), 1);
+    assert_eq!(restocked[0]["quantity"], 4);
+    let history = app
+        .api(&session, "GET", &format!("/api/items/{milk}/history"), None)
+        .await
+        .json();
+    assert_eq!(history["items"][0]["kind"], "purchased");
+    assert_eq!(history["items"][0]["quantity_delta"], 3);
+
+    // The shopping list ticks what was bought
+    let page = app.get("/web/shopping-list", Some(&session)).await.text();
+    assert!(page.contains(&format!("name=\"item_{bread}\"")), "{page}");
+    let field = format!("item_{bread}");
+    let response = app
+        .post_form(
+            "/web/items/restock-all",
+            &[(field.as_str(), "on")],
+            Some(&session),
+        )
+        .await;
+    assert!(response.status.is_redirection());
+    let response = app.api(&session, "GET", "/api/shopping-list", None).await;
+    assert_eq!(response.json(), json!([]));
+
+    // Nothing left to restock
+    let response = app
+        .api(&session, "POST", "/api/items/restock-all", None)
+        .await;
+    assert_eq!(response.json(), json!([]));
+}
+
+#[sqlx::test]
+async fn a_shopping_trip_is_checked_out_at_once(pool: PgPool) {
+    let (app, session) = list_with_two_entries(pool).await;
+    let store = app
+        .api(
+            &session,
+            "POST",
+            "/api/stores",
+            Some(json!({ "name": "Lidl" })),
+        )
+        .await
+        .json()["id"]
+        .clone();
+    let list = app
+        .api(&session, "GET", "/api/shopping-list", None)
+        .await
+        .json();
+    let id_of = |name: &str| {
+        list.as_array()
+            .unwrap()
+            .iter()
+            .find(|entry| entry["item_name"] == name)
+            .unwrap()["item_id"]
+            .as_i64()
+            .unwrap()
+    };
+    let (milk, bread) = (id_of("Mleko"), id_of("Chleb (żytni)"));
+
+    // One unknown item and nothing is bought
+    let body = json!({ "items": [{ "item_id": milk, "quantity": 3 },
+                                 { "item_id": 999_999, "quantity": 1 }] });
+    let response = app
+        .api(&session, "POST", "/api/shopping-list/checkout", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.api(&session, "GET", "/api/purchases", None).await;
+    assert_eq!(response.json()["items"], json!([]));
+
+    let page = app
+        .get("/web/shopping-list/checkout", Some(&session))
+        .await
+        .text();
+    assert!(page.contains(&format!("name=\"price_{bread}\"")), "{page}");
+    let (item, quantity, price) = (
+        format!("item_{milk}"),
+        format!("quantity_{milk}"),
+        format!("price_{milk}"),
+    );
+    let store = store.to_string();
+    let response = app
+        .post_form(
+            "/web/shopping-list/checkout",
+            &[
+                ("store_id", store.as_str()),
+                (item.as_str(), "on"),
+                (quantity.as_str(), "3"),
+                (price.as_str(), "8,97"),
+                (format!("quantity_{bread}").as_str(), "1"),
+            ],
+            Some(&session),
+        )
+        .await;
+    assert!(response.status.is_redirection(), "{}", response.text());
+
+    // Only the ticked milk was bought, at its price, and left the list
+    let list = app
+        .api(&session, "GET", "/api/shopping-list", None)
+        .await
+        .json();
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["item_id"], bread);
+    let purchases = app
+        .api(&session, "GET", "/api/purchases", None)
+        .await
+        .json();
+    assert_eq!(purchases["items"][0]["price"], "8.97");
+    assert_eq!(purchases["items"][0]["store_name"], "Lidl");
+
+    let body = json!({ "items": [] });
+    let response = app
+        .api(&session, "POST", "/api/shopping-list/checkout", Some(body))
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
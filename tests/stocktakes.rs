@@ -0,0 +1,26 @@
+use axum::http::StatusCode;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn starting_twice_at_once_gives_the_same_stocktake(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    for name in ["Mleko", "Kawa"] {
+        let item = json!({ "name": name, "quantity": 2, "category_id": null });
+        let response = app.api(&session, "POST", "/api/items", Some(item)).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    }
+
+    let (first, second) = tokio::join!(
+        app.api(&session, "POST", "/api/stocktakes", None),
+        app.api(&session, "POST", "/api/stocktakes", None),
+    );
+    assert_eq!(first.status, StatusCode::CREATED, "{}", first.text());
+    assert_eq!(second.status, StatusCode::CREATED, "{}", second.text());
+    let (first, second) = (first.json(), second.json());
+    assert_eq!(first["id"], second["id"]);
+    assert_eq!(first["entries"].as_array().unwrap().len(), 2);
+    assert_eq!(second["entries"].as_array().unwrap().len(), 2);
+}
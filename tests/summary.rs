@@ -0,0 +1,91 @@
+use household_inventory::assets::Templates;
+use household_inventory::config::{MailSettings, MailTransport};
+use household_inventory::mail::Mailer;
+use household_inventory::models::Month;
+use household_inventory::testing::TestApp;
+use household_inventory::{auth, db, summary};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime, macros::date};
+
+#[sqlx::test]
+async fn the_summary_of_last_month_goes_out_once(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let preferences = json!({ "monthly_summary": true });
+    app.api(&session, "PUT", "/api/preferences", Some(preferences))
+        .await;
+    // Didn't ask for it
+    app.sign_up("Ola", "ola@example.com", "hunter2").await;
+
+    let kitchen = app.create_category(&session, "Kuchnia", None).await;
+    let item = json!({ "quantity": 5, "restock_threshold": 1, "category_id": kitchen });
+    let coffee = app.create_item(&session, "Kawa", item).await;
+    let uri = format!("/api/items/{coffee}/use");
+    app.api(&session, "POST", &uri, Some(json!({ "quantity": 3 })))
+        .await;
+    let uri = format!("/api/items/{coffee}/purchase");
+    let purchase = json!({ "quantity": 2, "price": "20,00" });
+    app.api(&session, "POST", &uri, Some(purchase)).await;
+    let uri = format!("/api/items/{coffee}/discard");
+    let discard = json!({ "quantity": 1, "reason": "expired" });
+    app.api(&session, "POST", &uri, Some(discard)).await;
+
+    // Everything happened this month, which is over on the 1st of the next
+    let this_month = Month::containing(OffsetDateTime::now_utc().date());
+    let next_month = this_month.0 + Duration::days(31);
+    let templates = Arc::new(Templates::load().unwrap());
+    let user = db::get_account_by_email(&pool, "ala@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+    let numbers = db::get_monthly_summary(&pool, user.id, this_month, 5)
+        .await
+        .unwrap();
+    let email = summary::render(&templates, &numbers, "Ala").await.unwrap();
+    assert_eq!(
+        email.subject,
+        format!("Podsumowanie miesiąca: {}", summary::month_name(this_month))
+    );
+    assert!(email.text.contains("Zużyte sztuki: 3"), "{}", email.text);
+    assert!(
+        email.text.contains("Wydane pieniądze: 20.00 zł"),
+        "{}",
+        email.text
+    );
+    assert!(email.text.contains("(ok. 10.00 zł)"), "{}", email.text);
+    assert!(email.text.contains("1. Kuchnia ×3"), "{}", email.text);
+    assert!(email.html.contains("<li>Kawa ×3</li>"), "{}", email.html);
+
+    let dir = std::env::temp_dir().join(format!(
+        "household-inventory-mail-{}",
+        auth::random_token().unwrap()
+    ));
+    let mailer = Mailer::from_settings(&MailSettings {
+        transport: MailTransport::Dir(dir.to_string_lossy().into_owned()),
+        from: "Inwentarz <inventory@example.com>".to_string(),
+    })
+    .unwrap();
+    summary::send_due(&pool, &templates, &mailer, this_month.0)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read_dir(&dir).map_or(0, |dir| dir.count()), 0);
+    for _ in 0..2 {
+        summary::send_due(&pool, &templates, &mailer, next_month)
+            .await
+            .unwrap();
+    }
+    let sent: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(sent.len(), 1);
+    let message = std::fs::read_to_string(sent[0].as_ref().unwrap().path()).unwrap();
+    assert!(message.contains("To: Ala <ala@example.com>"), "{message}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_summary_is_of_the_month_before() {
+    let month = summary::previous_month(date!(2025 - 01 - 01));
+    assert_eq!(month.to_string(), "2024-12");
+    assert_eq!(summary::month_name(month), "grudzień 2024");
+}
@@ -0,0 +1,146 @@
+use axum::http::StatusCode;
+use household_inventory::db;
+use household_inventory::testing::{Session, TestApp};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+use std::time::Duration;
+
+/// The version of the item a client holds after reading it.
+async fn version(app: &TestApp, session: &Session, id: i64) -> Value {
+    let item = app
+        .api(session, "GET", &format!("/api/items/{id}"), None)
+        .await;
+    item.json()["version"].clone()
+}
+
+fn ids(records: &Value) -> Vec<i64> {
+    records
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|record| {
+            record
+                .as_i64()
+                .unwrap_or_else(|| record["id"].as_i64().unwrap())
+        })
+        .collect()
+}
+
+#[sqlx::test]
+async fn sync_returns_changes_and_deletions_since_last_time(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    app.create_category(&session, "Nabiał", None).await;
+    let milk = app
+        .create_item(&session, "Mleko", json!({ "quantity": 1 }))
+        .await;
+    let bread = app
+        .create_item(&session, "Chleb", json!({ "quantity": 1 }))
+        .await;
+
+    let full = app.api(&session, "GET", "/api/sync", None).await;
+    assert_eq!(full.status, StatusCode::OK);
+    let full = full.json();
+    assert_eq!(ids(&full["items"]), ids(&json!([milk, bread])));
+    assert_eq!(full["categories"].as_array().unwrap().len(), 1);
+
+    let since = full["server_time"].as_str().unwrap().replace('+', "%2B");
+    app.api(&session, "POST", &format!("/api/items/{milk}/use"), None)
+        .await;
+    app.api(&session, "DELETE", &format!("/api/items/{bread}"), None)
+        .await;
+
+    let delta = app
+        .api(&session, "GET", &format!("/api/sync?since={since}"), None)
+        .await
+        .json();
+    assert_eq!(ids(&delta["items"]), ids(&json!([milk])));
+    assert_eq!(delta["items"][0]["quantity"], 0);
+    assert_eq!(ids(&delta["deleted"]["items"]), ids(&json!([bread])));
+    assert_eq!(delta["categories"], json!([]));
+}
+
+#[sqlx::test]
+async fn sync_batch_reports_each_change(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool).await;
+    let milk = app
+        .create_item(&session, "Mleko", json!({ "quantity": 1 }))
+        .await;
+    let bread = app
+        .create_item(&session, "Chleb", json!({ "quantity": 1 }))
+        .await;
+    let stale = version(&app, &session, milk).await;
+    let bread_version = version(&app, &session, bread).await;
+    // Someone else changes the milk before the client comes back online
+    app.api(&session, "POST", &format!("/api/items/{milk}/use"), None)
+        .await;
+
+    let response = app
+        .api(
+            &session,
+            "POST",
+            "/api/sync",
+            Some(json!({ "changes": [
+                { "op": "create", "item": { "name": "Ser", "quantity": 2, "category_id": null } },
+                { "op": "update", "id": milk, "item": { "quantity": 5, "version": stale } },
+                { "op": "delete", "id": bread, "version": bread_version },
+                { "op": "create", "item": { "name": " ", "quantity": 1, "category_id": null } },
+            ] })),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let results = response.json()["results"].clone();
+    assert_eq!(results[0]["status"], "applied");
+    assert_eq!(results[0]["item"]["name"], "Ser");
+    assert_eq!(results[1]["status"], "conflict");
+    assert_eq!(results[1]["code"], "VERSION_CONFLICT");
+    assert_eq!(results[1]["current"]["quantity"], 0);
+    assert_eq!(results[2]["status"], "applied");
+    assert_eq!(results[3]["status"], "rejected");
+    assert_eq!(results[3]["code"], "VALIDATION_FAILED");
+
+    let gone = app
+        .api(&session, "GET", &format!("/api/items/{bread}"), None)
+        .await;
+    assert_eq!(gone.status, StatusCode::NOT_FOUND);
+}
+
+/// The payload of the next notification, if one comes within a second.
+async fn next_change(listener: &mut PgListener) -> Option<String> {
+    let notification = tokio::time::timeout(Duration::from_secs(1), listener.recv()).await;
+    notification.ok().map(|n| n.unwrap().payload().to_string())
+}
+
+#[sqlx::test]
+async fn listeners_hear_of_each_committed_change(pool: PgPool) {
+    let (app, session) = TestApp::signed_in(pool.clone()).await;
+    let user_id: i32 = sqlx::query_scalar("SELECT id FROM users")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let mut listener = db::listen_for_item_changes(&pool).await.unwrap();
+    let user_id = Some(user_id.to_string());
+
+    let id = app.create_item(&session, "Mleko", json!({})).await;
+    assert_eq!(next_change(&mut listener).await, user_id);
+    let uri = format!("/api/items/{id}/purchase");
+    let purchase = json!({ "quantity": 2 });
+    app.api(&session, "POST", &uri, Some(purchase)).await;
+    assert_eq!(next_change(&mut listener).await, user_id);
+    app.create_category(&session, "Nabiał", None).await;
+    assert_eq!(next_change(&mut listener).await, user_id);
+    app.api(&session, "DELETE", &format!("/api/items/{id}"), None)
+        .await;
+    assert_eq!(next_change(&mut listener).await, user_id);
+
+    // Nothing for reads, nor for writes that are rolled back
+    app.api(&session, "GET", "/api/items", None).await;
+    let mut tx = pool.begin().await.unwrap();
+    sqlx::query("UPDATE categories SET name = 'Mleczne'")
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    tx.rollback().await.unwrap();
+    assert_eq!(next_change(&mut listener).await, None);
+}
@@ -0,0 +1,83 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use household_inventory::config::TlsFiles;
+use household_inventory::testing::TestApp;
+use household_inventory::tls;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use tower::ServiceExt;
+
+#[sqlx::test]
+async fn the_server_speaks_https_and_marks_the_cookie_secure(pool: PgPool) {
+    let files = TlsFiles {
+        cert: "tests/fixtures/localhost.crt".to_string(),
+        key: "tests/fixtures/localhost.key".to_string(),
+    };
+    let tls_config = tls::load(&files).await.unwrap();
+    let app = TestApp::with_state(pool, |state| state.proxy.https = true);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let service = app
+        .router
+        .clone()
+        .into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(axum_server::from_tcp_rustls(listener, tls_config).serve(service));
+
+    // The fixture is self-signed; only the handshake is under test here
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("https://localhost:{port}/health/live"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let login = app
+        .post_form(
+            "/web/login",
+            &[("email", "ala@example.com"), ("password", "hunter2")],
+            None,
+        )
+        .await;
+    let cookie = login.headers[header::SET_COOKIE].to_str().unwrap();
+    assert!(cookie.contains("Secure"), "{cookie}");
+}
+
+#[tokio::test]
+async fn plain_http_is_redirected_to_the_same_url_over_https() {
+    let redirect = tls::redirect_to_https(8443);
+
+    let response = redirect
+        .clone()
+        .oneshot(
+            Request::get("/web/items?sort=name")
+                .header(header::HOST, "pantry.example.com:8080")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "https://pantry.example.com:8443/web/items?sort=name"
+    );
+
+    let response = tls::redirect_to_https(443)
+        .oneshot(
+            Request::get("/")
+                .header(header::HOST, "pantry.example.com")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "https://pantry.example.com/"
+    );
+}
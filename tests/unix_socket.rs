@@ -0,0 +1,43 @@
+#![cfg(unix)]
+
+use household_inventory::testing::TestApp;
+use household_inventory::unix_socket;
+use sqlx::PgPool;
+use std::future::IntoFuture;
+use std::os::unix::fs::PermissionsExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+#[sqlx::test]
+async fn the_app_is_served_on_a_unix_socket_with_the_configured_mode(pool: PgPool) {
+    let dir = std::env::temp_dir().join(format!("inventory-socket-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("inventory.sock");
+    let path = path.to_str().unwrap();
+
+    // A socket left behind by an earlier run is replaced
+    let stale = unix_socket::bind(path, None).unwrap();
+    drop(stale);
+    let listener = unix_socket::bind(path, Some(0o660)).unwrap();
+    let mode = std::fs::metadata(path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o660);
+
+    let app = TestApp::new(pool);
+    tokio::spawn(axum::serve(listener, app.router.clone()).into_future());
+
+    let mut stream = UnixStream::connect(path).await.unwrap();
+    stream
+        .write_all(b"GET /health/live HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with(r#"{"status":"ok"}"#), "{response}");
+
+    // Anything else at the path is left alone
+    std::fs::remove_file(path).unwrap();
+    std::fs::write(path, "not a socket").unwrap();
+    assert!(unix_socket::bind(path, None).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
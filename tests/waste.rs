@@ -0,0 +1,89 @@
+use axum::http::StatusCode;
+use household_inventory::models::Month;
+use household_inventory::testing::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn thrown_away_stock_is_reported_with_its_value(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let session = app.sign_up("Ala", "ala@example.com", "hunter2").await;
+    let milk = app
+        .create_item(
+            &session,
+            "Mleko",
+            json!({ "quantity": 0, "restock_threshold": 1 }),
+        )
+        .await;
+    let bread = app
+        .create_item(
+            &session,
+            "Chleb",
+            json!({ "quantity": 3, "restock_threshold": 1 }),
+        )
+        .await;
+    let uri = format!("/api/items/{milk}/purchase");
+    let purchase = json!({ "quantity": 4, "price": "15,96" });
+    app.api(&session, "POST", &uri, Some(purchase)).await;
+
+    let uri = format!("/api/items/{milk}/discard");
+    let discard = json!({ "quantity": 2, "reason": "expired" });
+    let response = app.api(&session, "POST", &uri, Some(discard)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.json()["quantity"], 2);
+    let adjust = json!({ "delta": -1, "reason": "lost" });
+    let uri = format!("/api/items/{milk}/adjust");
+    app.api(&session, "POST", &uri, Some(adjust)).await;
+    // Bread was never bought with a price
+    let response = app
+        .post_form(
+            &format!("/web/items/{bread}/discard"),
+            &[("quantity", "1"), ("reason", "spoiled")],
+            Some(&session),
+        )
+        .await;
+    assert!(response.status.is_redirection());
+
+    let report = app.api(&session, "GET", "/api/waste", None).await.json();
+    assert_eq!(report["quantity"], 4);
+    assert_eq!(report["value"], "11.97");
+    assert_eq!(report["unpriced_quantity"], 1);
+    assert_eq!(report["items"][0]["item_name"], "Mleko");
+    assert_eq!(report["items"][0]["value"], "11.97");
+    assert_eq!(report["items"][1]["value"], json!(null));
+    assert_eq!(report["reasons"][0]["reason"], "expired");
+    assert_eq!(report["reasons"][0]["value"], "7.98");
+    let months = report["months"].as_array().unwrap();
+    assert_eq!(months.len(), 12);
+    assert_eq!(months[11]["month"], report["month"]);
+    assert_eq!(months[11]["quantity"], 4);
+
+    let response = app
+        .api(&session, "GET", "/api/waste?month=2020-01", None)
+        .await;
+    assert_eq!(response.json()["quantity"], 0);
+    let response = app
+        .api(&session, "GET", "/api/waste?month=2020-13", None)
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let page = app.get("/web/waste", Some(&session)).await;
+    assert_eq!(page.status, StatusCode::OK);
+    let page = page.text();
+    assert!(page.contains("11.97 zł"), "{page}");
+    assert!(page.contains("Przeterminowane"), "{page}");
+
+    let uri = format!("/api/items/{milk}/discard");
+    let response = app
+        .api(&session, "POST", &uri, Some(json!({ "reason": "used" })))
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[test]
+fn months_are_written_year_first() {
+    let month: Month = "2025-08".parse().unwrap();
+    assert_eq!(month.to_string(), "2025-08");
+    assert!("2025-8-01".parse::<Month>().is_err());
+    assert!("sierpień".parse::<Month>().is_err());
+}